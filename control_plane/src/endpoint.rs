@@ -56,7 +56,7 @@
 use reqwest::header::CONTENT_TYPE;
 use safekeeper_api::membership::SafekeeperGeneration;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use tracing::{debug, info};
 use url::Host;
 use utils::id::{NodeId, TenantId, TimelineId};
 
@@ -908,6 +908,47 @@ pub async fn reconfigure(
             spec.safekeeper_connstrings = safekeeper_connstrings;
         }
 
+        self.configure(spec).await
+    }
+
+    /// Switches a running endpoint to new safekeepers, in the given membership generation.
+    ///
+    /// Unlike [`Self::reconfigure`], this is persisted in the spec, so that later
+    /// reconfigurations and restarts keep using the new safekeepers.
+    pub async fn reconfigure_safekeepers(
+        &self,
+        safekeepers: Vec<NodeId>,
+        generation: SafekeeperGeneration,
+    ) -> Result<()> {
+        let spec_path = self.endpoint_path().join("spec.json");
+        let mut spec: ComputeSpec = {
+            let file = std::fs::File::open(&spec_path)?;
+            serde_json::from_reader(file)?
+        };
+
+        // Notifications may be retried concurrently: ignore stale ones.
+        if spec
+            .safekeepers_generation
+            .is_some_and(|current| current > generation.into_inner())
+        {
+            info!(
+                "ignoring safekeepers of generation {generation}, already at {:?}",
+                spec.safekeepers_generation
+            );
+            return Ok(());
+        }
+
+        spec.safekeeper_connstrings = self.build_safekeepers_connstrs(safekeepers)?;
+        spec.safekeepers_generation = Some(generation.into_inner());
+        std::fs::write(spec_path, serde_json::to_string_pretty(&spec)?)?;
+
+        let postgresql_conf = self.read_postgresql_conf()?;
+        spec.cluster.postgresql_conf = Some(postgresql_conf);
+
+        self.configure(spec).await
+    }
+
+    async fn configure(&self, spec: ComputeSpec) -> Result<()> {
         let client = reqwest::Client::builder()
             .timeout(Duration::from_secs(120))
             .build()
@@ -186,6 +186,14 @@ pub struct NeonStorageControllerConf {
     pub use_https_safekeeper_api: bool,
 
     pub use_local_compute_notifications: bool,
+
+    #[serde(with = "humantime_serde")]
+    pub safekeeper_rebalance_interval: Option<Duration>,
+
+    pub safekeeper_migration_concurrency: Option<usize>,
+
+    #[serde(with = "humantime_serde")]
+    pub safekeeper_wal_verification_interval: Option<Duration>,
 }
 
 impl NeonStorageControllerConf {
@@ -216,6 +224,9 @@ fn default() -> Self {
             timelines_onto_safekeepers: false,
             use_https_safekeeper_api: false,
             use_local_compute_notifications: true,
+            safekeeper_rebalance_interval: None,
+            safekeeper_migration_concurrency: None,
+            safekeeper_wal_verification_interval: None,
         }
     }
 }
@@ -486,6 +486,11 @@ pub fn parse_config(mut settings: HashMap<&str, &str>) -> anyhow::Result<models:
                 .map(|x| x.parse::<u64>())
                 .transpose()
                 .context("Failed to parse 'min_resident_size_override' as integer")?,
+            max_resident_size: settings
+                .remove("max_resident_size")
+                .map(|x| x.parse::<u64>())
+                .transpose()
+                .context("Failed to parse 'max_resident_size' as integer")?,
             evictions_low_residence_duration_metric_threshold: settings
                 .remove("evictions_low_residence_duration_metric_threshold")
                 .map(humantime::parse_duration)
@@ -506,6 +511,11 @@ pub fn parse_config(mut settings: HashMap<&str, &str>) -> anyhow::Result<models:
                 .map(serde_json::from_str)
                 .transpose()
                 .context("parse `timeline_get_throttle` from json")?,
+            wal_ingest_throttle: settings
+                .remove("wal_ingest_throttle")
+                .map(serde_json::from_str)
+                .transpose()
+                .context("parse `wal_ingest_throttle` from json")?,
             lsn_lease_length: settings.remove("lsn_lease_length")
                 .map(humantime::parse_duration)
                 .transpose()
@@ -550,6 +560,26 @@ pub fn parse_config(mut settings: HashMap<&str, &str>) -> anyhow::Result<models:
                 .map(serde_json::from_str)
                 .transpose()
                 .context("Falied to parse 'sampling_ratio'")?,
+            delta_compression: settings
+                .remove("delta_compression")
+                .map(|x| x.parse::<models::ImageCompressionAlgorithm>())
+                .transpose()
+                .context("Failed to parse 'delta_compression'")?,
+            eviction_priority: settings
+                .remove("eviction_priority")
+                .map(|x| x.parse::<u8>())
+                .transpose()
+                .context("Failed to parse 'eviction_priority' as integer")?,
+            scrub_period: settings
+                .remove("scrub_period")
+                .map(humantime::parse_duration)
+                .transpose()
+                .context("Failed to parse 'scrub_period' as duration")?,
+            scrub_bytes_per_second: settings
+                .remove("scrub_bytes_per_second")
+                .map(|x| x.parse::<NonZeroU64>())
+                .transpose()
+                .context("Failed to parse 'scrub_bytes_per_second' as non zero integer")?,
         };
         if !settings.is_empty() {
             bail!("Unrecognized tenant settings: {settings:?}")
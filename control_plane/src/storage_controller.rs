@@ -621,6 +621,24 @@ pub async fn start(&self, start_args: NeonStorageControllerStartArgs) -> anyhow:
             args.push("--timelines-onto-safekeepers".to_string());
         }
 
+        if let Some(interval) = self.config.safekeeper_rebalance_interval {
+            args.push(format!(
+                "--safekeeper-rebalance-interval={}",
+                humantime::Duration::from(interval)
+            ))
+        }
+
+        if let Some(concurrency) = self.config.safekeeper_migration_concurrency.as_ref() {
+            args.push(format!("--safekeeper-migration-concurrency={concurrency}"))
+        }
+
+        if let Some(interval) = self.config.safekeeper_wal_verification_interval {
+            args.push(format!(
+                "--safekeeper-wal-verification-interval={}",
+                humantime::Duration::from(interval)
+            ))
+        }
+
         println!("Starting storage controller");
 
         background_process::start_process(
@@ -8,15 +8,17 @@
 use pageserver_api::controller_api::{
     AvailabilityZone, MigrationConfig, NodeAvailabilityWrapper, NodeConfigureRequest,
     NodeDescribeResponse, NodeRegisterRequest, NodeSchedulingPolicy, NodeShardResponse,
-    PlacementPolicy, SafekeeperDescribeResponse, SafekeeperSchedulingPolicyRequest,
-    ShardSchedulingPolicy, ShardsPreferredAzsRequest, ShardsPreferredAzsResponse,
-    SkSchedulingPolicy, TenantCreateRequest, TenantDescribeResponse, TenantPolicyRequest,
-    TenantShardMigrateRequest, TenantShardMigrateResponse,
+    PlacementPolicy, SafekeeperDescribeResponse, SafekeeperMigrationDescribe,
+    SafekeeperOperationDescribe, SafekeeperSchedulingPolicyRequest,
+    SafekeeperWalVerificationDescribe, ShardSchedulingPolicy, ShardsPreferredAzsRequest,
+    ShardsPreferredAzsResponse, SkSchedulingPolicy, TenantCreateRequest, TenantDescribeResponse,
+    TenantPolicyRequest, TenantShardMigrateRequest, TenantShardMigrateResponse,
+    TimelineSafekeeperMigrateRequest,
 };
 use pageserver_api::models::{
     EvictionPolicy, EvictionPolicyLayerAccessThreshold, ShardParameters, TenantConfig,
-    TenantConfigPatchRequest, TenantConfigRequest, TenantShardSplitRequest,
-    TenantShardSplitResponse,
+    TenantConfigPatchRequest, TenantConfigRequest, TenantShardMergeRequest,
+    TenantShardMergeResponse, TenantShardSplitRequest, TenantShardSplitResponse,
 };
 use pageserver_api::shard::{ShardStripeSize, TenantShardId};
 use pageserver_client::mgmt_api::{self};
@@ -107,6 +109,13 @@ enum Command {
         #[arg(long)]
         stripe_size: Option<u32>,
     },
+    /// Merge pairs of shards of an existing tenant, halving its shard count.
+    TenantShardMerge {
+        #[arg(long)]
+        tenant_id: TenantId,
+        #[arg(long)]
+        shard_count: u8,
+    },
     /// Migrate the attached location for a tenant shard to a specific pageserver.
     TenantShardMigrate {
         #[arg(long)]
@@ -239,6 +248,53 @@ enum Command {
         #[arg(long)]
         scheduling_policy: SkSchedulingPolicyArg,
     },
+    /// Move a timeline to the specified set of safekeepers
+    TimelineSafekeeperMigrate {
+        #[arg(long)]
+        tenant_id: TenantId,
+        #[arg(long)]
+        timeline_id: TimelineId,
+        /// Safekeepers to host the timeline after the migration
+        #[arg(long, value_delimiter = ',')]
+        new_sk_set: Vec<NodeId>,
+    },
+    /// List timeline migrations between safekeepers which are in progress
+    SafekeeperMigrations {},
+    /// List timelines whose safekeepers were found to hold different WAL
+    SafekeeperWalDivergences {},
+    /// Start draining the specified safekeeper: move all its timelines to other safekeepers.
+    /// The drain is complete when the scheduling policy turns to pause.
+    SafekeeperStartDrain {
+        #[arg(long)]
+        node_id: NodeId,
+    },
+    /// Cancel draining the specified safekeeper and wait for `timeout`
+    /// for the operation to be canceled. May be retried.
+    SafekeeperCancelDrain {
+        #[arg(long)]
+        node_id: NodeId,
+        #[arg(long)]
+        timeout: humantime::Duration,
+    },
+    /// Start filling the specified safekeeper with timelines from the most loaded ones.
+    /// The fill is complete when the scheduling policy returns to active.
+    SafekeeperStartFill {
+        #[arg(long)]
+        node_id: NodeId,
+    },
+    /// Cancel filling the specified safekeeper and wait for `timeout`
+    /// for the operation to be canceled. May be retried.
+    SafekeeperCancelFill {
+        #[arg(long)]
+        node_id: NodeId,
+        #[arg(long)]
+        timeout: humantime::Duration,
+    },
+    /// Show the progress of the drain or fill of the specified safekeeper
+    SafekeeperOperation {
+        #[arg(long)]
+        node_id: NodeId,
+    },
     /// Downloads any missing heatmap layers for all shard for a given timeline
     DownloadHeatmapLayers {
         /// Tenant ID or tenant shard ID. When an unsharded tenant ID is specified,
@@ -381,6 +437,34 @@ async fn wait_for_scheduling_policy<F>(
     Ok(waiter.await??)
 }
 
+async fn wait_for_safekeeper_scheduling_policy<F>(
+    client: Client,
+    node_id: NodeId,
+    timeout: Duration,
+    f: F,
+) -> anyhow::Result<SkSchedulingPolicy>
+where
+    F: Fn(SkSchedulingPolicy) -> bool,
+{
+    let waiter = tokio::time::timeout(timeout, async move {
+        loop {
+            let sk = client
+                .dispatch::<(), SafekeeperDescribeResponse>(
+                    Method::GET,
+                    format!("control/v1/safekeeper/{node_id}"),
+                    None,
+                )
+                .await?;
+
+            if f(sk.scheduling_policy) {
+                return Ok::<SkSchedulingPolicy, mgmt_api::Error>(sk.scheduling_policy);
+            }
+        }
+    });
+
+    Ok(waiter.await??)
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
@@ -638,6 +722,34 @@ async fn main() -> anyhow::Result<()> {
                     .join(",")
             );
         }
+        Command::TenantShardMerge {
+            tenant_id,
+            shard_count,
+        } => {
+            let req = TenantShardMergeRequest {
+                new_shard_count: shard_count,
+                generation: None,
+            };
+
+            let response = storcon_client
+                .dispatch::<TenantShardMergeRequest, TenantShardMergeResponse>(
+                    Method::PUT,
+                    format!("control/v1/tenant/{tenant_id}/shard_merge"),
+                    Some(req),
+                )
+                .await?;
+            println!(
+                "Merged tenant {} into {} shards: {}",
+                tenant_id,
+                shard_count,
+                response
+                    .new_shards
+                    .iter()
+                    .map(|s| format!("{:?}", s))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            );
+        }
         Command::TenantShardMigrate {
             tenant_shard_id,
             node,
@@ -1222,6 +1334,175 @@ struct MigrationMove {
                 String::from(scheduling_policy)
             );
         }
+        Command::TimelineSafekeeperMigrate {
+            tenant_id,
+            timeline_id,
+            new_sk_set,
+        } => {
+            storcon_client
+                .dispatch::<TimelineSafekeeperMigrateRequest, ()>(
+                    Method::PUT,
+                    format!(
+                        "control/v1/tenant/{tenant_id}/timeline/{timeline_id}/safekeeper_migrate"
+                    ),
+                    Some(TimelineSafekeeperMigrateRequest {
+                        new_sk_set: new_sk_set.clone(),
+                    }),
+                )
+                .await?;
+            println!("Migrated timeline {tenant_id}/{timeline_id} to safekeepers {new_sk_set:?}");
+        }
+        Command::SafekeeperMigrations {} => {
+            let resp = storcon_client
+                .dispatch::<(), Vec<SafekeeperMigrationDescribe>>(
+                    Method::GET,
+                    "control/v1/safekeeper_migrations".to_string(),
+                    None,
+                )
+                .await?;
+
+            let mut table = comfy_table::Table::new();
+            table.set_header(["Tenant", "Timeline", "From", "To", "Started"]);
+            let format_set = |set: &[NodeId]| {
+                set.iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            };
+            for migration in resp {
+                table.add_row([
+                    migration.tenant_id.to_string(),
+                    migration.timeline_id.to_string(),
+                    format_set(&migration.from),
+                    format_set(&migration.to),
+                    migration.started_at.to_rfc3339(),
+                ]);
+            }
+            println!("{table}");
+        }
+        Command::SafekeeperWalDivergences {} => {
+            let resp = storcon_client
+                .dispatch::<(), Vec<SafekeeperWalVerificationDescribe>>(
+                    Method::GET,
+                    "control/v1/safekeeper_wal_divergences".to_string(),
+                    None,
+                )
+                .await?;
+
+            let mut table = comfy_table::Table::new();
+            table.set_header([
+                "Tenant",
+                "Timeline",
+                "From LSN",
+                "Until LSN",
+                "Digests",
+                "Detected",
+            ]);
+            for verification in resp {
+                let Some(divergence) = verification.divergence else {
+                    continue;
+                };
+                let digests = divergence
+                    .digests
+                    .iter()
+                    .map(|d| format!("{}: {}", d.node_id, d.sha256))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                table.add_row([
+                    verification.tenant_id.to_string(),
+                    verification.timeline_id.to_string(),
+                    divergence.from_lsn.to_string(),
+                    divergence.until_lsn.to_string(),
+                    digests,
+                    divergence.detected_at.to_rfc3339(),
+                ]);
+            }
+            println!("{table}");
+        }
+        Command::SafekeeperStartDrain { node_id } => {
+            storcon_client
+                .dispatch::<(), ()>(
+                    Method::PUT,
+                    format!("control/v1/safekeeper/{node_id}/drain"),
+                    None,
+                )
+                .await?;
+            println!("Drain started for safekeeper {node_id}");
+        }
+        Command::SafekeeperCancelDrain { node_id, timeout } => {
+            storcon_client
+                .dispatch::<(), ()>(
+                    Method::DELETE,
+                    format!("control/v1/safekeeper/{node_id}/drain"),
+                    None,
+                )
+                .await?;
+
+            println!("Waiting for safekeeper {node_id} to quiesce on scheduling policy ...");
+
+            let final_policy =
+                wait_for_safekeeper_scheduling_policy(storcon_client, node_id, *timeout, |sched| {
+                    matches!(
+                        sched,
+                        SkSchedulingPolicy::Active | SkSchedulingPolicy::Pause
+                    )
+                })
+                .await?;
+
+            println!(
+                "Drain was cancelled for safekeeper {node_id}. Scheduling policy is now {}",
+                String::from(final_policy)
+            );
+        }
+        Command::SafekeeperStartFill { node_id } => {
+            storcon_client
+                .dispatch::<(), ()>(
+                    Method::PUT,
+                    format!("control/v1/safekeeper/{node_id}/fill"),
+                    None,
+                )
+                .await?;
+            println!("Fill started for safekeeper {node_id}");
+        }
+        Command::SafekeeperCancelFill { node_id, timeout } => {
+            storcon_client
+                .dispatch::<(), ()>(
+                    Method::DELETE,
+                    format!("control/v1/safekeeper/{node_id}/fill"),
+                    None,
+                )
+                .await?;
+
+            println!("Waiting for safekeeper {node_id} to quiesce on scheduling policy ...");
+
+            let final_policy =
+                wait_for_safekeeper_scheduling_policy(storcon_client, node_id, *timeout, |sched| {
+                    matches!(sched, SkSchedulingPolicy::Active)
+                })
+                .await?;
+
+            println!(
+                "Fill was cancelled for safekeeper {node_id}. Scheduling policy is now {}",
+                String::from(final_policy)
+            );
+        }
+        Command::SafekeeperOperation { node_id } => {
+            let op = storcon_client
+                .dispatch::<(), SafekeeperOperationDescribe>(
+                    Method::GET,
+                    format!("control/v1/safekeeper/{node_id}/operation"),
+                    None,
+                )
+                .await?;
+            println!(
+                "{:?} of safekeeper {node_id} started at {}: {} timelines planned, {} migrated, {} failed",
+                op.kind,
+                op.started_at.to_rfc3339(),
+                op.planned,
+                op.migrated,
+                op.failed
+            );
+        }
         Command::DownloadHeatmapLayers {
             tenant_shard_id,
             timeline_id,
@@ -114,9 +114,12 @@ pub struct ConfigToml {
     pub wait_lsn_timeout: Duration,
     #[serde(with = "humantime_serde")]
     pub wal_redo_timeout: Duration,
+    pub wal_redo_max_processes_per_tenant: NonZeroUsize,
+    pub wal_redo_native: WalRedoNativeMode,
     pub superuser: String,
     pub locale: String,
     pub page_cache_size: usize,
+    pub materialized_page_cache_max_bytes: usize,
     pub max_file_descriptors: usize,
     pub pg_distrib_dir: Option<Utf8PathBuf>,
     #[serde_as(as = "serde_with::DisplayFromStr")]
@@ -141,8 +144,10 @@ pub struct ConfigToml {
     #[serde(with = "humantime_serde")]
     pub synthetic_size_calculation_interval: Duration,
     pub disk_usage_based_eviction: Option<DiskUsageEvictionTaskConfig>,
+    pub basebackup_cache: Option<BasebackupCacheConfig>,
     pub test_remote_failures: u64,
     pub ondemand_download_behavior_treat_error_as_warn: bool,
+    pub ondemand_download_partial_reads: bool,
     #[serde(with = "humantime_serde")]
     pub background_task_maximum_delay: Duration,
     pub control_plane_api: Option<reqwest::Url>,
@@ -163,6 +168,7 @@ pub struct ConfigToml {
     pub ingest_batch_size: u64,
     pub max_vectored_read_bytes: MaxVectoredReadBytes,
     pub image_compression: ImageCompressionAlgorithm,
+    pub layer_blob_checksums: bool,
     pub timeline_offloading: bool,
     pub ephemeral_bytes_per_memory_kb: usize,
     pub l0_flush: Option<crate::models::L0FlushConfig>,
@@ -195,6 +201,37 @@ pub struct DiskUsageEvictionTaskConfig {
     pub eviction_order: EvictionOrder,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BasebackupCacheConfig {
+    /// Budget for cached basebackups on local disk.
+    pub max_total_size_bytes: u64,
+    /// The variants prepared for every cached LSN.
+    pub compressions: Vec<BasebackupCompression>,
+    /// Also upload prepared basebackups to remote storage, so that they
+    /// survive restarts and migrations of the tenant.
+    pub remote_storage: bool,
+}
+
+impl Default for BasebackupCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_total_size_bytes: 1024 * 1024 * 1024,
+            compressions: vec![BasebackupCompression::Gzip],
+            remote_storage: false,
+        }
+    }
+}
+
+/// How a basebackup tarball is compressed on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BasebackupCompression {
+    Uncompressed,
+    Gzip,
+    Zstd,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "mode", rename_all = "kebab-case")]
 pub enum PageServicePipeliningConfig {
@@ -316,9 +353,22 @@ fn from(e: MockedError) -> Self {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type", content = "args")]
 pub enum EvictionOrder {
+    AbsoluteAccessed,
     RelativeAccessed {
         highest_layer_count_loses_first: bool,
     },
+    /// Like `RelativeAccessed`, but weighted by the estimated cost of downloading the layer again,
+    /// so that larger layers are evicted before many small ones.
+    CostAware {
+        /// Estimated fixed cost of a single layer download.
+        #[serde(with = "humantime_serde")]
+        download_latency: Duration,
+        /// Estimated remote storage download throughput.
+        download_bytes_per_second: NonZeroU64,
+    },
+    /// Like `RelativeAccessed`, but layers of tenants with a lower `eviction_priority` in their
+    /// tenant config are evicted first.
+    TenantPriority,
 }
 
 impl Default for EvictionOrder {
@@ -329,6 +379,22 @@ fn default() -> Self {
     }
 }
 
+/// Whether WAL redo applies the Postgres WAL records it knows how to replay in the pageserver
+/// itself, instead of sending them to the walredo process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WalRedoNativeMode {
+    /// All Postgres WAL records are applied by the walredo process.
+    #[default]
+    Disabled,
+    /// Supported records are applied in the pageserver, the rest by the walredo process. Batches
+    /// that fail to apply in the pageserver are retried with the walredo process.
+    Enabled,
+    /// Supported records are applied both ways, and the results compared. Mismatches are
+    /// logged, and the walredo process' result is used. For testing.
+    Verify,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 #[serde(transparent)]
 pub struct MaxVectoredReadBytes(pub NonZeroUsize);
@@ -405,6 +471,11 @@ pub struct TenantConfigToml {
     pub max_lsn_wal_lag: NonZeroU64,
     pub eviction_policy: crate::models::EvictionPolicy,
     pub min_resident_size_override: Option<u64>,
+    /// Cap on the total size of the tenant shard's resident layer files. Downloads and newly
+    /// written layers wait for the tenant's least recently used layers to be evicted to stay
+    /// below it, and are refused if that doesn't make room in time. Flushes are never refused,
+    /// they wait for as long as it takes.
+    pub max_resident_size: Option<u64>,
     // See the corresponding metric's help string.
     #[serde(with = "humantime_serde")]
     pub evictions_low_residence_duration_metric_threshold: Duration,
@@ -420,6 +491,9 @@ pub struct TenantConfigToml {
 
     pub timeline_get_throttle: crate::models::ThrottleConfig,
 
+    /// Limits the rate at which WAL is ingested for the tenant, in bytes.
+    pub wal_ingest_throttle: crate::models::WalIngestThrottleConfig,
+
     // How much WAL must be ingested before checking again whether a new image layer is required.
     // Expresed in multiples of checkpoint distance.
     pub image_layer_creation_check_threshold: u8,
@@ -460,6 +534,20 @@ pub struct TenantConfigToml {
     /// Tenant level performance sampling ratio override. Controls the ratio of get page requests
     /// that will get perf sampling for the tenant.
     pub sampling_ratio: Option<Ratio>,
+    /// Compression applied to WAL records and page images written into delta layers.
+    /// Disabled by default: delta layers can always be read regardless of this setting.
+    /// With `zstd-dict`, a dictionary is trained on the first values of each delta layer and
+    /// stored in the layer file; if training fails, the layer falls back to plain `zstd`.
+    pub delta_compression: ImageCompressionAlgorithm,
+    /// Priority of the tenant's layers under disk pressure, used by the `TenantPriority`
+    /// eviction order: layers of tenants with a lower priority are evicted first.
+    pub eviction_priority: u8,
+    /// Period of the scrub loop, which re-reads all resident layer files of the tenant shard
+    /// to detect corruption. Zero disables scrubbing.
+    #[serde(with = "humantime_serde")]
+    pub scrub_period: Duration,
+    /// Rate at which the scrub loop reads layer files.
+    pub scrub_bytes_per_second: NonZeroU64,
 }
 
 pub mod defaults {
@@ -469,6 +557,7 @@ pub mod defaults {
 
     pub const DEFAULT_WAIT_LSN_TIMEOUT: &str = "300 s";
     pub const DEFAULT_WAL_REDO_TIMEOUT: &str = "60 s";
+    pub const DEFAULT_WAL_REDO_MAX_PROCESSES_PER_TENANT: usize = 1;
 
     pub const DEFAULT_SUPERUSER: &str = "cloud_admin";
     pub const DEFAULT_LOCALE: &str = if cfg!(target_os = "macos") {
@@ -536,9 +625,15 @@ fn default() -> Self {
                 .expect("cannot parse default wait lsn timeout")),
             wal_redo_timeout: (humantime::parse_duration(DEFAULT_WAL_REDO_TIMEOUT)
                 .expect("cannot parse default wal redo timeout")),
+            wal_redo_max_processes_per_tenant: NonZeroUsize::new(
+                DEFAULT_WAL_REDO_MAX_PROCESSES_PER_TENANT,
+            )
+            .expect("Invalid default constant"),
+            wal_redo_native: WalRedoNativeMode::default(),
             superuser: (DEFAULT_SUPERUSER.to_string()),
             locale: DEFAULT_LOCALE.to_string(),
             page_cache_size: (DEFAULT_PAGE_CACHE_SIZE),
+            materialized_page_cache_max_bytes: 0,
             max_file_descriptors: (DEFAULT_MAX_FILE_DESCRIPTORS),
             pg_distrib_dir: None, // Utf8PathBuf::from("./pg_install"), // TODO: formely, this was std::env::current_dir()
             http_auth_type: (AuthType::Trust),
@@ -573,11 +668,14 @@ fn default() -> Self {
             metric_collection_bucket: (None),
 
             disk_usage_based_eviction: (None),
+            basebackup_cache: None,
 
             test_remote_failures: (0),
 
             ondemand_download_behavior_treat_error_as_warn: (false),
 
+            ondemand_download_partial_reads: (false),
+
             background_task_maximum_delay: (humantime::parse_duration(
                 DEFAULT_BACKGROUND_TASK_MAXIMUM_DELAY,
             )
@@ -602,6 +700,7 @@ fn default() -> Self {
                 NonZeroUsize::new(DEFAULT_MAX_VECTORED_READ_BYTES).unwrap(),
             )),
             image_compression: (DEFAULT_IMAGE_COMPRESSION),
+            layer_blob_checksums: false,
             timeline_offloading: true,
             ephemeral_bytes_per_memory_kb: (DEFAULT_EPHEMERAL_BYTES_PER_MEMORY_KB),
             l0_flush: None,
@@ -636,6 +735,7 @@ fn default() -> Self {
 }
 
 pub mod tenant_conf_defaults {
+    use crate::models::ImageCompressionAlgorithm;
 
     // FIXME: This current value is very low. I would imagine something like 1 GB or 10 GB
     // would be more appropriate. But a low value forces the code to be exercised more,
@@ -692,6 +792,11 @@ pub mod tenant_conf_defaults {
     pub const DEFAULT_GC_COMPACTION_ENABLED: bool = false;
     pub const DEFAULT_GC_COMPACTION_INITIAL_THRESHOLD_KB: u64 = 5 * 1024 * 1024; // 5GB
     pub const DEFAULT_GC_COMPACTION_RATIO_PERCENT: u64 = 100;
+    pub const DEFAULT_DELTA_COMPRESSION: ImageCompressionAlgorithm =
+        ImageCompressionAlgorithm::Disabled;
+    pub const DEFAULT_EVICTION_PRIORITY: u8 = 0;
+    pub const DEFAULT_SCRUB_PERIOD: &str = "0s";
+    pub const DEFAULT_SCRUB_BYTES_PER_SECOND: u64 = 16 * 1024 * 1024;
 }
 
 impl Default for TenantConfigToml {
@@ -729,6 +834,7 @@ fn default() -> Self {
                 .expect("cannot parse default max walreceiver Lsn wal lag"),
             eviction_policy: crate::models::EvictionPolicy::NoEviction,
             min_resident_size_override: None,
+            max_resident_size: None,
             evictions_low_residence_duration_metric_threshold: humantime::parse_duration(
                 DEFAULT_EVICTIONS_LOW_RESIDENCE_DURATION_METRIC_THRESHOLD,
             )
@@ -736,6 +842,7 @@ fn default() -> Self {
             heatmap_period: Duration::ZERO,
             lazy_slru_download: false,
             timeline_get_throttle: crate::models::ThrottleConfig::disabled(),
+            wal_ingest_throttle: crate::models::WalIngestThrottleConfig::disabled(),
             image_layer_creation_check_threshold: DEFAULT_IMAGE_LAYER_CREATION_CHECK_THRESHOLD,
             image_creation_preempt_threshold: DEFAULT_IMAGE_CREATION_PREEMPT_THRESHOLD,
             lsn_lease_length: LsnLease::DEFAULT_LENGTH,
@@ -747,6 +854,12 @@ fn default() -> Self {
             gc_compaction_initial_threshold_kb: DEFAULT_GC_COMPACTION_INITIAL_THRESHOLD_KB,
             gc_compaction_ratio_percent: DEFAULT_GC_COMPACTION_RATIO_PERCENT,
             sampling_ratio: None,
+            delta_compression: DEFAULT_DELTA_COMPRESSION,
+            eviction_priority: DEFAULT_EVICTION_PRIORITY,
+            scrub_period: humantime::parse_duration(DEFAULT_SCRUB_PERIOD)
+                .expect("cannot parse default scrub period"),
+            scrub_bytes_per_second: NonZeroU64::new(DEFAULT_SCRUB_BYTES_PER_SECOND)
+                .expect("default scrub bytes per second is non-zero"),
         }
     }
 }
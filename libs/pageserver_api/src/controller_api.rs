@@ -7,7 +7,8 @@
 /// API (`/control/v1` prefix).  Implemented by the server
 /// in [`storage_controller::http`]
 use serde::{Deserialize, Serialize};
-use utils::id::{NodeId, TenantId};
+use utils::id::{NodeId, TenantId, TimelineId};
+use utils::lsn::Lsn;
 
 use crate::models::{PageserverUtilization, ShardParameters, TenantConfig};
 use crate::shard::{ShardStripeSize, TenantShardId};
@@ -384,6 +385,8 @@ pub enum SkSchedulingPolicy {
     Active,
     Pause,
     Decomissioned,
+    Draining,
+    Filling,
 }
 
 impl FromStr for SkSchedulingPolicy {
@@ -394,9 +397,11 @@ fn from_str(s: &str) -> Result<Self, Self::Err> {
             "active" => Self::Active,
             "pause" => Self::Pause,
             "decomissioned" => Self::Decomissioned,
+            "draining" => Self::Draining,
+            "filling" => Self::Filling,
             _ => {
                 return Err(anyhow::anyhow!(
-                    "Unknown scheduling policy '{s}', try active,pause,decomissioned"
+                    "Unknown scheduling policy '{s}', try active,pause,decomissioned,draining,filling"
                 ));
             }
         })
@@ -410,6 +415,8 @@ fn from(value: SkSchedulingPolicy) -> String {
             Active => "active",
             Pause => "pause",
             Decomissioned => "decomissioned",
+            Draining => "draining",
+            Filling => "filling",
         }
         .to_string()
     }
@@ -499,6 +506,71 @@ pub struct SafekeeperSchedulingPolicyRequest {
     pub scheduling_policy: SkSchedulingPolicy,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TimelineSafekeeperMigrateRequest {
+    /// Safekeepers which should host the timeline after the migration.
+    pub new_sk_set: Vec<NodeId>,
+}
+
+/// Ongoing migration of a timeline between sets of safekeepers
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SafekeeperMigrationDescribe {
+    pub tenant_id: TenantId,
+    pub timeline_id: TimelineId,
+    pub from: Vec<NodeId>,
+    pub to: Vec<NodeId>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SafekeeperOperationKind {
+    Drain,
+    Fill,
+}
+
+/// Progress of an ongoing safekeeper drain or fill, counted in timelines
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SafekeeperOperationDescribe {
+    pub node_id: NodeId,
+    pub kind: SafekeeperOperationKind,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    /// Timelines the operation is going to move so far
+    pub planned: usize,
+    pub migrated: usize,
+    pub failed: usize,
+}
+
+/// Digest of a WAL range reported by one safekeeper
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SafekeeperWalDigest {
+    pub node_id: NodeId,
+    pub sha256: String,
+}
+
+/// Members of a timeline's safekeeper set hold different WAL in the committed range
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SafekeeperWalDivergence {
+    pub from_lsn: Lsn,
+    pub until_lsn: Lsn,
+    pub digests: Vec<SafekeeperWalDigest>,
+    pub detected_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Outcome of comparing the WAL of a timeline across its safekeepers
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SafekeeperWalVerificationDescribe {
+    pub tenant_id: TenantId,
+    pub timeline_id: TimelineId,
+    pub sk_set: Vec<NodeId>,
+    /// End of the last WAL range found identical on all the members
+    pub verified_lsn: Lsn,
+    pub verified_at: chrono::DateTime<chrono::Utc>,
+    /// Set once a divergence is found, verification of the timeline stops then
+    /// until its safekeeper set changes.
+    pub divergence: Option<SafekeeperWalDivergence>,
+}
+
 #[cfg(test)]
 mod test {
     use serde_json;
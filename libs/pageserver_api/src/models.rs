@@ -26,7 +26,7 @@
 use crate::config::Ratio;
 use crate::key::{CompactKey, Key};
 use crate::reltag::RelTag;
-use crate::shard::{ShardCount, ShardStripeSize, TenantShardId};
+use crate::shard::{ShardCount, ShardNumber, ShardStripeSize, TenantShardId};
 
 /// The state of a tenant in this pageserver.
 ///
@@ -336,6 +336,15 @@ pub struct SafekeeperInfo {
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(untagged)]
 pub enum TimelineCreateRequestMode {
+    /// Branch off a timeline of another tenant. Its shard with the same shard index as the new
+    /// timeline's must be attached to the same pageserver.
+    // NB: must come before Branch, which would otherwise match and ignore `ancestor_tenant_id`.
+    CrossTenantBranch {
+        ancestor_tenant_id: TenantId,
+        ancestor_timeline_id: TimelineId,
+        #[serde(default)]
+        ancestor_start_lsn: Option<Lsn>,
+    },
     Branch {
         ancestor_timeline_id: TimelineId,
         #[serde(default)]
@@ -417,6 +426,24 @@ pub struct TenantShardSplitResponse {
     pub new_shards: Vec<TenantShardId>,
 }
 
+/// Merge pairs of shards, halving the tenant's shard count.  Shards N and N+new_shard_count
+/// merge into shard N of the new shard count.
+#[derive(Serialize, Deserialize)]
+pub struct TenantShardMergeRequest {
+    pub new_shard_count: u8,
+
+    // The generation to attach the merged shard in.  This is only set by the storage controller
+    // when it calls into the pageserver: it must be greater than the generations of both parents.
+    // If unset, the pageserver uses the successor of the parents' highest generation.
+    #[serde(default)]
+    pub generation: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TenantShardMergeResponse {
+    pub new_shards: Vec<TenantShardId>,
+}
+
 /// Parameters that apply to all shards in a tenant.  Used during tenant creation.
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
@@ -542,6 +569,8 @@ pub struct TenantConfigPatch {
     #[serde(skip_serializing_if = "FieldPatch::is_noop")]
     pub min_resident_size_override: FieldPatch<u64>,
     #[serde(skip_serializing_if = "FieldPatch::is_noop")]
+    pub max_resident_size: FieldPatch<u64>,
+    #[serde(skip_serializing_if = "FieldPatch::is_noop")]
     pub evictions_low_residence_duration_metric_threshold: FieldPatch<String>,
     #[serde(skip_serializing_if = "FieldPatch::is_noop")]
     pub heatmap_period: FieldPatch<String>,
@@ -550,6 +579,8 @@ pub struct TenantConfigPatch {
     #[serde(skip_serializing_if = "FieldPatch::is_noop")]
     pub timeline_get_throttle: FieldPatch<ThrottleConfig>,
     #[serde(skip_serializing_if = "FieldPatch::is_noop")]
+    pub wal_ingest_throttle: FieldPatch<WalIngestThrottleConfig>,
+    #[serde(skip_serializing_if = "FieldPatch::is_noop")]
     pub image_layer_creation_check_threshold: FieldPatch<u8>,
     #[serde(skip_serializing_if = "FieldPatch::is_noop")]
     pub image_creation_preempt_threshold: FieldPatch<usize>,
@@ -571,6 +602,14 @@ pub struct TenantConfigPatch {
     pub gc_compaction_ratio_percent: FieldPatch<u64>,
     #[serde(skip_serializing_if = "FieldPatch::is_noop")]
     pub sampling_ratio: FieldPatch<Option<Ratio>>,
+    #[serde(skip_serializing_if = "FieldPatch::is_noop")]
+    pub delta_compression: FieldPatch<ImageCompressionAlgorithm>,
+    #[serde(skip_serializing_if = "FieldPatch::is_noop")]
+    pub eviction_priority: FieldPatch<u8>,
+    #[serde(skip_serializing_if = "FieldPatch::is_noop")]
+    pub scrub_period: FieldPatch<String>,
+    #[serde(skip_serializing_if = "FieldPatch::is_noop")]
+    pub scrub_bytes_per_second: FieldPatch<NonZeroU64>,
 }
 
 /// Like [`crate::config::TenantConfigToml`], but preserves the information
@@ -646,6 +685,9 @@ pub struct TenantConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub min_resident_size_override: Option<u64>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_resident_size: Option<u64>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(with = "humantime_serde")]
     pub evictions_low_residence_duration_metric_threshold: Option<Duration>,
@@ -660,6 +702,9 @@ pub struct TenantConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timeline_get_throttle: Option<ThrottleConfig>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wal_ingest_throttle: Option<WalIngestThrottleConfig>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image_layer_creation_check_threshold: Option<u8>,
 
@@ -694,6 +739,19 @@ pub struct TenantConfig {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sampling_ratio: Option<Option<Ratio>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta_compression: Option<ImageCompressionAlgorithm>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eviction_priority: Option<u8>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(with = "humantime_serde")]
+    pub scrub_period: Option<Duration>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scrub_bytes_per_second: Option<NonZeroU64>,
 }
 
 impl TenantConfig {
@@ -722,10 +780,12 @@ pub fn apply_patch(
             mut max_lsn_wal_lag,
             mut eviction_policy,
             mut min_resident_size_override,
+            mut max_resident_size,
             mut evictions_low_residence_duration_metric_threshold,
             mut heatmap_period,
             mut lazy_slru_download,
             mut timeline_get_throttle,
+            mut wal_ingest_throttle,
             mut image_layer_creation_check_threshold,
             mut image_creation_preempt_threshold,
             mut lsn_lease_length,
@@ -737,6 +797,10 @@ pub fn apply_patch(
             mut gc_compaction_initial_threshold_kb,
             mut gc_compaction_ratio_percent,
             mut sampling_ratio,
+            mut delta_compression,
+            mut eviction_priority,
+            mut scrub_period,
+            mut scrub_bytes_per_second,
         } = self;
 
         patch.checkpoint_distance.apply(&mut checkpoint_distance);
@@ -791,6 +855,9 @@ pub fn apply_patch(
         patch
             .min_resident_size_override
             .apply(&mut min_resident_size_override);
+        patch
+            .max_resident_size
+            .apply(&mut max_resident_size);
         patch
             .evictions_low_residence_duration_metric_threshold
             .map(|v| humantime::parse_duration(&v))?
@@ -803,6 +870,7 @@ pub fn apply_patch(
         patch
             .timeline_get_throttle
             .apply(&mut timeline_get_throttle);
+        patch.wal_ingest_throttle.apply(&mut wal_ingest_throttle);
         patch
             .image_layer_creation_check_threshold
             .apply(&mut image_layer_creation_check_threshold);
@@ -832,6 +900,15 @@ pub fn apply_patch(
             .gc_compaction_ratio_percent
             .apply(&mut gc_compaction_ratio_percent);
         patch.sampling_ratio.apply(&mut sampling_ratio);
+        patch.delta_compression.apply(&mut delta_compression);
+        patch.eviction_priority.apply(&mut eviction_priority);
+        patch
+            .scrub_period
+            .map(|v| humantime::parse_duration(&v))?
+            .apply(&mut scrub_period);
+        patch
+            .scrub_bytes_per_second
+            .apply(&mut scrub_bytes_per_second);
 
         Ok(Self {
             checkpoint_distance,
@@ -854,10 +931,12 @@ pub fn apply_patch(
             max_lsn_wal_lag,
             eviction_policy,
             min_resident_size_override,
+            max_resident_size,
             evictions_low_residence_duration_metric_threshold,
             heatmap_period,
             lazy_slru_download,
             timeline_get_throttle,
+            wal_ingest_throttle,
             image_layer_creation_check_threshold,
             image_creation_preempt_threshold,
             lsn_lease_length,
@@ -869,6 +948,10 @@ pub fn apply_patch(
             gc_compaction_initial_threshold_kb,
             gc_compaction_ratio_percent,
             sampling_ratio,
+            delta_compression,
+            eviction_priority,
+            scrub_period,
+            scrub_bytes_per_second,
         })
     }
 
@@ -929,6 +1012,9 @@ pub fn merge(
             min_resident_size_override: self
                 .min_resident_size_override
                 .or(global_conf.min_resident_size_override),
+            max_resident_size: self
+                .max_resident_size
+                .or(global_conf.max_resident_size),
             evictions_low_residence_duration_metric_threshold: self
                 .evictions_low_residence_duration_metric_threshold
                 .unwrap_or(global_conf.evictions_low_residence_duration_metric_threshold),
@@ -940,6 +1026,10 @@ pub fn merge(
                 .timeline_get_throttle
                 .clone()
                 .unwrap_or(global_conf.timeline_get_throttle),
+            wal_ingest_throttle: self
+                .wal_ingest_throttle
+                .clone()
+                .unwrap_or(global_conf.wal_ingest_throttle),
             image_layer_creation_check_threshold: self
                 .image_layer_creation_check_threshold
                 .unwrap_or(global_conf.image_layer_creation_check_threshold),
@@ -971,6 +1061,16 @@ pub fn merge(
                 .gc_compaction_ratio_percent
                 .unwrap_or(global_conf.gc_compaction_ratio_percent),
             sampling_ratio: self.sampling_ratio.unwrap_or(global_conf.sampling_ratio),
+            delta_compression: self
+                .delta_compression
+                .unwrap_or(global_conf.delta_compression),
+            eviction_priority: self
+                .eviction_priority
+                .unwrap_or(global_conf.eviction_priority),
+            scrub_period: self.scrub_period.unwrap_or(global_conf.scrub_period),
+            scrub_bytes_per_second: self
+                .scrub_bytes_per_second
+                .unwrap_or(global_conf.scrub_bytes_per_second),
         }
     }
 }
@@ -1057,6 +1157,16 @@ pub enum ImageCompressionAlgorithm {
     Zstd {
         level: Option<i8>,
     },
+    /// Zstandard compression with a dictionary trained on the contents of each image layer
+    /// file, and stored inside of it. Levels behave like for [`Self::Zstd`]. Dictionaries
+    /// are only used for image layers: delta layers, and image layers for which no
+    /// dictionary could be trained, fall back to plain zstd.
+    ZstdDict {
+        level: Option<i8>,
+    },
+    /// LZ4 block compression. Compresses worse than zstd, but decompression is much cheaper,
+    /// which matters for latency-sensitive reads.
+    Lz4,
 }
 
 impl FromStr for ImageCompressionAlgorithm {
@@ -1068,7 +1178,8 @@ fn from_str(s: &str) -> Result<Self, Self::Err> {
             .ok_or_else(|| anyhow::anyhow!("empty string"))?;
         match first {
             "disabled" => Ok(ImageCompressionAlgorithm::Disabled),
-            "zstd" => {
+            "lz4" => Ok(ImageCompressionAlgorithm::Lz4),
+            "zstd" | "zstd-dict" => {
                 let level = if let Some(v) = components.next() {
                     let v: i8 = v.parse()?;
                     Some(v)
@@ -1076,7 +1187,11 @@ fn from_str(s: &str) -> Result<Self, Self::Err> {
                     None
                 };
 
-                Ok(ImageCompressionAlgorithm::Zstd { level })
+                if first == "zstd" {
+                    Ok(ImageCompressionAlgorithm::Zstd { level })
+                } else {
+                    Ok(ImageCompressionAlgorithm::ZstdDict { level })
+                }
             }
             _ => anyhow::bail!("invalid specifier '{first}'"),
         }
@@ -1087,6 +1202,7 @@ impl Display for ImageCompressionAlgorithm {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ImageCompressionAlgorithm::Disabled => write!(f, "disabled"),
+            ImageCompressionAlgorithm::Lz4 => write!(f, "lz4"),
             ImageCompressionAlgorithm::Zstd { level } => {
                 if let Some(level) = level {
                     write!(f, "zstd({})", level)
@@ -1094,6 +1210,13 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                     write!(f, "zstd")
                 }
             }
+            ImageCompressionAlgorithm::ZstdDict { level } => {
+                if let Some(level) = level {
+                    write!(f, "zstd-dict({})", level)
+                } else {
+                    write!(f, "zstd-dict")
+                }
+            }
         }
     }
 }
@@ -1175,6 +1298,34 @@ pub fn steady_rps(&self) -> f64 {
     }
 }
 
+/// Limits the rate at which a tenant's WAL is ingested, in bytes of WAL.
+///
+/// Unlike [`ThrottleConfig`], which predates it, the throttle is switched on and off by an
+/// explicit `enabled` field.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct WalIngestThrottleConfig {
+    pub enabled: bool,
+    pub initial: u32,
+    #[serde(with = "humantime_serde")]
+    pub refill_interval: Duration,
+    pub refill_amount: NonZeroU32,
+    pub max: u32,
+}
+
+impl WalIngestThrottleConfig {
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            // other values don't matter when disabled.
+            initial: 0,
+            refill_interval: Duration::from_millis(1),
+            refill_amount: NonZeroU32::new(1).unwrap(),
+            max: 1,
+        }
+    }
+}
+
 #[cfg(test)]
 mod throttle_config_tests {
     use super::*;
@@ -1197,6 +1348,29 @@ fn test_enabled_backwards_compat() {
         let config: ThrottleConfig = serde_json::from_value(input).unwrap();
         assert!(config.enabled.is_enabled());
     }
+    #[test]
+    fn test_wal_ingest_throttle_enabled_is_explicit() {
+        let input = serde_json::json!({
+            "enabled": true,
+            "initial": 0,
+            "refill_interval": "1s",
+            "refill_amount": 16777216,
+            "max": 16777216,
+        });
+        let config: WalIngestThrottleConfig = serde_json::from_value(input).unwrap();
+        assert!(config.enabled);
+        assert!(!WalIngestThrottleConfig::disabled().enabled);
+
+        // `task_kinds` has no meaning for the WAL ingest throttle
+        let input = serde_json::json!({
+            "task_kinds": ["WalReceiverConnectionHandler"],
+            "initial": 0,
+            "refill_interval": "1s",
+            "refill_amount": 16777216,
+            "max": 16777216,
+        });
+        assert!(serde_json::from_value::<WalIngestThrottleConfig>(input).is_err());
+    }
 }
 
 /// A flattened analog of a `pagesever::tenant::LocationMode`, which
@@ -1374,6 +1548,85 @@ pub struct TimelineArchivalConfigRequest {
     pub state: TimelineArchivalState,
 }
 
+/// Retention settings of a single timeline, overriding those of its tenant.
+///
+/// Beyond the PITR window, `tiers` keep restore points at a coarser granularity, e.g. hourly
+/// restore points for a week and daily ones for a month. Restore points are aligned to multiples
+/// of their tier's interval since the UNIX epoch, so that those of different tiers coincide.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimelineRetentionPolicy {
+    /// Overrides the tenant's `gc_horizon`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gc_horizon: Option<u64>,
+
+    /// Overrides the tenant's `pitr_interval`: every LSN within it can be read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(with = "humantime_serde")]
+    pub pitr_interval: Option<Duration>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tiers: Vec<RetentionTier>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionTier {
+    /// Time between two restore points of this tier, in whole seconds.
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+
+    /// How long the restore points of this tier are kept.
+    #[serde(with = "humantime_serde")]
+    pub keep_for: Duration,
+}
+
+impl TimelineRetentionPolicy {
+    /// Upper bound on the restore points a policy may keep, as each one is resolved to an LSN
+    /// separately and holds back GC.
+    pub const MAX_RESTORE_POINTS: u64 = 1000;
+
+    pub fn validate(&self) -> Result<(), String> {
+        let mut restore_points = 0;
+        for tier in &self.tiers {
+            if tier.interval.is_zero() || tier.interval.subsec_nanos() != 0 {
+                return Err(format!(
+                    "tier interval must be a non-zero number of seconds, got {:?}",
+                    tier.interval
+                ));
+            }
+            if tier.keep_for < tier.interval {
+                return Err(format!(
+                    "tier keep_for {:?} is shorter than its interval {:?}",
+                    tier.keep_for, tier.interval
+                ));
+            }
+            restore_points += tier.keep_for.as_secs() / tier.interval.as_secs();
+        }
+        if restore_points > Self::MAX_RESTORE_POINTS {
+            return Err(format!(
+                "tiers keep {restore_points} restore points, at most {} are allowed",
+                Self::MAX_RESTORE_POINTS
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A point in time kept by a [`RetentionTier`], and the LSN it was resolved to.
+#[serde_as]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestorePoint {
+    #[serde_as(as = "SystemTimeAsRfc3339Millis")]
+    pub timestamp: SystemTime,
+    pub lsn: Lsn,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct TimelineRetentionPolicyRequest {
+    /// `None` reverts the timeline to the retention settings of its tenant.
+    pub retention_policy: Option<TimelineRetentionPolicy>,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct TimelinePatchIndexPartRequest {
     pub rel_size_migration: Option<RelSizeMigration>,
@@ -1499,6 +1752,13 @@ pub struct TimelineInfo {
 
     /// Whether the timeline is invisible in synthetic size calculations.
     pub is_invisible: Option<bool>,
+
+    /// The retention settings of the timeline, if they override those of its tenant.
+    pub retention_policy: Option<TimelineRetentionPolicy>,
+
+    /// Restore points kept beyond the PITR window by the retention policy, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub restore_points: Vec<RestorePoint>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1601,6 +1861,67 @@ pub struct DownloadRemoteLayersTaskSpawnRequest {
     pub max_concurrent_downloads: NonZeroUsize,
 }
 
+/// Reset a timeline in place to an earlier LSN, discarding everything it ingested after it.
+///
+/// The reset index is published in `generation`, which must be newer than the one the tenant
+/// is attached in: the tenant comes back attached in it. With `dry_run`, the request is only
+/// validated and nothing is changed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimelineResetToLsnRequest {
+    pub lsn: Lsn,
+    #[serde(default)]
+    pub generation: Option<u32>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Export a timeline as a PGDATA directory, in the layout that
+/// [`TimelineCreateRequestModeImportPgdata`] imports from.
+///
+/// A sharded tenant is exported by shard zero, which reads the relation blocks of the other
+/// shards from the page service of their pageservers. The storage controller fills in where
+/// they are.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimelineExportPgdataRequest {
+    /// Defaults to the last record LSN of the timeline.
+    #[serde(default)]
+    pub lsn: Option<Lsn>,
+    pub location: ImportPgdataLocation,
+    /// The other shards of a sharded tenant.
+    #[serde(default)]
+    pub shards: Vec<TimelineExportPgdataShard>,
+    /// JWT for the page service of the other shards' pageservers, if they require one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_token: Option<String>,
+}
+
+/// Where to read the relation blocks of a shard from, for [`TimelineExportPgdataRequest`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TimelineExportPgdataShard {
+    pub shard_number: ShardNumber,
+    pub listen_pg_addr: String,
+    pub listen_pg_port: u16,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TimelineExportPgdataTaskInfo {
+    pub task_id: String,
+    pub state: TimelineExportPgdataTaskState,
+    pub lsn: Lsn,
+    pub exported_file_count: u64,
+    pub exported_bytes: u64,
+    /// Set if `state` is `Failed`.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum TimelineExportPgdataTaskState {
+    Running,
+    Completed,
+    Failed,
+    ShutDown,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct IngestAuxFilesRequest {
     pub aux_files: HashMap<String, String>,
@@ -1640,7 +1961,11 @@ pub struct WalRedoManagerProcessStatus {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalRedoManagerStatus {
     pub last_redo_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// The first process of [`Self::processes`], kept for compatibility.
     pub process: Option<WalRedoManagerProcessStatus>,
+    /// All launched walredo processes of the tenant shard's pool.
+    #[serde(default)]
+    pub processes: Vec<WalRedoManagerProcessStatus>,
 }
 
 /// The progress of a secondary tenant.
@@ -1731,6 +2056,36 @@ pub struct TopTenantShardItem {
     pub max_logical_size_per_shard: u64,
 }
 
+/// Resident size of a tenant shard compared to its `max_resident_size`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct TenantDiskQuotaStatus {
+    /// `None` if the tenant shard has no cap.
+    pub max_resident_size: Option<u64>,
+
+    /// Total size of layers on local disk for all timelines in this shard.
+    pub resident_size: u64,
+}
+
+/// Outcome of the last complete pass of the scrub loop over the resident layer files of a
+/// tenant shard.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TenantScrubStatus {
+    /// `None` if no pass has completed yet.
+    pub last_scrub_finished_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    pub layers_checked: usize,
+    pub bytes_checked: u64,
+
+    pub corrupt_layers: Vec<CorruptLayer>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CorruptLayer {
+    pub timeline_id: TimelineId,
+    pub layer_file_name: String,
+    pub error: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct TopTenantShardsResponse {
     pub shards: Vec<TopTenantShardItem>,
@@ -1813,6 +2168,8 @@ pub enum PagestreamFeMessage {
     GetPage(PagestreamGetPageRequest),
     DbSize(PagestreamDbSizeRequest),
     GetSlruSegment(PagestreamGetSlruSegmentRequest),
+    Prefetch(PagestreamPrefetchRequest),
+    GetPages(PagestreamGetPagesRequest),
     #[cfg(feature = "testing")]
     Test(PagestreamTestRequest),
 }
@@ -1826,6 +2183,7 @@ pub enum PagestreamBeMessage {
     Error(PagestreamErrorResponse),
     DbSize(PagestreamDbSizeResponse),
     GetSlruSegment(PagestreamGetSlruSegmentResponse),
+    GetPages(PagestreamGetPagesResponse),
     #[cfg(feature = "testing")]
     Test(PagestreamTestResponse),
 }
@@ -1838,6 +2196,8 @@ enum PagestreamFeMessageTag {
     GetPage = 2,
     DbSize = 3,
     GetSlruSegment = 4,
+    Prefetch = 5,
+    GetPages = 6,
     /* future tags above this line */
     /// For testing purposes, not available in production.
     #[cfg(feature = "testing")]
@@ -1853,6 +2213,7 @@ enum PagestreamBeMessageTag {
     Error = 103,
     DbSize = 104,
     GetSlruSegment = 105,
+    GetPages = 106,
     /* future tags above this line */
     /// For testing purposes, not available in production.
     #[cfg(feature = "testing")]
@@ -1868,6 +2229,8 @@ fn try_from(value: u8) -> Result<Self, u8> {
             2 => Ok(PagestreamFeMessageTag::GetPage),
             3 => Ok(PagestreamFeMessageTag::DbSize),
             4 => Ok(PagestreamFeMessageTag::GetSlruSegment),
+            5 => Ok(PagestreamFeMessageTag::Prefetch),
+            6 => Ok(PagestreamFeMessageTag::GetPages),
             #[cfg(feature = "testing")]
             99 => Ok(PagestreamFeMessageTag::Test),
             _ => Err(value),
@@ -1885,6 +2248,7 @@ fn try_from(value: u8) -> Result<Self, u8> {
             103 => Ok(PagestreamBeMessageTag::Error),
             104 => Ok(PagestreamBeMessageTag::DbSize),
             105 => Ok(PagestreamBeMessageTag::GetSlruSegment),
+            106 => Ok(PagestreamBeMessageTag::GetPages),
             #[cfg(feature = "testing")]
             199 => Ok(PagestreamBeMessageTag::Test),
             _ => Err(value),
@@ -1921,10 +2285,16 @@ fn try_from(value: u8) -> Result<Self, u8> {
 // We copy fields from request to response to make checking more reliable: request ID is formed from process ID
 // and local counter, so in principle there can be duplicated requests IDs if process PID is reused.
 //
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+// V4 version of protocol adds the Prefetch request, a hint that the compute is about to read a range
+// of blocks of a relation. The pageserver does not respond to it. It also adds the GetPages request,
+// which asks for up to PAGESTREAM_MAX_GETPAGES_PAGES pages, possibly of different relations, at a
+// single LSN, and is answered with all pages in one response. Other messages are the same as in V3.
+//
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum PagestreamProtocolVersion {
     V2,
     V3,
+    V4,
 }
 
 pub type RequestId = u64;
@@ -1968,6 +2338,24 @@ pub struct PagestreamGetSlruSegmentRequest {
     pub segno: u32,
 }
 
+/// Maximum number of pages in a single [`PagestreamGetPagesRequest`].
+pub const PAGESTREAM_MAX_GETPAGES_PAGES: usize = 32;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PagestreamGetPagesRequest {
+    pub hdr: PagestreamRequest,
+    pub pages: Vec<(RelTag, u32)>,
+}
+
+/// Hint to read `nblocks` blocks of `rel`, starting at `blkno`, into the pageserver's caches.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PagestreamPrefetchRequest {
+    pub hdr: PagestreamRequest,
+    pub rel: RelTag,
+    pub blkno: u32,
+    pub nblocks: u32,
+}
+
 #[derive(Debug)]
 pub struct PagestreamExistsResponse {
     pub req: PagestreamExistsRequest,
@@ -1986,6 +2374,13 @@ pub struct PagestreamGetPageResponse {
     pub page: Bytes,
 }
 
+/// Pages in the same order as in the request.
+#[derive(Debug)]
+pub struct PagestreamGetPagesResponse {
+    pub req: PagestreamGetPagesRequest,
+    pub pages: Vec<Bytes>,
+}
+
 #[derive(Debug)]
 pub struct PagestreamGetSlruSegmentResponse {
     pub req: PagestreamGetSlruSegmentRequest,
@@ -2031,7 +2426,7 @@ pub struct TenantHistorySize {
 
 impl PagestreamFeMessage {
     /// Serialize a compute -> pageserver message. This is currently only used in testing
-    /// tools. Always uses protocol version 3.
+    /// tools. Always uses protocol version 3, or 4 for messages that only exist in version 4.
     pub fn serialize(&self) -> Bytes {
         let mut bytes = BytesMut::new();
 
@@ -2086,6 +2481,34 @@ pub fn serialize(&self) -> Bytes {
                 bytes.put_u8(req.kind);
                 bytes.put_u32(req.segno);
             }
+
+            Self::Prefetch(req) => {
+                bytes.put_u8(PagestreamFeMessageTag::Prefetch as u8);
+                bytes.put_u64(req.hdr.reqid);
+                bytes.put_u64(req.hdr.request_lsn.0);
+                bytes.put_u64(req.hdr.not_modified_since.0);
+                bytes.put_u32(req.rel.spcnode);
+                bytes.put_u32(req.rel.dbnode);
+                bytes.put_u32(req.rel.relnode);
+                bytes.put_u8(req.rel.forknum);
+                bytes.put_u32(req.blkno);
+                bytes.put_u32(req.nblocks);
+            }
+
+            Self::GetPages(req) => {
+                bytes.put_u8(PagestreamFeMessageTag::GetPages as u8);
+                bytes.put_u64(req.hdr.reqid);
+                bytes.put_u64(req.hdr.request_lsn.0);
+                bytes.put_u64(req.hdr.not_modified_since.0);
+                bytes.put_u32(req.pages.len() as u32);
+                for (rel, blkno) in &req.pages {
+                    bytes.put_u32(rel.spcnode);
+                    bytes.put_u32(rel.dbnode);
+                    bytes.put_u32(rel.relnode);
+                    bytes.put_u8(rel.forknum);
+                    bytes.put_u32(*blkno);
+                }
+            }
             #[cfg(feature = "testing")]
             Self::Test(req) => {
                 bytes.put_u8(PagestreamFeMessageTag::Test as u8);
@@ -2117,7 +2540,7 @@ pub fn parse<R: std::io::Read>(
                 Lsn::from(body.read_u64::<BigEndian>()?),
                 Lsn::from(body.read_u64::<BigEndian>()?),
             ),
-            PagestreamProtocolVersion::V3 => (
+            PagestreamProtocolVersion::V3 | PagestreamProtocolVersion::V4 => (
                 body.read_u64::<BigEndian>()?,
                 Lsn::from(body.read_u64::<BigEndian>()?),
                 Lsn::from(body.read_u64::<BigEndian>()?),
@@ -2194,6 +2617,55 @@ pub fn parse<R: std::io::Read>(
                     segno: body.read_u32::<BigEndian>()?,
                 },
             )),
+            PagestreamFeMessageTag::Prefetch => {
+                if protocol_version < PagestreamProtocolVersion::V4 {
+                    anyhow::bail!("prefetch requests require protocol version 4");
+                }
+                Ok(PagestreamFeMessage::Prefetch(PagestreamPrefetchRequest {
+                    hdr: PagestreamRequest {
+                        reqid,
+                        request_lsn,
+                        not_modified_since,
+                    },
+                    rel: RelTag {
+                        spcnode: body.read_u32::<BigEndian>()?,
+                        dbnode: body.read_u32::<BigEndian>()?,
+                        relnode: body.read_u32::<BigEndian>()?,
+                        forknum: body.read_u8()?,
+                    },
+                    blkno: body.read_u32::<BigEndian>()?,
+                    nblocks: body.read_u32::<BigEndian>()?,
+                }))
+            }
+            PagestreamFeMessageTag::GetPages => {
+                if protocol_version < PagestreamProtocolVersion::V4 {
+                    anyhow::bail!("getpages requests require protocol version 4");
+                }
+                let count = body.read_u32::<BigEndian>()? as usize;
+                if count > PAGESTREAM_MAX_GETPAGES_PAGES {
+                    anyhow::bail!(
+                        "too many pages in getpages request: {count} > {PAGESTREAM_MAX_GETPAGES_PAGES}"
+                    );
+                }
+                let mut pages = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let rel = RelTag {
+                        spcnode: body.read_u32::<BigEndian>()?,
+                        dbnode: body.read_u32::<BigEndian>()?,
+                        relnode: body.read_u32::<BigEndian>()?,
+                        forknum: body.read_u8()?,
+                    };
+                    pages.push((rel, body.read_u32::<BigEndian>()?));
+                }
+                Ok(PagestreamFeMessage::GetPages(PagestreamGetPagesRequest {
+                    hdr: PagestreamRequest {
+                        reqid,
+                        request_lsn,
+                        not_modified_since,
+                    },
+                    pages,
+                }))
+            }
             #[cfg(feature = "testing")]
             PagestreamFeMessageTag::Test => Ok(PagestreamFeMessage::Test(PagestreamTestRequest {
                 hdr: PagestreamRequest {
@@ -2252,6 +2724,14 @@ pub fn serialize(&self, protocol_version: PagestreamProtocolVersion) -> Bytes {
                         bytes.put(&resp.segment[..]);
                     }
 
+                    Self::GetPages(resp) => {
+                        bytes.put_u8(Tag::GetPages as u8);
+                        bytes.put_u32(resp.pages.len() as u32);
+                        for page in &resp.pages {
+                            bytes.put(&page[..]);
+                        }
+                    }
+
                     #[cfg(feature = "testing")]
                     Self::Test(resp) => {
                         bytes.put_u8(Tag::Test as u8);
@@ -2262,7 +2742,7 @@ pub fn serialize(&self, protocol_version: PagestreamProtocolVersion) -> Bytes {
                     }
                 }
             }
-            PagestreamProtocolVersion::V3 => {
+            PagestreamProtocolVersion::V3 | PagestreamProtocolVersion::V4 => {
                 match self {
                     Self::Exists(resp) => {
                         bytes.put_u8(Tag::Exists as u8);
@@ -2329,6 +2809,22 @@ pub fn serialize(&self, protocol_version: PagestreamProtocolVersion) -> Bytes {
                         bytes.put(&resp.segment[..]);
                     }
 
+                    Self::GetPages(resp) => {
+                        bytes.put_u8(Tag::GetPages as u8);
+                        bytes.put_u64(resp.req.hdr.reqid);
+                        bytes.put_u64(resp.req.hdr.request_lsn.0);
+                        bytes.put_u64(resp.req.hdr.not_modified_since.0);
+                        bytes.put_u32(resp.pages.len() as u32);
+                        for ((rel, blkno), page) in resp.req.pages.iter().zip(&resp.pages) {
+                            bytes.put_u32(rel.spcnode);
+                            bytes.put_u32(rel.dbnode);
+                            bytes.put_u32(rel.relnode);
+                            bytes.put_u8(rel.forknum);
+                            bytes.put_u32(*blkno);
+                            bytes.put(&page[..]);
+                        }
+                    }
+
                     #[cfg(feature = "testing")]
                     Self::Test(resp) => {
                         bytes.put_u8(Tag::Test as u8);
@@ -2482,6 +2978,41 @@ pub fn deserialize(buf: Bytes) -> anyhow::Result<Self> {
                         segment: segment.into(),
                     })
                 }
+                Tag::GetPages => {
+                    let reqid = buf.read_u64::<BigEndian>()?;
+                    let request_lsn = Lsn(buf.read_u64::<BigEndian>()?);
+                    let not_modified_since = Lsn(buf.read_u64::<BigEndian>()?);
+                    let count = buf.read_u32::<BigEndian>()? as usize;
+                    if count > PAGESTREAM_MAX_GETPAGES_PAGES {
+                        anyhow::bail!("too many pages in getpages response: {count}");
+                    }
+                    let mut req_pages = Vec::with_capacity(count);
+                    let mut pages = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        let rel = RelTag {
+                            spcnode: buf.read_u32::<BigEndian>()?,
+                            dbnode: buf.read_u32::<BigEndian>()?,
+                            relnode: buf.read_u32::<BigEndian>()?,
+                            forknum: buf.read_u8()?,
+                        };
+                        let blkno = buf.read_u32::<BigEndian>()?;
+                        let mut page = vec![0; BLCKSZ as usize];
+                        buf.read_exact(&mut page)?;
+                        req_pages.push((rel, blkno));
+                        pages.push(page.into());
+                    }
+                    Self::GetPages(PagestreamGetPagesResponse {
+                        req: PagestreamGetPagesRequest {
+                            hdr: PagestreamRequest {
+                                reqid,
+                                request_lsn,
+                                not_modified_since,
+                            },
+                            pages: req_pages,
+                        },
+                        pages,
+                    })
+                }
                 #[cfg(feature = "testing")]
                 Tag::Test => {
                     let reqid = buf.read_u64::<BigEndian>()?;
@@ -2523,6 +3054,7 @@ pub fn kind(&self) -> &'static str {
             Self::Error(_) => "Error",
             Self::DbSize(_) => "DbSize",
             Self::GetSlruSegment(_) => "GetSlruSegment",
+            Self::GetPages(_) => "GetPages",
             #[cfg(feature = "testing")]
             Self::Test(_) => "Test",
         }
@@ -2616,6 +3148,92 @@ fn test_pagestream() {
         }
     }
 
+    #[test]
+    fn test_pagestream_prefetch() {
+        let msg = PagestreamFeMessage::Prefetch(PagestreamPrefetchRequest {
+            hdr: PagestreamRequest {
+                reqid: 1,
+                request_lsn: Lsn(4),
+                not_modified_since: Lsn(3),
+            },
+            rel: RelTag {
+                forknum: 0,
+                spcnode: 2,
+                dbnode: 3,
+                relnode: 4,
+            },
+            blkno: 7,
+            nblocks: 128,
+        });
+        let bytes = msg.serialize();
+        let reconstructed =
+            PagestreamFeMessage::parse(&mut bytes.clone().reader(), PagestreamProtocolVersion::V4)
+                .unwrap();
+        assert!(msg == reconstructed);
+
+        // prefetch hints are not part of the older protocol versions
+        PagestreamFeMessage::parse(&mut bytes.reader(), PagestreamProtocolVersion::V3).unwrap_err();
+    }
+
+    #[test]
+    fn test_pagestream_getpages() {
+        let hdr = PagestreamRequest {
+            reqid: 1,
+            request_lsn: Lsn(4),
+            not_modified_since: Lsn(3),
+        };
+        let rel = |relnode| RelTag {
+            forknum: 0,
+            spcnode: 2,
+            dbnode: 3,
+            relnode,
+        };
+        let req = PagestreamGetPagesRequest {
+            hdr,
+            pages: vec![(rel(4), 7), (rel(5), 0), (rel(4), 8)],
+        };
+
+        let msg = PagestreamFeMessage::GetPages(req.clone());
+        let bytes = msg.serialize();
+        let reconstructed =
+            PagestreamFeMessage::parse(&mut bytes.clone().reader(), PagestreamProtocolVersion::V4)
+                .unwrap();
+        assert!(msg == reconstructed);
+        PagestreamFeMessage::parse(&mut bytes.reader(), PagestreamProtocolVersion::V3).unwrap_err();
+
+        let pages: Vec<Bytes> = (0..3u8)
+            .map(|i| Bytes::from(vec![i; BLCKSZ as usize]))
+            .collect();
+        let resp = PagestreamBeMessage::GetPages(PagestreamGetPagesResponse {
+            req: req.clone(),
+            pages: pages.clone(),
+        });
+        let bytes = resp.serialize(PagestreamProtocolVersion::V4);
+        match PagestreamBeMessage::deserialize(bytes).unwrap() {
+            PagestreamBeMessage::GetPages(resp) => {
+                assert_eq!(resp.req, req);
+                assert_eq!(resp.pages, pages);
+            }
+            other => panic!("unexpected response: {}", other.kind()),
+        }
+
+        // requests are limited in size
+        let too_many = PagestreamFeMessage::GetPages(PagestreamGetPagesRequest {
+            hdr,
+            pages: vec![(rel(4), 0); PAGESTREAM_MAX_GETPAGES_PAGES + 1],
+        });
+        PagestreamFeMessage::parse(
+            &mut too_many.serialize().reader(),
+            PagestreamProtocolVersion::V4,
+        )
+        .unwrap_err();
+
+        // and so are responses with too many pages
+        let mut too_many = resp.serialize(PagestreamProtocolVersion::V4).to_vec();
+        too_many[25..29].copy_from_slice(&(PAGESTREAM_MAX_GETPAGES_PAGES as u32 + 1).to_be_bytes());
+        PagestreamBeMessage::deserialize(too_many.into()).unwrap_err();
+    }
+
     #[test]
     fn test_tenantinfo_serde() {
         // Test serialization/deserialization of TenantInfo
@@ -2750,6 +3368,9 @@ fn test_image_compression_algorithm_parsing() {
             ("zstd", Zstd { level: None }),
             ("zstd(18)", Zstd { level: Some(18) }),
             ("zstd(-3)", Zstd { level: Some(-3) }),
+            ("zstd-dict", ZstdDict { level: None }),
+            ("zstd-dict(3)", ZstdDict { level: Some(3) }),
+            ("lz4", Lz4),
         ];
 
         for (display, expected) in cases {
@@ -2815,4 +3436,41 @@ fn test_tenant_config_patch_request_serde() {
 
         assert_eq!(patched, expected);
     }
+
+    #[test]
+    fn test_timeline_retention_policy() {
+        let policy: TimelineRetentionPolicy = serde_json::from_value(json!({
+            "pitr_interval": "1day",
+            "tiers": [
+                { "interval": "1h", "keep_for": "7days" },
+                { "interval": "1day", "keep_for": "30days" },
+            ],
+        }))
+        .unwrap();
+        assert_eq!(policy.gc_horizon, None);
+        assert_eq!(policy.pitr_interval, Some(Duration::from_secs(86400)));
+        assert_eq!(
+            policy.tiers[0],
+            RetentionTier {
+                interval: Duration::from_secs(3600),
+                keep_for: Duration::from_secs(7 * 86400),
+            }
+        );
+        policy.validate().unwrap();
+
+        let empty: TimelineRetentionPolicy = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(empty, TimelineRetentionPolicy::default());
+        assert_eq!(serde_json::to_value(&empty).unwrap(), json!({}));
+
+        let invalid = [
+            json!({ "tiers": [{ "interval": "0s", "keep_for": "1h" }] }),
+            json!({ "tiers": [{ "interval": "1500ms", "keep_for": "1h" }] }),
+            json!({ "tiers": [{ "interval": "1day", "keep_for": "1h" }] }),
+            json!({ "tiers": [{ "interval": "1min", "keep_for": "30days" }] }),
+        ];
+        for policy in invalid {
+            let policy: TimelineRetentionPolicy = serde_json::from_value(policy).unwrap();
+            assert!(policy.validate().is_err(), "{policy:?}");
+        }
+    }
 }
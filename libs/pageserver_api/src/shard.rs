@@ -644,4 +644,44 @@ fn shard_id_split() {
             ]
         );
     }
+
+    #[test]
+    fn shard_id_merge() {
+        let tenant_id = TenantId::generate();
+
+        // count=4 into count=2: shards 1 and 3 merge into shard 1
+        let child = TenantShardId {
+            tenant_id,
+            shard_count: ShardCount(2),
+            shard_number: ShardNumber(1),
+        };
+        for parent_number in [1, 3] {
+            let parent = TenantShardId {
+                tenant_id,
+                shard_count: ShardCount(4),
+                shard_number: ShardNumber(parent_number),
+            };
+            assert_eq!(parent.merge(ShardCount(2)), child);
+        }
+
+        // Merging is the inverse of splitting
+        for parent in child.split(ShardCount(8)) {
+            assert_eq!(parent.merge(ShardCount(2)), child);
+        }
+
+        // count=2 into count=1
+        let parent = TenantShardId {
+            tenant_id,
+            shard_count: ShardCount(2),
+            shard_number: ShardNumber(1),
+        };
+        assert_eq!(
+            parent.merge(ShardCount(1)),
+            TenantShardId {
+                tenant_id,
+                shard_count: ShardCount(1),
+                shard_number: ShardNumber(0)
+            }
+        );
+    }
 }
@@ -281,6 +281,19 @@ pub fn generate_pg_control(
     )
 }
 
+pub fn generate_standalone_checkpoint(
+    pg_control_bytes: &[u8],
+    wal_segment: &mut [u8],
+    prev_lsn: Lsn,
+    pg_version: u32,
+) -> anyhow::Result<Bytes> {
+    dispatch_pgversion!(
+        pg_version,
+        pgv::xlog_utils::generate_standalone_checkpoint(pg_control_bytes, wal_segment, prev_lsn),
+        anyhow::bail!("Unknown version {}", pg_version)
+    )
+}
+
 // PG timeline is always 1, changing it doesn't have any useful meaning in Neon.
 //
 // NOTE: this is not to be confused with Neon timelines; different concept!
@@ -150,10 +150,24 @@ pub const fn HEAPBLK_TO_OFFSET(x: u32) -> u32 {
 pub const XLH_UPDATE_OLD_ALL_VISIBLE_CLEARED: u8 = (1 << 0) as u8;
 pub const XLH_UPDATE_NEW_ALL_VISIBLE_CLEARED: u8 = (1 << 1) as u8;
 pub const XLH_DELETE_ALL_VISIBLE_CLEARED: u8 = (1 << 0) as u8;
+pub const XLH_DELETE_IS_SUPER: u8 = (1 << 3) as u8;
+pub const XLH_DELETE_IS_PARTITION_MOVE: u8 = (1 << 5) as u8;
+pub const XLH_UPDATE_PREFIX_FROM_OLD: u8 = (1 << 5) as u8;
+pub const XLH_UPDATE_SUFFIX_FROM_OLD: u8 = (1 << 6) as u8;
+pub const XLHL_XMAX_IS_MULTI: u8 = 0x01;
+pub const XLHL_XMAX_LOCK_ONLY: u8 = 0x02;
+pub const XLHL_XMAX_EXCL_LOCK: u8 = 0x04;
+pub const XLHL_XMAX_KEYSHR_LOCK: u8 = 0x08;
+pub const XLHL_KEYS_UPDATED: u8 = 0x10;
+// Neon addition, so that the combo CID flag survives redo
+pub const XLHL_COMBOCID: u8 = 0x20;
 
 // From heapam_xlog.h
 pub const XLOG_HEAP2_REWRITE: u8 = 0x00;
 
+// From nbtxlog.h
+pub const XLOG_BTREE_INSERT_LEAF: u8 = 0x00;
+
 // From replication/message.h
 pub const XLOG_LOGICAL_MESSAGE: u8 = 0x00;
 
@@ -169,6 +183,7 @@ pub const fn HEAPBLK_TO_OFFSET(x: u32) -> u32 {
 pub const RM_STANDBY_ID: u8 = 8;
 pub const RM_HEAP2_ID: u8 = 9;
 pub const RM_HEAP_ID: u8 = 10;
+pub const RM_BTREE_ID: u8 = 11;
 pub const RM_REPLORIGIN_ID: u8 = 19;
 pub const RM_LOGICALMSG_ID: u8 = 21;
 
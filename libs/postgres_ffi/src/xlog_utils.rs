@@ -179,6 +179,51 @@ pub fn generate_pg_control(
     Ok((pg_control.encode(), pg_control.system_identifier, was_shutdown))
 }
 
+/// Make a pg_control file and WAL segment generated by [`generate_pg_control`] and
+/// [`generate_wal_segment`] usable by vanilla PostgreSQL, which doesn't have the neon-specific
+/// startup code: write a shutdown checkpoint record at the start LSN into the segment, and
+/// point pg_control at it. `prev_lsn` is the start of the preceding record, or 0 if unknown.
+///
+/// Returns the new pg_control file.
+pub fn generate_standalone_checkpoint(
+    pg_control_bytes: &[u8],
+    wal_segment: &mut [u8],
+    prev_lsn: Lsn,
+) -> anyhow::Result<Bytes> {
+    use super::wal_generator::{Record, WalGenerator};
+
+    let mut pg_control = ControlFileData::decode(pg_control_bytes)?;
+    let checkpoint = pg_control.checkPointCopy;
+    // generate_pg_control set the redo pointer to the start LSN, past any page header.
+    let checkpoint_lsn = Lsn(checkpoint.redo);
+
+    let record = Record {
+        rmid: pg_constants::RM_XLOG_ID,
+        info: pg_constants::XLOG_CHECKPOINT_SHUTDOWN,
+        data: checkpoint.encode()?,
+    };
+    let mut generator = WalGenerator::new(std::iter::once(record), checkpoint_lsn);
+    generator.prev_lsn = prev_lsn;
+    let (_, record_bytes) = generator.next().expect("one record");
+
+    let start = checkpoint_lsn.segment_offset(WAL_SEGMENT_SIZE);
+    let end = start + record_bytes.len();
+    anyhow::ensure!(
+        wal_segment.len() == WAL_SEGMENT_SIZE,
+        "unexpected WAL segment size {}",
+        wal_segment.len()
+    );
+    // The generator would write a long page header without the system identifier.
+    anyhow::ensure!(
+        end <= WAL_SEGMENT_SIZE,
+        "checkpoint record at {checkpoint_lsn} would cross a WAL segment boundary"
+    );
+    wal_segment[start..end].copy_from_slice(&record_bytes);
+
+    pg_control.checkPoint = checkpoint_lsn.0;
+    Ok(pg_control.encode())
+}
+
 pub fn get_current_timestamp() -> TimestampTz {
     to_pg_timestamp(SystemTime::now())
 }
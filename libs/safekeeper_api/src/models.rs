@@ -33,6 +33,11 @@ pub struct TimelineCreateRequest {
     // However, we allow specifying custom value higher than start_lsn for
     // manual recovery case, see test_s3_wal_replay.
     pub commit_lsn: Option<Lsn>,
+    // Normal creation refuses to recreate a deleted timeline, so that computes which still
+    // stream to it don't bring it back. Set when the timeline is deliberately recreated, like
+    // when the storage controller resets it to an earlier LSN.
+    #[serde(default)]
+    pub replace_deleted: bool,
 }
 
 /// Same as TermLsn, but serializes LSN using display serializer
@@ -288,6 +293,34 @@ pub struct TimelineTermBumpResponse {
     pub current_term: u64,
 }
 
+/// Digest of the WAL in the requested LSN range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimelineDigest {
+    pub sha256: String,
+}
+
+/// Replication slot of an external WAL consumer (e.g. pg_receivewal).
+///
+/// Slots are per safekeeper: they are not replicated to the other members, and
+/// pull_timeline carries over only the slots of the donor. Hence a safekeeper
+/// holding slots can't be excluded from the membership configuration.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReplicationSlotInfo {
+    pub name: String,
+    /// WAL since this LSN is retained for the consumer.
+    pub restart_lsn: Lsn,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReplicationSlotCreateRequest {
+    pub name: String,
+    /// Retain WAL since this LSN; commit_lsn of the timeline if not given.
+    /// Must not be below both remote_consistent_lsn and backup_lsn, as WAL
+    /// there may already be removed.
+    #[serde(default)]
+    pub restart_lsn: Option<Lsn>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SafekeeperUtilization {
     pub timeline_count: u64,
@@ -32,6 +32,10 @@ pub struct PageserverFeedback {
     pub replytime: SystemTime,
     /// Used to track feedbacks from different shards. Always zero for unsharded tenants.
     pub shard_number: u32,
+    /// How long the pageserver's latest WAL ingest waited for the tenant's ingest throttle,
+    /// in microseconds. Zero when not throttled.
+    #[serde(default)]
+    pub ingest_throttle_wait_us: u64,
 }
 
 impl PageserverFeedback {
@@ -43,6 +47,7 @@ pub fn empty() -> PageserverFeedback {
             disk_consistent_lsn: Lsn::INVALID,
             replytime: *PG_EPOCH,
             shard_number: 0,
+            ingest_throttle_wait_us: 0,
         }
     }
 
@@ -101,6 +106,13 @@ pub fn serialize(&self, buf: &mut BytesMut) {
             buf.put_u32(self.shard_number);
         }
 
+        if self.ingest_throttle_wait_us > 0 {
+            nkeys += 1;
+            buf.put_slice(b"ps_ingest_throttle_wait_us\0");
+            buf.put_i32(8);
+            buf.put_u64(self.ingest_throttle_wait_us);
+        }
+
         buf[buf_ptr] = nkeys;
     }
 
@@ -147,6 +159,11 @@ pub fn parse(mut buf: Bytes) -> PageserverFeedback {
                     assert_eq!(len, 4);
                     rf.shard_number = buf.get_u32();
                 }
+                b"ps_ingest_throttle_wait_us" => {
+                    let len = buf.get_i32();
+                    assert_eq!(len, 8);
+                    rf.ingest_throttle_wait_us = buf.get_u64();
+                }
                 _ => {
                     let len = buf.get_i32();
                     warn!(
@@ -206,6 +223,19 @@ fn test_replication_feedback_serialization() {
         assert_eq!(rf, rf_parsed);
     }
 
+    #[test]
+    fn test_replication_feedback_ingest_throttle_wait() {
+        let mut rf = PageserverFeedback::empty();
+        rf.replytime = *PG_EPOCH + Duration::from_secs(100_000_000);
+        rf.shard_number = 3;
+        rf.ingest_throttle_wait_us = 250_000;
+        let mut data = BytesMut::new();
+        rf.serialize(&mut data);
+
+        let rf_parsed = PageserverFeedback::parse(data.freeze());
+        assert_eq!(rf, rf_parsed);
+    }
+
     #[test]
     fn test_replication_feedback_unknown_key() {
         let mut rf = PageserverFeedback::empty();
@@ -163,6 +163,17 @@ pub fn split(&self, new_shard_count: ShardCount) -> Vec<TenantShardId> {
 
         child_shards
     }
+
+    /// Calculate the shard that this TenantShardId merges into when reducing the overall tenant
+    /// to the given number of shards. This is the inverse of [`Self::split`]: the parents of a
+    /// merged shard are the shards that `split` of the merged shard would produce.
+    pub fn merge(&self, new_shard_count: ShardCount) -> TenantShardId {
+        TenantShardId {
+            tenant_id: self.tenant_id,
+            shard_number: ShardNumber(self.shard_number.0 % new_shard_count.count()),
+            shard_count: new_shard_count,
+        }
+    }
 }
 
 impl std::fmt::Display for ShardNumber {
@@ -11,6 +11,7 @@
 use pageserver::tenant::storage_layer::InMemoryLayer;
 use pageserver::{page_cache, virtual_file};
 use pageserver_api::key::Key;
+use pageserver_api::models::ImageCompressionAlgorithm;
 use pageserver_api::shard::TenantShardId;
 use pageserver_api::value::Value;
 use tokio_util::sync::CancellationToken;
@@ -126,7 +127,12 @@ async fn ingest(
             max_concurrency: NonZeroUsize::new(1).unwrap(),
         });
         let (_desc, path) = layer
-            .write_to_disk(&ctx, None, l0_flush_state.inner())
+            .write_to_disk(
+                &ctx,
+                None,
+                l0_flush_state.inner(),
+                ImageCompressionAlgorithm::Disabled,
+            )
             .await?
             .unwrap();
         tokio::fs::remove_file(path).await?;
@@ -45,6 +45,7 @@ fn run_bench(b: &mut Bencher, inprogress: usize) -> anyhow::Result<()> {
             shard: ShardIndex::new(ShardNumber(1), ShardCount(2)),
             generation: Generation::Valid(1),
             file_size: 0,
+            cross_tenant_owner: None,
         };
 
         // Construct the (initial and uploaded) index with layer0.
@@ -437,6 +437,60 @@ pub async fn timeline_archival_config(
             .map_err(Error::ReceiveBody)
     }
 
+    pub async fn timeline_reset_to_lsn(
+        &self,
+        tenant_shard_id: TenantShardId,
+        timeline_id: TimelineId,
+        req: &TimelineResetToLsnRequest,
+    ) -> Result<()> {
+        let uri = format!(
+            "{}/v1/tenant/{tenant_shard_id}/timeline/{timeline_id}/reset_to_lsn",
+            self.mgmt_api_endpoint
+        );
+
+        self.request(Method::PUT, &uri, req)
+            .await?
+            .json()
+            .await
+            .map_err(Error::ReceiveBody)
+    }
+
+    pub async fn timeline_export_pgdata(
+        &self,
+        tenant_shard_id: TenantShardId,
+        timeline_id: TimelineId,
+        req: &TimelineExportPgdataRequest,
+    ) -> Result<TimelineExportPgdataTaskInfo> {
+        let uri = format!(
+            "{}/v1/tenant/{tenant_shard_id}/timeline/{timeline_id}/export_pgdata",
+            self.mgmt_api_endpoint
+        );
+
+        self.request(Method::POST, &uri, req)
+            .await?
+            .json()
+            .await
+            .map_err(Error::ReceiveBody)
+    }
+
+    pub async fn timeline_retention_policy(
+        &self,
+        tenant_shard_id: TenantShardId,
+        timeline_id: TimelineId,
+        req: &TimelineRetentionPolicyRequest,
+    ) -> Result<()> {
+        let uri = format!(
+            "{}/v1/tenant/{tenant_shard_id}/timeline/{timeline_id}/retention_policy",
+            self.mgmt_api_endpoint
+        );
+
+        self.request(Method::PUT, &uri, req)
+            .await?
+            .json()
+            .await
+            .map_err(Error::ReceiveBody)
+    }
+
     pub async fn timeline_detach_ancestor(
         &self,
         tenant_shard_id: TenantShardId,
@@ -528,6 +582,22 @@ pub async fn tenant_shard_split(
             .map_err(Error::ReceiveBody)
     }
 
+    pub async fn tenant_shard_merge(
+        &self,
+        tenant_shard_id: TenantShardId,
+        req: TenantShardMergeRequest,
+    ) -> Result<TenantShardMergeResponse> {
+        let uri = format!(
+            "{}/v1/tenant/{}/shard_merge",
+            self.mgmt_api_endpoint, tenant_shard_id
+        );
+        self.request(Method::PUT, &uri, req)
+            .await?
+            .json()
+            .await
+            .map_err(Error::ReceiveBody)
+    }
+
     pub async fn timeline_list(
         &self,
         tenant_shard_id: &TenantShardId,
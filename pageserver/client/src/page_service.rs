@@ -6,6 +6,7 @@
     PagestreamBeMessage, PagestreamFeMessage, PagestreamGetPageRequest, PagestreamGetPageResponse,
 };
 use pageserver_api::reltag::RelTag;
+use pageserver_api::shard::TenantShardId;
 use tokio::task::JoinHandle;
 use tokio_postgres::CopyOutStream;
 use tokio_util::sync::CancellationToken;
@@ -36,9 +37,8 @@ pub async fn new(connstring: String) -> anyhow::Result<Self> {
             async move {
                 tokio::select! {
                     _ = conn_task_cancel.cancelled() => { }
-                    res = connection => {
-                        res.unwrap();
-                    }
+                    // An error also ends the client's queries and copy streams, which return it.
+                    _ = connection => { }
                 }
             }
         });
@@ -83,6 +83,19 @@ pub async fn pagestream(
         })
     }
 
+    /// Lease `lsn` on the shard, see the `lease lsn` page service command.
+    pub async fn lease_lsn(
+        &self,
+        tenant_shard_id: TenantShardId,
+        timeline_id: TimelineId,
+        lsn: Lsn,
+    ) -> anyhow::Result<()> {
+        self.client
+            .simple_query(&format!("lease lsn {tenant_shard_id} {timeline_id} {lsn}"))
+            .await?;
+        Ok(())
+    }
+
     pub async fn basebackup(&self, req: &BasebackupRequest) -> anyhow::Result<CopyOutStream> {
         let BasebackupRequest {
             tenant_id,
@@ -237,7 +250,8 @@ pub async fn getpage_recv(&mut self) -> anyhow::Result<PagestreamGetPageResponse
             PagestreamBeMessage::Exists(_)
             | PagestreamBeMessage::Nblocks(_)
             | PagestreamBeMessage::DbSize(_)
-            | PagestreamBeMessage::GetSlruSegment(_) => {
+            | PagestreamBeMessage::GetSlruSegment(_)
+            | PagestreamBeMessage::GetPages(_) => {
                 anyhow::bail!(
                     "unexpected be message kind in response to getpage request: {}",
                     next.kind()
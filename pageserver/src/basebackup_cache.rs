@@ -0,0 +1,769 @@
+//!
+//! Cache of prepared basebackups
+//!
+//! [`crate::basebackup::send_basebackup_tarball`] builds the tarball from the keyspace on
+//! every compute start, which dominates cold start latency for timelines with large
+//! catalogs. A compute that shuts down cleanly ends its WAL with a shutdown checkpoint, and
+//! the next compute on the timeline usually starts right at the end of it. When WAL ingest
+//! on shard zero sees a shutdown checkpoint, it asks this cache to prepare basebackups at
+//! the end of that record in the background, one per configured compression. The page
+//! service then sends the prepared file as-is to requests for the same timeline, LSN and
+//! compression.
+//!
+//! Replica and full basebackups are never cached.
+//!
+//! # Storage
+//!
+//! Prepared basebackups are files in the `basebackup_cache` directory of the workdir,
+//! bounded by `max_total_size_bytes`: the least recently used ones are evicted first. The
+//! directory is emptied at startup, and a timeline's files are removed when it shuts down,
+//! as the timeline may be deleted or have its history rewritten afterwards.
+//!
+//! With `remote_storage` enabled, prepared basebackups are also uploaded to
+//! [`remote_basebackups_path`], within the timeline's prefix so that timeline deletion
+//! removes them. The first cache miss on a timeline after a restart or a migration lists
+//! that prefix, and requests for a listed basebackup download it instead of generating it.
+//!
+//! Like layers, remote basebackups carry the generation that uploaded them in their name:
+//! two attached generations never overwrite each other's objects. After an upload, only the
+//! newest LSN is kept: the superseded objects of the uploading generation and of older
+//! generations are deleted, never those of newer generations. Objects left behind by a
+//! shard split, or by an upload that was interrupted before it could delete what it
+//! superseded, are removed by the scrubber.
+//!
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, Weak};
+
+use anyhow::Context;
+use async_compression::tokio::write::{GzipEncoder, ZstdEncoder};
+use camino::Utf8PathBuf;
+use once_cell::sync::OnceCell;
+use pageserver_api::config::{BasebackupCacheConfig, BasebackupCompression};
+use pageserver_api::shard::TenantShardId;
+use remote_storage::{
+    DownloadError, DownloadKind, DownloadOpts, GenericRemoteStorage, ListingMode, RemotePath,
+    TimeoutOrCancel,
+};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{Instrument, info, info_span, warn};
+use utils::backoff;
+use utils::generation::Generation;
+use utils::id::TimelineId;
+use utils::lsn::Lsn;
+
+use crate::basebackup::send_basebackup_tarball;
+use crate::config::PageServerConf;
+use crate::context::{DownloadBehavior, RequestContext};
+use crate::metrics::BASEBACKUP_CACHE;
+use crate::task_mgr::{self, BACKGROUND_RUNTIME, TaskKind};
+use crate::tenant::Timeline;
+use crate::tenant::remote_timeline_client::{
+    FAILED_REMOTE_OP_RETRIES, FAILED_UPLOAD_WARN_THRESHOLD, remote_basebackups_path,
+};
+use crate::tenant::timeline::{WaitLsnTimeout, WaitLsnWaiter};
+
+static BASEBACKUP_CACHE_INSTANCE: OnceCell<BasebackupCache> = OnceCell::new();
+
+///
+/// Initialize the basebackup cache if it is configured, and spawn its background task.
+/// This must be called once at page server startup.
+///
+pub fn init(
+    conf: &'static PageServerConf,
+    remote_storage: GenericRemoteStorage,
+    cancel: CancellationToken,
+) -> anyhow::Result<()> {
+    let Some(config) = &conf.basebackup_cache else {
+        return Ok(());
+    };
+
+    // Files left behind by a previous run are not in the index: start from scratch.
+    let dir = conf.basebackup_cache_dir();
+    match std::fs::remove_dir_all(&dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("remove {dir}")),
+    }
+    std::fs::create_dir_all(&dir).with_context(|| format!("create {dir}"))?;
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let cache = BasebackupCache {
+        config: config.clone(),
+        dir,
+        remote_storage: config.remote_storage.then_some(remote_storage),
+        inner: Mutex::new(Inner::default()),
+        tx,
+    };
+    if BASEBACKUP_CACHE_INSTANCE.set(cache).is_err() {
+        panic!("basebackup cache already initialized");
+    }
+
+    let cache = BASEBACKUP_CACHE_INSTANCE.get().unwrap();
+    BACKGROUND_RUNTIME.spawn(task_mgr::exit_on_panic_or_error(
+        "basebackup cache",
+        async move {
+            cache.run(rx, cancel).await;
+            anyhow::Ok(())
+        },
+    ));
+
+    Ok(())
+}
+
+///
+/// Get a handle to the basebackup cache, or None if it is not configured.
+///
+pub fn get() -> Option<&'static BasebackupCache> {
+    BASEBACKUP_CACHE_INSTANCE.get()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct EntryKey {
+    tenant_shard_id: TenantShardId,
+    timeline_id: TimelineId,
+    lsn: Lsn,
+    compression: BasebackupCompression,
+}
+
+impl EntryKey {
+    fn file_name(&self) -> String {
+        format!(
+            "{}-{}-{}",
+            self.tenant_shard_id,
+            self.timeline_id,
+            object_name(self.lsn, self.compression)
+        )
+    }
+
+    fn remote_path(&self, generation: Generation) -> RemotePath {
+        remote_basebackups_path(&self.tenant_shard_id, &self.timeline_id).join(format!(
+            "{}{}",
+            object_name(self.lsn, self.compression),
+            generation.get_suffix()
+        ))
+    }
+}
+
+const COMPRESSIONS: [BasebackupCompression; 3] = [
+    BasebackupCompression::Uncompressed,
+    BasebackupCompression::Gzip,
+    BasebackupCompression::Zstd,
+];
+
+fn extension(compression: BasebackupCompression) -> &'static str {
+    match compression {
+        BasebackupCompression::Uncompressed => "tar",
+        BasebackupCompression::Gzip => "tar.gz",
+        BasebackupCompression::Zstd => "tar.zst",
+    }
+}
+
+/// Name of a basebackup within its timeline: the LSN in hex, which sorts by LSN, and an
+/// extension for the compression.
+fn object_name(lsn: Lsn, compression: BasebackupCompression) -> String {
+    format!("{:016X}.{}", lsn.0, extension(compression))
+}
+
+fn parse_object_name(name: &str) -> Option<(Lsn, BasebackupCompression)> {
+    let (lsn, ext) = name.split_once('.')?;
+    let lsn = Lsn(u64::from_str_radix(lsn, 16).ok()?);
+    let compression = COMPRESSIONS.into_iter().find(|c| extension(*c) == ext)?;
+    Some((lsn, compression))
+}
+
+/// Parse the name of a remote basebackup object: an [`object_name`] with the suffix of the
+/// generation that uploaded it.
+pub fn parse_remote_object_name(name: &str) -> Option<(Lsn, BasebackupCompression, Generation)> {
+    let (name, generation) = match name.rsplit_once('-') {
+        Some((name, suffix)) => (name, Generation::parse_suffix(suffix)?),
+        None => (name, Generation::none()),
+    };
+    let (lsn, compression) = parse_object_name(name)?;
+    Some((lsn, compression, generation))
+}
+
+enum Location {
+    Local { size: u64 },
+    Remote,
+}
+
+struct Entry {
+    location: Location,
+    /// Generation of the remote object that backs this entry, if any: the one that prepared
+    /// it, or the one that it was downloaded from.
+    generation: Generation,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<EntryKey, Entry>,
+    local_bytes: u64,
+    /// Logical clock for [`Entry::last_used`].
+    clock: u64,
+    /// Timelines whose remote basebackups have been listed into `entries`.
+    remote_listed: HashSet<(TenantShardId, TimelineId)>,
+}
+
+enum Request {
+    Prepare { timeline: Weak<Timeline>, lsn: Lsn },
+    RemoveFiles(Vec<Utf8PathBuf>),
+}
+
+/// See module-level comment.
+pub struct BasebackupCache {
+    config: BasebackupCacheConfig,
+    dir: Utf8PathBuf,
+    remote_storage: Option<GenericRemoteStorage>,
+    inner: Mutex<Inner>,
+    tx: mpsc::UnboundedSender<Request>,
+}
+
+impl BasebackupCache {
+    /// Prepare basebackups of `timeline` at `lsn` in the background.
+    pub(crate) fn prepare(&self, timeline: Weak<Timeline>, lsn: Lsn) {
+        // Ignore error sending: that just means we're shutting down.
+        self.tx.send(Request::Prepare { timeline, lsn }).ok();
+    }
+
+    /// Open the prepared basebackup of `timeline` at `lsn`, if there is one.
+    pub(crate) async fn lookup(
+        &self,
+        timeline: &Timeline,
+        lsn: Lsn,
+        compression: BasebackupCompression,
+    ) -> Option<tokio::fs::File> {
+        let key = EntryKey {
+            tenant_shard_id: timeline.tenant_shard_id,
+            timeline_id: timeline.timeline_id,
+            lsn,
+            compression,
+        };
+
+        if let Some(remote_storage) = &self.remote_storage {
+            if let Err(e) = self.list_remote(remote_storage, timeline).await {
+                warn!("Failed to list remote basebackups: {e:#}");
+            }
+        }
+
+        let (is_local, generation) = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.clock += 1;
+            let clock = inner.clock;
+            match inner.entries.get_mut(&key) {
+                Some(entry) => {
+                    entry.last_used = clock;
+                    (
+                        matches!(entry.location, Location::Local { .. }),
+                        entry.generation,
+                    )
+                }
+                None => {
+                    BASEBACKUP_CACHE.misses.inc();
+                    return None;
+                }
+            }
+        };
+
+        let path = self.dir.join(key.file_name());
+        if is_local {
+            // The file may have been evicted since we looked at the index: that is a miss.
+            let file = tokio::fs::File::open(&path).await.ok();
+            match file {
+                Some(_) => BASEBACKUP_CACHE.hits_local.inc(),
+                None => BASEBACKUP_CACHE.misses.inc(),
+            }
+            return file;
+        }
+
+        // unwrap safety: remote entries are only listed if remote storage is enabled
+        let remote_storage = self.remote_storage.as_ref().unwrap();
+        match self
+            .download(remote_storage, timeline, &key, generation, &path)
+            .await
+        {
+            Ok(size) => {
+                self.insert_local(key, size, generation);
+                let file = tokio::fs::File::open(&path).await.ok();
+                match file {
+                    Some(_) => BASEBACKUP_CACHE.hits_remote.inc(),
+                    None => BASEBACKUP_CACHE.misses.inc(),
+                }
+                file
+            }
+            Err(e) => {
+                if !matches!(
+                    e.downcast_ref::<DownloadError>(),
+                    Some(DownloadError::NotFound)
+                ) {
+                    warn!(%lsn, ?compression, "Failed to download basebackup: {e:#}");
+                }
+                // Superseded by a newer basebackup, or the download failed: forget about it,
+                // a later preparation or listing will bring it back if it is worth it.
+                self.inner.lock().unwrap().entries.remove(&key);
+                BASEBACKUP_CACHE.misses.inc();
+                None
+            }
+        }
+    }
+
+    /// Forget about a timeline that is shutting down, and remove its local files.
+    ///
+    /// Called after the timeline's gate is closed, so that no preparation is in flight.
+    pub(crate) fn remove_timeline(&self, tenant_shard_id: TenantShardId, timeline_id: TimelineId) {
+        let mut removed = Vec::new();
+        {
+            let mut inner = self.inner.lock().unwrap();
+            let mut local_bytes = inner.local_bytes;
+            inner.entries.retain(|key, entry| {
+                if key.tenant_shard_id != tenant_shard_id || key.timeline_id != timeline_id {
+                    return true;
+                }
+                if let Location::Local { size } = entry.location {
+                    local_bytes -= size;
+                    removed.push(self.dir.join(key.file_name()));
+                }
+                false
+            });
+            inner.local_bytes = local_bytes;
+            inner.remote_listed.remove(&(tenant_shard_id, timeline_id));
+            BASEBACKUP_CACHE.current_bytes.set(local_bytes);
+        }
+
+        if !removed.is_empty() {
+            self.tx.send(Request::RemoveFiles(removed)).ok();
+        }
+    }
+
+    /// Remove the basebackups of a timeline from remote storage, because its history was
+    /// rewritten.
+    ///
+    /// Called while the timeline is shut down, see [`Self::remove_timeline`].
+    pub(crate) async fn remove_remote_timeline(
+        &self,
+        tenant_shard_id: TenantShardId,
+        timeline_id: TimelineId,
+        cancel: &CancellationToken,
+    ) -> anyhow::Result<()> {
+        let Some(remote_storage) = &self.remote_storage else {
+            return Ok(());
+        };
+        let prefix = remote_basebackups_path(&tenant_shard_id, &timeline_id);
+        backoff::retry(
+            || remote_storage.delete_prefix(&prefix, cancel),
+            TimeoutOrCancel::caused_by_cancel,
+            FAILED_UPLOAD_WARN_THRESHOLD,
+            FAILED_REMOTE_OP_RETRIES,
+            "delete remote basebackups",
+            cancel,
+        )
+        .await
+        .ok_or_else(|| anyhow::Error::new(TimeoutOrCancel::Cancel))
+        .and_then(|x| x)
+    }
+
+    async fn run(&self, mut rx: mpsc::UnboundedReceiver<Request>, cancel: CancellationToken) {
+        loop {
+            let request = tokio::select! {
+                r = rx.recv() => {
+                    match r {
+                        Some(r) => r,
+                        None => break,
+                    }
+                }
+                _ = cancel.cancelled() => break,
+            };
+
+            match request {
+                Request::Prepare { timeline, lsn } => {
+                    let Some(timeline) = timeline.upgrade() else {
+                        continue;
+                    };
+                    let span = info_span!("prepare_basebackup",
+                        tenant_id = %timeline.tenant_shard_id.tenant_id,
+                        shard_id = %timeline.tenant_shard_id.shard_slug(),
+                        timeline_id = %timeline.timeline_id,
+                        %lsn,
+                    );
+                    if let Err(e) = self.prepare_timeline(&timeline, lsn).instrument(span).await {
+                        if !timeline.cancel.is_cancelled() {
+                            BASEBACKUP_CACHE.prepare_errors.inc();
+                            warn!(
+                                tenant_id = %timeline.tenant_shard_id.tenant_id,
+                                timeline_id = %timeline.timeline_id,
+                                %lsn,
+                                "Failed to prepare basebackup: {e:#}"
+                            );
+                        }
+                    }
+                }
+                Request::RemoveFiles(paths) => {
+                    for path in paths {
+                        if let Err(e) = tokio::fs::remove_file(&path).await {
+                            if e.kind() != std::io::ErrorKind::NotFound {
+                                warn!("Failed to remove cached basebackup {path}: {e}");
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn prepare_timeline(&self, timeline: &Arc<Timeline>, lsn: Lsn) -> anyhow::Result<()> {
+        // Holding the gate keeps [`Self::remove_timeline`] from running until we are done.
+        let Ok(_gate) = timeline.gate.enter() else {
+            return Ok(());
+        };
+
+        let ctx = RequestContext::new(TaskKind::BasebackupCache, DownloadBehavior::Download)
+            .with_scope_timeline(timeline);
+        timeline
+            .wait_lsn(
+                lsn,
+                WaitLsnWaiter::BasebackupCache,
+                WaitLsnTimeout::Default,
+                &ctx,
+            )
+            .await?;
+
+        for &compression in &self.config.compressions {
+            let key = EntryKey {
+                tenant_shard_id: timeline.tenant_shard_id,
+                timeline_id: timeline.timeline_id,
+                lsn,
+                compression,
+            };
+            let already_local = matches!(
+                self.inner.lock().unwrap().entries.get(&key),
+                Some(Entry {
+                    location: Location::Local { .. },
+                    ..
+                })
+            );
+            if already_local {
+                continue;
+            }
+
+            let path = self.dir.join(key.file_name());
+            let size = self.generate(timeline, &key, &path, &ctx).await?;
+            BASEBACKUP_CACHE.prepared.inc();
+            info!(?compression, size, "Prepared basebackup");
+
+            self.insert_local(key, size, timeline.generation);
+
+            if let Some(remote_storage) = &self.remote_storage {
+                self.upload(remote_storage, timeline, &key, &path, size)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn generate(
+        &self,
+        timeline: &Timeline,
+        key: &EntryKey,
+        path: &Utf8PathBuf,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<u64> {
+        let temp_path = Utf8PathBuf::from(format!("{path}.tmp"));
+        let file = tokio::fs::File::create(&temp_path)
+            .await
+            .with_context(|| format!("create {temp_path}"))?;
+        let mut writer = BufWriter::new(file);
+
+        let lsn = Some(key.lsn);
+        match key.compression {
+            BasebackupCompression::Uncompressed => {
+                send_basebackup_tarball(&mut writer, timeline, lsn, None, false, false, ctx)
+                    .await?;
+            }
+            BasebackupCompression::Gzip => {
+                // Unlike the page service, we are not on the critical path of compute startup:
+                // spend the CPU on a smaller download.
+                let mut encoder =
+                    GzipEncoder::with_quality(&mut writer, async_compression::Level::Best);
+                send_basebackup_tarball(&mut encoder, timeline, lsn, None, false, false, ctx)
+                    .await?;
+                encoder.shutdown().await?;
+            }
+            BasebackupCompression::Zstd => {
+                let mut encoder =
+                    ZstdEncoder::with_quality(&mut writer, async_compression::Level::Default);
+                send_basebackup_tarball(&mut encoder, timeline, lsn, None, false, false, ctx)
+                    .await?;
+                encoder.shutdown().await?;
+            }
+        }
+        writer.flush().await?;
+        drop(writer);
+
+        let size = tokio::fs::metadata(&temp_path).await?.len();
+        tokio::fs::rename(&temp_path, path)
+            .await
+            .with_context(|| format!("rename {temp_path} to {path}"))?;
+        Ok(size)
+    }
+
+    /// Account for a basebackup that is now on local disk, and evict the least recently
+    /// used ones beyond the budget.
+    fn insert_local(&self, key: EntryKey, size: u64, generation: Generation) {
+        let mut evicted = Vec::new();
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.clock += 1;
+            let entry = Entry {
+                location: Location::Local { size },
+                generation,
+                last_used: inner.clock,
+            };
+            if let Some(Entry {
+                location: Location::Local { size },
+                ..
+            }) = inner.entries.insert(key, entry)
+            {
+                inner.local_bytes -= size;
+            }
+            inner.local_bytes += size;
+
+            while inner.local_bytes > self.config.max_total_size_bytes {
+                let Some((&victim, _)) = inner
+                    .entries
+                    .iter()
+                    .filter(|(_, e)| matches!(e.location, Location::Local { .. }))
+                    .min_by_key(|(_, e)| e.last_used)
+                else {
+                    break;
+                };
+
+                let entry = inner.entries.get_mut(&victim).unwrap();
+                let Location::Local { size } = entry.location else {
+                    unreachable!("filtered above");
+                };
+                // An uploaded basebackup stays available for download
+                if self.remote_storage.is_some() {
+                    entry.location = Location::Remote;
+                } else {
+                    inner.entries.remove(&victim);
+                }
+                inner.local_bytes -= size;
+                evicted.push(self.dir.join(victim.file_name()));
+                BASEBACKUP_CACHE.evictions.inc();
+            }
+
+            BASEBACKUP_CACHE.current_bytes.set(inner.local_bytes);
+        }
+
+        if !evicted.is_empty() {
+            self.tx.send(Request::RemoveFiles(evicted)).ok();
+        }
+    }
+
+    /// Upload a prepared basebackup, then delete the remote basebackups it supersedes: those at
+    /// older LSNs, or at the same LSN from older generations. Objects of newer generations are
+    /// left alone, as a newer attachment of the timeline may be using them.
+    async fn upload(
+        &self,
+        remote_storage: &GenericRemoteStorage,
+        timeline: &Timeline,
+        key: &EntryKey,
+        path: &Utf8PathBuf,
+        size: u64,
+    ) -> anyhow::Result<()> {
+        let cancel = &timeline.cancel;
+        let generation = timeline.generation;
+        let remote_path = key.remote_path(generation);
+        backoff::retry(
+            || async {
+                let file = tokio::fs::File::open(path)
+                    .await
+                    .with_context(|| format!("open {path}"))?;
+                let reader = tokio_util::io::ReaderStream::new(file);
+                remote_storage
+                    .upload(reader, size as usize, &remote_path, None, cancel)
+                    .await
+            },
+            TimeoutOrCancel::caused_by_cancel,
+            FAILED_UPLOAD_WARN_THRESHOLD,
+            FAILED_REMOTE_OP_RETRIES,
+            "upload basebackup",
+            cancel,
+        )
+        .await
+        .ok_or_else(|| anyhow::Error::new(TimeoutOrCancel::Cancel))
+        .and_then(|x| x)?;
+
+        let prefix = remote_basebackups_path(&key.tenant_shard_id, &key.timeline_id);
+        let listing = remote_storage
+            .list(Some(&prefix), ListingMode::NoDelimiter, None, cancel)
+            .await?;
+        let superseded = listing
+            .keys
+            .into_iter()
+            .filter(|object| {
+                object
+                    .key
+                    .object_name()
+                    .and_then(parse_remote_object_name)
+                    .is_some_and(|(lsn, _, object_generation)| {
+                        object_generation <= generation
+                            && (lsn, object_generation) < (key.lsn, generation)
+                    })
+            })
+            .map(|object| object.key)
+            .collect::<Vec<_>>();
+        if !superseded.is_empty() {
+            remote_storage.delete_objects(&superseded, cancel).await?;
+        }
+
+        self.inner.lock().unwrap().entries.retain(|k, e| {
+            k.tenant_shard_id != key.tenant_shard_id
+                || k.timeline_id != key.timeline_id
+                || k.lsn >= key.lsn
+                || e.generation > generation
+                || !matches!(e.location, Location::Remote)
+        });
+
+        Ok(())
+    }
+
+    /// On the first lookup for a timeline, learn which basebackups are in remote storage.
+    async fn list_remote(
+        &self,
+        remote_storage: &GenericRemoteStorage,
+        timeline: &Timeline,
+    ) -> anyhow::Result<()> {
+        let id = (timeline.tenant_shard_id, timeline.timeline_id);
+        if self.inner.lock().unwrap().remote_listed.contains(&id) {
+            return Ok(());
+        }
+
+        let prefix = remote_basebackups_path(&timeline.tenant_shard_id, &timeline.timeline_id);
+        let listing = remote_storage
+            .list(
+                Some(&prefix),
+                ListingMode::NoDelimiter,
+                None,
+                &timeline.cancel,
+            )
+            .await?;
+
+        let mut inner = self.inner.lock().unwrap();
+        for object in listing.keys {
+            let Some((lsn, compression, generation)) =
+                object.key.object_name().and_then(parse_remote_object_name)
+            else {
+                continue;
+            };
+            let key = EntryKey {
+                tenant_shard_id: timeline.tenant_shard_id,
+                timeline_id: timeline.timeline_id,
+                lsn,
+                compression,
+            };
+            // Several generations may have uploaded the same basebackup: use the newest one,
+            // which is the least likely to be deleted as superseded.
+            let entry = inner.entries.entry(key).or_insert(Entry {
+                location: Location::Remote,
+                generation,
+                last_used: 0,
+            });
+            if matches!(entry.location, Location::Remote) && entry.generation < generation {
+                entry.generation = generation;
+            }
+        }
+        inner.remote_listed.insert(id);
+
+        Ok(())
+    }
+
+    async fn download(
+        &self,
+        remote_storage: &GenericRemoteStorage,
+        timeline: &Timeline,
+        key: &EntryKey,
+        generation: Generation,
+        path: &Utf8PathBuf,
+    ) -> anyhow::Result<u64> {
+        let opts = DownloadOpts {
+            kind: DownloadKind::Large,
+            ..Default::default()
+        };
+        let download = remote_storage
+            .download(&key.remote_path(generation), &opts, &timeline.cancel)
+            .await?;
+
+        // Concurrent downloads of the same basebackup each use their own temporary file.
+        let temp_path = Utf8PathBuf::from(format!(
+            "{path}.{}.download",
+            self.inner.lock().unwrap().clock
+        ));
+        let mut file = tokio::fs::File::create(&temp_path)
+            .await
+            .with_context(|| format!("create {temp_path}"))?;
+        let mut reader = tokio_util::io::StreamReader::new(download.download_stream);
+        let size = tokio::io::copy(&mut reader, &mut file).await;
+        let size = match size {
+            Ok(size) => size,
+            Err(e) => {
+                tokio::fs::remove_file(&temp_path).await.ok();
+                return Err(e).with_context(|| format!("download to {temp_path}"));
+            }
+        };
+        file.flush().await?;
+        drop(file);
+
+        tokio::fs::rename(&temp_path, path)
+            .await
+            .with_context(|| format!("rename {temp_path} to {path}"))?;
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn object_names_roundtrip() {
+        for compression in COMPRESSIONS {
+            let lsn = Lsn(0x16B9188);
+            let name = object_name(lsn, compression);
+            assert_eq!(parse_object_name(&name), Some((lsn, compression)));
+        }
+
+        // Names sort by LSN
+        assert!(
+            object_name(Lsn(0x2000000), BasebackupCompression::Gzip)
+                > object_name(Lsn(0x1FFFFFF), BasebackupCompression::Gzip)
+        );
+
+        assert_eq!(parse_object_name("0000000001000000.tar.xz"), None);
+        assert_eq!(parse_object_name("index_part.json"), None);
+    }
+
+    #[test]
+    fn remote_object_names_roundtrip() {
+        let key = EntryKey {
+            tenant_shard_id: TenantShardId::unsharded(utils::id::TenantId::generate()),
+            timeline_id: TimelineId::generate(),
+            lsn: Lsn(0x16B9188),
+            compression: BasebackupCompression::Zstd,
+        };
+        for generation in [Generation::none(), Generation::new(0xa)] {
+            let path = key.remote_path(generation);
+            assert_eq!(
+                path.object_name().and_then(parse_remote_object_name),
+                Some((key.lsn, key.compression, generation))
+            );
+        }
+
+        assert_eq!(parse_remote_object_name("0000000001000000.tar-zz"), None);
+        assert_eq!(parse_remote_object_name("index_part.json-0000000a"), None);
+    }
+}
@@ -26,8 +26,9 @@
 };
 use pageserver::tenant::{TenantSharedResources, mgr, secondary};
 use pageserver::{
-    CancellableTask, ConsumptionMetricsTasks, HttpEndpointListener, HttpsEndpointListener, http,
-    page_cache, page_service, task_mgr, virtual_file,
+    CancellableTask, ConsumptionMetricsTasks, HttpEndpointListener, HttpsEndpointListener,
+    basebackup_cache, http, materialized_page_cache, page_cache, page_service, task_mgr,
+    virtual_file,
 };
 use postgres_backend::AuthType;
 use remote_storage::GenericRemoteStorage;
@@ -216,6 +217,8 @@ fn main() -> anyhow::Result<()> {
     );
     tracing::info!("Initializing page_cache...");
     page_cache::init(conf.page_cache_size);
+    tracing::info!("Initializing materialized_page_cache...");
+    materialized_page_cache::init(conf.materialized_page_cache_max_bytes);
 
     start_pageserver(launch_ts, conf, ignored, otel_guard).context("Failed to start pageserver")?;
 
@@ -474,6 +477,8 @@ fn start_pageserver(
     // Set up remote storage client
     let remote_storage = BACKGROUND_RUNTIME.block_on(create_remote_storage_client(conf))?;
 
+    basebackup_cache::init(conf, remote_storage.clone(), shutdown_pageserver.clone())?;
+
     // Set up deletion queue
     let (deletion_queue, deletion_workers) = DeletionQueue::new(
         remote_storage.clone(),
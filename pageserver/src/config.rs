@@ -14,7 +14,9 @@
 use anyhow::{Context, bail, ensure};
 use camino::{Utf8Path, Utf8PathBuf};
 use once_cell::sync::OnceCell;
-use pageserver_api::config::{DiskUsageEvictionTaskConfig, MaxVectoredReadBytes};
+use pageserver_api::config::{
+    BasebackupCacheConfig, DiskUsageEvictionTaskConfig, MaxVectoredReadBytes, WalRedoNativeMode,
+};
 use pageserver_api::models::ImageCompressionAlgorithm;
 use pageserver_api::shard::TenantShardId;
 use postgres_backend::AuthType;
@@ -77,11 +79,19 @@ pub struct PageServerConf {
     pub wait_lsn_timeout: Duration,
     // How long to wait for WAL redo to complete.
     pub wal_redo_timeout: Duration,
+    /// Upper bound on the number of walredo processes of a tenant shard. Processes are launched
+    /// when concurrent redo requests find all existing ones busy, and shut down once idle.
+    pub wal_redo_max_processes_per_tenant: NonZeroUsize,
+    /// Whether WAL redo applies supported Postgres WAL records in the pageserver, see
+    /// [`crate::walredo`].
+    pub wal_redo_native: WalRedoNativeMode,
 
     pub superuser: String,
     pub locale: String,
 
     pub page_cache_size: usize,
+    /// Memory budget of the [`crate::materialized_page_cache`]. Zero disables the cache.
+    pub materialized_page_cache_max_bytes: usize,
     pub max_file_descriptors: usize,
 
     // Repository directory, relative to current working directory.
@@ -136,10 +146,17 @@ pub struct PageServerConf {
 
     pub disk_usage_based_eviction: Option<DiskUsageEvictionTaskConfig>,
 
+    /// Configuration of the [`crate::basebackup_cache`]. Unset disables the cache.
+    pub basebackup_cache: Option<BasebackupCacheConfig>,
+
     pub test_remote_failures: u64,
 
     pub ondemand_download_behavior_treat_error_as_warn: bool,
 
+    /// Serve reads on evicted image layers from byte ranges fetched from remote storage,
+    /// while the full layer is downloaded in the background.
+    pub ondemand_download_partial_reads: bool,
+
     /// How long will background tasks be delayed at most after initial load of tenants.
     ///
     /// Our largest initialization completions are in the range of 100-200s, so perhaps 10s works
@@ -179,6 +196,10 @@ pub struct PageServerConf {
 
     pub image_compression: ImageCompressionAlgorithm,
 
+    /// Store a crc32c checksum after every blob of newly written image and delta layers, and
+    /// verify it when reading. Layers written without checksums remain readable.
+    pub layer_blob_checksums: bool,
+
     /// Whether to offload archived timelines automatically
     pub timeline_offloading: bool,
 
@@ -240,6 +261,10 @@ pub fn tenants_path(&self) -> Utf8PathBuf {
         self.workdir.join(TENANTS_SEGMENT_NAME)
     }
 
+    pub fn basebackup_cache_dir(&self) -> Utf8PathBuf {
+        self.workdir.join("basebackup_cache")
+    }
+
     pub fn deletion_prefix(&self) -> Utf8PathBuf {
         self.workdir.join("deletion")
     }
@@ -343,9 +368,12 @@ pub fn parse_and_validate(
             availability_zone,
             wait_lsn_timeout,
             wal_redo_timeout,
+            wal_redo_max_processes_per_tenant,
+            wal_redo_native,
             superuser,
             locale,
             page_cache_size,
+            materialized_page_cache_max_bytes,
             max_file_descriptors,
             pg_distrib_dir,
             http_auth_type,
@@ -360,8 +388,10 @@ pub fn parse_and_validate(
             metric_collection_bucket,
             synthetic_size_calculation_interval,
             disk_usage_based_eviction,
+            basebackup_cache,
             test_remote_failures,
             ondemand_download_behavior_treat_error_as_warn,
+            ondemand_download_partial_reads,
             background_task_maximum_delay,
             control_plane_api,
             control_plane_api_token,
@@ -374,6 +404,7 @@ pub fn parse_and_validate(
             ingest_batch_size,
             max_vectored_read_bytes,
             image_compression,
+            layer_blob_checksums,
             timeline_offloading,
             ephemeral_bytes_per_memory_kb,
             l0_flush,
@@ -406,9 +437,12 @@ pub fn parse_and_validate(
             availability_zone,
             wait_lsn_timeout,
             wal_redo_timeout,
+            wal_redo_max_processes_per_tenant,
+            wal_redo_native,
             superuser,
             locale,
             page_cache_size,
+            materialized_page_cache_max_bytes,
             max_file_descriptors,
             http_auth_type,
             pg_auth_type,
@@ -422,8 +456,10 @@ pub fn parse_and_validate(
             metric_collection_bucket,
             synthetic_size_calculation_interval,
             disk_usage_based_eviction,
+            basebackup_cache,
             test_remote_failures,
             ondemand_download_behavior_treat_error_as_warn,
+            ondemand_download_partial_reads,
             background_task_maximum_delay,
             control_plane_api,
             control_plane_emergency_mode,
@@ -432,6 +468,7 @@ pub fn parse_and_validate(
             ingest_batch_size,
             max_vectored_read_bytes,
             image_compression,
+            layer_blob_checksums,
             timeline_offloading,
             ephemeral_bytes_per_memory_kb,
             import_pgdata_upcall_api,
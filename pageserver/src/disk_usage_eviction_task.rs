@@ -36,13 +36,17 @@
 //! during page reconstruction.
 //! An alternative default for all tenants can be specified in the `tenant_config` section of the config.
 //! Lastly, each tenant can have an override in their respective tenant config (`min_resident_size_override`).
+//!
+//! Within the partitions formed by the reservation, the order in which layers are evicted is
+//! selected with [`EvictionOrder`].
 
 // Implementation notes:
 // - The `#[allow(dead_code)]` above various structs are to suppress warnings about only the Debug impl
 //   reading these fields. We use the Debug impl for semi-structured logging, though.
 
+use std::num::NonZeroU64;
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use anyhow::Context;
 use pageserver_api::config::DiskUsageEvictionTaskConfig;
@@ -71,6 +75,13 @@
 /// partitioning.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EvictionOrder {
+    /// Order the layers to be evicted by how recently they have been accessed in absolute
+    /// time.
+    ///
+    /// This strategy is unfair when some tenants grow faster than others towards the slower
+    /// growing.
+    AbsoluteAccessed,
+
     /// Order the layers to be evicted by how recently they have been accessed relatively within
     /// the set of resident layers of a tenant.
     RelativeAccessed {
@@ -82,6 +93,22 @@ pub enum EvictionOrder {
         /// `relative_last_activity==0.0` ties.
         highest_layer_count_loses_first: bool,
     },
+
+    /// Like [`EvictionOrder::RelativeAccessed`] with `highest_layer_count_loses_first`, but the
+    /// relative access time is scaled by the estimated cost per byte of downloading the layer
+    /// again. Downloading many small layers costs more than downloading a few large ones with
+    /// the same total size, so among equally old layers the larger ones are evicted first.
+    CostAware {
+        /// Fixed cost of a single layer download, dominating for small layers.
+        download_latency: Duration,
+        /// Download throughput, dominating for large layers.
+        download_bytes_per_second: NonZeroU64,
+    },
+
+    /// Like [`EvictionOrder::RelativeAccessed`] with `highest_layer_count_loses_first`, but
+    /// within each partition the layers of tenants with a lower `eviction_priority` are evicted
+    /// before any layer of a tenant with a higher priority.
+    TenantPriority,
 }
 
 impl From<pageserver_api::config::EvictionOrder> for EvictionOrder {
@@ -92,6 +119,15 @@ fn from(value: pageserver_api::config::EvictionOrder) -> Self {
             } => Self::RelativeAccessed {
                 highest_layer_count_loses_first,
             },
+            pageserver_api::config::EvictionOrder::AbsoluteAccessed => Self::AbsoluteAccessed,
+            pageserver_api::config::EvictionOrder::CostAware {
+                download_latency,
+                download_bytes_per_second,
+            } => Self::CostAware {
+                download_latency,
+                download_bytes_per_second,
+            },
+            pageserver_api::config::EvictionOrder::TenantPriority => Self::TenantPriority,
         }
     }
 }
@@ -101,9 +137,35 @@ fn sort(&self, candidates: &mut [(EvictionPartition, EvictionCandidate)]) {
         use EvictionOrder::*;
 
         match self {
+            AbsoluteAccessed => candidates.sort_unstable_by_key(|(partition, candidate)| {
+                (*partition, candidate.last_activity_ts)
+            }),
             RelativeAccessed { .. } => candidates.sort_unstable_by_key(|(partition, candidate)| {
                 (*partition, candidate.relative_last_activity)
             }),
+            CostAware {
+                download_latency,
+                download_bytes_per_second,
+            } => candidates.sort_by_cached_key(|(partition, candidate)| {
+                let cost = redownload_cost_per_byte(
+                    candidate.layer.get_file_size(),
+                    *download_latency,
+                    *download_bytes_per_second,
+                );
+                let weighted = candidate.relative_last_activity.into_inner() * cost;
+                (
+                    *partition,
+                    finite_f32::FiniteF32::try_from(weighted)
+                        .unwrap_or(finite_f32::FiniteF32::ZERO),
+                )
+            }),
+            TenantPriority => candidates.sort_unstable_by_key(|(partition, candidate)| {
+                (
+                    *partition,
+                    candidate.eviction_priority,
+                    candidate.relative_last_activity,
+                )
+            }),
         }
     }
 
@@ -113,49 +175,70 @@ fn relative_last_activity(&self, total: usize, index: usize) -> finite_f32::Fini
         use EvictionOrder::*;
 
         match self {
+            AbsoluteAccessed => finite_f32::FiniteF32::ZERO,
             RelativeAccessed {
                 highest_layer_count_loses_first,
-            } => {
-                // keeping the -1 or not decides if every tenant should lose their least recently accessed
-                // layer OR if this should happen in the order of having highest layer count:
-                let fudge = if *highest_layer_count_loses_first {
-                    // relative_last_activity vs. tenant layer count:
-                    // - 0.1..=1.0 (10 layers)
-                    // - 0.01..=1.0 (100 layers)
-                    // - 0.001..=1.0 (1000 layers)
-                    //
-                    // leading to evicting less of the smallest tenants.
-                    0
-                } else {
-                    // use full 0.0..=1.0 range, which means even the smallest tenants could always lose a
-                    // layer. the actual ordering is unspecified: for 10k tenants on a pageserver it could
-                    // be that less than 10k layer evictions is enough, so we would not need to evict from
-                    // all tenants.
-                    //
-                    // as the tenant ordering is now deterministic this could hit the same tenants
-                    // disproportionetly on multiple invocations. alternative could be to remember how many
-                    // layers did we evict last time from this tenant, and inject that as an additional
-                    // fudge here.
-                    1
-                };
+            } => relative_last_activity(*highest_layer_count_loses_first, total, index),
+            CostAware { .. } | TenantPriority => relative_last_activity(true, total, index),
+        }
+    }
+}
 
-                let total = total.checked_sub(fudge).filter(|&x| x > 1).unwrap_or(1);
-                let divider = total as f32;
+fn relative_last_activity(
+    highest_layer_count_loses_first: bool,
+    total: usize,
+    index: usize,
+) -> finite_f32::FiniteF32 {
+    // keeping the -1 or not decides if every tenant should lose their least recently accessed
+    // layer OR if this should happen in the order of having highest layer count:
+    let fudge = if highest_layer_count_loses_first {
+        // relative_last_activity vs. tenant layer count:
+        // - 0.1..=1.0 (10 layers)
+        // - 0.01..=1.0 (100 layers)
+        // - 0.001..=1.0 (1000 layers)
+        //
+        // leading to evicting less of the smallest tenants.
+        0
+    } else {
+        // use full 0.0..=1.0 range, which means even the smallest tenants could always lose a
+        // layer. the actual ordering is unspecified: for 10k tenants on a pageserver it could
+        // be that less than 10k layer evictions is enough, so we would not need to evict from
+        // all tenants.
+        //
+        // as the tenant ordering is now deterministic this could hit the same tenants
+        // disproportionetly on multiple invocations. alternative could be to remember how many
+        // layers did we evict last time from this tenant, and inject that as an additional
+        // fudge here.
+        1
+    };
 
-                // most recently used is always (total - 0) / divider == 1.0
-                // least recently used depends on the fudge:
-                // -       (total - 1) - (total - 1) / total => 0 / total
-                // -             total - (total - 1) / total => 1 / total
-                let distance = (total - index) as f32;
+    let total = total.checked_sub(fudge).filter(|&x| x > 1).unwrap_or(1);
+    let divider = total as f32;
 
-                finite_f32::FiniteF32::try_from_normalized(distance / divider)
-                    .unwrap_or_else(|val| {
-                        tracing::warn!(%fudge, "calculated invalid relative_last_activity for i={index}, total={total}: {val}");
-                        finite_f32::FiniteF32::ZERO
-                    })
-            }
-        }
-    }
+    // most recently used is always (total - 0) / divider == 1.0
+    // least recently used depends on the fudge:
+    // -       (total - 1) - (total - 1) / total => 0 / total
+    // -             total - (total - 1) / total => 1 / total
+    let distance = (total - index) as f32;
+
+    finite_f32::FiniteF32::try_from_normalized(distance / divider)
+        .unwrap_or_else(|val| {
+            tracing::warn!(%fudge, "calculated invalid relative_last_activity for i={index}, total={total}: {val}");
+            finite_f32::FiniteF32::ZERO
+        })
+}
+
+/// Estimated seconds it takes to download a layer of `file_size` again, per byte freed by
+/// evicting it.
+fn redownload_cost_per_byte(
+    file_size: u64,
+    download_latency: Duration,
+    download_bytes_per_second: NonZeroU64,
+) -> f32 {
+    let file_size = file_size.max(1) as f64;
+    let seconds =
+        download_latency.as_secs_f64() + file_size / download_bytes_per_second.get() as f64;
+    (seconds / file_size) as f32
 }
 
 #[derive(Default)]
@@ -621,6 +704,8 @@ pub(crate) struct EvictionCandidate {
     pub(crate) layer: EvictionLayer,
     pub(crate) last_activity_ts: SystemTime,
     pub(crate) relative_last_activity: finite_f32::FiniteF32,
+    /// The owning tenant's `eviction_priority`, filled in while collecting candidates.
+    pub(crate) eviction_priority: u8,
     pub(crate) visibility: LayerVisibilityHint,
 }
 
@@ -876,6 +961,8 @@ async fn collect_eviction_candidates(
             max_layer_size
         };
 
+        let eviction_priority = tenant.get_eviction_priority();
+
         // Sort layers most-recently-used first, then calculate [`EvictionPartition`] for each layer,
         // where the inputs are:
         //  - whether the layer is visible
@@ -895,6 +982,7 @@ async fn collect_eviction_candidates(
                     // be 1.0; this is for us to evict it last.
                     candidate.relative_last_activity =
                         eviction_order.relative_last_activity(total, i);
+                    candidate.eviction_priority = eviction_priority;
 
                     let partition = match candidate.visibility {
                         LayerVisibilityHint::Covered => {
@@ -964,6 +1052,8 @@ async fn collect_eviction_candidates(
 
         let started_at = std::time::Instant::now();
 
+        let eviction_priority = tenant.get_eviction_priority();
+
         layer_info
             .resident_layers
             .sort_unstable_by_key(|layer_info| std::cmp::Reverse(layer_info.last_activity_ts));
@@ -976,6 +1066,7 @@ async fn collect_eviction_candidates(
                 .map(|(i, mut candidate)| {
                     candidate.relative_last_activity =
                         eviction_order.relative_last_activity(total_layers, i);
+                    candidate.eviction_priority = eviction_priority;
                     (
                         // Secondary locations' layers are always considered above the min resident size,
                         // i.e. secondary locations are permitted to be trimmed to zero layers if all
@@ -1304,4 +1395,19 @@ fn relative_spare_bounds() {
         assert_eq!(v.last(), Some(&0.1));
         assert!(v.windows(2).all(|slice| slice[0] > slice[1]));
     }
+    #[test]
+    fn redownload_cost_favors_evicting_large_layers() {
+        let latency = Duration::from_millis(50);
+        let bandwidth = NonZeroU64::new(100 * 1024 * 1024).unwrap();
+
+        let small = redownload_cost_per_byte(8 * 1024, latency, bandwidth);
+        let large = redownload_cost_per_byte(256 * 1024 * 1024, latency, bandwidth);
+        assert!(large < small, "large={large} small={small}");
+
+        // the per byte cost never drops below the throughput bound
+        assert!(large >= 1.0 / bandwidth.get() as f32);
+
+        // empty files must not produce infinities
+        assert!(redownload_cost_per_byte(0, latency, bandwidth).is_finite());
+    }
 }
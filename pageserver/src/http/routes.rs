@@ -33,14 +33,15 @@
     LsnLeaseRequest, OffloadedTimelineInfo, PageTraceEvent, ShardParameters, StatusResponse,
     TenantConfigPatchRequest, TenantConfigRequest, TenantDetails, TenantInfo,
     TenantLocationConfigRequest, TenantLocationConfigResponse, TenantScanRemoteStorageResponse,
-    TenantScanRemoteStorageShard, TenantShardLocation, TenantShardSplitRequest,
-    TenantShardSplitResponse, TenantSorting, TenantState, TenantWaitLsnRequest,
-    TimelineArchivalConfigRequest, TimelineCreateRequest, TimelineCreateRequestMode,
-    TimelineCreateRequestModeImportPgdata, TimelineGcRequest, TimelineInfo,
-    TimelinePatchIndexPartRequest, TimelineVisibilityState, TimelinesInfoAndOffloaded,
+    TenantScanRemoteStorageShard, TenantShardLocation, TenantShardMergeRequest,
+    TenantShardMergeResponse, TenantShardSplitRequest, TenantShardSplitResponse, TenantSorting,
+    TenantState, TenantWaitLsnRequest, TimelineArchivalConfigRequest, TimelineCreateRequest,
+    TimelineCreateRequestMode, TimelineCreateRequestModeImportPgdata, TimelineExportPgdataRequest,
+    TimelineGcRequest, TimelineInfo, TimelinePatchIndexPartRequest, TimelineResetToLsnRequest,
+    TimelineRetentionPolicyRequest, TimelineVisibilityState, TimelinesInfoAndOffloaded,
     TopTenantShardItem, TopTenantShardsRequest, TopTenantShardsResponse,
 };
-use pageserver_api::shard::{ShardCount, TenantShardId};
+use pageserver_api::shard::{ShardCount, ShardNumber, TenantShardId};
 use remote_storage::{DownloadError, GenericRemoteStorage, TimeTravelError};
 use scopeguard::defer;
 use tenant_size_model::svg::SvgBranchKind;
@@ -170,6 +171,7 @@ fn from(pre: PageReconstructError) -> ApiError {
             PageReconstructError::Cancelled => ApiError::Cancelled,
             PageReconstructError::AncestorLsnTimeout(e) => ApiError::Timeout(format!("{e}").into()),
             PageReconstructError::WalRedo(pre) => ApiError::InternalServerError(pre),
+            PageReconstructError::Corruption(pre) => ApiError::InternalServerError(pre),
         }
     }
 }
@@ -305,6 +307,12 @@ fn from(value: crate::tenant::DeleteTimelineError) -> Self {
                 format!("Cannot delete timeline which has child timelines: {children:?}")
                     .into_boxed_str(),
             ),
+            HasCrossTenantChildren(children) => ApiError::PreconditionFailed(
+                format!(
+                    "Cannot delete timeline which has child timelines in other tenants: {children:?}"
+                )
+                .into_boxed_str(),
+            ),
             a @ AlreadyInProgress(_) => ApiError::Conflict(a.to_string()),
             Cancelled => ApiError::ResourceUnavailable("shutting down".into()),
             Other(e) => ApiError::InternalServerError(e),
@@ -354,6 +362,9 @@ fn from(value: crate::tenant::mgr::DeleteTenantError) -> Self {
         use crate::tenant::mgr::DeleteTenantError::*;
         match value {
             SlotError(e) => e.into(),
+            e @ HasCrossTenantChildren(_) => {
+                ApiError::PreconditionFailed(e.to_string().into_boxed_str())
+            }
             Other(o) => ApiError::InternalServerError(o),
             Cancelled => ApiError::ShuttingDown,
         }
@@ -486,6 +497,8 @@ async fn build_timeline_info_common(
         is_archived: Some(is_archived),
         rel_size_migration: Some(timeline.get_rel_size_v2_status()),
         is_invisible: Some(is_invisible),
+        retention_policy: timeline.get_retention_policy(),
+        restore_points: timeline.remote_client.restore_points(),
 
         walreceiver_status,
     };
@@ -578,6 +591,30 @@ async fn timeline_create_handler(
             ancestor_timeline_id,
             ancestor_start_lsn,
         }),
+        TimelineCreateRequestMode::CrossTenantBranch {
+            ancestor_tenant_id,
+            ancestor_timeline_id,
+            ancestor_start_lsn,
+        } => {
+            check_permission(&request, Some(ancestor_tenant_id))?;
+            let ancestor_tenant = get_state(&request)
+                .tenant_manager
+                .get_attached_tenant_shard(TenantShardId {
+                    tenant_id: ancestor_tenant_id,
+                    shard_number: tenant_shard_id.shard_number,
+                    shard_count: tenant_shard_id.shard_count,
+                })?;
+            let ancestor_timeline = ancestor_tenant
+                .get_timeline(ancestor_timeline_id, false)
+                .map_err(ApiError::from)?;
+            tenant::CreateTimelineParams::CrossTenantBranch(
+                tenant::CreateTimelineParamsCrossTenantBranch {
+                    new_timeline_id,
+                    ancestor_timeline,
+                    ancestor_start_lsn,
+                },
+            )
+        }
         TimelineCreateRequestMode::ImportPgdata {
             import_pgdata:
                 TimelineCreateRequestModeImportPgdata {
@@ -589,23 +626,7 @@ async fn timeline_create_handler(
                 idempotency_key.0,
             ),
             new_timeline_id,
-            location: {
-                use import_pgdata::index_part_format::Location;
-                use pageserver_api::models::ImportPgdataLocation;
-                match location {
-                    #[cfg(feature = "testing")]
-                    ImportPgdataLocation::LocalFs { path } => Location::LocalFs { path },
-                    ImportPgdataLocation::AwsS3 {
-                        region,
-                        bucket,
-                        key,
-                    } => Location::AwsS3 {
-                        region,
-                        bucket,
-                        key,
-                    },
-                }
-            },
+            location: location.into(),
         }),
     };
 
@@ -862,6 +883,55 @@ async fn timeline_archival_config_handler(
     json_response(StatusCode::OK, ())
 }
 
+async fn timeline_retention_policy_handler(
+    mut request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    use crate::tenant::remote_timeline_client::WaitCompletionError;
+    use crate::tenant::upload_queue::NotInitialized;
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    let timeline_id: TimelineId = parse_request_param(&request, "timeline_id")?;
+
+    let request_data: TimelineRetentionPolicyRequest = json_request(&mut request).await?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+    let state = get_state(&request);
+
+    if let Some(retention_policy) = &request_data.retention_policy {
+        retention_policy
+            .validate()
+            .map_err(|e| ApiError::BadRequest(anyhow!("invalid retention policy: {e}")))?;
+    }
+
+    async {
+        let tenant = state
+            .tenant_manager
+            .get_attached_tenant_shard(tenant_shard_id)?;
+
+        tenant.wait_to_become_active(ACTIVE_TENANT_TIMEOUT).await?;
+
+        let timeline = tenant.get_timeline(timeline_id, true)?;
+
+        timeline
+            .set_retention_policy(request_data.retention_policy.clone())
+            .await
+            .map_err(|e| {
+                if e.is::<NotInitialized>() || e.is::<WaitCompletionError>() {
+                    ApiError::ShuttingDown
+                } else {
+                    ApiError::InternalServerError(e)
+                }
+            })
+    }
+    .instrument(info_span!("timeline_retention_policy",
+                tenant_id = %tenant_shard_id.tenant_id,
+                shard_id = %tenant_shard_id.shard_slug(),
+                retention_policy = ?request_data.retention_policy,
+                %timeline_id))
+    .await?;
+
+    json_response(StatusCode::OK, ())
+}
+
 /// This API is used to patch the index part of a timeline. You must ensure such patches are safe to apply. Use this API as an emergency
 /// measure only.
 ///
@@ -1122,9 +1192,19 @@ async fn timeline_delete_handler(
             }
         })?;
     tenant.wait_to_become_active(ACTIVE_TENANT_TIMEOUT).await?;
+    let cross_tenant_ancestor = tenant
+        .get_timeline(timeline_id, false)
+        .ok()
+        .and_then(|timeline| timeline.remote_client.cross_tenant_ancestor());
     tenant.delete_timeline(timeline_id).instrument(info_span!("timeline_delete", tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(), %timeline_id))
         .await?;
 
+    if let Some(ancestor) = cross_tenant_ancestor {
+        state
+            .tenant_manager
+            .forget_cross_tenant_child(tenant_shard_id, timeline_id, ancestor);
+    }
+
     json_response(StatusCode::ACCEPTED, ())
 }
 
@@ -1382,6 +1462,40 @@ async fn tenant_shard_split_handler(
     json_response(StatusCode::OK, TenantShardSplitResponse { new_shards })
 }
 
+async fn tenant_shard_merge_handler(
+    mut request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let req: TenantShardMergeRequest = json_request(&mut request).await?;
+
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    let state = get_state(&request);
+    let ctx = RequestContext::new(TaskKind::MgmtRequest, DownloadBehavior::Warn);
+
+    let tenant = state
+        .tenant_manager
+        .get_attached_tenant_shard(tenant_shard_id)?;
+    tenant.wait_to_become_active(ACTIVE_TENANT_TIMEOUT).await?;
+
+    let new_shard = state
+        .tenant_manager
+        .shard_merge(
+            tenant,
+            ShardCount::new(req.new_shard_count),
+            req.generation.map(Generation::new),
+            &ctx,
+        )
+        .await
+        .map_err(ApiError::InternalServerError)?;
+
+    json_response(
+        StatusCode::OK,
+        TenantShardMergeResponse {
+            new_shards: vec![new_shard],
+        },
+    )
+}
+
 async fn layer_map_info_handler(
     request: Request<Body>,
     _cancel: CancellationToken,
@@ -1459,6 +1573,9 @@ async fn timeline_layer_scan_disposable_keys(
             | tenant::storage_layer::layer::DownloadError::PreStatFailed(_) => {
                 ApiError::InternalServerError(err.into())
             }
+            tenant::storage_layer::layer::DownloadError::ResidentSizeQuotaExceeded => {
+                ApiError::ResourceUnavailable(err.to_string().into())
+            }
             #[cfg(test)]
             tenant::storage_layer::layer::DownloadError::Failpoint(_) => {
                 ApiError::InternalServerError(err.into())
@@ -1580,6 +1697,9 @@ async fn layer_download_handler(
             | tenant::storage_layer::layer::DownloadError::DownloadCancelled => {
                 ApiError::ShuttingDown
             }
+            e @ tenant::storage_layer::layer::DownloadError::ResidentSizeQuotaExceeded => {
+                ApiError::ResourceUnavailable(e.to_string().into())
+            }
             other => ApiError::InternalServerError(other.into()),
         })?;
 
@@ -1845,6 +1965,36 @@ async fn get_tenant_config_handler(
     json_response(StatusCode::OK, response)
 }
 
+async fn get_tenant_disk_quota_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+    let state = get_state(&request);
+
+    let tenant = state
+        .tenant_manager
+        .get_attached_tenant_shard(tenant_shard_id)?;
+
+    json_response(StatusCode::OK, tenant.get_disk_quota_status())
+}
+
+async fn get_tenant_scrub_status_handler(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+    let state = get_state(&request);
+
+    let tenant = state
+        .tenant_manager
+        .get_attached_tenant_shard(tenant_shard_id)?;
+
+    json_response(StatusCode::OK, tenant.get_scrub_status())
+}
+
 async fn update_tenant_config_handler(
     mut request: Request<Body>,
     _cancel: CancellationToken,
@@ -2531,6 +2681,98 @@ async fn timeline_download_remote_layers_handler_get(
     json_response(StatusCode::OK, info)
 }
 
+async fn timeline_export_pgdata_handler_post(
+    mut request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    let timeline_id: TimelineId = parse_request_param(&request, "timeline_id")?;
+    let body: TimelineExportPgdataRequest = json_request(&mut request).await?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+
+    // Shard zero exports the whole tenant, and must be told where each other shard is.
+    if tenant_shard_id.shard_number != ShardNumber(0) {
+        return Err(ApiError::BadRequest(anyhow!(
+            "sharded tenants are exported by shard zero"
+        )));
+    }
+    let mut shard_numbers = body
+        .shards
+        .iter()
+        .map(|shard| shard.shard_number)
+        .collect::<Vec<_>>();
+    shard_numbers.sort();
+    if !shard_numbers
+        .into_iter()
+        .eq((1..tenant_shard_id.shard_count.count()).map(ShardNumber))
+    {
+        return Err(ApiError::BadRequest(anyhow!(
+            "the locations of shards {:?} do not match a tenant with {} shards",
+            body.shards,
+            tenant_shard_id.shard_count.count(),
+        )));
+    }
+
+    let state = get_state(&request);
+
+    let timeline =
+        active_timeline_of_active_tenant(&state.tenant_manager, tenant_shard_id, timeline_id)
+            .await?;
+    let ctx = RequestContext::new(TaskKind::MgmtRequest, DownloadBehavior::Download)
+        .with_scope_timeline(&timeline);
+
+    let lsn = match body.lsn {
+        Some(lsn) => {
+            timeline
+                .wait_lsn(
+                    lsn,
+                    WaitLsnWaiter::HttpEndpoint,
+                    WaitLsnTimeout::Default,
+                    &ctx,
+                )
+                .await
+                .map_err(|e| ApiError::PreconditionFailed(format!("{e}").into_boxed_str()))?;
+            lsn
+        }
+        None => timeline.get_last_record_lsn(),
+    };
+
+    // Validates the LSN against the GC cutoff, and protects it until the export task takes over.
+    timeline
+        .init_lsn_lease(lsn, timeline.get_lsn_lease_length(), &ctx)
+        .map_err(ApiError::BadRequest)?;
+
+    match timeline.spawn_export_pgdata(
+        lsn,
+        body.location.into(),
+        body.shards,
+        body.auth_token,
+        &ctx,
+    ) {
+        Ok(st) => json_response(StatusCode::ACCEPTED, st),
+        Err(st) => json_response(StatusCode::CONFLICT, st),
+    }
+}
+
+async fn timeline_export_pgdata_handler_get(
+    request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+    let timeline_id: TimelineId = parse_request_param(&request, "timeline_id")?;
+    let state = get_state(&request);
+
+    let timeline =
+        active_timeline_of_active_tenant(&state.tenant_manager, tenant_shard_id, timeline_id)
+            .await?;
+    let info = timeline
+        .get_export_pgdata_task_info()
+        .context("task never started since last pageserver process start")
+        .map_err(|e| ApiError::NotFound(e.into()))?;
+    json_response(StatusCode::OK, info)
+}
+
 async fn timeline_detach_ancestor_handler(
     request: Request<Body>,
     _cancel: CancellationToken,
@@ -2628,6 +2870,62 @@ async fn timeline_detach_ancestor_handler(
     .await
 }
 
+async fn timeline_reset_to_lsn_handler(
+    mut request: Request<Body>,
+    _cancel: CancellationToken,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_shard_id: TenantShardId = parse_request_param(&request, "tenant_shard_id")?;
+    let timeline_id: TimelineId = parse_request_param(&request, "timeline_id")?;
+    let body: TimelineResetToLsnRequest = json_request(&mut request).await?;
+    check_permission(&request, Some(tenant_shard_id.tenant_id))?;
+
+    let span = tracing::info_span!("reset_to_lsn", tenant_id=%tenant_shard_id.tenant_id, shard_id=%tenant_shard_id.shard_slug(), %timeline_id, lsn=%body.lsn);
+
+    async move {
+        let state = get_state(&request);
+
+        let tenant = state
+            .tenant_manager
+            .get_attached_tenant_shard(tenant_shard_id)?;
+        tenant.wait_to_become_active(ACTIVE_TENANT_TIMEOUT).await?;
+
+        if body.dry_run {
+            let timeline = tenant.get_timeline(timeline_id, true)?;
+            timeline.check_reset_to_lsn(&tenant, body.lsn)?;
+            return json_response(StatusCode::OK, ());
+        }
+        drop(tenant);
+
+        let Some(generation) = body.generation else {
+            return Err(ApiError::BadRequest(anyhow!(
+                "a generation is required to reset a timeline"
+            )));
+        };
+
+        let ctx = RequestContext::new(TaskKind::MgmtRequest, DownloadBehavior::Download);
+        state
+            .tenant_manager
+            .reset_timeline_to_lsn(
+                tenant_shard_id,
+                timeline_id,
+                body.lsn,
+                Generation::new(generation),
+                &ctx,
+            )
+            .await?;
+
+        // Return once the timeline can be used again.
+        let tenant = state
+            .tenant_manager
+            .get_attached_tenant_shard(tenant_shard_id)?;
+        tenant.wait_to_become_active(ACTIVE_TENANT_TIMEOUT).await?;
+
+        json_response(StatusCode::OK, ())
+    }
+    .instrument(span)
+    .await
+}
+
 async fn deletion_queue_flush(
     r: Request<Body>,
     cancel: CancellationToken,
@@ -3712,9 +4010,18 @@ pub fn make_router(
         .put("/v1/tenant/:tenant_shard_id/shard_split", |r| {
             api_handler(r, tenant_shard_split_handler)
         })
+        .put("/v1/tenant/:tenant_shard_id/shard_merge", |r| {
+            api_handler(r, tenant_shard_merge_handler)
+        })
         .get("/v1/tenant/:tenant_shard_id/config", |r| {
             api_handler(r, get_tenant_config_handler)
         })
+        .get("/v1/tenant/:tenant_shard_id/disk_quota", |r| {
+            api_handler(r, get_tenant_disk_quota_handler)
+        })
+        .get("/v1/tenant/:tenant_shard_id/scrub", |r| {
+            api_handler(r, get_tenant_scrub_status_handler)
+        })
         .put("/v1/tenant/:tenant_shard_id/location_config", |r| {
             api_handler(r, put_tenant_location_config_handler)
         })
@@ -3748,6 +4055,10 @@ pub fn make_router(
             "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/archival_config",
             |r| api_handler(r, timeline_archival_config_handler),
         )
+        .put(
+            "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/retention_policy",
+            |r| api_handler(r, timeline_retention_policy_handler),
+        )
         .get("/v1/tenant/:tenant_shard_id/timeline/:timeline_id", |r| {
             api_handler(r, timeline_detail_handler)
         })
@@ -3803,10 +4114,22 @@ pub fn make_router(
             "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/download_remote_layers",
             |r| api_handler(r, timeline_download_remote_layers_handler_get),
         )
+        .post(
+            "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/export_pgdata",
+            |r| api_handler(r, timeline_export_pgdata_handler_post),
+        )
+        .get(
+            "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/export_pgdata",
+            |r| api_handler(r, timeline_export_pgdata_handler_get),
+        )
         .put(
             "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/detach_ancestor",
             |r| api_handler(r, timeline_detach_ancestor_handler),
         )
+        .put(
+            "/v1/tenant/:tenant_shard_id/timeline/:timeline_id/reset_to_lsn",
+            |r| api_handler(r, timeline_reset_to_lsn_handler),
+        )
         .delete("/v1/tenant/:tenant_shard_id/timeline/:timeline_id", |r| {
             api_handler(r, timeline_delete_handler)
         })
@@ -0,0 +1,426 @@
+//!
+//! Cache of reconstructed page images
+//!
+//! [`crate::page_cache::PageCache`] only holds immutable file blocks, so a page that
+//! needs WAL redo is rebuilt through walredo every time it is read, even if it is read
+//! repeatedly at the same LSN. The materialized page cache remembers walredo output,
+//! keyed by the timeline, the [`Key`] and the LSN the page was reconstructed at.
+//!
+//! A cached image is used in two ways by [`crate::tenant::Timeline::get_vectored`]:
+//! * If the request LSN matches a cached entry exactly, the image is returned without
+//!   visiting any layers.
+//! * Otherwise, the newest cached image below the request LSN becomes the base image
+//!   for reconstruction: the read path stops collecting WAL records once it goes below
+//!   the cached LSN, so only the newer records are replayed.
+//!
+//! # Coherency
+//!
+//! The image of a key at a given LSN never changes once all WAL up to that LSN has
+//! been ingested. Hence the timeline only inserts images for LSNs at or below its
+//! `last_record_lsn`, and ingest only ever adds values above it, so ingest doesn't
+//! need to invalidate anything.
+//!
+//! Entries are scoped by a [`TimelineCacheId`] that is unique to each `Timeline`
+//! object, so a timeline that is shut down and re-attached, e.g. after being reset to
+//! an earlier LSN, never sees stale entries. The timeline drops its entries on
+//! shutdown to give the memory back.
+//!
+//! # Memory budget
+//!
+//! The cache is sized by `materialized_page_cache_max_bytes` in the pageserver config.
+//! Every entry is charged its image size plus a fixed bookkeeping overhead, and the
+//! least recently used entries are evicted to stay within the budget. A budget of
+//! zero disables the cache.
+//!
+//! # Sharding
+//!
+//! Lookups happen on the getpage path of all tenants, so the cache is split into
+//! shards by a hash of the timeline and key, each with its own lock, LRU order and an
+//! equal part of the budget. All images of a key are in the same shard, so a lookup
+//! below an LSN sees all of them. Small budgets get fewer shards, so that each shard
+//! still holds a useful number of images.
+//!
+
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bytes::Bytes;
+use once_cell::sync::OnceCell;
+use pageserver_api::key::Key;
+use utils::lsn::Lsn;
+
+use crate::metrics::MATERIALIZED_PAGE_CACHE;
+
+static MATERIALIZED_PAGE_CACHE_INSTANCE: OnceCell<MaterializedPageCache> = OnceCell::new();
+
+/// Approximate per-entry bookkeeping cost charged against the budget, on top of the image.
+const ENTRY_OVERHEAD: usize = 128;
+
+/// Upper bound on the number of shards, see module-level comment.
+const MAX_SHARDS: usize = 64;
+
+/// Smallest budget a shard gets, see module-level comment.
+const MIN_SHARD_BYTES: usize = 16 * 1024 * 1024;
+
+///
+/// Initialize the materialized page cache. This must be called once at page server startup.
+///
+pub fn init(max_bytes: usize) {
+    if MATERIALIZED_PAGE_CACHE_INSTANCE
+        .set(MaterializedPageCache::new(max_bytes))
+        .is_err()
+    {
+        panic!("materialized page cache already initialized");
+    }
+}
+
+///
+/// Get a handle to the materialized page cache.
+///
+pub fn get() -> &'static MaterializedPageCache {
+    //
+    // Unit tests and tools like pagectl don't go through page server startup, and
+    // no one calls materialized_page_cache::init(). They get a disabled cache, so
+    // that they exercise the regular read path.
+    //
+    MATERIALIZED_PAGE_CACHE_INSTANCE.get_or_init(|| MaterializedPageCache::new(0))
+}
+
+/// See module-level comment.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimelineCacheId(u64);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// See module-level comment.
+pub fn next_timeline_cache_id() -> TimelineCacheId {
+    TimelineCacheId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct CacheKey {
+    timeline: TimelineCacheId,
+    key: Key,
+    lsn: Lsn,
+}
+
+struct CachedPage {
+    img: Bytes,
+    /// Position in [`Inner::lru`].
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    pages: BTreeMap<CacheKey, CachedPage>,
+    /// Recency order of the entries in `pages`, oldest first.
+    lru: BTreeMap<u64, CacheKey>,
+    next_tick: u64,
+    size_bytes: usize,
+}
+
+pub struct MaterializedPageCache {
+    max_bytes: usize,
+    /// Budget of each of the `shards`.
+    shard_max_bytes: usize,
+    shards: Vec<Mutex<Inner>>,
+    hasher: RandomState,
+}
+
+impl MaterializedPageCache {
+    fn new(max_bytes: usize) -> Self {
+        MATERIALIZED_PAGE_CACHE.max_bytes.set(max_bytes as u64);
+        let num_shards = (max_bytes / MIN_SHARD_BYTES).clamp(1, MAX_SHARDS);
+        Self {
+            max_bytes,
+            shard_max_bytes: max_bytes / num_shards,
+            shards: (0..num_shards).map(|_| Mutex::default()).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes > 0
+    }
+
+    fn shard(&self, timeline: TimelineCacheId, key: Key) -> &Mutex<Inner> {
+        let hash = self.hasher.hash_one((timeline, key));
+        &self.shards[hash as usize % self.shards.len()]
+    }
+
+    /// Find the newest cached image of `key` at or below `lsn`.
+    ///
+    /// Returns the LSN the image was reconstructed at together with the image.
+    pub fn lookup(&self, timeline: TimelineCacheId, key: Key, lsn: Lsn) -> Option<(Lsn, Bytes)> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let mut inner = self.shard(timeline, key).lock().unwrap();
+        let inner = &mut *inner;
+        let start = CacheKey {
+            timeline,
+            key,
+            lsn: Lsn(0),
+        };
+        let end = CacheKey { timeline, key, lsn };
+        let found = inner
+            .pages
+            .range_mut(start..=end)
+            .next_back()
+            .map(|(cache_key, page)| {
+                inner.lru.remove(&page.last_used);
+                page.last_used = inner.next_tick;
+                inner.lru.insert(inner.next_tick, *cache_key);
+                inner.next_tick += 1;
+                (cache_key.lsn, page.img.clone())
+            });
+
+        match &found {
+            Some((found_lsn, _)) if *found_lsn == lsn => MATERIALIZED_PAGE_CACHE.hits_exact.inc(),
+            Some(_) => MATERIALIZED_PAGE_CACHE.hits_base.inc(),
+            None => MATERIALIZED_PAGE_CACHE.misses.inc(),
+        }
+        found
+    }
+
+    /// Remember the image of `key` reconstructed at `lsn`.
+    ///
+    /// The caller must ensure that all WAL up to `lsn` has been ingested, see the
+    /// module-level comment.
+    pub fn insert(&self, timeline: TimelineCacheId, key: Key, lsn: Lsn, img: Bytes) {
+        let charge = img.len() + ENTRY_OVERHEAD;
+        if !self.is_enabled() || charge > self.shard_max_bytes {
+            return;
+        }
+
+        let mut inner = self.shard(timeline, key).lock().unwrap();
+        let size_before = inner.size_bytes;
+        let cache_key = CacheKey { timeline, key, lsn };
+        inner.remove(&cache_key);
+
+        while inner.size_bytes + charge > self.shard_max_bytes {
+            let Some((_, victim)) = inner.lru.pop_first() else {
+                break;
+            };
+            let page = inner
+                .pages
+                .remove(&victim)
+                .expect("lru and pages are in sync");
+            inner.size_bytes -= page.img.len() + ENTRY_OVERHEAD;
+            MATERIALIZED_PAGE_CACHE.evictions.inc();
+        }
+
+        let tick = inner.next_tick;
+        inner.next_tick += 1;
+        inner.lru.insert(tick, cache_key);
+        inner.pages.insert(
+            cache_key,
+            CachedPage {
+                img,
+                last_used: tick,
+            },
+        );
+        inner.size_bytes += charge;
+        MATERIALIZED_PAGE_CACHE.inserts.inc();
+        update_current_bytes(size_before, inner.size_bytes);
+    }
+
+    /// Drop all cached images of a timeline.
+    pub fn invalidate_timeline(&self, timeline: TimelineCacheId) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let start = CacheKey {
+            timeline,
+            key: Key::MIN,
+            lsn: Lsn(0),
+        };
+        let end = CacheKey {
+            timeline,
+            key: Key::MAX,
+            lsn: Lsn::MAX,
+        };
+        for shard in &self.shards {
+            let mut inner = shard.lock().unwrap();
+            let size_before = inner.size_bytes;
+            let stale = inner
+                .pages
+                .range(start..=end)
+                .map(|(cache_key, _)| *cache_key)
+                .collect::<Vec<_>>();
+            for cache_key in stale {
+                inner.remove(&cache_key);
+            }
+            update_current_bytes(size_before, inner.size_bytes);
+        }
+    }
+
+    #[cfg(test)]
+    fn size_bytes(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().size_bytes)
+            .sum()
+    }
+}
+
+/// Shards change their size concurrently, so the metric is updated by the difference.
+fn update_current_bytes(before: usize, after: usize) {
+    if after >= before {
+        MATERIALIZED_PAGE_CACHE
+            .current_bytes
+            .add((after - before) as u64);
+    } else {
+        MATERIALIZED_PAGE_CACHE
+            .current_bytes
+            .sub((before - after) as u64);
+    }
+}
+
+impl Inner {
+    fn remove(&mut self, cache_key: &CacheKey) {
+        if let Some(page) = self.pages.remove(cache_key) {
+            self.lru.remove(&page.last_used);
+            self.size_bytes -= page.img.len() + ENTRY_OVERHEAD;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(blkno: u32) -> Key {
+        Key {
+            field1: 0,
+            field2: 1663,
+            field3: 5,
+            field4: 1000,
+            field5: 0,
+            field6: blkno,
+        }
+    }
+
+    fn img(b: u8) -> Bytes {
+        Bytes::from(vec![b; 8192])
+    }
+
+    const ENTRY: usize = 8192 + ENTRY_OVERHEAD;
+
+    #[test]
+    fn lookup_returns_newest_image_at_or_below_lsn() {
+        let cache = MaterializedPageCache::new(10 * ENTRY);
+        let tl = next_timeline_cache_id();
+
+        cache.insert(tl, key(1), Lsn(0x10), img(1));
+        cache.insert(tl, key(1), Lsn(0x20), img(2));
+        cache.insert(tl, key(2), Lsn(0x18), img(3));
+
+        assert_eq!(cache.lookup(tl, key(1), Lsn(0x8)), None);
+        assert_eq!(
+            cache.lookup(tl, key(1), Lsn(0x10)),
+            Some((Lsn(0x10), img(1)))
+        );
+        assert_eq!(
+            cache.lookup(tl, key(1), Lsn(0x1f)),
+            Some((Lsn(0x10), img(1)))
+        );
+        assert_eq!(
+            cache.lookup(tl, key(1), Lsn(0x30)),
+            Some((Lsn(0x20), img(2)))
+        );
+        assert_eq!(
+            cache.lookup(tl, key(2), Lsn(0x30)),
+            Some((Lsn(0x18), img(3)))
+        );
+        assert_eq!(cache.lookup(tl, key(3), Lsn(0x30)), None);
+
+        // Entries are scoped by timeline.
+        let other = next_timeline_cache_id();
+        assert_eq!(cache.lookup(other, key(1), Lsn(0x30)), None);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_within_budget() {
+        let cache = MaterializedPageCache::new(2 * ENTRY);
+        let tl = next_timeline_cache_id();
+
+        cache.insert(tl, key(1), Lsn(0x10), img(1));
+        cache.insert(tl, key(2), Lsn(0x10), img(2));
+        // Touch key 1 so that key 2 becomes the eviction victim.
+        assert!(cache.lookup(tl, key(1), Lsn(0x10)).is_some());
+        cache.insert(tl, key(3), Lsn(0x10), img(3));
+
+        assert!(cache.lookup(tl, key(1), Lsn(0x10)).is_some());
+        assert_eq!(cache.lookup(tl, key(2), Lsn(0x10)), None);
+        assert!(cache.lookup(tl, key(3), Lsn(0x10)).is_some());
+        assert_eq!(cache.size_bytes(), 2 * ENTRY);
+
+        // Re-inserting the same entry does not double-charge.
+        cache.insert(tl, key(3), Lsn(0x10), img(4));
+        assert_eq!(
+            cache.lookup(tl, key(3), Lsn(0x10)),
+            Some((Lsn(0x10), img(4)))
+        );
+        assert_eq!(cache.size_bytes(), 2 * ENTRY);
+    }
+
+    #[test]
+    fn invalidate_timeline_only_drops_its_own_entries() {
+        let cache = MaterializedPageCache::new(10 * ENTRY);
+        let tl1 = next_timeline_cache_id();
+        let tl2 = next_timeline_cache_id();
+
+        cache.insert(tl1, key(1), Lsn(0x10), img(1));
+        cache.insert(tl2, key(1), Lsn(0x10), img(2));
+
+        cache.invalidate_timeline(tl1);
+
+        assert_eq!(cache.lookup(tl1, key(1), Lsn(0x10)), None);
+        assert_eq!(
+            cache.lookup(tl2, key(1), Lsn(0x10)),
+            Some((Lsn(0x10), img(2)))
+        );
+        assert_eq!(cache.size_bytes(), ENTRY);
+    }
+
+    #[test]
+    fn sharded_cache_finds_images_below_lsn() {
+        let cache = MaterializedPageCache::new(4 * MIN_SHARD_BYTES);
+        assert_eq!(cache.shards.len(), 4);
+        let tl = next_timeline_cache_id();
+
+        for blkno in 0..64 {
+            cache.insert(tl, key(blkno), Lsn(0x10), img(1));
+            cache.insert(tl, key(blkno), Lsn(0x20), img(2));
+        }
+        for blkno in 0..64 {
+            assert_eq!(
+                cache.lookup(tl, key(blkno), Lsn(0x1f)),
+                Some((Lsn(0x10), img(1)))
+            );
+            assert_eq!(
+                cache.lookup(tl, key(blkno), Lsn(0x30)),
+                Some((Lsn(0x20), img(2)))
+            );
+        }
+        assert_eq!(cache.size_bytes(), 128 * ENTRY);
+
+        cache.invalidate_timeline(tl);
+        assert_eq!(cache.size_bytes(), 0);
+    }
+
+    #[test]
+    fn disabled_cache_stores_nothing() {
+        let cache = MaterializedPageCache::new(0);
+        let tl = next_timeline_cache_id();
+
+        cache.insert(tl, key(1), Lsn(0x10), img(1));
+        assert_eq!(cache.lookup(tl, key(1), Lsn(0x10)), None);
+        assert_eq!(cache.size_bytes(), 0);
+    }
+}
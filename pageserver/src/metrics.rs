@@ -33,6 +33,7 @@
 use crate::pgdatadir_mapping::DatadirModificationStats;
 use crate::task_mgr::TaskKind;
 use crate::tenant::Timeline;
+use crate::tenant::disk_quota::DiskQuota;
 use crate::tenant::layer_map::LayerMap;
 use crate::tenant::mgr::TenantSlot;
 use crate::tenant::storage_layer::{InMemoryLayer, PersistentLayerDesc};
@@ -460,6 +461,99 @@ pub(crate) fn page_cache_errors_inc(error_kind: PageCacheErrorKind) {
         .inc();
 }
 
+pub(crate) struct MaterializedPageCacheMetrics {
+    pub max_bytes: UIntGauge,
+    pub current_bytes: UIntGauge,
+    pub hits_exact: IntCounter,
+    pub hits_base: IntCounter,
+    pub misses: IntCounter,
+    pub inserts: IntCounter,
+    pub evictions: IntCounter,
+}
+
+static MATERIALIZED_PAGE_CACHE_LOOKUPS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pageserver_materialized_page_cache_lookups_total",
+        "Number of lookups in the materialized page cache, by outcome. \
+         An exact hit skips reconstruction, a base hit shortens it.",
+        &["outcome"]
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static MATERIALIZED_PAGE_CACHE: Lazy<MaterializedPageCacheMetrics> =
+    Lazy::new(|| MaterializedPageCacheMetrics {
+        max_bytes: register_uint_gauge!(
+            "pageserver_materialized_page_cache_max_bytes",
+            "Memory budget of the materialized page cache in bytes"
+        )
+        .expect("failed to define a metric"),
+        current_bytes: register_uint_gauge!(
+            "pageserver_materialized_page_cache_current_bytes",
+            "Memory charged to the materialized page cache in bytes"
+        )
+        .expect("failed to define a metric"),
+        hits_exact: MATERIALIZED_PAGE_CACHE_LOOKUPS.with_label_values(&["hit_exact"]),
+        hits_base: MATERIALIZED_PAGE_CACHE_LOOKUPS.with_label_values(&["hit_base"]),
+        misses: MATERIALIZED_PAGE_CACHE_LOOKUPS.with_label_values(&["miss"]),
+        inserts: register_int_counter!(
+            "pageserver_materialized_page_cache_inserts_total",
+            "Number of reconstructed page images inserted into the materialized page cache"
+        )
+        .expect("failed to define a metric"),
+        evictions: register_int_counter!(
+            "pageserver_materialized_page_cache_evictions_total",
+            "Number of images evicted from the materialized page cache to stay within its budget"
+        )
+        .expect("failed to define a metric"),
+    });
+
+pub(crate) struct BasebackupCacheMetrics {
+    pub current_bytes: UIntGauge,
+    pub hits_local: IntCounter,
+    pub hits_remote: IntCounter,
+    pub misses: IntCounter,
+    pub prepared: IntCounter,
+    pub prepare_errors: IntCounter,
+    pub evictions: IntCounter,
+}
+
+static BASEBACKUP_CACHE_LOOKUPS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "pageserver_basebackup_cache_lookups_total",
+        "Number of basebackup requests looked up in the basebackup cache, by outcome",
+        &["outcome"]
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static BASEBACKUP_CACHE: Lazy<BasebackupCacheMetrics> =
+    Lazy::new(|| BasebackupCacheMetrics {
+        current_bytes: register_uint_gauge!(
+            "pageserver_basebackup_cache_current_bytes",
+            "Local disk space used by cached basebackups in bytes"
+        )
+        .expect("failed to define a metric"),
+        hits_local: BASEBACKUP_CACHE_LOOKUPS.with_label_values(&["hit_local"]),
+        hits_remote: BASEBACKUP_CACHE_LOOKUPS.with_label_values(&["hit_remote"]),
+        misses: BASEBACKUP_CACHE_LOOKUPS.with_label_values(&["miss"]),
+        prepared: register_int_counter!(
+            "pageserver_basebackup_cache_prepared_total",
+            "Number of basebackups prepared by the basebackup cache"
+        )
+        .expect("failed to define a metric"),
+        prepare_errors: register_int_counter!(
+            "pageserver_basebackup_cache_prepare_errors_total",
+            "Number of basebackups the basebackup cache failed to prepare"
+        )
+        .expect("failed to define a metric"),
+        evictions: register_int_counter!(
+            "pageserver_basebackup_cache_evictions_total",
+            "Number of cached basebackups evicted to stay within the local disk budget"
+        )
+        .expect("failed to define a metric"),
+    });
+
 pub(crate) static WAIT_LSN_TIME: Lazy<Histogram> = Lazy::new(|| {
     register_histogram!(
         "pageserver_wait_lsn_seconds",
@@ -716,6 +810,64 @@ pub(crate) enum LayerLevel {
     .expect("failed to define a metric")
 });
 
+pub(crate) static TENANT_MAX_RESIDENT_SIZE: Lazy<UIntGaugeVec> = Lazy::new(|| {
+    register_uint_gauge_vec!(
+        "pageserver_tenant_max_resident_size",
+        "The configured max_resident_size of a tenant shard, only reported for shards with one.",
+        &["tenant_id", "shard_id"]
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static TENANT_RESIDENT_SIZE: Lazy<UIntGaugeVec> = Lazy::new(|| {
+    register_uint_gauge_vec!(
+        "pageserver_tenant_resident_size",
+        "The size of the layer files of a tenant shard present in the pageserver's filesystem, as checked against its max_resident_size.",
+        &["tenant_id", "shard_id"]
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static TENANT_DISK_QUOTA_EVICTED_LAYERS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_tenant_disk_quota_evicted_layers_total",
+        "Layers evicted because their tenant shard exceeded its max_resident_size"
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static TENANT_DISK_QUOTA_EVICTED_BYTES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_tenant_disk_quota_evicted_bytes_total",
+        "Bytes of layers evicted because their tenant shard exceeded its max_resident_size"
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static LAYER_SCRUB_CHECKED_LAYERS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_layer_scrub_checked_layers_total",
+        "Resident layer files checked for corruption by the scrub loop"
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static LAYER_SCRUB_CHECKED_BYTES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_layer_scrub_checked_bytes_total",
+        "Bytes of resident layer files checked for corruption by the scrub loop"
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static LAYER_SCRUB_CORRUPT_LAYERS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_layer_scrub_corrupt_layers_total",
+        "Resident layer files found to be corrupt by the scrub loop"
+    )
+    .expect("failed to define a metric")
+});
+
 static REMOTE_PHYSICAL_SIZE: Lazy<UIntGaugeVec> = Lazy::new(|| {
     register_uint_gauge_vec!(
         "pageserver_remote_physical_size",
@@ -750,6 +902,22 @@ pub(crate) enum LayerLevel {
     .unwrap()
 });
 
+pub(crate) static REMOTE_ONDEMAND_PARTIAL_READS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_remote_ondemand_partial_reads_total",
+        "Total reads served from partially downloaded layers",
+    )
+    .unwrap()
+});
+
+pub(crate) static REMOTE_ONDEMAND_PARTIAL_DOWNLOADED_BYTES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_remote_ondemand_partial_downloaded_bytes_total",
+        "Total bytes of layers downloaded as byte ranges for partial reads",
+    )
+    .unwrap()
+});
+
 static CURRENT_LOGICAL_SIZE: Lazy<UIntGaugeVec> = Lazy::new(|| {
     register_uint_gauge_vec!(
         "pageserver_current_logical_size",
@@ -825,6 +993,30 @@ pub(crate) enum LayerLevel {
     .expect("failed to define a metric")
 });
 
+pub(crate) static COMPRESSION_DELTA_INPUT_BYTES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_compression_delta_in_bytes_total",
+        "Size of data written into compressed delta layers before compression"
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static COMPRESSION_DELTA_INPUT_BYTES_CHOSEN: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_compression_delta_in_bytes_chosen",
+        "Size of data whose compressed form was written into delta layers"
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static COMPRESSION_DELTA_OUTPUT_BYTES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_compression_delta_out_bytes_total",
+        "Size of compressed delta layers written"
+    )
+    .expect("failed to define a metric")
+});
+
 pub(crate) static RELSIZE_CACHE_ENTRIES: Lazy<UIntGauge> = Lazy::new(|| {
     register_uint_gauge!(
         "pageserver_relsize_cache_entries",
@@ -1710,6 +1902,7 @@ pub enum SmgrQueryType {
     GetPageAtLsn,
     GetDbSize,
     GetSlruSegment,
+    GetPages,
     #[cfg(feature = "testing")]
     Test,
 }
@@ -1868,6 +2061,22 @@ pub(crate) struct SmgrQueryTimePerTimeline {
     .expect("failed to define a metric")
 });
 
+pub(crate) static PAGE_SERVICE_PREFETCH_STARTED: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_page_service_prefetch_started_total",
+        "Number of pagestream prefetch hints for which a background prefetch was started"
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static PAGE_SERVICE_PREFETCH_DROPPED: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_page_service_prefetch_dropped_total",
+        "Number of pagestream prefetch hints dropped because too many prefetches were in flight"
+    )
+    .expect("failed to define a metric")
+});
+
 fn set_page_service_config_max_batch_size(conf: &PageServicePipeliningConfig) {
     PAGE_SERVICE_CONFIG_MAX_BATCH_SIZE.reset();
     let (label_values, value) = match conf {
@@ -2107,6 +2316,7 @@ pub(crate) fn observe<T>(self, res: &Result<T, QueryError>) {
 
 #[derive(Clone, Copy, enum_map::Enum, IntoStaticStr)]
 pub(crate) enum ComputeCommandKind {
+    PageStreamV4,
     PageStreamV3,
     PageStreamV2,
     Basebackup,
@@ -2847,6 +3057,62 @@ fn default() -> Self {
 pub(crate) static WAL_REDO_PROCESS_COUNTERS: Lazy<WalRedoProcessCounters> =
     Lazy::new(WalRedoProcessCounters::default);
 
+pub(crate) static WAL_REDO_POOL_PROCESSES: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "pageserver_wal_redo_pool_processes",
+        "Number of walredo processes in the pools of all tenant shards"
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static WAL_REDO_POOL_QUEUED_REQUESTS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_wal_redo_pool_queued_requests_total",
+        "Redo requests sent to a busy walredo process because the pool of the tenant shard was full"
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static WAL_REDO_POOL_QUIESCED_PROCESSES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_wal_redo_pool_quiesced_processes_total",
+        "Walredo processes removed from their pool because they were idle"
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static WAL_REDO_POOL_FAILED_PROCESSES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_wal_redo_pool_failed_processes_total",
+        "Walredo processes removed from their pool after a redo request failed"
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static WAL_REDO_NATIVE_RECORDS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_wal_redo_native_records_total",
+        "Postgres WAL records applied by the pageserver instead of a walredo process"
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static WAL_REDO_NATIVE_FALLBACKS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_wal_redo_native_fallbacks_total",
+        "Redo requests which failed natively and were retried with the walredo process"
+    )
+    .expect("failed to define a metric")
+});
+
+pub(crate) static WAL_REDO_NATIVE_MISMATCHES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "pageserver_wal_redo_native_mismatches_total",
+        "Redo requests for which native redo and the walredo process produced different pages"
+    )
+    .expect("failed to define a metric")
+});
+
 /// Similar to `prometheus::HistogramTimer` but does not record on drop.
 pub(crate) struct StorageTimeMetricsTimer {
     metrics: StorageTimeMetrics,
@@ -2980,6 +3246,8 @@ pub(crate) struct TimelineMetrics {
     pub wait_lsn_in_progress_micros: GlobalAndPerTenantIntCounter,
     pub wait_lsn_start_finish_counterpair: IntCounterPair,
     pub wait_ondemand_download_time: wait_ondemand_download_time::WaitOndemandDownloadTimeSum,
+    /// Tenant-wide sum of `resident_physical_size_gauge`.
+    disk_quota: Arc<DiskQuota>,
     shutdown: std::sync::atomic::AtomicBool,
 }
 
@@ -2988,6 +3256,7 @@ pub fn new(
         tenant_shard_id: &TenantShardId,
         timeline_id_raw: &TimelineId,
         evictions_with_low_residence_duration_builder: EvictionsWithLowResidenceDurationBuilder,
+        disk_quota: Arc<DiskQuota>,
     ) -> Self {
         let tenant_id = tenant_shard_id.tenant_id.to_string();
         let shard_id = format!("{}", tenant_shard_id.shard_slug());
@@ -3166,6 +3435,7 @@ pub fn new(
             wait_lsn_in_progress_micros,
             wait_lsn_start_finish_counterpair,
             wait_ondemand_download_time,
+            disk_quota,
             shutdown: std::sync::atomic::AtomicBool::default(),
         }
     }
@@ -3177,11 +3447,17 @@ pub(crate) fn record_new_file_metrics(&self, sz: u64) {
     pub(crate) fn resident_physical_size_sub(&self, sz: u64) {
         self.resident_physical_size_gauge.sub(sz);
         crate::metrics::RESIDENT_PHYSICAL_SIZE_GLOBAL.sub(sz);
+        if !self.shutdown.load(std::sync::atomic::Ordering::Relaxed) {
+            self.disk_quota.resident_size_sub(sz);
+        }
     }
 
     pub(crate) fn resident_physical_size_add(&self, sz: u64) {
         self.resident_physical_size_gauge.add(sz);
         crate::metrics::RESIDENT_PHYSICAL_SIZE_GLOBAL.add(sz);
+        if !self.shutdown.load(std::sync::atomic::Ordering::Relaxed) {
+            self.disk_quota.resident_size_add(sz);
+        }
     }
 
     pub(crate) fn resident_physical_size_get(&self) -> u64 {
@@ -3294,6 +3570,7 @@ pub(crate) fn shutdown(&self) {
         let _ = STANDBY_HORIZON.remove_label_values(&[tenant_id, shard_id, timeline_id]);
         {
             RESIDENT_PHYSICAL_SIZE_GLOBAL.sub(self.resident_physical_size_get());
+            self.disk_quota.resident_size_sub(self.resident_physical_size_get());
             let _ = RESIDENT_PHYSICAL_SIZE.remove_label_values(&[tenant_id, shard_id, timeline_id]);
         }
         let _ = VISIBLE_PHYSICAL_SIZE.remove_label_values(&[tenant_id, shard_id, timeline_id]);
@@ -3404,6 +3681,11 @@ pub(crate) fn remove_tenant_metrics(tenant_shard_id: &TenantShardId) {
 
     tenant_throttling::remove_tenant_metrics(tenant_shard_id);
 
+    let tid = tenant_shard_id.tenant_id.to_string();
+    let shard_id = tenant_shard_id.shard_slug().to_string();
+    let _ = TENANT_MAX_RESIDENT_SIZE.remove_label_values(&[&tid, &shard_id]);
+    let _ = TENANT_RESIDENT_SIZE.remove_label_values(&[&tid, &shard_id]);
+
     // we leave the BROKEN_TENANTS_SET entry if any
 }
 
@@ -3967,11 +4249,14 @@ pub(crate) fn inc_by(&self, n: u64) {
 }
 
 pub(crate) mod tenant_throttling {
+    use std::time::Instant;
+
     use metrics::register_int_counter_vec;
     use once_cell::sync::Lazy;
     use utils::shard::TenantShardId;
 
     use super::GlobalAndPerTenantIntCounter;
+    use crate::tenant::throttle::ThrottleResult;
 
     pub(crate) struct Metrics<const KIND: usize> {
         pub(super) count_accounted_start: GlobalAndPerTenantIntCounter,
@@ -4046,8 +4331,9 @@ pub(crate) struct Metrics<const KIND: usize> {
         .unwrap()
     });
 
-    const KINDS: &[&str] = &["pagestream"];
+    const KINDS: &[&str] = &["pagestream", "wal_ingest"];
     pub type Pagestream = Metrics<0>;
+    pub type WalIngest = Metrics<1>;
 
     impl<const KIND: usize> Metrics<KIND> {
         pub(crate) fn new(tenant_shard_id: &TenantShardId) -> Self {
@@ -4087,6 +4373,22 @@ pub(crate) fn new(tenant_shard_id: &TenantShardId) -> Self {
                 },
             }
         }
+
+        /// For throttles that aren't tracked through [`super::SmgrOpTimer`]: call before
+        /// starting to wait for the throttle.
+        pub(crate) fn observe_throttle_start(&self) {
+            self.count_accounted_start.inc();
+        }
+
+        /// Counterpart of [`Self::observe_throttle_start`], `start` being the time the wait started.
+        pub(crate) fn observe_throttle_done(&self, start: Instant, throttle: &ThrottleResult) {
+            self.count_accounted_finish.inc();
+            if let ThrottleResult::Throttled { end } = throttle {
+                self.count_throttled.inc();
+                self.wait_time
+                    .inc_by((*end - start).as_micros().try_into().unwrap());
+            }
+        }
     }
 
     pub(crate) fn preinitialize_global_metrics() {
@@ -4238,9 +4540,18 @@ pub fn preinitialize_metrics(
         &tokio_epoll_uring::THREAD_LOCAL_LAUNCH_SUCCESSES,
         &REMOTE_ONDEMAND_DOWNLOADED_LAYERS,
         &REMOTE_ONDEMAND_DOWNLOADED_BYTES,
+        &REMOTE_ONDEMAND_PARTIAL_READS,
+        &REMOTE_ONDEMAND_PARTIAL_DOWNLOADED_BYTES,
         &CIRCUIT_BREAKERS_BROKEN,
         &CIRCUIT_BREAKERS_UNBROKEN,
         &PAGE_SERVICE_SMGR_FLUSH_INPROGRESS_MICROS_GLOBAL,
+        &PAGE_SERVICE_PREFETCH_STARTED,
+        &PAGE_SERVICE_PREFETCH_DROPPED,
+        &TENANT_DISK_QUOTA_EVICTED_LAYERS,
+        &TENANT_DISK_QUOTA_EVICTED_BYTES,
+        &LAYER_SCRUB_CHECKED_LAYERS,
+        &LAYER_SCRUB_CHECKED_BYTES,
+        &LAYER_SCRUB_CORRUPT_LAYERS,
         &WAIT_LSN_IN_PROGRESS_GLOBAL_MICROS,
     ]
     .into_iter()
@@ -4270,6 +4581,12 @@ pub fn preinitialize_metrics(
     [
         &BACKGROUND_LOOP_PERIOD_OVERRUN_COUNT,
         &SMGR_QUERY_STARTED_GLOBAL,
+        &WAL_REDO_POOL_QUEUED_REQUESTS,
+        &WAL_REDO_POOL_QUIESCED_PROCESSES,
+        &WAL_REDO_POOL_FAILED_PROCESSES,
+        &WAL_REDO_NATIVE_RECORDS,
+        &WAL_REDO_NATIVE_FALLBACKS,
+        &WAL_REDO_NATIVE_MISMATCHES,
     ]
     .into_iter()
     .for_each(|c| {
@@ -4278,6 +4595,7 @@ pub fn preinitialize_metrics(
 
     // gauges
     WALRECEIVER_ACTIVE_MANAGERS.get();
+    WAL_REDO_POOL_PROCESSES.get();
 
     // histograms
     [
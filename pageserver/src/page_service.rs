@@ -2,6 +2,7 @@
 //! requests.
 
 use std::borrow::Cow;
+use std::collections::BTreeSet;
 use std::num::NonZeroUsize;
 use std::os::fd::AsRawFd;
 use std::str::FromStr;
@@ -11,25 +12,27 @@
 
 use crate::PERF_TRACE_TARGET;
 use anyhow::{Context, bail};
-use async_compression::tokio::write::GzipEncoder;
+use async_compression::tokio::write::{GzipEncoder, ZstdEncoder};
 use bytes::Buf;
 use futures::FutureExt;
 use itertools::Itertools;
 use once_cell::sync::OnceCell;
 use pageserver_api::config::{
-    PageServicePipeliningConfig, PageServicePipeliningConfigPipelined,
+    BasebackupCompression, PageServicePipeliningConfig, PageServicePipeliningConfigPipelined,
     PageServiceProtocolPipelinedExecutionStrategy,
 };
 use pageserver_api::key::rel_block_to_key;
+use pageserver_api::keyspace::KeySpaceAccum;
 use pageserver_api::models::{
     self, PageTraceEvent, PagestreamBeMessage, PagestreamDbSizeRequest, PagestreamDbSizeResponse,
     PagestreamErrorResponse, PagestreamExistsRequest, PagestreamExistsResponse,
-    PagestreamFeMessage, PagestreamGetPageRequest, PagestreamGetSlruSegmentRequest,
-    PagestreamGetSlruSegmentResponse, PagestreamNblocksRequest, PagestreamNblocksResponse,
+    PagestreamFeMessage, PagestreamGetPageRequest, PagestreamGetPagesRequest,
+    PagestreamGetPagesResponse, PagestreamGetSlruSegmentRequest, PagestreamGetSlruSegmentResponse,
+    PagestreamNblocksRequest, PagestreamNblocksResponse, PagestreamPrefetchRequest,
     PagestreamProtocolVersion, PagestreamRequest, TenantState,
 };
 use pageserver_api::reltag::SlruKind;
-use pageserver_api::shard::TenantShardId;
+use pageserver_api::shard::{ShardCount, ShardIndex, TenantShardId};
 use postgres_backend::{
     AuthType, PostgresBackend, PostgresBackendReader, QueryError, is_expected_io_error,
 };
@@ -58,8 +61,8 @@
     DownloadBehavior, PerfInstrumentFutureExt, RequestContext, RequestContextBuilder,
 };
 use crate::metrics::{
-    self, COMPUTE_COMMANDS_COUNTERS, ComputeCommandKind, LIVE_CONNECTIONS, SmgrOpTimer,
-    TimelineMetrics,
+    self, COMPUTE_COMMANDS_COUNTERS, ComputeCommandKind, LIVE_CONNECTIONS,
+    PAGE_SERVICE_PREFETCH_DROPPED, PAGE_SERVICE_PREFETCH_STARTED, SmgrOpTimer, TimelineMetrics,
 };
 use crate::pgdatadir_mapping::Version;
 use crate::span::{
@@ -73,7 +76,7 @@
 use crate::tenant::storage_layer::IoConcurrency;
 use crate::tenant::timeline::{self, WaitLsnError};
 use crate::tenant::{GetTimelineError, PageReconstructError, Timeline};
-use crate::{basebackup, timed_after_cancellation};
+use crate::{basebackup, basebackup_cache, timed_after_cancellation};
 
 /// How long we may wait for a [`crate::tenant::mgr::TenantSlot::InProgress`]` and/or a [`crate::tenant::Tenant`] which
 /// is not yet in state [`TenantState::Active`].
@@ -84,6 +87,25 @@
 /// Threshold at which to log slow GetPage requests.
 const LOG_SLOW_GETPAGE_THRESHOLD: Duration = Duration::from_secs(30);
 
+/// Maximum number of prefetch hints executed concurrently per connection. Further hints are
+/// dropped until one of them completes.
+const MAX_INFLIGHT_PREFETCHES_PER_CONNECTION: usize = 4;
+
+/// Upper bound on the number of blocks a single prefetch hint reads.
+const MAX_PREFETCH_NBLOCKS: u32 = 8192;
+
+/// The blocks a prefetch hint asks for, capped at [`MAX_PREFETCH_NBLOCKS`].
+fn prefetch_block_range(req: &PagestreamPrefetchRequest) -> std::ops::Range<u32> {
+    let end = req
+        .blkno
+        .saturating_add(req.nblocks.min(MAX_PREFETCH_NBLOCKS));
+    req.blkno..end
+}
+
+// A GetPages request is served with a single vectored get.
+const _: () =
+    assert!(models::PAGESTREAM_MAX_GETPAGES_PAGES as u64 <= Timeline::MAX_GET_VECTORED_KEYS);
+
 ///////////////////////////////////////////////////////////////////////////////
 
 pub struct Listener {
@@ -383,6 +405,10 @@ struct PageServerHandler {
 
     pipelining_config: PageServicePipeliningConfig,
 
+    /// Background reads started by pagestream prefetch hints.
+    /// Dropping the set aborts them, so they do not outlive the connection.
+    prefetch_tasks: tokio::task::JoinSet<()>,
+
     gate_guard: GateGuard,
 }
 
@@ -679,6 +705,20 @@ enum BatchedFeMessage {
         shard: timeline::handle::WeakHandle<TenantManagerTypes>,
         req: models::PagestreamGetSlruSegmentRequest,
     },
+    Prefetch {
+        span: Span,
+        /// Every local shard that owns at least one block of the hint.
+        shards: smallvec::SmallVec<[timeline::handle::WeakHandle<TenantManagerTypes>; 1]>,
+        req: models::PagestreamPrefetchRequest,
+    },
+    GetPages {
+        span: Span,
+        timer: SmgrOpTimer,
+        shard: timeline::handle::WeakHandle<TenantManagerTypes>,
+        effective_request_lsn: Lsn,
+        req: models::PagestreamGetPagesRequest,
+        ctx: RequestContext,
+    },
     #[cfg(feature = "testing")]
     Test {
         span: Span,
@@ -701,7 +741,8 @@ fn observe_execution_start(&mut self, at: Instant) {
             BatchedFeMessage::Exists { timer, .. }
             | BatchedFeMessage::Nblocks { timer, .. }
             | BatchedFeMessage::DbSize { timer, .. }
-            | BatchedFeMessage::GetSlruSegment { timer, .. } => {
+            | BatchedFeMessage::GetSlruSegment { timer, .. }
+            | BatchedFeMessage::GetPages { timer, .. } => {
                 timer.observe_execution_start(at);
             }
             BatchedFeMessage::GetPage { pages, .. } => {
@@ -715,7 +756,7 @@ fn observe_execution_start(&mut self, at: Instant) {
                     req.timer.observe_execution_start(at);
                 }
             }
-            BatchedFeMessage::RespondError { .. } => {}
+            BatchedFeMessage::Prefetch { .. } | BatchedFeMessage::RespondError { .. } => {}
         }
     }
 }
@@ -741,6 +782,7 @@ pub fn new(
             timeline_handles: Some(TimelineHandles::new(tenant_manager)),
             cancel,
             pipelining_config,
+            prefetch_tasks: tokio::task::JoinSet::new(),
             gate_guard,
         }
     }
@@ -820,6 +862,7 @@ async fn pagestream_read_message<IO>(
         async fn record_op_start_and_throttle(
             shard: &timeline::handle::Handle<TenantManagerTypes>,
             op: metrics::SmgrQueryType,
+            key_count: usize,
             received_at: Instant,
         ) -> Result<SmgrOpTimer, QueryError> {
             // It's important to start the smgr op metric recorder as early as possible
@@ -829,7 +872,7 @@ async fn record_op_start_and_throttle(
             let now = Instant::now();
             timer.observe_throttle_start(now);
             let throttled = tokio::select! {
-                res = shard.pagestream_throttle.throttle(1, now) => res,
+                res = shard.pagestream_throttle.throttle(key_count, now) => res,
                 _ = shard.cancel.cancelled() => return Err(QueryError::Shutdown),
             };
             timer.observe_throttle_done(throttled);
@@ -846,6 +889,7 @@ async fn record_op_start_and_throttle(
                 let timer = record_op_start_and_throttle(
                     &shard,
                     metrics::SmgrQueryType::GetRelExists,
+                    1,
                     received_at,
                 )
                 .await?;
@@ -864,6 +908,7 @@ async fn record_op_start_and_throttle(
                 let timer = record_op_start_and_throttle(
                     &shard,
                     metrics::SmgrQueryType::GetRelSize,
+                    1,
                     received_at,
                 )
                 .await?;
@@ -882,6 +927,7 @@ async fn record_op_start_and_throttle(
                 let timer = record_op_start_and_throttle(
                     &shard,
                     metrics::SmgrQueryType::GetDbSize,
+                    1,
                     received_at,
                 )
                 .await?;
@@ -900,6 +946,7 @@ async fn record_op_start_and_throttle(
                 let timer = record_op_start_and_throttle(
                     &shard,
                     metrics::SmgrQueryType::GetSlruSegment,
+                    1,
                     received_at,
                 )
                 .await?;
@@ -910,6 +957,130 @@ async fn record_op_start_and_throttle(
                     req,
                 }
             }
+            PagestreamFeMessage::GetPages(req) => {
+                let span = tracing::info_span!(parent: &parent_span, "handle_get_pages_request", npages = req.pages.len(), req_lsn = %req.hdr.request_lsn);
+
+                macro_rules! respond_error {
+                    ($error:expr) => {{
+                        let error = BatchedFeMessage::RespondError {
+                            span,
+                            error: BatchedPageStreamError {
+                                req: req.hdr,
+                                err: $error,
+                            },
+                        };
+                        return Ok(Some(error));
+                    }};
+                }
+
+                let Some((first_rel, first_blkno)) = req.pages.first() else {
+                    respond_error!(PageStreamError::BadRequest(
+                        "getpages request without pages".into()
+                    ));
+                };
+
+                let res = timeline_handles
+                    .get(
+                        tenant_id,
+                        timeline_id,
+                        ShardSelector::Page(rel_block_to_key(*first_rel, *first_blkno)),
+                    )
+                    .await;
+                let shard = match res {
+                    Ok(shard) => shard,
+                    Err(GetActiveTimelineError::Tenant(GetActiveTenantError::NotFound(_))) => {
+                        // see GetPage
+                        respond_error!(PageStreamError::Reconnect(
+                            "getpages request routed to wrong shard".into()
+                        ));
+                    }
+                    Err(e) => respond_error!(e.into()),
+                };
+
+                // all pages are read with a single vectored get on one shard
+                let shard_identity = shard.get_shard_identity();
+                if !req.pages.iter().all(|(rel, blkno)| {
+                    shard_identity.is_key_local(&rel_block_to_key(*rel, *blkno))
+                }) {
+                    respond_error!(PageStreamError::BadRequest(
+                        "getpages request spans multiple shards".into()
+                    ));
+                }
+
+                let ctx = ctx.with_scope_page_service_pagestream(&shard);
+
+                let timer = record_op_start_and_throttle(
+                    &shard,
+                    metrics::SmgrQueryType::GetPages,
+                    req.pages.len(),
+                    received_at,
+                )
+                .await?;
+
+                let res = Self::wait_or_get_last_lsn(
+                    &shard,
+                    req.hdr.request_lsn,
+                    req.hdr.not_modified_since,
+                    &shard.get_applied_gc_cutoff_lsn(),
+                    &ctx,
+                )
+                .await;
+                let effective_request_lsn = match res {
+                    Ok(lsn) => lsn,
+                    Err(e) => respond_error!(e),
+                };
+
+                BatchedFeMessage::GetPages {
+                    span,
+                    timer,
+                    shard: shard.downgrade(),
+                    effective_request_lsn,
+                    req,
+                    ctx,
+                }
+            }
+            PagestreamFeMessage::Prefetch(req) => {
+                let key = rel_block_to_key(req.rel, req.blkno);
+                let first_shard = timeline_handles
+                    .get(tenant_id, timeline_id, ShardSelector::Page(key))
+                    .await?;
+                let span = tracing::info_span!(parent: &parent_span, "handle_prefetch_request", rel = %req.rel, blkno = %req.blkno, nblocks = %req.nblocks, req_lsn = %req.hdr.request_lsn);
+
+                // A hint may span stripes that belong to other shards. Route it to every shard
+                // that owns one of its blocks; shards that are not attached here are skipped.
+                let shard_identity = *first_shard.get_shard_identity();
+                let mut shards = smallvec::SmallVec::new();
+                if shard_identity.count >= ShardCount(2) {
+                    let shard_numbers = prefetch_block_range(&req)
+                        .map(|blkno| {
+                            shard_identity.get_shard_number(&rel_block_to_key(req.rel, blkno))
+                        })
+                        .collect::<BTreeSet<_>>();
+                    for shard_number in shard_numbers {
+                        if shard_number == shard_identity.number {
+                            shards.push(first_shard.downgrade());
+                            continue;
+                        }
+                        let shard_index = ShardIndex {
+                            shard_number,
+                            shard_count: shard_identity.count,
+                        };
+                        match timeline_handles
+                            .get(tenant_id, timeline_id, ShardSelector::Known(shard_index))
+                            .await
+                        {
+                            Ok(shard) => shards.push(shard.downgrade()),
+                            Err(e) => {
+                                debug!(%shard_index, "not prefetching on shard: {e}");
+                            }
+                        }
+                    }
+                } else {
+                    shards.push(first_shard.downgrade());
+                }
+
+                BatchedFeMessage::Prefetch { span, shards, req }
+            }
             PagestreamFeMessage::GetPage(req) => {
                 // avoid a somewhat costly Span::record() by constructing the entire span in one go.
                 macro_rules! mkspan {
@@ -1007,6 +1178,7 @@ macro_rules! respond_error {
                 let timer = record_op_start_and_throttle(
                     &shard,
                     metrics::SmgrQueryType::GetPageAtLsn,
+                    1,
                     received_at,
                 )
                 .maybe_perf_instrument(&ctx, |current_perf_span| {
@@ -1055,9 +1227,13 @@ macro_rules! respond_error {
                     .get(tenant_id, timeline_id, ShardSelector::Zero)
                     .await?;
                 let span = tracing::info_span!(parent: &parent_span, "handle_test_request", shard_id = %shard.tenant_shard_id.shard_slug());
-                let timer =
-                    record_op_start_and_throttle(&shard, metrics::SmgrQueryType::Test, received_at)
-                        .await?;
+                let timer = record_op_start_and_throttle(
+                    &shard,
+                    metrics::SmgrQueryType::Test,
+                    1,
+                    received_at,
+                )
+                .await?;
                 BatchedFeMessage::Test {
                     span,
                     shard: shard.downgrade(),
@@ -1450,6 +1626,45 @@ macro_rules! upgrade_handle_and_set_context {
                     span,
                 )
             }
+            BatchedFeMessage::GetPages {
+                span,
+                timer,
+                shard,
+                effective_request_lsn,
+                req,
+                ctx: req_ctx,
+            } => {
+                fail::fail_point!("ps::handle-pagerequest-message::getpages");
+                let (shard, _ctx) = upgrade_handle_and_set_context!(shard);
+                (
+                    vec![
+                        self.handle_get_pages_request(
+                            &shard,
+                            effective_request_lsn,
+                            &req,
+                            io_concurrency,
+                            &req_ctx,
+                        )
+                        .instrument(span.clone())
+                        .await
+                        .map(|msg| (msg, timer))
+                        .map_err(|err| BatchedPageStreamError { err, req: req.hdr }),
+                    ],
+                    span,
+                )
+            }
+            BatchedFeMessage::Prefetch { span, shards, req } => {
+                for shard in shards {
+                    // a shard that went away since the hint was routed is simply skipped
+                    let Ok(shard) = shard.upgrade() else {
+                        continue;
+                    };
+                    let ctx = ctx.with_scope_page_service_pagestream(&shard);
+                    span.in_scope(|| self.start_prefetch(shard, req, io_concurrency.clone(), &ctx));
+                }
+                // prefetch hints are not responded to
+                (Vec::new(), span)
+            }
             #[cfg(feature = "testing")]
             BatchedFeMessage::Test {
                 span,
@@ -1884,13 +2099,14 @@ async fn wait_or_get_last_lsn(
             ));
         }
 
-        // Clients should only read from recent LSNs on their timeline, or from locations holding an LSN lease.
+        // Clients should only read from recent LSNs on their timeline, or from locations holding an LSN lease
+        // or kept as a restore point.
         //
         // We may have older data available, but we make a best effort to detect this case and return an error,
         // to distinguish a misbehaving client (asking for old LSN) from a storage issue (data missing at a legitimate LSN).
         if request_lsn < **latest_gc_cutoff_lsn && !timeline.is_gc_blocked_by_lsn_lease_deadline() {
             let gc_info = &timeline.gc_info.read().unwrap();
-            if !gc_info.lsn_covered_by_lease(request_lsn) {
+            if !gc_info.lsn_covered_by_lease_or_restore_point(request_lsn) {
                 return Err(
                     PageStreamError::BadRequest(format!(
                         "tried to request a page version that was garbage collected. requested at {} gc cutoff {}",
@@ -2063,6 +2279,148 @@ async fn handle_db_size_request(
         }))
     }
 
+    /// Read all pages of a GetPages request with a single vectored get. If any of the pages
+    /// cannot be read, the whole request fails.
+    #[instrument(skip_all)]
+    async fn handle_get_pages_request(
+        &mut self,
+        timeline: &Timeline,
+        effective_lsn: Lsn,
+        req: &PagestreamGetPagesRequest,
+        io_concurrency: IoConcurrency,
+        ctx: &RequestContext,
+    ) -> Result<PagestreamBeMessage, PageStreamError> {
+        debug_assert_current_span_has_tenant_and_timeline_id();
+
+        if let Some(page_trace) = timeline.page_trace.load().as_ref() {
+            let time = SystemTime::now();
+            for (rel, blkno) in &req.pages {
+                let key = rel_block_to_key(*rel, *blkno).to_compact();
+                // Ignore error (trace buffer may be full or tracer may have disconnected).
+                _ = page_trace.try_send(PageTraceEvent {
+                    key,
+                    effective_lsn,
+                    time,
+                });
+            }
+        }
+
+        let results = timeline
+            .get_rel_page_at_lsn_batched(
+                req.pages
+                    .iter()
+                    .map(|(rel, blkno)| (rel, blkno, ctx.attached_child())),
+                effective_lsn,
+                io_concurrency,
+                ctx,
+            )
+            .await;
+        assert_eq!(results.len(), req.pages.len());
+
+        let pages = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+
+        Ok(PagestreamBeMessage::GetPages(PagestreamGetPagesResponse {
+            req: req.clone(),
+            pages,
+        }))
+    }
+
+    /// Start reading the blocks of a prefetch hint in the background, so that layers get
+    /// downloaded and the caches get warmed before the compute asks for the pages.
+    ///
+    /// Only the blocks local to `shard` are read. The reads are charged to the tenant's
+    /// pagestream throttle like regular GetPage requests.
+    ///
+    /// The pages themselves are discarded. Hints are best-effort: they are dropped if too many
+    /// are already in flight on this connection, and failures are only logged.
+    fn start_prefetch(
+        &mut self,
+        shard: timeline::handle::Handle<TenantManagerTypes>,
+        req: PagestreamPrefetchRequest,
+        io_concurrency: IoConcurrency,
+        ctx: &RequestContext,
+    ) {
+        // reap completed prefetches
+        while self.prefetch_tasks.try_join_next().is_some() {}
+
+        if self.prefetch_tasks.len() >= MAX_INFLIGHT_PREFETCHES_PER_CONNECTION {
+            debug!("dropping prefetch hint, too many prefetches in flight");
+            PAGE_SERVICE_PREFETCH_DROPPED.inc();
+            return;
+        }
+
+        let gate_guard = match self.gate_guard.try_clone() {
+            Ok(guard) => guard,
+            Err(_) => return,
+        };
+
+        let ctx = ctx.detached_child(TaskKind::PageRequestHandler, DownloadBehavior::Download);
+        let shard_slug = shard.tenant_shard_id.shard_slug();
+
+        PAGE_SERVICE_PREFETCH_STARTED.inc();
+        self.prefetch_tasks.spawn(
+            async move {
+                let _gate_guard = gate_guard;
+                let cancel = shard.cancel.clone();
+                tokio::select! {
+                    _ = cancel.cancelled() => {}
+                    res = Self::prefetch_pages(&shard, req, io_concurrency, &ctx) => {
+                        if let Err(e) = res {
+                            debug!("prefetch failed: {e:#}");
+                        }
+                    }
+                }
+            }
+            .instrument(info_span!("prefetch", shard_id = %shard_slug)),
+        );
+    }
+
+    async fn prefetch_pages(
+        timeline: &Timeline,
+        req: PagestreamPrefetchRequest,
+        io_concurrency: IoConcurrency,
+        ctx: &RequestContext,
+    ) -> Result<(), PageStreamError> {
+        let lsn = Self::wait_or_get_last_lsn(
+            timeline,
+            req.hdr.request_lsn,
+            req.hdr.not_modified_since,
+            &timeline.get_applied_gc_cutoff_lsn(),
+            ctx,
+        )
+        .await?;
+
+        let shard_identity = timeline.get_shard_identity();
+        let mut keys = prefetch_block_range(&req)
+            .map(|blkno| rel_block_to_key(req.rel, blkno))
+            .filter(|key| shard_identity.is_key_local(key))
+            .peekable();
+
+        while keys.peek().is_some() {
+            let mut accum = KeySpaceAccum::new();
+            let mut key_count = 0;
+            for key in keys.by_ref().take(Timeline::MAX_GET_VECTORED_KEYS as usize) {
+                accum.add_key(key);
+                key_count += 1;
+            }
+
+            // Prefetches compete with the compute's own reads for the tenant's read budget.
+            timeline
+                .pagestream_throttle
+                .throttle(key_count, Instant::now())
+                .await;
+
+            // A hint may reach past the end of the relation, in which case the batch fails with
+            // a missing key. The remaining batches would fail the same way, so stop there.
+            timeline
+                .get_vectored(accum.to_keyspace(), lsn, io_concurrency.clone(), ctx)
+                .await
+                .map_err(|e| PageStreamError::from(PageReconstructError::from(e)))?;
+        }
+
+        Ok(())
+    }
+
     #[instrument(skip_all)]
     async fn handle_get_page_at_lsn_request_batched(
         &mut self,
@@ -2221,7 +2579,7 @@ async fn handle_basebackup_request<IO>(
         lsn: Option<Lsn>,
         prev_lsn: Option<Lsn>,
         full_backup: bool,
-        gzip: bool,
+        compression: BasebackupCompression,
         replica: bool,
         ctx: &RequestContext,
     ) -> Result<(), QueryError>
@@ -2274,6 +2632,17 @@ fn map_basebackup_error(err: BasebackupError) -> QueryError {
 
         let lsn_awaited_after = started.elapsed();
 
+        // Replica basebackups differ from the primary's at the same LSN, and full backups are
+        // rare: only regular ones are cached. Cached basebackups take the previous record LSN
+        // from the timeline, so requests that specify one bypass the cache.
+        let cached = match basebackup_cache::get() {
+            Some(basebackup_cache) if !full_backup && !replica && prev_lsn.is_none() => {
+                let lsn = lsn.unwrap_or_else(|| timeline.get_last_record_lsn());
+                basebackup_cache.lookup(&timeline, lsn, compression).await
+            }
+            _ => None,
+        };
+
         // switch client to COPYOUT
         pgb.write_message_noflush(&BeMessage::CopyOutResponse)
             .map_err(QueryError::Disconnected)?;
@@ -2281,7 +2650,21 @@ fn map_basebackup_error(err: BasebackupError) -> QueryError {
 
         // Send a tarball of the latest layer on the timeline. Compress if not
         // fullbackup. TODO Compress in that case too (tests need to be updated)
-        if full_backup {
+        if let Some(mut file) = cached {
+            let mut writer = pgb.copyout_writer();
+            tokio::io::copy(&mut file, &mut writer).await.map_err(|e| {
+                map_basebackup_error(BasebackupError::Client(
+                    e,
+                    "handle_basebackup_request,cached",
+                ))
+            })?;
+            writer.flush().await.map_err(|e| {
+                map_basebackup_error(BasebackupError::Client(
+                    e,
+                    "handle_basebackup_request,cached,flush",
+                ))
+            })?;
+        } else if full_backup {
             let mut writer = pgb.copyout_writer();
             basebackup::send_basebackup_tarball(
                 &mut writer,
@@ -2296,44 +2679,67 @@ fn map_basebackup_error(err: BasebackupError) -> QueryError {
             .map_err(map_basebackup_error)?;
         } else {
             let mut writer = BufWriter::new(pgb.copyout_writer());
-            if gzip {
-                let mut encoder = GzipEncoder::with_quality(
-                    &mut writer,
-                    // NOTE using fast compression because it's on the critical path
-                    //      for compute startup. For an empty database, we get
-                    //      <100KB with this method. The Level::Best compression method
-                    //      gives us <20KB, but maybe we should add basebackup caching
-                    //      on compute shutdown first.
-                    async_compression::Level::Fastest,
-                );
-                basebackup::send_basebackup_tarball(
-                    &mut encoder,
-                    &timeline,
-                    lsn,
-                    prev_lsn,
-                    full_backup,
-                    replica,
-                    &ctx,
-                )
-                .await
-                .map_err(map_basebackup_error)?;
-                // shutdown the encoder to ensure the gzip footer is written
-                encoder
-                    .shutdown()
+            match compression {
+                BasebackupCompression::Gzip => {
+                    let mut encoder = GzipEncoder::with_quality(
+                        &mut writer,
+                        // NOTE using fast compression because it's on the critical path
+                        //      for compute startup. For an empty database, we get
+                        //      <100KB with this method. The Level::Best compression method
+                        //      gives us <20KB: the basebackup cache, which prepares them
+                        //      off the critical path, uses it.
+                        async_compression::Level::Fastest,
+                    );
+                    basebackup::send_basebackup_tarball(
+                        &mut encoder,
+                        &timeline,
+                        lsn,
+                        prev_lsn,
+                        full_backup,
+                        replica,
+                        &ctx,
+                    )
                     .await
-                    .map_err(|e| QueryError::Disconnected(ConnectionError::Io(e)))?;
-            } else {
-                basebackup::send_basebackup_tarball(
-                    &mut writer,
-                    &timeline,
-                    lsn,
-                    prev_lsn,
-                    full_backup,
-                    replica,
-                    &ctx,
-                )
-                .await
-                .map_err(map_basebackup_error)?;
+                    .map_err(map_basebackup_error)?;
+                    // shutdown the encoder to ensure the gzip footer is written
+                    encoder
+                        .shutdown()
+                        .await
+                        .map_err(|e| QueryError::Disconnected(ConnectionError::Io(e)))?;
+                }
+                BasebackupCompression::Zstd => {
+                    let mut encoder =
+                        ZstdEncoder::with_quality(&mut writer, async_compression::Level::Fastest);
+                    basebackup::send_basebackup_tarball(
+                        &mut encoder,
+                        &timeline,
+                        lsn,
+                        prev_lsn,
+                        full_backup,
+                        replica,
+                        &ctx,
+                    )
+                    .await
+                    .map_err(map_basebackup_error)?;
+                    // shutdown the encoder to ensure the zstd frame is finished
+                    encoder
+                        .shutdown()
+                        .await
+                        .map_err(|e| QueryError::Disconnected(ConnectionError::Io(e)))?;
+                }
+                BasebackupCompression::Uncompressed => {
+                    basebackup::send_basebackup_tarball(
+                        &mut writer,
+                        &timeline,
+                        lsn,
+                        prev_lsn,
+                        full_backup,
+                        replica,
+                        &ctx,
+                    )
+                    .await
+                    .map_err(map_basebackup_error)?;
+                }
             }
             writer.flush().await.map_err(|e| {
                 map_basebackup_error(BasebackupError::Client(
@@ -2379,13 +2785,13 @@ fn check_permission(&self, tenant_id: Option<TenantId>) -> Result<(), QueryError
     }
 }
 
-/// `basebackup tenant timeline [lsn] [--gzip] [--replica]`
+/// `basebackup tenant timeline [lsn] [--gzip|--zstd] [--replica]`
 #[derive(Debug, Clone, Eq, PartialEq)]
 struct BaseBackupCmd {
     tenant_id: TenantId,
     timeline_id: TimelineId,
     lsn: Option<Lsn>,
-    gzip: bool,
+    compression: BasebackupCompression,
     replica: bool,
 }
 
@@ -2517,16 +2923,20 @@ fn parse(query: &str) -> anyhow::Result<Self> {
             flags_parse_from = 2;
         }
 
-        let mut gzip = false;
+        let mut compression = BasebackupCompression::Uncompressed;
         let mut replica = false;
 
         for &param in &parameters[flags_parse_from..] {
             match param {
-                "--gzip" => {
-                    if gzip {
-                        bail!("duplicate parameter for basebackup command: {param}")
+                "--gzip" | "--zstd" => {
+                    if compression != BasebackupCompression::Uncompressed {
+                        bail!("duplicate compression parameter for basebackup command: {param}")
                     }
-                    gzip = true
+                    compression = if param == "--gzip" {
+                        BasebackupCompression::Gzip
+                    } else {
+                        BasebackupCompression::Zstd
+                    };
                 }
                 "--replica" => {
                     if replica {
@@ -2541,7 +2951,7 @@ fn parse(query: &str) -> anyhow::Result<Self> {
             tenant_id,
             timeline_id,
             lsn,
-            gzip,
+            compression,
             replica,
         })
     }
@@ -2585,6 +2995,10 @@ fn parse(query: &str) -> anyhow::Result<Self> {
                 other,
                 PagestreamProtocolVersion::V3,
             )?)),
+            "pagestream_v4" => Ok(Self::PageStream(PageStreamCmd::parse(
+                other,
+                PagestreamProtocolVersion::V4,
+            )?)),
             "basebackup" => Ok(Self::BaseBackup(BaseBackupCmd::parse(other)?)),
             "fullbackup" => Ok(Self::FullBackup(FullBackupCmd::parse(other)?)),
             "lease" => {
@@ -2745,6 +3159,7 @@ async fn process_query(
                 let command_kind = match protocol_version {
                     PagestreamProtocolVersion::V2 => ComputeCommandKind::PageStreamV2,
                     PagestreamProtocolVersion::V3 => ComputeCommandKind::PageStreamV3,
+                    PagestreamProtocolVersion::V4 => ComputeCommandKind::PageStreamV4,
                 };
                 COMPUTE_COMMANDS_COUNTERS.for_command(command_kind).inc();
 
@@ -2755,7 +3170,7 @@ async fn process_query(
                 tenant_id,
                 timeline_id,
                 lsn,
-                gzip,
+                compression,
                 replica,
             }) => {
                 tracing::Span::current()
@@ -2776,7 +3191,7 @@ async fn process_query(
                         lsn,
                         None,
                         false,
-                        gzip,
+                        compression,
                         replica,
                         &ctx,
                     )
@@ -2813,7 +3228,7 @@ async fn process_query(
                     lsn,
                     prev_lsn,
                     true,
-                    false,
+                    BasebackupCompression::Uncompressed,
                     false,
                     &ctx,
                 )
@@ -2941,6 +3356,16 @@ fn pageservice_cmd_parse() {
                 protocol_version: PagestreamProtocolVersion::V2,
             })
         );
+        let cmd =
+            PageServiceCmd::parse(&format!("pagestream_v4 {tenant_id} {timeline_id}")).unwrap();
+        assert_eq!(
+            cmd,
+            PageServiceCmd::PageStream(PageStreamCmd {
+                tenant_id,
+                timeline_id,
+                protocol_version: PagestreamProtocolVersion::V4,
+            })
+        );
         let cmd = PageServiceCmd::parse(&format!("basebackup {tenant_id} {timeline_id}")).unwrap();
         assert_eq!(
             cmd,
@@ -2948,7 +3373,7 @@ fn pageservice_cmd_parse() {
                 tenant_id,
                 timeline_id,
                 lsn: None,
-                gzip: false,
+                compression: BasebackupCompression::Uncompressed,
                 replica: false
             })
         );
@@ -2960,7 +3385,7 @@ fn pageservice_cmd_parse() {
                 tenant_id,
                 timeline_id,
                 lsn: None,
-                gzip: true,
+                compression: BasebackupCompression::Gzip,
                 replica: false
             })
         );
@@ -2972,7 +3397,7 @@ fn pageservice_cmd_parse() {
                 tenant_id,
                 timeline_id,
                 lsn: None,
-                gzip: false,
+                compression: BasebackupCompression::Uncompressed,
                 replica: false
             })
         );
@@ -2984,7 +3409,7 @@ fn pageservice_cmd_parse() {
                 tenant_id,
                 timeline_id,
                 lsn: Some(Lsn::from_str("0/16ABCDE").unwrap()),
-                gzip: false,
+                compression: BasebackupCompression::Uncompressed,
                 replica: false
             })
         );
@@ -2998,7 +3423,7 @@ fn pageservice_cmd_parse() {
                 tenant_id,
                 timeline_id,
                 lsn: None,
-                gzip: true,
+                compression: BasebackupCompression::Gzip,
                 replica: true
             })
         );
@@ -3012,10 +3437,24 @@ fn pageservice_cmd_parse() {
                 tenant_id,
                 timeline_id,
                 lsn: Some(Lsn::from_str("0/16ABCDE").unwrap()),
-                gzip: true,
+                compression: BasebackupCompression::Gzip,
                 replica: true
             })
         );
+        let cmd = PageServiceCmd::parse(&format!(
+            "basebackup {tenant_id} {timeline_id} 0/16ABCDE --zstd"
+        ))
+        .unwrap();
+        assert_eq!(
+            cmd,
+            PageServiceCmd::BaseBackup(BaseBackupCmd {
+                tenant_id,
+                timeline_id,
+                lsn: Some(Lsn::from_str("0/16ABCDE").unwrap()),
+                compression: BasebackupCompression::Zstd,
+                replica: false
+            })
+        );
         let cmd = PageServiceCmd::parse(&format!("fullbackup {tenant_id} {timeline_id}")).unwrap();
         assert_eq!(
             cmd,
@@ -3087,6 +3526,10 @@ fn pageservice_cmd_err_handling() {
             "basebackup {tenant_id} {timeline_id} --gzip --gzip"
         ));
         assert!(cmd.is_err());
+        let cmd = PageServiceCmd::parse(&format!(
+            "basebackup {tenant_id} {timeline_id} --gzip --zstd"
+        ));
+        assert!(cmd.is_err());
         let cmd = PageServiceCmd::parse(&format!(
             "basebackup {tenant_id} {timeline_id} --gzip --unknown"
         ));
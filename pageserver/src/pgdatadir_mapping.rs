@@ -369,7 +369,8 @@ pub(crate) async fn get_rel_page_at_lsn_batched(
                                 x @ PageReconstructError::Other(_)
                                 | x @ PageReconstructError::AncestorLsnTimeout(_)
                                 | x @ PageReconstructError::WalRedo(_)
-                                | x @ PageReconstructError::MissingKey(_) => {
+                                | x @ PageReconstructError::MissingKey(_)
+                                | x @ PageReconstructError::Corruption(_) => {
                                     PageReconstructError::Other(anyhow::anyhow!(
                                         "there was more than one request for this key in the batch, error logged once: {x:?}"
                                     ))
@@ -411,6 +412,10 @@ pub(crate) async fn get_rel_page_at_lsn_batched(
                             )))
                         }
                         // TODO: restructure get_vectored API to make this error per-key
+                        GetVectoredError::Corruption(err) => Err(PageReconstructError::Corruption(
+                            anyhow::anyhow!("whole vectored get request failed: {err:?}"),
+                        )),
+                        // TODO: restructure get_vectored API to make this error per-key
                         GetVectoredError::Other(err) => Err(PageReconstructError::Other(
                             anyhow::anyhow!("whole vectored get request failed: {err:?}"),
                         )),
@@ -328,9 +328,18 @@ pub enum TaskKind {
     // Tenant housekeeping (flush idle ephemeral layers, shut down idle walredo, etc.).
     TenantHousekeeping,
 
+    // Tenant disk quota enforcement. One per tenant.
+    TenantDiskQuota,
+
+    // Tenant layer file scrubbing. One per tenant.
+    TenantScrub,
+
     /// See [`crate::disk_usage_eviction_task`].
     DiskUsageEviction,
 
+    /// See [`crate::basebackup_cache`].
+    BasebackupCache,
+
     /// See [`crate::tenant::secondary`].
     SecondaryDownloads,
 
@@ -380,6 +389,9 @@ pub enum TaskKind {
     DetachAncestor,
 
     ImportPgdata,
+
+    // Task that exports a timeline as a PGDATA directory
+    ExportPgdata,
 }
 
 #[derive(Default)]
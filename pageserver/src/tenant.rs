@@ -17,6 +17,7 @@
 use std::fmt::{Debug, Display};
 use std::fs::File;
 use std::future::Future;
+use std::num::NonZeroU64;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, Weak};
 use std::time::{Duration, Instant, SystemTime};
@@ -34,8 +35,8 @@
 pub use pageserver_api::models::TenantState;
 use pageserver_api::models::{self, RelSizeMigration};
 use pageserver_api::models::{
-    CompactInfoResponse, LsnLease, TimelineArchivalState, TimelineState, TopTenantShardItem,
-    WalRedoManagerStatus,
+    CompactInfoResponse, LsnLease, TenantScrubStatus, TimelineArchivalState, TimelineState,
+    TopTenantShardItem, WalRedoManagerStatus,
 };
 use pageserver_api::shard::{ShardIdentity, ShardStripeSize, TenantShardId};
 use remote_storage::{DownloadError, GenericRemoteStorage, TimeoutOrCancel};
@@ -68,6 +69,7 @@
 use utils::{backoff, completion, failpoint_support, fs_ext, pausable_failpoint};
 
 use self::config::{AttachedLocationConfig, AttachmentMode, LocationConf};
+use self::disk_quota::DiskQuota;
 use self::metadata::TimelineMetadata;
 use self::mgr::{GetActiveTenantError, GetTenantError};
 use self::remote_timeline_client::upload::{upload_index_part, upload_tenant_manifest};
@@ -105,7 +107,7 @@
 static INIT_DB_SEMAPHORE: Lazy<Semaphore> = Lazy::new(|| Semaphore::new(8));
 use utils::crashsafe;
 use utils::generation::Generation;
-use utils::id::TimelineId;
+use utils::id::{TenantId, TenantTimelineId, TimelineId};
 use utils::lsn::{Lsn, RecordLsn};
 
 pub mod blob_io;
@@ -131,8 +133,11 @@
 
 pub mod size;
 
+pub(crate) mod disk_quota;
 mod gc_block;
 mod gc_result;
+mod scrub;
+mod shard_merge;
 pub(crate) mod throttle;
 
 pub(crate) use timeline::{LogicalSizeCalculationCause, PageReconstructError, Timeline};
@@ -317,6 +322,12 @@ pub struct Tenant {
     /// Signals the tenant compaction loop that there is L0 compaction work to be done.
     pub(crate) l0_compaction_trigger: Arc<Notify>,
 
+    /// Resident layer bytes of all timelines, checked against `max_resident_size`.
+    pub(crate) disk_quota: Arc<DiskQuota>,
+
+    /// Outcome of the last complete pass of the scrub loop over the resident layer files.
+    scrub_status: std::sync::Mutex<TenantScrubStatus>,
+
     /// Scheduled gc-compaction tasks.
     scheduled_compaction_tasks: std::sync::Mutex<HashMap<TimelineId, Arc<GcCompactionQueue>>>,
 
@@ -342,6 +353,12 @@ pub struct Tenant {
 
     pub(crate) pagestream_throttle_metrics: Arc<crate::metrics::tenant_throttling::Pagestream>,
 
+    /// Throttle applied to WAL ingest, in bytes of WAL, by the walreceiver connections of all
+    /// [`Tenant::timelines`].
+    pub(crate) wal_ingest_throttle: Arc<throttle::Throttle>,
+
+    pub(crate) wal_ingest_throttle_metrics: Arc<crate::metrics::tenant_throttling::WalIngest>,
+
     /// An ongoing timeline detach concurrency limiter.
     ///
     /// As a tenant will likely be restarted as part of timeline detach ancestor it makes no sense
@@ -741,6 +758,9 @@ pub enum DeleteTimelineError {
     #[error("HasChildren")]
     HasChildren(Vec<TimelineId>),
 
+    #[error("HasCrossTenantChildren")]
+    HasCrossTenantChildren(Vec<TenantTimelineId>),
+
     #[error("Timeline deletion is already in progress")]
     AlreadyInProgress(Arc<tokio::sync::Mutex<DeleteTimelineFlow>>),
 
@@ -756,6 +776,9 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::NotFound => write!(f, "NotFound"),
             Self::HasChildren(c) => f.debug_tuple("HasChildren").field(c).finish(),
+            Self::HasCrossTenantChildren(c) => {
+                f.debug_tuple("HasCrossTenantChildren").field(c).finish()
+            }
             Self::AlreadyInProgress(_) => f.debug_tuple("AlreadyInProgress").finish(),
             Self::Cancelled => f.debug_tuple("Cancelled").finish(),
             Self::Other(e) => f.debug_tuple("Other").field(e).finish(),
@@ -845,6 +868,7 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 pub(crate) enum CreateTimelineParams {
     Bootstrap(CreateTimelineParamsBootstrap),
     Branch(CreateTimelineParamsBranch),
+    CrossTenantBranch(CreateTimelineParamsCrossTenantBranch),
     ImportPgdata(CreateTimelineParamsImportPgdata),
 }
 
@@ -863,6 +887,15 @@ pub(crate) struct CreateTimelineParamsBranch {
     pub(crate) ancestor_start_lsn: Option<Lsn>,
 }
 
+/// The ancestor lives in another tenant, whose shard with our shard index is attached to this
+/// pageserver. See [`timeline::cross_tenant_branch`].
+#[derive(Debug)]
+pub(crate) struct CreateTimelineParamsCrossTenantBranch {
+    pub(crate) new_timeline_id: TimelineId,
+    pub(crate) ancestor_timeline: Arc<Timeline>,
+    pub(crate) ancestor_start_lsn: Option<Lsn>,
+}
+
 #[derive(Debug)]
 pub(crate) struct CreateTimelineParamsImportPgdata {
     pub(crate) new_timeline_id: TimelineId,
@@ -900,6 +933,11 @@ pub(crate) enum CreateTimelineIdempotency {
         ancestor_timeline_id: TimelineId,
         ancestor_start_lsn: Lsn,
     },
+    CrossTenantBranch {
+        ancestor_tenant_id: TenantId,
+        ancestor_timeline_id: TimelineId,
+        ancestor_start_lsn: Lsn,
+    },
     ImportPgdata(CreatingTimelineIdempotencyImportPgdata),
 }
 
@@ -1106,7 +1144,13 @@ async fn timeline_init_and_sync(
                 })
             }
             None => {
-                if metadata.ancestor_timeline().is_none() {
+                if let Some(ancestor) = &index_part.cross_tenant_ancestor {
+                    CreateTimelineIdempotency::CrossTenantBranch {
+                        ancestor_tenant_id: ancestor.tenant_id,
+                        ancestor_timeline_id: ancestor.timeline_id,
+                        ancestor_start_lsn: ancestor.lsn,
+                    }
+                } else if metadata.ancestor_timeline().is_none() {
                     CreateTimelineIdempotency::Bootstrap {
                         pg_version: metadata.pg_version(),
                     }
@@ -1144,6 +1188,18 @@ async fn timeline_init_and_sync(
 
         timeline.remote_client.init_upload_queue(&index_part)?;
 
+        // Restore points below the GC cutoff must stay readable before GC refreshes them.
+        {
+            let mut restore_points = index_part
+                .restore_points
+                .iter()
+                .map(|p| p.lsn)
+                .collect::<Vec<_>>();
+            restore_points.sort();
+            restore_points.dedup();
+            timeline.gc_info.write().unwrap().restore_points = restore_points;
+        }
+
         timeline
             .load_layer_map(disk_consistent_lsn, index_part)
             .await
@@ -2659,6 +2715,71 @@ pub(crate) async fn create_timeline(
                 self.branch_timeline(&ancestor_timeline, new_timeline_id, ancestor_start_lsn, ctx)
                     .await?
             }
+            CreateTimelineParams::CrossTenantBranch(CreateTimelineParamsCrossTenantBranch {
+                new_timeline_id,
+                ancestor_timeline,
+                mut ancestor_start_lsn,
+            }) => {
+                if !ancestor_timeline.is_active() {
+                    return Err(CreateTimelineError::AncestorNotActive);
+                }
+
+                if ancestor_timeline.is_archived() == Some(true) {
+                    info!("tried to branch archived timeline");
+                    return Err(CreateTimelineError::AncestorArchived);
+                }
+
+                // The layers of the ancestor are referenced by their remote path, which must be
+                // readable with our sharding.
+                if ancestor_timeline.get_shard_identity() != &self.shard_identity {
+                    return Err(CreateTimelineError::Other(anyhow::anyhow!(
+                        "ancestor tenant shard {} is not sharded like {}",
+                        ancestor_timeline.tenant_shard_id,
+                        self.tenant_shard_id
+                    )));
+                }
+
+                // Only the layers of the ancestor itself are referenced, not the ones of its own
+                // ancestors.
+                if ancestor_timeline.get_ancestor_timeline_id().is_some()
+                    || ancestor_timeline
+                        .remote_client
+                        .cross_tenant_ancestor()
+                        .is_some()
+                {
+                    return Err(CreateTimelineError::Other(anyhow::anyhow!(
+                        "cannot branch off timeline {} across tenants: it is a branch itself",
+                        ancestor_timeline.timeline_id
+                    )));
+                }
+
+                if let Some(lsn) = ancestor_start_lsn.as_mut() {
+                    *lsn = lsn.align();
+
+                    ancestor_timeline
+                        .wait_lsn(
+                            *lsn,
+                            timeline::WaitLsnWaiter::Tenant,
+                            timeline::WaitLsnTimeout::Default,
+                            ctx,
+                        )
+                        .await
+                        .map_err(|e| match e {
+                            e @ (WaitLsnError::Timeout(_) | WaitLsnError::BadState { .. }) => {
+                                CreateTimelineError::AncestorLsn(anyhow::anyhow!(e))
+                            }
+                            WaitLsnError::Shutdown => CreateTimelineError::ShuttingDown,
+                        })?;
+                }
+
+                self.cross_tenant_branch_timeline(
+                    &ancestor_timeline,
+                    new_timeline_id,
+                    ancestor_start_lsn,
+                    ctx,
+                )
+                .await?
+            }
             CreateTimelineParams::ImportPgdata(params) => {
                 self.create_timeline_import_pgdata(
                     params,
@@ -4034,6 +4155,34 @@ pub fn get_min_resident_size_override(&self) -> Option<u64> {
             .or(self.conf.default_tenant_conf.min_resident_size_override)
     }
 
+    pub fn get_max_resident_size(&self) -> Option<u64> {
+        let tenant_conf = self.tenant_conf.load().tenant_conf.clone();
+        tenant_conf
+            .max_resident_size
+            .or(self.conf.default_tenant_conf.max_resident_size)
+    }
+
+    pub fn get_scrub_period(&self) -> Duration {
+        let tenant_conf = self.tenant_conf.load().tenant_conf.clone();
+        tenant_conf
+            .scrub_period
+            .unwrap_or(self.conf.default_tenant_conf.scrub_period)
+    }
+
+    pub fn get_scrub_bytes_per_second(&self) -> NonZeroU64 {
+        let tenant_conf = self.tenant_conf.load().tenant_conf.clone();
+        tenant_conf
+            .scrub_bytes_per_second
+            .unwrap_or(self.conf.default_tenant_conf.scrub_bytes_per_second)
+    }
+
+    pub fn get_eviction_priority(&self) -> u8 {
+        let tenant_conf = self.tenant_conf.load().tenant_conf.clone();
+        tenant_conf
+            .eviction_priority
+            .unwrap_or(self.conf.default_tenant_conf.eviction_priority)
+    }
+
     pub fn get_heatmap_period(&self) -> Option<Duration> {
         let tenant_conf = self.tenant_conf.load().tenant_conf.clone();
         let heatmap_period = tenant_conf
@@ -4135,16 +4284,28 @@ pub(crate) fn set_new_location_config(&self, new_conf: AttachedTenantConf) {
     fn get_pagestream_throttle_config(
         psconf: &'static PageServerConf,
         overrides: &pageserver_api::models::TenantConfig,
-    ) -> throttle::Config {
+    ) -> pageserver_api::models::ThrottleConfig {
         overrides
             .timeline_get_throttle
             .clone()
             .unwrap_or(psconf.default_tenant_conf.timeline_get_throttle.clone())
     }
 
+    fn get_wal_ingest_throttle_config(
+        psconf: &'static PageServerConf,
+        overrides: &pageserver_api::models::TenantConfig,
+    ) -> pageserver_api::models::WalIngestThrottleConfig {
+        overrides
+            .wal_ingest_throttle
+            .clone()
+            .unwrap_or(psconf.default_tenant_conf.wal_ingest_throttle.clone())
+    }
+
     pub(crate) fn tenant_conf_updated(&self, new_conf: &pageserver_api::models::TenantConfig) {
         let conf = Self::get_pagestream_throttle_config(self.conf, new_conf);
-        self.pagestream_throttle.reconfigure(conf)
+        self.pagestream_throttle.reconfigure(conf);
+        let conf = Self::get_wal_ingest_throttle_config(self.conf, new_conf);
+        self.wal_ingest_throttle.reconfigure(conf);
     }
 
     /// Helper function to create a new Timeline struct.
@@ -4317,6 +4478,8 @@ fn new(
                 Some(Duration::from_secs(3600 * 24)),
             )),
             l0_compaction_trigger: Arc::new(Notify::new()),
+            disk_quota: Arc::new(DiskQuota::default()),
+            scrub_status: std::sync::Mutex::new(TenantScrubStatus::default()),
             scheduled_compaction_tasks: Mutex::new(Default::default()),
             activate_now_sem: tokio::sync::Semaphore::new(0),
             attach_wal_lag_cooldown: Arc::new(std::sync::OnceLock::new()),
@@ -4328,6 +4491,12 @@ fn new(
             pagestream_throttle_metrics: Arc::new(
                 crate::metrics::tenant_throttling::Pagestream::new(&tenant_shard_id),
             ),
+            wal_ingest_throttle: Arc::new(throttle::Throttle::new(
+                Tenant::get_wal_ingest_throttle_config(conf, &attached_conf.tenant_conf),
+            )),
+            wal_ingest_throttle_metrics: Arc::new(
+                crate::metrics::tenant_throttling::WalIngest::new(&tenant_shard_id),
+            ),
             tenant_conf: Arc::new(ArcSwap::from_pointee(attached_conf)),
             ongoing_timeline_detach: std::sync::Mutex::default(),
             gc_block: Default::default(),
@@ -4621,21 +4790,37 @@ async fn refresh_gc_info_internal(
 
         let mut gc_cutoffs: HashMap<TimelineId, GcCutoffs> =
             HashMap::with_capacity(timelines.len());
+        let mut restore_points: HashMap<TimelineId, Vec<Lsn>> =
+            HashMap::with_capacity(timelines.len());
 
         // Ensures all timelines use the same start time when computing the time cutoff.
         let now_ts_for_pitr_calc = SystemTime::now();
         for timeline in timelines.iter() {
             let ctx = &ctx.with_scope_timeline(timeline);
+            let retention_policy = timeline.get_retention_policy().unwrap_or_default();
+            let horizon = retention_policy.gc_horizon.unwrap_or(horizon);
+            let pitr = retention_policy.pitr_interval.unwrap_or(pitr);
+
             let cutoff = timeline
                 .get_last_record_lsn()
                 .checked_sub(horizon)
                 .unwrap_or(Lsn(0));
 
-            let cutoffs = timeline
+            let mut cutoffs = timeline
                 .find_gc_cutoffs(now_ts_for_pitr_calc, cutoff, pitr, cancel, ctx)
                 .await?;
+
+            let timeline_restore_points = timeline
+                .refresh_restore_points(now_ts_for_pitr_calc, pitr, cancel, ctx)
+                .await?;
+            timeline_restore_points.clamp_gc_cutoffs(&mut cutoffs);
+            restore_points.insert(timeline.timeline_id, timeline_restore_points.lsns);
+
             let old = gc_cutoffs.insert(timeline.timeline_id, cutoffs);
             assert!(old.is_none());
+
+            // Before the expired leases are culled below.
+            timeline::cross_tenant_branch::refresh_children(timeline, cancel, ctx).await?;
         }
 
         if !self.is_active() || self.cancel.is_cancelled() {
@@ -4703,6 +4888,9 @@ async fn refresh_gc_info_internal(
                         time: Lsn(cutoffs.time.0.max(original_cutoffs.time.0)),
                     }
                 }
+                if let Some(lsns) = restore_points.remove(&timeline.timeline_id) {
+                    target.restore_points = lsns;
+                }
             }
 
             gc_timelines.push(timeline);
@@ -4848,9 +5036,9 @@ async fn branch_timeline_impl(
         {
             let gc_info = src_timeline.gc_info.read().unwrap();
             let planned_cutoff = gc_info.min_cutoff();
-            if gc_info.lsn_covered_by_lease(start_lsn) {
+            if gc_info.lsn_covered_by_lease_or_restore_point(start_lsn) {
                 tracing::info!(
-                    "skipping comparison of {start_lsn} with gc cutoff {} and planned gc cutoff {planned_cutoff} due to lsn lease",
+                    "skipping comparison of {start_lsn} with gc cutoff {} and planned gc cutoff {planned_cutoff} due to lsn lease or restore point",
                     *applied_gc_cutoff_lsn
                 );
             } else {
@@ -4931,6 +5119,108 @@ async fn branch_timeline_impl(
         Ok(CreateTimelineResult::Created(new_timeline))
     }
 
+    /// Branch a new root timeline off a timeline of another tenant, see
+    /// [`timeline::cross_tenant_branch`].
+    async fn cross_tenant_branch_timeline(
+        self: &Arc<Self>,
+        src_timeline: &Arc<Timeline>,
+        dst_id: TimelineId,
+        start_lsn: Option<Lsn>,
+        ctx: &RequestContext,
+    ) -> Result<CreateTimelineResult, CreateTimelineError> {
+        let src_tenant_id = src_timeline.tenant_shard_id.tenant_id;
+        let src_id = src_timeline.timeline_id;
+
+        let start_lsn = start_lsn.unwrap_or_else(|| {
+            let lsn = src_timeline.get_last_record_lsn();
+            info!(
+                "branching timeline {dst_id} from timeline {src_tenant_id}/{src_id} at last record LSN: {lsn}"
+            );
+            lsn
+        });
+
+        let timeline_create_guard = match self
+            .start_creating_timeline(
+                dst_id,
+                CreateTimelineIdempotency::CrossTenantBranch {
+                    ancestor_tenant_id: src_tenant_id,
+                    ancestor_timeline_id: src_id,
+                    ancestor_start_lsn: start_lsn,
+                },
+            )
+            .await?
+        {
+            StartCreatingTimelineResult::CreateGuard(guard) => guard,
+            StartCreatingTimelineResult::Idempotent(timeline) => {
+                return Ok(CreateTimelineResult::Idempotent(timeline));
+            }
+        };
+
+        // Held until the branch is durable, see `cross_tenant_branch::refresh_children`.
+        let ancestor_gc_guard =
+            timeline::cross_tenant_branch::lock_ancestor_gc(src_timeline).await?;
+
+        let RecordLsn {
+            last: src_last,
+            prev: src_prev,
+        } = src_timeline.get_last_record_rlsn();
+        let dst_prev = if src_last == start_lsn {
+            Some(src_prev)
+        } else {
+            None
+        };
+
+        // A root timeline: its history up to the branch point is in the referenced layers.
+        let metadata = TimelineMetadata::new(
+            start_lsn,
+            dst_prev,
+            None,
+            Lsn(0),
+            *src_timeline.applied_gc_cutoff_lsn.read(),
+            src_timeline.initdb_lsn,
+            src_timeline.pg_version,
+        );
+
+        let (mut uninitialized_timeline, _timeline_ctx) = self
+            .prepare_new_timeline(
+                dst_id,
+                &metadata,
+                timeline_create_guard,
+                start_lsn + 1,
+                None,
+                Some(src_timeline.get_rel_size_v2_status()),
+                ctx,
+            )
+            .await?;
+
+        let ancestor_gc = &ancestor_gc_guard;
+        uninitialized_timeline
+            .write(|timeline| async move {
+                timeline::cross_tenant_branch::adopt_ancestor_layers(
+                    &timeline,
+                    src_timeline,
+                    start_lsn,
+                    ancestor_gc,
+                    ctx,
+                )
+                .await
+            })
+            .await?;
+
+        let new_timeline = uninitialized_timeline.finish_creation().await?;
+
+        new_timeline
+            .remote_client
+            .wait_completion()
+            .await
+            .map_err(|_| CreateTimelineError::ShuttingDown)?;
+        drop(ancestor_gc_guard);
+
+        // Callers are responsible for activating the timeline.
+
+        Ok(CreateTimelineResult::Created(new_timeline))
+    }
+
     /// For unit tests, make this visible so that other modules can directly create timelines
     #[cfg(test)]
     #[tracing::instrument(skip_all, fields(tenant_id=%self.tenant_shard_id.tenant_id, shard_id=%self.tenant_shard_id.shard_slug(), %timeline_id))]
@@ -5250,7 +5540,10 @@ fn get_timeline_resources_for(&self, remote_client: RemoteTimelineClient) -> Tim
             remote_client,
             pagestream_throttle: self.pagestream_throttle.clone(),
             pagestream_throttle_metrics: self.pagestream_throttle_metrics.clone(),
+            wal_ingest_throttle: self.wal_ingest_throttle.clone(),
+            wal_ingest_throttle_metrics: self.wal_ingest_throttle_metrics.clone(),
             l0_compaction_trigger: self.l0_compaction_trigger.clone(),
+            disk_quota: self.disk_quota.clone(),
             l0_flush_global_state: self.l0_flush_global_state.clone(),
         }
     }
@@ -9062,6 +9355,7 @@ fn get_key(id: u32) -> Key {
                 },
                 leases: Default::default(),
                 within_ancestor_pitr: false,
+                restore_points: Vec::new(),
             };
         }
 
@@ -9606,6 +9900,7 @@ fn get_key(id: u32) -> Key {
                 },
                 leases: Default::default(),
                 within_ancestor_pitr: false,
+                restore_points: Vec::new(),
             };
         }
 
@@ -9855,6 +10150,7 @@ fn get_key(id: u32) -> Key {
                 },
                 leases: Default::default(),
                 within_ancestor_pitr: false,
+                restore_points: Vec::new(),
             };
         }
 
@@ -10104,6 +10400,7 @@ fn get_key(id: u32) -> Key {
                 },
                 leases: Default::default(),
                 within_ancestor_pitr: false,
+                restore_points: Vec::new(),
             };
         }
 
@@ -10124,6 +10421,7 @@ fn get_key(id: u32) -> Key {
                 },
                 leases: Default::default(),
                 within_ancestor_pitr: false,
+                restore_points: Vec::new(),
             };
         }
 
@@ -10634,6 +10932,7 @@ fn get_key(id: u32) -> Key {
                 },
                 leases: Default::default(),
                 within_ancestor_pitr: false,
+                restore_points: Vec::new(),
             };
         }
 
@@ -11023,6 +11322,7 @@ fn get_key(id: u32) -> Key {
                 },
                 leases: Default::default(),
                 within_ancestor_pitr: false,
+                restore_points: Vec::new(),
             };
         }
 
@@ -11275,6 +11575,7 @@ fn get_key(id: u32) -> Key {
                 },
                 leases: Default::default(),
                 within_ancestor_pitr: false,
+                restore_points: Vec::new(),
             };
         }
 
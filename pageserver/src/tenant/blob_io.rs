@@ -7,15 +7,22 @@
 //! is written as a four-byte integer, in big-endian, with the high
 //! bit set. This way, we can detect whether it's 1- or 4-byte header
 //! by peeking at the first byte. For blobs larger than 128 bits,
-//! we also specify three reserved bits, only one of the three bit
-//! patterns is currently in use (0b011) and signifies compression
-//! with zstd.
+//! we also specify three reserved bits, three of the bit patterns are
+//! currently in use: 0b001 signifies compression with zstd, 0b010
+//! signifies compression with zstd using a dictionary that is stored
+//! elsewhere in the same layer file (see [`ZstdDictionary`]), and 0b011
+//! signifies LZ4 block compression, with the uncompressed size prepended.
 //!
 //! len <  128: 0XXXXXXX
 //! len >= 128: 1CCCXXXX XXXXXXXX XXXXXXXX XXXXXXXX
 //!
+//! If the layer file was written with checksums, every blob is followed by
+//! a 4-byte big-endian crc32c of the data as stored, i.e. after compression.
+//! The length field does not include the checksum. Whether a file has
+//! checksums is recorded in its summary, readers have to be told about it.
+//!
 use std::cmp::min;
-use std::io::{Error, ErrorKind};
+use std::io::{Error, ErrorKind, Read};
 
 use async_compression::Level;
 use bytes::{BufMut, BytesMut};
@@ -99,7 +106,17 @@ pub async fn read_blob_into_buf(
             }
             buf_to_write = dstbuf;
             None
-        } else if compression_bits == BYTE_ZSTD {
+        } else if compression_bits == BYTE_ZSTD || compression_bits == BYTE_LZ4 {
+            buf_to_write = &mut tmp_buf;
+            Some(dstbuf)
+        } else if compression_bits == BYTE_ZSTD_DICT {
+            if self.zstd_dict.is_none() {
+                let error = std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "blob compressed with zstd dictionary, but no dictionary available",
+                );
+                return Err(error);
+            }
             buf_to_write = &mut tmp_buf;
             Some(dstbuf)
         } else {
@@ -130,11 +147,34 @@ pub async fn read_blob_into_buf(
             off += this_blk_len;
         }
 
+        if self.verify_checksums {
+            let mut checksum_buf = [0u8; BLOB_CHECKSUM_SIZE];
+            let mut filled = 0;
+            while filled < BLOB_CHECKSUM_SIZE {
+                if off == PAGE_SZ {
+                    // continue on next page
+                    blknum += 1;
+                    buf = self.read_blk(blknum, ctx).await?;
+                    off = 0;
+                }
+                let this_blk_len = min(BLOB_CHECKSUM_SIZE - filled, PAGE_SZ - off);
+                checksum_buf[filled..filled + this_blk_len]
+                    .copy_from_slice(&buf[off..off + this_blk_len]);
+                filled += this_blk_len;
+                off += this_blk_len;
+            }
+            verify_blob_checksum(buf_to_write, u32::from_be_bytes(checksum_buf))?;
+        }
+
         if let Some(dstbuf) = compression {
             if compression_bits == BYTE_ZSTD {
                 let mut decoder = async_compression::tokio::write::ZstdDecoder::new(dstbuf);
                 decoder.write_all(buf_to_write).await?;
                 decoder.flush().await?;
+            } else if compression_bits == BYTE_LZ4 {
+                *dstbuf = decompress_lz4(buf_to_write)?;
+            } else if let Some(zstd_dict) = self.zstd_dict {
+                zstd_dict.decompress(buf_to_write, dstbuf)?;
             } else {
                 unreachable!("already checked above")
             }
@@ -153,6 +193,167 @@ pub async fn read_blob_into_buf(
 
 pub(super) const BYTE_UNCOMPRESSED: u8 = 0x80;
 pub(super) const BYTE_ZSTD: u8 = BYTE_UNCOMPRESSED | 0x10;
+pub(super) const BYTE_ZSTD_DICT: u8 = BYTE_UNCOMPRESSED | 0x20;
+pub(super) const BYTE_LZ4: u8 = BYTE_UNCOMPRESSED | 0x30;
+
+/// Size of the crc32c that follows every blob in layer files written with checksums.
+pub(crate) const BLOB_CHECKSUM_SIZE: usize = 4;
+
+/// The crc32c stored after a blob does not match the blob's contents: the layer file is corrupt.
+#[derive(Debug, thiserror::Error)]
+#[error("blob checksum mismatch: stored {expected:#010x}, computed {actual:#010x}")]
+pub(crate) struct BlobChecksumMismatch {
+    expected: u32,
+    actual: u32,
+}
+
+impl BlobChecksumMismatch {
+    /// Returns true if `err` was returned because a blob failed checksum verification.
+    pub(crate) fn is_cause_of(err: &Error) -> bool {
+        err.get_ref().is_some_and(|inner| inner.is::<Self>())
+    }
+}
+
+/// Verify the stored, possibly compressed, bytes of a blob against the checksum written after it.
+pub(crate) fn verify_blob_checksum(stored: &[u8], expected: u32) -> Result<(), Error> {
+    let actual = crc32c::crc32c(stored);
+    if actual != expected {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            BlobChecksumMismatch { expected, actual },
+        ));
+    }
+    Ok(())
+}
+
+/// Decompress a blob that was written with [`BYTE_LZ4`].
+///
+/// The blob starts with the decompressed size, which is checked against
+/// [`MAX_SUPPORTED_BLOB_LEN`] before allocating the output buffer: no blob
+/// that large could have been written.
+pub(crate) fn decompress_lz4(src: &[u8]) -> Result<Vec<u8>, Error> {
+    let Some(size_prefix) = src.first_chunk::<4>() else {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "lz4 blob too short for its size prefix",
+        ));
+    };
+    let size = u32::from_le_bytes(*size_prefix) as usize;
+    if size > MAX_SUPPORTED_BLOB_LEN {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("lz4 blob decompresses to {size} bytes, more than any blob can have"),
+        ));
+    }
+    lz4_flex::block::decompress_size_prepended(src)
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Compress `src` with plain zstd, at zstd's default level if `level` is not set.
+async fn compress_zstd(src: &[u8], level: Option<i8>) -> Vec<u8> {
+    let mut encoder = if let Some(level) = level {
+        async_compression::tokio::write::ZstdEncoder::with_quality(
+            Vec::new(),
+            Level::Precise(level.into()),
+        )
+    } else {
+        async_compression::tokio::write::ZstdEncoder::new(Vec::new())
+    };
+    encoder.write_all(src).await.unwrap();
+    encoder.shutdown().await.unwrap();
+    encoder.into_inner()
+}
+
+/// A zstd dictionary trained on the values of a single layer file.
+///
+/// Blobs compressed with it are marked with [`BYTE_ZSTD_DICT`], and can only be
+/// decompressed by a reader that has loaded the dictionary from the same file.
+/// Layer files store it in its own blocks after the index, see [`Self::to_blocks`],
+/// and record its location in their summary.
+pub struct ZstdDictionary {
+    raw: Vec<u8>,
+    decoder: zstd::dict::DecoderDictionary<'static>,
+}
+
+impl ZstdDictionary {
+    /// Minimum number of samples we require before attempting to train a dictionary.
+    /// With fewer samples zstd either fails or produces a dictionary that isn't worth it.
+    const MIN_TRAINING_SAMPLES: usize = 16;
+
+    /// Maximum size of a trained dictionary. This is zstd's default: dictionaries much
+    /// smaller than that don't capture enough of the data to pay off.
+    pub(crate) const MAX_SIZE: usize = 112 * 1024;
+
+    /// Amount of data layer writers buffer as training samples before training the
+    /// dictionary; zstd recommends about a hundred times the dictionary size.
+    pub(crate) const SAMPLE_BYTES: usize = 100 * Self::MAX_SIZE;
+
+    pub fn new(raw: Vec<u8>) -> Self {
+        let decoder = zstd::dict::DecoderDictionary::copy(&raw);
+        Self { raw, decoder }
+    }
+
+    /// Train a dictionary of at most `max_size` bytes on the given samples.
+    ///
+    /// Returns `None` if there are too few samples or training fails, in which
+    /// case the caller should go on without a dictionary.
+    pub fn train<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> Option<Self> {
+        if samples.len() < Self::MIN_TRAINING_SAMPLES {
+            return None;
+        }
+        match zstd::dict::from_samples(samples, max_size) {
+            Ok(raw) if !raw.is_empty() => Some(Self::new(raw)),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("failed to train zstd dictionary: {e}");
+                None
+            }
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// The dictionary as it is written to a layer file: zero padded to whole blocks.
+    pub(crate) fn to_blocks(&self) -> Vec<u8> {
+        let mut buf = self.raw.clone();
+        buf.resize(self.raw.len().next_multiple_of(PAGE_SZ), 0);
+        buf
+    }
+
+    /// Read a dictionary of `len` bytes written with [`Self::to_blocks`], starting at `start_blk`.
+    pub(crate) async fn read(
+        reader: &BlockCursor<'_>,
+        start_blk: u32,
+        len: u32,
+        ctx: &RequestContext,
+    ) -> Result<Self, Error> {
+        if len as usize > Self::MAX_SIZE {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("zstd dictionary of {len} bytes is too large"),
+            ));
+        }
+        let mut raw = Vec::with_capacity(len as usize);
+        let mut blknum = start_blk;
+        while raw.len() < len as usize {
+            let buf = reader.read_blk(blknum, ctx).await?;
+            let this_blk_len = min(len as usize - raw.len(), PAGE_SZ);
+            raw.extend_from_slice(&buf[..this_blk_len]);
+            blknum += 1;
+        }
+        Ok(Self::new(raw))
+    }
+
+    /// Decompress a blob that was written with [`BYTE_ZSTD_DICT`], appending to `dstbuf`.
+    pub(crate) fn decompress(&self, src: &[u8], dstbuf: &mut Vec<u8>) -> Result<(), Error> {
+        let mut decoder =
+            zstd::stream::read::Decoder::with_prepared_dictionary(src, &self.decoder)?;
+        decoder.read_to_end(dstbuf)?;
+        Ok(())
+    }
+}
 
 /// A wrapper of `VirtualFile` that allows users to write blobs.
 ///
@@ -166,6 +367,10 @@ pub struct BlobWriter<const BUFFERED: bool> {
     buf: Vec<u8>,
     /// We do tiny writes for the length headers; they need to be in an owned buffer;
     io_buf: Option<BytesMut>,
+    /// Used for [`ImageCompressionAlgorithm::ZstdDict`], if a dictionary has been set.
+    zstd_dict_compressor: Option<zstd::bulk::Compressor<'static>>,
+    /// Write a crc32c after every blob, see [`Self::enable_checksums`].
+    checksums: bool,
 }
 
 impl<const BUFFERED: bool> BlobWriter<BUFFERED> {
@@ -175,6 +380,8 @@ pub fn new(inner: VirtualFile, start_offset: u64) -> Self {
             offset: start_offset,
             buf: Vec::with_capacity(Self::CAPACITY),
             io_buf: Some(BytesMut::new()),
+            zstd_dict_compressor: None,
+            checksums: false,
         }
     }
 
@@ -182,6 +389,45 @@ pub fn size(&self) -> u64 {
         self.offset
     }
 
+    /// Use `dict` for all subsequent blobs written with [`ImageCompressionAlgorithm::ZstdDict`].
+    ///
+    /// Without a dictionary, such blobs are compressed with plain zstd instead.
+    pub(crate) fn set_zstd_dictionary(
+        &mut self,
+        dict: &ZstdDictionary,
+        level: Option<i8>,
+    ) -> Result<(), Error> {
+        // Level 0 selects zstd's default level.
+        let level = level.map(i32::from).unwrap_or(0);
+        self.zstd_dict_compressor = Some(zstd::bulk::Compressor::with_dictionary(
+            level,
+            dict.as_bytes(),
+        )?);
+        Ok(())
+    }
+
+    /// Compress `src` with the dictionary set with [`Self::set_zstd_dictionary`].
+    ///
+    /// Returns `None` if there is no dictionary or compressing with it failed, in which
+    /// case the caller falls back to plain zstd.
+    fn compress_with_zstd_dict(&mut self, src: &[u8]) -> Option<Vec<u8>> {
+        let compressor = self.zstd_dict_compressor.as_mut()?;
+        match compressor.compress(src) {
+            Ok(compressed) => Some(compressed),
+            Err(e) => {
+                // Not worth failing the write for, use plain zstd instead.
+                warn!("failed to compress blob with zstd dictionary: {e}");
+                None
+            }
+        }
+    }
+
+    /// Follow every blob with a crc32c of its stored bytes. Must be called before the first blob
+    /// is written; the caller records in the file that readers have to verify the checksums.
+    pub(crate) fn enable_checksums(&mut self) {
+        self.checksums = true;
+    }
+
     const CAPACITY: usize = if BUFFERED { 64 * 1024 } else { 0 };
 
     /// Writes the given buffer directly to the underlying `VirtualFile`.
@@ -339,30 +585,35 @@ pub(crate) async fn write_blob_maybe_compressed<Buf: IoBuf + Send>(
                         srcbuf,
                     );
                 }
-                let (high_bit_mask, len_written, srcbuf) = match algorithm {
+                let compressed = match algorithm {
+                    ImageCompressionAlgorithm::ZstdDict { level } => {
+                        match self.compress_with_zstd_dict(&srcbuf[..]) {
+                            Some(compressed) => Some((BYTE_ZSTD_DICT, compressed)),
+                            None => Some((BYTE_ZSTD, compress_zstd(&srcbuf[..], level).await)),
+                        }
+                    }
                     ImageCompressionAlgorithm::Zstd { level } => {
-                        let mut encoder = if let Some(level) = level {
-                            async_compression::tokio::write::ZstdEncoder::with_quality(
-                                Vec::new(),
-                                Level::Precise(level.into()),
-                            )
-                        } else {
-                            async_compression::tokio::write::ZstdEncoder::new(Vec::new())
-                        };
-                        encoder.write_all(&srcbuf[..]).await.unwrap();
-                        encoder.shutdown().await.unwrap();
-                        let compressed = encoder.into_inner();
+                        Some((BYTE_ZSTD, compress_zstd(&srcbuf[..], level).await))
+                    }
+                    ImageCompressionAlgorithm::Lz4 => Some((
+                        BYTE_LZ4,
+                        lz4_flex::block::compress_prepend_size(&srcbuf[..]),
+                    )),
+                    ImageCompressionAlgorithm::Disabled => None,
+                };
+                let (high_bit_mask, len_written) = match compressed {
+                    Some((compression_bits, compressed)) => {
                         compression_info.compressed_size = Some(compressed.len());
                         if compressed.len() < len {
                             compression_info.written_compressed = true;
                             let compressed_len = compressed.len();
                             compressed_buf = Some(compressed);
-                            (BYTE_ZSTD, compressed_len, srcbuf)
+                            (compression_bits, compressed_len)
                         } else {
-                            (BYTE_UNCOMPRESSED, len, srcbuf)
+                            (BYTE_UNCOMPRESSED, len)
                         }
                     }
-                    ImageCompressionAlgorithm::Disabled => (BYTE_UNCOMPRESSED, len, srcbuf),
+                    None => (BYTE_UNCOMPRESSED, len),
                 };
                 let mut len_buf = (len_written as u32).to_be_bytes();
                 assert_eq!(len_buf[0] & 0xf0, 0);
@@ -377,13 +628,29 @@ pub(crate) async fn write_blob_maybe_compressed<Buf: IoBuf + Send>(
             Ok(_) => (),
             Err(e) => return (srcbuf, Err(e)),
         }
+        let checksum = self
+            .checksums
+            .then(|| crc32c::crc32c(compressed_buf.as_deref().unwrap_or(&srcbuf[..])));
         let (srcbuf, res) = if let Some(compressed_buf) = compressed_buf {
             let (_buf, res) = self.write_all(compressed_buf.slice_len(), ctx).await;
             (srcbuf, res)
         } else {
             self.write_all(srcbuf, ctx).await
         };
-        (srcbuf, res.map(|_| (offset, compression_info)))
+        if let Err(e) = res {
+            return (srcbuf, Err(e));
+        }
+        if let Some(checksum) = checksum {
+            let mut io_buf = self.io_buf.take().expect("we always put it back below");
+            io_buf.clear();
+            io_buf.put_u32(checksum);
+            let (io_buf_slice, res) = self.write_all(io_buf.slice_len(), ctx).await;
+            self.io_buf = Some(io_buf_slice.into_raw_slice().into_inner());
+            if let Err(e) = res {
+                return (srcbuf, Err(e));
+            }
+        }
+        (srcbuf, Ok((offset, compression_info)))
     }
 }
 
@@ -424,13 +691,25 @@ pub(crate) mod tests {
     use crate::task_mgr::TaskKind;
     use crate::tenant::block_io::BlockReaderRef;
 
-    async fn round_trip_test<const BUFFERED: bool>(blobs: &[Vec<u8>]) -> Result<(), Error> {
-        round_trip_test_compressed::<BUFFERED>(blobs, false).await
+    /// How the blobs of a test are written, and read back.
+    #[derive(Clone, Copy)]
+    pub(crate) struct BlobOptions {
+        pub(crate) algorithm: ImageCompressionAlgorithm,
+        pub(crate) checksums: bool,
+    }
+
+    impl Default for BlobOptions {
+        fn default() -> Self {
+            BlobOptions {
+                algorithm: ImageCompressionAlgorithm::Disabled,
+                checksums: false,
+            }
+        }
     }
 
-    pub(crate) async fn write_maybe_compressed<const BUFFERED: bool>(
+    pub(crate) async fn write_blobs<const BUFFERED: bool>(
         blobs: &[Vec<u8>],
-        compression: bool,
+        opts: BlobOptions,
         ctx: &RequestContext,
     ) -> Result<(Utf8TempDir, Utf8PathBuf, Vec<u64>), Error> {
         let temp_dir = camino_tempfile::tempdir()?;
@@ -441,14 +720,13 @@ pub(crate) async fn write_maybe_compressed<const BUFFERED: bool>(
         {
             let file = VirtualFile::create(pathbuf.as_path(), ctx).await?;
             let mut wtr = BlobWriter::<BUFFERED>::new(file, 0);
+            if opts.checksums {
+                wtr.enable_checksums();
+            }
             for blob in blobs.iter() {
-                let (_, res) = if compression {
+                let (_, res) = if opts.algorithm != ImageCompressionAlgorithm::Disabled {
                     let res = wtr
-                        .write_blob_maybe_compressed(
-                            blob.clone().slice_len(),
-                            ctx,
-                            ImageCompressionAlgorithm::Zstd { level: Some(1) },
-                        )
+                        .write_blob_maybe_compressed(blob.clone().slice_len(), ctx, opts.algorithm)
                         .await;
                     (res.0, res.1.map(|(off, _)| off))
                 } else {
@@ -467,18 +745,21 @@ pub(crate) async fn write_maybe_compressed<const BUFFERED: bool>(
         Ok((temp_dir, pathbuf, offsets))
     }
 
-    async fn round_trip_test_compressed<const BUFFERED: bool>(
+    async fn round_trip_test<const BUFFERED: bool>(
         blobs: &[Vec<u8>],
-        compression: bool,
+        opts: BlobOptions,
     ) -> Result<(), Error> {
         let ctx =
             RequestContext::new(TaskKind::UnitTest, DownloadBehavior::Error).with_scope_unit_test();
-        let (_temp_dir, pathbuf, offsets) =
-            write_maybe_compressed::<BUFFERED>(blobs, compression, &ctx).await?;
+        let (_temp_dir, pathbuf, offsets) = write_blobs::<BUFFERED>(blobs, opts, &ctx).await?;
 
         let file = VirtualFile::open(pathbuf, &ctx).await?;
         let rdr = BlockReaderRef::VirtualFile(&file);
-        let rdr = BlockCursor::new_with_compression(rdr, compression);
+        let rdr = BlockCursor::new_with_compression(
+            rdr,
+            opts.algorithm != ImageCompressionAlgorithm::Disabled,
+        )
+        .with_checksums(opts.checksums);
         for (idx, (blob, offset)) in blobs.iter().zip(offsets.iter()).enumerate() {
             let blob_read = rdr.read_blob(*offset, &ctx).await?;
             assert_eq!(
@@ -497,8 +778,8 @@ pub(crate) fn random_array(len: usize) -> Vec<u8> {
     #[tokio::test]
     async fn test_one() -> Result<(), Error> {
         let blobs = &[vec![12, 21, 22]];
-        round_trip_test::<false>(blobs).await?;
-        round_trip_test::<true>(blobs).await?;
+        round_trip_test::<false>(blobs, BlobOptions::default()).await?;
+        round_trip_test::<true>(blobs, BlobOptions::default()).await?;
         Ok(())
     }
 
@@ -510,10 +791,18 @@ async fn test_hello_simple() -> Result<(), Error> {
             Vec::new(),
             b"foobar".to_vec(),
         ];
-        round_trip_test::<false>(blobs).await?;
-        round_trip_test::<true>(blobs).await?;
-        round_trip_test_compressed::<false>(blobs, true).await?;
-        round_trip_test_compressed::<true>(blobs, true).await?;
+        for algorithm in [
+            ImageCompressionAlgorithm::Disabled,
+            ImageCompressionAlgorithm::Zstd { level: Some(1) },
+            ImageCompressionAlgorithm::Lz4,
+        ] {
+            let opts = BlobOptions {
+                algorithm,
+                ..Default::default()
+            };
+            round_trip_test::<false>(blobs, opts).await?;
+            round_trip_test::<true>(blobs, opts).await?;
+        }
         Ok(())
     }
 
@@ -527,20 +816,95 @@ async fn test_really_big_array() -> Result<(), Error> {
             vec![0xf3; 24 * PAGE_SZ],
             b"foobar".to_vec(),
         ];
-        round_trip_test::<false>(blobs).await?;
-        round_trip_test::<true>(blobs).await?;
-        round_trip_test_compressed::<false>(blobs, true).await?;
-        round_trip_test_compressed::<true>(blobs, true).await?;
+        for algorithm in [
+            ImageCompressionAlgorithm::Disabled,
+            ImageCompressionAlgorithm::Zstd { level: Some(1) },
+            ImageCompressionAlgorithm::Lz4,
+            // Without a dictionary, zstd-dict falls back to plain zstd.
+            ImageCompressionAlgorithm::ZstdDict { level: Some(1) },
+        ] {
+            let opts = BlobOptions {
+                algorithm,
+                ..Default::default()
+            };
+            round_trip_test::<false>(blobs, opts).await?;
+            round_trip_test::<true>(blobs, opts).await?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zstd_dict() -> Result<(), Error> {
+        let ctx =
+            RequestContext::new(TaskKind::UnitTest, DownloadBehavior::Error).with_scope_unit_test();
+        let blobs = (0..256)
+            .map(|i| {
+                format!("key {i:08}, value {:x}; ", i * 7919)
+                    .repeat(16)
+                    .into_bytes()
+            })
+            .collect::<Vec<_>>();
+        let dict = ZstdDictionary::train(&blobs, 4096).expect("enough samples to train on");
+
+        let temp_dir = camino_tempfile::tempdir()?;
+        let pathbuf = temp_dir.path().join("file");
+        let mut offsets = Vec::new();
+        {
+            let file = VirtualFile::create(pathbuf.as_path(), &ctx).await?;
+            let mut wtr = BlobWriter::<true>::new(file, 0);
+            wtr.set_zstd_dictionary(&dict, Some(1))?;
+            for blob in blobs.iter() {
+                let (_, res) = wtr
+                    .write_blob_maybe_compressed(
+                        blob.clone().slice_len(),
+                        &ctx,
+                        ImageCompressionAlgorithm::ZstdDict { level: Some(1) },
+                    )
+                    .await;
+                let (offs, compression_info) = res?;
+                assert!(compression_info.written_compressed);
+                offsets.push(offs);
+            }
+            let (_, res) = wtr.write_blob(vec![0; PAGE_SZ].slice_len(), &ctx).await;
+            res?;
+            wtr.flush_buffer(&ctx).await?;
+        }
+
+        let file = VirtualFile::open(pathbuf, &ctx).await?;
+        let rdr = BlockCursor::new_with_compression(BlockReaderRef::VirtualFile(&file), true)
+            .with_zstd_dict(Some(&dict));
+        for (blob, offset) in blobs.iter().zip(offsets.iter()) {
+            assert_eq!(blob, &rdr.read_blob(*offset, &ctx).await?);
+        }
+
+        // The blobs can't be read without the dictionary.
+        let rdr = BlockCursor::new_with_compression(BlockReaderRef::VirtualFile(&file), true);
+        assert!(rdr.read_blob(offsets[0], &ctx).await.is_err());
         Ok(())
     }
 
+    #[test]
+    fn test_lz4_size_prefix_bounded() {
+        let blob = vec![0x42; 8192];
+        let compressed = lz4_flex::block::compress_prepend_size(&blob);
+        assert_eq!(decompress_lz4(&compressed).unwrap(), blob);
+
+        // A corrupt size prefix must not make us allocate gigabytes.
+        let mut corrupt = compressed.clone();
+        corrupt[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = decompress_lz4(&corrupt).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        assert!(decompress_lz4(&compressed[..2]).is_err());
+    }
+
     #[tokio::test]
     async fn test_arrays_inc() -> Result<(), Error> {
         let blobs = (0..PAGE_SZ / 8)
             .map(|v| random_array(v * 16))
             .collect::<Vec<_>>();
-        round_trip_test::<false>(&blobs).await?;
-        round_trip_test::<true>(&blobs).await?;
+        round_trip_test::<false>(&blobs, BlobOptions::default()).await?;
+        round_trip_test::<true>(&blobs, BlobOptions::default()).await?;
         Ok(())
     }
 
@@ -557,8 +921,8 @@ async fn test_arrays_random_size() -> Result<(), Error> {
                 random_array(sz.into())
             })
             .collect::<Vec<_>>();
-        round_trip_test::<false>(&blobs).await?;
-        round_trip_test::<true>(&blobs).await?;
+        round_trip_test::<false>(&blobs, BlobOptions::default()).await?;
+        round_trip_test::<true>(&blobs, BlobOptions::default()).await?;
         Ok(())
     }
 
@@ -569,8 +933,57 @@ async fn test_arrays_page_boundary() -> Result<(), Error> {
             random_array(PAGE_SZ - 4),
             random_array(PAGE_SZ - 4),
         ];
-        round_trip_test::<false>(blobs).await?;
-        round_trip_test::<true>(blobs).await?;
+        round_trip_test::<false>(blobs, BlobOptions::default()).await?;
+        round_trip_test::<true>(blobs, BlobOptions::default()).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checksums() -> Result<(), Error> {
+        let blobs = &[
+            b"test".to_vec(),
+            Vec::new(),
+            random_array(10 * PAGE_SZ),
+            random_array(PAGE_SZ - 4),
+            vec![0xf3; 24 * PAGE_SZ],
+            b"foobar".to_vec(),
+        ];
+        for algorithm in [
+            ImageCompressionAlgorithm::Disabled,
+            ImageCompressionAlgorithm::Zstd { level: Some(1) },
+            ImageCompressionAlgorithm::Lz4,
+        ] {
+            let opts = BlobOptions {
+                algorithm,
+                checksums: true,
+            };
+            round_trip_test::<false>(blobs, opts).await?;
+            round_trip_test::<true>(blobs, opts).await?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checksum_mismatch() -> Result<(), Error> {
+        let ctx =
+            RequestContext::new(TaskKind::UnitTest, DownloadBehavior::Error).with_scope_unit_test();
+        let blobs = &[random_array(PAGE_SZ), random_array(PAGE_SZ)];
+        let opts = BlobOptions {
+            checksums: true,
+            ..Default::default()
+        };
+        let (_temp_dir, pathbuf, offsets) = write_blobs::<true>(blobs, opts, &ctx).await?;
+
+        // Flip a bit in the middle of the second blob
+        let mut contents = std::fs::read(&pathbuf)?;
+        contents[offsets[1] as usize + 100] ^= 0x01;
+        std::fs::write(&pathbuf, contents)?;
+
+        let file = VirtualFile::open(pathbuf, &ctx).await?;
+        let rdr = BlockCursor::new(BlockReaderRef::VirtualFile(&file)).with_checksums(true);
+        assert_eq!(rdr.read_blob(offsets[0], &ctx).await?, blobs[0]);
+        let err = rdr.read_blob(offsets[1], &ctx).await.unwrap_err();
+        assert!(BlobChecksumMismatch::is_cause_of(&err), "{err}");
         Ok(())
     }
 }
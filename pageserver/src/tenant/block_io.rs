@@ -6,6 +6,7 @@
 
 use bytes::Bytes;
 
+use super::blob_io::ZstdDictionary;
 use super::storage_layer::delta_layer::{Adapter, DeltaLayerInner};
 use crate::context::RequestContext;
 use crate::page_cache::{self, FileId, PAGE_SZ, PageReadGuard, PageWriteGuard, ReadBufResult};
@@ -131,6 +132,10 @@ async fn read_blk(
 ///
 pub struct BlockCursor<'a> {
     pub(super) read_compressed: bool,
+    /// Blobs are followed by a checksum, which is verified on read.
+    pub(super) verify_checksums: bool,
+    /// Dictionary of the layer file, for blobs compressed with a zstd dictionary.
+    pub(super) zstd_dict: Option<&'a ZstdDictionary>,
     reader: BlockReaderRef<'a>,
 }
 
@@ -141,13 +146,27 @@ pub(crate) fn new(reader: BlockReaderRef<'a>) -> Self {
     pub(crate) fn new_with_compression(reader: BlockReaderRef<'a>, read_compressed: bool) -> Self {
         BlockCursor {
             read_compressed,
+            verify_checksums: false,
+            zstd_dict: None,
             reader,
         }
     }
+    /// Verify the checksums of the blobs read, for layer files written with checksums.
+    pub(crate) fn with_checksums(mut self, verify_checksums: bool) -> Self {
+        self.verify_checksums = verify_checksums;
+        self
+    }
+    /// Use the given dictionary to decompress blobs compressed with a zstd dictionary.
+    pub(crate) fn with_zstd_dict(mut self, zstd_dict: Option<&'a ZstdDictionary>) -> Self {
+        self.zstd_dict = zstd_dict;
+        self
+    }
     // Needed by cli
     pub fn new_fileblockreader(reader: &'a FileBlockReader) -> Self {
         BlockCursor {
             read_compressed: false,
+            verify_checksums: false,
+            zstd_dict: None,
             reader: BlockReaderRef::FileBlockReader(reader),
         }
     }
@@ -106,6 +106,9 @@ pub enum DiskBtreeError {
 
     #[error("IoError: {0}")]
     Io(#[from] io::Error),
+
+    #[error("Corrupt index: {0}")]
+    Corrupt(String),
 }
 
 pub type Result<T> = result::Result<T, DiskBtreeError>;
@@ -149,6 +152,12 @@ fn deparse(buf: &[u8]) -> Result<OnDiskNode<L>> {
         let values_len = num_children as usize * VALUE_SZ;
         //off += values_len as u64;
 
+        if values_off + values_len > buf.len() {
+            return Err(DiskBtreeError::Corrupt(format!(
+                "node with {num_children} children of {suffix_len} byte suffixes does not fit into a page"
+            )));
+        }
+
         let prefix = &buf[prefix_off..prefix_off + prefix_len as usize];
         let keys = &buf[keys_off..keys_off + keys_len];
         let values = &buf[values_off..values_off + values_len];
@@ -255,6 +264,97 @@ pub fn new(start_blk: u32, root_blk: u32, reader: R) -> Self {
         Ok(result)
     }
 
+    /// Walk the whole tree and check its structure: every node must be well-formed and visited
+    /// once, levels must decrease by one towards the leaves, and the keys of the leaves must be
+    /// in strictly increasing order. `visitor` is called for every key and value, in key order.
+    ///
+    /// Unlike the other methods, this returns [`DiskBtreeError::Corrupt`] instead of panicking
+    /// on a malformed tree, so it can be used to scrub layer files.
+    pub async fn validate<V>(&self, mut visitor: V, ctx: &RequestContext) -> Result<()>
+    where
+        V: FnMut(&[u8], u64),
+    {
+        let corrupt = |msg: String| Err(DiskBtreeError::Corrupt(msg));
+
+        let block_cursor = self.reader.block_cursor();
+        let mut visited = std::collections::HashSet::new();
+        let mut last_key: Option<Vec<u8>> = None;
+        // block number, level expected from the parent, and the parent's key for it
+        let mut stack: Vec<(u32, Option<u8>, Vec<u8>)> = vec![(self.root_blk, None, Vec::new())];
+        while let Some((node_blknum, expected_level, lower_bound)) = stack.pop() {
+            if !visited.insert(node_blknum) {
+                return corrupt(format!("node {node_blknum} is referenced more than once"));
+            }
+
+            let node_buf = block_cursor
+                .read_blk(self.start_blk + node_blknum, ctx)
+                .await?;
+            let node = OnDiskNode::<L>::deparse(node_buf.as_ref())?;
+            let prefix_len = node.prefix_len as usize;
+            let suffix_len = node.suffix_len as usize;
+
+            if node.num_children == 0 {
+                return corrupt(format!("node {node_blknum} has no children"));
+            }
+            if prefix_len + suffix_len != L {
+                return corrupt(format!(
+                    "node {node_blknum} has a {prefix_len} byte prefix and {suffix_len} byte suffixes for {L} byte keys"
+                ));
+            }
+            if let Some(expected_level) = expected_level {
+                if node.level != expected_level {
+                    return corrupt(format!(
+                        "node {node_blknum} is at level {} instead of {expected_level}",
+                        node.level
+                    ));
+                }
+            }
+
+            let mut keybuf = node.prefix.to_vec();
+            keybuf.resize(L, 0);
+
+            let mut children = Vec::new();
+            for idx in 0..node.num_children as usize {
+                let key_off = idx * suffix_len;
+                keybuf[prefix_len..].copy_from_slice(&node.keys[key_off..key_off + suffix_len]);
+                if keybuf < lower_bound {
+                    return corrupt(format!(
+                        "key {} in node {node_blknum} is below the parent's key {}",
+                        hex::encode(&keybuf),
+                        hex::encode(&lower_bound)
+                    ));
+                }
+
+                let value = node.value(idx);
+                if node.level == 0 {
+                    if let Some(last_key) = &last_key {
+                        if keybuf <= *last_key {
+                            return corrupt(format!(
+                                "key {} in node {node_blknum} is not above the previous key {}",
+                                hex::encode(&keybuf),
+                                hex::encode(last_key)
+                            ));
+                        }
+                    }
+                    visitor(&keybuf, value.to_u64());
+                    last_key = Some(keybuf.clone());
+                } else {
+                    if value.0[0] != 0x80 {
+                        return corrupt(format!(
+                            "internal node {node_blknum} has an invalid child reference"
+                        ));
+                    }
+                    children.push((value.to_blknum(), Some(node.level - 1), keybuf.clone()));
+                }
+            }
+
+            // the stack is popped from the back, so push the children in reverse key order
+            stack.extend(children.into_iter().rev());
+        }
+
+        Ok(())
+    }
+
     pub fn iter<'a>(self, start_key: &'a [u8; L], ctx: &'a RequestContext) -> DiskBtreeIterator<'a>
     where
         R: 'a + Send,
@@ -1205,6 +1305,46 @@ async fn particular_data() -> Result<()> {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn validate() -> Result<()> {
+        let mut disk = TestDisk::new();
+        let mut writer = DiskBtreeBuilder::<_, 8>::new(&mut disk);
+        let ctx =
+            RequestContext::new(TaskKind::UnitTest, DownloadBehavior::Error).with_scope_unit_test();
+
+        const NUM_KEYS: u64 = 1000;
+        for idx in 0..NUM_KEYS {
+            writer.append(&u64::to_be_bytes(idx * 2), idx)?;
+        }
+        let (root_offset, _writer) = writer.finish()?;
+        let mut reader = DiskBtreeReader::<_, 8>::new(0, root_offset, disk);
+
+        let mut values = Vec::new();
+        reader
+            .validate(|_key, value| values.push(value), &ctx)
+            .await?;
+        assert_eq!(values, (0..NUM_KEYS).collect::<Vec<_>>());
+
+        // the leaves are written first, so block 0 is the leftmost leaf
+        let leaf = reader.reader.blocks[0].clone();
+
+        // children not fitting into the page
+        let mut buf = leaf.to_vec();
+        buf[0..2].copy_from_slice(&u16::MAX.to_be_bytes());
+        reader.reader.blocks[0] = Bytes::from(buf);
+        let err = reader.validate(|_, _| {}, &ctx).await.unwrap_err();
+        assert!(matches!(err, DiskBtreeError::Corrupt(_)), "{err}");
+
+        // a leaf claiming to be an internal node
+        let mut buf = leaf.to_vec();
+        buf[2] = 1;
+        reader.reader.blocks[0] = Bytes::from(buf);
+        let err = reader.validate(|_, _| {}, &ctx).await.unwrap_err();
+        assert!(matches!(err, DiskBtreeError::Corrupt(_)), "{err}");
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,331 @@
+//! Per-tenant cap on resident layer bytes.
+//!
+//! The global disk usage based eviction only reacts once the whole filesystem is under pressure,
+//! by which time a single fast growing tenant may already have filled the disk. A tenant shard
+//! with `max_resident_size` configured is instead kept below its own cap: every layer file that
+//! is about to become resident first reserves its on-disk size. If the tenant shard is at its
+//! cap, the reservation wakes up the tenant's disk quota loop, which evicts the least recently
+//! used layers of the tenant until the reservation fits.
+//!
+//! - on-demand downloads, and the chunks of partially downloaded image layers, reserve room
+//!   before downloading, and are refused with [`DownloadError::ResidentSizeQuotaExceeded`] if no
+//!   room is made in time.
+//! - layers written by compaction, image layer creation and imports reserve room before being
+//!   added to the [`LayerManager`], and fail the operation if no room is made in time. It is
+//!   retried by the next iteration.
+//! - layers written by flushes wait for room without a timeout. The flush loop stalls and with
+//!   it ingest, the same way as when there are too many L0 layers.
+//!
+//! Layer files are only counted once they are complete: the temporary files being written by a
+//! flush or a compaction are not part of the resident size.
+//!
+//! Layers that are in use cannot be evicted, so a tenant shard whose working set is larger than
+//! its cap gets its downloads refused.
+//!
+//! [`DownloadError::ResidentSizeQuotaExceeded`]: crate::tenant::storage_layer::layer::DownloadError::ResidentSizeQuotaExceeded
+//! [`LayerManager`]: crate::tenant::timeline::layer_manager::LayerManager
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use pageserver_api::models::TenantDiskQuotaStatus;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+use super::Tenant;
+use crate::disk_usage_eviction_task::EvictionLayer;
+use crate::metrics::{
+    TENANT_DISK_QUOTA_EVICTED_BYTES, TENANT_DISK_QUOTA_EVICTED_LAYERS, TENANT_MAX_RESIDENT_SIZE,
+    TENANT_RESIDENT_SIZE,
+};
+use crate::tenant::storage_layer::{EvictionError, LayerVisibilityHint};
+
+/// Same as the disk usage based eviction: our LRU ordering goes stale fast.
+const EVICTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a reservation waits for the disk quota loop to evict layers.
+const RESERVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Resident layer bytes of a tenant shard, shared by the timelines of the tenant shard.
+#[derive(Default)]
+pub(crate) struct DiskQuota {
+    /// Sum of the timelines' resident physical size.
+    resident_size: AtomicU64,
+    /// Bytes of layers being downloaded or written, which will be resident soon.
+    reserved: AtomicU64,
+    /// Bytes of layers waiting in [`DiskQuota::reserve`] for room to be made.
+    waiting: AtomicU64,
+    /// Wakes up the tenant disk quota loop when layers became resident.
+    pub(crate) evict: Notify,
+    /// Wakes up reservations waiting for room.
+    freed: Notify,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum DiskQuotaError {
+    #[error("tenant shard is at its max_resident_size")]
+    Exceeded,
+    #[error("cancelled")]
+    Cancelled,
+}
+
+/// Room reserved by [`DiskQuota::reserve`], released on drop. Drop it only once the layer is
+/// accounted for in the resident size.
+pub(crate) struct DiskQuotaReservation {
+    quota: Arc<DiskQuota>,
+    size: u64,
+}
+
+impl Drop for DiskQuotaReservation {
+    fn drop(&mut self) {
+        self.quota.reserved.fetch_sub(self.size, Ordering::Relaxed);
+        self.quota.freed.notify_waiters();
+    }
+}
+
+impl DiskQuota {
+    pub(crate) fn resident_size(&self) -> u64 {
+        self.resident_size.load(Ordering::Relaxed)
+    }
+
+    /// The resident size the tenant shard is headed for: resident layers, plus the ones being
+    /// downloaded or written, plus the ones waiting for room.
+    fn wanted_size(&self) -> u64 {
+        self.resident_size()
+            + self.reserved.load(Ordering::Relaxed)
+            + self.waiting.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn resident_size_add(&self, size: u64) {
+        self.resident_size.fetch_add(size, Ordering::Relaxed);
+        self.evict.notify_one();
+    }
+
+    pub(crate) fn resident_size_sub(&self, size: u64) {
+        let _ = self.resident_size.fetch_update(
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            |resident_size| Some(resident_size.saturating_sub(size)),
+        );
+        self.freed.notify_waiters();
+    }
+
+    /// Reserves room for a layer of `size` bytes that is about to become resident. If that would
+    /// take the tenant shard above `max_resident_size`, waits for the disk quota loop to evict
+    /// other layers, for at most [`RESERVE_TIMEOUT`].
+    pub(crate) async fn reserve(
+        self: &Arc<Self>,
+        size: u64,
+        max_resident_size: Option<u64>,
+        cancel: &CancellationToken,
+    ) -> Result<DiskQuotaReservation, DiskQuotaError> {
+        let deadline = tokio::time::Instant::now() + RESERVE_TIMEOUT;
+        self.reserve_until(size, max_resident_size, Some(deadline), cancel)
+            .await
+    }
+
+    /// Like [`DiskQuota::reserve`], but waits for as long as it takes, for flushes which must not
+    /// be refused.
+    pub(crate) async fn reserve_without_timeout(
+        self: &Arc<Self>,
+        size: u64,
+        max_resident_size: Option<u64>,
+        cancel: &CancellationToken,
+    ) -> Result<DiskQuotaReservation, DiskQuotaError> {
+        self.reserve_until(size, max_resident_size, None, cancel)
+            .await
+    }
+
+    async fn reserve_until(
+        self: &Arc<Self>,
+        size: u64,
+        max_resident_size: Option<u64>,
+        deadline: Option<tokio::time::Instant>,
+        cancel: &CancellationToken,
+    ) -> Result<DiskQuotaReservation, DiskQuotaError> {
+        let Some(max_resident_size) = max_resident_size else {
+            return Ok(self.reserve_unchecked(size));
+        };
+        if size > max_resident_size {
+            return Err(DiskQuotaError::Exceeded);
+        }
+
+        self.waiting.fetch_add(size, Ordering::Relaxed);
+        let _waiting = scopeguard::guard((), |()| {
+            self.waiting.fetch_sub(size, Ordering::Relaxed);
+        });
+
+        loop {
+            // register for wakeups before checking, so that no release in between is missed
+            let freed = self.freed.notified();
+            tokio::pin!(freed);
+            freed.as_mut().enable();
+
+            let reserved = self.reserved.load(Ordering::Relaxed);
+            if self.resident_size() + reserved + size <= max_resident_size {
+                if self
+                    .reserved
+                    .compare_exchange(
+                        reserved,
+                        reserved + size,
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    )
+                    .is_ok()
+                {
+                    return Ok(DiskQuotaReservation {
+                        quota: Arc::clone(self),
+                        size,
+                    });
+                }
+                continue;
+            }
+
+            self.evict.notify_one();
+            tokio::select! {
+                _ = freed => {}
+                _ = sleep_until(deadline) => return Err(DiskQuotaError::Exceeded),
+                _ = cancel.cancelled() => return Err(DiskQuotaError::Cancelled),
+            }
+        }
+    }
+
+    /// Reserves room without checking the cap, for tenant shards without one.
+    fn reserve_unchecked(self: &Arc<Self>, size: u64) -> DiskQuotaReservation {
+        self.reserved.fetch_add(size, Ordering::Relaxed);
+        DiskQuotaReservation {
+            quota: Arc::clone(self),
+            size,
+        }
+    }
+}
+
+/// Sleeps until `deadline`, or forever without one.
+async fn sleep_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+impl Tenant {
+    /// Total size of the resident layer files of all timelines of this tenant shard.
+    pub(crate) fn resident_size(&self) -> u64 {
+        self.disk_quota.resident_size()
+    }
+
+    pub(crate) fn get_disk_quota_status(&self) -> TenantDiskQuotaStatus {
+        TenantDiskQuotaStatus {
+            max_resident_size: self.get_max_resident_size(),
+            resident_size: self.resident_size(),
+        }
+    }
+
+    /// Evicts the least recently used layers of this tenant shard until its resident size, and
+    /// the room reserved or waited for by layers about to become resident, fit below
+    /// `max_resident_size`.
+    /// Returns the amount of bytes evicted.
+    pub(crate) async fn enforce_disk_quota(&self, cancel: &CancellationToken) -> u64 {
+        let tenant_id = self.tenant_shard_id.tenant_id.to_string();
+        let shard_id = self.tenant_shard_id.shard_slug().to_string();
+        let labels = [tenant_id.as_str(), shard_id.as_str()];
+
+        let Some(max_resident_size) = self.get_max_resident_size() else {
+            let _ = TENANT_MAX_RESIDENT_SIZE.remove_label_values(&labels);
+            let _ = TENANT_RESIDENT_SIZE.remove_label_values(&labels);
+            return 0;
+        };
+
+        let resident_size = self.resident_size();
+        TENANT_MAX_RESIDENT_SIZE
+            .with_label_values(&labels)
+            .set(max_resident_size);
+        TENANT_RESIDENT_SIZE
+            .with_label_values(&labels)
+            .set(resident_size);
+
+        let wanted = self.disk_quota.wanted_size();
+        if wanted <= max_resident_size {
+            return 0;
+        }
+
+        let mut candidates = Vec::new();
+        for timeline in self.list_timelines() {
+            if !timeline.is_active() {
+                continue;
+            }
+            let info = timeline.get_local_layers_for_disk_usage_eviction().await;
+            candidates.extend(info.resident_layers);
+
+            if cancel.is_cancelled() {
+                return 0;
+            }
+        }
+
+        // covered layers first, then least recently used first
+        candidates.sort_unstable_by_key(|candidate| {
+            (
+                matches!(candidate.visibility, LayerVisibilityHint::Visible),
+                candidate.last_activity_ts,
+            )
+        });
+
+        let mut planned = wanted;
+        let victims = candidates
+            .into_iter()
+            .take_while(|candidate| {
+                let over = planned > max_resident_size;
+                planned = planned.saturating_sub(candidate.layer.get_file_size());
+                over
+            })
+            .filter_map(|candidate| match candidate.layer {
+                EvictionLayer::Attached(layer) => Some(layer),
+                EvictionLayer::Secondary(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        info!(
+            resident_size,
+            max_resident_size,
+            layers = victims.len(),
+            "tenant shard is at its max_resident_size, evicting layers"
+        );
+
+        let evictions = victims.into_iter().map(|layer| async move {
+            let file_size = layer.layer_desc().file_size;
+            match layer.evict_and_wait(EVICTION_TIMEOUT).await {
+                Ok(()) => {
+                    TENANT_DISK_QUOTA_EVICTED_LAYERS.inc();
+                    TENANT_DISK_QUOTA_EVICTED_BYTES.inc_by(file_size);
+                    file_size
+                }
+                Err(EvictionError::NotFound | EvictionError::Downloaded) => 0,
+                Err(EvictionError::Timeout) => {
+                    debug!(%layer, "timed out evicting layer");
+                    0
+                }
+            }
+        });
+
+        let evicted = tokio::select! {
+            evicted = futures::future::join_all(evictions) => evicted.into_iter().sum(),
+            _ = cancel.cancelled() => return 0,
+        };
+
+        let resident_size = self.resident_size();
+        TENANT_RESIDENT_SIZE
+            .with_label_values(&labels)
+            .set(resident_size);
+
+        if resident_size > max_resident_size {
+            info!(
+                resident_size,
+                max_resident_size, evicted, "tenant shard still exceeds its max_resident_size"
+            );
+        }
+
+        evicted
+    }
+}
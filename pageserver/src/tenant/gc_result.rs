@@ -14,6 +14,7 @@ pub struct GcResult {
     pub layers_needed_by_pitr: u64,
     pub layers_needed_by_branches: u64,
     pub layers_needed_by_leases: u64,
+    pub layers_needed_by_restore_points: u64,
     pub layers_not_updated: u64,
     pub layers_removed: u64, // # of layer files removed because they have been made obsolete by newer ondisk files.
 
@@ -44,6 +45,7 @@ fn add_assign(&mut self, other: Self) {
         self.layers_needed_by_cutoff += other.layers_needed_by_cutoff;
         self.layers_needed_by_branches += other.layers_needed_by_branches;
         self.layers_needed_by_leases += other.layers_needed_by_leases;
+        self.layers_needed_by_restore_points += other.layers_needed_by_restore_points;
         self.layers_not_updated += other.layers_not_updated;
         self.layers_removed += other.layers_removed;
 
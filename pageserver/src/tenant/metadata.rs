@@ -309,6 +309,24 @@ pub fn detach_from_ancestor(&mut self, branchpoint: &(TimelineId, Lsn)) {
         self.body.ancestor_lsn = Lsn(0);
     }
 
+    /// Makes the timeline end at `lsn`, for `Timeline::reset_to_lsn`.
+    pub fn reset_to_lsn(&mut self, lsn: Lsn) -> anyhow::Result<()> {
+        ensure!(
+            lsn <= self.body.disk_consistent_lsn,
+            "lsn {lsn} is ahead of disk_consistent_lsn {}",
+            self.body.disk_consistent_lsn
+        );
+        ensure!(
+            lsn >= self.body.latest_gc_cutoff_lsn,
+            "lsn {lsn} is below the gc cutoff {}",
+            self.body.latest_gc_cutoff_lsn
+        );
+        self.body.disk_consistent_lsn = lsn;
+        // unknown, like at the start of a branch which isn't at the end of its ancestor
+        self.body.prev_record_lsn = None;
+        Ok(())
+    }
+
     pub fn latest_gc_cutoff_lsn(&self) -> Lsn {
         self.body.latest_gc_cutoff_lsn
     }
@@ -30,12 +30,16 @@
 use utils::crashsafe::path_with_suffix_extension;
 use utils::fs_ext::PathExt;
 use utils::generation::Generation;
-use utils::id::{TenantId, TimelineId};
+use utils::id::{TenantId, TenantTimelineId, TimelineId};
+use utils::lsn::Lsn;
 use utils::{backoff, completion, crashsafe};
 
+use super::remote_timeline_client::index::CrossTenantAncestor;
 use super::remote_timeline_client::remote_tenant_path;
 use super::secondary::SecondaryTenant;
+use super::timeline::cross_tenant_branch;
 use super::timeline::detach_ancestor::{self, PreparedTimelineDetach};
+use super::timeline::reset_to_lsn;
 use super::{GlobalShutDown, TenantSharedResources};
 use crate::config::PageServerConf;
 use crate::context::{DownloadBehavior, RequestContext};
@@ -472,6 +476,9 @@ pub(crate) enum DeleteTenantError {
     #[error("Tenant map slot error {0}")]
     SlotError(#[from] TenantSlotError),
 
+    #[error("Tenant has timelines referenced by timelines of other tenants: {0:?}")]
+    HasCrossTenantChildren(Vec<TenantTimelineId>),
+
     #[error("Cancelled")]
     Cancelled,
 
@@ -654,6 +661,7 @@ pub async fn init_tenant_mgr(
                     "Starting secondary tenant"
                 );
                 TenantSlot::Secondary(SecondaryTenant::new(
+                    conf,
                     tenant_shard_id,
                     shard_identity,
                     location_conf.tenant_conf,
@@ -1130,6 +1138,7 @@ enum FastPathModified {
             LocationMode::Secondary(secondary_config) => {
                 let shard_identity = new_location_config.shard;
                 TenantSlot::Secondary(SecondaryTenant::new(
+                    self.conf,
                     tenant_shard_id,
                     shard_identity,
                     new_location_config.tenant_conf,
@@ -1388,9 +1397,28 @@ async fn delete_local(
             Ok(())
         }
 
+        // Cross-tenant children deleted while we were not attached to the same pageserver may
+        // still be registered.
+        if let Ok(tenant) = self.get_attached_tenant_shard(tenant_shard_id) {
+            for timeline in tenant.list_timelines() {
+                cross_tenant_branch::forget_deleted_children(&timeline, &tenant.cancel).await?;
+            }
+        }
+
         let slot_guard = tenant_map_acquire_slot(&tenant_shard_id, TenantSlotAcquireMode::Any)?;
         match &slot_guard.old_value {
             Some(TenantSlot::Attached(tenant)) => {
+                // Deleting the remote prefix would pull the layers out from under the branches.
+                let children = tenant
+                    .list_timelines()
+                    .iter()
+                    .flat_map(|t| t.remote_client.cross_tenant_children().unwrap_or_default())
+                    .map(|c| c.id())
+                    .collect::<Vec<_>>();
+                if !children.is_empty() {
+                    return Err(DeleteTenantError::HasCrossTenantChildren(children));
+                }
+
                 // Legacy deletion flow: the tenant remains attached, goes to Stopping state, and
                 // deletion will be resumed across restarts.
                 let tenant = tenant.clone();
@@ -1609,7 +1637,230 @@ pub(crate) async fn do_shard_split(
         )));
 
         // Phase 4: wait for child chards WAL ingest to catch up to target LSN
-        for child_shard_id in &child_shards {
+        self.wait_child_shards_caught_up(&child_shards, &target_lsns, ctx)
+            .await?;
+
+        // Phase 5: Shut down the parent shard, and erase it from disk
+        let (_guard, progress) = completion::channel();
+        match parent.shutdown(progress, ShutdownMode::Hard).await {
+            Ok(()) => {}
+            Err(other) => {
+                other.wait().await;
+            }
+        }
+        let local_tenant_directory = self.conf.tenant_path(&tenant_shard_id);
+        let tmp_path = safe_rename_tenant_dir(&local_tenant_directory)
+            .await
+            .with_context(|| format!("local tenant directory {local_tenant_directory:?} rename"))?;
+        self.background_purges.spawn(tmp_path);
+
+        fail::fail_point!("shard-split-pre-finish", |_| Err(anyhow::anyhow!(
+            "failpoint"
+        )));
+
+        parent_slot_guard.drop_old_value()?;
+
+        // Phase 6: Release the InProgress on the parent shard
+        drop(parent_slot_guard);
+
+        Ok(child_shards)
+    }
+
+    #[instrument(skip_all, fields(tenant_id=%tenant.get_tenant_shard_id().tenant_id, shard_id=%tenant.get_tenant_shard_id().shard_slug(), new_shard_count=%new_shard_count.literal()))]
+    pub(crate) async fn shard_merge(
+        &self,
+        tenant: Arc<Tenant>,
+        new_shard_count: ShardCount,
+        generation: Option<Generation>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<TenantShardId> {
+        let tenant_shard_id = *tenant.get_tenant_shard_id();
+        let r = self
+            .do_shard_merge(tenant, new_shard_count, generation, ctx)
+            .await;
+        if r.is_err() {
+            // [`Tenant::merge_prepare`] stops the parents' WAL ingest and uploads, and they are
+            // shut down at the very end of a merge: reset them to leave things in a working state.
+            for parent_id in tenant_shard_id
+                .merge(new_shard_count)
+                .split(tenant_shard_id.shard_count)
+            {
+                let Some(TenantSlot::Attached(_)) = self.get(parent_id) else {
+                    continue;
+                };
+                tracing::warn!("Resetting {parent_id} after shard merge failure");
+                if let Err(e) = self.reset_tenant(parent_id, false, ctx).await {
+                    tracing::error!("Failed to reset {parent_id}: {e}");
+                }
+            }
+        }
+
+        r
+    }
+
+    pub(crate) async fn do_shard_merge(
+        &self,
+        tenant: Arc<Tenant>,
+        new_shard_count: ShardCount,
+        generation: Option<Generation>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<TenantShardId> {
+        let tenant_shard_id = *tenant.get_tenant_shard_id();
+        drop(tenant);
+
+        // Validate the incoming request
+        if u16::from(new_shard_count.count()) * 2 != u16::from(tenant_shard_id.shard_count.count())
+        {
+            anyhow::bail!("Requested shard count is not half of the current shard count");
+        }
+
+        // Plan: identify the merged shard, and the two parents which merge into it
+        let child_shard = tenant_shard_id.merge(new_shard_count);
+        let parent_ids = child_shard.split(tenant_shard_id.shard_count);
+        tracing::info!(
+            "Shards {} merge into: {}",
+            parent_ids
+                .iter()
+                .map(|id| format!("{}", id.to_index()))
+                .join(","),
+            child_shard.to_index()
+        );
+
+        let mut parents = Vec::new();
+        for parent_id in &parent_ids {
+            let parent = self.get_attached_tenant_shard(*parent_id)?;
+            parent.wait_to_become_active(ACTIVE_TENANT_TIMEOUT).await?;
+            parents.push(parent);
+        }
+        let [lower, upper] = parents.as_slice() else {
+            unreachable!("a shard count is halved by merging pairs of shards")
+        };
+        if lower.get_shard_stripe_size() != upper.get_shard_stripe_size() {
+            anyhow::bail!("Shards to merge have different stripe sizes");
+        }
+
+        let parent_generation = std::cmp::max(lower.generation, upper.generation);
+        let generation = generation.unwrap_or_else(|| parent_generation.next());
+        if generation <= parent_generation {
+            // The merged shard's index must not be confused with any index that its shard
+            // index had from before the tenant was split to the current shard count.
+            anyhow::bail!(
+                "Merged shard generation {generation:?} must be newer than the parents' {parent_generation:?}"
+            );
+        }
+
+        let mut child_shard_identity = lower.shard_identity;
+        child_shard_identity.count = child_shard.shard_count;
+        child_shard_identity.number = child_shard.shard_number;
+        let child_tenant_conf = lower.get_tenant_conf();
+
+        fail::fail_point!("shard-merge-pre-prepare", |_| Err(anyhow::anyhow!(
+            "failpoint"
+        )));
+
+        // Phase 1: Write out the merged shard's layers and remote index files
+        lower
+            .merge_prepare(upper, child_shard, child_shard_identity, generation, ctx)
+            .await?;
+
+        fail::fail_point!("shard-merge-post-prepare", |_| Err(anyhow::anyhow!(
+            "failpoint"
+        )));
+
+        // Phase 2: Put the parent shards to InProgress and grab references to the parent Tenants
+        drop(parents);
+        let mut parent_slot_guards = Vec::new();
+        for parent_id in &parent_ids {
+            let slot_guard = tenant_map_acquire_slot(parent_id, TenantSlotAcquireMode::Any)?;
+            match slot_guard.get_old_value() {
+                Some(TenantSlot::Attached(_)) => {}
+                Some(TenantSlot::Secondary(_)) => {
+                    anyhow::bail!("Tenant location {parent_id} in secondary mode")
+                }
+                Some(TenantSlot::InProgress(_)) => unreachable!(),
+                None => anyhow::bail!("Detached parent shard {parent_id} in the middle of merge!"),
+            }
+            parent_slot_guards.push(slot_guard);
+        }
+
+        // Take a snapshot of where the parents' WAL ingest had got to: we will wait for the
+        // merged shard to reach the furthest of them.
+        let mut target_lsns = HashMap::new();
+        for slot_guard in &parent_slot_guards {
+            let Some(TenantSlot::Attached(parent)) = slot_guard.get_old_value() else {
+                unreachable!("checked above");
+            };
+            for timeline in parent.timelines.lock().unwrap().values() {
+                let lsn = timeline.get_last_record_lsn();
+                target_lsns
+                    .entry(timeline.timeline_id)
+                    .and_modify(|target: &mut Lsn| *target = std::cmp::max(*target, lsn))
+                    .or_insert(lsn);
+            }
+        }
+
+        // Phase 3: Spawn the merged shard
+        let child_location_conf = LocationConf {
+            mode: LocationMode::Attached(AttachedLocationConfig {
+                generation,
+                attach_mode: AttachmentMode::Single,
+            }),
+            shard: child_shard_identity,
+            tenant_conf: child_tenant_conf,
+        };
+        self.upsert_location(
+            child_shard,
+            child_location_conf,
+            None,
+            SpawnMode::Eager,
+            ctx,
+        )
+        .await?;
+
+        fail::fail_point!("shard-merge-post-child-conf", |_| Err(anyhow::anyhow!(
+            "failpoint"
+        )));
+
+        // Phase 4: wait for the merged shard's WAL ingest to catch up to target LSN
+        self.wait_child_shards_caught_up(&[child_shard], &target_lsns, ctx)
+            .await?;
+
+        // Phase 5: Shut down the parent shards, and erase them from disk
+        for (parent_id, mut slot_guard) in parent_ids.iter().zip(parent_slot_guards) {
+            let Some(TenantSlot::Attached(parent)) = slot_guard.get_old_value() else {
+                unreachable!("checked above");
+            };
+            let (_guard, progress) = completion::channel();
+            match parent.shutdown(progress, ShutdownMode::Hard).await {
+                Ok(()) => {}
+                Err(other) => {
+                    other.wait().await;
+                }
+            }
+            let local_tenant_directory = self.conf.tenant_path(parent_id);
+            let tmp_path = safe_rename_tenant_dir(&local_tenant_directory)
+                .await
+                .with_context(|| {
+                    format!("local tenant directory {local_tenant_directory:?} rename")
+                })?;
+            self.background_purges.spawn(tmp_path);
+
+            slot_guard.drop_old_value()?;
+        }
+
+        Ok(child_shard)
+    }
+
+    /// Part of [`Self::shard_split`] and [`Self::shard_merge`]: wait for the WAL ingest of child
+    /// shards to catch up with where their parents had got to.  This is an optimization to make
+    /// the cutover more seamless for clients, so failures to catch up are not fatal.
+    async fn wait_child_shards_caught_up(
+        &self,
+        child_shards: &[TenantShardId],
+        target_lsns: &HashMap<TimelineId, Lsn>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
+        for child_shard_id in child_shards {
             let child_shard_id = *child_shard_id;
             let child_shard = {
                 let locked = self.tenants.read().unwrap();
@@ -1671,30 +1922,7 @@ pub(crate) async fn do_shard_split(
             }
         }
 
-        // Phase 5: Shut down the parent shard, and erase it from disk
-        let (_guard, progress) = completion::channel();
-        match parent.shutdown(progress, ShutdownMode::Hard).await {
-            Ok(()) => {}
-            Err(other) => {
-                other.wait().await;
-            }
-        }
-        let local_tenant_directory = self.conf.tenant_path(&tenant_shard_id);
-        let tmp_path = safe_rename_tenant_dir(&local_tenant_directory)
-            .await
-            .with_context(|| format!("local tenant directory {local_tenant_directory:?} rename"))?;
-        self.background_purges.spawn(tmp_path);
-
-        fail::fail_point!("shard-split-pre-finish", |_| Err(anyhow::anyhow!(
-            "failpoint"
-        )));
-
-        parent_slot_guard.drop_old_value()?;
-
-        // Phase 6: Release the InProgress on the parent shard
-        drop(parent_slot_guard);
-
-        Ok(child_shards)
+        Ok(())
     }
 
     /// Part of [`Self::shard_split`]: hard link parent shard layers into child shards, as an optimization
@@ -2067,6 +2295,186 @@ pub(crate) async fn complete_detaching_timeline_ancestor(
         }
     }
 
+    /// Unregisters a deleted cross-tenant branch from its ancestor, if the ancestor's shard is
+    /// attached to this pageserver. Otherwise the ancestor unregisters it later on, when it finds
+    /// out that the branch is gone from remote storage.
+    ///
+    /// See [`super::timeline::cross_tenant_branch`].
+    pub(crate) fn forget_cross_tenant_child(
+        &self,
+        child_shard_id: TenantShardId,
+        child_timeline_id: TimelineId,
+        ancestor: CrossTenantAncestor,
+    ) {
+        let ancestor_shard_id = TenantShardId {
+            tenant_id: ancestor.tenant_id,
+            shard_number: child_shard_id.shard_number,
+            shard_count: child_shard_id.shard_count,
+        };
+        let child = TenantTimelineId::new(child_shard_id.tenant_id, child_timeline_id);
+        let res = self
+            .get_attached_tenant_shard(ancestor_shard_id)
+            .map_err(anyhow::Error::new)
+            .and_then(|tenant| Ok(tenant.get_timeline(ancestor.timeline_id, false)?))
+            .and_then(|timeline| {
+                Ok(timeline
+                    .remote_client
+                    .schedule_removing_cross_tenant_child(child)?)
+            });
+        match res {
+            Ok(false) => {}
+            Ok(true) => info!(
+                ancestor_tenant_id = %ancestor.tenant_id,
+                ancestor_timeline_id = %ancestor.timeline_id,
+                "unregistered deleted cross-tenant branch from its ancestor"
+            ),
+            Err(e) => warn!(
+                ancestor_tenant_id = %ancestor.tenant_id,
+                ancestor_timeline_id = %ancestor.timeline_id,
+                "failed to unregister deleted cross-tenant branch from its ancestor: {e:#}"
+            ),
+        }
+    }
+
+    /// Resets a timeline in place to an earlier LSN, then reloads the tenant.
+    ///
+    /// See [`super::timeline::reset_to_lsn`].
+    pub(crate) async fn reset_timeline_to_lsn(
+        &self,
+        tenant_shard_id: TenantShardId,
+        timeline_id: TimelineId,
+        lsn: Lsn,
+        generation: Generation,
+        ctx: &RequestContext,
+    ) -> Result<(), reset_to_lsn::Error> {
+        use reset_to_lsn::Error;
+
+        let slot_guard =
+            tenant_map_acquire_slot(&tenant_shard_id, TenantSlotAcquireMode::MustExist).map_err(
+                |e| {
+                    use TenantSlotError::*;
+
+                    match e {
+                        MapState(TenantMapError::ShuttingDown) => Error::ShuttingDown,
+                        NotFound(_) | InProgress | MapState(_) => Error::Reset(e.into()),
+                    }
+                },
+            )?;
+
+        let tenant = {
+            let old_slot = slot_guard
+                .get_old_value()
+                .as_ref()
+                .expect("requested MustExist");
+
+            let Some(tenant) = old_slot.get_attached() else {
+                return Err(Error::Reset(anyhow::anyhow!(
+                    "Tenant is not in attached state"
+                )));
+            };
+
+            if !tenant.is_active() {
+                return Err(Error::Reset(anyhow::anyhow!("Tenant is not active")));
+            }
+
+            if generation <= tenant.generation() {
+                return Err(Error::StaleGeneration {
+                    requested: generation,
+                    current: tenant.generation(),
+                });
+            }
+
+            tenant.clone()
+        };
+
+        let timeline = tenant
+            .get_timeline(timeline_id, true)
+            .map_err(Error::NotFound)?;
+
+        // The tenant comes back in the generation the reset index was published in. If the
+        // publication failed, the newer generation is still safe to attach in: it loads the
+        // latest index of the older generations. The config is prepared before anything changes,
+        // so that nothing but shutdown can keep the tenant from coming back once it is shut down.
+        let mut config = Tenant::load_tenant_config(self.conf, &tenant_shard_id)
+            .map_err(|e| Error::Reset(e.into()))?;
+        let LocationMode::Attached(attached) = &mut config.mode else {
+            return Err(Error::Reset(anyhow::anyhow!(
+                "location config is not attached"
+            )));
+        };
+        attached.generation = generation;
+        let shard_identity = config.shard;
+        let attached_conf = AttachedTenantConf::try_from(config.clone()).map_err(Error::Reset)?;
+
+        let res = timeline.reset_to_lsn(&tenant, lsn, generation, ctx).await;
+        match res {
+            Ok(()) | Err(Error::Publish(_)) => {}
+            // nothing was changed, dropping the slot guard puts the tenant back
+            Err(e) => return Err(e),
+        }
+        drop(timeline);
+
+        let mut slot_guard = slot_guard;
+
+        let (_guard, progress) = utils::completion::channel();
+        match tenant.shutdown(progress, ShutdownMode::Reload).await {
+            Ok(()) => {
+                slot_guard.drop_old_value().expect("it was just shutdown");
+            }
+            Err(_barrier) => {
+                slot_guard.revert();
+                return Err(Error::ShuttingDown);
+            }
+        }
+
+        // Basebackups cached after the LSN are not part of the history anymore. The local ones
+        // were removed by the shutdown.
+        let remove_basebackups = match crate::basebackup_cache::get() {
+            Some(cache) => {
+                cache
+                    .remove_remote_timeline(tenant_shard_id, timeline_id, &self.cancel)
+                    .await
+            }
+            None => Ok(()),
+        };
+
+        // If the config can't be persisted, the tenant is still attached: a restart gets the
+        // generation from the storage controller anyway.
+        let persist_config = Tenant::persist_tenant_config(self.conf, &tenant_shard_id, &config)
+            .await
+            .context("persist location config")
+            .map_err(Error::Reset);
+
+        let tenant_path = self.conf.tenant_path(&tenant_shard_id);
+        let tenant = tenant_spawn(
+            self.conf,
+            tenant_shard_id,
+            &tenant_path,
+            self.resources.clone(),
+            attached_conf,
+            shard_identity,
+            None,
+            SpawnMode::Eager,
+            ctx,
+        )
+        .map_err(|_| Error::ShuttingDown)?;
+
+        slot_guard
+            .upsert(TenantSlot::Attached(tenant))
+            .map_err(|e| match e {
+                TenantSlotUpsertError::ShuttingDown(_) => Error::ShuttingDown,
+                other => Error::Reset(other.into()),
+            })?;
+
+        persist_config?;
+        // A retry of the reset removes the cached basebackups again.
+        remove_basebackups
+            .context("remove remote basebackups")
+            .map_err(Error::Reset)?;
+
+        res
+    }
+
     /// A page service client sends a TenantId, and to look up the correct Tenant we must
     /// resolve this to a fully qualified TenantShardId.
     ///
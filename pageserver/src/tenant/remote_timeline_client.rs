@@ -192,9 +192,12 @@
     download_index_part, download_initdb_tar_zst, download_tenant_manifest, is_temp_download_file,
     list_remote_tenant_shards, list_remote_timelines,
 };
-use index::GcCompactionState;
 pub(crate) use index::LayerFileMetadata;
-use pageserver_api::models::{RelSizeMigration, TimelineArchivalState, TimelineVisibilityState};
+use index::{CrossTenantAncestor, CrossTenantChild, GcCompactionState};
+use pageserver_api::models::{
+    RelSizeMigration, RestorePoint, TimelineArchivalState, TimelineRetentionPolicy,
+    TimelineVisibilityState,
+};
 use pageserver_api::shard::{ShardIndex, TenantShardId};
 use regex::Regex;
 use remote_storage::{
@@ -207,7 +210,7 @@
 use utils::backoff::{
     self, DEFAULT_BASE_BACKOFF_SECONDS, DEFAULT_MAX_BACKOFF_SECONDS, exponential_backoff,
 };
-use utils::id::{TenantId, TimelineId};
+use utils::id::{TenantId, TenantTimelineId, TimelineId};
 use utils::lsn::Lsn;
 use utils::pausable_failpoint;
 use utils::shard::ShardNumber;
@@ -224,8 +227,8 @@
 use crate::deletion_queue::{DeletionQueueClient, DeletionQueueError};
 use crate::metrics::{
     MeasureRemoteOp, REMOTE_ONDEMAND_DOWNLOADED_BYTES, REMOTE_ONDEMAND_DOWNLOADED_LAYERS,
-    RemoteOpFileKind, RemoteOpKind, RemoteTimelineClientMetrics,
-    RemoteTimelineClientMetricsCallTrackSize,
+    REMOTE_ONDEMAND_PARTIAL_DOWNLOADED_BYTES, RemoteOpFileKind, RemoteOpKind,
+    RemoteTimelineClientMetrics, RemoteTimelineClientMetricsCallTrackSize,
 };
 use crate::task_mgr::{BACKGROUND_RUNTIME, TaskKind, shutdown_token};
 use crate::tenant::metadata::TimelineMetadata;
@@ -254,6 +257,9 @@
 
 pub(crate) const INITDB_PRESERVED_PATH: &str = "initdb-preserved.tar.zst";
 
+/// Prefix of the [`crate::basebackup_cache`]'s objects within a timeline's prefix.
+pub const BASEBACKUPS_SEGMENT_NAME: &str = "basebackups";
+
 /// Default buffer size when interfacing with [`tokio::fs::File`].
 pub(crate) const BUFFER_SIZE: usize = 32 * 1024;
 
@@ -583,6 +589,79 @@ pub(crate) fn is_invisible(&self) -> Option<bool> {
             .ok()
     }
 
+    /// Returns the timelines of other tenants referencing layers of this timeline, including the
+    /// ones whose registration has not been uploaded yet.
+    pub(crate) fn cross_tenant_children(&self) -> Option<Vec<CrossTenantChild>> {
+        self.upload_queue
+            .lock()
+            .unwrap()
+            .initialized_mut()
+            .map(|q| q.dirty.cross_tenant_children.clone())
+            .ok()
+    }
+
+    /// Returns the ancestor of a timeline branched off another tenant.
+    pub(crate) fn cross_tenant_ancestor(&self) -> Option<CrossTenantAncestor> {
+        self.upload_queue
+            .lock()
+            .unwrap()
+            .initialized_mut()
+            .ok()
+            .and_then(|q| q.clean.0.cross_tenant_ancestor)
+    }
+
+    /// Returns the retention policy overriding the one of the tenant, if any.
+    pub(crate) fn retention_policy(&self) -> Option<TimelineRetentionPolicy> {
+        self.upload_queue
+            .lock()
+            .unwrap()
+            .initialized_mut()
+            .ok()
+            .and_then(|q| q.dirty.retention_policy.clone())
+    }
+
+    /// Returns the restore points resolved for the retention policy, oldest first.
+    pub(crate) fn restore_points(&self) -> Vec<RestorePoint> {
+        self.upload_queue
+            .lock()
+            .unwrap()
+            .initialized_mut()
+            .map(|q| q.dirty.restore_points.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns true if the cross-tenant `child` of this timeline still exists in remote storage,
+    /// in any shard of its tenant.
+    pub(crate) async fn cross_tenant_child_exists(
+        &self,
+        child: TenantTimelineId,
+        cancel: &CancellationToken,
+    ) -> anyhow::Result<bool> {
+        let (shards, _other_keys) =
+            list_remote_tenant_shards(&self.storage_impl, child.tenant_id, cancel.clone()).await?;
+
+        for tenant_shard_id in shards {
+            match download_index_part(
+                &self.storage_impl,
+                &tenant_shard_id,
+                &child.timeline_id,
+                Generation::MAX,
+                cancel,
+            )
+            .instrument(info_span!("download_index_part",
+                         tenant_id=%tenant_shard_id.tenant_id,
+                         shard_id=%tenant_shard_id.shard_slug(),
+                         timeline_id=%child.timeline_id))
+            .await
+            {
+                Ok((index_part, _, _)) if index_part.deleted_at.is_none() => return Ok(true),
+                Ok(_) | Err(DownloadError::NotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(false)
+    }
+
     /// Returns `Ok(Some(timestamp))` if the timeline has been archived, `Ok(None)` if the timeline hasn't been archived.
     ///
     /// Return Err(_) if the remote index_part hasn't been downloaded yet, or the timeline hasn't been stopped yet.
@@ -754,6 +833,48 @@ pub async fn download_layer_file(
         Ok(downloaded_size)
     }
 
+    /// Download the byte range `range` of a layer file into memory.
+    ///
+    /// 'layer_metadata' is the metadata from the remote index file.
+    pub(crate) async fn download_layer_range(
+        &self,
+        layer_file_name: &LayerName,
+        layer_metadata: &LayerFileMetadata,
+        range: std::ops::Range<u64>,
+        cancel: &CancellationToken,
+        ctx: &RequestContext,
+    ) -> Result<Vec<u8>, DownloadError> {
+        let bytes = {
+            let _unfinished_gauge_guard = self.metrics.call_begin(
+                &RemoteOpFileKind::Layer,
+                &RemoteOpKind::Download,
+                crate::metrics::RemoteTimelineClientMetricsCallTrackSize::DontTrackSize {
+                    reason: "no need for a downloads gauge",
+                },
+            );
+            download::download_layer_range(
+                &self.storage_impl,
+                self.tenant_shard_id,
+                self.timeline_id,
+                layer_file_name,
+                layer_metadata,
+                range,
+                cancel,
+            )
+            .measure_remote_op(
+                Some(ctx.task_kind()),
+                RemoteOpFileKind::Layer,
+                RemoteOpKind::Download,
+                Arc::clone(&self.metrics),
+            )
+            .await?
+        };
+
+        REMOTE_ONDEMAND_PARTIAL_DOWNLOADED_BYTES.inc_by(bytes.len() as u64);
+
+        Ok(bytes)
+    }
+
     //
     // Upload operations.
     //
@@ -961,6 +1082,34 @@ pub(crate) fn schedule_index_upload_for_gc_compaction_state_update(
         Ok(())
     }
 
+    /// Launch an index-file upload operation in the background, setting the retention policy.
+    ///
+    /// The restore points resolved so far are kept, GC drops those the policy does not keep.
+    pub(crate) fn schedule_index_upload_for_retention_policy_update(
+        self: &Arc<Self>,
+        retention_policy: Option<TimelineRetentionPolicy>,
+    ) -> Result<(), NotInitialized> {
+        let mut guard = self.upload_queue.lock().unwrap();
+        let upload_queue = guard.initialized_mut()?;
+        upload_queue.dirty.retention_policy = retention_policy;
+        self.schedule_index_upload(upload_queue);
+        Ok(())
+    }
+
+    /// Launch an index-file upload operation in the background, if the restore points changed.
+    pub(crate) fn schedule_index_upload_for_restore_points_update(
+        self: &Arc<Self>,
+        restore_points: Vec<RestorePoint>,
+    ) -> Result<(), NotInitialized> {
+        let mut guard = self.upload_queue.lock().unwrap();
+        let upload_queue = guard.initialized_mut()?;
+        if upload_queue.dirty.restore_points != restore_points {
+            upload_queue.dirty.restore_points = restore_points;
+            self.schedule_index_upload(upload_queue);
+        }
+        Ok(())
+    }
+
     /// Launch an index-file upload operation in the background, setting `rel_size_v2_status` field.
     pub(crate) fn schedule_index_upload_for_rel_size_v2_status_update(
         self: &Arc<Self>,
@@ -1112,6 +1261,189 @@ pub(crate) async fn schedule_adding_existing_layers_to_index_detach_and_wait(
         Ok(())
     }
 
+    /// Uploads, in the newer `generation`, a version of `index_part.json` which ends the timeline
+    /// at `lsn`, with the `discarded` layers replaced by the `rewritten` ones.
+    ///
+    /// The index of the current generation is left as it is: it keeps referencing the discarded
+    /// layers, which stay in remote storage as an archive of the discarded history. The upload
+    /// queue is not changed either, so the caller must reload the tenant in `generation`.
+    ///
+    /// This is used with `Timeline::reset_to_lsn` functionality.
+    pub(crate) async fn upload_reset_index_part(
+        self: &Arc<Self>,
+        lsn: Lsn,
+        discarded: &[Layer],
+        rewritten: &[Layer],
+        generation: Generation,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            generation > self.generation,
+            "generation {generation:?} is not newer than {:?}",
+            self.generation
+        );
+
+        // The new index must contain everything uploaded so far in this generation.
+        self.wait_completion().await?;
+
+        let index_part = {
+            let mut guard = self.upload_queue.lock().unwrap();
+            let upload_queue = guard.initialized_mut()?;
+            let mut index_part = upload_queue.dirty.clone();
+
+            for layer in rewritten {
+                anyhow::ensure!(
+                    !index_part
+                        .layer_metadata
+                        .contains_key(&layer.layer_desc().layer_name()),
+                    "rewritten layer existed already {layer}"
+                );
+            }
+
+            for layer in discarded {
+                index_part
+                    .layer_metadata
+                    .remove(&layer.layer_desc().layer_name());
+            }
+            for layer in rewritten {
+                index_part
+                    .layer_metadata
+                    .insert(layer.layer_desc().layer_name(), layer.metadata());
+            }
+            index_part.metadata.reset_to_lsn(lsn)?;
+            if let Some(gc_compaction) = &index_part.gc_compaction {
+                if gc_compaction.last_completed_lsn > lsn {
+                    index_part.gc_compaction = None;
+                }
+            }
+            // Restore points after the reset point would restore discarded history.
+            index_part.restore_points.retain(|p| p.lsn <= lsn);
+            index_part
+        };
+
+        backoff::retry(
+            || {
+                upload::upload_index_part(
+                    &self.storage_impl,
+                    &self.tenant_shard_id,
+                    &self.timeline_id,
+                    generation,
+                    &index_part,
+                    &self.cancel,
+                )
+            },
+            TimeoutOrCancel::caused_by_cancel,
+            FAILED_UPLOAD_WARN_THRESHOLD,
+            FAILED_REMOTE_OP_RETRIES,
+            "upload_reset_index_part",
+            &self.cancel,
+        )
+        .await
+        .ok_or_else(|| anyhow::Error::new(TimeoutOrCancel::Cancel))
+        .and_then(|x| x)
+    }
+
+    /// Records `child` as referencing some layers of this timeline, and waits for the index to be
+    /// uploaded. From then on, these layers are not deleted from remote storage when unlinked.
+    ///
+    /// Fails if one of the layers was unlinked in the meantime, and may already be deleted.
+    ///
+    /// This is used with cross-tenant branching.
+    pub(crate) async fn schedule_adding_cross_tenant_child_and_wait(
+        self: &Arc<Self>,
+        child: CrossTenantChild,
+    ) -> anyhow::Result<()> {
+        let barrier = {
+            let mut guard = self.upload_queue.lock().unwrap();
+            let upload_queue = guard.initialized_mut()?;
+
+            for (name, metadata) in &child.layers {
+                let linked = upload_queue
+                    .dirty
+                    .layer_metadata
+                    .get(name)
+                    .is_some_and(|m| is_same_remote_layer_path(name, m, name, metadata));
+                anyhow::ensure!(linked, "layer {name} was unlinked concurrently");
+            }
+
+            // A retried creation may reference other layers than the previous attempt.
+            let children = &mut upload_queue.dirty.cross_tenant_children;
+            match children.iter_mut().find(|c| c.id() == child.id()) {
+                Some(existing) => existing.layers.extend(child.layers),
+                None => children.push(child),
+            }
+            self.schedule_index_upload(upload_queue);
+
+            self.schedule_barrier0(upload_queue)
+        };
+
+        Self::wait_completion0(barrier).await?;
+        Ok(())
+    }
+
+    /// Forgets about a deleted cross-tenant `child`, and schedules the deletion of the layers
+    /// which were only kept for it. Returns false if `child` was not registered.
+    pub(crate) fn schedule_removing_cross_tenant_child(
+        self: &Arc<Self>,
+        child: TenantTimelineId,
+    ) -> Result<bool, NotInitialized> {
+        let mut guard = self.upload_queue.lock().unwrap();
+        let upload_queue = guard.initialized_mut()?;
+
+        let children = &mut upload_queue.dirty.cross_tenant_children;
+        let Some(pos) = children.iter().position(|c| c.id() == child) else {
+            return Ok(false);
+        };
+        let removed = children.remove(pos);
+        self.schedule_index_upload(upload_queue);
+
+        let unlinked = removed
+            .layers
+            .into_iter()
+            .filter(|(name, metadata)| {
+                !upload_queue
+                    .dirty
+                    .layer_metadata
+                    .get(name)
+                    .is_some_and(|m| is_same_remote_layer_path(name, m, name, metadata))
+            })
+            .collect::<Vec<_>>();
+
+        // These were kept from being deleted when they were unlinked.
+        #[cfg(feature = "testing")]
+        for (name, metadata) in &unlinked {
+            upload_queue
+                .dangling_files
+                .insert(name.clone(), metadata.generation);
+        }
+
+        // The deletions wait for the index upload above.
+        self.schedule_deletion_of_unlinked0(upload_queue, unlinked);
+        Ok(true)
+    }
+
+    /// Adds the layers of a new cross-tenant branch to the index: the ones referenced from the
+    /// `ancestor` timeline, and the ones rewritten and already uploaded for it.
+    pub(crate) fn schedule_adding_cross_tenant_ancestor_layers(
+        self: &Arc<Self>,
+        ancestor: CrossTenantAncestor,
+        layers: &[Layer],
+    ) -> anyhow::Result<()> {
+        let mut guard = self.upload_queue.lock().unwrap();
+        let upload_queue = guard.initialized_mut()?;
+
+        for layer in layers {
+            let prev = upload_queue
+                .dirty
+                .layer_metadata
+                .insert(layer.layer_desc().layer_name(), layer.metadata());
+            anyhow::ensure!(prev.is_none(), "ancestor layer existed already {layer}");
+        }
+        upload_queue.dirty.cross_tenant_ancestor = Some(ancestor);
+
+        self.schedule_index_upload(upload_queue);
+        Ok(())
+    }
+
     /// Adds a gc blocking reason for this timeline if one does not exist already.
     ///
     /// A retryable step of timeline detach ancestor.
@@ -1411,6 +1743,27 @@ fn schedule_deletion_of_unlinked0(
             retain
         });
 
+        // Likewise, layers referenced from another tenant's timeline are not ours to delete, and
+        // our layers referenced by another tenant's timeline are deleted once it is gone, see
+        // `schedule_removing_cross_tenant_child`.
+        with_metadata.retain(|(name, meta)| {
+            let retain = meta.cross_tenant_owner.is_none();
+            if !retain {
+                tracing::debug!("Skipping deletion of cross-tenant layer {name}");
+            }
+            retain
+        });
+        let children = &upload_queue.dirty.cross_tenant_children;
+        let (referenced, unreferenced): (Vec<_>, Vec<_>) = with_metadata
+            .into_iter()
+            .partition(|(name, meta)| children.iter().any(|c| c.references(name, meta)));
+        with_metadata = unreferenced;
+        for (name, _meta) in &referenced {
+            info!("Skipping deletion of layer {name} referenced by a cross-tenant child");
+            #[cfg(feature = "testing")]
+            upload_queue.dangling_files.remove(name);
+        }
+
         for (name, meta) in &with_metadata {
             info!(
                 "scheduling deletion of layer {}{} (shard {})",
@@ -1739,14 +2092,13 @@ pub(crate) async fn copy_timeline_layer(
         adopted_as: &Layer,
         cancel: &CancellationToken,
     ) -> anyhow::Result<()> {
-        let source_remote_path = remote_layer_path(
+        let source_remote_path = remote_layer_path_in_index(
             &self.tenant_shard_id.tenant_id,
             &adopted
                 .get_timeline_id()
                 .expect("Source timeline should be alive"),
-            adopted.metadata().shard,
             &adopted.layer_desc().layer_name(),
-            adopted.metadata().generation,
+            &adopted.metadata(),
         );
 
         let target_remote_path = remote_layer_path(
@@ -1831,8 +2183,11 @@ pub(crate) async fn delete_all(self: &Arc<Self>) -> Result<(), DeleteTimelineErr
                     //   these timelines are present but corrupt (their index exists but some layers don't)
                     //
                     // These layers will eventually be cleaned up by the scrubber when it does physical GC.
+                    //
+                    // Layers referenced from another tenant's timeline are deleted by that timeline.
                     meta.shard.shard_number == self.tenant_shard_id.shard_number
                         && meta.shard.shard_count == self.tenant_shard_id.shard_count
+                        && meta.cross_tenant_owner.is_none()
                 })
                 .map(|(file_name, meta)| {
                     remote_layer_path(
@@ -2632,6 +2987,15 @@ pub fn remote_timeline_path(
     remote_timelines_path(tenant_shard_id).join(Utf8Path::new(&timeline_id.to_string()))
 }
 
+/// Where the [`crate::basebackup_cache`] keeps a timeline's prepared basebackups. It is within
+/// the timeline's prefix, so that the basebackups are deleted along with the timeline.
+pub fn remote_basebackups_path(
+    tenant_shard_id: &TenantShardId,
+    timeline_id: &TimelineId,
+) -> RemotePath {
+    remote_timeline_path(tenant_shard_id, timeline_id).join(BASEBACKUPS_SEGMENT_NAME)
+}
+
 /// Obtains the path of the given Layer in the remote
 ///
 /// Note that the shard component of a remote layer path is _not_ always the same
@@ -2655,6 +3019,27 @@ pub fn remote_layer_path(
     RemotePath::from_string(&path).expect("Failed to construct path")
 }
 
+/// Like [`remote_layer_path`], for a layer in the index of the given timeline: layers referenced by
+/// a cross-tenant branch live under the prefix of the timeline which owns them.
+pub fn remote_layer_path_in_index(
+    tenant_id: &TenantId,
+    timeline_id: &TimelineId,
+    layer_file_name: &LayerName,
+    metadata: &LayerFileMetadata,
+) -> RemotePath {
+    let (tenant_id, timeline_id) = match &metadata.cross_tenant_owner {
+        Some(owner) => (&owner.tenant_id, &owner.timeline_id),
+        None => (tenant_id, timeline_id),
+    };
+    remote_layer_path(
+        tenant_id,
+        timeline_id,
+        metadata.shard,
+        layer_file_name,
+        metadata.generation,
+    )
+}
+
 /// Returns true if a and b have the same layer path within a tenant/timeline. This is essentially
 /// remote_layer_path(a) == remote_layer_path(b) without the string allocations.
 ///
@@ -2667,7 +3052,10 @@ pub fn is_same_remote_layer_path(
     bmeta: &LayerFileMetadata,
 ) -> bool {
     // NB: don't assert remote_layer_path(a) == remote_layer_path(b); too expensive even for debug.
-    aname == bname && ameta.shard == bmeta.shard && ameta.generation == bmeta.generation
+    aname == bname
+        && ameta.shard == bmeta.shard
+        && ameta.generation == bmeta.generation
+        && ameta.cross_tenant_owner == bmeta.cross_tenant_owner
 }
 
 pub fn remote_initdb_archive_path(tenant_id: &TenantId, timeline_id: &TimelineId) -> RemotePath {
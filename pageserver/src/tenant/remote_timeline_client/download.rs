@@ -5,6 +5,7 @@
 
 use std::collections::HashSet;
 use std::future::Future;
+use std::ops::{Bound, Range};
 use std::str::FromStr;
 use std::time::SystemTime;
 
@@ -38,7 +39,7 @@
     debug_assert_current_span_has_tenant_and_timeline_id, debug_assert_current_span_has_tenant_id,
 };
 use crate::tenant::Generation;
-use crate::tenant::remote_timeline_client::{remote_layer_path, remote_timelines_path};
+use crate::tenant::remote_timeline_client::{remote_layer_path_in_index, remote_timelines_path};
 use crate::tenant::storage_layer::LayerName;
 use crate::virtual_file::{MaybeFatalIo, VirtualFile, on_fatal_io_error};
 
@@ -64,12 +65,11 @@ pub async fn download_layer_file<'a>(
 
     let timeline_path = conf.timeline_path(&tenant_shard_id, &timeline_id);
 
-    let remote_path = remote_layer_path(
+    let remote_path = remote_layer_path_in_index(
         &tenant_shard_id.tenant_id,
         &timeline_id,
-        layer_metadata.shard,
         layer_file_name,
-        layer_metadata.generation,
+        layer_metadata,
     );
 
     // Perform a rename inspired by durable_rename from file_utils.c.
@@ -135,6 +135,62 @@ pub async fn download_layer_file<'a>(
     Ok(bytes_amount)
 }
 
+/// Download the byte range `range` of a layer file into memory.
+///
+/// Used for partially resident layers, which only fetch the parts of the file needed by reads.
+pub async fn download_layer_range(
+    storage: &GenericRemoteStorage,
+    tenant_shard_id: TenantShardId,
+    timeline_id: TimelineId,
+    layer_file_name: &LayerName,
+    layer_metadata: &LayerFileMetadata,
+    range: Range<u64>,
+    cancel: &CancellationToken,
+) -> Result<Vec<u8>, DownloadError> {
+    debug_assert_current_span_has_tenant_and_timeline_id();
+    assert!(range.start < range.end && range.end <= layer_metadata.file_size);
+
+    let remote_path = remote_layer_path_in_index(
+        &tenant_shard_id.tenant_id,
+        &timeline_id,
+        layer_file_name,
+        layer_metadata,
+    );
+
+    let download_opts = DownloadOpts {
+        byte_start: Bound::Included(range.start),
+        byte_end: Bound::Excluded(range.end),
+        ..Default::default()
+    };
+
+    let bytes = download_retry(
+        || async {
+            let download = storage
+                .download(&remote_path, &download_opts, cancel)
+                .await?;
+
+            let mut bytes = Vec::with_capacity((range.end - range.start) as usize);
+            let mut stream = StreamReader::new(download.download_stream);
+            tokio::io::copy_buf(&mut stream, &mut bytes).await?;
+
+            Ok(bytes)
+        },
+        &format!("download {remote_path:?} range {range:?}"),
+        cancel,
+    )
+    .await?;
+
+    let expected = range.end - range.start;
+    if expected != bytes.len() as u64 {
+        return Err(DownloadError::Other(anyhow!(
+            "Should have downloaded {expected} bytes of range {range:?} from {remote_path:?} but downloaded {} bytes",
+            bytes.len()
+        )));
+    }
+
+    Ok(bytes)
+}
+
 /// Download the object `src_path` in the remote `storage` to local path `dst_path`.
 ///
 /// If Ok() is returned, the download succeeded and the inode & data have been made durable.
@@ -7,10 +7,10 @@
 
 use chrono::NaiveDateTime;
 use pageserver_api::models::AuxFilePolicy;
-use pageserver_api::models::RelSizeMigration;
+use pageserver_api::models::{RelSizeMigration, RestorePoint, TimelineRetentionPolicy};
 use pageserver_api::shard::ShardIndex;
 use serde::{Deserialize, Serialize};
-use utils::id::TimelineId;
+use utils::id::{TenantId, TenantTimelineId, TimelineId};
 use utils::lsn::Lsn;
 
 use super::is_same_remote_layer_path;
@@ -114,6 +114,63 @@ pub struct IndexPart {
     /// The timestamp when the timeline was marked invisible in synthetic size calculations.
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub(crate) marked_invisible_at: Option<NaiveDateTime>,
+
+    /// Set on a timeline branched off a timeline of another tenant. The layers of the ancestor
+    /// up to the branch point are referenced in [`Self::layer_metadata`] with
+    /// [`LayerFileMetadata::cross_tenant_owner`] instead of being copied.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) cross_tenant_ancestor: Option<CrossTenantAncestor>,
+
+    /// Timelines of other tenants which reference layers of this timeline.
+    ///
+    /// Layers referenced by any of them are not deleted from remote storage when unlinked from
+    /// this index, and the scrubber counts them as referenced.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub cross_tenant_children: Vec<CrossTenantChild>,
+
+    /// Overrides the retention settings of the tenant for this timeline.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) retention_policy: Option<TimelineRetentionPolicy>,
+
+    /// The restore points of [`Self::retention_policy`] resolved so far, oldest first.
+    ///
+    /// Persisted because a point in time can no longer be resolved to an LSN once GC has moved
+    /// past it. Shards other than shard zero copy these from shard zero's index.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) restore_points: Vec<RestorePoint>,
+}
+
+/// The ancestor of a timeline branched off another tenant, see [`IndexPart::cross_tenant_ancestor`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub struct CrossTenantAncestor {
+    pub tenant_id: TenantId,
+    pub timeline_id: TimelineId,
+    pub lsn: Lsn,
+}
+
+/// A timeline of another tenant branched off this one, see [`IndexPart::cross_tenant_children`].
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct CrossTenantChild {
+    pub tenant_id: TenantId,
+    pub timeline_id: TimelineId,
+    /// The branch point, which an LSN lease protects from GC for as long as the child exists.
+    pub lsn: Lsn,
+    /// The layers of this timeline which the child referenced when it was created. The child
+    /// never references more of them later on.
+    pub layers: HashMap<LayerName, LayerFileMetadata>,
+}
+
+impl CrossTenantChild {
+    pub fn id(&self) -> TenantTimelineId {
+        TenantTimelineId::new(self.tenant_id, self.timeline_id)
+    }
+
+    /// Returns true if the child references this layer of ours.
+    pub fn references(&self, name: &LayerName, metadata: &LayerFileMetadata) -> bool {
+        self.layers
+            .get(name)
+            .is_some_and(|m| is_same_remote_layer_path(name, m, name, metadata))
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -142,10 +199,13 @@ impl IndexPart {
     /// - 12: +l2_lsn
     /// - 13: +gc_compaction
     /// - 14: +marked_invisible_at
-    const LATEST_VERSION: usize = 14;
+    /// - 15: +cross_tenant_ancestor, +cross_tenant_children, +LayerFileMetadata::cross_tenant_owner
+    /// - 16: +retention_policy, +restore_points
+    const LATEST_VERSION: usize = 16;
 
     // Versions we may see when reading from a bucket.
-    pub const KNOWN_VERSIONS: &'static [usize] = &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14];
+    pub const KNOWN_VERSIONS: &'static [usize] =
+        &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
 
     pub const FILE_NAME: &'static str = "index_part.json";
 
@@ -165,6 +225,10 @@ pub fn empty(metadata: TimelineMetadata) -> Self {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         }
     }
 
@@ -233,6 +297,12 @@ pub struct LayerFileMetadata {
     #[serde(default = "ShardIndex::unsharded")]
     #[serde(skip_serializing_if = "ShardIndex::is_unsharded")]
     pub shard: ShardIndex,
+
+    /// The timeline of another tenant under whose remote prefix the layer is stored, for layers
+    /// referenced by a cross-tenant branch. `None` for the layers of this timeline.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cross_tenant_owner: Option<TenantTimelineId>,
 }
 
 impl LayerFileMetadata {
@@ -241,6 +311,7 @@ pub fn new(file_size: u64, generation: Generation, shard: ShardIndex) -> Self {
             file_size,
             generation,
             shard,
+            cross_tenant_owner: None,
         }
     }
     /// Helper to get both generation and file size in a tuple
@@ -453,14 +524,16 @@ fn v1_indexpart_is_parsed() {
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     // serde_json should always parse this but this might be a double with jq for
                     // example.
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -475,6 +548,10 @@ fn v1_indexpart_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -502,14 +579,16 @@ fn v1_indexpart_is_parsed_with_optional_missing_layers() {
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     // serde_json should always parse this but this might be a double with jq for
                     // example.
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -524,6 +603,10 @@ fn v1_indexpart_is_parsed_with_optional_missing_layers() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -552,14 +635,16 @@ fn v2_indexpart_is_parsed_with_deleted_at() {
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     // serde_json should always parse this but this might be a double with jq for
                     // example.
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -574,6 +659,10 @@ fn v2_indexpart_is_parsed_with_deleted_at() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -627,6 +716,10 @@ fn empty_layers_are_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         };
 
         let empty_layers_parsed = IndexPart::from_json_bytes(empty_layers_json.as_bytes()).unwrap();
@@ -653,14 +746,16 @@ fn v4_indexpart_is_parsed() {
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     // serde_json should always parse this but this might be a double with jq for
                     // example.
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -675,6 +770,10 @@ fn v4_indexpart_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -703,11 +802,13 @@ fn v5_indexpart_is_parsed() {
                     file_size: 23289856,
                     generation: Generation::new(1),
                     shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None,
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000014EF499-00000000015A7619".parse().unwrap(), LayerFileMetadata {
                     file_size: 1015808,
                     generation: Generation::new(1),
                     shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None,
                 })
             ]),
             disk_consistent_lsn: Lsn::from_str("0/15A7618").unwrap(),
@@ -726,6 +827,10 @@ fn v5_indexpart_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -756,14 +861,16 @@ fn v6_indexpart_is_parsed() {
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     // serde_json should always parse this but this might be a double with jq for
                     // example.
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -782,6 +889,10 @@ fn v6_indexpart_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -815,12 +926,14 @@ fn v7_indexpart_is_parsed() {
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -843,6 +956,10 @@ fn v7_indexpart_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -877,12 +994,14 @@ fn v8_indexpart_is_parsed() {
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -905,6 +1024,10 @@ fn v8_indexpart_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -941,12 +1064,14 @@ fn v9_indexpart_is_parsed() {
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -972,6 +1097,10 @@ fn v9_indexpart_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -1017,12 +1146,14 @@ fn v10_importpgdata_is_parsed() {
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -1052,6 +1183,10 @@ fn v10_importpgdata_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -1098,12 +1233,14 @@ fn v11_rel_size_migration_is_parsed() {
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -1133,6 +1270,10 @@ fn v11_rel_size_migration_is_parsed() {
             l2_lsn: None,
             gc_compaction: None,
             marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -1183,12 +1324,14 @@ fn v12_v13_l2_gc_ompaction_is_parsed() {
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -1220,6 +1363,10 @@ fn v12_v13_l2_gc_ompaction_is_parsed() {
                 last_completed_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
             }),
             marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
@@ -1271,12 +1418,14 @@ fn v14_marked_invisible_at_is_parsed() {
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
                     file_size: 25600000,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 }),
                 ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016B59D8-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
                     file_size: 9007199254741001,
                     generation: Generation::none(),
-                    shard: ShardIndex::unsharded()
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None
                 })
             ]),
             disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
@@ -1308,12 +1457,197 @@ fn v14_marked_invisible_at_is_parsed() {
                 last_completed_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
             }),
             marked_invisible_at: Some(parse_naive_datetime("2023-07-31T09:00:00.123000000")),
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: None,
+            restore_points: Vec::new(),
         };
 
         let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
         assert_eq!(part, expected);
     }
 
+    #[test]
+    fn v15_cross_tenant_branch_is_parsed() {
+        let example = r#"{
+            "version": 15,
+            "layer_metadata":{
+                "000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9": { "file_size": 25600000, "generation": 3, "cross_tenant_owner": { "tenant_id": "e45a7f37d3ee2ff17dc14bf4f4e3f52e", "timeline_id": "9cd1c5e6d1b0d5d1e6e8e4c7a4b5f1a2" } },
+                "000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016960E9-00000000016B5A51": { "file_size": 9007199254741001 }
+            },
+            "disk_consistent_lsn":"0/16B5A50",
+            "metadata": {
+                "disk_consistent_lsn": "0/16B5A50",
+                "prev_record_lsn": "0/16B59D8",
+                "ancestor_timeline": null,
+                "ancestor_lsn": "0/0",
+                "latest_gc_cutoff_lsn": "0/1696070",
+                "initdb_lsn": "0/1696070",
+                "pg_version": 14
+            },
+            "cross_tenant_ancestor": {
+                "tenant_id": "e45a7f37d3ee2ff17dc14bf4f4e3f52e",
+                "timeline_id": "9cd1c5e6d1b0d5d1e6e8e4c7a4b5f1a2",
+                "lsn": "0/16960E8"
+            },
+            "cross_tenant_children": [{
+                "tenant_id": "3aa8fcc61f6d357410b7de754b1d9001",
+                "timeline_id": "a52e2a1be6a2a1d3e0fe6a95fd2f6a79",
+                "lsn": "0/16B5A50",
+                "layers": {
+                    "000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016960E9-00000000016B5A51": { "file_size": 9007199254741001 }
+                }
+            }]
+        }"#;
+
+        let ancestor = TenantTimelineId::new(
+            TenantId::from_str("e45a7f37d3ee2ff17dc14bf4f4e3f52e").unwrap(),
+            TimelineId::from_str("9cd1c5e6d1b0d5d1e6e8e4c7a4b5f1a2").unwrap(),
+        );
+
+        let expected = IndexPart {
+            version: 15,
+            layer_metadata: HashMap::from([
+                ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
+                    file_size: 25600000,
+                    generation: Generation::new(3),
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: Some(ancestor),
+                }),
+                ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016960E9-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
+                    file_size: 9007199254741001,
+                    generation: Generation::none(),
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None,
+                })
+            ]),
+            disk_consistent_lsn: "0/16B5A50".parse::<Lsn>().unwrap(),
+            metadata: TimelineMetadata::new(
+                Lsn::from_str("0/16B5A50").unwrap(),
+                Some(Lsn::from_str("0/16B59D8").unwrap()),
+                None,
+                Lsn::INVALID,
+                Lsn::from_str("0/1696070").unwrap(),
+                Lsn::from_str("0/1696070").unwrap(),
+                14,
+            ).with_recalculated_checksum().unwrap(),
+            deleted_at: None,
+            lineage: Default::default(),
+            gc_blocking: None,
+            last_aux_file_policy: Default::default(),
+            archived_at: None,
+            import_pgdata: None,
+            rel_size_migration: None,
+            l2_lsn: None,
+            gc_compaction: None,
+            marked_invisible_at: None,
+            cross_tenant_ancestor: Some(CrossTenantAncestor {
+                tenant_id: ancestor.tenant_id,
+                timeline_id: ancestor.timeline_id,
+                lsn: "0/16960E8".parse::<Lsn>().unwrap(),
+            }),
+            cross_tenant_children: vec![CrossTenantChild {
+                tenant_id: TenantId::from_str("3aa8fcc61f6d357410b7de754b1d9001").unwrap(),
+                timeline_id: TimelineId::from_str("a52e2a1be6a2a1d3e0fe6a95fd2f6a79").unwrap(),
+                lsn: "0/16B5A50".parse::<Lsn>().unwrap(),
+                layers: HashMap::from([
+                    ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__00000000016960E9-00000000016B5A51".parse().unwrap(), LayerFileMetadata {
+                        file_size: 9007199254741001,
+                        generation: Generation::none(),
+                        shard: ShardIndex::unsharded(),
+                        cross_tenant_owner: None,
+                    }),
+                ]),
+            }],
+            retention_policy: None,
+            restore_points: Vec::new(),
+        };
+
+        let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
+        assert_eq!(part, expected);
+
+        // The references survive a round trip.
+        let bytes = part.to_json_bytes().unwrap();
+        assert_eq!(IndexPart::from_json_bytes(&bytes).unwrap(), expected);
+    }
+
+    #[test]
+    fn v16_retention_policy_is_parsed() {
+        let example = r#"{
+            "version": 16,
+            "layer_metadata":{
+                "000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9": { "file_size": 25600000 }
+            },
+            "disk_consistent_lsn":"0/16960E8",
+            "metadata": {
+                "disk_consistent_lsn": "0/16960E8",
+                "prev_record_lsn": "0/1696070",
+                "ancestor_timeline": null,
+                "ancestor_lsn": "0/0",
+                "latest_gc_cutoff_lsn": "0/1696070",
+                "initdb_lsn": "0/1696070",
+                "pg_version": 14
+            },
+            "retention_policy": {
+                "pitr_interval": "1day",
+                "tiers": [{ "interval": "1h", "keep_for": "7days" }]
+            },
+            "restore_points": [{ "timestamp": "2024-07-19T09:00:00.000Z", "lsn": "0/1696078" }]
+        }"#;
+
+        let expected = IndexPart {
+            version: 16,
+            layer_metadata: HashMap::from([
+                ("000000000000000000000000000000000000-FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF__0000000001696070-00000000016960E9".parse().unwrap(), LayerFileMetadata {
+                    file_size: 25600000,
+                    generation: Generation::none(),
+                    shard: ShardIndex::unsharded(),
+                    cross_tenant_owner: None,
+                })
+            ]),
+            disk_consistent_lsn: "0/16960E8".parse::<Lsn>().unwrap(),
+            metadata: TimelineMetadata::new(
+                Lsn::from_str("0/16960E8").unwrap(),
+                Some(Lsn::from_str("0/1696070").unwrap()),
+                None,
+                Lsn::INVALID,
+                Lsn::from_str("0/1696070").unwrap(),
+                Lsn::from_str("0/1696070").unwrap(),
+                14,
+            ).with_recalculated_checksum().unwrap(),
+            deleted_at: None,
+            lineage: Default::default(),
+            gc_blocking: None,
+            last_aux_file_policy: Default::default(),
+            archived_at: None,
+            import_pgdata: None,
+            rel_size_migration: None,
+            l2_lsn: None,
+            gc_compaction: None,
+            marked_invisible_at: None,
+            cross_tenant_ancestor: None,
+            cross_tenant_children: Vec::new(),
+            retention_policy: Some(TimelineRetentionPolicy {
+                gc_horizon: None,
+                pitr_interval: Some(std::time::Duration::from_secs(86400)),
+                tiers: vec![pageserver_api::models::RetentionTier {
+                    interval: std::time::Duration::from_secs(3600),
+                    keep_for: std::time::Duration::from_secs(7 * 86400),
+                }],
+            }),
+            restore_points: vec![RestorePoint {
+                timestamp: humantime::parse_rfc3339("2024-07-19T09:00:00Z").unwrap(),
+                lsn: "0/1696078".parse::<Lsn>().unwrap(),
+            }],
+        };
+
+        let part = IndexPart::from_json_bytes(example.as_bytes()).unwrap();
+        assert_eq!(part, expected);
+
+        let bytes = part.to_json_bytes().unwrap();
+        assert_eq!(IndexPart::from_json_bytes(&bytes).unwrap(), expected);
+    }
+
     fn parse_naive_datetime(s: &str) -> NaiveDateTime {
         chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S.%f").unwrap()
     }
@@ -97,7 +97,7 @@ pub(crate) async fn upload_tenant_manifest(
 /// No extra checks for overlapping files is made and any files that are already present remotely will be overwritten, if submitted during the upload.
 ///
 /// On an error, bumps the retries count and reschedules the entire task.
-pub(super) async fn upload_timeline_layer<'a>(
+pub(crate) async fn upload_timeline_layer<'a>(
     storage: &'a GenericRemoteStorage,
     local_path: &'a Utf8Path,
     remote_path: &'a RemotePath,
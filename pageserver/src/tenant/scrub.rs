@@ -0,0 +1,137 @@
+//! Online scrubbing of resident layer files.
+//!
+//! Layer files are only read in full when they are compacted, so a layer which went bad on the
+//! local disk can serve reads for a long time before anyone notices, or never be noticed at all
+//! if only some of its pages are hot. When `scrub_period` is configured, the tenant's scrub loop
+//! periodically re-reads every resident delta and image layer of the tenant shard and checks:
+//!
+//! - that the size of the local file matches the size recorded in `index_part.json`,
+//! - the structure of the layer's b-tree index, see [`DiskBtreeReader::validate`],
+//! - that every value can be read, decompressed and deserialized, including the blob checksums
+//!   of layers written with `layer_blob_checksums`.
+//!
+//! Reading is paced to `scrub_bytes_per_second`, so that scrubbing does not compete with the
+//! foreground IO. Corrupt layers are only reported, through the metrics, the log and
+//! [`Tenant::get_scrub_status`]: the scrub loop neither evicts nor repairs them.
+//!
+//! [`DiskBtreeReader::validate`]: crate::tenant::disk_btree::DiskBtreeReader::validate
+
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, ensure};
+use pageserver_api::models::{CorruptLayer, TenantScrubStatus};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+use super::Tenant;
+use crate::context::RequestContext;
+use crate::metrics::{
+    LAYER_SCRUB_CHECKED_BYTES, LAYER_SCRUB_CHECKED_LAYERS, LAYER_SCRUB_CORRUPT_LAYERS,
+};
+use crate::tenant::storage_layer::{AsLayerDesc, ResidentLayer};
+use crate::tenant::tasks::{BackgroundLoopKind, acquire_concurrency_permit};
+
+impl Tenant {
+    pub(crate) fn get_scrub_status(&self) -> TenantScrubStatus {
+        self.scrub_status.lock().unwrap().clone()
+    }
+
+    /// Check all resident layer files of this tenant shard for corruption, and publish the
+    /// outcome as the new scrub status. A cancelled pass leaves the previous status in place.
+    pub(crate) async fn scrub_layers(&self, cancel: &CancellationToken, ctx: &RequestContext) {
+        let bytes_per_second = self.get_scrub_bytes_per_second().get();
+        let mut status = TenantScrubStatus::default();
+
+        for timeline in self.list_timelines() {
+            if !timeline.is_active() {
+                continue;
+            }
+
+            let layers = {
+                let Ok(_gate) = timeline.gate.enter() else {
+                    continue;
+                };
+                timeline
+                    .layers
+                    .read()
+                    .await
+                    .likely_resident_layers()
+                    .cloned()
+                    .collect::<Vec<_>>()
+            };
+
+            for layer in layers {
+                if cancel.is_cancelled() || timeline.cancel.is_cancelled() {
+                    return;
+                }
+
+                let started_at = Instant::now();
+                let file_size = layer.layer_desc().file_size;
+                {
+                    // don't hold up timeline shutdown while pacing below
+                    let Ok(_gate) = timeline.gate.enter() else {
+                        return;
+                    };
+
+                    // the layer might have been evicted in the meantime, there is nothing to check
+                    let Some(layer) = layer.keep_resident().await else {
+                        continue;
+                    };
+
+                    let res = {
+                        let _permit =
+                            acquire_concurrency_permit(BackgroundLoopKind::Scrub, ctx).await;
+                        scrub_layer(&layer, ctx).await
+                    };
+
+                    status.layers_checked += 1;
+                    status.bytes_checked += file_size;
+                    LAYER_SCRUB_CHECKED_LAYERS.inc();
+                    LAYER_SCRUB_CHECKED_BYTES.inc_by(file_size);
+
+                    if let Err(e) = res {
+                        error!(timeline_id=%timeline.timeline_id, %layer, "layer file is corrupt: {e:#}");
+                        LAYER_SCRUB_CORRUPT_LAYERS.inc();
+                        status.corrupt_layers.push(CorruptLayer {
+                            timeline_id: timeline.timeline_id,
+                            layer_file_name: layer.layer_desc().layer_name().to_string(),
+                            error: format!("{e:#}"),
+                        });
+                    }
+                }
+
+                let budget = Duration::from_secs_f64(file_size as f64 / bytes_per_second as f64);
+                let pause = budget.saturating_sub(started_at.elapsed());
+                tokio::select! {
+                    _ = tokio::time::sleep(pause) => {}
+                    _ = cancel.cancelled() => return,
+                    _ = timeline.cancel.cancelled() => return,
+                }
+            }
+        }
+
+        info!(
+            layers = status.layers_checked,
+            bytes = status.bytes_checked,
+            corrupt = status.corrupt_layers.len(),
+            "finished scrubbing layer files"
+        );
+
+        status.last_scrub_finished_at = Some(chrono::Utc::now());
+        *self.scrub_status.lock().unwrap() = status;
+    }
+}
+
+async fn scrub_layer(layer: &ResidentLayer, ctx: &RequestContext) -> anyhow::Result<()> {
+    let local_size = tokio::fs::metadata(layer.local_path())
+        .await
+        .context("stat layer file")?
+        .len();
+    let expected_size = layer.metadata().file_size;
+    ensure!(
+        local_size == expected_size,
+        "layer file is {local_size} bytes, but index_part.json records {expected_size} bytes"
+    );
+
+    layer.scrub(ctx).await
+}
@@ -24,6 +24,7 @@
 use super::mgr::TenantManager;
 use super::span::debug_assert_current_span_has_tenant_id;
 use super::storage_layer::LayerName;
+use crate::config::PageServerConf;
 use crate::context::RequestContext;
 use crate::disk_usage_eviction_task::DiskUsageEvictionInfo;
 use crate::metrics::{SECONDARY_HEATMAP_TOTAL_SIZE, SECONDARY_RESIDENT_PHYSICAL_SIZE};
@@ -88,6 +89,8 @@ fn from(gte: GetTenantError) -> Self {
 // secondary tenant should cancel any work in flight.
 #[derive(Debug)]
 pub(crate) struct SecondaryTenant {
+    conf: &'static PageServerConf,
+
     /// Carrying a tenant shard ID simplifies callers such as the downloader
     /// which need to organize many of these objects by ID.
     tenant_shard_id: TenantShardId,
@@ -119,6 +122,7 @@ pub(crate) struct SecondaryTenant {
 
 impl SecondaryTenant {
     pub(crate) fn new(
+        conf: &'static PageServerConf,
         tenant_shard_id: TenantShardId,
         shard_identity: ShardIdentity,
         tenant_conf: pageserver_api::models::TenantConfig,
@@ -135,6 +139,7 @@ pub(crate) fn new(
             .unwrap();
 
         Arc::new(Self {
+            conf,
             tenant_shard_id,
             // todo: shall we make this a descendent of the
             // main cancellation token, or is it sufficient that
@@ -212,6 +217,15 @@ pub(crate) fn get_tenant_shard_id(&self) -> &TenantShardId {
         &self.tenant_shard_id
     }
 
+    /// Same as [`super::Tenant::get_eviction_priority`], for the secondary location.
+    pub(crate) fn get_eviction_priority(&self) -> u8 {
+        self.tenant_conf
+            .lock()
+            .unwrap()
+            .eviction_priority
+            .unwrap_or(self.conf.default_tenant_conf.eviction_priority)
+    }
+
     pub(crate) fn get_layers_for_eviction(self: &Arc<Self>) -> (DiskUsageEvictionInfo, usize) {
         self.detail.lock().unwrap().get_layers_for_eviction(self)
     }
@@ -366,6 +366,7 @@ pub(super) fn get_layers_for_eviction(
                         }),
                         last_activity_ts: ods.access_time,
                         relative_last_activity: finite_f32::FiniteF32::ZERO,
+                        eviction_priority: 0,
                         // Secondary location layers are presumed visible, because Covered layers
                         // are excluded from the heatmap
                         visibility: LayerVisibilityHint::Visible,
@@ -0,0 +1,532 @@
+//! Merging two shards of a tenant into one shard of half the shard count.
+//!
+//! A shard split can reuse the parent's layer files as they are, because each child just ignores
+//! the keys which it doesn't own. A merge cannot do the same: the layer files of the two parents
+//! cover overlapping key and LSN ranges, and each of them only holds the keys of its own shard,
+//! so the union of them is not a valid layer map. Instead, the merged shard gets a fresh set of
+//! image layers: for each timeline, we read every key that the merged shard will hold at a
+//! merge LSN from whichever parent holds it, write the images into layers under the merged
+//! shard's own prefix, and upload an `index_part.json` whose `disk_consistent_lsn` is the merge
+//! LSN. Once attached, the merged shard ingests WAL from the merge LSN onwards like any other.
+//!
+//! The safekeepers only retain WAL that some pageserver has not yet made durable in remote
+//! storage. Before picking the merge LSN, we therefore stop the parents' WAL ingest and uploads:
+//! their `remote_consistent_lsn` stays at or below the merge LSN, and the safekeepers keep the
+//! WAL which the merged shard needs until it has taken over. If the merge fails, the parents are
+//! reset to resume both.
+//!
+//! History below the merge LSN is not carried over. The merge LSN becomes the merged shard's GC
+//! cutoff, so reads and branch creation below it are refused, as if GC had run up to it. The
+//! parents' older layers cannot simply be referenced by the merged shard the way a split child
+//! references its parent's: an image layer of one parent covers a key range, but lacks the keys
+//! of the other parent in it, so the read path would find a key missing rather than look it up
+//! further down. Merges are therefore refused while anything still needs that history: child
+//! branches (in this or another tenant), LSN leases, restore points, or a PITR interval.
+//!
+//! Once the merge is complete, nothing references the parents' remote objects any more, and the
+//! storage controller deletes them.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use bytes::{Buf, Bytes};
+use pageserver_api::key::Key;
+use pageserver_api::keyspace::{KeySpace, KeySpaceAccum, KeySpaceRandomAccum};
+use pageserver_api::shard::{ShardIdentity, TenantShardId};
+use remote_storage::{GenericRemoteStorage, TimeoutOrCancel};
+use tokio_util::sync::CancellationToken;
+use tracing::{Instrument, info, info_span};
+use utils::backoff;
+use utils::crashsafe;
+use utils::generation::Generation;
+use utils::id::TimelineId;
+use utils::lsn::Lsn;
+
+use super::Tenant;
+use super::metadata::TimelineMetadata;
+use super::remote_timeline_client::index::{IndexPart, LayerFileMetadata};
+use super::remote_timeline_client::upload::{
+    upload_index_part, upload_tenant_manifest, upload_timeline_layer,
+};
+use super::remote_timeline_client::{
+    FAILED_REMOTE_OP_RETRIES, FAILED_UPLOAD_WARN_THRESHOLD, remote_layer_path,
+};
+use super::storage_layer::layer::local_layer_path;
+use super::storage_layer::{ImageLayerWriter, IoConcurrency, ValuesReconstructState};
+use super::timeline::Timeline;
+use crate::config::PageServerConf;
+use crate::context::RequestContext;
+
+impl Tenant {
+    /// Prepare the merge of this shard and `sibling` into `child_shard`, see the module docs.
+    ///
+    /// Writes the merged shard's layers, indices and tenant manifest to remote storage in
+    /// `generation`. Local copies of the layers are left in the merged shard's directory, so
+    /// that it does not have to download them again once it is attached. `self` must be the
+    /// parent with the lower shard number.
+    pub(crate) async fn merge_prepare(
+        &self,
+        sibling: &Tenant,
+        child_shard: TenantShardId,
+        child_identity: ShardIdentity,
+        generation: Generation,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
+        // Offloaded timelines have no layers loaded that we could read from.
+        if !self.timelines_offloaded.lock().unwrap().is_empty()
+            || !sibling.timelines_offloaded.lock().unwrap().is_empty()
+        {
+            anyhow::bail!("Cannot merge shards with offloaded timelines");
+        }
+
+        let timelines = self.timelines.lock().unwrap().clone();
+        let sibling_timelines = sibling.timelines.lock().unwrap().clone();
+        if timelines.keys().collect::<HashSet<_>>()
+            != sibling_timelines.keys().collect::<HashSet<_>>()
+        {
+            anyhow::bail!("Shards to merge do not have the same timelines");
+        }
+
+        // The merged shard only keeps history from the merge LSN onwards.
+        if !self.get_pitr_interval().is_zero() {
+            anyhow::bail!(
+                "Cannot merge shards with a non-zero pitr_interval: the history below the merge LSN would be lost"
+            );
+        }
+        for timeline in timelines.values().chain(sibling_timelines.values()) {
+            check_history_not_needed(timeline)?;
+        }
+
+        // From here on, the parents have to be reset if the merge fails, see the module docs.
+        for timeline in timelines.values().chain(sibling_timelines.values()) {
+            stop_ingest_and_uploads(timeline)
+                .instrument(
+                    info_span!("stop_ingest_and_uploads", timeline_id=%timeline.timeline_id),
+                )
+                .await;
+        }
+
+        let merge = ShardMerge {
+            conf: self.conf,
+            remote_storage: &self.remote_storage,
+            child_shard,
+            child_identity,
+            generation,
+            target_layer_size: self.get_compaction_target_size(),
+            cancel: &self.cancel,
+        };
+
+        // We do not block timeline creation/deletion during merges inside the pageserver: it is
+        // up to higher levels to ensure that they do not start a merge while doing these.
+        for (timeline_id, timeline) in &timelines {
+            let sibling_timeline = &sibling_timelines[timeline_id];
+            if !timeline.is_active() || !sibling_timeline.is_active() {
+                anyhow::bail!("Timeline {timeline_id} is not active");
+            }
+
+            let index_part = merge
+                .merge_timeline(timeline, sibling_timeline, ctx)
+                .instrument(info_span!("merge_timeline", %timeline_id))
+                .await?;
+
+            info!(%timeline_id, "Uploading index_part for merged shard {}", child_shard.to_index());
+            upload_index_part(
+                &self.remote_storage,
+                &child_shard,
+                timeline_id,
+                generation,
+                &index_part,
+                &self.cancel,
+            )
+            .await?;
+        }
+
+        info!(
+            "Uploading tenant manifest for merged shard {}",
+            child_shard.to_index()
+        );
+        upload_tenant_manifest(
+            &self.remote_storage,
+            &child_shard,
+            generation,
+            &self.build_tenant_manifest(),
+            &self.cancel,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Refuse to merge a timeline if anything still depends on its history below the merge LSN,
+/// see the module docs.
+fn check_history_not_needed(timeline: &Timeline) -> anyhow::Result<()> {
+    let timeline_id = timeline.timeline_id;
+
+    // Branches are always forked below the merge LSN, which is the parent's last record LSN.
+    if timeline.get_ancestor_timeline_id().is_some()
+        || timeline.remote_client.cross_tenant_ancestor().is_some()
+    {
+        anyhow::bail!("Cannot merge shards with branches: timeline {timeline_id} is a branch");
+    }
+    {
+        let gc_info = timeline.gc_info.read().unwrap();
+        if !gc_info.retain_lsns.is_empty() {
+            anyhow::bail!("Cannot merge shards with branches: timeline {timeline_id} has children");
+        }
+        let now = SystemTime::now();
+        if gc_info.leases.values().any(|lease| !lease.is_expired(&now)) {
+            anyhow::bail!("Cannot merge shards with LSN leases on timeline {timeline_id}");
+        }
+        if !gc_info.restore_points.is_empty() {
+            anyhow::bail!("Cannot merge shards with restore points on timeline {timeline_id}");
+        }
+    }
+    let cross_tenant_children = timeline
+        .remote_client
+        .cross_tenant_children()
+        .unwrap_or_default()
+        .iter()
+        .map(|c| c.id())
+        .collect::<Vec<_>>();
+    if !cross_tenant_children.is_empty() {
+        anyhow::bail!(
+            "Cannot merge shards with branches: timeline {timeline_id} has children in other tenants: {cross_tenant_children:?}"
+        );
+    }
+    Ok(())
+}
+
+/// Stop a parent timeline's WAL ingest and uploads, so that its `remote_consistent_lsn`, which
+/// the safekeepers' WAL retention follows, does not advance past the merge LSN.
+async fn stop_ingest_and_uploads(timeline: &Timeline) {
+    let walreceiver = timeline.walreceiver.lock().unwrap().take();
+    if let Some(walreceiver) = walreceiver {
+        walreceiver.cancel();
+    }
+    info!("Shutting down remote storage client");
+    timeline.remote_client.shutdown().await;
+}
+
+/// The parameters of a merge which are common to all of its timelines.
+struct ShardMerge<'a> {
+    conf: &'static PageServerConf,
+    remote_storage: &'a GenericRemoteStorage,
+    child_shard: TenantShardId,
+    child_identity: ShardIdentity,
+    generation: Generation,
+    target_layer_size: u64,
+    cancel: &'a CancellationToken,
+}
+
+/// Which of the two parent shards a key is read from.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Parent {
+    Lower,
+    Upper,
+}
+
+impl ShardMerge<'_> {
+    /// Write and upload the image layers of the merged timeline, returning the index which
+    /// references them.
+    async fn merge_timeline(
+        &self,
+        lower: &Arc<Timeline>,
+        upper: &Arc<Timeline>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<IndexPart> {
+        let timeline_id = lower.timeline_id;
+
+        // Merge at the LSN which the parent that is further behind has ingested up to: both
+        // parents can serve reads there. The parents' uploads are stopped, so the safekeepers
+        // retain the WAL after it for the merged shard to ingest.
+        let record_lsn = std::cmp::min_by_key(
+            lower.get_last_record_rlsn(),
+            upper.get_last_record_rlsn(),
+            |r| r.last,
+        );
+        let lsn = record_lsn.last;
+        info!("Merging at lsn {lsn}");
+
+        let timeline_path = self.conf.timeline_path(&self.child_shard, &timeline_id);
+        tokio::fs::create_dir_all(&timeline_path)
+            .await
+            .with_context(|| format!("Creating {timeline_path}"))?;
+
+        let mut index_part = IndexPart::empty(TimelineMetadata::new(
+            lsn,
+            Some(record_lsn.prev).filter(|prev| prev.is_valid()),
+            lower.get_ancestor_timeline_id(),
+            lower.get_ancestor_lsn(),
+            lsn,
+            lower.initdb_lsn,
+            lower.pg_version,
+        ));
+
+        let io_concurrency = |timeline: &Arc<Timeline>| {
+            timeline
+                .gate
+                .enter()
+                .map(|guard| IoConcurrency::spawn_from_conf(self.conf, guard))
+                .map_err(|_| anyhow::anyhow!("Timeline is shutting down"))
+        };
+        let lower_io_concurrency = io_concurrency(lower)?;
+        let upper_io_concurrency = io_concurrency(upper)?;
+
+        // Each parent contributes the keys from its own keyspace which it owns. Keys which are
+        // stored on all shards are taken from the lower parent.
+        let (lower_keyspace, sparse_keyspace) = lower.collect_keyspace(lsn, ctx).await?;
+        let (upper_keyspace, _) = upper.collect_keyspace(lsn, ctx).await?;
+        let source_of = |key: &Key| {
+            if !lower.get_shard_identity().is_key_disposable(key) && lower_keyspace.contains(key) {
+                Some(Parent::Lower)
+            } else if !upper.get_shard_identity().is_key_disposable(key)
+                && upper_keyspace.contains(key)
+            {
+                Some(Parent::Upper)
+            } else {
+                None
+            }
+        };
+        let mut all_keys = KeySpaceRandomAccum::new();
+        all_keys.add_keyspace(lower_keyspace.clone());
+        all_keys.add_keyspace(upper_keyspace.clone());
+        let all_keys = all_keys.to_keyspace();
+
+        let dense_range = Key::MIN..Key::metadata_key_range().start;
+        let mut writer = MergedLayerWriter::new(self, timeline_id, lsn, dense_range.start);
+        let mut keys = all_keys
+            .ranges
+            .iter()
+            .flat_map(|range| {
+                std::iter::successors(Some(range.start), |key| Some(key.next()))
+                    .take_while(|key| *key < range.end)
+            })
+            .filter_map(|key| source_of(&key).map(|parent| (parent, key)))
+            .peekable();
+        let mut batch = KeySpaceAccum::new();
+        while let Some((parent, key)) = keys.next() {
+            debug_assert!(!self.child_identity.is_key_disposable(&key));
+            batch.add_key(key);
+
+            // Read in batches of keys that come from the same parent
+            let batch_done = match keys.peek() {
+                Some((next_parent, _)) => {
+                    *next_parent != parent || batch.raw_size() >= Timeline::MAX_GET_VECTORED_KEYS
+                }
+                None => true,
+            };
+            if !batch_done {
+                continue;
+            }
+
+            let (timeline, io_concurrency) = match parent {
+                Parent::Lower => (lower, &lower_io_concurrency),
+                Parent::Upper => (upper, &upper_io_concurrency),
+            };
+            let results = timeline
+                .get_vectored(batch.consume_keyspace(), lsn, io_concurrency.clone(), ctx)
+                .await?;
+            for (key, img) in results {
+                let img = self
+                    .merged_rel_size(key, img?, lower, upper, lsn, ctx)
+                    .await;
+                writer.put_image(key, img, &mut index_part, ctx).await?;
+            }
+        }
+        writer.finish(dense_range.end, &mut index_part, ctx).await?;
+
+        // Metadata keys are only stored on shard zero, so there is nothing to do unless the lower
+        // parent is shard zero. They are few enough to read them in one go.
+        let sparse_keyspace: KeySpace = sparse_keyspace.0;
+        if !sparse_keyspace.is_empty() {
+            let metadata_range = Key::metadata_key_range();
+            let mut reconstruct_state = ValuesReconstructState::new(lower_io_concurrency);
+            let results = lower
+                .get_vectored_impl(sparse_keyspace, lsn, &mut reconstruct_state, ctx)
+                .await?;
+            let mut writer = MergedLayerWriter::new(self, timeline_id, lsn, metadata_range.start);
+            for (key, img) in results {
+                let img = img?;
+                // In the metadata keyspace, an empty image is a tombstone
+                if img.is_empty() || self.child_identity.is_key_disposable(&key) {
+                    continue;
+                }
+                writer.put_image(key, img, &mut index_part, ctx).await?;
+            }
+            writer
+                .finish(metadata_range.end, &mut index_part, ctx)
+                .await?;
+        }
+
+        crashsafe::fsync_async(&timeline_path)
+            .await
+            .with_context(|| format!("fsync {timeline_path}"))?;
+
+        info!(
+            "Wrote {} layers for merged shard {}",
+            index_part.layer_metadata.len(),
+            self.child_shard.to_index()
+        );
+
+        Ok(index_part)
+    }
+
+    /// Only shard zero keeps relation sizes exact: other shards only know the highest block they
+    /// have ingested. Unless the lower parent is shard zero, the merged shard therefore takes the
+    /// larger of the two parents' relation sizes.
+    async fn merged_rel_size(
+        &self,
+        key: Key,
+        img: Bytes,
+        lower: &Timeline,
+        upper: &Timeline,
+        lsn: Lsn,
+        ctx: &RequestContext,
+    ) -> Bytes {
+        if !key.is_rel_size_key() || lower.get_shard_identity().is_shard_zero() {
+            return img;
+        }
+
+        match upper.get(key, lsn, ctx).await {
+            Ok(upper_img) if (&upper_img[..]).get_u32_le() > (&img[..]).get_u32_le() => upper_img,
+            _ => img,
+        }
+    }
+}
+
+/// Writes the images of one key range of a merged timeline into image layers of about the
+/// target layer size, and uploads them. The layers tile the key range without gaps, so that
+/// reads of keys which have no image do not fall through to an ancestor timeline.
+struct MergedLayerWriter<'a> {
+    merge: &'a ShardMerge<'a>,
+    timeline_id: TimelineId,
+    lsn: Lsn,
+    /// Where the next layer starts: the end of the previous one.
+    next_start: Key,
+    writer: Option<ImageLayerWriter>,
+}
+
+impl<'a> MergedLayerWriter<'a> {
+    fn new(merge: &'a ShardMerge<'a>, timeline_id: TimelineId, lsn: Lsn, start: Key) -> Self {
+        Self {
+            merge,
+            timeline_id,
+            lsn,
+            next_start: start,
+            writer: None,
+        }
+    }
+
+    /// Keys must be written in ascending order.
+    async fn put_image(
+        &mut self,
+        key: Key,
+        img: Bytes,
+        index_part: &mut IndexPart,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
+        if self
+            .writer
+            .as_ref()
+            .is_some_and(|w| w.estimated_size() >= self.merge.target_layer_size)
+        {
+            self.finish_layer(key, index_part, ctx).await?;
+        }
+
+        if self.writer.is_none() {
+            self.writer = Some(
+                ImageLayerWriter::new(
+                    self.merge.conf,
+                    self.timeline_id,
+                    self.merge.child_shard,
+                    &(self.next_start..Key::MAX),
+                    self.lsn,
+                    ctx,
+                )
+                .await?,
+            );
+        }
+        self.writer.as_mut().unwrap().put_image(key, img, ctx).await
+    }
+
+    async fn finish(
+        mut self,
+        end_key: Key,
+        index_part: &mut IndexPart,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
+        if self.writer.is_some() {
+            self.finish_layer(end_key, index_part, ctx).await?;
+        }
+        Ok(())
+    }
+
+    async fn finish_layer(
+        &mut self,
+        end_key: Key,
+        index_part: &mut IndexPart,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
+        let ShardMerge {
+            conf,
+            remote_storage,
+            child_shard,
+            generation,
+            cancel,
+            ..
+        } = *self.merge;
+
+        let writer = self.writer.take().expect("checked by callers");
+        let (desc, temp_path) = writer.finish_with_end_key(end_key, ctx).await?;
+        self.next_start = end_key;
+
+        let layer_name = desc.layer_name();
+        let local_path = local_layer_path(
+            conf,
+            &child_shard,
+            &self.timeline_id,
+            &layer_name,
+            &generation,
+        );
+        // A previous, failed attempt to merge may have left the same layer behind
+        tokio::fs::rename(&temp_path, &local_path)
+            .await
+            .with_context(|| format!("Renaming {temp_path} to {local_path}"))?;
+
+        let remote_path = remote_layer_path(
+            &child_shard.tenant_id,
+            &self.timeline_id,
+            child_shard.to_index(),
+            &layer_name,
+            generation,
+        );
+        backoff::retry(
+            || {
+                upload_timeline_layer(
+                    remote_storage,
+                    &local_path,
+                    &remote_path,
+                    desc.file_size,
+                    cancel,
+                )
+            },
+            TimeoutOrCancel::caused_by_cancel,
+            FAILED_UPLOAD_WARN_THRESHOLD,
+            FAILED_REMOTE_OP_RETRIES,
+            "upload merged layer",
+            cancel,
+        )
+        .await
+        .ok_or_else(|| anyhow::Error::new(TimeoutOrCancel::Cancel))
+        .and_then(|x| x)?;
+
+        index_part.layer_metadata.insert(
+            layer_name,
+            LayerFileMetadata::new(desc.file_size, generation, child_shard.to_index()),
+        );
+        Ok(())
+    }
+}
@@ -40,6 +40,7 @@
 
 use self::inmemory_layer::InMemoryLayerFileId;
 use super::PageReconstructError;
+use super::blob_io::BlobChecksumMismatch;
 use super::layer_map::InMemoryLayerDesc;
 use super::timeline::{GetVectoredError, ReadPath};
 use crate::config::PageServerConf;
@@ -112,6 +113,10 @@ pub(crate) struct VectoredValueReconstructState {
     pub(crate) on_disk_values: Vec<(Lsn, OnDiskValueIoWaiter)>,
 
     pub(crate) situation: ValueReconstructSituation,
+
+    /// Image from the [`crate::materialized_page_cache`] to use as the base image
+    /// once the traversal goes at or below its LSN.
+    cached_img: Option<(Lsn, Bytes)>,
 }
 
 #[derive(Debug)]
@@ -181,6 +186,12 @@ pub(crate) async fn collect_pending_ios(
     ) -> Result<ValueReconstructState, PageReconstructError> {
         use utils::bin_ser::BeSer;
 
+        let Self {
+            on_disk_values,
+            situation: _,
+            cached_img,
+        } = self;
+
         let mut res = Ok(ValueReconstructState::default());
 
         // We should try hard not to bail early, so that by the time we return from this
@@ -193,7 +204,7 @@ pub(crate) async fn collect_pending_ios(
         // Revisit this when IO futures are replaced with a more sophisticated IO system
         // and an IO scheduler, where we know which IOs were submitted and which ones
         // just queued. Cf the comment on IoConcurrency::spawn_io.
-        for (lsn, waiter) in self.on_disk_values {
+        for (lsn, waiter) in on_disk_values {
             let value_recv_res = waiter
                 .wait_completion()
                 // we rely on the caller to poll us to completion, so this is not a bail point
@@ -209,6 +220,9 @@ pub(crate) async fn collect_pending_ios(
                         // This shouldn't happen - likely the sidecar task panicked.
                         res = Err(PageReconstructError::Other(wait_err.into()));
                     }
+                    (Ok(_), Ok(Err(err))) if BlobChecksumMismatch::is_cause_of(&err) => {
+                        res = Err(PageReconstructError::Corruption(err.into()));
+                    }
                     (Ok(_), Ok(Err(err))) => {
                         let err: std::io::Error = err;
                         // TODO: returning IO error here will fail a compute query.
@@ -241,6 +255,11 @@ pub(crate) async fn collect_pending_ios(
             })();
         }
 
+        if let (Ok(ok), Some(cached_img)) = (&mut res, cached_img) {
+            assert!(ok.img.is_none());
+            ok.img = Some(cached_img);
+        }
+
         res
     }
 }
@@ -651,6 +670,17 @@ pub(crate) fn on_image_layer_visited(&mut self, key_range: &Range<Key>) {
         );
     }
 
+    /// Provide a cached image of `key` at `lsn` as the base for its reconstruction.
+    ///
+    /// The key completes as soon as the traversal reaches a value at or below `lsn`,
+    /// without reading that value or anything older.
+    pub(crate) fn set_cached_image(&mut self, key: Key, lsn: Lsn, img: Bytes) {
+        debug_assert!(!key.is_sparse());
+        let state = self.keys.entry(key).or_default();
+        assert_eq!(state.situation, ValueReconstructSituation::Continue);
+        state.cached_img = Some((lsn, img));
+    }
+
     /// Update the state collected for a given key.
     /// Returns true if this was the last value needed for the key and false otherwise.
     ///
@@ -664,11 +694,24 @@ pub(crate) fn update_key(&mut self, key: &Key, lsn: Lsn, completes: bool) -> OnD
 
         let is_sparse_key = key.is_sparse();
 
+        if state.situation == ValueReconstructSituation::Continue
+            && state
+                .cached_img
+                .as_ref()
+                .is_some_and(|(cached_lsn, _)| lsn <= *cached_lsn)
+        {
+            // The cached image already reflects this value and all older ones.
+            state.situation = ValueReconstructSituation::Complete;
+            self.keys_done.add_key(*key);
+            return OnDiskValueIo::Unnecessary;
+        }
+
         let required_io = match state.situation {
             ValueReconstructSituation::Complete => {
-                if is_sparse_key {
+                if is_sparse_key || state.cached_img.is_some() {
                     // Sparse keyspace might be visited multiple times because
-                    // we don't track unmapped keyspaces.
+                    // we don't track unmapped keyspaces. Keys completed by a cached
+                    // image may see older values from the same layer.
                     return OnDiskValueIo::Unnecessary;
                 } else {
                     unreachable!()
@@ -688,6 +731,8 @@ pub(crate) fn update_key(&mut self, key: &Key, lsn: Lsn, completes: bool) -> OnD
 
         if completes && state.situation == ValueReconstructSituation::Continue {
             state.situation = ValueReconstructSituation::Complete;
+            // The key has a newer base than the cached image.
+            state.cached_img = None;
             if !is_sparse_key {
                 self.keys_done.add_key(*key);
             }
@@ -1249,4 +1294,44 @@ async fn test_io_concurrency_noise() {
 
         io_fut_exiting.await.unwrap();
     }
+
+    #[tokio::test]
+    async fn cached_image_completes_key_at_or_below_its_lsn() {
+        use utils::bin_ser::BeSer;
+
+        let key = Key::from_hex("000000067F000032BE0000400000000020B6").unwrap();
+        let cached_img = Bytes::from_static(b"cached image");
+        let record = NeonWalRecord::ClearVisibilityMapFlags {
+            new_heap_blkno: Some(7),
+            old_heap_blkno: None,
+            flags: 1,
+        };
+
+        let mut reconstruct_state = ValuesReconstructState::new(IoConcurrency::sequential());
+        reconstruct_state.set_cached_image(key, Lsn(0x20), cached_img.clone());
+
+        // Values above the cached LSN are read as usual.
+        let io = reconstruct_state.update_key(&key, Lsn(0x30), false);
+        assert!(matches!(io, OnDiskValueIo::Required { .. }));
+        io.complete(Ok(OnDiskValue::WalRecordOrImage(Bytes::from(
+            Value::WalRecord(record.clone()).ser().unwrap(),
+        ))));
+        let (done, _) = reconstruct_state.consume_done_keys();
+        assert!(done.is_empty());
+
+        // The first value at or below the cached LSN completes the key without IO,
+        // as do any older values from the same layer.
+        let io = reconstruct_state.update_key(&key, Lsn(0x18), false);
+        assert!(matches!(io, OnDiskValueIo::Unnecessary));
+        let io = reconstruct_state.update_key(&key, Lsn(0x10), true);
+        assert!(matches!(io, OnDiskValueIo::Unnecessary));
+        let (done, _) = reconstruct_state.consume_done_keys();
+        assert_eq!(done, KeySpace::single(key..key.next()));
+
+        let state = reconstruct_state.keys.remove(&key).unwrap();
+        assert_eq!(state.situation, ValueReconstructSituation::Complete);
+        let converted = state.collect_pending_ios().await.unwrap();
+        assert_eq!(converted.img, Some((Lsn(0x20), cached_img)));
+        assert_eq!(converted.records, vec![(Lsn(0x30), record)]);
+    }
 }
@@ -4,6 +4,7 @@
 
 use bytes::Bytes;
 use pageserver_api::key::{KEY_SIZE, Key};
+use pageserver_api::models::ImageCompressionAlgorithm;
 use pageserver_api::value::Value;
 use utils::id::TimelineId;
 use utils::lsn::Lsn;
@@ -298,6 +299,7 @@ pub struct SplitDeltaLayerWriter {
     timeline_id: TimelineId,
     tenant_shard_id: TenantShardId,
     lsn_range: Range<Lsn>,
+    compression: ImageCompressionAlgorithm,
     last_key_written: Key,
     batches: BatchLayerWriter,
 }
@@ -309,6 +311,7 @@ pub async fn new(
         tenant_shard_id: TenantShardId,
         lsn_range: Range<Lsn>,
         target_layer_size: u64,
+        compression: ImageCompressionAlgorithm,
     ) -> anyhow::Result<Self> {
         Ok(Self {
             target_layer_size,
@@ -317,6 +320,7 @@ pub async fn new(
             timeline_id,
             tenant_shard_id,
             lsn_range,
+            compression,
             last_key_written: Key::MIN,
             batches: BatchLayerWriter::new(conf).await?,
         })
@@ -344,6 +348,7 @@ pub async fn put_value(
                     self.tenant_shard_id,
                     key,
                     self.lsn_range.clone(),
+                    self.compression,
                     ctx,
                 )
                 .await?,
@@ -362,6 +367,7 @@ pub async fn put_value(
                     self.tenant_shard_id,
                     key,
                     self.lsn_range.clone(),
+                    self.compression,
                     ctx,
                 )
                 .await?;
@@ -480,6 +486,7 @@ async fn write_one_image() {
             tenant.tenant_shard_id,
             Lsn(0x18)..Lsn(0x20),
             4 * 1024 * 1024,
+            ImageCompressionAlgorithm::Disabled,
         )
         .await
         .unwrap();
@@ -556,6 +563,7 @@ async fn write_split_helper(harness_name: &'static str, discard: bool) {
             tenant.tenant_shard_id,
             Lsn(0x18)..Lsn(0x20),
             4 * 1024 * 1024,
+            ImageCompressionAlgorithm::Disabled,
         )
         .await
         .unwrap();
@@ -654,6 +662,7 @@ async fn write_large_img() {
             tenant.tenant_shard_id,
             Lsn(0x18)..Lsn(0x20),
             4 * 1024,
+            ImageCompressionAlgorithm::Disabled,
         )
         .await
         .unwrap();
@@ -730,6 +739,7 @@ async fn write_split_single_key() {
             tenant.tenant_shard_id,
             Lsn(0x10)..Lsn(N as u64 * 16 + 0x10),
             4 * 1024 * 1024,
+            ImageCompressionAlgorithm::Disabled,
         )
         .await
         .unwrap();
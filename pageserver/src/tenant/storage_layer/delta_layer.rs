@@ -36,6 +36,7 @@
 use std::sync::Arc;
 
 use anyhow::{Context, Result, bail, ensure};
+use bytes::Bytes;
 use camino::{Utf8Path, Utf8PathBuf};
 use futures::StreamExt;
 use itertools::Itertools;
@@ -62,7 +63,7 @@
 use crate::config::PageServerConf;
 use crate::context::{PageContentKind, RequestContext, RequestContextBuilder};
 use crate::page_cache::{self, FileId, PAGE_SZ};
-use crate::tenant::blob_io::BlobWriter;
+use crate::tenant::blob_io::{BlobChecksumMismatch, BlobWriter, ZstdDictionary};
 use crate::tenant::block_io::{BlockBuf, BlockCursor, BlockLease, BlockReader, FileBlockReader};
 use crate::tenant::disk_btree::{
     DiskBtreeBuilder, DiskBtreeIterator, DiskBtreeReader, VisitDirection,
@@ -98,6 +99,14 @@ pub struct Summary {
     pub index_start_blk: u32,
     /// Block within the 'index', where the B-tree root page is stored
     pub index_root_blk: u32,
+    /// Whether every value is followed by a checksum, see [`crate::tenant::blob_io`].
+    /// Files written before checksums were introduced have a zero here.
+    pub blob_checksums: bool,
+    /// Length of the zstd dictionary stored at `zstd_dict_start_blk`, or 0 if there is none.
+    /// Files written before dictionaries were introduced have zeroes here.
+    pub zstd_dict_len: u32,
+    /// Block number where the zstd dictionary begins, right after the 'index' part.
+    pub zstd_dict_start_blk: u32,
 }
 
 impl From<&DeltaLayer> for Summary {
@@ -129,6 +138,9 @@ pub(super) fn expected(
 
             index_start_blk: 0,
             index_root_blk: 0,
+            blob_checksums: false,
+            zstd_dict_len: 0,
+            zstd_dict_start_blk: 0,
         }
     }
 }
@@ -227,6 +239,12 @@ pub struct DeltaLayerInner {
     file: Arc<VirtualFile>,
     file_id: FileId,
 
+    /// Values are followed by a checksum, which is verified on read.
+    blob_checksums: bool,
+
+    /// Loaded when the layer is, if it was written with a dictionary.
+    zstd_dict: Option<Arc<ZstdDictionary>>,
+
     layer_key_range: Range<Key>,
     layer_lsn_range: Range<Lsn>,
 
@@ -391,9 +409,27 @@ struct DeltaLayerWriterInner {
     tree: DiskBtreeBuilder<BlockBuf, DELTA_KEY_SIZE>,
 
     blob_writer: BlobWriter<true>,
+    blob_checksums: bool,
+
+    /// Compression applied to the values written into this layer.
+    compression: ImageCompressionAlgorithm,
+
+    // Total uncompressed bytes passed into put_value_bytes
+    uncompressed_bytes: u64,
+
+    // Like `uncompressed_bytes`, but only of values
+    // where we have chosen their compressed form
+    uncompressed_bytes_chosen: u64,
 
     // Number of key-lsns in the layer.
     num_keys: usize,
+
+    // Values buffered as training samples for the zstd dictionary, written out
+    // once the dictionary has been trained. Only used with `ZstdDict` compression.
+    dict_samples: Vec<(Key, Lsn, Bytes, bool)>,
+    dict_samples_bytes: usize,
+    dict_training_done: bool,
+    zstd_dict: Option<ZstdDictionary>,
 }
 
 impl DeltaLayerWriterInner {
@@ -406,6 +442,7 @@ async fn new(
         tenant_shard_id: TenantShardId,
         key_start: Key,
         lsn_range: Range<Lsn>,
+        compression: ImageCompressionAlgorithm,
         ctx: &RequestContext,
     ) -> anyhow::Result<Self> {
         // Create the file initially with a temporary filename. We don't know
@@ -420,7 +457,11 @@ async fn new(
         let mut file = VirtualFile::create(&path, ctx).await?;
         // make room for the header block
         file.seek(SeekFrom::Start(PAGE_SZ as u64)).await?;
-        let blob_writer = BlobWriter::new(file, PAGE_SZ as u64);
+        let mut blob_writer = BlobWriter::new(file, PAGE_SZ as u64);
+        let blob_checksums = conf.layer_blob_checksums;
+        if blob_checksums {
+            blob_writer.enable_checksums();
+        }
 
         // Initialize the b-tree index builder
         let block_buf = BlockBuf::new();
@@ -434,7 +475,15 @@ async fn new(
             lsn_range,
             tree: tree_builder,
             blob_writer,
+            blob_checksums,
+            compression,
+            uncompressed_bytes: 0,
+            uncompressed_bytes_chosen: 0,
             num_keys: 0,
+            dict_samples: Vec::new(),
+            dict_samples_bytes: 0,
+            dict_training_done: !matches!(compression, ImageCompressionAlgorithm::ZstdDict { .. }),
+            zstd_dict: None,
         })
     }
 
@@ -479,14 +528,47 @@ async fn put_value_bytes<Buf>(
             self.lsn_range.start,
             lsn
         );
-        // We don't want to use compression in delta layer creation
-        let compression = ImageCompressionAlgorithm::Disabled;
+
+        self.num_keys += 1;
+
+        if !self.dict_training_done {
+            self.dict_samples_bytes += val.len();
+            self.dict_samples
+                .push((key, lsn, Bytes::copy_from_slice(&val), will_init));
+            if self.dict_samples_bytes >= ZstdDictionary::SAMPLE_BYTES {
+                let res = self.train_zstd_dict(ctx).await;
+                return (val, res);
+            }
+            return (val, Ok(()));
+        }
+
+        self.write_value(key, lsn, val, will_init, ctx).await
+    }
+
+    async fn write_value<Buf>(
+        &mut self,
+        key: Key,
+        lsn: Lsn,
+        val: FullSlice<Buf>,
+        will_init: bool,
+        ctx: &RequestContext,
+    ) -> (FullSlice<Buf>, anyhow::Result<()>)
+    where
+        Buf: IoBuf + Send,
+    {
+        let uncompressed_len = val.len() as u64;
         let (val, res) = self
             .blob_writer
-            .write_blob_maybe_compressed(val, ctx, compression)
+            .write_blob_maybe_compressed(val, ctx, self.compression)
             .await;
         let off = match res {
-            Ok((off, _)) => off,
+            Ok((off, compression_info)) => {
+                self.uncompressed_bytes += uncompressed_len;
+                if compression_info.written_compressed {
+                    self.uncompressed_bytes_chosen += uncompressed_len;
+                }
+                off
+            }
             Err(e) => return (val, Err(anyhow::anyhow!(e))),
         };
 
@@ -495,13 +577,40 @@ async fn put_value_bytes<Buf>(
         let delta_key = DeltaKey::from_key_lsn(&key, lsn);
         let res = self.tree.append(&delta_key.0, blob_ref.0);
 
-        self.num_keys += 1;
-
         (val, res.map_err(|e| anyhow::anyhow!(e)))
     }
 
+    ///
+    /// Train the zstd dictionary on the buffered values, then write them out.
+    ///
+    /// If training fails, e.g. because there were too few values, the layer is
+    /// written without a dictionary, i.e. with plain zstd.
+    ///
+    async fn train_zstd_dict(&mut self, ctx: &RequestContext) -> anyhow::Result<()> {
+        self.dict_training_done = true;
+        if let ImageCompressionAlgorithm::ZstdDict { level } = self.compression {
+            let samples = self
+                .dict_samples
+                .iter()
+                .map(|(_key, _lsn, val, _will_init)| val)
+                .collect::<Vec<_>>();
+            if let Some(dict) = ZstdDictionary::train(&samples, ZstdDictionary::MAX_SIZE) {
+                self.blob_writer.set_zstd_dictionary(&dict, level)?;
+                self.zstd_dict = Some(dict);
+            }
+        }
+        for (key, lsn, val, will_init) in std::mem::take(&mut self.dict_samples) {
+            let (_val, res) = self
+                .write_value(key, lsn, val.slice_len(), will_init, ctx)
+                .await;
+            res?;
+        }
+        self.dict_samples_bytes = 0;
+        Ok(())
+    }
+
     fn size(&self) -> u64 {
-        self.blob_writer.size() + self.tree.borrow_writer().size()
+        self.blob_writer.size() + self.tree.borrow_writer().size() + self.dict_samples_bytes as u64
     }
 
     ///
@@ -524,22 +633,41 @@ async fn finish(
     }
 
     async fn finish0(
-        self,
+        mut self,
         key_end: Key,
         ctx: &RequestContext,
     ) -> anyhow::Result<(PersistentLayerDesc, Utf8PathBuf)> {
+        if !self.dict_training_done {
+            self.train_zstd_dict(ctx).await?;
+        }
+
         let index_start_blk = self.blob_writer.size().div_ceil(PAGE_SZ as u64) as u32;
 
+        if self.compression != ImageCompressionAlgorithm::Disabled {
+            let compressed_size = self.blob_writer.size() - PAGE_SZ as u64; // Subtract PAGE_SZ for header
+            crate::metrics::COMPRESSION_DELTA_INPUT_BYTES.inc_by(self.uncompressed_bytes);
+            crate::metrics::COMPRESSION_DELTA_INPUT_BYTES_CHOSEN
+                .inc_by(self.uncompressed_bytes_chosen);
+            crate::metrics::COMPRESSION_DELTA_OUTPUT_BYTES.inc_by(compressed_size);
+        }
+
         let mut file = self.blob_writer.into_inner(ctx).await?;
 
         // Write out the index
         let (index_root_blk, block_buf) = self.tree.finish()?;
         file.seek(SeekFrom::Start(index_start_blk as u64 * PAGE_SZ as u64))
             .await?;
+        let zstd_dict_start_blk = index_start_blk + block_buf.blocks.len() as u32;
         for buf in block_buf.blocks {
             let (_buf, res) = file.write_all(buf.slice_len(), ctx).await;
             res?;
         }
+
+        // Write out the dictionary, if any, right after the index
+        if let Some(dict) = &self.zstd_dict {
+            let (_buf, res) = file.write_all(dict.to_blocks().slice_len(), ctx).await;
+            res?;
+        }
         assert!(self.lsn_range.start < self.lsn_range.end);
         // Fill in the summary on blk 0
         let summary = Summary {
@@ -551,6 +679,17 @@ async fn finish0(
             lsn_range: self.lsn_range.clone(),
             index_start_blk,
             index_root_blk,
+            blob_checksums: self.blob_checksums,
+            zstd_dict_len: self
+                .zstd_dict
+                .as_ref()
+                .map(|dict| dict.as_bytes().len() as u32)
+                .unwrap_or(0),
+            zstd_dict_start_blk: if self.zstd_dict.is_some() {
+                zstd_dict_start_blk
+            } else {
+                0
+            },
         };
 
         let mut buf = Vec::with_capacity(PAGE_SZ);
@@ -628,12 +767,18 @@ impl DeltaLayerWriter {
     ///
     /// Start building a new delta layer.
     ///
+    /// Values are written compressed with `compression` if that makes them smaller.
+    /// Readers detect compression from the blob headers, so any algorithm can be used.
+    /// With [`ImageCompressionAlgorithm::ZstdDict`], the dictionary is trained on the
+    /// first values of the layer, which are buffered until then.
+    ///
     pub async fn new(
         conf: &'static PageServerConf,
         timeline_id: TimelineId,
         tenant_shard_id: TenantShardId,
         key_start: Key,
         lsn_range: Range<Lsn>,
+        compression: ImageCompressionAlgorithm,
         ctx: &RequestContext,
     ) -> anyhow::Result<Self> {
         Ok(Self {
@@ -644,6 +789,7 @@ pub async fn new(
                     tenant_shard_id,
                     key_start,
                     lsn_range,
+                    compression,
                     ctx,
                 )
                 .await?,
@@ -713,7 +859,10 @@ pub(crate) fn num_keys(&self) -> usize {
 
     pub(crate) fn estimated_size(&self) -> u64 {
         let inner = self.inner.as_ref().unwrap();
-        inner.blob_writer.size() + inner.tree.borrow_writer().size() + PAGE_SZ as u64
+        inner.blob_writer.size()
+            + inner.tree.borrow_writer().size()
+            + inner.dict_samples_bytes as u64
+            + PAGE_SZ as u64
     }
 }
 
@@ -816,6 +965,9 @@ pub(super) async fn load(
             // production code path
             expected_summary.index_start_blk = actual_summary.index_start_blk;
             expected_summary.index_root_blk = actual_summary.index_root_blk;
+            expected_summary.blob_checksums = actual_summary.blob_checksums;
+            expected_summary.zstd_dict_len = actual_summary.zstd_dict_len;
+            expected_summary.zstd_dict_start_blk = actual_summary.zstd_dict_start_blk;
             // mask out the timeline_id, but still require the layers to be from the same tenant
             expected_summary.timeline_id = actual_summary.timeline_id;
 
@@ -828,11 +980,32 @@ pub(super) async fn load(
             }
         }
 
+        let zstd_dict = if actual_summary.zstd_dict_len > 0 {
+            ensure!(
+                actual_summary.zstd_dict_start_blk > actual_summary.index_start_blk,
+                "zstd dictionary at block {} does not follow the index",
+                actual_summary.zstd_dict_start_blk
+            );
+            let dict = ZstdDictionary::read(
+                &block_reader.block_cursor(),
+                actual_summary.zstd_dict_start_blk,
+                actual_summary.zstd_dict_len,
+                ctx,
+            )
+            .await
+            .context("read zstd dictionary")?;
+            Some(Arc::new(dict))
+        } else {
+            None
+        };
+
         Ok(DeltaLayerInner {
             file,
             file_id,
             index_start_blk: actual_summary.index_start_blk,
             index_root_blk: actual_summary.index_root_blk,
+            blob_checksums: actual_summary.blob_checksums,
+            zstd_dict,
             max_vectored_read_bytes,
             layer_key_range: actual_summary.key_range,
             layer_lsn_range: actual_summary.lsn_range,
@@ -1019,16 +1192,21 @@ async fn do_reads_and_update_state(
 
             let read_extend_residency = this.clone();
             let read_from = self.file.clone();
+            let blob_checksums = self.blob_checksums;
+            let zstd_dict = self.zstd_dict.clone();
             let read_ctx = ctx.attached_child();
             reconstruct_state
                 .spawn_io(async move {
-                    let vectored_blob_reader = VectoredBlobReader::new(&read_from);
+                    let vectored_blob_reader = VectoredBlobReader::new(&read_from)
+                        .with_checksums(blob_checksums)
+                        .with_zstd_dict(zstd_dict);
                     let buf = IoBufferMut::with_capacity(buf_size);
 
                     let res = vectored_blob_reader.read_blobs(&read, buf, &read_ctx).await;
                     match res {
                         Ok(blobs_buf) => {
                             let view = BufView::new_slice(&blobs_buf.buf);
+                            let mut verified = blob_checksums;
                             for meta in blobs_buf.blobs.iter().rev() {
                                 let io = ios.remove(&(meta.meta.key, meta.meta.lsn)).unwrap();
 
@@ -1036,6 +1214,10 @@ async fn do_reads_and_update_state(
                                 let blob_read = match blob_read {
                                     Ok(buf) => buf,
                                     Err(e) => {
+                                        if BlobChecksumMismatch::is_cause_of(&e) {
+                                            read_extend_residency.as_ref().evict_corrupted();
+                                        }
+                                        verified = false;
                                         io.complete(Err(e));
                                         continue;
                                     }
@@ -1047,6 +1229,10 @@ async fn do_reads_and_update_state(
                             }
 
                             assert!(ios.is_empty());
+
+                            if verified {
+                                read_extend_residency.as_ref().read_verified();
+                            }
                         }
                         Err(err) => {
                             for (_, sender) in ios {
@@ -1253,7 +1439,9 @@ fn is_last(&self) -> bool {
             for builder in builders {
                 let read = builder.build();
 
-                let reader = VectoredBlobReader::new(&self.file);
+                let reader = VectoredBlobReader::new(&self.file)
+                    .with_checksums(self.blob_checksums)
+                    .with_zstd_dict(self.zstd_dict.clone());
 
                 let mut buf = buffer.take().unwrap();
 
@@ -1453,6 +1641,54 @@ pub(crate) async fn load_keys(&self, ctx: &RequestContext) -> anyhow::Result<Vec
             .await
             .map(|entries| entries.into_iter().map(|entry| entry.key).collect())
     }
+
+    /// Check the whole layer file for corruption: the structure of the index, that every
+    /// value of the index lies within the values section, and that every value can be read,
+    /// decompressed and deserialized, verifying the blob checksums if the layer has them.
+    pub(crate) async fn scrub(&self, ctx: &RequestContext) -> anyhow::Result<()> {
+        let block_reader = FileBlockReader::new(&self.file, self.file_id);
+        let tree_reader = DiskBtreeReader::<_, DELTA_KEY_SIZE>::new(
+            self.index_start_blk,
+            self.index_root_blk,
+            block_reader,
+        );
+
+        let values_range = PAGE_SZ as u64..self.index_start_offset();
+        let mut num_entries = 0;
+        let mut out_of_range = None;
+        tree_reader
+            .validate(
+                |key, value| {
+                    num_entries += 1;
+                    let pos = BlobRef(value).pos();
+                    if out_of_range.is_none() && !values_range.contains(&pos) {
+                        out_of_range = Some((DeltaKey::from_slice(key), pos));
+                    }
+                },
+                ctx,
+            )
+            .await
+            .context("validate index")?;
+        if let Some((key, pos)) = out_of_range {
+            bail!(
+                "value of {} at {} is at offset {pos} outside of {values_range:?}",
+                key.key(),
+                key.lsn()
+            );
+        }
+
+        let mut num_values = 0;
+        let mut iter = self.iter(ctx);
+        while iter.next().await.context("read values")?.is_some() {
+            num_values += 1;
+        }
+        ensure!(
+            num_values == num_entries,
+            "read {num_values} values, but the index has {num_entries} entries"
+        );
+
+        Ok(())
+    }
 }
 
 /// A set of data associated with a delta layer key and its value
@@ -1480,9 +1716,12 @@ pub async fn load(&self, ctx: &RequestContext) -> Result<Value> {
     }
 
     async fn load_raw(&self, ctx: &RequestContext) -> Result<Vec<u8>> {
-        let reader = BlockCursor::new(crate::tenant::block_io::BlockReaderRef::Adapter(Adapter(
-            self.layer,
-        )));
+        let reader = BlockCursor::new_with_compression(
+            crate::tenant::block_io::BlockReaderRef::Adapter(Adapter(self.layer)),
+            true,
+        )
+        .with_checksums(self.layer.blob_checksums)
+        .with_zstd_dict(self.layer.zstd_dict.as_deref());
         let buf = reader.read_blob(self.blob_ref.pos(), ctx).await?;
         Ok(buf)
     }
@@ -1560,7 +1799,9 @@ async fn next_batch(&mut self) -> anyhow::Result<()> {
                 }
             }
         };
-        let vectored_blob_reader = VectoredBlobReader::new(&self.delta_layer.file);
+        let vectored_blob_reader = VectoredBlobReader::new(&self.delta_layer.file)
+            .with_checksums(self.delta_layer.blob_checksums)
+            .with_zstd_dict(self.delta_layer.zstd_dict.clone());
         let mut next_batch = std::collections::VecDeque::new();
         let buf_size = plan.size();
         let buf = IoBufferMut::with_capacity(buf_size);
@@ -1607,6 +1848,7 @@ pub(crate) mod test {
     use crate::DEFAULT_PG_VERSION;
     use crate::context::DownloadBehavior;
     use crate::task_mgr::TaskKind;
+    use crate::tenant::blob_io::tests::random_array;
     use crate::tenant::disk_btree::tests::TestDisk;
     use crate::tenant::harness::{TIMELINE_ID, TenantHarness};
     use crate::tenant::storage_layer::{Layer, ResidentLayer};
@@ -1885,6 +2127,7 @@ async fn test_delta_layer_vectored_read_end_to_end() -> anyhow::Result<()> {
             harness.tenant_shard_id,
             entries_meta.key_range.start,
             entries_meta.lsn_range.clone(),
+            ImageCompressionAlgorithm::Disabled,
             &ctx,
         )
         .await?;
@@ -1931,7 +2174,9 @@ async fn test_delta_layer_vectored_read_end_to_end() -> anyhow::Result<()> {
             )
             .await?;
 
-            let vectored_blob_reader = VectoredBlobReader::new(&inner.file);
+            let vectored_blob_reader = VectoredBlobReader::new(&inner.file)
+                .with_checksums(inner.blob_checksums)
+                .with_zstd_dict(inner.zstd_dict.clone());
             let buf_size = DeltaLayerInner::get_min_read_buffer_size(
                 &vectored_reads,
                 constants::MAX_VECTORED_READ_BYTES,
@@ -2079,6 +2324,7 @@ async fn copy_delta_prefix_smoke() {
                 tenant.tenant_shard_id,
                 Key::MIN,
                 Lsn(0x11)..truncate_at,
+                ImageCompressionAlgorithm::Disabled,
                 ctx,
             )
             .await
@@ -2195,10 +2441,24 @@ pub(crate) fn sort_delta_value(
         (k1, l1, order_1).cmp(&(k2, l2, order_2))
     }
 
+    /// How [`produce_delta_layer`] writes the layer.
+    pub(crate) struct DeltaLayerOptions {
+        pub(crate) compression: ImageCompressionAlgorithm,
+    }
+
+    impl Default for DeltaLayerOptions {
+        fn default() -> Self {
+            DeltaLayerOptions {
+                compression: ImageCompressionAlgorithm::Disabled,
+            }
+        }
+    }
+
     pub(crate) async fn produce_delta_layer(
         tenant: &Tenant,
         tline: &Arc<Timeline>,
         mut deltas: Vec<(Key, Lsn, Value)>,
+        opts: DeltaLayerOptions,
         ctx: &RequestContext,
     ) -> anyhow::Result<ResidentLayer> {
         deltas.sort_by(sort_delta);
@@ -2213,6 +2473,7 @@ pub(crate) async fn produce_delta_layer(
             tenant.tenant_shard_id,
             *key_start,
             (*lsn_min)..lsn_end,
+            opts.compression,
             ctx,
         )
         .await?;
@@ -2273,9 +2534,15 @@ fn get_key(id: u32) -> Key {
                 )
             })
             .collect_vec();
-        let resident_layer = produce_delta_layer(&tenant, &tline, test_deltas.clone(), &ctx)
-            .await
-            .unwrap();
+        let resident_layer = produce_delta_layer(
+            &tenant,
+            &tline,
+            test_deltas.clone(),
+            DeltaLayerOptions::default(),
+            &ctx,
+        )
+        .await
+        .unwrap();
         let delta_layer = resident_layer.get_as_delta(&ctx).await.unwrap();
         for max_read_size in [1, 1024] {
             for batch_size in [1, 2, 4, 8, 3, 7, 13] {
@@ -2305,4 +2572,177 @@ fn get_key(id: u32) -> Key {
             }
         }
     }
+
+    #[tokio::test]
+    async fn delta_layer_compressed_values() {
+        use pageserver_api::record::NeonWalRecord;
+
+        let harness = TenantHarness::create("delta_layer_compressed_values")
+            .await
+            .unwrap();
+        let (tenant, ctx) = harness.load().await;
+
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await
+            .unwrap();
+
+        fn get_key(id: u32) -> Key {
+            let mut key = Key::from_hex("000000000033333333444444445500000000").unwrap();
+            key.field6 = id;
+            key
+        }
+
+        // A mix of compressible page images, compressible WAL records and values
+        // too short to ever be considered for compression.
+        const N: usize = 200;
+        let test_deltas = (0..N)
+            .map(|idx| {
+                let key = get_key(idx as u32 / 4);
+                let lsn = Lsn(0x10 * ((idx as u64) % 4 + 1));
+                let value = match idx % 3 {
+                    0 => Value::Image(Bytes::from(vec![(idx % 256) as u8; PAGE_SZ])),
+                    1 => Value::WalRecord(NeonWalRecord::Postgres {
+                        will_init: false,
+                        rec: Bytes::from(vec![b'a'; 1024]),
+                    }),
+                    _ => Value::Image(Bytes::from(format!("img{idx:05}"))),
+                };
+                (key, lsn, value)
+            })
+            .collect_vec();
+
+        let uncompressed = produce_delta_layer(
+            &tenant,
+            &tline,
+            test_deltas.clone(),
+            DeltaLayerOptions::default(),
+            &ctx,
+        )
+        .await
+        .unwrap();
+        let compressed = produce_delta_layer(
+            &tenant,
+            &tline,
+            test_deltas.clone(),
+            DeltaLayerOptions {
+                compression: ImageCompressionAlgorithm::Zstd { level: Some(1) },
+            },
+            &ctx,
+        )
+        .await
+        .unwrap();
+        assert!(
+            compressed.layer_desc().file_size < uncompressed.layer_desc().file_size,
+            "compressed={} uncompressed={}",
+            compressed.layer_desc().file_size,
+            uncompressed.layer_desc().file_size
+        );
+
+        let mut sorted = test_deltas;
+        sorted.sort_by(sort_delta);
+
+        // Vectored read path
+        let delta_layer = compressed.get_as_delta(&ctx).await.unwrap();
+        let mut iter = delta_layer.iter(&ctx);
+        assert_delta_iter_equal(&mut iter, &sorted).await;
+
+        // Block cursor read path
+        let entries = delta_layer.index_entries(&ctx).await.unwrap();
+        assert_eq!(entries.len(), sorted.len());
+        for (entry, (key, lsn, value)) in entries.iter().zip(sorted.iter()) {
+            assert_eq!(entry.key, *key);
+            assert_eq!(entry.lsn, *lsn);
+            assert_eq!(&entry.val.load(&ctx).await.unwrap(), value);
+        }
+    }
+
+    #[tokio::test]
+    async fn delta_layer_zstd_dict() {
+        let harness = TenantHarness::create("delta_layer_zstd_dict")
+            .await
+            .unwrap();
+        let (tenant, ctx) = harness.load().await;
+
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await
+            .unwrap();
+
+        fn get_key(id: u32) -> Key {
+            let mut key = Key::from_hex("000000000033333333444444445500000000").unwrap();
+            key.field6 = id;
+            key
+        }
+        // Page images that share most of their contents, but that don't
+        // compress well on their own.
+        let shared = random_array(4096);
+        let test_imgs = (0..256u32)
+            .map(|idx| {
+                let mut img = shared.clone();
+                img.extend_from_slice(&random_array(64));
+                img.extend_from_slice(format!("img{idx:05}").as_bytes());
+                Bytes::from(img)
+            })
+            .collect_vec();
+
+        let mut sizes = Vec::new();
+        for (compression, lsn_base) in [
+            (ImageCompressionAlgorithm::Zstd { level: Some(1) }, 0x10),
+            (
+                ImageCompressionAlgorithm::ZstdDict { level: Some(1) },
+                0x100,
+            ),
+        ] {
+            let test_deltas = test_imgs
+                .iter()
+                .enumerate()
+                .map(|(idx, img)| {
+                    (
+                        get_key(idx as u32 / 4),
+                        Lsn(lsn_base + 0x10 * (idx as u64 % 4)),
+                        Value::Image(img.clone()),
+                    )
+                })
+                .collect_vec();
+            let mut sorted = test_deltas.clone();
+            sorted.sort_by(sort_delta);
+
+            let resident_layer = produce_delta_layer(
+                &tenant,
+                &tline,
+                test_deltas,
+                DeltaLayerOptions { compression },
+                &ctx,
+            )
+            .await
+            .unwrap();
+            sizes.push(resident_layer.layer_desc().file_size);
+
+            let delta_layer = resident_layer.get_as_delta(&ctx).await.unwrap();
+            assert_eq!(
+                delta_layer.zstd_dict.is_some(),
+                matches!(compression, ImageCompressionAlgorithm::ZstdDict { .. })
+            );
+
+            // Vectored read path
+            let mut iter = delta_layer.iter(&ctx);
+            assert_delta_iter_equal(&mut iter, &sorted).await;
+
+            // Block cursor read path
+            let entries = delta_layer.index_entries(&ctx).await.unwrap();
+            assert_eq!(entries.len(), sorted.len());
+            for (entry, (_key, _lsn, value)) in entries.iter().zip(sorted.iter()) {
+                assert_eq!(&entry.val.load(&ctx).await.unwrap(), value);
+            }
+        }
+
+        let [zstd_size, zstd_dict_size] = sizes[..] else {
+            unreachable!()
+        };
+        assert!(
+            zstd_dict_size < zstd_size,
+            "zstd-dict: {zstd_dict_size} bytes, zstd: {zstd_size} bytes"
+        );
+    }
 }
@@ -153,9 +153,15 @@ fn get_key(id: u32) -> Key {
                 )
             })
             .collect_vec();
-        let resident_layer_1 = produce_delta_layer(&tenant, &tline, test_deltas1.clone(), &ctx)
-            .await
-            .unwrap();
+        let resident_layer_1 = produce_delta_layer(
+            &tenant,
+            &tline,
+            test_deltas1.clone(),
+            Default::default(),
+            &ctx,
+        )
+        .await
+        .unwrap();
 
         let merge_iter = MergeIterator::create(
             &[resident_layer_1.get_as_delta(&ctx).await.unwrap()],
@@ -25,6 +25,9 @@
 //! layer, and offsets to the other parts. The "index" is a B-tree,
 //! mapping from Key to an offset in the "values" part.  The
 //! actual page images are stored in the "values" part.
+//!
+//! With [`ImageCompressionAlgorithm::ZstdDict`], a zstd dictionary trained on
+//! the layer's images follows the index, in blocks of its own.
 use std::collections::{HashMap, VecDeque};
 use std::fs::File;
 use std::io::SeekFrom;
@@ -41,6 +44,7 @@
 use pageserver_api::config::MaxVectoredReadBytes;
 use pageserver_api::key::{DBDIR_KEY, KEY_SIZE, Key};
 use pageserver_api::keyspace::KeySpace;
+use pageserver_api::models::ImageCompressionAlgorithm;
 use pageserver_api::shard::{ShardIdentity, TenantShardId};
 use pageserver_api::value::Value;
 use rand::Rng;
@@ -55,14 +59,14 @@
 
 use super::layer_name::ImageLayerName;
 use super::{
-    AsLayerDesc, LayerName, OnDiskValue, OnDiskValueIo, PersistentLayerDesc, ResidentLayer,
+    AsLayerDesc, Layer, LayerName, OnDiskValue, OnDiskValueIo, PersistentLayerDesc, ResidentLayer,
     ValuesReconstructState,
 };
 use crate::config::PageServerConf;
 use crate::context::{PageContentKind, RequestContext, RequestContextBuilder};
 use crate::page_cache::{self, FileId, PAGE_SZ};
-use crate::tenant::blob_io::BlobWriter;
-use crate::tenant::block_io::{BlockBuf, FileBlockReader};
+use crate::tenant::blob_io::{BlobChecksumMismatch, BlobWriter, ZstdDictionary};
+use crate::tenant::block_io::{BlockBuf, BlockReader, FileBlockReader};
 use crate::tenant::disk_btree::{
     DiskBtreeBuilder, DiskBtreeIterator, DiskBtreeReader, VisitDirection,
 };
@@ -96,6 +100,14 @@ pub struct Summary {
     pub index_start_blk: u32,
     /// Block within the 'index', where the B-tree root page is stored
     pub index_root_blk: u32,
+    /// Length of the zstd dictionary stored at `zstd_dict_start_blk`, or 0 if there is none.
+    /// Files written before dictionaries were introduced have zeroes here.
+    pub zstd_dict_len: u32,
+    /// Whether every image is followed by a checksum, see [`crate::tenant::blob_io`].
+    /// Files written before checksums were introduced have a zero here.
+    pub blob_checksums: bool,
+    /// Block number where the zstd dictionary begins, right after the 'index' part.
+    pub zstd_dict_start_blk: u32,
     // the 'values' part starts after the summary header, on block 1.
 }
 
@@ -127,8 +139,17 @@ pub(super) fn expected(
 
             index_start_blk: 0,
             index_root_blk: 0,
+            zstd_dict_len: 0,
+            blob_checksums: false,
+            zstd_dict_start_blk: 0,
         }
     }
+
+    /// Offset at which the index starts. The index and the zstd dictionary, if any,
+    /// extend until the end of the file.
+    pub(super) fn index_start_offset(&self) -> u64 {
+        self.index_start_blk as u64 * PAGE_SZ as u64
+    }
 }
 
 /// This is used only from `pagectl`. Within pageserver, all layers are
@@ -167,6 +188,12 @@ pub struct ImageLayerInner {
     file: Arc<VirtualFile>,
     file_id: FileId,
 
+    /// Loaded when the layer is, if it was written with a dictionary.
+    zstd_dict: Option<Arc<ZstdDictionary>>,
+
+    /// Images are followed by a checksum, which is verified on read.
+    blob_checksums: bool,
+
     max_vectored_read_bytes: Option<MaxVectoredReadBytes>,
 }
 
@@ -384,6 +411,29 @@ pub(crate) fn lsn(&self) -> Lsn {
         self.lsn
     }
 
+    /// Offset at which the index starts, see [`Summary::index_start_offset`].
+    pub(super) fn index_start_offset(&self) -> u64 {
+        self.index_start_blk as u64 * PAGE_SZ as u64
+    }
+
+    /// Read only the summary of the layer file at `path`.
+    ///
+    /// Used to find out which parts of a partially downloaded file are needed to load it.
+    pub(super) async fn read_summary(
+        path: &Utf8Path,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<Summary> {
+        let file = VirtualFile::open_v2(path, ctx)
+            .await
+            .context("open layer file")?;
+        let block_reader = FileBlockReader::new(&file, page_cache::next_file_id());
+        let summary_blk = block_reader
+            .read_blk(0, ctx)
+            .await
+            .context("read first block")?;
+        Summary::des_prefix(summary_blk.as_ref()).context("deserialize first block")
+    }
+
     pub(super) async fn load(
         path: &Utf8Path,
         lsn: Lsn,
@@ -414,6 +464,9 @@ pub(super) async fn load(
             // production code path
             expected_summary.index_start_blk = actual_summary.index_start_blk;
             expected_summary.index_root_blk = actual_summary.index_root_blk;
+            expected_summary.zstd_dict_len = actual_summary.zstd_dict_len;
+            expected_summary.blob_checksums = actual_summary.blob_checksums;
+            expected_summary.zstd_dict_start_blk = actual_summary.zstd_dict_start_blk;
             // mask out the timeline_id, but still require the layers to be from the same tenant
             expected_summary.timeline_id = actual_summary.timeline_id;
 
@@ -426,12 +479,33 @@ pub(super) async fn load(
             }
         }
 
+        let zstd_dict = if actual_summary.zstd_dict_len > 0 {
+            ensure!(
+                actual_summary.zstd_dict_start_blk > actual_summary.index_start_blk,
+                "zstd dictionary at block {} does not follow the index",
+                actual_summary.zstd_dict_start_blk
+            );
+            let dict = ZstdDictionary::read(
+                &block_reader.block_cursor(),
+                actual_summary.zstd_dict_start_blk,
+                actual_summary.zstd_dict_len,
+                ctx,
+            )
+            .await
+            .context("read zstd dictionary")?;
+            Some(Arc::new(dict))
+        } else {
+            None
+        };
+
         Ok(ImageLayerInner {
             index_start_blk: actual_summary.index_start_blk,
             index_root_blk: actual_summary.index_root_blk,
             lsn,
             file,
             file_id,
+            zstd_dict,
+            blob_checksums: actual_summary.blob_checksums,
             max_vectored_read_bytes,
             key_range: actual_summary.key_range,
         })
@@ -451,7 +525,8 @@ pub(super) async fn get_values_reconstruct_data(
             .await
             .map_err(GetVectoredError::Other)?;
 
-        self.do_reads_and_update_state(this, reads, reconstruct_state, ctx)
+        let layer = Some(this.as_ref().clone());
+        self.do_reads_and_update_state(this, layer, reads, reconstruct_state, ctx)
             .await;
 
         reconstruct_state.on_image_layer_visited(&self.key_range);
@@ -464,7 +539,7 @@ pub(super) async fn get_values_reconstruct_data(
     ///
     /// If shard_identity is provided, it will be used to filter keys down to those stored on
     /// this shard.
-    async fn plan_reads(
+    pub(super) async fn plan_reads(
         &self,
         keyspace: KeySpace,
         shard_identity: Option<&ShardIdentity>,
@@ -547,7 +622,9 @@ pub(super) async fn filter(
             )
             .await?;
 
-        let vectored_blob_reader = VectoredBlobReader::new(&self.file);
+        let vectored_blob_reader = VectoredBlobReader::new(&self.file)
+            .with_zstd_dict(self.zstd_dict.clone())
+            .with_checksums(self.blob_checksums);
         let mut key_count = 0;
         for read in plan.into_iter() {
             let buf_size = read.size();
@@ -571,9 +648,13 @@ pub(super) async fn filter(
         Ok(key_count)
     }
 
-    async fn do_reads_and_update_state(
+    /// Execute the planned reads. `this` is kept alive until all reads have completed, to keep
+    /// the layer file around. If an image fails checksum verification, `layer` is evicted so that
+    /// it gets downloaded again, and reads passing it are reported with [`Layer::read_verified`].
+    pub(super) async fn do_reads_and_update_state(
         &self,
-        this: ResidentLayer,
+        this: impl Clone + Send + 'static,
+        layer: Option<Layer>,
         reads: Vec<VectoredRead>,
         reconstruct_state: &mut ValuesReconstructState,
         ctx: &RequestContext,
@@ -625,16 +706,22 @@ async fn do_reads_and_update_state(
 
             let read_extend_residency = this.clone();
             let read_from = self.file.clone();
+            let zstd_dict = self.zstd_dict.clone();
+            let blob_checksums = self.blob_checksums;
+            let layer = layer.clone();
             let read_ctx = ctx.attached_child();
             reconstruct_state
                 .spawn_io(async move {
                     let buf = IoBufferMut::with_capacity(buf_size);
-                    let vectored_blob_reader = VectoredBlobReader::new(&read_from);
+                    let vectored_blob_reader = VectoredBlobReader::new(&read_from)
+                        .with_zstd_dict(zstd_dict)
+                        .with_checksums(blob_checksums);
                     let res = vectored_blob_reader.read_blobs(&read, buf, &read_ctx).await;
 
                     match res {
                         Ok(blobs_buf) => {
                             let view = BufView::new_slice(&blobs_buf.buf);
+                            let mut verified = blob_checksums;
                             for meta in blobs_buf.blobs.iter() {
                                 let io: OnDiskValueIo =
                                     ios.remove(&(meta.meta.key, meta.meta.lsn)).unwrap();
@@ -643,6 +730,10 @@ async fn do_reads_and_update_state(
                                 let img_buf = match img_buf {
                                     Ok(img_buf) => img_buf,
                                     Err(e) => {
+                                        if BlobChecksumMismatch::is_cause_of(&e) {
+                                            layer.iter().for_each(Layer::evict_corrupted);
+                                        }
+                                        verified = false;
                                         io.complete(Err(e));
                                         continue;
                                     }
@@ -652,6 +743,10 @@ async fn do_reads_and_update_state(
                             }
 
                             assert!(ios.is_empty());
+
+                            if verified {
+                                layer.iter().for_each(Layer::read_verified);
+                            }
                         }
                         Err(err) => {
                             for (_, io) in ios {
@@ -702,6 +797,58 @@ pub(crate) async fn load_keys(&self, ctx: &RequestContext) -> anyhow::Result<Vec
             .map(|(_, blob_meta)| blob_meta.key)
             .collect())
     }
+
+    /// Check the whole layer file for corruption: the structure of the index, that every key
+    /// of the index is within the layer's key range and its image within the images section,
+    /// and that every image can be read and decompressed, verifying the blob checksums if the
+    /// layer has them.
+    pub(crate) async fn scrub(&self, ctx: &RequestContext) -> anyhow::Result<()> {
+        let block_reader = FileBlockReader::new(&self.file, self.file_id);
+        let tree_reader = DiskBtreeReader::<_, KEY_SIZE>::new(
+            self.index_start_blk,
+            self.index_root_blk,
+            block_reader,
+        );
+
+        let images_range = PAGE_SZ as u64..self.index_start_offset();
+        let mut num_entries = 0;
+        let mut invalid = None;
+        tree_reader
+            .validate(
+                |raw_key, offset| {
+                    num_entries += 1;
+                    let key = Key::from_slice(&raw_key[..KEY_SIZE]);
+                    if invalid.is_some() {
+                        return;
+                    }
+                    if !self.key_range.contains(&key) {
+                        invalid = Some(format!("key {key} is outside of the layer's key range"));
+                    } else if !images_range.contains(&offset) {
+                        invalid = Some(format!(
+                            "image of {key} is at offset {offset} outside of {images_range:?}"
+                        ));
+                    }
+                },
+                ctx,
+            )
+            .await
+            .context("validate index")?;
+        if let Some(invalid) = invalid {
+            bail!(invalid);
+        }
+
+        let mut num_images = 0;
+        let mut iter = self.iter(ctx);
+        while iter.next().await.context("read images")?.is_some() {
+            num_images += 1;
+        }
+        ensure!(
+            num_images == num_entries,
+            "read {num_images} images, but the index has {num_entries} entries"
+        );
+
+        Ok(())
+    }
 }
 
 /// A builder object for constructing a new image layer.
@@ -737,6 +884,13 @@ struct ImageLayerWriterInner {
     // Number of keys in the layer.
     num_keys: usize,
 
+    // Images buffered as training samples for the zstd dictionary, written out
+    // once the dictionary has been trained. Only used with `ZstdDict` compression.
+    dict_samples: Vec<(Key, Bytes)>,
+    dict_samples_bytes: usize,
+    dict_training_done: bool,
+    zstd_dict: Option<ZstdDictionary>,
+
     blob_writer: BlobWriter<false>,
     tree: DiskBtreeBuilder<BlockBuf, KEY_SIZE>,
 
@@ -780,7 +934,10 @@ async fn new(
         };
         // make room for the header block
         file.seek(SeekFrom::Start(PAGE_SZ as u64)).await?;
-        let blob_writer = BlobWriter::new(file, PAGE_SZ as u64);
+        let mut blob_writer = BlobWriter::new(file, PAGE_SZ as u64);
+        if conf.layer_blob_checksums {
+            blob_writer.enable_checksums();
+        }
 
         // Initialize the b-tree index builder
         let block_buf = BlockBuf::new();
@@ -799,6 +956,13 @@ async fn new(
             uncompressed_bytes_eligible: 0,
             uncompressed_bytes_chosen: 0,
             num_keys: 0,
+            dict_samples: Vec::new(),
+            dict_samples_bytes: 0,
+            dict_training_done: !matches!(
+                conf.image_compression,
+                ImageCompressionAlgorithm::ZstdDict { .. }
+            ),
+            zstd_dict: None,
             #[cfg(feature = "testing")]
             last_written_key: Key::MIN,
         };
@@ -818,10 +982,34 @@ async fn put_image(
         ctx: &RequestContext,
     ) -> anyhow::Result<()> {
         ensure!(self.key_range.contains(&key));
+        self.num_keys += 1;
+
+        #[cfg(feature = "testing")]
+        {
+            self.last_written_key = key;
+        }
+
+        if !self.dict_training_done {
+            self.dict_samples_bytes += img.len();
+            self.dict_samples.push((key, img));
+            if self.dict_samples_bytes >= ZstdDictionary::SAMPLE_BYTES {
+                self.train_zstd_dict(ctx).await?;
+            }
+            return Ok(());
+        }
+
+        self.write_image(key, img, ctx).await
+    }
+
+    async fn write_image(
+        &mut self,
+        key: Key,
+        img: Bytes,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
         let compression = self.conf.image_compression;
         let uncompressed_len = img.len() as u64;
         self.uncompressed_bytes += uncompressed_len;
-        self.num_keys += 1;
         let (_img, res) = self
             .blob_writer
             .write_blob_maybe_compressed(img.slice_len(), ctx, compression)
@@ -841,11 +1029,32 @@ async fn put_image(
         key.write_to_byte_slice(&mut keybuf);
         self.tree.append(&keybuf, off)?;
 
-        #[cfg(feature = "testing")]
-        {
-            self.last_written_key = key;
-        }
+        Ok(())
+    }
 
+    ///
+    /// Train the zstd dictionary on the buffered images, then write them out.
+    ///
+    /// If training fails, e.g. because there were too few images, the layer is
+    /// written without a dictionary, i.e. with plain zstd.
+    ///
+    async fn train_zstd_dict(&mut self, ctx: &RequestContext) -> anyhow::Result<()> {
+        self.dict_training_done = true;
+        if let ImageCompressionAlgorithm::ZstdDict { level } = self.conf.image_compression {
+            let samples = self
+                .dict_samples
+                .iter()
+                .map(|(_key, img)| img)
+                .collect::<Vec<_>>();
+            if let Some(dict) = ZstdDictionary::train(&samples, ZstdDictionary::MAX_SIZE) {
+                self.blob_writer.set_zstd_dictionary(&dict, level)?;
+                self.zstd_dict = Some(dict);
+            }
+        }
+        for (key, img) in std::mem::take(&mut self.dict_samples) {
+            self.write_image(key, img, ctx).await?;
+        }
+        self.dict_samples_bytes = 0;
         Ok(())
     }
 
@@ -872,10 +1081,14 @@ async fn finish(
     /// Finish writing the image layer.
     ///
     async fn finish0(
-        self,
+        mut self,
         ctx: &RequestContext,
         end_key: Option<Key>,
     ) -> anyhow::Result<(PersistentLayerDesc, Utf8PathBuf)> {
+        if !self.dict_training_done {
+            self.train_zstd_dict(ctx).await?;
+        }
+
         let index_start_blk = self.blob_writer.size().div_ceil(PAGE_SZ as u64) as u32;
 
         // Calculate compression ratio
@@ -892,11 +1105,18 @@ async fn finish0(
         file.seek(SeekFrom::Start(index_start_blk as u64 * PAGE_SZ as u64))
             .await?;
         let (index_root_blk, block_buf) = self.tree.finish()?;
+        let zstd_dict_start_blk = index_start_blk + block_buf.blocks.len() as u32;
         for buf in block_buf.blocks {
             let (_buf, res) = file.write_all(buf.slice_len(), ctx).await;
             res?;
         }
 
+        // Write out the dictionary, if any, right after the index
+        if let Some(dict) = &self.zstd_dict {
+            let (_buf, res) = file.write_all(dict.to_blocks().slice_len(), ctx).await;
+            res?;
+        }
+
         let final_key_range = if let Some(end_key) = end_key {
             self.key_range.start..end_key
         } else {
@@ -913,6 +1133,17 @@ async fn finish0(
             lsn: self.lsn,
             index_start_blk,
             index_root_blk,
+            zstd_dict_len: self
+                .zstd_dict
+                .as_ref()
+                .map(|dict| dict.as_bytes().len() as u32)
+                .unwrap_or(0),
+            blob_checksums: self.conf.layer_blob_checksums,
+            zstd_dict_start_blk: if self.zstd_dict.is_some() {
+                zstd_dict_start_blk
+            } else {
+                0
+            },
         };
 
         let mut buf = Vec::with_capacity(PAGE_SZ);
@@ -1021,7 +1252,10 @@ pub async fn put_image(
     /// Estimated size of the image layer.
     pub(crate) fn estimated_size(&self) -> u64 {
         let inner = self.inner.as_ref().unwrap();
-        inner.blob_writer.size() + inner.tree.borrow_writer().size() + PAGE_SZ as u64
+        inner.blob_writer.size()
+            + inner.tree.borrow_writer().size()
+            + inner.dict_samples_bytes as u64
+            + PAGE_SZ as u64
     }
 
     pub(crate) fn num_keys(&self) -> usize {
@@ -1039,7 +1273,7 @@ pub(crate) async fn finish(
     }
 
     /// Finish writing the image layer with an end key, used in [`super::batch_split_writer::SplitImageLayerWriter`]. The end key determines the end of the image layer's covered range and is exclusive.
-    pub(super) async fn finish_with_end_key(
+    pub(crate) async fn finish_with_end_key(
         mut self,
         end_key: Key,
         ctx: &RequestContext,
@@ -1096,7 +1330,9 @@ async fn next_batch(&mut self) -> anyhow::Result<()> {
                 }
             }
         };
-        let vectored_blob_reader = VectoredBlobReader::new(&self.image_layer.file);
+        let vectored_blob_reader = VectoredBlobReader::new(&self.image_layer.file)
+            .with_zstd_dict(self.image_layer.zstd_dict.clone())
+            .with_checksums(self.image_layer.blob_checksums);
         let mut next_batch = std::collections::VecDeque::new();
         let buf_size = plan.size();
         let buf = IoBufferMut::with_capacity(buf_size);
@@ -1139,6 +1375,7 @@ mod test {
     use bytes::Bytes;
     use itertools::Itertools;
     use pageserver_api::key::Key;
+    use pageserver_api::models::ImageCompressionAlgorithm;
     use pageserver_api::shard::{ShardCount, ShardIdentity, ShardNumber, ShardStripeSize};
     use pageserver_api::value::Value;
     use utils::generation::Generation;
@@ -1147,7 +1384,9 @@ mod test {
 
     use super::{ImageLayerIterator, ImageLayerWriter};
     use crate::DEFAULT_PG_VERSION;
+    use crate::config::PageServerConf;
     use crate::context::RequestContext;
+    use crate::tenant::blob_io::tests::random_array;
     use crate::tenant::harness::{TIMELINE_ID, TenantHarness};
     use crate::tenant::storage_layer::{Layer, ResidentLayer};
     use crate::tenant::vectored_blob_io::StreamingVectoredReadPlanner;
@@ -1328,20 +1567,37 @@ async fn image_layer_rewrite() {
         }
     }
 
+    /// How [`produce_image_layer`] writes the layer.
+    #[derive(Default)]
+    struct ImageLayerOptions {
+        /// Overrides the `image_compression` of the tenant's pageserver config.
+        compression: Option<ImageCompressionAlgorithm>,
+    }
+
     async fn produce_image_layer(
         tenant: &Tenant,
         tline: &Arc<Timeline>,
         mut images: Vec<(Key, Bytes)>,
         lsn: Lsn,
+        opts: ImageLayerOptions,
         ctx: &RequestContext,
     ) -> anyhow::Result<ResidentLayer> {
+        let conf = match opts.compression {
+            Some(compression) => {
+                let mut conf = PageServerConf::dummy_conf(tenant.conf.workdir.clone());
+                conf.image_compression = compression;
+                Box::leak(Box::new(conf))
+            }
+            None => tenant.conf,
+        };
+
         images.sort();
         let (key_start, _) = images.first().unwrap();
         let (key_last, _) = images.last().unwrap();
         let key_end = key_last.next();
         let key_range = *key_start..key_end;
         let mut writer = ImageLayerWriter::new(
-            tenant.conf,
+            conf,
             tline.timeline_id,
             tenant.tenant_shard_id,
             &key_range,
@@ -1402,10 +1658,16 @@ fn get_key(id: u32) -> Key {
         let test_imgs = (0..N)
             .map(|idx| (get_key(idx as u32), Bytes::from(format!("img{idx:05}"))))
             .collect_vec();
-        let resident_layer =
-            produce_image_layer(&tenant, &tline, test_imgs.clone(), Lsn(0x10), &ctx)
-                .await
-                .unwrap();
+        let resident_layer = produce_image_layer(
+            &tenant,
+            &tline,
+            test_imgs.clone(),
+            Lsn(0x10),
+            ImageLayerOptions::default(),
+            &ctx,
+        )
+        .await
+        .unwrap();
         let img_layer = resident_layer.get_as_image(&ctx).await.unwrap();
         for max_read_size in [1, 1024] {
             for batch_size in [1, 2, 4, 8, 3, 7, 13] {
@@ -1435,4 +1697,76 @@ fn get_key(id: u32) -> Key {
             }
         }
     }
+
+    #[tokio::test]
+    async fn image_layer_zstd_dict() {
+        let harness = TenantHarness::create("image_layer_zstd_dict")
+            .await
+            .unwrap();
+        let (tenant, ctx) = harness.load().await;
+
+        let tline = tenant
+            .create_test_timeline(TIMELINE_ID, Lsn(0x10), DEFAULT_PG_VERSION, &ctx)
+            .await
+            .unwrap();
+
+        fn get_key(id: u32) -> Key {
+            let mut key = Key::from_hex("000000000033333333444444445500000000").unwrap();
+            key.field6 = id;
+            key
+        }
+        // Pages that share most of their contents, like heap pages of one table,
+        // but that don't compress well on their own.
+        let shared = random_array(4096);
+        let test_imgs = (0..256u32)
+            .map(|idx| {
+                let mut img = shared.clone();
+                img.extend_from_slice(&random_array(64));
+                img.extend_from_slice(format!("img{idx:05}").as_bytes());
+                (get_key(idx), Bytes::from(img))
+            })
+            .collect_vec();
+
+        let mut sizes = Vec::new();
+        for (compression, lsn) in [
+            (
+                ImageCompressionAlgorithm::Zstd { level: Some(1) },
+                Lsn(0x10),
+            ),
+            (
+                ImageCompressionAlgorithm::ZstdDict { level: Some(1) },
+                Lsn(0x20),
+            ),
+        ] {
+            let resident_layer = produce_image_layer(
+                &tenant,
+                &tline,
+                test_imgs.clone(),
+                lsn,
+                ImageLayerOptions {
+                    compression: Some(compression),
+                },
+                &ctx,
+            )
+            .await
+            .unwrap();
+            sizes.push(resident_layer.metadata().file_size);
+
+            let img_layer = resident_layer.get_as_image(&ctx).await.unwrap();
+            assert_eq!(
+                img_layer.zstd_dict.is_some(),
+                matches!(compression, ImageCompressionAlgorithm::ZstdDict { .. })
+            );
+            let mut iter = img_layer.iter(&ctx);
+            assert_img_iter_equal(&mut iter, &test_imgs, lsn).await;
+        }
+
+        let [zstd_size, zstd_dict_size] = sizes[..] else {
+            unreachable!()
+        };
+        assert!(
+            zstd_dict_size < zstd_size,
+            "zstd-dict: {zstd_dict_size} bytes, zstd: {zstd_size} bytes"
+        );
+    }
 }
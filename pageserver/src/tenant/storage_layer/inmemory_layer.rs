@@ -16,7 +16,7 @@
 use camino::Utf8PathBuf;
 use pageserver_api::key::{CompactKey, Key};
 use pageserver_api::keyspace::KeySpace;
-use pageserver_api::models::InMemoryLayerInfo;
+use pageserver_api::models::{ImageCompressionAlgorithm, InMemoryLayerInfo};
 use pageserver_api::shard::TenantShardId;
 use tokio::sync::RwLock;
 use tokio_util::sync::CancellationToken;
@@ -719,6 +719,7 @@ pub async fn write_to_disk(
         ctx: &RequestContext,
         key_range: Option<Range<Key>>,
         l0_flush_global_state: &l0_flush::Inner,
+        compression: ImageCompressionAlgorithm,
     ) -> Result<Option<(PersistentLayerDesc, Utf8PathBuf)>> {
         // Grab the lock in read-mode. We hold it over the I/O, but because this
         // layer is not writeable anymore, no one should be trying to acquire the
@@ -759,6 +760,7 @@ pub async fn write_to_disk(
             self.tenant_shard_id,
             Key::MIN,
             self.start_lsn..end_lsn,
+            compression,
             ctx,
         )
         .await?;
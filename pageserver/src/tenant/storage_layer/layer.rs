@@ -11,7 +11,7 @@
 use pageserver_api::shard::{ShardIdentity, ShardIndex, TenantShardId};
 use tracing::{Instrument, info_span};
 use utils::generation::Generation;
-use utils::id::TimelineId;
+use utils::id::{TenantTimelineId, TimelineId};
 use utils::lsn::Lsn;
 use utils::sync::{gate, heavier_once_cell};
 
@@ -26,9 +26,12 @@
 use crate::span::debug_assert_current_span_has_tenant_and_timeline_id;
 use crate::task_mgr::TaskKind;
 use crate::tenant::Timeline;
+use crate::tenant::disk_quota::DiskQuotaError;
 use crate::tenant::remote_timeline_client::LayerFileMetadata;
 use crate::tenant::timeline::{CompactionError, GetVectoredError};
 
+mod partial;
+
 #[cfg(test)]
 mod tests;
 
@@ -184,6 +187,7 @@ pub(crate) fn for_evicted(
             None,
             metadata.generation,
             metadata.shard,
+            metadata.cross_tenant_owner,
         )));
 
         debug_assert!(owner.0.needs_download_blocking().unwrap().is_some());
@@ -224,6 +228,7 @@ pub(crate) fn for_resident(
                 Some(inner),
                 metadata.generation,
                 metadata.shard,
+                metadata.cross_tenant_owner,
             )
         }));
 
@@ -272,6 +277,7 @@ pub(crate) fn finish_creating(
                 Some(inner),
                 timeline.generation,
                 timeline.get_shard_index(),
+                None,
             )
         }));
 
@@ -303,6 +309,23 @@ pub(crate) async fn evict_and_wait(&self, timeout: Duration) -> Result<(), Evict
         self.0.evict_and_wait(timeout).await
     }
 
+    /// Requests the layer to be evicted after a read found it to be corrupt, so that the next
+    /// access downloads it again.
+    ///
+    /// Does not wait: the file is evicted once the ongoing reads have completed, unless a new
+    /// read gets to the layer first. If the downloaded copy turns out to be corrupt as well, the
+    /// remote copy is the corrupt one: the layer is marked broken instead of being evicted again.
+    pub(crate) fn evict_corrupted(&self) {
+        self.0.evict_corrupted();
+    }
+
+    /// Called after a read of the layer passed checksum verification. Once a copy downloaded
+    /// after a corrupt one reads cleanly, the next corruption evicts the layer again instead of
+    /// marking it broken.
+    pub(crate) fn read_verified(&self) {
+        self.0.read_verified();
+    }
+
     /// Delete the layer file when the `self` gets dropped, also try to schedule a remote index upload
     /// then.
     ///
@@ -325,6 +348,40 @@ pub(crate) async fn get_values_reconstruct_data(
         reconstruct_data: &mut ValuesReconstructState,
         ctx: &RequestContext,
     ) -> Result<(), GetVectoredError> {
+        if self.0.broken.load(Ordering::Relaxed) {
+            return Err(GetVectoredError::Corruption(anyhow::anyhow!(
+                "layer {self} is broken: its remote copy failed checksum verification"
+            )));
+        }
+
+        let partial = self
+            .0
+            .get_partial_for_read(ctx)
+            .await
+            .map_err(|err| match err {
+                DownloadError::TimelineShutdown | DownloadError::DownloadCancelled => {
+                    GetVectoredError::Cancelled
+                }
+                other => GetVectoredError::Other(anyhow::anyhow!(other)),
+            })?;
+
+        if let Some(partial) = partial {
+            self.record_access(ctx);
+
+            return partial
+                .get_values_reconstruct_data(&self.0, keyspace, reconstruct_data, ctx)
+                .instrument(
+                    tracing::debug_span!("get_values_reconstruct_data", layer=%self, partial=true),
+                )
+                .await
+                .map_err(|err| match err {
+                    GetVectoredError::Other(err) => GetVectoredError::Other(
+                        err.context(format!("get_values_reconstruct_data for layer {self}")),
+                    ),
+                    err => err,
+                });
+        }
+
         let downloaded = {
             let ctx = RequestContextBuilder::from(ctx)
                 .perf_span(|crnt_perf_span| {
@@ -565,6 +622,9 @@ pub(crate) fn set_visibility(&self, visibility: LayerVisibilityHint) {
     }
 }
 
+/// [`LayerInner::corrupted_version`] of a layer not found to be corrupt.
+const NOT_CORRUPTED: usize = usize::MAX;
+
 /// The download-ness ([`DownloadedLayer`]) can be either resident or wanted evicted.
 ///
 /// However when we want something evicted, we cannot evict it right away as there might be current
@@ -709,6 +769,10 @@ struct LayerInner {
     /// a shard split since the layer was originally written.
     shard: ShardIndex,
 
+    /// See [`LayerFileMetadata::cross_tenant_owner`]. Always `None` for layers created in this
+    /// process.
+    cross_tenant_owner: Option<TenantTimelineId>,
+
     /// When the Layer was last evicted but has not been downloaded since.
     ///
     /// This is used for skipping evicted layers from the previous heatmap (see
@@ -716,6 +780,20 @@ struct LayerInner {
     /// (see [`LayerImplMetrics::redownload_after`]).
     last_evicted_at: std::sync::Mutex<Option<std::time::Instant>>,
 
+    /// Serves reads while the layer is evicted and being downloaded, see
+    /// [`PageServerConf::ondemand_download_partial_reads`].
+    ///
+    /// Cleared once the layer has been initialized as resident.
+    partial: std::sync::Mutex<Option<Arc<partial::PartialLayer>>>,
+
+    /// The version of the first initialization found to be corrupt, or [`NOT_CORRUPTED`]. Reset
+    /// once a later initialization reads cleanly.
+    corrupted_version: AtomicUsize,
+
+    /// Set once an initialization after `corrupted_version` was found to be corrupt too: the
+    /// remote copy is corrupt, so reads of the layer fail right away.
+    broken: AtomicBool,
+
     #[cfg(test)]
     failpoints: std::sync::Mutex<Vec<failpoints::Failpoint>>,
 }
@@ -852,6 +930,7 @@ fn new(
         downloaded: Option<Arc<DownloadedLayer>>,
         generation: Generation,
         shard: ShardIndex,
+        cross_tenant_owner: Option<TenantTimelineId>,
     ) -> Self {
         let (inner, version, init_status) = if let Some(inner) = downloaded {
             let version = inner.version;
@@ -887,7 +966,11 @@ fn new(
             consecutive_failures: AtomicUsize::new(0),
             generation,
             shard,
+            cross_tenant_owner,
             last_evicted_at: std::sync::Mutex::default(),
+            partial: std::sync::Mutex::default(),
+            corrupted_version: AtomicUsize::new(NOT_CORRUPTED),
+            broken: AtomicBool::new(false),
             #[cfg(test)]
             failpoints: Default::default(),
         }
@@ -969,6 +1052,80 @@ pub(crate) async fn evict_and_wait(&self, timeout: Duration) -> Result<(), Evict
         }
     }
 
+    fn evict_corrupted(&self) {
+        let strong = {
+            let Some(mut either) = self.inner.get() else {
+                return;
+            };
+            let version = match &*either {
+                ResidentOrWantedEvicted::Resident(strong) => strong.version,
+                ResidentOrWantedEvicted::WantedEvicted(_, version) => *version,
+            };
+            match self.corrupted_version.compare_exchange(
+                NOT_CORRUPTED,
+                version,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => either.downgrade(),
+                // more reads of the copy we are already evicting
+                Err(corrupted_version) if corrupted_version == version => None,
+                Err(_) => {
+                    drop(either);
+                    if !self.broken.swap(true, Ordering::Relaxed) {
+                        LAYER_IMPL_METRICS.inc_broken_layers();
+                        tracing::error!(
+                            layer=%self,
+                            "downloaded layer failed checksum verification again, marking it broken"
+                        );
+                    }
+                    return;
+                }
+            }
+        };
+
+        if strong.is_some() {
+            // drop the DownloadedLayer outside of the holding the guard
+            drop(strong);
+
+            LAYER_IMPL_METRICS.inc_started_evictions();
+            LAYER_IMPL_METRICS.inc_corrupted_evictions();
+            tracing::warn!(layer=%self, "evicting layer which failed checksum verification");
+        }
+    }
+
+    fn read_verified(&self) {
+        let corrupted_version = self.corrupted_version.load(Ordering::Relaxed);
+        if corrupted_version == NOT_CORRUPTED {
+            return;
+        }
+
+        let version = {
+            let Some(either) = self.inner.get() else {
+                return;
+            };
+            match &*either {
+                ResidentOrWantedEvicted::Resident(strong) => strong.version,
+                ResidentOrWantedEvicted::WantedEvicted(_, version) => *version,
+            }
+        };
+
+        // reads of the corrupt copy which is still being evicted do not count
+        if version > corrupted_version
+            && self
+                .corrupted_version
+                .compare_exchange(
+                    corrupted_version,
+                    NOT_CORRUPTED,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+        {
+            tracing::info!(layer=%self, "downloaded layer passed checksum verification");
+        }
+    }
+
     /// Cancellation safe.
     async fn get_or_maybe_download(
         self: &Arc<Self>,
@@ -1110,6 +1267,67 @@ async fn get_or_maybe_download(
         .await
     }
 
+    /// Returns the [`partial::PartialLayer`] to serve a read from, if the layer is an evicted image
+    /// layer and partial reads are enabled. Starts the full download in the background.
+    async fn get_partial_for_read(
+        self: &Arc<Self>,
+        ctx: &RequestContext,
+    ) -> Result<Option<Arc<partial::PartialLayer>>, DownloadError> {
+        if !self.conf.ondemand_download_partial_reads || self.desc.is_delta {
+            return Ok(None);
+        }
+
+        let likely_resident = self
+            .inner
+            .get()
+            .map(|rowe| rowe.is_likely_resident())
+            .unwrap_or(false);
+        if likely_resident {
+            return Ok(None);
+        }
+
+        match self.needs_download().await {
+            Ok(Some(NeedsDownload::NotFound | NeedsDownload::WrongSize { .. })) => {}
+            // leave any other outcome to the regular download path
+            Ok(Some(NeedsDownload::NotFile(_)) | None) | Err(_) => return Ok(None),
+        }
+
+        self.check_expected_download(ctx)?;
+
+        let partial = self
+            .partial
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| Arc::new(partial::PartialLayer::new(self)))
+            .clone();
+
+        if partial
+            .promotion_started
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+        {
+            let this = self.clone();
+            let started = partial.clone();
+            let ctx = RequestContextBuilder::from(ctx)
+                .task_kind(TaskKind::LayerDownload)
+                .download_behavior(DownloadBehavior::Download)
+                .detached_child();
+
+            Self::spawn(
+                async move {
+                    if let Err(e) = this.get_or_maybe_download(true, &ctx).await {
+                        tracing::info!("background download of partially read layer failed: {e}");
+                        // allow the next read to retry
+                        started.promotion_started.store(false, Ordering::Release);
+                    }
+                }
+                .in_current_span(),
+            );
+        }
+
+        Ok(Some(partial))
+    }
+
     /// Nag or fail per RequestContext policy
     fn check_expected_download(&self, ctx: &RequestContext) -> Result<(), DownloadError> {
         use crate::context::DownloadBehavior::*;
@@ -1154,9 +1372,24 @@ async fn download_init_and_wait(
             .enter()
             .map_err(|_| DownloadError::DownloadCancelled)?;
 
+        // held until the downloaded layer is accounted for in the resident size
+        let reservation = timeline
+            .disk_quota
+            .reserve(
+                self.desc.file_size,
+                timeline.get_max_resident_size(),
+                &timeline.cancel,
+            )
+            .await
+            .map_err(|e| match e {
+                DiskQuotaError::Exceeded => DownloadError::ResidentSizeQuotaExceeded,
+                DiskQuotaError::Cancelled => DownloadError::DownloadCancelled,
+            })?;
+
         Self::spawn(
             async move {
                 let _guard = guard;
+                let _reservation = reservation;
 
                 // now that we have commited to downloading, send out an update to:
                 // - unhang any pending eviction
@@ -1318,6 +1551,9 @@ fn initialize_after_layer_is_on_disk(
 
         self.inner.set(value, permit);
 
+        // reads in flight keep their own reference to the partial layer
+        drop(self.partial.lock().unwrap().take());
+
         res
     }
 
@@ -1621,7 +1857,10 @@ fn evict_blocking(
     }
 
     fn metadata(&self) -> LayerFileMetadata {
-        LayerFileMetadata::new(self.desc.file_size, self.generation, self.shard)
+        LayerFileMetadata {
+            cross_tenant_owner: self.cross_tenant_owner,
+            ..LayerFileMetadata::new(self.desc.file_size, self.generation, self.shard)
+        }
     }
 
     /// Needed to use entered runtime in tests, but otherwise use BACKGROUND_RUNTIME.
@@ -1688,6 +1927,8 @@ pub(crate) enum DownloadError {
     DownloadCancelled,
     #[error("pre-condition: stat before download failed")]
     PreStatFailed(#[source] std::io::Error),
+    #[error("tenant shard is at its max_resident_size and no layers could be evicted")]
+    ResidentSizeQuotaExceeded,
 
     #[cfg(test)]
     #[error("failpoint: {0:?}")]
@@ -1974,6 +2215,18 @@ pub(crate) async fn copy_delta_prefix(
         }
     }
 
+    /// Check the whole layer file for corruption. Unlike reads, this does not count as an
+    /// access to the layer.
+    #[tracing::instrument(level = tracing::Level::DEBUG, skip_all, fields(layer=%self))]
+    pub(crate) async fn scrub(&self, ctx: &RequestContext) -> anyhow::Result<()> {
+        use LayerKind::*;
+
+        match self.downloaded.get(&self.owner.0, ctx).await? {
+            Delta(d) => d.scrub(ctx).await,
+            Image(i) => i.scrub(ctx).await,
+        }
+    }
+
     pub(crate) fn local_path(&self) -> &Utf8Path {
         &self.owner.0.path
     }
@@ -2032,6 +2285,8 @@ pub(crate) struct LayerImplMetrics {
     started_evictions: IntCounter,
     completed_evictions: IntCounter,
     cancelled_evictions: enum_map::EnumMap<EvictionCancelled, IntCounter>,
+    corrupted_evictions: IntCounter,
+    broken_layers: IntCounter,
 
     started_deletes: IntCounter,
     completed_deletes: IntCounter,
@@ -2073,6 +2328,18 @@ fn default() -> Self {
             cancelled_evictions.with_label_values(&[s])
         }));
 
+        let corrupted_evictions = metrics::register_int_counter!(
+            "pageserver_layer_corrupted_evictions",
+            "Evictions started because a read found the layer file to be corrupt"
+        )
+        .unwrap();
+
+        let broken_layers = metrics::register_int_counter!(
+            "pageserver_layer_broken",
+            "Layers whose downloaded copy failed checksum verification too"
+        )
+        .unwrap();
+
         let started_deletes = metrics::register_int_counter!(
             "pageserver_layer_started_deletes",
             "Deletions on drop pending in the Layer implementation"
@@ -2147,6 +2414,8 @@ fn default() -> Self {
             started_evictions,
             completed_evictions,
             cancelled_evictions,
+            corrupted_evictions,
+            broken_layers,
 
             started_deletes,
             completed_deletes,
@@ -2170,6 +2439,12 @@ fn inc_completed_evictions(&self) {
     fn inc_eviction_cancelled(&self, reason: EvictionCancelled) {
         self.cancelled_evictions[reason].inc()
     }
+    fn inc_corrupted_evictions(&self) {
+        self.corrupted_evictions.inc();
+    }
+    fn inc_broken_layers(&self) {
+        self.broken_layers.inc();
+    }
 
     fn inc_started_deletes(&self) {
         self.started_deletes.inc();
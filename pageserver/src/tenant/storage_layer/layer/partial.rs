@@ -0,0 +1,278 @@
+//! Partially resident image layers.
+//!
+//! With [`PageServerConf::ondemand_download_partial_reads`] enabled, a read on an evicted image
+//! layer does not wait for the whole layer file to be downloaded. Instead, the summary block, the
+//! index and the byte ranges needed by the read are fetched from remote storage and written into a
+//! sparse temporary file next to the layer file, while the full download is started in the
+//! background. Once the full download completes, the [`PartialLayer`] is dropped together with
+//! its file.
+//!
+//! The temporary file ends with [`TEMP_FILE_SUFFIX`], so it is removed on startup should we crash
+//! before dropping it. Each downloaded chunk holds a reservation in the tenant shard's disk quota
+//! until then, since the file is not part of the resident size.
+//!
+//! [`PageServerConf::ondemand_download_partial_reads`]: crate::config::PageServerConf::ondemand_download_partial_reads
+
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use camino::Utf8PathBuf;
+use pageserver_api::keyspace::KeySpace;
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+
+use super::{DownloadError, LayerInner};
+use crate::TEMP_FILE_SUFFIX;
+use crate::context::RequestContext;
+use crate::page_cache::PAGE_SZ;
+use crate::tenant::disk_quota::{DiskQuotaError, DiskQuotaReservation};
+use crate::tenant::storage_layer::ValuesReconstructState;
+use crate::tenant::storage_layer::image_layer::{self, ImageLayerInner};
+use crate::tenant::timeline::GetVectoredError;
+use crate::virtual_file::owned_buffers_io::io_buf_ext::IoBufExt;
+use crate::virtual_file::{self, IoBufferMut, VirtualFile};
+
+/// Granularity at which parts of the layer file are downloaded and tracked.
+const CHUNK_SIZE: u64 = 256 * 1024;
+
+pub(super) struct PartialLayer {
+    /// Path of the sparse local file holding the downloaded chunks.
+    path: Utf8PathBuf,
+
+    file_size: u64,
+
+    /// Opened on first download.
+    file: tokio::sync::OnceCell<VirtualFile>,
+
+    /// One cell per [`CHUNK_SIZE`] sized part of the layer file, initialized once the part has
+    /// been written to [`Self::file`]. The reservation is released when the file is removed.
+    chunks: Vec<tokio::sync::OnceCell<DiskQuotaReservation>>,
+
+    /// Loaded once the summary and the index have been downloaded.
+    inner: tokio::sync::OnceCell<ImageLayerInner>,
+
+    /// Set while a background download of the full layer is in progress.
+    pub(super) promotion_started: AtomicBool,
+}
+
+impl Drop for PartialLayer {
+    fn drop(&mut self) {
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                tracing::warn!(path=%self.path, "failed to remove partial layer file: {e}");
+            }
+        }
+    }
+}
+
+impl PartialLayer {
+    pub(super) fn new(owner: &LayerInner) -> Self {
+        let rand_string: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(8)
+            .map(char::from)
+            .collect();
+
+        let path = Utf8PathBuf::from(format!(
+            "{}.partial-{rand_string}.{TEMP_FILE_SUFFIX}",
+            owner.path
+        ));
+
+        let file_size = owner.desc.file_size;
+        let chunks = (0..file_size.div_ceil(CHUNK_SIZE))
+            .map(|_| tokio::sync::OnceCell::new())
+            .collect();
+
+        PartialLayer {
+            path,
+            file_size,
+            file: tokio::sync::OnceCell::new(),
+            chunks,
+            inner: tokio::sync::OnceCell::new(),
+            promotion_started: AtomicBool::new(false),
+        }
+    }
+
+    /// Look up the keys in the provided keyspace, downloading only the parts of the layer file
+    /// which are needed for it.
+    pub(super) async fn get_values_reconstruct_data(
+        self: &Arc<Self>,
+        owner: &LayerInner,
+        keyspace: KeySpace,
+        reconstruct_state: &mut ValuesReconstructState,
+        ctx: &RequestContext,
+    ) -> Result<(), GetVectoredError> {
+        let inner = self.load(owner, ctx).await?;
+
+        let reads = inner
+            .plan_reads(keyspace, None, ctx)
+            .await
+            .map_err(GetVectoredError::Other)?;
+
+        let downloads = reads
+            .iter()
+            .map(|read| self.ensure_downloaded(owner, read.start..read.end, ctx));
+        futures::future::try_join_all(downloads).await?;
+
+        crate::metrics::REMOTE_ONDEMAND_PARTIAL_READS.inc();
+
+        // the spawned reads keep `self` alive, and with it, the file. evicting the layer would not
+        // help with a corrupt image: the partial file is not the layer file.
+        inner
+            .do_reads_and_update_state(self.clone(), None, reads, reconstruct_state, ctx)
+            .await;
+
+        reconstruct_state.on_image_layer_visited(&owner.desc.key_range);
+
+        Ok(())
+    }
+
+    async fn load(
+        &self,
+        owner: &LayerInner,
+        ctx: &RequestContext,
+    ) -> Result<&ImageLayerInner, GetVectoredError> {
+        self.inner
+            .get_or_try_init(|| async {
+                self.ensure_downloaded(owner, 0..PAGE_SZ as u64, ctx)
+                    .await?;
+
+                // Loading the layer reads the zstd dictionary, which is stored after the index.
+                let on_disk = ImageLayerInner::read_summary(&self.path, ctx)
+                    .await
+                    .map_err(GetVectoredError::Other)?;
+                let index_range = on_disk.index_start_offset()..self.file_size;
+                self.ensure_downloaded(owner, index_range, ctx).await?;
+
+                let lsn = owner.desc.image_layer_lsn();
+                let summary = Some(image_layer::Summary::expected(
+                    owner.desc.tenant_shard_id.tenant_id,
+                    owner.desc.timeline_id,
+                    owner.desc.key_range.clone(),
+                    lsn,
+                ));
+                let inner = ImageLayerInner::load(
+                    &self.path,
+                    lsn,
+                    summary,
+                    Some(owner.conf.max_vectored_read_bytes),
+                    ctx,
+                )
+                .await
+                .map_err(GetVectoredError::Other)?;
+
+                Ok(inner)
+            })
+            .await
+    }
+
+    /// Make sure that the chunks covering `range` have been downloaded to the local file.
+    ///
+    /// Concurrent callers needing the same chunk wait for a single download.
+    async fn ensure_downloaded(
+        &self,
+        owner: &LayerInner,
+        range: Range<u64>,
+        ctx: &RequestContext,
+    ) -> Result<(), GetVectoredError> {
+        let end = range.end.min(self.file_size);
+        if range.start >= end {
+            return Ok(());
+        }
+
+        let first = range.start / CHUNK_SIZE;
+        let last = (end - 1) / CHUNK_SIZE;
+
+        let downloads = (first..=last).map(|idx| {
+            self.chunks[idx as usize].get_or_try_init(|| self.download_chunk(owner, idx, ctx))
+        });
+        futures::future::try_join_all(downloads).await?;
+
+        Ok(())
+    }
+
+    async fn download_chunk(
+        &self,
+        owner: &LayerInner,
+        idx: u64,
+        ctx: &RequestContext,
+    ) -> Result<DiskQuotaReservation, GetVectoredError> {
+        let timeline = owner
+            .timeline
+            .upgrade()
+            .ok_or(GetVectoredError::Cancelled)?;
+
+        let _guard = timeline
+            .gate
+            .enter()
+            .map_err(|_| GetVectoredError::Cancelled)?;
+
+        let start = idx * CHUNK_SIZE;
+        let end = (start + CHUNK_SIZE).min(self.file_size);
+
+        let reservation = timeline
+            .disk_quota
+            .reserve(
+                end - start,
+                timeline.get_max_resident_size(),
+                &timeline.cancel,
+            )
+            .await
+            .map_err(|e| match e {
+                DiskQuotaError::Exceeded => GetVectoredError::Other(anyhow::anyhow!(
+                    DownloadError::ResidentSizeQuotaExceeded
+                )),
+                DiskQuotaError::Cancelled => GetVectoredError::Cancelled,
+            })?;
+
+        let bytes = timeline
+            .remote_client
+            .download_layer_range(
+                &owner.desc.layer_name(),
+                &owner.metadata(),
+                start..end,
+                &timeline.cancel,
+                ctx,
+            )
+            .await
+            .map_err(|e| match e {
+                remote_storage::DownloadError::Cancelled => GetVectoredError::Cancelled,
+                other => GetVectoredError::Other(
+                    anyhow::anyhow!(other).context("download partial layer range"),
+                ),
+            })?;
+
+        let file = self
+            .file
+            .get_or_try_init(|| async {
+                VirtualFile::open_with_options(
+                    &self.path,
+                    virtual_file::OpenOptions::new()
+                        .read(true)
+                        .write(true)
+                        .create(true)
+                        .truncate(true),
+                    ctx,
+                )
+                .await
+            })
+            .await
+            .map_err(|e| {
+                GetVectoredError::Other(anyhow::anyhow!(e).context("create partial layer file"))
+            })?;
+
+        let mut buf = IoBufferMut::with_capacity(bytes.len());
+        buf.extend_from_slice(&bytes);
+        let (_, res) = file
+            .write_all_at(buf.freeze().slice_len(), start, ctx)
+            .await;
+        res.map_err(|e| {
+            GetVectoredError::Other(anyhow::anyhow!(e).context("write partial layer file"))
+        })?;
+
+        Ok(reservation)
+    }
+}
@@ -241,6 +241,99 @@ async fn smoke_test() {
     assert_eq!(0, LAYER_IMPL_METRICS.inits_cancelled.get())
 }
 
+/// Reads on an evicted image layer are served from ranged downloads when
+/// `ondemand_download_partial_reads` is enabled, and the layer still becomes resident.
+#[tokio::test]
+async fn partial_read_on_evicted_image_layer() {
+    let mut h = TenantHarness::create("partial_read_on_evicted_image_layer")
+        .await
+        .unwrap();
+    h.conf = {
+        let mut conf = h.conf.clone();
+        conf.ondemand_download_partial_reads = true;
+        Box::leak(Box::new(conf))
+    };
+    let span = h.span();
+    let download_span = span.in_scope(|| tracing::info_span!("downloading", timeline_id = 1));
+    let (tenant, ctx) = h.load().await;
+    let io_concurrency = IoConcurrency::spawn_for_test();
+
+    let key = Key::from_hex("620000000033333333444444445500000000").unwrap();
+    let image_layers = vec![(Lsn(0x40), vec![(key, test_img("foo"))])];
+
+    let timeline = tenant
+        .create_test_timeline_with_layers(
+            TimelineId::generate(),
+            Lsn(0x10),
+            14,
+            &ctx,
+            Default::default(), // in-memory layers
+            Default::default(),
+            image_layers,
+            Lsn(0x100),
+        )
+        .await
+        .unwrap();
+    let ctx = &ctx.with_scope_timeline(&timeline);
+
+    timeline.remote_client.wait_completion().await.unwrap();
+
+    let layer = {
+        let layers = timeline.layers.read().await;
+        layers
+            .likely_resident_layers()
+            .find(|l| {
+                let desc = l.layer_desc();
+                !desc.is_delta && desc.image_layer_lsn() == Lsn(0x40)
+            })
+            .cloned()
+            .expect("image layer was created")
+    };
+
+    layer.evict_and_wait(FOREVER).await.unwrap();
+
+    let dl_ctx = RequestContextBuilder::from(ctx)
+        .download_behavior(DownloadBehavior::Download)
+        .attached_child();
+
+    let partial_reads_before = crate::metrics::REMOTE_ONDEMAND_PARTIAL_READS.get();
+
+    let img = {
+        let mut data = ValuesReconstructState::new(io_concurrency.clone());
+        layer
+            .get_values_reconstruct_data(
+                KeySpace::single(key..key.next()),
+                Lsn(0x40)..Lsn(0x41),
+                &mut data,
+                &dl_ctx,
+            )
+            .instrument(download_span.clone())
+            .await
+            .unwrap();
+        data.keys
+            .remove(&key)
+            .expect("must be present")
+            .collect_pending_ios()
+            .await
+            .expect("must not error")
+            .img
+            .take()
+            .expect("image layer has the key")
+    };
+
+    assert_eq!(img.1, test_img("foo"));
+    assert!(crate::metrics::REMOTE_ONDEMAND_PARTIAL_READS.get() > partial_reads_before);
+
+    // joins the background download started by the partial read
+    layer
+        .download_and_keep_resident(&dl_ctx)
+        .instrument(download_span)
+        .await
+        .unwrap();
+
+    assert!(layer.0.partial.lock().unwrap().is_none());
+}
+
 /// This test demonstrates a previous hang when a eviction and deletion were requested at the same
 /// time. Now both of them complete per Arc drop semantics.
 #[tokio::test(start_paused = true)]
@@ -318,6 +411,176 @@ async fn evict_and_wait_on_wanted_deleted() {
     assert_eq!(0, LAYER_IMPL_METRICS.inits_cancelled.get())
 }
 
+/// A tenant shard at its `max_resident_size` refuses downloads when no layers get evicted to make
+/// room for them.
+#[tokio::test(start_paused = true)]
+async fn download_refused_at_max_resident_size() {
+    let h = TenantHarness::create("download_refused_at_max_resident_size")
+        .await
+        .unwrap();
+    let (tenant, ctx) = h.load().await;
+
+    let timeline = tenant
+        .create_test_timeline(TimelineId::generate(), Lsn(0x10), 14, &ctx)
+        .await
+        .unwrap();
+    let ctx = ctx.with_scope_timeline(&timeline);
+
+    let layer = {
+        let mut layers = {
+            let layers = timeline.layers.read().await;
+            layers.likely_resident_layers().cloned().collect::<Vec<_>>()
+        };
+
+        assert_eq!(layers.len(), 1);
+
+        layers.swap_remove(0)
+    };
+    let file_size = layer.layer_desc().file_size;
+
+    layer.evict_and_wait(FOREVER).await.unwrap();
+    let resident_size = tenant.resident_size();
+
+    let set_max_resident_size = |max_resident_size| {
+        tenant
+            .update_tenant_config(|mut conf| {
+                conf.max_resident_size = Some(max_resident_size);
+                Ok(conf)
+            })
+            .unwrap();
+    };
+
+    let dl_ctx = RequestContextBuilder::from(&ctx)
+        .download_behavior(DownloadBehavior::Download)
+        .attached_child();
+
+    // one byte short of room for the layer, and the disk quota loop is not running to evict
+    set_max_resident_size(resident_size + file_size - 1);
+    let e = layer.download_and_keep_resident(&dl_ctx).await.unwrap_err();
+    assert!(
+        matches!(e, DownloadError::ResidentSizeQuotaExceeded),
+        "{e:?}"
+    );
+    assert!(!layer.is_likely_resident());
+    assert_eq!(tenant.resident_size(), resident_size);
+
+    // with room for it, the layer is downloaded
+    set_max_resident_size(resident_size + file_size);
+    let resident = layer.download_and_keep_resident(&dl_ctx).await.unwrap();
+    assert_eq!(tenant.resident_size(), resident_size + file_size);
+    drop(resident);
+}
+
+/// A layer is evicted when a read finds it corrupt, but if the downloaded copy is corrupt too,
+/// the layer is marked broken and stays resident, and its reads fail right away.
+#[tokio::test(start_paused = true)]
+async fn corrupt_after_download_is_not_evicted_again() {
+    let h = TenantHarness::create("corrupt_after_download_is_not_evicted_again")
+        .await
+        .unwrap();
+    let (tenant, ctx) = h.load().await;
+
+    let timeline = tenant
+        .create_test_timeline(TimelineId::generate(), Lsn(0x10), 14, &ctx)
+        .await
+        .unwrap();
+    let ctx = ctx.with_scope_timeline(&timeline);
+
+    let layer = {
+        let mut layers = {
+            let layers = timeline.layers.read().await;
+            layers.likely_resident_layers().cloned().collect::<Vec<_>>()
+        };
+
+        assert_eq!(layers.len(), 1);
+
+        layers.swap_remove(0)
+    };
+
+    layer.evict_corrupted();
+    // the eviction is already underway, or done
+    match layer.evict_and_wait(FOREVER).await {
+        Ok(()) | Err(EvictionError::NotFound) => {}
+        Err(e) => panic!("unexpected eviction error: {e:?}"),
+    }
+    assert!(!layer.is_likely_resident());
+    assert_eq!(1, LAYER_IMPL_METRICS.corrupted_evictions.get());
+
+    let dl_ctx = RequestContextBuilder::from(&ctx)
+        .download_behavior(DownloadBehavior::Download)
+        .attached_child();
+    drop(layer.download_and_keep_resident(&dl_ctx).await.unwrap());
+
+    layer.evict_corrupted();
+    layer.evict_corrupted();
+    assert!(layer.is_likely_resident());
+    assert_eq!(1, LAYER_IMPL_METRICS.corrupted_evictions.get());
+    assert_eq!(1, LAYER_IMPL_METRICS.broken_layers.get());
+
+    let mut data = ValuesReconstructState::new(IoConcurrency::spawn_for_test());
+    let e = layer
+        .get_values_reconstruct_data(
+            KeySpace {
+                ranges: vec![CONTROLFILE_KEY..CONTROLFILE_KEY.next()],
+            },
+            Lsn(0x10)..Lsn(0x11),
+            &mut data,
+            &ctx,
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(e, GetVectoredError::Corruption(_)), "{e:?}");
+}
+
+/// Once a copy downloaded after a corrupt one reads cleanly, a second corruption evicts the layer
+/// again instead of marking it broken.
+#[tokio::test(start_paused = true)]
+async fn corrupt_after_clean_read_is_evicted_again() {
+    let h = TenantHarness::create("corrupt_after_clean_read_is_evicted_again")
+        .await
+        .unwrap();
+    let (tenant, ctx) = h.load().await;
+
+    let timeline = tenant
+        .create_test_timeline(TimelineId::generate(), Lsn(0x10), 14, &ctx)
+        .await
+        .unwrap();
+    let ctx = ctx.with_scope_timeline(&timeline);
+
+    let layer = {
+        let mut layers = {
+            let layers = timeline.layers.read().await;
+            layers.likely_resident_layers().cloned().collect::<Vec<_>>()
+        };
+
+        assert_eq!(layers.len(), 1);
+
+        layers.swap_remove(0)
+    };
+
+    let dl_ctx = RequestContextBuilder::from(&ctx)
+        .download_behavior(DownloadBehavior::Download)
+        .attached_child();
+
+    for corrupted_evictions in 1..=2 {
+        layer.evict_corrupted();
+        match layer.evict_and_wait(FOREVER).await {
+            Ok(()) | Err(EvictionError::NotFound) => {}
+            Err(e) => panic!("unexpected eviction error: {e:?}"),
+        }
+        assert!(!layer.is_likely_resident());
+        assert_eq!(
+            corrupted_evictions,
+            LAYER_IMPL_METRICS.corrupted_evictions.get()
+        );
+
+        drop(layer.download_and_keep_resident(&dl_ctx).await.unwrap());
+        layer.read_verified();
+    }
+
+    assert_eq!(0, LAYER_IMPL_METRICS.broken_layers.get());
+}
+
 /// This test ensures we are able to read the layer while the layer eviction has been
 /// started but not completed.
 #[test]
@@ -916,7 +1179,7 @@ async fn eviction_cancellation_on_drop() {
 fn layer_size() {
     assert_eq!(size_of::<LayerAccessStats>(), 8);
     assert_eq!(size_of::<PersistentLayerDesc>(), 104);
-    assert_eq!(size_of::<LayerInner>(), 296);
+    assert_eq!(size_of::<LayerInner>(), 312);
     // it also has the utf8 path
 }
 
@@ -409,9 +409,15 @@ fn get_key(id: u32) -> Key {
                 Value::Image(Bytes::copy_from_slice(b"test")),
             ),
         ];
-        let resident_layer_1 = produce_delta_layer(&tenant, &tline, test_deltas1.clone(), &ctx)
-            .await
-            .unwrap();
+        let resident_layer_1 = produce_delta_layer(
+            &tenant,
+            &tline,
+            test_deltas1.clone(),
+            Default::default(),
+            &ctx,
+        )
+        .await
+        .unwrap();
         let test_deltas2 = vec![
             (
                 get_key(3),
@@ -424,9 +430,15 @@ fn get_key(id: u32) -> Key {
                 Value::Image(Bytes::copy_from_slice(b"test")),
             ),
         ];
-        let resident_layer_2 = produce_delta_layer(&tenant, &tline, test_deltas2.clone(), &ctx)
-            .await
-            .unwrap();
+        let resident_layer_2 = produce_delta_layer(
+            &tenant,
+            &tline,
+            test_deltas2.clone(),
+            Default::default(),
+            &ctx,
+        )
+        .await
+        .unwrap();
         let mut merge_iter = MergeIterator::create(
             &[
                 resident_layer_2.get_as_delta(&ctx).await.unwrap(),
@@ -472,9 +484,15 @@ fn get_key(id: u32) -> Key {
                 )
             })
             .collect_vec();
-        let resident_layer_1 = produce_delta_layer(&tenant, &tline, test_deltas1.clone(), &ctx)
-            .await
-            .unwrap();
+        let resident_layer_1 = produce_delta_layer(
+            &tenant,
+            &tline,
+            test_deltas1.clone(),
+            Default::default(),
+            &ctx,
+        )
+        .await
+        .unwrap();
         let test_deltas2 = (0..N)
             .map(|idx| {
                 (
@@ -484,9 +502,15 @@ fn get_key(id: u32) -> Key {
                 )
             })
             .collect_vec();
-        let resident_layer_2 = produce_delta_layer(&tenant, &tline, test_deltas2.clone(), &ctx)
-            .await
-            .unwrap();
+        let resident_layer_2 = produce_delta_layer(
+            &tenant,
+            &tline,
+            test_deltas2.clone(),
+            Default::default(),
+            &ctx,
+        )
+        .await
+        .unwrap();
         let test_deltas3 = (0..N)
             .map(|idx| {
                 (
@@ -496,9 +520,15 @@ fn get_key(id: u32) -> Key {
                 )
             })
             .collect_vec();
-        let resident_layer_3 = produce_delta_layer(&tenant, &tline, test_deltas3.clone(), &ctx)
-            .await
-            .unwrap();
+        let resident_layer_3 = produce_delta_layer(
+            &tenant,
+            &tline,
+            test_deltas3.clone(),
+            Default::default(),
+            &ctx,
+        )
+        .await
+        .unwrap();
         let mut merge_iter = MergeIterator::create(
             &[
                 resident_layer_1.get_as_delta(&ctx).await.unwrap(),
@@ -569,18 +599,30 @@ fn get_key(id: u32) -> Key {
                 Value::WalRecord(NeonWalRecord::wal_append("b")),
             ),
         ];
-        let resident_layer_1 = produce_delta_layer(&tenant, &tline, test_deltas1.clone(), &ctx)
-            .await
-            .unwrap();
+        let resident_layer_1 = produce_delta_layer(
+            &tenant,
+            &tline,
+            test_deltas1.clone(),
+            Default::default(),
+            &ctx,
+        )
+        .await
+        .unwrap();
         let mut test_deltas2 = test_deltas1.clone();
         test_deltas2.push((
             get_key(10),
             Lsn(0x20),
             Value::Image(Bytes::copy_from_slice(b"test")),
         ));
-        let resident_layer_2 = produce_delta_layer(&tenant, &tline, test_deltas2.clone(), &ctx)
-            .await
-            .unwrap();
+        let resident_layer_2 = produce_delta_layer(
+            &tenant,
+            &tline,
+            test_deltas2.clone(),
+            Default::default(),
+            &ctx,
+        )
+        .await
+        .unwrap();
         let test_deltas3 = vec![
             (
                 get_key(0),
@@ -598,18 +640,30 @@ fn get_key(id: u32) -> Key {
                 Value::Image(Bytes::copy_from_slice(b"test")),
             ),
         ];
-        let resident_layer_3 = produce_delta_layer(&tenant, &tline, test_deltas3.clone(), &ctx)
-            .await
-            .unwrap();
+        let resident_layer_3 = produce_delta_layer(
+            &tenant,
+            &tline,
+            test_deltas3.clone(),
+            Default::default(),
+            &ctx,
+        )
+        .await
+        .unwrap();
         let mut test_deltas4 = test_deltas3.clone();
         test_deltas4.push((
             get_key(20),
             Lsn(0x20),
             Value::Image(Bytes::copy_from_slice(b"test")),
         ));
-        let resident_layer_4 = produce_delta_layer(&tenant, &tline, test_deltas4.clone(), &ctx)
-            .await
-            .unwrap();
+        let resident_layer_4 = produce_delta_layer(
+            &tenant,
+            &tline,
+            test_deltas4.clone(),
+            Default::default(),
+            &ctx,
+        )
+        .await
+        .unwrap();
         let mut expect = Vec::new();
         expect.extend(test_deltas1);
         expect.extend(test_deltas2);
@@ -77,6 +77,8 @@ pub(crate) enum BackgroundLoopKind {
     Compaction,
     Gc,
     Eviction,
+    DiskQuota,
+    Scrub,
     TenantHouseKeeping,
     ConsumptionMetricsCollectMetrics,
     ConsumptionMetricsSyntheticSizeWorker,
@@ -116,7 +118,8 @@ pub(crate) async fn acquire_concurrency_permit(
     }
 }
 
-/// Start per tenant background loops: compaction, GC, and ingest housekeeping.
+/// Start per tenant background loops: compaction, GC, ingest housekeeping, disk quota
+/// enforcement, and layer file scrubbing.
 pub fn start_background_loops(tenant: &Arc<Tenant>, can_start: Option<&Barrier>) {
     let tenant_shard_id = tenant.tenant_shard_id;
 
@@ -195,6 +198,56 @@ pub fn start_background_loops(tenant: &Arc<Tenant>, can_start: Option<&Barrier>)
             }
         },
     );
+
+    task_mgr::spawn(
+        BACKGROUND_RUNTIME.handle(),
+        TaskKind::TenantDiskQuota,
+        tenant_shard_id,
+        None,
+        &format!("disk quota for tenant {tenant_shard_id}"),
+        {
+            let tenant = Arc::clone(tenant);
+            let can_start = can_start.cloned();
+            async move {
+                let cancel = task_mgr::shutdown_token(); // NB: must be in async context
+                tokio::select! {
+                    _ = cancel.cancelled() => return Ok(()),
+                    _ = Barrier::maybe_wait(can_start) => {}
+                };
+                TENANT_TASK_EVENTS.with_label_values(&["start"]).inc();
+                defer!(TENANT_TASK_EVENTS.with_label_values(&["stop"]).inc());
+                disk_quota_loop(tenant, cancel)
+                    .instrument(info_span!("disk_quota_loop", tenant_id = %tenant_shard_id.tenant_id, shard_id = %tenant_shard_id.shard_slug()))
+                    .await;
+                Ok(())
+            }
+        },
+    );
+
+    task_mgr::spawn(
+        BACKGROUND_RUNTIME.handle(),
+        TaskKind::TenantScrub,
+        tenant_shard_id,
+        None,
+        &format!("scrubber for tenant {tenant_shard_id}"),
+        {
+            let tenant = Arc::clone(tenant);
+            let can_start = can_start.cloned();
+            async move {
+                let cancel = task_mgr::shutdown_token(); // NB: must be in async context
+                tokio::select! {
+                    _ = cancel.cancelled() => return Ok(()),
+                    _ = Barrier::maybe_wait(can_start) => {}
+                };
+                TENANT_TASK_EVENTS.with_label_values(&["start"]).inc();
+                defer!(TENANT_TASK_EVENTS.with_label_values(&["stop"]).inc());
+                scrub_loop(tenant, cancel)
+                    .instrument(info_span!("scrub_loop", tenant_id = %tenant_shard_id.tenant_id, shard_id = %tenant_shard_id.shard_slug()))
+                    .await;
+                Ok(())
+            }
+        },
+    );
 }
 
 /// Compaction task's main loop.
@@ -427,6 +480,7 @@ async fn gc_loop(tenant: Arc<Tenant>, cancel: CancellationToken) {
 /// Tenant housekeeping's main loop.
 async fn tenant_housekeeping_loop(tenant: Arc<Tenant>, cancel: CancellationToken) {
     let mut last_throttle_flag_reset_at = Instant::now();
+    let mut last_ingest_throttle_flag_reset_at = Instant::now();
     loop {
         if wait_for_active_tenant(&tenant, &cancel).await.is_break() {
             return;
@@ -471,6 +525,93 @@ async fn tenant_housekeeping_loop(tenant: Arc<Tenant>, cancel: CancellationToken
                 "shard was throttled in the last n_seconds"
             );
         });
+
+        // Log any WAL ingest throttling.
+        info_span!(parent: None, "wal_ingest_throttle", tenant_id=%tenant.tenant_shard_id, shard_id=%tenant.tenant_shard_id.shard_slug()).in_scope(|| {
+            let now = Instant::now();
+            let prev = std::mem::replace(&mut last_ingest_throttle_flag_reset_at, now);
+            let Stats { count_accounted_start: _, count_accounted_finish, count_throttled, sum_throttled_usecs} = tenant.wal_ingest_throttle.reset_stats();
+            if count_throttled == 0 {
+                return;
+            }
+            let allowed_bytes_per_second = tenant.wal_ingest_throttle.steady_rps();
+            let delta = now - prev;
+            info!(
+                n_seconds=%format_args!("{:.3}", delta.as_secs_f64()),
+                count_accounted = count_accounted_finish,
+                count_throttled,
+                sum_throttled_usecs,
+                allowed_bytes_per_second=%format_args!("{allowed_bytes_per_second:.0}"),
+                "shard's WAL ingest was throttled in the last n_seconds"
+            );
+        });
+    }
+}
+
+/// Disk quota task's main loop. Runs whenever layers of the tenant became resident or are waiting
+/// for room, and periodically to pick up changes of `max_resident_size`.
+async fn disk_quota_loop(tenant: Arc<Tenant>, cancel: CancellationToken) {
+    const RECHECK_PERIOD: Duration = Duration::from_secs(10);
+
+    loop {
+        if wait_for_active_tenant(&tenant, &cancel).await.is_break() {
+            return;
+        }
+
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = tenant.disk_quota.evict.notified() => {},
+            _ = tokio::time::sleep(RECHECK_PERIOD) => {},
+        }
+
+        let iteration = Iteration {
+            started_at: Instant::now(),
+            period: RECHECK_PERIOD,
+            kind: BackgroundLoopKind::DiskQuota,
+        };
+        iteration.run(tenant.enforce_disk_quota(&cancel)).await;
+    }
+}
+
+/// Scrub task's main loop. Each iteration reads all resident layer files of the tenant, at the
+/// rate configured by `scrub_bytes_per_second`, and then sleeps for `scrub_period`.
+async fn scrub_loop(tenant: Arc<Tenant>, cancel: CancellationToken) {
+    let ctx = RequestContext::todo_child(TaskKind::TenantScrub, DownloadBehavior::Error);
+    let mut first = true;
+
+    loop {
+        if wait_for_active_tenant(&tenant, &cancel).await.is_break() {
+            return;
+        }
+
+        let period = tenant.get_scrub_period();
+
+        if first {
+            first = false;
+            if sleep_random(period, &cancel).await.is_err() {
+                break;
+            }
+        }
+
+        let sleep_duration = if period == Duration::ZERO {
+            // check again in 10 seconds, in case it's been enabled again.
+            Duration::from_secs(10)
+        } else {
+            let iteration = Iteration {
+                started_at: Instant::now(),
+                period,
+                kind: BackgroundLoopKind::Scrub,
+            };
+            iteration.run(tenant.scrub_layers(&cancel, &ctx)).await;
+            period
+        };
+
+        if sleep_jitter(sleep_duration, sleep_duration * 5 / 100, &cancel)
+            .await
+            .is_err()
+        {
+            break;
+        }
     }
 }
 
@@ -1,8 +1,10 @@
+use std::num::NonZeroU32;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use arc_swap::ArcSwap;
+use pageserver_api::models::{ThrottleConfig, WalIngestThrottleConfig};
 use utils::leaky_bucket::{LeakyBucketConfig, RateLimiter};
 
 /// Throttle for `async` functions.
@@ -29,7 +31,52 @@ pub struct Inner {
     rate_limiter: Arc<RateLimiter>,
 }
 
-pub type Config = pageserver_api::models::ThrottleConfig;
+/// The settings of a [`Throttle`], from a [`ThrottleConfig`] or a [`WalIngestThrottleConfig`].
+pub struct Config {
+    pub enabled: bool,
+    pub initial: u32,
+    pub refill_interval: Duration,
+    pub refill_amount: NonZeroU32,
+    pub max: u32,
+}
+
+impl From<ThrottleConfig> for Config {
+    fn from(config: ThrottleConfig) -> Self {
+        let ThrottleConfig {
+            enabled,
+            initial,
+            refill_interval,
+            refill_amount,
+            max,
+        } = config;
+        Config {
+            enabled: enabled.is_enabled(),
+            initial,
+            refill_interval,
+            refill_amount,
+            max,
+        }
+    }
+}
+
+impl From<WalIngestThrottleConfig> for Config {
+    fn from(config: WalIngestThrottleConfig) -> Self {
+        let WalIngestThrottleConfig {
+            enabled,
+            initial,
+            refill_interval,
+            refill_amount,
+            max,
+        } = config;
+        Config {
+            enabled,
+            initial,
+            refill_interval,
+            refill_amount,
+            max,
+        }
+    }
+}
 
 /// See [`Throttle::reset_stats`].
 pub struct Stats {
@@ -50,9 +97,9 @@ pub enum ThrottleResult {
 }
 
 impl Throttle {
-    pub fn new(config: Config) -> Self {
+    pub fn new(config: impl Into<Config>) -> Self {
         Self {
-            inner: ArcSwap::new(Arc::new(Self::new_inner(config))),
+            inner: ArcSwap::new(Arc::new(Self::new_inner(config.into()))),
             count_accounted_start: AtomicU64::new(0),
             count_accounted_finish: AtomicU64::new(0),
             count_throttled: AtomicU64::new(0),
@@ -80,12 +127,12 @@ fn new_inner(config: Config) -> Inner {
         let rate_limiter = RateLimiter::with_initial_tokens(config, f64::from(initial_tokens));
 
         Inner {
-            enabled: enabled.is_enabled(),
+            enabled,
             rate_limiter: Arc::new(rate_limiter),
         }
     }
-    pub fn reconfigure(&self, config: Config) {
-        self.inner.store(Arc::new(Self::new_inner(config)));
+    pub fn reconfigure(&self, config: impl Into<Config>) {
+        self.inner.store(Arc::new(Self::new_inner(config.into())));
     }
 
     /// The [`Throttle`] keeps an internal flag that is true if there was ever any actual throttling.
@@ -104,7 +151,7 @@ pub fn reset_stats(&self) -> Stats {
         }
     }
 
-    /// See [`Config::steady_rps`].
+    /// See [`ThrottleConfig::steady_rps`].
     pub fn steady_rps(&self) -> f64 {
         self.inner.load().rate_limiter.steady_rps()
     }
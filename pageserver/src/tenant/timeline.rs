@@ -1,8 +1,10 @@
 pub(crate) mod analysis;
 pub(crate) mod compaction;
+pub(crate) mod cross_tenant_branch;
 pub mod delete;
 pub(crate) mod detach_ancestor;
 mod eviction_task;
+mod export_pgdata;
 pub(crate) mod handle;
 mod heatmap_layers_downloader;
 pub(crate) mod import_pgdata;
@@ -10,6 +12,8 @@
 pub mod layer_manager;
 pub(crate) mod logical_size;
 pub mod offload;
+pub(crate) mod reset_to_lsn;
+mod retention;
 pub mod span;
 pub mod uninit;
 mod walreceiver;
@@ -47,8 +51,8 @@
 use pageserver_api::models::{
     CompactKeyRange, CompactLsnRange, CompactionAlgorithm, CompactionAlgorithmSettings,
     DetachBehavior, DownloadRemoteLayersTaskInfo, DownloadRemoteLayersTaskSpawnRequest,
-    EvictionPolicy, InMemoryLayerInfo, LayerMapInfo, LsnLease, PageTraceEvent, RelSizeMigration,
-    TimelineState,
+    EvictionPolicy, ImageCompressionAlgorithm, InMemoryLayerInfo, LayerMapInfo, LsnLease,
+    PageTraceEvent, RelSizeMigration, TimelineExportPgdataTaskInfo, TimelineState,
 };
 use pageserver_api::reltag::{BlockNumber, RelTag};
 use pageserver_api::shard::{ShardIdentity, ShardIndex, ShardNumber, TenantShardId};
@@ -85,6 +89,7 @@
 use self::layer_manager::LayerManager;
 use self::logical_size::LogicalSize;
 use self::walreceiver::{WalReceiver, WalReceiverConf};
+use super::disk_quota::{DiskQuota, DiskQuotaError, DiskQuotaReservation};
 use super::remote_timeline_client::RemoteTimelineClient;
 use super::remote_timeline_client::index::{GcCompactionState, IndexPart};
 use super::secondary::heatmap::HeatMapLayer;
@@ -96,6 +101,7 @@
     debug_assert_current_span_has_tenant_and_timeline_id,
 };
 use crate::aux_file::AuxFileSizeEstimator;
+use crate::basebackup_cache;
 use crate::config::PageServerConf;
 use crate::context::{
     DownloadBehavior, PerfInstrumentFutureExt, RequestContext, RequestContextBuilder,
@@ -103,6 +109,7 @@
 use crate::disk_usage_eviction_task::{DiskUsageEvictionInfo, EvictionCandidate, finite_f32};
 use crate::keyspace::{KeyPartitioning, KeySpace};
 use crate::l0_flush::{self, L0FlushGlobalState};
+use crate::materialized_page_cache::{self, TimelineCacheId};
 use crate::metrics::{
     DELTAS_PER_READ_GLOBAL, LAYERS_PER_READ_AMORTIZED_GLOBAL, LAYERS_PER_READ_BATCH_GLOBAL,
     LAYERS_PER_READ_GLOBAL, ScanLatencyOngoingRecording, TimelineMetrics,
@@ -192,7 +199,10 @@ pub struct TimelineResources {
     pub remote_client: RemoteTimelineClient,
     pub pagestream_throttle: Arc<crate::tenant::throttle::Throttle>,
     pub pagestream_throttle_metrics: Arc<crate::metrics::tenant_throttling::Pagestream>,
+    pub wal_ingest_throttle: Arc<crate::tenant::throttle::Throttle>,
+    pub wal_ingest_throttle_metrics: Arc<crate::metrics::tenant_throttling::WalIngest>,
     pub l0_compaction_trigger: Arc<Notify>,
+    pub disk_quota: Arc<DiskQuota>,
     pub l0_flush_global_state: l0_flush::L0FlushGlobalState,
 }
 
@@ -256,6 +266,9 @@ pub struct Timeline {
     // WAL redo manager. `None` only for broken tenants.
     walredo_mgr: Option<Arc<super::WalRedoManager>>,
 
+    /// Scopes this timeline's entries in the [`materialized_page_cache`].
+    materialized_page_cache_id: TimelineCacheId,
+
     /// Remote storage client.
     /// See [`remote_timeline_client`](super::remote_timeline_client) module comment for details.
     pub(crate) remote_client: Arc<RemoteTimelineClient>,
@@ -368,6 +381,9 @@ pub struct Timeline {
 
     download_all_remote_layers_task_info: RwLock<Option<DownloadRemoteLayersTaskInfo>>,
 
+    /// See [`export_pgdata`].
+    export_pgdata_task_info: RwLock<Option<TimelineExportPgdataTaskInfo>>,
+
     state: watch::Sender<TimelineState>,
 
     /// Prevent two tasks from deleting the timeline at the same time. If held, the
@@ -402,6 +418,9 @@ pub struct Timeline {
     /// Notifies the tenant compaction loop that there is pending L0 compaction work.
     l0_compaction_trigger: Arc<Notify>,
 
+    /// Resident layer bytes of the tenant shard, checked against `max_resident_size`.
+    pub(crate) disk_quota: Arc<DiskQuota>,
+
     /// Make sure we only have one running gc at a time.
     ///
     /// Must only be taken in two places:
@@ -414,6 +433,11 @@ pub struct Timeline {
     /// Cloned from [`super::Tenant::pagestream_throttle`] on construction.
     pub(crate) pagestream_throttle: Arc<crate::tenant::throttle::Throttle>,
 
+    /// Cloned from [`super::Tenant::wal_ingest_throttle`] on construction.
+    pub(crate) wal_ingest_throttle: Arc<crate::tenant::throttle::Throttle>,
+
+    pub(crate) wal_ingest_throttle_metrics: Arc<crate::metrics::tenant_throttling::WalIngest>,
+
     /// Size estimator for aux file v2
     pub(crate) aux_file_size_estimator: AuxFileSizeEstimator,
 
@@ -485,6 +509,9 @@ pub(crate) struct GcInfo {
 
     /// Whether our branch point is within our ancestor's PITR interval (for cost estimation)
     pub(crate) within_ancestor_pitr: bool,
+
+    /// LSNs of the restore points kept by the timeline's retention policy, in ascending order.
+    pub(crate) restore_points: Vec<Lsn>,
 }
 
 impl GcInfo {
@@ -531,6 +558,11 @@ pub(super) fn remove_child_offloaded(&mut self, child_id: TimelineId) -> bool {
     pub(crate) fn lsn_covered_by_lease(&self, lsn: Lsn) -> bool {
         self.leases.contains_key(&lsn)
     }
+
+    /// Whether `lsn` can be read even if it is below the GC cutoff.
+    pub(crate) fn lsn_covered_by_lease_or_restore_point(&self, lsn: Lsn) -> bool {
+        self.lsn_covered_by_lease(lsn) || self.restore_points.binary_search(&lsn).is_ok()
+    }
 }
 
 /// The `GcInfo` component describing which Lsns need to be retained.  Functionally, this
@@ -585,6 +617,12 @@ pub(crate) enum PageReconstructError {
 
     #[error("{0}")]
     MissingKey(MissingKeyError),
+
+    /// A layer file failed checksum verification. The layer has been evicted, so that it is
+    /// downloaded again on the next access, unless the downloaded copy was corrupt too: then the
+    /// layer is broken and its reads fail right away.
+    #[error(transparent)]
+    Corruption(anyhow::Error),
 }
 
 impl From<anyhow::Error> for PageReconstructError {
@@ -761,6 +799,15 @@ fn from(_: layer_manager::Shutdown) -> Self {
     }
 }
 
+impl From<DiskQuotaError> for CreateImageLayersError {
+    fn from(e: DiskQuotaError) -> Self {
+        match e {
+            DiskQuotaError::Cancelled => CreateImageLayersError::Cancelled,
+            DiskQuotaError::Exceeded => CreateImageLayersError::Other(anyhow::anyhow!(e)),
+        }
+    }
+}
+
 #[derive(thiserror::Error, Debug, Clone)]
 pub(crate) enum FlushLayerError {
     /// Timeline cancellation token was cancelled
@@ -821,6 +868,10 @@ pub(crate) enum GetVectoredError {
     #[error("ancestry walk")]
     GetReadyAncestorError(#[source] GetReadyAncestorError),
 
+    /// A layer on the read path is broken: its remote copy failed checksum verification.
+    #[error(transparent)]
+    Corruption(anyhow::Error),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -992,6 +1043,7 @@ fn from(e: GetVectoredError) -> Self {
             err @ GetVectoredError::Oversized(_) => PageReconstructError::Other(err.into()),
             GetVectoredError::MissingKey(err) => PageReconstructError::MissingKey(err),
             GetVectoredError::GetReadyAncestorError(err) => PageReconstructError::from(err),
+            GetVectoredError::Corruption(err) => PageReconstructError::Corruption(err),
             GetVectoredError::Other(err) => PageReconstructError::Other(err),
         }
     }
@@ -1019,6 +1071,7 @@ pub(crate) enum WaitLsnWaiter<'a> {
     Tenant,
     PageService,
     HttpEndpoint,
+    BasebackupCache,
 }
 
 /// Argument to [`Timeline::shutdown`].
@@ -1280,11 +1333,43 @@ pub(crate) async fn scan(
 
     pub(super) async fn get_vectored_impl(
         &self,
-        keyspace: KeySpace,
+        mut keyspace: KeySpace,
         lsn: Lsn,
         reconstruct_state: &mut ValuesReconstructState,
         ctx: &RequestContext,
     ) -> Result<BTreeMap<Key, Result<Bytes, PageReconstructError>>, GetVectoredError> {
+        // Relation pages of small reads go through the materialized page cache. Images are
+        // only inserted once all WAL up to their LSN is ingested, see the module docs.
+        let page_cache = materialized_page_cache::get();
+        let use_page_cache = page_cache.is_enabled()
+            && keyspace.total_raw_size() <= Self::MAX_GET_VECTORED_KEYS as usize;
+        let insert_into_page_cache = use_page_cache && lsn <= self.get_last_record_lsn();
+        let mut cached_results = BTreeMap::new();
+        if use_page_cache {
+            for range in keyspace.clone().ranges {
+                let mut key = range.start;
+                while key != range.end {
+                    if key.is_rel_block_key() {
+                        match page_cache.lookup(self.materialized_page_cache_id, key, lsn) {
+                            Some((cached_lsn, img)) if cached_lsn == lsn => {
+                                keyspace
+                                    .remove_overlapping_with(&KeySpace::single(key..key.next()));
+                                cached_results.insert(key, Ok(img));
+                            }
+                            Some((cached_lsn, img)) => {
+                                reconstruct_state.set_cached_image(key, cached_lsn, img)
+                            }
+                            None => {}
+                        }
+                    }
+                    key = key.next();
+                }
+            }
+            if keyspace.is_empty() {
+                return Ok(cached_results);
+            }
+        }
+
         let read_path = if self.conf.enable_read_path_debugging || ctx.read_path_debug() {
             Some(ReadPath::new(keyspace.clone(), lsn))
         } else {
@@ -1335,6 +1420,7 @@ pub(super) async fn get_vectored_impl(
         for (key, state) in std::mem::take(&mut reconstruct_state.keys) {
             futs.push({
                 let walredo_self = self.myself.upgrade().expect("&self method holds the arc");
+                let insert_into_page_cache = insert_into_page_cache && key.is_rel_block_key();
                 let ctx = RequestContextBuilder::from(&ctx)
                     .perf_span(|crnt_perf_span| {
                         info_span!(
@@ -1378,6 +1464,7 @@ pub(super) async fn get_vectored_impl(
                     );
 
                     let walredo_deltas = converted.num_deltas();
+                    let redo_performed = !converted.records.is_empty();
                     let walredo_res = walredo_self
                         .reconstruct_value(key, lsn, converted)
                         .maybe_perf_instrument(&ctx, |crnt_perf_span| {
@@ -1390,12 +1477,23 @@ pub(super) async fn get_vectored_impl(
                         })
                         .await;
 
+                    if let Ok(img) = &walredo_res {
+                        if insert_into_page_cache && redo_performed {
+                            materialized_page_cache::get().insert(
+                                walredo_self.materialized_page_cache_id,
+                                key,
+                                lsn,
+                                img.clone(),
+                            );
+                        }
+                    }
+
                     (key, walredo_res)
                 }
             });
         }
 
-        let results = futs
+        let mut results = futs
             .collect::<BTreeMap<Key, Result<Bytes, PageReconstructError>>>()
             .maybe_perf_instrument(&ctx, |crnt_perf_span| crnt_perf_span.clone())
             .await;
@@ -1439,6 +1537,8 @@ pub(super) async fn get_vectored_impl(
             }
         }
 
+        results.append(&mut cached_results);
+
         Ok(results)
     }
 
@@ -1527,7 +1627,8 @@ pub(crate) async fn wait_lsn(
                         }
                         WaitLsnWaiter::Tenant
                         | WaitLsnWaiter::PageService
-                        | WaitLsnWaiter::HttpEndpoint => unreachable!(
+                        | WaitLsnWaiter::HttpEndpoint
+                        | WaitLsnWaiter::BasebackupCache => unreachable!(
                             "tenant or page_service context are not expected to have task kind {:?}",
                             ctx.task_kind()
                         ),
@@ -2227,6 +2328,14 @@ pub(crate) async fn shutdown(&self, mode: ShutdownMode) {
         // and use a TBD variant of shutdown_tasks that asserts that there were no tasks left.
         self.gate.close().await;
 
+        // No reads are left that could insert into the cache.
+        materialized_page_cache::get().invalidate_timeline(self.materialized_page_cache_id);
+
+        // No basebackup preparation is in flight: it holds the gate.
+        if let Some(basebackup_cache) = basebackup_cache::get() {
+            basebackup_cache.remove_timeline(self.tenant_shard_id, self.timeline_id);
+        }
+
         self.metrics.shutdown();
     }
 
@@ -2680,6 +2789,30 @@ fn get_compaction_algorithm_settings(&self) -> CompactionAlgorithmSettings {
             .clone()
     }
 
+    pub(crate) fn get_max_resident_size(&self) -> Option<u64> {
+        let tenant_conf = self.tenant_conf.load();
+        tenant_conf
+            .tenant_conf
+            .max_resident_size
+            .or(self.conf.default_tenant_conf.max_resident_size)
+    }
+
+    /// Reserves room for newly written layer files in the tenant shard's disk quota. Hold the
+    /// reservation until the layers have been added to the layer map, which accounts for them in
+    /// the resident size.
+    pub(crate) async fn reserve_new_layers<'a>(
+        &self,
+        layers: impl IntoIterator<Item = &'a ResidentLayer>,
+    ) -> Result<DiskQuotaReservation, DiskQuotaError> {
+        let size = layers
+            .into_iter()
+            .map(|layer| layer.layer_desc().file_size)
+            .sum();
+        self.disk_quota
+            .reserve(size, self.get_max_resident_size(), &self.cancel)
+            .await
+    }
+
     fn get_eviction_policy(&self) -> EvictionPolicy {
         let tenant_conf = self.tenant_conf.load();
         tenant_conf
@@ -2746,6 +2879,14 @@ fn get_image_creation_preempt_threshold(&self) -> usize {
             )
     }
 
+    pub(crate) fn get_delta_compression(&self) -> ImageCompressionAlgorithm {
+        let tenant_conf = self.tenant_conf.load();
+        tenant_conf
+            .tenant_conf
+            .delta_compression
+            .unwrap_or(self.conf.default_tenant_conf.delta_compression)
+    }
+
     /// Resolve the effective WAL receiver protocol to use for this tenant.
     ///
     /// Priority order is:
@@ -2845,6 +2986,7 @@ pub(super) fn new(
                     "mtime",
                     evictions_low_residence_duration_metric_threshold,
                 ),
+                resources.disk_quota.clone(),
             ));
             let aux_file_metrics = metrics.aux_file_size_gauge.clone();
 
@@ -2861,6 +3003,7 @@ pub(super) fn new(
                 gc_compaction_layer_update_lock: tokio::sync::RwLock::new(()),
 
                 walredo_mgr,
+                materialized_page_cache_id: materialized_page_cache::next_timeline_cache_id(),
                 walreceiver: Mutex::new(None),
 
                 remote_client: Arc::new(resources.remote_client),
@@ -2934,6 +3077,7 @@ pub(super) fn new(
                 }),
 
                 download_all_remote_layers_task_info: RwLock::new(None),
+                export_pgdata_task_info: RwLock::new(None),
 
                 state,
 
@@ -2948,12 +3092,16 @@ pub(super) fn new(
                 compaction_lock: tokio::sync::Mutex::default(),
                 compaction_failed: AtomicBool::default(),
                 l0_compaction_trigger: resources.l0_compaction_trigger,
+                disk_quota: resources.disk_quota,
                 gc_lock: tokio::sync::Mutex::default(),
 
                 standby_horizon: AtomicLsn::new(0),
 
                 pagestream_throttle: resources.pagestream_throttle,
 
+                wal_ingest_throttle: resources.wal_ingest_throttle,
+                wal_ingest_throttle_metrics: resources.wal_ingest_throttle_metrics,
+
                 aux_file_size_estimator: AuxFileSizeEstimator::new(aux_file_metrics),
 
                 #[cfg(test)]
@@ -4269,6 +4417,14 @@ pub(crate) fn get_shard_identity(&self) -> &ShardIdentity {
         &self.shard_identity
     }
 
+    /// Ask the [`basebackup_cache`] to prepare basebackups at `lsn` in the background, if it
+    /// is enabled.
+    pub(crate) fn prepare_basebackup(&self, lsn: Lsn) {
+        if let Some(basebackup_cache) = basebackup_cache::get() {
+            basebackup_cache.prepare(self.myself.clone(), lsn);
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn shard_timeline_id(&self) -> ShardTimelineId {
         ShardTimelineId {
@@ -4692,6 +4848,28 @@ async fn flush_frozen_layer(
             return Err(FlushLayerError::Cancelled);
         }
 
+        // Make room for the new delta layer before it becomes resident; image layers reserved
+        // their own. A flush is never refused: waiting for eviction stalls the flush loop, and
+        // with it ingest.
+        let _reservation = match &delta_layer_to_add {
+            Some(layer) => Some(
+                self.disk_quota
+                    .reserve_without_timeout(
+                        layer.layer_desc().file_size,
+                        self.get_max_resident_size(),
+                        &self.cancel,
+                    )
+                    .await
+                    .map_err(|e| match e {
+                        DiskQuotaError::Cancelled => FlushLayerError::Cancelled,
+                        DiskQuotaError::Exceeded => {
+                            FlushLayerError::from_anyhow(self, anyhow::anyhow!(e))
+                        }
+                    })?,
+            ),
+            None => None,
+        };
+
         let disk_consistent_lsn = Lsn(lsn_range.end.0 - 1);
 
         // The new on-disk layers are now in the layer map. We can remove the
@@ -4808,7 +4986,12 @@ async fn create_delta_layer(
         let ctx = ctx.attached_child();
         let work = async move {
             let Some((desc, path)) = frozen_layer
-                .write_to_disk(&ctx, key_range, self_clone.l0_flush_global_state.inner())
+                .write_to_disk(
+                    &ctx,
+                    key_range,
+                    self_clone.l0_flush_global_state.inner(),
+                    self_clone.get_delta_compression(),
+                )
                 .await?
             else {
                 return Ok(None);
@@ -5445,6 +5628,8 @@ async fn create_image_layers(
 
         let image_layers = batch_image_writer.finish(self, ctx).await?;
 
+        let _reservation = self.reserve_new_layers(&image_layers).await?;
+
         let mut guard = self.layers.write().await;
 
         // FIXME: we could add the images to be uploaded *before* returning from here, but right
@@ -5601,6 +5786,31 @@ pub(crate) async fn complete_detaching_timeline_ancestor(
     ) -> Result<(), detach_ancestor::Error> {
         detach_ancestor::complete(self, tenant, attempt, ctx).await
     }
+
+    /// Checks that the timeline could be reset to `lsn`, without changing anything.
+    pub(crate) fn check_reset_to_lsn(
+        self: &Arc<Timeline>,
+        tenant: &crate::tenant::Tenant,
+        lsn: Lsn,
+    ) -> Result<(), reset_to_lsn::Error> {
+        reset_to_lsn::check(self, tenant, lsn)
+    }
+
+    /// Discard everything the timeline ingested after `lsn`, which must be within the PITR
+    /// window, not before any branch of the timeline and not before any lease.
+    ///
+    /// This method is to be called while holding the TenantManager's tenant slot. After it
+    /// returns successfully, or with [`reset_to_lsn::Error::Publish`], the tenant must be
+    /// reloaded in `generation`.
+    pub(crate) async fn reset_to_lsn(
+        self: &Arc<Timeline>,
+        tenant: &crate::tenant::Tenant,
+        lsn: Lsn,
+        generation: Generation,
+        ctx: &RequestContext,
+    ) -> Result<(), reset_to_lsn::Error> {
+        reset_to_lsn::reset_to_lsn(self, tenant, lsn, generation, ctx).await
+    }
 }
 
 impl Drop for Timeline {
@@ -5661,7 +5871,9 @@ pub fn is_critical(&self) -> bool {
             Self::CollectKeySpaceError(
                 CollectKeySpaceError::Decode(_)
                     | CollectKeySpaceError::PageRead(
-                        PageReconstructError::MissingKey(_) | PageReconstructError::WalRedo(_),
+                        PageReconstructError::MissingKey(_)
+                            | PageReconstructError::WalRedo(_)
+                            | PageReconstructError::Corruption(_),
                     )
             )
         )
@@ -5700,7 +5912,8 @@ fn from(e: super::storage_layer::layer::DownloadError) -> Self {
             | super::storage_layer::layer::DownloadError::DownloadRequired
             | super::storage_layer::layer::DownloadError::NotFile(_)
             | super::storage_layer::layer::DownloadError::DownloadFailed
-            | super::storage_layer::layer::DownloadError::PreStatFailed(_) => {
+            | super::storage_layer::layer::DownloadError::PreStatFailed(_)
+            | super::storage_layer::layer::DownloadError::ResidentSizeQuotaExceeded => {
                 CompactionError::Other(anyhow::anyhow!(e))
             }
             #[cfg(test)]
@@ -5717,6 +5930,15 @@ fn from(_: layer_manager::Shutdown) -> Self {
     }
 }
 
+impl From<DiskQuotaError> for CompactionError {
+    fn from(e: DiskQuotaError) -> Self {
+        match e {
+            DiskQuotaError::Cancelled => CompactionError::ShuttingDown,
+            DiskQuotaError::Exceeded => CompactionError::Other(anyhow::anyhow!(e)),
+        }
+    }
+}
+
 #[serde_as]
 #[derive(serde::Serialize)]
 struct RecordedDuration(#[serde_as(as = "serde_with::DurationMicroSeconds")] Duration);
@@ -5806,6 +6028,10 @@ async fn finish_compact_batch(
         new_images: &[ResidentLayer],
         layers_to_remove: &[Layer],
     ) -> Result<(), CompactionError> {
+        let _reservation = self
+            .reserve_new_layers(new_deltas.iter().chain(new_images))
+            .await?;
+
         let mut guard = tokio::select! {
             guard = self.layers.write() => guard,
             _ = self.cancel.cancelled() => {
@@ -5866,6 +6092,10 @@ async fn rewrite_layers(
         mut replace_layers: Vec<(Layer, ResidentLayer)>,
         mut drop_layers: Vec<Layer>,
     ) -> Result<(), CompactionError> {
+        let _reservation = self
+            .reserve_new_layers(replace_layers.iter().map(|(_, new)| new))
+            .await?;
+
         let mut guard = self.layers.write().await;
 
         // Trim our lists in case our caller (compaction) raced with someone else (GC) removing layers: we want
@@ -6084,7 +6314,7 @@ pub(super) async fn gc(&self) -> Result<GcResult, GcError> {
             return Err(GcError::TimelineCancelled);
         }
 
-        let (space_cutoff, time_cutoff, retain_lsns, max_lsn_with_valid_lease) = {
+        let (space_cutoff, time_cutoff, retain_lsns, max_lsn_with_valid_lease, restore_points) = {
             let gc_info = self.gc_info.read().unwrap();
 
             let space_cutoff = min(gc_info.cutoffs.space, self.get_disk_consistent_lsn());
@@ -6106,6 +6336,7 @@ pub(super) async fn gc(&self) -> Result<GcResult, GcError> {
                 time_cutoff,
                 retain_lsns,
                 max_lsn_with_valid_lease,
+                gc_info.restore_points.clone(),
             )
         };
 
@@ -6142,6 +6373,7 @@ pub(super) async fn gc(&self) -> Result<GcResult, GcError> {
                 time_cutoff,
                 retain_lsns,
                 max_lsn_with_valid_lease,
+                restore_points,
                 new_gc_cutoff,
             )
             .instrument(
@@ -6161,6 +6393,7 @@ async fn gc_timeline(
         time_cutoff: Lsn,
         retain_lsns: Vec<Lsn>,
         max_lsn_with_valid_lease: Option<Lsn>,
+        restore_points: Vec<Lsn>,
         new_gc_cutoff: Lsn,
     ) -> Result<GcResult, GcError> {
         // FIXME: if there is an ongoing detach_from_ancestor, we should just skip gc
@@ -6210,7 +6443,8 @@ async fn gc_timeline(
         // 2. it is older than PITR interval;
         // 3. it doesn't need to be retained for 'retain_lsns';
         // 4. it does not need to be kept for LSNs holding valid leases.
-        // 5. newer on-disk image layers cover the layer's whole key range
+        // 5. it is not needed to read at a restore point of the retention policy;
+        // 6. newer on-disk image layers cover the layer's whole key range
         //
         // TODO holding a write lock is too agressive and avoidable
         let mut guard = self.layers.write().await;
@@ -6275,7 +6509,31 @@ async fn gc_timeline(
                 }
             }
 
-            // 5. Is there a later on-disk layer for this relation?
+            // 5. Is it needed to read at a restore point?
+            //
+            // Unlike for branch points, only the layers between a restore point and the image
+            // layers below it are needed: everything older can go.
+            for restore_point in &restore_points {
+                let lsn_range = l.get_lsn_range();
+                // start_lsn is inclusive, end_lsn is exclusive
+                if lsn_range.start <= *restore_point
+                    && (lsn_range.end > *restore_point
+                        || !layers.image_layer_exists(
+                            &l.get_key_range(),
+                            &(lsn_range.end..*restore_point + 1),
+                        ))
+                {
+                    info!(
+                        "keeping {} because it is needed to read at restore point {}",
+                        l.layer_name(),
+                        restore_point,
+                    );
+                    result.layers_needed_by_restore_points += 1;
+                    continue 'outer;
+                }
+            }
+
+            // 6. Is there a later on-disk layer for this relation?
             //
             // The end-LSN is exclusive, while disk_consistent_lsn is
             // inclusive. For example, if disk_consistent_lsn is 100, it is
@@ -6620,6 +6878,7 @@ pub(crate) async fn get_local_layers_for_disk_usage_eviction(&self) -> DiskUsage
                     layer: layer.to_owned().into(),
                     last_activity_ts,
                     relative_last_activity: finite_f32::FiniteF32::ZERO,
+                    eviction_priority: 0,
                     visibility: layer.visibility(),
                 }
             })
@@ -6755,6 +7014,7 @@ pub(super) async fn force_create_delta_layer(
             self.tenant_shard_id,
             deltas.key_range.start,
             deltas.lsn_range,
+            self.get_delta_compression(),
             ctx,
         )
         .await?;
@@ -1851,6 +1851,7 @@ fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
                                 debug!("Create new layer {}..{}", lsn_range.start, lsn_range.end);
                                 lsn_range.clone()
                             },
+                            self.get_delta_compression(),
                             ctx,
                         )
                         .await
@@ -2492,7 +2493,7 @@ async fn check_compaction_space(
 
     /// Get a watermark for gc-compaction, that is the lowest LSN that we can use as the `gc_horizon` for
     /// the compaction algorithm. It is min(space_cutoff, time_cutoff, latest_gc_cutoff, standby_horizon).
-    /// Leases and retain_lsns are considered in the gc-compaction job itself so we don't need to account for them
+    /// Leases, retain_lsns and restore points are considered in the gc-compaction job itself so we don't need to account for them
     /// here.
     pub(crate) fn get_gc_compaction_watermark(self: &Arc<Self>) -> Lsn {
         let gc_cutoff_lsn = {
@@ -2797,6 +2798,11 @@ async fn compact_with_gc_inner(
                     retain_lsns_below_horizon.push(*lsn);
                 }
             }
+            for lsn in &gc_info.restore_points {
+                if lsn < &gc_cutoff {
+                    retain_lsns_below_horizon.push(*lsn);
+                }
+            }
             let mut selected_layers: Vec<Layer> = Vec::new();
             drop(gc_info);
             // Firstly, pick all the layers intersect or below the gc_cutoff, get the largest LSN in the selected layers.
@@ -3059,6 +3065,7 @@ async fn compact_with_gc_inner(
             self.tenant_shard_id,
             lowest_retain_lsn..end_lsn,
             self.get_compaction_target_size(),
+            self.get_delta_compression(),
         )
         .await
         .context("failed to create delta layer writer")
@@ -3153,6 +3160,7 @@ async fn get_ancestor_image(
                                 self.tenant_shard_id,
                                 desc.key_range.start,
                                 desc.lsn_range.clone(),
+                                self.get_delta_compression(),
                                 ctx,
                             )
                             .await
@@ -3170,6 +3178,7 @@ async fn get_ancestor_image(
                                 self.tenant_shard_id,
                                 job_desc.compaction_key_range.end,
                                 desc.lsn_range.clone(),
+                                self.get_delta_compression(),
                                 ctx,
                             )
                             .await
@@ -3537,6 +3546,8 @@ async fn get_ancestor_image(
             // Therefore, the gc-compaction layer update operation should wait for all ongoing reads, block all pending reads,
             // and only allow reads to continue after the update is finished.
 
+            let _reservation = self.reserve_new_layers(&compact_to).await?;
+
             let update_guard = self.gc_compaction_layer_update_lock.write().await;
             // Acquiring the update guard ensures current read operations end and new read operations are blocked.
             // TODO: can we use `latest_gc_cutoff` Rcu to achieve the same effect?
@@ -3736,6 +3747,7 @@ async fn create_delta(
             self.timeline.tenant_shard_id,
             key_range.start,
             lsn_range.clone(),
+            self.timeline.get_delta_compression(),
             ctx,
         )
         .await?;
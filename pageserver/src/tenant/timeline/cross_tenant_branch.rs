@@ -0,0 +1,313 @@
+//! Branching a timeline off a timeline of another tenant.
+//!
+//! A regular branch reads the history before its branch point from its ancestor timeline. That
+//! does not work across tenants, so a cross-tenant branch is created as a root timeline instead,
+//! whose `index_part.json` references the layers of the ancestor up to the branch point by their
+//! remote path under the ancestor's prefix, see [`LayerFileMetadata::cross_tenant_owner`]. Only the
+//! delta layers straddling the branch point are rewritten into the new timeline.
+//!
+//! The ancestor records the branch and the layers it references in its own `index_part.json`, see
+//! [`CrossTenantChild`]. GC and compaction on the ancestor still unlink these layers, but only
+//! delete them from remote storage once the branch is gone. GC on the ancestor is also held back
+//! at the branch point by an LSN lease, which is renewed for as long as the branch exists.
+//!
+//! When the branch is deleted, it is unregistered from its ancestor right away if the ancestor is
+//! attached to the same pageserver. Otherwise, each GC iteration of the ancestor checks whether
+//! its branches still exist in remote storage, see [`refresh_children`].
+//!
+//! The ancestor's tenant shard must be attached to the same pageserver as the branch when it is
+//! created, with the same sharding.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+use utils::id::TenantTimelineId;
+use utils::lsn::Lsn;
+
+use super::{FlushLayerError, Timeline};
+use crate::context::RequestContext;
+use crate::tenant::remote_timeline_client::LayerFileMetadata;
+use crate::tenant::remote_timeline_client::index::{CrossTenantAncestor, CrossTenantChild};
+use crate::tenant::storage_layer::{AsLayerDesc as _, Layer};
+use crate::tenant::timeline::detach_ancestor;
+use crate::tenant::{CreateTimelineError, GcError};
+
+/// Locks GC on the `ancestor` of a new branch, which must be held until the branch is durable in
+/// remote storage, for [`refresh_children`] not to take it for a deleted one.
+pub(crate) async fn lock_ancestor_gc(
+    ancestor: &Timeline,
+) -> Result<tokio::sync::MutexGuard<'_, ()>, CreateTimelineError> {
+    tokio::select! {
+        guard = ancestor.gc_lock.lock() => Ok(guard),
+        _ = ancestor.cancel.cancelled() => Err(CreateTimelineError::ShuttingDown),
+    }
+}
+
+/// Fills the freshly created `timeline` with the layers of `ancestor` up to `lsn`.
+///
+/// The caller holds the guard of [`lock_ancestor_gc`].
+///
+/// See [`Tenant::create_timeline`](crate::tenant::Tenant::create_timeline).
+pub(crate) async fn adopt_ancestor_layers(
+    timeline: &Arc<Timeline>,
+    ancestor: &Arc<Timeline>,
+    lsn: Lsn,
+    _ancestor_gc: &tokio::sync::MutexGuard<'_, ()>,
+    ctx: &RequestContext,
+) -> Result<(), CreateTimelineError> {
+    // Everything up to the branch point must be in historic layers for us to reference.
+    if lsn > ancestor.get_disk_consistent_lsn() {
+        ancestor.freeze_and_flush().await.map_err(|e| match e {
+            FlushLayerError::Cancelled | FlushLayerError::NotRunning(_) => {
+                CreateTimelineError::ShuttingDown
+            }
+            FlushLayerError::CreateImageLayersError(_) | FlushLayerError::Other(_) => {
+                CreateTimelineError::Other(anyhow::anyhow!(e))
+            }
+        })?;
+    }
+
+    check_lsn(ancestor, lsn)?;
+
+    ancestor
+        .init_lsn_lease(lsn, lease_length(ancestor), ctx)
+        .map_err(CreateTimelineError::AncestorLsn)?;
+
+    let (referenced, straddling) = {
+        let layers = ancestor.layers.read().await;
+        let layer_map = layers
+            .layer_map()
+            .map_err(|_| CreateTimelineError::ShuttingDown)?;
+
+        let mut referenced = vec![];
+        let mut straddling = vec![];
+        for desc in layer_map.iter_historic_layers() {
+            // off by one chances here:
+            // - start is inclusive
+            // - end is exclusive
+            if desc.lsn_range.start > lsn {
+                continue;
+            }
+            let layer = layers.get_from_desc(&desc);
+            if desc.is_delta && desc.lsn_range.end > lsn + 1 {
+                straddling.push(layer);
+            } else {
+                referenced.push(layer);
+            }
+        }
+        (referenced, straddling)
+    };
+
+    for layer in &referenced {
+        if layer.metadata().cross_tenant_owner.is_some() {
+            return Err(CreateTimelineError::Other(anyhow::anyhow!(
+                "ancestor layer {layer} is itself referenced from another tenant"
+            )));
+        }
+    }
+
+    // Must be durable before anything references the layers of the ancestor.
+    ancestor
+        .remote_client
+        .schedule_adding_cross_tenant_child_and_wait(CrossTenantChild {
+            tenant_id: timeline.tenant_shard_id.tenant_id,
+            timeline_id: timeline.timeline_id,
+            lsn,
+            layers: referenced
+                .iter()
+                .map(|layer| (layer.layer_desc().layer_name(), layer.metadata()))
+                .collect(),
+        })
+        .await
+        .map_err(|e| {
+            if ancestor.cancel.is_cancelled() {
+                CreateTimelineError::ShuttingDown
+            } else {
+                CreateTimelineError::Other(e.context("register cross-tenant child"))
+            }
+        })?;
+
+    // The referenced layers must exist in remote storage.
+    ancestor
+        .remote_client
+        .wait_completion()
+        .await
+        .map_err(|_| CreateTimelineError::ShuttingDown)?;
+
+    tracing::info!(
+        referenced = referenced.len(),
+        to_rewrite = straddling.len(),
+        "collected ancestor layers"
+    );
+
+    let owner = TenantTimelineId::new(ancestor.tenant_shard_id.tenant_id, ancestor.timeline_id);
+    let mut adopted = Vec::with_capacity(referenced.len() + straddling.len());
+    for layer in &referenced {
+        adopted.push(Layer::for_evicted(
+            timeline.conf,
+            timeline,
+            layer.layer_desc().layer_name(),
+            LayerFileMetadata {
+                cross_tenant_owner: Some(owner),
+                ..layer.metadata()
+            },
+        ));
+    }
+
+    let mut rewritten = 0;
+    for layer in &straddling {
+        let copied = detach_ancestor::upload_rewritten_layer(
+            lsn + 1,
+            layer,
+            timeline,
+            &timeline.cancel,
+            ctx,
+        )
+        .await
+        .map_err(|e| match e {
+            detach_ancestor::Error::ShuttingDown => CreateTimelineError::ShuttingDown,
+            other => CreateTimelineError::Other(other.into()),
+        })?;
+        if let Some(copied) = copied {
+            tracing::info!(%layer, %copied, "rewrote and uploaded");
+            adopted.push(copied);
+            rewritten += 1;
+        }
+    }
+    if rewritten > 0 {
+        detach_ancestor::fsync_timeline_dir(timeline, ctx).await;
+    }
+
+    timeline
+        .layers
+        .write()
+        .await
+        .open_mut()
+        .map_err(|_| CreateTimelineError::ShuttingDown)?
+        .initialize_local_layers(adopted.clone(), lsn + 1);
+
+    timeline
+        .remote_client
+        .schedule_adding_cross_tenant_ancestor_layers(
+            CrossTenantAncestor {
+                tenant_id: owner.tenant_id,
+                timeline_id: owner.timeline_id,
+                lsn,
+            },
+            &adopted,
+        )
+        .map_err(CreateTimelineError::Other)?;
+
+    Ok(())
+}
+
+/// Same rules as for the start of a regular branch.
+fn check_lsn(ancestor: &Timeline, lsn: Lsn) -> Result<(), CreateTimelineError> {
+    let applied_gc_cutoff_lsn = ancestor.get_applied_gc_cutoff_lsn();
+    let gc_info = ancestor.gc_info.read().unwrap();
+    if gc_info.lsn_covered_by_lease(lsn) {
+        return Ok(());
+    }
+
+    let planned_cutoff = gc_info.min_cutoff();
+    let cutoff = std::cmp::max(*applied_gc_cutoff_lsn, planned_cutoff);
+    if lsn < cutoff {
+        return Err(CreateTimelineError::AncestorLsn(anyhow::anyhow!(
+            "invalid branch start lsn: less than GC cutoff {cutoff}"
+        )));
+    }
+    Ok(())
+}
+
+/// Branches hold back GC on their ancestor for as long as they exist: the lease is renewed by
+/// every GC iteration, see [`refresh_children`].
+fn lease_length(ancestor: &Timeline) -> Duration {
+    let tenant_conf = ancestor.tenant_conf.load();
+    let gc_period = tenant_conf
+        .tenant_conf
+        .gc_period
+        .unwrap_or(ancestor.conf.default_tenant_conf.gc_period);
+    ancestor.get_lsn_lease_length() + gc_period
+}
+
+/// Renews the LSN leases of the cross-tenant children of `timeline`, after unregistering the ones
+/// which no longer exist, see [`forget_deleted_children`].
+pub(crate) async fn refresh_children(
+    timeline: &Timeline,
+    cancel: &CancellationToken,
+    ctx: &RequestContext,
+) -> Result<(), GcError> {
+    let children = forget_deleted_children(timeline, cancel)
+        .await
+        .map_err(|e| {
+            if cancel.is_cancelled() || timeline.cancel.is_cancelled() {
+                GcError::TimelineCancelled
+            } else {
+                GcError::Remote(e)
+            }
+        })?;
+
+    for child in children {
+        if let Err(e) = timeline.renew_lsn_lease(child.lsn, lease_length(timeline), ctx) {
+            tracing::warn!(child = %child.id(), lsn = %child.lsn, "failed to renew lease of cross-tenant child: {e:#}");
+        }
+    }
+    Ok(())
+}
+
+/// Unregisters the cross-tenant children of `timeline` which no longer exist in remote storage,
+/// and returns the others.
+///
+/// This is how a branch is eventually unregistered if it was deleted while its ancestor was not
+/// attached to the same pageserver, or if its creation failed. It runs in every GC iteration, and
+/// before deleting the ancestor.
+pub(crate) async fn forget_deleted_children(
+    timeline: &Timeline,
+    cancel: &CancellationToken,
+) -> anyhow::Result<Vec<CrossTenantChild>> {
+    if timeline
+        .remote_client
+        .cross_tenant_children()
+        .is_none_or(|children| children.is_empty())
+    {
+        return Ok(Vec::new());
+    }
+
+    // Branches being created are registered before they exist, under this lock.
+    let _gc = tokio::select! {
+        guard = timeline.gc_lock.lock() => guard,
+        _ = timeline.cancel.cancelled() => anyhow::bail!("timeline shutting down"),
+    };
+    let children = timeline
+        .remote_client
+        .cross_tenant_children()
+        .unwrap_or_default();
+
+    let mut remaining = Vec::with_capacity(children.len());
+    for child in children {
+        let id = child.id();
+        let exists = match timeline
+            .remote_client
+            .cross_tenant_child_exists(id, cancel)
+            .await
+        {
+            Ok(exists) => exists,
+            Err(e) if cancel.is_cancelled() => return Err(e),
+            Err(e) => {
+                tracing::warn!(child = %id, "failed to check whether cross-tenant child exists: {e:#}");
+                true
+            }
+        };
+
+        if exists {
+            remaining.push(child);
+        } else {
+            tracing::info!(child = %id, "unregistering deleted cross-tenant child");
+            timeline
+                .remote_client
+                .schedule_removing_cross_tenant_child(id)?;
+        }
+    }
+    Ok(remaining)
+}
@@ -197,6 +197,20 @@ pub async fn run(
     ) -> Result<(), DeleteTimelineError> {
         super::debug_assert_current_span_has_tenant_and_timeline_id();
 
+        // Cross-tenant children deleted while we were not attached to the same pageserver may
+        // still be registered.
+        if let Ok(timeline) = tenant.get_timeline(timeline_id, false) {
+            super::cross_tenant_branch::forget_deleted_children(&timeline, &tenant.cancel)
+                .await
+                .map_err(|e| {
+                    if tenant.cancel.is_cancelled() {
+                        DeleteTimelineError::Cancelled
+                    } else {
+                        DeleteTimelineError::Other(e)
+                    }
+                })?;
+        }
+
         let (timeline, mut guard) =
             make_timeline_delete_guard(tenant, timeline_id, TimelineDeleteGuardKind::Delete)?;
 
@@ -473,6 +487,20 @@ pub(super) fn make_timeline_delete_guard(
         return Err(DeleteTimelineError::HasChildren(children));
     }
 
+    // Timelines of other tenants may read our layers from remote storage, which we are about to
+    // remove, or to stop tracking when offloading.
+    if let TimelineOrOffloaded::Timeline(timeline) = &timeline {
+        let cross_tenant_children = timeline
+            .remote_client
+            .cross_tenant_children()
+            .unwrap_or_default();
+        if !cross_tenant_children.is_empty() {
+            return Err(DeleteTimelineError::HasCrossTenantChildren(
+                cross_tenant_children.iter().map(|c| c.id()).collect(),
+            ));
+        }
+    }
+
     // Note that using try_lock here is important to avoid a deadlock.
     // Here we take lock on timelines and then the deletion guard.
     // At the end of the operation we're holding the guard and need to lock timelines map
@@ -739,7 +739,7 @@ fn partition_work(
     Ok((later_by_lsn, straddling_branchpoint, rest_of_historic))
 }
 
-async fn upload_rewritten_layer(
+pub(super) async fn upload_rewritten_layer(
     end_lsn: Lsn,
     layer: &Layer,
     target: &Arc<Timeline>,
@@ -779,6 +779,7 @@ async fn copy_lsn_prefix(
         target_timeline.tenant_shard_id,
         layer.layer_desc().key_range.start,
         layer.layer_desc().lsn_range.start..end_lsn,
+        target_timeline.get_delta_compression(),
         ctx,
     )
     .await
@@ -839,6 +840,8 @@ async fn remote_copy(
     debug_assert!(metadata.generation <= generation);
     metadata.generation = generation;
     metadata.shard = shard_identity.shard_index();
+    // The copy is ours, even if the adopted layer is referenced from another tenant.
+    metadata.cross_tenant_owner = None;
 
     let conf = adoptee.conf;
     let file_name = adopted.layer_desc().layer_name();
@@ -1302,7 +1305,7 @@ fn check_no_archived_children_of_ancestor(
     Ok(())
 }
 
-async fn fsync_timeline_dir(timeline: &Timeline, ctx: &RequestContext) {
+pub(super) async fn fsync_timeline_dir(timeline: &Timeline, ctx: &RequestContext) {
     let path = &timeline
         .conf
         .timeline_path(&timeline.tenant_shard_id, &timeline.timeline_id);
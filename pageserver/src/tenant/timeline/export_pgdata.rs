@@ -0,0 +1,539 @@
+//! Export a timeline at an LSN as a PGDATA directory in remote storage.
+//!
+//! The layout is the one [`super::import_pgdata`] reads: every file of the data directory is
+//! an object under `pgdata/`, and `status/pgdata` says whether the directory is complete. An
+//! export can thus be imported as a new timeline, e.g. to migrate it to another region, and
+//! customers can download it and start it with vanilla PostgreSQL of the same major version.
+//! Like with `aws s3 sync`, empty directories have no object, so they must be recreated after
+//! downloading.
+//!
+//! The non-relational files are those of a basebackup at the LSN. pg_control is then made to
+//! point to a shutdown checkpoint record at the LSN, which we write into the WAL segment, so that
+//! PostgreSQL starts without Neon's startup changes. Relation segments are assembled block by
+//! block, and streamed to remote storage as they are, so that memory use doesn't depend on their
+//! size (up to 1 GiB). Smaller files are buffered and their uploads retried. An upload error
+//! fails the export.
+//!
+//! A sharded tenant is exported by shard zero, which holds everything but the blocks of
+//! relations. It reads the blocks owned by other shards from the page service of the pageservers
+//! where they are attached, like a compute would, and leases the LSN on them for the duration of
+//! the export. The storage controller tells it where they are.
+//!
+//! See /v1/tenant/:tenant_shard_id/timeline/:timeline_id/export_pgdata.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use pageserver_api::key::rel_block_to_key;
+use pageserver_api::models::{
+    PagestreamGetPageRequest, PagestreamRequest, TimelineExportPgdataShard,
+    TimelineExportPgdataTaskInfo, TimelineExportPgdataTaskState,
+};
+use pageserver_api::reltag::RelTag;
+use pageserver_api::shard::{ShardNumber, TenantShardId};
+use pageserver_client::page_service;
+use postgres_ffi::relfile_utils::{INIT_FORKNUM, MAIN_FORKNUM};
+use postgres_ffi::{BLCKSZ, RELSEG_SIZE};
+use remote_storage::RemotePath;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{Instrument, info, info_span, warn};
+use utils::lsn::Lsn;
+
+use super::Timeline;
+use super::import_pgdata::importbucket_client::{self, RemoteStorageWrapper};
+use super::import_pgdata::{importbucket_format, index_part_format};
+use crate::basebackup;
+use crate::context::{DownloadBehavior, RequestContext};
+use crate::pgdatadir_mapping::Version;
+use crate::task_mgr::{self, TaskKind};
+
+/// Capacity of the pipe between the basebackup generation and the uploads.
+const PIPE_CAPACITY: usize = 1024 * 1024;
+
+/// Number of blocks in the chunks in which relation segments are streamed, and how many of them
+/// are in flight. The blocks of a chunk that other shards own are requested all at once.
+const STREAM_CHUNK_BLOCKS: u32 = 128;
+const STREAM_CHUNKS_IN_FLIGHT: usize = 4;
+
+/// The other shards of a sharded tenant, see the module docs.
+struct ShardPeers {
+    /// Connections on which the LSN is leased.
+    leases: Vec<(TenantShardId, page_service::Client)>,
+    /// Connections on which relation blocks are read.
+    pagestreams: HashMap<ShardNumber, page_service::PagestreamClient>,
+}
+
+impl Timeline {
+    /// Spawn a task that exports the timeline at `lsn` to `location`.
+    ///
+    /// The caller must have validated that `lsn` can be read, and leased it. For a sharded
+    /// tenant, this must be shard zero, and `shards` the locations of all other shards.
+    pub(crate) fn spawn_export_pgdata(
+        self: &Arc<Self>,
+        lsn: Lsn,
+        location: index_part_format::Location,
+        shards: Vec<TimelineExportPgdataShard>,
+        auth_token: Option<String>,
+        ctx: &RequestContext,
+    ) -> Result<TimelineExportPgdataTaskInfo, TimelineExportPgdataTaskInfo> {
+        let mut status_guard = self.export_pgdata_task_info.write().unwrap();
+        if let Some(st) = &*status_guard {
+            if st.state == TimelineExportPgdataTaskState::Running {
+                return Err(st.clone());
+            }
+        }
+
+        let timeline = Arc::clone(self);
+        let task_ctx = ctx.detached_child(TaskKind::ExportPgdata, DownloadBehavior::Download);
+        let task_id = task_mgr::spawn(
+            task_mgr::BACKGROUND_RUNTIME.handle(),
+            TaskKind::ExportPgdata,
+            self.tenant_shard_id,
+            Some(self.timeline_id),
+            "export pgdata",
+            async move {
+                let res = timeline
+                    .export_pgdata(lsn, location, shards, auth_token, &task_ctx)
+                    .await;
+
+                let mut status_guard = timeline.export_pgdata_task_info.write().unwrap();
+                let Some(st) = &mut *status_guard else {
+                    warn!("task status is supposed to be Some(), since we are running");
+                    return Ok(());
+                };
+                match res {
+                    Ok(()) => {
+                        info!(
+                            files = st.exported_file_count,
+                            bytes = st.exported_bytes,
+                            "export complete"
+                        );
+                        st.state = TimelineExportPgdataTaskState::Completed;
+                    }
+                    Err(_) if timeline.cancel.is_cancelled() => {
+                        st.state = TimelineExportPgdataTaskState::ShutDown;
+                    }
+                    Err(e) => {
+                        warn!("export failed: {e:#}");
+                        st.state = TimelineExportPgdataTaskState::Failed;
+                        st.error = Some(format!("{e:#}"));
+                    }
+                }
+                Ok(())
+            }
+            .instrument(info_span!(parent: None, "export_pgdata", tenant_id = %self.tenant_shard_id.tenant_id, shard_id = %self.tenant_shard_id.shard_slug(), timeline_id = %self.timeline_id, %lsn)),
+        );
+
+        let initial_info = TimelineExportPgdataTaskInfo {
+            task_id: format!("{task_id}"),
+            state: TimelineExportPgdataTaskState::Running,
+            lsn,
+            exported_file_count: 0,
+            exported_bytes: 0,
+            error: None,
+        };
+        *status_guard = Some(initial_info.clone());
+
+        Ok(initial_info)
+    }
+
+    pub(crate) fn get_export_pgdata_task_info(&self) -> Option<TimelineExportPgdataTaskInfo> {
+        self.export_pgdata_task_info.read().unwrap().clone()
+    }
+
+    async fn export_pgdata(
+        self: &Arc<Self>,
+        lsn: Lsn,
+        location: index_part_format::Location,
+        shards: Vec<TimelineExportPgdataShard>,
+        auth_token: Option<String>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
+        let _gate = self.gate.enter().context("timeline is shutting down")?;
+        let cancel = self.cancel.child_token();
+
+        let storage = importbucket_client::new(self.conf, &location, cancel.clone()).await?;
+        let pgdata_status_key = RemotePath::from_string("status/pgdata").unwrap();
+
+        // An import of the location must wait for us, even if it held a previous export.
+        storage
+            .put_json(
+                &pgdata_status_key,
+                &importbucket_format::PgdataStatus { done: false },
+            )
+            .await
+            .context("put pgdata status")?;
+
+        let ShardPeers {
+            leases,
+            mut pagestreams,
+        } = self
+            .connect_shard_peers(&shards, auth_token.as_deref(), lsn)
+            .await?;
+
+        // Keep GC away from the LSN for the whole export.
+        let lease_length = self.get_lsn_lease_length();
+        let renew_period = std::cmp::max(lease_length / 2, Duration::from_secs(1));
+        {
+            let export = self.export_files(lsn, &storage, &mut pagestreams, ctx);
+            tokio::pin!(export);
+            loop {
+                tokio::select! {
+                    res = &mut export => {
+                        res?;
+                        break;
+                    }
+                    _ = tokio::time::sleep(renew_period) => {
+                        self.renew_lsn_lease(lsn, lease_length, ctx)
+                            .context("renew lsn lease")?;
+                        for (tenant_shard_id, client) in &leases {
+                            client
+                                .lease_lsn(*tenant_shard_id, self.timeline_id, lsn)
+                                .await
+                                .with_context(|| {
+                                    format!("renew lsn lease on shard {tenant_shard_id}")
+                                })?;
+                        }
+                    }
+                }
+            }
+        }
+
+        for (_, pagestream) in pagestreams {
+            pagestream.shutdown().await;
+        }
+
+        storage
+            .put_json(
+                &pgdata_status_key,
+                &importbucket_format::PgdataStatus { done: true },
+            )
+            .await
+            .context("put pgdata status")?;
+
+        Ok(())
+    }
+
+    /// Connect to the page service of the other shards' pageservers, and lease `lsn` on them.
+    async fn connect_shard_peers(
+        &self,
+        shards: &[TimelineExportPgdataShard],
+        auth_token: Option<&str>,
+        lsn: Lsn,
+    ) -> anyhow::Result<ShardPeers> {
+        let mut peers = ShardPeers {
+            leases: Vec::with_capacity(shards.len()),
+            pagestreams: HashMap::with_capacity(shards.len()),
+        };
+        for shard in shards {
+            let tenant_shard_id = TenantShardId {
+                tenant_id: self.tenant_shard_id.tenant_id,
+                shard_number: shard.shard_number,
+                shard_count: self.tenant_shard_id.shard_count,
+            };
+            let mut connstr = format!(
+                "host={} port={} user=no_user",
+                shard.listen_pg_addr, shard.listen_pg_port
+            );
+            if let Some(auth_token) = auth_token {
+                connstr.push_str(&format!(" password={auth_token}"));
+            }
+            let connect = || async {
+                page_service::Client::new(connstr.clone())
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "connect to shard {tenant_shard_id} at {}:{}",
+                            shard.listen_pg_addr, shard.listen_pg_port
+                        )
+                    })
+            };
+
+            let client = connect().await?;
+            client
+                .lease_lsn(tenant_shard_id, self.timeline_id, lsn)
+                .await
+                .with_context(|| format!("lease lsn on shard {tenant_shard_id}"))?;
+            peers.leases.push((tenant_shard_id, client));
+
+            let pagestream = connect()
+                .await?
+                .pagestream(self.tenant_shard_id.tenant_id, self.timeline_id)
+                .await
+                .with_context(|| format!("open pagestream to shard {tenant_shard_id}"))?;
+            peers.pagestreams.insert(shard.shard_number, pagestream);
+        }
+        Ok(peers)
+    }
+
+    /// Generate a basebackup at `lsn` and upload each of its files as it is generated, then
+    /// the relations.
+    async fn export_files(
+        self: &Arc<Self>,
+        lsn: Lsn,
+        storage: &RemoteStorageWrapper,
+        pagestreams: &mut HashMap<ShardNumber, page_service::PagestreamClient>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
+        let (mut writer, reader) = tokio::io::duplex(PIPE_CAPACITY);
+
+        // Without relation data, except for the init forks of unlogged relations: all of it is
+        // on shard zero.
+        let generate = async move {
+            basebackup::send_basebackup_tarball(
+                &mut writer,
+                self,
+                Some(lsn),
+                None,
+                false,
+                false,
+                ctx,
+            )
+            .await?;
+            writer.shutdown().await?;
+            anyhow::Ok(())
+        };
+
+        let upload = async move {
+            let mut pg_control = None;
+            let mut wal_segment = None;
+
+            let mut entries = tokio_tar::Archive::new(reader).entries()?;
+            while let Some(entry) = entries.next().await {
+                let mut entry = entry?;
+                let header = entry.header();
+                let len = header.entry_size()? as usize;
+                let path = header.path()?.to_string_lossy().into_owned();
+                match header.entry_type() {
+                    tokio_tar::EntryType::Regular => {}
+                    tokio_tar::EntryType::Directory => continue,
+                    entry_type => anyhow::bail!("unexpected entry {path} of type {entry_type:?}"),
+                }
+
+                // Both are rewritten once the archive is complete. The WAL segment is
+                // the only one in the basebackup, of the configured WAL segment size.
+                let buf = read_entry(&mut entry, len).await?;
+                if path == "global/pg_control" {
+                    pg_control = Some(buf);
+                } else if path.starts_with("pg_wal/") {
+                    wal_segment = Some((path, buf));
+                } else {
+                    self.put_pgdata_file(storage, &path, Bytes::from(buf))
+                        .await?;
+                }
+            }
+
+            self.export_rels(lsn, storage, pagestreams, ctx).await?;
+
+            let pg_control = pg_control.context("basebackup has no pg_control")?;
+            let (wal_segment_path, wal_segment) =
+                wal_segment.context("basebackup has no WAL segment")?;
+            let mut wal_segment = BytesMut::from(&wal_segment[..]);
+
+            let end_of_timeline = self.get_last_record_rlsn();
+            let prev_lsn = if end_of_timeline.last == lsn {
+                end_of_timeline.prev
+            } else {
+                Lsn(0)
+            };
+            let pg_control = postgres_ffi::generate_standalone_checkpoint(
+                &pg_control,
+                &mut wal_segment,
+                prev_lsn,
+                self.pg_version,
+            )?;
+
+            self.put_pgdata_file(storage, &wal_segment_path, wal_segment.freeze())
+                .await?;
+            // Last, so that an incomplete export can't be mistaken for a data directory.
+            self.put_pgdata_file(storage, "global/pg_control", pg_control)
+                .await?;
+            anyhow::Ok(())
+        };
+
+        tokio::try_join!(generate, upload)?;
+        Ok(())
+    }
+
+    /// Upload the segments of all relations at `lsn`, except for those the basebackup has.
+    async fn export_rels(
+        &self,
+        lsn: Lsn,
+        storage: &RemoteStorageWrapper,
+        pagestreams: &mut HashMap<ShardNumber, page_service::PagestreamClient>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
+        for (spcnode, dbnode) in self.list_dbdirs(lsn, ctx).await?.into_keys() {
+            let rels = self
+                .list_rels(spcnode, dbnode, Version::Lsn(lsn), ctx)
+                .await?;
+            for &rel in rels.iter() {
+                // The basebackup has the init forks, and copies of them as main forks.
+                if rel.forknum == INIT_FORKNUM
+                    || (rel.forknum == MAIN_FORKNUM
+                        && rels.contains(&rel.with_forknum(INIT_FORKNUM)))
+                {
+                    continue;
+                }
+                self.export_rel(rel, lsn, storage, pagestreams, ctx).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn export_rel(
+        &self,
+        rel: RelTag,
+        lsn: Lsn,
+        storage: &RemoteStorageWrapper,
+        pagestreams: &mut HashMap<ShardNumber, page_service::PagestreamClient>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<()> {
+        // Shard zero has the size of every relation.
+        let nblocks = self.get_rel_size(rel, Version::Lsn(lsn), ctx).await?;
+        if nblocks == 0 {
+            return self
+                .put_pgdata_file(storage, &rel.to_segfile_name(0), Bytes::new())
+                .await;
+        }
+
+        for (segno, startblk) in (0..nblocks).step_by(RELSEG_SIZE as usize).enumerate() {
+            let endblk = std::cmp::min(startblk + RELSEG_SIZE, nblocks);
+            let len = (endblk - startblk) as usize * BLCKSZ as usize;
+            let path = rel.to_segfile_name(segno as u32);
+            let pagestreams = &mut *pagestreams;
+            self.stream_pgdata_file(storage, &path, len, |tx| async move {
+                for chunk_start in (startblk..endblk).step_by(STREAM_CHUNK_BLOCKS as usize) {
+                    let chunk_end = std::cmp::min(chunk_start + STREAM_CHUNK_BLOCKS, endblk);
+                    let chunk = self
+                        .read_rel_blocks(rel, chunk_start..chunk_end, lsn, pagestreams, ctx)
+                        .await?;
+                    // The upload only stops reading on error, which it returns.
+                    if tx.send(Ok(chunk)).await.is_err() {
+                        break;
+                    }
+                }
+                anyhow::Ok(())
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Read `blknums` of `rel` at `lsn`, each from the shard that owns it.
+    async fn read_rel_blocks(
+        &self,
+        rel: RelTag,
+        blknums: Range<u32>,
+        lsn: Lsn,
+        pagestreams: &mut HashMap<ShardNumber, page_service::PagestreamClient>,
+        ctx: &RequestContext,
+    ) -> anyhow::Result<Bytes> {
+        let shard_identity = self.get_shard_identity();
+
+        // Send the requests for the other shards' blocks first, so that they are read while we
+        // read ours. Each shard responds in the order of its requests.
+        let mut owners = Vec::with_capacity(blknums.len());
+        for blkno in blknums.clone() {
+            let key = rel_block_to_key(rel, blkno);
+            if shard_identity.is_key_local(&key) {
+                owners.push(None);
+                continue;
+            }
+            let shard_number = shard_identity.get_shard_number(&key);
+            let pagestream = pagestreams
+                .get_mut(&shard_number)
+                .with_context(|| format!("no location for shard {shard_number:?}"))?;
+            pagestream
+                .getpage_send(PagestreamGetPageRequest {
+                    hdr: PagestreamRequest {
+                        reqid: 0,
+                        request_lsn: lsn,
+                        not_modified_since: lsn,
+                    },
+                    rel,
+                    blkno,
+                })
+                .await
+                .with_context(|| {
+                    format!("request {rel} block {blkno} from shard {shard_number:?}")
+                })?;
+            owners.push(Some(shard_number));
+        }
+
+        let mut buf = BytesMut::with_capacity(blknums.len() * BLCKSZ as usize);
+        for (blkno, owner) in blknums.zip(owners) {
+            let page = match owner {
+                None => self.get(rel_block_to_key(rel, blkno), lsn, ctx).await?,
+                Some(shard_number) => {
+                    pagestreams
+                        .get_mut(&shard_number)
+                        .expect("checked when sending the request")
+                        .getpage_recv()
+                        .await
+                        .with_context(|| {
+                            format!("read {rel} block {blkno} from shard {shard_number:?}")
+                        })?
+                        .page
+                }
+            };
+            buf.extend_from_slice(&page);
+        }
+        Ok(buf.freeze())
+    }
+
+    async fn put_pgdata_file(
+        &self,
+        storage: &RemoteStorageWrapper,
+        path: &str,
+        bytes: Bytes,
+    ) -> anyhow::Result<()> {
+        let len = bytes.len();
+        storage.put(&storage.pgdata().join(path), bytes).await?;
+        self.account_exported_file(len);
+        Ok(())
+    }
+
+    /// Upload the `len` bytes which `produce` sends to `path` in chunks, keeping only a few
+    /// chunks in memory.
+    async fn stream_pgdata_file<F, Fut>(
+        &self,
+        storage: &RemoteStorageWrapper,
+        path: &str,
+        len: usize,
+        produce: F,
+    ) -> anyhow::Result<()>
+    where
+        F: FnOnce(mpsc::Sender<std::io::Result<Bytes>>) -> Fut,
+        Fut: Future<Output = anyhow::Result<()>>,
+    {
+        let (tx, rx) = mpsc::channel(STREAM_CHUNKS_IN_FLIGHT);
+        let upload = storage.put_stream(&storage.pgdata().join(path), ReceiverStream::new(rx), len);
+
+        tokio::try_join!(produce(tx), upload)?;
+        self.account_exported_file(len);
+        Ok(())
+    }
+
+    fn account_exported_file(&self, len: usize) {
+        let mut status_guard = self.export_pgdata_task_info.write().unwrap();
+        if let Some(st) = &mut *status_guard {
+            st.exported_file_count += 1;
+            st.exported_bytes += len as u64;
+        }
+    }
+}
+
+async fn read_entry(entry: &mut (impl AsyncRead + Unpin), len: usize) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(len);
+    entry.read_to_end(&mut buf).await?;
+    Ok(buf)
+}
@@ -11,8 +11,8 @@
 use crate::tenant::metadata::TimelineMetadata;
 
 mod flow;
-mod importbucket_client;
-mod importbucket_format;
+pub(crate) mod importbucket_client;
+pub(crate) mod importbucket_format;
 pub(crate) mod index_part_format;
 pub(crate) mod upcall_api;
 
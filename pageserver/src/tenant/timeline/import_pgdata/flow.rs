@@ -756,6 +756,8 @@ async fn run(self, ctx: &RequestContext) -> anyhow::Result<()> {
         };
 
         // this is sharing the same code as create_image_layers
+        let _reservation = self.timeline.reserve_new_layers([&resident_layer]).await?;
+
         let mut guard = self.timeline.layers.write().await;
         guard
             .open_mut()?
@@ -202,7 +202,11 @@ pub async fn put_json<T>(&self, path: &RemotePath, value: &T) -> anyhow::Result<
         T: serde::Serialize,
     {
         let buf = serde_json::to_vec(value)?;
-        let bytes = Bytes::from(buf);
+        self.put(path, Bytes::from(buf)).await
+    }
+
+    #[instrument(level = tracing::Level::DEBUG, skip_all, fields(%path, len = bytes.len()))]
+    pub async fn put(&self, path: &RemotePath, bytes: Bytes) -> anyhow::Result<()> {
         utils::backoff::retry(
             || async {
                 let size = bytes.len();
@@ -214,11 +218,26 @@ pub async fn put_json<T>(&self, path: &RemotePath, value: &T) -> anyhow::Result<
             remote_storage::TimeoutOrCancel::caused_by_cancel,
             1,
             u32::MAX,
-            &format!("put json {path}"),
+            &format!("put {path}"),
             &self.cancel,
         )
         .await
-        .expect("practically infinite retries")
+        .ok_or_else(|| anyhow::Error::new(remote_storage::TimeoutOrCancel::Cancel))
+        .and_then(|x| x)
+    }
+
+    /// Like [`Self::put`], for an object whose content is produced while it is uploaded. This is
+    /// not retried: the content can only be read once.
+    #[instrument(level = tracing::Level::DEBUG, skip_all, fields(%path, len = size))]
+    pub async fn put_stream(
+        &self,
+        path: &RemotePath,
+        content: impl futures::Stream<Item = std::io::Result<Bytes>> + Send + Sync + 'static,
+        size: usize,
+    ) -> anyhow::Result<()> {
+        self.storage
+            .upload_storage_object(content, size, path, &self.cancel)
+            .await
     }
 
     #[instrument(level = tracing::Level::DEBUG, skip_all, fields(%path))]
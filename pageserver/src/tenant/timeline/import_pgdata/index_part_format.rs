@@ -1,5 +1,6 @@
 #[cfg(feature = "testing")]
 use camino::Utf8PathBuf;
+use pageserver_api::models::ImportPgdataLocation;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -47,6 +48,24 @@ pub enum Location {
     },
 }
 
+impl From<ImportPgdataLocation> for Location {
+    fn from(location: ImportPgdataLocation) -> Self {
+        match location {
+            #[cfg(feature = "testing")]
+            ImportPgdataLocation::LocalFs { path } => Location::LocalFs { path },
+            ImportPgdataLocation::AwsS3 {
+                region,
+                bucket,
+                key,
+            } => Location::AwsS3 {
+                region,
+                bucket,
+                key,
+            },
+        }
+    }
+}
+
 impl Root {
     pub fn is_done(&self) -> bool {
         match self {
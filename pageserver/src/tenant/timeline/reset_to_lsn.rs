@@ -0,0 +1,323 @@
+//! Reset a timeline in place to an earlier LSN.
+//!
+//! Restoring a branch to a past point is usually done by creating a child timeline at that point
+//! and repointing the compute at it. Resetting keeps the timeline ID instead, so connection
+//! strings don't change: everything the timeline ingested after the LSN is discarded, like if it
+//! had been created at the end of a `pg_rewind`.
+//!
+//! This reuses the machinery of [`super::detach_ancestor`]: the delta layers straddling the LSN
+//! are rewritten to their prefix up to the LSN, and a new `index_part.json` without the discarded
+//! layers and with `disk_consistent_lsn` at the LSN is uploaded. The new index is published in a
+//! new generation, and the tenant is then reloaded in that generation. The index of the previous
+//! generation is left untouched: it is the archive of the discarded history, and keeps the
+//! discarded layers referenced until the scrubber collects it with the other old indices.
+//!
+//! The storage controller drives the operation for all the shards of a tenant, allocates the new
+//! generations, and resets the timeline on the safekeepers, so that the discarded WAL is not
+//! ingested again. Computes must be stopped before the reset.
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use http_utils::error::ApiError;
+use utils::generation::Generation;
+use utils::id::TimelineId;
+use utils::lsn::Lsn;
+
+use super::layer_manager::LayerManager;
+use super::{FlushLayerError, Timeline};
+use crate::context::RequestContext;
+use crate::tenant::Tenant;
+use crate::tenant::storage_layer::{AsLayerDesc as _, Layer, PersistentLayerKey};
+use crate::tenant::timeline::detach_ancestor;
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Error {
+    #[error("lsn {lsn} is ahead of the last record lsn {last_record_lsn}")]
+    AheadOfLastRecordLsn { lsn: Lsn, last_record_lsn: Lsn },
+
+    #[error("lsn {lsn} is before the start of the timeline at {start_lsn}")]
+    BeforeTimelineStart { lsn: Lsn, start_lsn: Lsn },
+
+    #[error("lsn {lsn} is not aligned")]
+    NotAligned { lsn: Lsn },
+
+    #[error("lsn {lsn} is outside of the PITR window, which starts at {cutoff}")]
+    OutsidePitrWindow { lsn: Lsn, cutoff: Lsn },
+
+    #[error("timeline {} is branched off after the lsn", .0)]
+    HasChildrenAfterLsn(TimelineId),
+
+    #[error("lsn {lease_lsn} after the lsn is leased")]
+    LeasedAfterLsn { lease_lsn: Lsn },
+
+    #[error("generation {requested:?} is not newer than the current generation {current:?}")]
+    StaleGeneration {
+        requested: Generation,
+        current: Generation,
+    },
+
+    #[error("archived: {}", .0)]
+    Archived(TimelineId),
+
+    #[error("shutting down, please retry later")]
+    ShuttingDown,
+
+    #[error(transparent)]
+    NotFound(crate::tenant::GetTimelineError),
+
+    #[error("resetting the timeline failed")]
+    Reset(#[source] anyhow::Error),
+
+    /// The new index may have been uploaded: the tenant must be reloaded.
+    #[error("publishing the reset index failed")]
+    Publish(#[source] anyhow::Error),
+}
+
+impl From<Error> for ApiError {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::AheadOfLastRecordLsn { .. }
+            | Error::BeforeTimelineStart { .. }
+            | Error::NotAligned { .. }
+            | Error::OutsidePitrWindow { .. }
+            | Error::Archived(_)
+            | Error::StaleGeneration { .. } => ApiError::BadRequest(anyhow::anyhow!("{value}")),
+            Error::HasChildrenAfterLsn(_) | Error::LeasedAfterLsn { .. } => {
+                ApiError::Conflict(value.to_string())
+            }
+            Error::ShuttingDown => ApiError::ShuttingDown,
+            Error::NotFound(e) => ApiError::from(e),
+            Error::Reset(_) | Error::Publish(_) => ApiError::InternalServerError(value.into()),
+        }
+    }
+}
+
+impl From<detach_ancestor::Error> for Error {
+    fn from(value: detach_ancestor::Error) -> Self {
+        match value {
+            detach_ancestor::Error::ShuttingDown => Error::ShuttingDown,
+            other => Error::Reset(other.into()),
+        }
+    }
+}
+
+/// See [`Timeline::check_reset_to_lsn`].
+pub(super) fn check(timeline: &Arc<Timeline>, tenant: &Tenant, lsn: Lsn) -> Result<(), Error> {
+    if timeline.is_archived() != Some(false) {
+        return Err(Error::Archived(timeline.timeline_id));
+    }
+    if !lsn.is_aligned() {
+        return Err(Error::NotAligned { lsn });
+    }
+
+    check_lsn(timeline, tenant, lsn)
+}
+
+/// See [`Timeline::reset_to_lsn`].
+pub(super) async fn reset_to_lsn(
+    timeline: &Arc<Timeline>,
+    tenant: &Tenant,
+    lsn: Lsn,
+    generation: Generation,
+    ctx: &RequestContext,
+) -> Result<(), Error> {
+    if timeline.is_archived() != Some(false) {
+        return Err(Error::Archived(timeline.timeline_id));
+    }
+    if !lsn.is_aligned() {
+        return Err(Error::NotAligned { lsn });
+    }
+
+    // Keep everything which could change the layers of the timeline away until the tenant is
+    // reloaded: GC, compaction, and WAL ingest.
+    let _gc = tokio::select! {
+        guard = timeline.gc_lock.lock() => guard,
+        _ = timeline.cancel.cancelled() => return Err(Error::ShuttingDown),
+    };
+    let _compaction = tokio::select! {
+        guard = timeline.compaction_lock.lock() => guard,
+        _ = timeline.cancel.cancelled() => return Err(Error::ShuttingDown),
+    };
+    let mut write_guard = tokio::select! {
+        guard = timeline.write_lock.lock() => guard,
+        _ = timeline.cancel.cancelled() => return Err(Error::ShuttingDown),
+    };
+
+    check_lsn(timeline, tenant, lsn)?;
+
+    tracing::info!(
+        last_record_lsn = %timeline.get_last_record_lsn(),
+        "resetting timeline to {lsn}"
+    );
+
+    // All of the history must be in historic layers for us to pick from.
+    let flush = timeline
+        .freeze_inmem_layer_at(timeline.get_last_record_lsn(), &mut write_guard)
+        .await;
+    let flushed = match flush {
+        Ok(request) => timeline.wait_flush_completion(request).await,
+        Err(e) => Err(e),
+    };
+    flushed.map_err(|e| match e {
+        FlushLayerError::Cancelled | FlushLayerError::NotRunning(_) => Error::ShuttingDown,
+        FlushLayerError::CreateImageLayersError(_) | FlushLayerError::Other(_) => {
+            Error::Reset(e.into())
+        }
+    })?;
+    if timeline.cancel.is_cancelled() {
+        return Err(Error::ShuttingDown);
+    }
+
+    let (discarded, straddling) = {
+        let layers = timeline.layers.read().await;
+        partition_layers(lsn, &layers)?
+    };
+
+    tracing::info!(
+        discarded = discarded.len(),
+        to_rewrite = straddling.len(),
+        "collected layers"
+    );
+
+    let end_lsn = lsn + 1;
+    let mut rewritten = Vec::with_capacity(straddling.len());
+    for layer in &straddling {
+        let copied = detach_ancestor::upload_rewritten_layer(
+            end_lsn,
+            layer,
+            timeline,
+            &timeline.cancel,
+            ctx,
+        )
+        .await?;
+        if let Some(copied) = copied {
+            tracing::info!(%layer, %copied, "rewrote and uploaded");
+            rewritten.push(copied);
+        }
+    }
+    if !rewritten.is_empty() {
+        detach_ancestor::fsync_timeline_dir(timeline, ctx).await;
+    }
+
+    let discarded = discarded.into_iter().chain(straddling).collect::<Vec<_>>();
+    timeline
+        .remote_client
+        .upload_reset_index_part(lsn, &discarded, &rewritten, generation)
+        .await
+        .context("publish reset index_part.json")
+        .map_err(Error::Publish)?;
+
+    tracing::info!(
+        discarded = discarded.len(),
+        rewritten = rewritten.len(),
+        ?generation,
+        "reset timeline to {lsn}, the tenant must now be reloaded in the new generation"
+    );
+
+    Ok(())
+}
+
+fn check_lsn(timeline: &Arc<Timeline>, tenant: &Tenant, lsn: Lsn) -> Result<(), Error> {
+    let last_record_lsn = timeline.get_last_record_lsn();
+    if lsn > last_record_lsn {
+        return Err(Error::AheadOfLastRecordLsn {
+            lsn,
+            last_record_lsn,
+        });
+    }
+
+    let start_lsn = std::cmp::max(timeline.get_ancestor_lsn(), timeline.initdb_lsn);
+    if lsn < start_lsn {
+        return Err(Error::BeforeTimelineStart { lsn, start_lsn });
+    }
+
+    // Same rules as for the start of a branch, except that a lease doesn't bring back history
+    // which GC has already removed: the reset index must keep the applied cutoff at or below
+    // its `disk_consistent_lsn`.
+    {
+        let applied_gc_cutoff_lsn = *timeline.get_applied_gc_cutoff_lsn();
+        if lsn < applied_gc_cutoff_lsn {
+            return Err(Error::OutsidePitrWindow {
+                lsn,
+                cutoff: applied_gc_cutoff_lsn,
+            });
+        }
+        let gc_info = timeline.gc_info.read().unwrap();
+        if !gc_info.lsn_covered_by_lease(lsn) && lsn < gc_info.min_cutoff() {
+            return Err(Error::OutsidePitrWindow {
+                lsn,
+                cutoff: gc_info.min_cutoff(),
+            });
+        }
+    }
+
+    // Leases are taken by static computes and by branch creation on the history they read: they
+    // cannot be pointed into discarded history.
+    let now = SystemTime::now();
+    {
+        let gc_info = timeline.gc_info.read().unwrap();
+        let leased = gc_info
+            .leases
+            .range(lsn + 1..)
+            .find(|(_, lease)| !lease.is_expired(&now));
+        if let Some((lease_lsn, _)) = leased {
+            return Err(Error::LeasedAfterLsn {
+                lease_lsn: *lease_lsn,
+            });
+        }
+    }
+
+    // Children branched after the LSN would lose the history they were branched from.
+    for child in tenant.timelines.lock().unwrap().values() {
+        let is_child = matches!(child.ancestor_timeline.as_ref(), Some(ancestor) if Arc::ptr_eq(ancestor, timeline));
+        if is_child && child.get_ancestor_lsn() > lsn {
+            return Err(Error::HasChildrenAfterLsn(child.timeline_id));
+        }
+    }
+    for offloaded in tenant.timelines_offloaded.lock().unwrap().values() {
+        if offloaded.ancestor_timeline_id == Some(timeline.timeline_id)
+            && offloaded
+                .ancestor_retain_lsn
+                .is_none_or(|retain_lsn| retain_lsn > lsn)
+        {
+            return Err(Error::HasChildrenAfterLsn(offloaded.timeline_id));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the layers with data only after `lsn`, and the delta layers with data on both sides
+/// of it.
+fn partition_layers(lsn: Lsn, source: &LayerManager) -> Result<(Vec<Layer>, Vec<Layer>), Error> {
+    let mut discarded = vec![];
+    let mut straddling = vec![];
+
+    let layer_map = source.layer_map().map_err(|_| Error::ShuttingDown)?;
+    for desc in layer_map.iter_historic_layers() {
+        // off by one chances here:
+        // - start is inclusive
+        // - end is exclusive
+        if desc.lsn_range.start > lsn {
+            discarded.push(source.get_from_desc(&desc));
+        } else if desc.is_delta && desc.lsn_range.end > lsn + 1 {
+            // The rewritten layer must not replace a layer we keep.
+            let rewritten = PersistentLayerKey {
+                key_range: desc.key_range.clone(),
+                lsn_range: desc.lsn_range.start..lsn + 1,
+                is_delta: true,
+            };
+            if source.contains_key(&rewritten) {
+                return Err(Error::Reset(anyhow::anyhow!(
+                    "rewriting {} would replace an existing layer",
+                    desc.layer_name()
+                )));
+            }
+            straddling.push(source.get_from_desc(&desc));
+        }
+    }
+
+    Ok((discarded, straddling))
+}
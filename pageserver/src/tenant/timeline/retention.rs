@@ -0,0 +1,254 @@
+//! Per-timeline retention policies, see [`TimelineRetentionPolicy`].
+//!
+//! The tiers of a policy keep restore points beyond the PITR window. When GC refreshes its
+//! cutoffs, the points in time that fell out of the PITR window are resolved to LSNs, and the
+//! resulting restore points are persisted in the index part: once GC has moved past a point in
+//! time, its LSN can no longer be looked up. Other shards copy the restore points of shard zero,
+//! and hold back their GC until they have. GC then retains the restore points much like branch
+//! points, except that only the layers needed to read at them are kept, see
+//! [`Timeline::gc_timeline`]. gc-compaction produces image layers at them.
+
+use std::collections::BTreeSet;
+use std::time::{Duration, SystemTime};
+
+use pageserver_api::models::{RestorePoint, RetentionTier, TimelineRetentionPolicy};
+use pageserver_api::shard::ShardNumber;
+use postgres_ffi::to_pg_timestamp;
+use tokio_util::sync::CancellationToken;
+use utils::lsn::Lsn;
+
+use super::{GcCutoffs, Timeline};
+use crate::context::RequestContext;
+use crate::pgdatadir_mapping::LsnForTimestamp;
+use crate::tenant::GcError;
+
+impl Timeline {
+    /// Returns the retention policy overriding the tenant's settings for this timeline, if any.
+    pub(crate) fn get_retention_policy(&self) -> Option<TimelineRetentionPolicy> {
+        self.remote_client.retention_policy()
+    }
+
+    /// Sets or clears the retention policy, and waits for it to be persisted.
+    ///
+    /// Takes effect at the next GC iteration.
+    pub(crate) async fn set_retention_policy(
+        &self,
+        retention_policy: Option<TimelineRetentionPolicy>,
+    ) -> anyhow::Result<()> {
+        self.remote_client
+            .schedule_index_upload_for_retention_policy_update(retention_policy)?;
+        self.remote_client.wait_completion().await?;
+        Ok(())
+    }
+
+    /// Resolves the restore points kept by the retention policy as of `now`, and persists them.
+    ///
+    /// `pitr` is the PITR interval in effect: restore points within it are not needed yet.
+    pub(crate) async fn refresh_restore_points(
+        &self,
+        now: SystemTime,
+        pitr: Duration,
+        cancel: &CancellationToken,
+        ctx: &RequestContext,
+    ) -> Result<RestorePoints, GcError> {
+        let tiers = self
+            .get_retention_policy()
+            .map(|policy| policy.tiers)
+            .unwrap_or_default();
+        let existing = self.remote_client.restore_points();
+        if tiers.is_empty() && existing.is_empty() {
+            return Ok(RestorePoints::default());
+        }
+
+        let mut max_gc_cutoff = None;
+
+        let restore_points = if self.shard_identity.is_shard_zero() {
+            // Shard Zero has SLRU data and can resolve the restore points itself.
+            let mut restore_points = Vec::new();
+            for timestamp in restore_point_times(&tiers, now, pitr) {
+                if let Some(existing) = existing.iter().find(|p| p.timestamp == timestamp) {
+                    restore_points.push(*existing);
+                    continue;
+                }
+                let lsn = match self
+                    .find_lsn_for_timestamp(to_pg_timestamp(timestamp), cancel, ctx)
+                    .await?
+                {
+                    LsnForTimestamp::Present(lsn) => lsn,
+                    // No commits since, see `find_gc_time_cutoff`.
+                    LsnForTimestamp::Future(_) => self.get_last_record_lsn(),
+                    // Before the history we have, either because the timeline did not exist yet
+                    // or because it was garbage collected before the policy was set.
+                    LsnForTimestamp::Past(_) | LsnForTimestamp::NoData(_) => continue,
+                };
+                tracing::info!(
+                    timestamp = %humantime::format_rfc3339(timestamp),
+                    %lsn,
+                    "resolved restore point"
+                );
+                restore_points.push(RestorePoint { timestamp, lsn });
+            }
+            restore_points
+        } else {
+            // Other shards cannot resolve timestamps, and copy the restore points of shard zero.
+            //
+            // Those which shard zero has not resolved yet are newer than its GC cutoff, which was
+            // published after them: GC must not go past that cutoff until they are copied, even
+            // if our own cutoffs advance further.
+            match self
+                .remote_client
+                .download_foreign_index(ShardNumber(0), cancel)
+                .await
+            {
+                Ok((index_part, _index_generation, _index_mtime)) => {
+                    max_gc_cutoff = Some(index_part.metadata.latest_gc_cutoff_lsn());
+                    index_part.restore_points
+                }
+                Err(e) => {
+                    tracing::warn!("failed to load restore points of shard zero: {e:#}");
+                    max_gc_cutoff = Some(*self.get_applied_gc_cutoff_lsn());
+                    existing
+                }
+            }
+        };
+
+        let mut lsns = restore_points.iter().map(|p| p.lsn).collect::<Vec<_>>();
+        lsns.sort();
+        lsns.dedup();
+
+        self.remote_client
+            .schedule_index_upload_for_restore_points_update(restore_points)?;
+
+        Ok(RestorePoints {
+            lsns,
+            max_gc_cutoff,
+        })
+    }
+}
+
+/// The restore points of a timeline, see [`Timeline::refresh_restore_points`].
+#[derive(Debug, Default)]
+pub(crate) struct RestorePoints {
+    /// The LSNs of the restore points, in ascending order.
+    pub(crate) lsns: Vec<Lsn>,
+    /// The GC cutoffs must not advance past this LSN, as restore points older than it may not
+    /// be known yet.
+    pub(crate) max_gc_cutoff: Option<Lsn>,
+}
+
+impl RestorePoints {
+    /// Holds back `cutoffs` at [`Self::max_gc_cutoff`].
+    pub(crate) fn clamp_gc_cutoffs(&self, cutoffs: &mut GcCutoffs) {
+        if let Some(max) = self.max_gc_cutoff {
+            cutoffs.time = std::cmp::min(cutoffs.time, max);
+            cutoffs.space = std::cmp::min(cutoffs.space, max);
+        }
+    }
+}
+
+/// The points in time to keep restore points for, as of `now`.
+///
+/// Only points older than the PITR window are returned.
+fn restore_point_times(
+    tiers: &[RetentionTier],
+    now: SystemTime,
+    pitr: Duration,
+) -> BTreeSet<SystemTime> {
+    let Some(pitr_cutoff) = now.checked_sub(pitr) else {
+        return BTreeSet::new();
+    };
+    let since_epoch = pitr_cutoff
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut times = BTreeSet::new();
+    for tier in tiers {
+        let interval = tier.interval.as_secs();
+        if interval == 0 {
+            // Rejected by `TimelineRetentionPolicy::validate`.
+            continue;
+        }
+        let Some(oldest) = now.checked_sub(tier.keep_for) else {
+            continue;
+        };
+        let mut time =
+            SystemTime::UNIX_EPOCH + Duration::from_secs(since_epoch / interval * interval);
+        while time >= oldest {
+            times.insert(time);
+            let Some(previous) = time.checked_sub(tier.interval) else {
+                break;
+            };
+            time = previous;
+        }
+    }
+    times
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    const HOUR: u64 = 3600;
+    const DAY: u64 = 24 * HOUR;
+
+    #[test]
+    fn tiers_are_aligned_and_merged() {
+        let tiers = [
+            RetentionTier {
+                interval: Duration::from_secs(HOUR),
+                keep_for: Duration::from_secs(DAY),
+            },
+            RetentionTier {
+                interval: Duration::from_secs(DAY),
+                keep_for: Duration::from_secs(3 * DAY),
+            },
+        ];
+        let now = at(100 * DAY + 90 * 60);
+
+        let times = restore_point_times(&tiers, now, Duration::from_secs(2 * HOUR));
+
+        let mut expected = BTreeSet::new();
+        // The hourly tier, from the last full hour before the PITR window back to a day ago.
+        for hour in 2..=23 {
+            expected.insert(at(99 * DAY + HOUR * hour));
+        }
+        // The daily tier, back to three days ago.
+        expected.insert(at(99 * DAY));
+        expected.insert(at(98 * DAY));
+        assert_eq!(times, expected);
+    }
+
+    #[test]
+    fn nothing_beyond_the_pitr_window() {
+        let tiers = [RetentionTier {
+            interval: Duration::from_secs(HOUR),
+            keep_for: Duration::from_secs(DAY),
+        }];
+
+        let times = restore_point_times(&tiers, at(100 * DAY), Duration::from_secs(2 * DAY));
+
+        assert!(times.is_empty(), "{times:?}");
+    }
+
+    #[test]
+    fn gc_cutoffs_are_held_back() {
+        let restore_points = RestorePoints {
+            lsns: vec![Lsn(0x10)],
+            max_gc_cutoff: Some(Lsn(0x30)),
+        };
+        let mut cutoffs = GcCutoffs {
+            space: Lsn(0x20),
+            time: Lsn(0x40),
+        };
+
+        restore_points.clamp_gc_cutoffs(&mut cutoffs);
+
+        assert_eq!(cutoffs.space, Lsn(0x20));
+        assert_eq!(cutoffs.time, Lsn(0x30));
+    }
+}
@@ -4,7 +4,7 @@
 use std::pin::pin;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::{Context, anyhow};
 use bytes::BytesMut;
@@ -39,6 +39,7 @@
 use crate::metrics::{LIVE_CONNECTIONS, WAL_INGEST, WALRECEIVER_STARTED_CONNECTIONS};
 use crate::pgdatadir_mapping::DatadirModification;
 use crate::task_mgr::{TaskKind, WALRECEIVER_RUNTIME};
+use crate::tenant::throttle::ThrottleResult;
 use crate::tenant::{
     Timeline, WalReceiverInfo, debug_assert_current_span_has_tenant_and_timeline_id,
 };
@@ -286,6 +287,9 @@ pub(super) async fn handle_walreceiver_connection(
     };
 
     let mut expected_wal_start = startpoint;
+    // How long the latest WAL message waited for the tenant's ingest throttle, reported
+    // back in the [`PageserverFeedback`].
+    let mut ingest_throttle_wait = Duration::ZERO;
     while let Some(replication_message) = {
         select! {
             _ = cancellation.cancelled() => {
@@ -333,6 +337,38 @@ pub(super) async fn handle_walreceiver_connection(
             return Ok(());
         }
 
+        // Throttle ingest before processing the WAL. While we wait, the WAL isn't acknowledged
+        // to the safekeeper, so the write lag grows and the compute's backpressure kicks in.
+        let wal_bytes = match &replication_message {
+            ReplicationMessage::XLogData(xlog_data) => xlog_data.data().len() as u64,
+            ReplicationMessage::RawInterpretedWalRecords(raw) => {
+                // The interpreted records may be compressed and filtered down to this
+                // shard, so account for the raw WAL they were extracted from.
+                u64::from(raw.streaming_lsn()).saturating_sub(expected_wal_start.0)
+            }
+            _ => 0,
+        };
+        if wal_bytes > 0 {
+            let start = Instant::now();
+            timeline
+                .wal_ingest_throttle_metrics
+                .observe_throttle_start();
+            let throttled = select! {
+                res = timeline.wal_ingest_throttle.throttle(wal_bytes as usize, start) => res,
+                _ = cancellation.cancelled() => {
+                    debug!("walreceiver interrupted while throttled");
+                    return Ok(());
+                }
+            };
+            timeline
+                .wal_ingest_throttle_metrics
+                .observe_throttle_done(start, &throttled);
+            ingest_throttle_wait = match throttled {
+                ThrottleResult::NotThrottled { .. } => Duration::ZERO,
+                ThrottleResult::Throttled { end } => end - start,
+            };
+        }
+
         let status_update = match replication_message {
             ReplicationMessage::RawInterpretedWalRecords(raw) => {
                 WAL_INGEST.bytes_received.inc_by(raw.data().len() as u64);
@@ -708,6 +744,7 @@ async fn commit(
                 remote_consistent_lsn,
                 replytime: ts,
                 shard_number: timeline.tenant_shard_id.shard_number.0 as u32,
+                ingest_throttle_wait_us: ingest_throttle_wait.as_micros() as u64,
             };
 
             debug!("neon_status_update {status_update:?}");
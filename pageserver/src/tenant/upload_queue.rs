@@ -640,6 +640,7 @@ fn make_layer_with_size(timeline: &Arc<Timeline>, name: &str, size: usize) -> Re
             generation: timeline.generation,
             shard: timeline.get_shard_index(),
             file_size: size as u64,
+            cross_tenant_owner: None,
         };
         make_layer_with_metadata(timeline, name, metadata)
     }
@@ -1377,6 +1378,7 @@ fn make_ops(layer: ResidentLayer) -> Vec<UploadOp> {
                 shard,
                 generation: Generation::Valid(generation),
                 file_size: 0,
+                cross_tenant_owner: None,
             };
             make_layer_with_metadata(&tli, name, metadata)
         };
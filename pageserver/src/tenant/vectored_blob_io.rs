@@ -17,6 +17,7 @@
 
 use std::collections::BTreeMap;
 use std::ops::Deref;
+use std::sync::Arc;
 
 use bytes::Bytes;
 use pageserver_api::key::Key;
@@ -26,7 +27,10 @@
 use utils::vec_map::VecMap;
 
 use crate::context::RequestContext;
-use crate::tenant::blob_io::{BYTE_UNCOMPRESSED, BYTE_ZSTD, LEN_COMPRESSION_BIT_MASK};
+use crate::tenant::blob_io::{
+    BLOB_CHECKSUM_SIZE, BYTE_LZ4, BYTE_UNCOMPRESSED, BYTE_ZSTD, BYTE_ZSTD_DICT,
+    LEN_COMPRESSION_BIT_MASK, ZstdDictionary, decompress_lz4, verify_blob_checksum,
+};
 use crate::virtual_file::{self, IoBufferMut, VirtualFile};
 
 /// Metadata bundled with the start and end offset of a blob.
@@ -117,13 +121,21 @@ pub struct VectoredBlob {
     end: usize,
     /// Compression used on the the blob.
     compression_bits: u8,
+    /// Dictionary of the layer file, for blobs compressed with [`BYTE_ZSTD_DICT`].
+    zstd_dict: Option<Arc<ZstdDictionary>>,
+    /// Checksum stored after the blob, if the layer file was written with checksums.
+    checksum: Option<u32>,
 }
 
 impl VectoredBlob {
-    /// Reads a decompressed view of the blob.
+    /// Reads a decompressed view of the blob, after verifying its checksum if it has one.
     pub(crate) async fn read<'a>(&self, buf: &BufView<'a>) -> Result<BufView<'a>, std::io::Error> {
         let view = buf.view(self.start..self.end);
 
+        if let Some(checksum) = self.checksum {
+            verify_blob_checksum(&view, checksum)?;
+        }
+
         match self.compression_bits {
             BYTE_UNCOMPRESSED => Ok(view),
             BYTE_ZSTD => {
@@ -135,6 +147,25 @@ pub(crate) async fn read<'a>(&self, buf: &BufView<'a>) -> Result<BufView<'a>, st
                 // Zero-copy conversion from `Vec` to `Bytes`
                 Ok(BufView::new_bytes(Bytes::from(decompressed_vec)))
             }
+            BYTE_ZSTD_DICT => {
+                let Some(dict) = self.zstd_dict.as_ref() else {
+                    let error = std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(
+                            "Failed to decompress blob for {}@{}, {}..{}: no zstd dictionary available",
+                            self.meta.key, self.meta.lsn, self.start, self.end
+                        ),
+                    );
+                    return Err(error);
+                };
+                let mut decompressed_vec = Vec::new();
+                dict.decompress(&view, &mut decompressed_vec)?;
+                Ok(BufView::new_bytes(Bytes::from(decompressed_vec)))
+            }
+            BYTE_LZ4 => {
+                let decompressed_vec = decompress_lz4(&view)?;
+                Ok(BufView::new_bytes(Bytes::from(decompressed_vec)))
+            }
             bits => {
                 let error = std::io::Error::new(
                     std::io::ErrorKind::InvalidData,
@@ -445,11 +476,29 @@ pub fn finish(self) -> Vec<VectoredRead> {
 /// Disk reader for vectored blob spans (does not go through the page cache)
 pub struct VectoredBlobReader<'a> {
     file: &'a VirtualFile,
+    zstd_dict: Option<Arc<ZstdDictionary>>,
+    checksums: bool,
 }
 
 impl<'a> VectoredBlobReader<'a> {
     pub fn new(file: &'a VirtualFile) -> Self {
-        Self { file }
+        Self {
+            file,
+            zstd_dict: None,
+            checksums: false,
+        }
+    }
+
+    /// Use the given dictionary to decompress blobs marked with [`BYTE_ZSTD_DICT`].
+    pub fn with_zstd_dict(mut self, zstd_dict: Option<Arc<ZstdDictionary>>) -> Self {
+        self.zstd_dict = zstd_dict;
+        self
+    }
+
+    /// Whether every blob is followed by a checksum, which [`VectoredBlob::read`] verifies.
+    pub fn with_checksums(mut self, checksums: bool) -> Self {
+        self.checksums = checksums;
+        self
     }
 
     /// Read the requested blobs into the buffer.
@@ -528,11 +577,30 @@ pub async fn read_blobs(
             let start = (blob_start_in_buf + size_length) as usize;
             let end = start + blob_size as usize;
 
+            // The checksum sits between the blob and the start of the next one, so it is
+            // always covered by the read.
+            let checksum = if self.checksums {
+                let Some(checksum_buf) = buf.get(end..end + BLOB_CHECKSUM_SIZE) else {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!(
+                            "checksum of blob for {}@{} at {} extends past the read",
+                            meta.key, meta.lsn, blob_start
+                        ),
+                    ));
+                };
+                Some(u32::from_be_bytes(checksum_buf.try_into().unwrap()))
+            } else {
+                None
+            };
+
             metas.push(VectoredBlob {
                 start,
                 end,
                 meta: *meta,
                 compression_bits,
+                zstd_dict: self.zstd_dict.clone(),
+                checksum,
             });
         }
 
@@ -679,7 +747,9 @@ fn add_blob(
 mod tests {
     use anyhow::Error;
 
-    use super::super::blob_io::tests::{random_array, write_maybe_compressed};
+    use pageserver_api::models::ImageCompressionAlgorithm;
+
+    use super::super::blob_io::tests::{BlobOptions, random_array, write_blobs};
     use super::*;
     use crate::context::DownloadBehavior;
     use crate::page_cache::PAGE_SZ;
@@ -960,11 +1030,10 @@ fn streaming_planner_edge_test() {
         }
     }
 
-    async fn round_trip_test_compressed(blobs: &[Vec<u8>], compression: bool) -> Result<(), Error> {
+    async fn round_trip_test(blobs: &[Vec<u8>], opts: BlobOptions) -> Result<(), Error> {
         let ctx =
             RequestContext::new(TaskKind::UnitTest, DownloadBehavior::Error).with_scope_unit_test();
-        let (_temp_dir, pathbuf, offsets) =
-            write_maybe_compressed::<true>(blobs, compression, &ctx).await?;
+        let (_temp_dir, pathbuf, offsets) = write_blobs::<true>(blobs, opts, &ctx).await?;
 
         let file = VirtualFile::open(&pathbuf, &ctx).await?;
         let file_len = std::fs::metadata(&pathbuf)?.len();
@@ -973,7 +1042,7 @@ async fn round_trip_test_compressed(blobs: &[Vec<u8>], compression: bool) -> Res
         let reserved_bytes = blobs.iter().map(|bl| bl.len()).max().unwrap() * 2 + 16;
         let mut buf = IoBufferMut::with_capacity(reserved_bytes);
 
-        let vectored_blob_reader = VectoredBlobReader::new(&file);
+        let vectored_blob_reader = VectoredBlobReader::new(&file).with_checksums(opts.checksums);
         let meta = BlobMeta {
             key: Key::MIN,
             lsn: Lsn(0),
@@ -1012,8 +1081,17 @@ async fn test_really_big_array() -> Result<(), Error> {
             vec![0xf3; 24 * PAGE_SZ],
             b"foobar".to_vec(),
         ];
-        round_trip_test_compressed(blobs, false).await?;
-        round_trip_test_compressed(blobs, true).await?;
+        for algorithm in [
+            ImageCompressionAlgorithm::Disabled,
+            ImageCompressionAlgorithm::Zstd { level: Some(1) },
+            ImageCompressionAlgorithm::Lz4,
+        ] {
+            let opts = BlobOptions {
+                algorithm,
+                ..Default::default()
+            };
+            round_trip_test(blobs, opts).await?;
+        }
         Ok(())
     }
 
@@ -1022,8 +1100,40 @@ async fn test_arrays_inc() -> Result<(), Error> {
         let blobs = (0..PAGE_SZ / 8)
             .map(|v| random_array(v * 16))
             .collect::<Vec<_>>();
-        round_trip_test_compressed(&blobs, false).await?;
-        round_trip_test_compressed(&blobs, true).await?;
+        for algorithm in [
+            ImageCompressionAlgorithm::Disabled,
+            ImageCompressionAlgorithm::Zstd { level: Some(1) },
+            ImageCompressionAlgorithm::Lz4,
+        ] {
+            let opts = BlobOptions {
+                algorithm,
+                ..Default::default()
+            };
+            round_trip_test(&blobs, opts).await?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checksums() -> Result<(), Error> {
+        let blobs = &[
+            b"test".to_vec(),
+            random_array(10 * PAGE_SZ),
+            Vec::new(),
+            vec![0xf3; 24 * PAGE_SZ],
+            b"foobar".to_vec(),
+        ];
+        for algorithm in [
+            ImageCompressionAlgorithm::Disabled,
+            ImageCompressionAlgorithm::Zstd { level: Some(1) },
+            ImageCompressionAlgorithm::Lz4,
+        ] {
+            let opts = BlobOptions {
+                algorithm,
+                checksums: true,
+            };
+            round_trip_test(blobs, opts).await?;
+        }
         Ok(())
     }
 }
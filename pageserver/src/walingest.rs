@@ -1227,6 +1227,14 @@ async fn ingest_raw_xlog_record(
             }
         });
 
+        // The next compute on this timeline will most likely start right after the shutdown
+        // checkpoint: have its basebackup ready. Only shard zero serves basebackups.
+        if info == pg_constants::XLOG_CHECKPOINT_SHUTDOWN
+            && modification.tline.get_shard_identity().is_shard_zero()
+        {
+            modification.tline.prepare_basebackup(lsn);
+        }
+
         Ok(())
     }
 
@@ -24,12 +24,17 @@
 /// Code to apply [`NeonWalRecord`]s.
 pub(crate) mod apply_neon;
 
+/// Code to apply the most frequent Postgres WAL records without the walredo process.
+pub(crate) mod native;
+
 use std::future::Future;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
 
 use anyhow::Context;
 use bytes::{Bytes, BytesMut};
+use pageserver_api::config::WalRedoNativeMode;
 use pageserver_api::key::Key;
 use pageserver_api::models::{WalRedoManagerProcessStatus, WalRedoManagerStatus};
 use pageserver_api::record::NeonWalRecord;
@@ -41,24 +46,56 @@
 
 use crate::config::PageServerConf;
 use crate::metrics::{
-    WAL_REDO_BYTES_HISTOGRAM, WAL_REDO_PROCESS_LAUNCH_DURATION_HISTOGRAM,
-    WAL_REDO_RECORDS_HISTOGRAM, WAL_REDO_TIME,
+    WAL_REDO_BYTES_HISTOGRAM, WAL_REDO_NATIVE_FALLBACKS, WAL_REDO_NATIVE_MISMATCHES,
+    WAL_REDO_NATIVE_RECORDS, WAL_REDO_POOL_FAILED_PROCESSES, WAL_REDO_POOL_PROCESSES,
+    WAL_REDO_POOL_QUEUED_REQUESTS, WAL_REDO_POOL_QUIESCED_PROCESSES,
+    WAL_REDO_PROCESS_LAUNCH_DURATION_HISTOGRAM, WAL_REDO_RECORDS_HISTOGRAM, WAL_REDO_TIME,
 };
 
-/// The real implementation that uses a Postgres process to
+/// The real implementation that uses a pool of Postgres processes to
 /// perform WAL replay.
 ///
-/// Only one thread can use the process at a time, that is controlled by the
-/// Mutex. In the future, we might want to launch a pool of processes to allow
-/// concurrent replay of multiple records.
+/// A process applies one request at a time; concurrent requests sent to the
+/// same process queue up behind each other. To replay concurrently, the manager
+/// keeps a pool of up to [`PageServerConf::wal_redo_max_processes_per_tenant`]
+/// processes: a request that finds all launched processes busy launches another
+/// one, and only once the pool is full do requests queue up, behind the least
+/// busy process. Processes that have been idle for a while are shut down again
+/// by [`Self::maybe_quiesce`], so the pool shrinks back with demand.
 pub struct PostgresRedoManager {
     tenant_shard_id: TenantShardId,
     conf: &'static PageServerConf,
     last_redo_at: std::sync::Mutex<Option<Instant>>,
+    /// Never empty. Each slot holds at most one process, see [`PoolSlot`].
+    pool: Box<[PoolSlot]>,
+
+    /// Gate that is entered when launching a walredo process and held open
+    /// until the process has been `kill()`ed and `wait()`ed upon.
+    ///
+    /// Manager shutdown waits for this gate to close after setting the
+    /// [`ProcessOnceCell::ManagerShutDown`] state in every [`PoolSlot::redo_process`].
+    ///
+    /// This type of usage is a bit unusual because gates usually keep track of
+    /// concurrent operations, e.g., every [`Self::request_redo`] that is inflight.
+    /// But we use it here to keep track of the _processes_ that we have launched,
+    /// which may outlive any individual redo request because
+    /// - we keep walredo process around until its quiesced to amortize spawn cost and
+    /// - the Arc may be held by multiple concurrent redo requests, so, just because
+    ///   you replace the [`PoolSlot::redo_process`] cell's content doesn't mean the
+    ///   process gets killed immediately.
+    ///
+    /// We could simplify this by getting rid of the [`Arc`].
+    /// See the comment on [`PoolSlot::redo_process`] for more details.
+    launched_processes: utils::sync::gate::Gate,
+}
+
+/// One process of [`PostgresRedoManager::pool`].
+#[derive(Default)]
+struct PoolSlot {
     /// We use [`heavier_once_cell`] for
     ///
     /// 1. coalescing the lazy spawning of walredo processes ([`ProcessOnceCell::Spawned`])
-    /// 2. prevent new processes from being spawned on [`Self::shutdown`] (=> [`ProcessOnceCell::ManagerShutDown`]).
+    /// 2. prevent new processes from being spawned on [`PostgresRedoManager::shutdown`] (=> [`ProcessOnceCell::ManagerShutDown`]).
     ///
     /// # Spawning
     ///
@@ -78,30 +115,61 @@ pub struct PostgresRedoManager {
     ///
     /// # Shutdown
     ///
-    /// See [`Self::launched_processes`].
+    /// See [`PostgresRedoManager::launched_processes`].
     redo_process: heavier_once_cell::OnceCell<ProcessOnceCell>,
 
-    /// Gate that is entered when launching a walredo process and held open
-    /// until the process has been `kill()`ed and `wait()`ed upon.
-    ///
-    /// Manager shutdown waits for this gate to close after setting the
-    /// [`ProcessOnceCell::ManagerShutDown`] state in [`Self::redo_process`].
-    ///
-    /// This type of usage is a bit unusual because gates usually keep track of
-    /// concurrent operations, e.g., every [`Self::request_redo`] that is inflight.
-    /// But we use it here to keep track of the _processes_ that we have launched,
-    /// which may outlive any individual redo request because
-    /// - we keep walredo process around until its quiesced to amortize spawn cost and
-    /// - the Arc may be held by multiple concurrent redo requests, so, just because
-    ///   you replace the [`Self::redo_process`] cell's content doesn't mean the
-    ///   process gets killed immediately.
-    ///
-    /// We could simplify this by getting rid of the [`Arc`].
-    /// See the comment on [`Self::redo_process`] for more details.
-    launched_processes: utils::sync::gate::Gate,
+    /// Number of redo requests which picked this slot and have not completed yet.
+    in_flight: AtomicUsize,
+
+    /// When a redo request last picked this slot.
+    last_used_at: std::sync::Mutex<Option<Instant>>,
 }
 
-/// See [`PostgresRedoManager::redo_process`].
+impl PoolSlot {
+    fn is_launched(&self) -> bool {
+        matches!(
+            self.redo_process.get().as_deref(),
+            Some(ProcessOnceCell::Spawned(_))
+        )
+    }
+
+    /// Prevent new processes from being spawned in this slot, and drop our reference to its
+    /// current process. Returns `true` if this call was the one that did it.
+    async fn shutdown(&self) -> bool {
+        let maybe_permit = match self.redo_process.get_or_init_detached().await {
+            Ok(guard) => {
+                if matches!(&*guard, ProcessOnceCell::ManagerShutDown) {
+                    None
+                } else {
+                    let (proc, permit) = guard.take_and_deinit();
+                    drop(proc); // this just drops the Arc, its refcount may not be zero yet
+                    Some(permit)
+                }
+            }
+            Err(permit) => Some(permit),
+        };
+        if let Some(permit) = maybe_permit {
+            self.redo_process
+                .set(ProcessOnceCell::ManagerShutDown, permit);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// How a run of consecutive records of a redo request is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BatchKind {
+    /// By [`apply_neon`].
+    Neon,
+    /// By [`native`], see [`PageServerConf::wal_redo_native`].
+    Native,
+    /// By the walredo process.
+    Postgres,
+}
+
+/// See [`PoolSlot::redo_process`].
 enum ProcessOnceCell {
     Spawned(Arc<Process>),
     ManagerShutDown,
@@ -122,6 +190,12 @@ fn deref(&self) -> &Self::Target {
     }
 }
 
+impl Drop for Process {
+    fn drop(&mut self) {
+        WAL_REDO_POOL_PROCESSES.dec();
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("cancelled")]
@@ -163,47 +237,45 @@ pub async fn request_redo(
 
         let base_img_lsn = base_img.as_ref().map(|p| p.0).unwrap_or(Lsn::INVALID);
         let mut img = base_img.map(|p| p.1);
-        let mut batch_neon = apply_neon::can_apply_in_neon(&records[0].1);
+        // decode each record once, for both the choice of the batch and its native redo
+        let (kinds, native_records): (Vec<_>, Vec<_>) = records
+            .iter()
+            .map(|(_, record)| self.batch_kind(key, record, pg_version))
+            .unzip();
+        let mut batch_kind = kinds[0];
         let mut batch_start = 0;
-        for (i, record) in records.iter().enumerate().skip(1) {
-            let rec_neon = apply_neon::can_apply_in_neon(&record.1);
-
-            if rec_neon != batch_neon {
-                let result = if batch_neon {
-                    self.apply_batch_neon(key, lsn, img, &records[batch_start..i])
-                } else {
-                    self.apply_batch_postgres(
+        for (i, &rec_kind) in kinds.iter().enumerate().skip(1) {
+            if rec_kind != batch_kind {
+                let result = self
+                    .apply_batch(
+                        batch_kind,
                         key,
                         lsn,
                         img,
                         base_img_lsn,
                         &records[batch_start..i],
-                        self.conf.wal_redo_timeout,
+                        &native_records[batch_start..i],
                         pg_version,
                     )
-                    .await
-                };
+                    .await;
                 img = Some(result?);
 
-                batch_neon = rec_neon;
+                batch_kind = rec_kind;
                 batch_start = i;
             }
         }
         // last batch
-        if batch_neon {
-            self.apply_batch_neon(key, lsn, img, &records[batch_start..])
-        } else {
-            self.apply_batch_postgres(
-                key,
-                lsn,
-                img,
-                base_img_lsn,
-                &records[batch_start..],
-                self.conf.wal_redo_timeout,
-                pg_version,
-            )
-            .await
-        }
+        self.apply_batch(
+            batch_kind,
+            key,
+            lsn,
+            img,
+            base_img_lsn,
+            &records[batch_start..],
+            &native_records[batch_start..],
+            pg_version,
+        )
+        .await
     }
 
     /// Do a ping request-response roundtrip.
@@ -223,6 +295,18 @@ pub async fn ping(&self, pg_version: u32) -> Result<(), Error> {
     }
 
     pub fn status(&self) -> WalRedoManagerStatus {
+        let processes: Vec<_> = self
+            .pool
+            .iter()
+            .filter_map(|slot| {
+                slot.redo_process.get().and_then(|p| match &*p {
+                    ProcessOnceCell::Spawned(p) => {
+                        Some(WalRedoManagerProcessStatus { pid: p.id() })
+                    }
+                    ProcessOnceCell::ManagerShutDown => None,
+                })
+            })
+            .collect();
         WalRedoManagerStatus {
             last_redo_at: {
                 let at = *self.last_redo_at.lock().unwrap();
@@ -232,10 +316,8 @@ pub fn status(&self) -> WalRedoManagerStatus {
                     chrono::Utc::now().checked_sub_signed(chrono::Duration::from_std(age).ok()?)
                 })
             },
-            process: self.redo_process.get().and_then(|p| match &*p {
-                ProcessOnceCell::Spawned(p) => Some(WalRedoManagerProcessStatus { pid: p.id() }),
-                ProcessOnceCell::ManagerShutDown => None,
-            }),
+            process: processes.first().cloned(),
+            processes,
         }
     }
 }
@@ -253,7 +335,9 @@ pub fn new(
             tenant_shard_id,
             conf,
             last_redo_at: std::sync::Mutex::default(),
-            redo_process: heavier_once_cell::OnceCell::default(),
+            pool: (0..conf.wal_redo_max_processes_per_tenant.get())
+                .map(|_| PoolSlot::default())
+                .collect(),
             launched_processes: utils::sync::gate::Gate::default(),
         }
     }
@@ -267,32 +351,20 @@ pub fn new(
     /// - no redo process is running
     /// - no new redo process will be spawned
     /// - redo requests that need walredo process will fail with [`Error::Cancelled`]
-    /// - [`apply_neon`]-only redo requests may still work, but this may change in the future
+    /// - [`apply_neon`]-only and [`native`]-only redo requests may still work, but this may change in the future
     ///
     /// # Cancel-Safety
     ///
     /// This method is cancellation-safe.
     pub async fn shutdown(&self) -> bool {
-        // prevent new processes from being spawned
-        let maybe_permit = match self.redo_process.get_or_init_detached().await {
-            Ok(guard) => {
-                if matches!(&*guard, ProcessOnceCell::ManagerShutDown) {
-                    None
-                } else {
-                    let (proc, permit) = guard.take_and_deinit();
-                    drop(proc); // this just drops the Arc, its refcount may not be zero yet
-                    Some(permit)
-                }
-            }
-            Err(permit) => Some(permit),
-        };
-        let it_was_us = if let Some(permit) = maybe_permit {
-            self.redo_process
-                .set(ProcessOnceCell::ManagerShutDown, permit);
-            true
-        } else {
-            false
-        };
+        // prevent new processes from being spawned. every caller goes through the slots in the
+        // same order, so the first slot decides which call initiated the shutdown.
+        let mut it_was_us = None;
+        for slot in self.pool.iter() {
+            let shut_down = slot.shutdown().await;
+            it_was_us.get_or_insert(shut_down);
+        }
+        let it_was_us = it_was_us.expect("pool is never empty");
         // wait for ongoing requests to drain and the refcounts of all Arc<WalRedoProcess> that
         // we ever launched to drop to zero, which when it happens synchronously kill()s & wait()s
         // for the underlying process.
@@ -303,15 +375,58 @@ pub async fn shutdown(&self) -> bool {
     /// This type doesn't have its own background task to check for idleness: we
     /// rely on our owner calling this function periodically in its own housekeeping
     /// loops.
+    ///
+    /// Every process of the pool is shut down on its own once no request has picked it for
+    /// `idle_timeout`.
     pub(crate) fn maybe_quiesce(&self, idle_timeout: Duration) {
-        if let Ok(g) = self.last_redo_at.try_lock() {
-            if let Some(last_redo_at) = *g {
-                if last_redo_at.elapsed() >= idle_timeout {
-                    drop(g);
-                    drop(self.redo_process.get().map(|guard| guard.take_and_deinit()));
+        for slot in self.pool.iter() {
+            if slot.in_flight.load(Ordering::Relaxed) > 0 {
+                continue;
+            }
+            let Ok(g) = slot.last_used_at.try_lock() else {
+                continue;
+            };
+            if !g.is_some_and(|last_used_at| last_used_at.elapsed() >= idle_timeout) {
+                continue;
+            }
+            drop(g);
+            let Some(guard) = slot.redo_process.get() else {
+                continue;
+            };
+            if matches!(&*guard, ProcessOnceCell::Spawned(_)) {
+                let (proc, _permit) = guard.take_and_deinit();
+                drop(proc);
+                WAL_REDO_POOL_QUIESCED_PROCESSES.inc();
+            }
+        }
+    }
+
+    /// Pick the pool slot for a redo request: an idle launched process if there is one,
+    /// otherwise an empty slot so that the pool grows, and only once the pool is full, the
+    /// least busy process.
+    fn pick_slot(&self) -> &PoolSlot {
+        let mut empty = None;
+        let mut least_busy: Option<(&PoolSlot, usize)> = None;
+        for slot in self.pool.iter() {
+            let in_flight = slot.in_flight.load(Ordering::Relaxed);
+            if slot.is_launched() {
+                if in_flight == 0 {
+                    return slot;
                 }
+            } else if in_flight == 0 {
+                // nobody is launching a process in this slot
+                empty.get_or_insert(slot);
+                continue;
             }
+            if least_busy.is_none_or(|(_, busiest)| in_flight < busiest) {
+                least_busy = Some((slot, in_flight));
+            }
+        }
+        if let Some(slot) = empty {
+            return slot;
         }
+        WAL_REDO_POOL_QUEUED_REQUESTS.inc();
+        least_busy.expect("pool is never empty").0
     }
 
     /// # Cancel-Safety
@@ -326,7 +441,14 @@ async fn do_with_walredo_process<
         pg_version: u32,
         closure: F,
     ) -> Result<O, Error> {
-        let proc: Arc<Process> = match self.redo_process.get_or_init_detached().await {
+        let slot = self.pick_slot();
+        slot.in_flight.fetch_add(1, Ordering::Relaxed);
+        scopeguard::defer! {
+            *slot.last_used_at.lock().unwrap() = Some(Instant::now());
+            slot.in_flight.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        let proc: Arc<Process> = match slot.redo_process.get_or_init_detached().await {
             Ok(guard) => match &*guard {
                 ProcessOnceCell::Spawned(proc) => Arc::clone(proc),
                 ProcessOnceCell::ManagerShutDown => {
@@ -352,6 +474,7 @@ async fn do_with_walredo_process<
                     .context("launch walredo process")?,
                     _launched_processes_guard,
                 });
+                WAL_REDO_POOL_PROCESSES.inc();
                 let duration = start.elapsed();
                 WAL_REDO_PROCESS_LAUNCH_DURATION_HISTOGRAM.observe(duration.as_secs_f64());
                 info!(
@@ -359,7 +482,7 @@ async fn do_with_walredo_process<
                     pid = proc.id(),
                     "launched walredo process"
                 );
-                self.redo_process
+                slot.redo_process
                     .set(ProcessOnceCell::Spawned(Arc::clone(&proc)), permit);
                 proc
             }
@@ -372,7 +495,7 @@ async fn do_with_walredo_process<
             // Avoid concurrent callers hitting the same issue by taking `proc` out of the rotation.
             // Note that there may be other tasks concurrent with us that also hold `proc`.
             // We have to deal with that here.
-            // Also read the doc comment on field `self.redo_process`.
+            // Also read the doc comment on field `PoolSlot::redo_process`.
             //
             // NB: there may still be other concurrent threads using `proc`.
             // The last one will send SIGKILL when the underlying Arc reaches refcount 0.
@@ -384,7 +507,7 @@ async fn do_with_walredo_process<
             // than we can SIGKILL & `wait` for them to exit. By doing it the way we do here,
             // we limit this risk of run-away to at most $num_runtimes * $num_executor_threads.
             // This probably needs revisiting at some later point.
-            match self.redo_process.get() {
+            match slot.redo_process.get() {
                 None => (),
                 Some(guard) => {
                     match &*guard {
@@ -393,6 +516,7 @@ async fn do_with_walredo_process<
                             if Arc::ptr_eq(&proc, guard_proc) {
                                 // We're the first to observe an error from `proc`, it's our job to take it out of rotation.
                                 guard.take_and_deinit();
+                                WAL_REDO_POOL_FAILED_PROCESSES.inc();
                             } else {
                                 // Another task already spawned another redo process (further up in this method)
                                 // and put it into `redo_process`. Do nothing, our view of the world is behind.
@@ -408,6 +532,119 @@ async fn do_with_walredo_process<
         result
     }
 
+    /// Also returns the record decoded for [`native::apply_natively`], for native batches.
+    fn batch_kind(
+        &self,
+        key: Key,
+        record: &NeonWalRecord,
+        pg_version: u32,
+    ) -> (BatchKind, Option<native::NativeWalRecord>) {
+        if apply_neon::can_apply_in_neon(record) {
+            return (BatchKind::Neon, None);
+        }
+        if self.conf.wal_redo_native != WalRedoNativeMode::Disabled {
+            if let Some(native) = native::decode_natively(key, record, pg_version) {
+                return (BatchKind::Native, Some(native));
+            }
+        }
+        (BatchKind::Postgres, None)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn apply_batch(
+        &self,
+        kind: BatchKind,
+        key: Key,
+        lsn: Lsn,
+        base_img: Option<Bytes>,
+        base_img_lsn: Lsn,
+        records: &[(Lsn, NeonWalRecord)],
+        native_records: &[Option<native::NativeWalRecord>],
+        pg_version: u32,
+    ) -> Result<Bytes, Error> {
+        match (kind, base_img) {
+            (BatchKind::Neon, base_img) => self.apply_batch_neon(key, lsn, base_img, records),
+            (BatchKind::Native, Some(base_img))
+                if self.conf.wal_redo_native == WalRedoNativeMode::Verify =>
+            {
+                let native =
+                    self.apply_batch_native(key, lsn, base_img.clone(), records, native_records);
+                let postgres = self
+                    .apply_batch_postgres(
+                        key,
+                        lsn,
+                        Some(base_img),
+                        base_img_lsn,
+                        records,
+                        self.conf.wal_redo_timeout,
+                        pg_version,
+                    )
+                    .await?;
+                match native {
+                    Ok(native) if native == postgres => {}
+                    Ok(native) => {
+                        WAL_REDO_NATIVE_MISMATCHES.inc();
+                        let first_difference =
+                            native.iter().zip(postgres.iter()).position(|(a, b)| a != b);
+                        error!(
+                            "native redo of {} WAL records {}..{} to key {key} at LSN {lsn} differs from the walredo process, first at byte {:?}",
+                            records.len(),
+                            records.first().map(|p| p.0).unwrap_or(Lsn(0)),
+                            records.last().map(|p| p.0).unwrap_or(Lsn(0)),
+                            first_difference,
+                        );
+                    }
+                    Err(e) => {
+                        WAL_REDO_NATIVE_MISMATCHES.inc();
+                        error!(
+                            "native redo of {} WAL records to key {key} at LSN {lsn} failed, but the walredo process succeeded: {e:#}",
+                            records.len(),
+                        );
+                    }
+                }
+                Ok(postgres)
+            }
+            (BatchKind::Native, Some(base_img)) => {
+                match self.apply_batch_native(key, lsn, base_img.clone(), records, native_records) {
+                    Ok(page) => Ok(page),
+                    Err(e) => {
+                        // The walredo process remains the reference: a record that native redo
+                        // can't handle must not fail the read.
+                        WAL_REDO_NATIVE_FALLBACKS.inc();
+                        warn!(
+                            "native redo of {} WAL records to key {key} at LSN {lsn} failed, falling back to the walredo process: {e:#}",
+                            records.len(),
+                        );
+                        self.apply_batch_postgres(
+                            key,
+                            lsn,
+                            Some(base_img),
+                            base_img_lsn,
+                            records,
+                            self.conf.wal_redo_timeout,
+                            pg_version,
+                        )
+                        .await
+                    }
+                }
+            }
+            // Native redo modifies an existing page. Without one, the walredo process does
+            // whatever Postgres would do.
+            (BatchKind::Native | BatchKind::Postgres, base_img) => {
+                self.apply_batch_postgres(
+                    key,
+                    lsn,
+                    base_img,
+                    base_img_lsn,
+                    records,
+                    self.conf.wal_redo_timeout,
+                    pg_version,
+                )
+                .await
+            }
+        }
+    }
+
     ///
     /// Process one request for WAL redo using wal-redo postgres
     ///
@@ -533,6 +770,43 @@ fn apply_batch_neon(
         Ok(page.freeze())
     }
 
+    ///
+    /// Process a batch of Postgres WAL records using the Rust implementation of their redo.
+    ///
+    fn apply_batch_native(
+        &self,
+        key: Key,
+        lsn: Lsn,
+        base_img: Bytes,
+        records: &[(Lsn, NeonWalRecord)],
+        native_records: &[Option<native::NativeWalRecord>],
+    ) -> Result<Bytes, Error> {
+        let start_time = Instant::now();
+
+        let mut page = BytesMut::from(&base_img[..]);
+        for ((record_lsn, _), native) in records.iter().zip(native_records) {
+            let native = native
+                .as_ref()
+                .context("WAL record of a native batch was not decoded")?;
+            native::apply_natively(native, *record_lsn, &mut page).with_context(|| {
+                format!("apply WAL record at {record_lsn} natively to key {key}")
+            })?;
+        }
+        WAL_REDO_NATIVE_RECORDS.inc_by(records.len() as u64);
+
+        let duration = start_time.elapsed();
+        WAL_REDO_TIME.observe(duration.as_secs_f64());
+
+        debug!(
+            "natively applied {} WAL records in {} us to reconstruct page image at LSN {}",
+            records.len(),
+            duration.as_micros(),
+            lsn
+        );
+
+        Ok(page.freeze())
+    }
+
     fn apply_record_neon(
         &self,
         key: Key,
@@ -548,7 +822,9 @@ fn apply_record_neon(
 
 #[cfg(test)]
 mod tests {
+    use std::num::NonZeroUsize;
     use std::str::FromStr;
+    use std::time::Duration;
 
     use bytes::Bytes;
     use pageserver_api::key::Key;
@@ -631,6 +907,43 @@ async fn short_v14_fails_for_wrong_key_but_returns_zero_page() {
         assert_eq!(page, crate::ZERO_PAGE);
     }
 
+    #[tokio::test]
+    async fn pool_grows_with_concurrent_requests() {
+        let expected = std::fs::read("test_data/short_v14_redo.page").unwrap();
+
+        let h = RedoHarness::with_max_processes(2).unwrap();
+
+        let redo = || {
+            h.manager
+                .request_redo(
+                    Key {
+                        field1: 0,
+                        field2: 1663,
+                        field3: 13010,
+                        field4: 1259,
+                        field5: 0,
+                        field6: 0,
+                    },
+                    Lsn::from_str("0/16E2408").unwrap(),
+                    None,
+                    short_records(),
+                    14,
+                )
+                .instrument(h.span())
+        };
+
+        // the first two requests find no idle process and launch one each, the third one
+        // queues up behind one of them
+        let (a, b, c) = tokio::join!(redo(), redo(), redo());
+        for page in [a, b, c] {
+            assert_eq!(&expected, &*page.unwrap());
+        }
+        assert_eq!(h.manager.status().processes.len(), 2);
+
+        h.manager.maybe_quiesce(Duration::ZERO);
+        assert!(h.manager.status().processes.is_empty());
+    }
+
     #[tokio::test]
     async fn test_stderr() {
         let h = RedoHarness::new().unwrap();
@@ -677,10 +990,15 @@ struct RedoHarness {
 
     impl RedoHarness {
         fn new() -> anyhow::Result<Self> {
+            Self::with_max_processes(1)
+        }
+
+        fn with_max_processes(max_processes: usize) -> anyhow::Result<Self> {
             crate::tenant::harness::setup_logging();
 
             let repo_dir = camino_tempfile::tempdir()?;
-            let conf = PageServerConf::dummy_conf(repo_dir.path().to_path_buf());
+            let mut conf = PageServerConf::dummy_conf(repo_dir.path().to_path_buf());
+            conf.wal_redo_max_processes_per_tenant = NonZeroUsize::new(max_processes).unwrap();
             let conf = Box::leak(Box::new(conf));
             let tenant_shard_id = TenantShardId::unsharded(TenantId::generate());
 
@@ -0,0 +1,1401 @@
+//! Redo of the most frequent Postgres WAL records in Rust.
+//!
+//! Every Postgres WAL record applied here saves a round trip to the walredo process. The code
+//! is a port of the redo routines of `pgxn/neon_rmgr/neon_rmgr.c` and of `btree_xlog_insert`,
+//! limited to the records which modify an existing page in place:
+//!
+//! - the heap insert, multi-insert, delete, update and lock records: those of the neon rmgr,
+//!   which Postgres 16 and 17 computes write instead of the `RM_HEAP_ID` and `RM_HEAP2_ID` ones,
+//!   and those of the `RM_HEAP_ID` and `RM_HEAP2_ID` rmgrs of the Postgres 14 and 15 forks,
+//! - btree leaf inserts, for all Postgres versions.
+//!
+//! The Postgres 14 and 15 forks log the command id of delete and lock records in the main data
+//! ([`v14::XlHeapDelete`], [`v14::XlHeapLock`]), and that of inserted and updated tuples in the
+//! tuple header of the block data, like the neon rmgr. Multi-insert records don't carry one:
+//! like in vanilla Postgres, the tuples get `FirstCommandId`.
+//!
+//! Records which initialize the page, carry a full-page image of it, or are of any other type
+//! are left to the walredo process, see [`decode_natively`].
+//!
+//! The walredo process remains the reference implementation. With
+//! [`WalRedoNativeMode::Enabled`], batches that fail here are retried with the walredo process.
+//! With [`WalRedoNativeMode::Verify`], requests are served by both, and differences reported.
+//!
+//! [`WalRedoNativeMode::Enabled`]: pageserver_api::config::WalRedoNativeMode::Enabled
+//! [`WalRedoNativeMode::Verify`]: pageserver_api::config::WalRedoNativeMode::Verify
+
+use anyhow::{Context, ensure};
+use bytes::{Buf, Bytes, BytesMut};
+use pageserver_api::key::Key;
+use pageserver_api::record::NeonWalRecord;
+use postgres_ffi::walrecord::v17::rm_neon::{
+    XlNeonHeapDelete, XlNeonHeapHeader, XlNeonHeapInsert, XlNeonHeapLock, XlNeonHeapMultiInsert,
+    XlNeonHeapUpdate, XlNeonMultiInsertTuple,
+};
+use postgres_ffi::walrecord::{DecodedWALRecord, decode_wal_record, v14};
+use postgres_ffi::{BLCKSZ, TransactionId, pg_constants, transaction_id_precedes};
+use utils::lsn::Lsn;
+
+//
+// From bufpage.h
+//
+const SIZE_OF_PAGE_HEADER_DATA: usize = 24;
+const PD_FLAGS_OFFSET: usize = 10;
+const PD_LOWER_OFFSET: usize = 12;
+const PD_UPPER_OFFSET: usize = 14;
+const PD_SPECIAL_OFFSET: usize = 16;
+const PD_PRUNE_XID_OFFSET: usize = 20;
+const PD_ALL_VISIBLE: u16 = 0x0004;
+const SIZE_OF_ITEM_ID_DATA: usize = 4;
+const LP_UNUSED: u32 = 0;
+const LP_NORMAL: u32 = 1;
+
+//
+// From htup_details.h
+//
+const SIZEOF_HEAP_TUPLE_HEADER: usize = 23;
+const MAX_HEAP_TUPLES_PER_PAGE: u16 = ((BLCKSZ as usize - SIZE_OF_PAGE_HEADER_DATA)
+    / (maxalign(SIZEOF_HEAP_TUPLE_HEADER) + SIZE_OF_ITEM_ID_DATA))
+    as u16;
+const T_XMIN_OFFSET: usize = 0;
+const T_XMAX_OFFSET: usize = 4;
+const T_CID_OFFSET: usize = 8;
+const T_CTID_OFFSET: usize = 12;
+const T_INFOMASK2_OFFSET: usize = 18;
+const T_INFOMASK_OFFSET: usize = 20;
+const T_HOFF_OFFSET: usize = 22;
+
+const HEAP_XMAX_KEYSHR_LOCK: u16 = 0x0010;
+const HEAP_COMBOCID: u16 = 0x0020;
+const HEAP_XMAX_EXCL_LOCK: u16 = 0x0040;
+const HEAP_XMAX_LOCK_ONLY: u16 = 0x0080;
+const HEAP_XMAX_SHR_LOCK: u16 = HEAP_XMAX_EXCL_LOCK | HEAP_XMAX_KEYSHR_LOCK;
+const HEAP_LOCK_MASK: u16 = HEAP_XMAX_SHR_LOCK | HEAP_XMAX_EXCL_LOCK | HEAP_XMAX_KEYSHR_LOCK;
+const HEAP_XMAX_COMMITTED: u16 = 0x0400;
+const HEAP_XMAX_INVALID: u16 = 0x0800;
+const HEAP_XMAX_IS_MULTI: u16 = 0x1000;
+const HEAP_MOVED_OFF: u16 = 0x4000;
+const HEAP_MOVED_IN: u16 = 0x8000;
+const HEAP_MOVED: u16 = HEAP_MOVED_OFF | HEAP_MOVED_IN;
+const HEAP_XMAX_BITS: u16 = HEAP_XMAX_COMMITTED
+    | HEAP_XMAX_INVALID
+    | HEAP_XMAX_IS_MULTI
+    | HEAP_LOCK_MASK
+    | HEAP_XMAX_LOCK_ONLY;
+const HEAP_KEYS_UPDATED: u16 = 0x2000;
+const HEAP_HOT_UPDATED: u16 = 0x4000;
+
+// From itemptr.h
+const MOVED_PARTITIONS_BLOCK_NUMBER: u32 = u32::MAX;
+const MOVED_PARTITIONS_OFFSET_NUMBER: u16 = 0xfffd;
+
+// From c.h
+const FIRST_COMMAND_ID: u32 = 0;
+
+/// The records we know how to apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NativeRecord {
+    HeapInsert,
+    HeapMultiInsert,
+    HeapDelete,
+    HeapUpdate { hot: bool },
+    HeapLock,
+    BtreeInsertLeaf,
+}
+
+impl NativeRecord {
+    /// Size of the record struct at the start of the main data.
+    fn main_data_len(&self, pg_version: u32) -> usize {
+        match (self, pg_version) {
+            (NativeRecord::HeapInsert, _) => 3,
+            (NativeRecord::HeapMultiInsert, 14 | 15) => 4,
+            (NativeRecord::HeapMultiInsert, _) => 8,
+            (NativeRecord::HeapDelete, 14 | 15) => 14,
+            (NativeRecord::HeapDelete, _) => 12,
+            (NativeRecord::HeapUpdate { .. }, _) => 18,
+            (NativeRecord::HeapLock, 14 | 15) => 14,
+            (NativeRecord::HeapLock, _) => 12,
+            (NativeRecord::BtreeInsertLeaf, _) => 2,
+        }
+    }
+}
+
+/// A WAL record which [`apply_natively`] can apply to the page of a key, decoded once by
+/// [`decode_natively`].
+pub(crate) struct NativeWalRecord {
+    kind: NativeRecord,
+    decoded: DecodedWALRecord,
+    /// Index of the block reference to the page the record is applied to.
+    block: usize,
+    pg_version: u32,
+}
+
+/// Decodes `rec` if it can be applied to the page of `key` by [`apply_natively`]. Returns `None`
+/// if we need to pass it to the walredo process.
+pub(crate) fn decode_natively(
+    key: Key,
+    rec: &NeonWalRecord,
+    pg_version: u32,
+) -> Option<NativeWalRecord> {
+    let NeonWalRecord::Postgres {
+        will_init: false,
+        rec,
+    } = rec
+    else {
+        return None;
+    };
+    let (rel, blknum) = key.to_rel_block().ok()?;
+
+    let mut decoded = DecodedWALRecord::default();
+    decode_wal_record(rec.clone(), &mut decoded, pg_version).ok()?;
+
+    let info = decoded.xl_info & pg_constants::XLR_RMGR_INFO_MASK;
+    let kind = match (decoded.xl_rmid, pg_version) {
+        (pg_constants::RM_NEON_ID, 16 | 17) => {
+            if info & pg_constants::XLOG_NEON_HEAP_INIT_PAGE != 0 {
+                return None;
+            }
+            match info & pg_constants::XLOG_HEAP_OPMASK {
+                pg_constants::XLOG_NEON_HEAP_INSERT => NativeRecord::HeapInsert,
+                pg_constants::XLOG_NEON_HEAP_MULTI_INSERT => NativeRecord::HeapMultiInsert,
+                pg_constants::XLOG_NEON_HEAP_DELETE => NativeRecord::HeapDelete,
+                pg_constants::XLOG_NEON_HEAP_UPDATE => NativeRecord::HeapUpdate { hot: false },
+                pg_constants::XLOG_NEON_HEAP_HOT_UPDATE => NativeRecord::HeapUpdate { hot: true },
+                pg_constants::XLOG_NEON_HEAP_LOCK => NativeRecord::HeapLock,
+                _ => return None,
+            }
+        }
+        (pg_constants::RM_HEAP_ID, 14 | 15) => {
+            if info & pg_constants::XLOG_HEAP_INIT_PAGE != 0 {
+                return None;
+            }
+            match info & pg_constants::XLOG_HEAP_OPMASK {
+                pg_constants::XLOG_HEAP_INSERT => NativeRecord::HeapInsert,
+                pg_constants::XLOG_HEAP_DELETE => NativeRecord::HeapDelete,
+                pg_constants::XLOG_HEAP_UPDATE => NativeRecord::HeapUpdate { hot: false },
+                pg_constants::XLOG_HEAP_HOT_UPDATE => NativeRecord::HeapUpdate { hot: true },
+                pg_constants::XLOG_HEAP_LOCK => NativeRecord::HeapLock,
+                _ => return None,
+            }
+        }
+        (pg_constants::RM_HEAP2_ID, 14 | 15) => {
+            if info & pg_constants::XLOG_HEAP_INIT_PAGE != 0 {
+                return None;
+            }
+            match info & pg_constants::XLOG_HEAP_OPMASK {
+                pg_constants::XLOG_HEAP2_MULTI_INSERT => NativeRecord::HeapMultiInsert,
+                _ => return None,
+            }
+        }
+        (pg_constants::RM_BTREE_ID, _) if info == pg_constants::XLOG_BTREE_INSERT_LEAF => {
+            NativeRecord::BtreeInsertLeaf
+        }
+        _ => return None,
+    };
+
+    let block = decoded.blocks.iter().position(|blk| {
+        blk.rnode_spcnode == rel.spcnode
+            && blk.rnode_dbnode == rel.dbnode
+            && blk.rnode_relnode == rel.relnode
+            && blk.forknum == rel.forknum
+            && blk.blkno == blknum
+    })?;
+    // a btree leaf insert only modifies its first block
+    if kind == NativeRecord::BtreeInsertLeaf && block != 0 {
+        return None;
+    }
+    let blk = &decoded.blocks[block];
+    if blk.has_image || blk.will_init {
+        return None;
+    }
+
+    Some(NativeWalRecord {
+        kind,
+        decoded,
+        block,
+        pg_version,
+    })
+}
+
+pub(crate) fn apply_natively(
+    record: &NativeWalRecord,
+    lsn: Lsn,
+    page: &mut BytesMut,
+) -> anyhow::Result<()> {
+    let NativeWalRecord {
+        kind,
+        ref decoded,
+        block,
+        pg_version,
+    } = *record;
+    ensure!(
+        page.len() == BLCKSZ as usize,
+        "unexpected page size {}",
+        page.len()
+    );
+
+    // Like XLogReadBufferForRedo: the page already contains the changes of the record.
+    if postgres_ffi::page_get_lsn(page) >= lsn {
+        return Ok(());
+    }
+
+    let mut main_data = decoded.record.slice(decoded.main_data_offset..);
+    ensure!(
+        main_data.len() >= kind.main_data_len(pg_version),
+        "main data of {kind:?} record too short: {} bytes",
+        main_data.len()
+    );
+    let blk = &decoded.blocks[block];
+    let block_data = if blk.has_data {
+        let start = blk.data_offset as usize;
+        decoded.record.slice(start..start + blk.data_len as usize)
+    } else {
+        Bytes::new()
+    };
+    let blkno = blk.blkno;
+    let xid = decoded.xl_xid;
+
+    // The insert and update records have the same layout in all versions.
+    match kind {
+        NativeRecord::HeapInsert => {
+            let xlrec = XlNeonHeapInsert::decode(&mut main_data);
+            heap_insert(page, blkno, xid, xlrec, block_data)?
+        }
+        NativeRecord::HeapMultiInsert => {
+            let xlrec = match pg_version {
+                14 | 15 => {
+                    let xlrec = v14::XlHeapMultiInsert::decode(&mut main_data);
+                    XlNeonHeapMultiInsert {
+                        flags: xlrec.flags,
+                        _padding: xlrec._padding,
+                        ntuples: xlrec.ntuples,
+                        t_cid: FIRST_COMMAND_ID,
+                    }
+                }
+                _ => XlNeonHeapMultiInsert::decode(&mut main_data),
+            };
+            heap_multi_insert(page, blkno, xid, xlrec, main_data, block_data)?
+        }
+        NativeRecord::HeapDelete => {
+            let xlrec = match pg_version {
+                14 | 15 => {
+                    let xlrec = v14::XlHeapDelete::decode(&mut main_data);
+                    XlNeonHeapDelete {
+                        xmax: xlrec.xmax,
+                        offnum: xlrec.offnum,
+                        infobits_set: xlrec.infobits_set,
+                        flags: xlrec.flags,
+                        t_cid: xlrec.t_cid,
+                    }
+                }
+                _ => XlNeonHeapDelete::decode(&mut main_data),
+            };
+            heap_delete(page, blkno, xid, xlrec)?
+        }
+        NativeRecord::HeapUpdate { hot } => {
+            let xlrec = XlNeonHeapUpdate::decode(&mut main_data);
+            // block 0 is the page of the new tuple, block 1 the page of the old tuple, if the
+            // update moved the tuple to another page.
+            let new_blkno = decoded.blocks[0].blkno;
+            let (is_old_page, is_new_page) = match (block, decoded.blocks.len()) {
+                (0, 1) => (true, true),
+                (0, _) => (false, true),
+                _ => (true, false),
+            };
+            heap_update(
+                page,
+                new_blkno,
+                xid,
+                hot,
+                is_old_page,
+                is_new_page,
+                xlrec,
+                block_data,
+            )?
+        }
+        NativeRecord::HeapLock => {
+            let xlrec = match pg_version {
+                14 | 15 => {
+                    let xlrec = v14::XlHeapLock::decode(&mut main_data);
+                    XlNeonHeapLock {
+                        locking_xid: xlrec.locking_xid,
+                        t_cid: xlrec.t_cid,
+                        offnum: xlrec.offnum,
+                        infobits_set: xlrec.infobits_set,
+                        flags: xlrec.flags,
+                    }
+                }
+                _ => XlNeonHeapLock::decode(&mut main_data),
+            };
+            heap_lock(page, blkno, xlrec)?
+        }
+        NativeRecord::BtreeInsertLeaf => {
+            let offnum = main_data.get_u16_le();
+            page_add_item(page, &block_data, offnum, false, false)?;
+        }
+    }
+
+    postgres_ffi::page_set_lsn(page, lsn);
+    Ok(())
+}
+
+fn heap_insert(
+    page: &mut [u8],
+    blkno: u32,
+    xid: TransactionId,
+    xlrec: XlNeonHeapInsert,
+    mut block_data: Bytes,
+) -> anyhow::Result<()> {
+    ensure!(
+        page_max_offset(page) + 1 >= xlrec.offnum,
+        "invalid max offset number"
+    );
+
+    ensure!(
+        block_data.remaining() > XlNeonHeapHeader::SIZE,
+        "tuple too short"
+    );
+    let xlhdr = XlNeonHeapHeader::decode(&mut block_data);
+    let mut tuple = vec![0u8; SIZEOF_HEAP_TUPLE_HEADER];
+    tuple.extend_from_slice(&block_data);
+    set_u16(&mut tuple, T_INFOMASK2_OFFSET, xlhdr.t_infomask2);
+    set_u16(&mut tuple, T_INFOMASK_OFFSET, xlhdr.t_infomask);
+    tuple[T_HOFF_OFFSET] = xlhdr.t_hoff;
+    set_u32(&mut tuple, T_XMIN_OFFSET, xid);
+    set_u32(&mut tuple, T_CID_OFFSET, xlhdr.t_cid);
+    set_ctid(&mut tuple, blkno, xlrec.offnum);
+
+    page_add_item(page, &tuple, xlrec.offnum, true, true)?;
+
+    if xlrec.flags & pg_constants::XLH_INSERT_ALL_VISIBLE_CLEARED != 0 {
+        page_clear_all_visible(page);
+    }
+    if xlrec.flags & pg_constants::XLH_INSERT_ALL_FROZEN_SET != 0 {
+        page_set_all_visible(page);
+    }
+    Ok(())
+}
+
+fn heap_multi_insert(
+    page: &mut [u8],
+    blkno: u32,
+    xid: TransactionId,
+    xlrec: XlNeonHeapMultiInsert,
+    mut offsets: Bytes,
+    block_data: Bytes,
+) -> anyhow::Result<()> {
+    ensure!(
+        offsets.remaining() >= 2 * xlrec.ntuples as usize,
+        "offsets array too short"
+    );
+
+    let mut pos = 0;
+    for _ in 0..xlrec.ntuples {
+        let offnum = offsets.get_u16_le();
+        ensure!(
+            page_max_offset(page) + 1 >= offnum,
+            "invalid max offset number"
+        );
+
+        // tuple headers are SHORTALIGNed
+        pos += pos % 2;
+        ensure!(
+            pos + XlNeonMultiInsertTuple::SIZE <= block_data.len(),
+            "tuple data too short"
+        );
+        let xlhdr = XlNeonMultiInsertTuple::decode(&mut block_data.slice(pos..));
+        pos += XlNeonMultiInsertTuple::SIZE;
+        let datalen = xlhdr.datalen as usize;
+        ensure!(pos + datalen <= block_data.len(), "tuple data too short");
+
+        let mut tuple = vec![0u8; SIZEOF_HEAP_TUPLE_HEADER];
+        tuple.extend_from_slice(&block_data[pos..pos + datalen]);
+        pos += datalen;
+        set_u16(&mut tuple, T_INFOMASK2_OFFSET, xlhdr.t_infomask2);
+        set_u16(&mut tuple, T_INFOMASK_OFFSET, xlhdr.t_infomask);
+        tuple[T_HOFF_OFFSET] = xlhdr.t_hoff;
+        set_u32(&mut tuple, T_XMIN_OFFSET, xid);
+        set_u32(&mut tuple, T_CID_OFFSET, xlrec.t_cid);
+        set_ctid(&mut tuple, blkno, offnum);
+
+        page_add_item(page, &tuple, offnum, true, true)?;
+    }
+    ensure!(pos == block_data.len(), "total tuple length mismatch");
+
+    if xlrec.flags & pg_constants::XLH_INSERT_ALL_VISIBLE_CLEARED != 0 {
+        page_clear_all_visible(page);
+    }
+    if xlrec.flags & pg_constants::XLH_INSERT_ALL_FROZEN_SET != 0 {
+        page_set_all_visible(page);
+    }
+    Ok(())
+}
+
+fn heap_delete(
+    page: &mut [u8],
+    blkno: u32,
+    xid: TransactionId,
+    xlrec: XlNeonHeapDelete,
+) -> anyhow::Result<()> {
+    let tuple = heap_tuple_mut(page, xlrec.offnum)?;
+    update_infomask(tuple, |infomask, infomask2| {
+        *infomask &= !(HEAP_XMAX_BITS | HEAP_MOVED);
+        *infomask2 &= !(HEAP_KEYS_UPDATED | HEAP_HOT_UPDATED);
+        fix_infomask_from_infobits(xlrec.infobits_set, infomask, infomask2);
+    });
+    if xlrec.flags & pg_constants::XLH_DELETE_IS_SUPER == 0 {
+        set_u32(tuple, T_XMAX_OFFSET, xlrec.xmax);
+    } else {
+        set_u32(tuple, T_XMIN_OFFSET, pg_constants::INVALID_TRANSACTION_ID);
+    }
+    set_u32(tuple, T_CID_OFFSET, xlrec.t_cid);
+    if xlrec.flags & pg_constants::XLH_DELETE_IS_PARTITION_MOVE != 0 {
+        set_ctid(
+            tuple,
+            MOVED_PARTITIONS_BLOCK_NUMBER,
+            MOVED_PARTITIONS_OFFSET_NUMBER,
+        );
+    } else {
+        set_ctid(tuple, blkno, xlrec.offnum);
+    }
+
+    page_set_prunable(page, xid);
+    if xlrec.flags & pg_constants::XLH_DELETE_ALL_VISIBLE_CLEARED != 0 {
+        page_clear_all_visible(page);
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn heap_update(
+    page: &mut [u8],
+    new_blkno: u32,
+    xid: TransactionId,
+    hot: bool,
+    is_old_page: bool,
+    is_new_page: bool,
+    xlrec: XlNeonHeapUpdate,
+    mut block_data: Bytes,
+) -> anyhow::Result<()> {
+    let mut old_tuple = None;
+    if is_old_page {
+        let tuple = heap_tuple_mut(page, xlrec.old_offnum)?;
+        update_infomask(tuple, |infomask, infomask2| {
+            *infomask &= !(HEAP_XMAX_BITS | HEAP_MOVED);
+            *infomask2 &= !HEAP_KEYS_UPDATED;
+            if hot {
+                *infomask2 |= HEAP_HOT_UPDATED;
+            } else {
+                *infomask2 &= !HEAP_HOT_UPDATED;
+            }
+            fix_infomask_from_infobits(xlrec.old_infobits_set, infomask, infomask2);
+        });
+        set_u32(tuple, T_XMAX_OFFSET, xlrec.old_xmax);
+        set_u32(tuple, T_CID_OFFSET, xlrec.t_cid);
+        // forward chain link
+        set_ctid(tuple, new_blkno, xlrec.new_offnum);
+        old_tuple = Some(tuple.to_vec());
+
+        page_set_prunable(page, xid);
+        if xlrec.flags & pg_constants::XLH_UPDATE_OLD_ALL_VISIBLE_CLEARED != 0 {
+            page_clear_all_visible(page);
+        }
+    }
+
+    if !is_new_page {
+        return Ok(());
+    }
+
+    ensure!(
+        page_max_offset(page) + 1 >= xlrec.new_offnum,
+        "invalid max offset number"
+    );
+
+    let mut prefixlen = 0;
+    let mut suffixlen = 0;
+    if xlrec.flags & pg_constants::XLH_UPDATE_PREFIX_FROM_OLD != 0 {
+        ensure!(block_data.remaining() >= 2, "tuple data too short");
+        prefixlen = block_data.get_u16_le() as usize;
+    }
+    if xlrec.flags & pg_constants::XLH_UPDATE_SUFFIX_FROM_OLD != 0 {
+        ensure!(block_data.remaining() >= 2, "tuple data too short");
+        suffixlen = block_data.get_u16_le() as usize;
+    }
+    ensure!(
+        block_data.remaining() >= XlNeonHeapHeader::SIZE,
+        "tuple data too short"
+    );
+    let xlhdr = XlNeonHeapHeader::decode(&mut block_data);
+
+    // Reconstruct the new tuple using the prefix and/or suffix from the old tuple, and the data
+    // stored in the WAL record.
+    let mut tuple = vec![0u8; SIZEOF_HEAP_TUPLE_HEADER];
+    if prefixlen > 0 || suffixlen > 0 {
+        let old_tuple = old_tuple
+            .as_deref()
+            .context("prefix or suffix from old tuple on another page")?;
+        if prefixlen > 0 {
+            // bitmap [+ padding] [+ oid] from the WAL record
+            let len = (xlhdr.t_hoff as usize)
+                .checked_sub(SIZEOF_HEAP_TUPLE_HEADER)
+                .filter(|len| *len <= block_data.len())
+                .context("invalid t_hoff")?;
+            tuple.extend_from_slice(&block_data[..len]);
+            let old_hoff = old_tuple[T_HOFF_OFFSET] as usize;
+            let prefix = old_tuple
+                .get(old_hoff..old_hoff + prefixlen)
+                .context("prefix longer than old tuple")?;
+            tuple.extend_from_slice(prefix);
+            tuple.extend_from_slice(&block_data[len..]);
+        } else {
+            tuple.extend_from_slice(&block_data);
+        }
+        let suffix = old_tuple
+            .len()
+            .checked_sub(suffixlen)
+            .context("suffix longer than old tuple")?;
+        tuple.extend_from_slice(&old_tuple[suffix..]);
+    } else {
+        tuple.extend_from_slice(&block_data);
+    }
+
+    set_u16(&mut tuple, T_INFOMASK2_OFFSET, xlhdr.t_infomask2);
+    set_u16(&mut tuple, T_INFOMASK_OFFSET, xlhdr.t_infomask);
+    tuple[T_HOFF_OFFSET] = xlhdr.t_hoff;
+    set_u32(&mut tuple, T_XMIN_OFFSET, xid);
+    set_u32(&mut tuple, T_CID_OFFSET, xlhdr.t_cid);
+    set_u32(&mut tuple, T_XMAX_OFFSET, xlrec.new_xmax);
+    set_ctid(&mut tuple, new_blkno, xlrec.new_offnum);
+
+    page_add_item(page, &tuple, xlrec.new_offnum, true, true)?;
+
+    if xlrec.flags & pg_constants::XLH_UPDATE_NEW_ALL_VISIBLE_CLEARED != 0 {
+        page_clear_all_visible(page);
+    }
+    Ok(())
+}
+
+fn heap_lock(page: &mut [u8], blkno: u32, xlrec: XlNeonHeapLock) -> anyhow::Result<()> {
+    let tuple = heap_tuple_mut(page, xlrec.offnum)?;
+    let mut locked_only = false;
+    update_infomask(tuple, |infomask, infomask2| {
+        *infomask &= !(HEAP_XMAX_BITS | HEAP_MOVED);
+        *infomask2 &= !HEAP_KEYS_UPDATED;
+        fix_infomask_from_infobits(xlrec.infobits_set, infomask, infomask2);
+
+        // Clear relevant update flags, but only if the modified infomask says there's no
+        // update.
+        locked_only = heap_xmax_is_locked_only(*infomask);
+        if locked_only {
+            *infomask2 &= !HEAP_HOT_UPDATED;
+        }
+    });
+    if locked_only {
+        // no forward chain link
+        set_ctid(tuple, blkno, xlrec.offnum);
+    }
+    set_u32(tuple, T_XMAX_OFFSET, xlrec.locking_xid);
+    set_u32(tuple, T_CID_OFFSET, xlrec.t_cid);
+    Ok(())
+}
+
+/// Port of `fix_infomask_from_infobits` from heapam.c.
+fn fix_infomask_from_infobits(infobits: u8, infomask: &mut u16, infomask2: &mut u16) {
+    *infomask &= !(HEAP_XMAX_IS_MULTI
+        | HEAP_XMAX_LOCK_ONLY
+        | HEAP_XMAX_KEYSHR_LOCK
+        | HEAP_XMAX_EXCL_LOCK
+        | HEAP_COMBOCID);
+    *infomask2 &= !HEAP_KEYS_UPDATED;
+
+    if infobits & pg_constants::XLHL_XMAX_IS_MULTI != 0 {
+        *infomask |= HEAP_XMAX_IS_MULTI;
+    }
+    if infobits & pg_constants::XLHL_XMAX_LOCK_ONLY != 0 {
+        *infomask |= HEAP_XMAX_LOCK_ONLY;
+    }
+    if infobits & pg_constants::XLHL_XMAX_EXCL_LOCK != 0 {
+        *infomask |= HEAP_XMAX_EXCL_LOCK;
+    }
+    if infobits & pg_constants::XLHL_COMBOCID != 0 {
+        *infomask |= HEAP_COMBOCID;
+    }
+    // note HEAP_XMAX_SHR_LOCK isn't considered here
+    if infobits & pg_constants::XLHL_XMAX_KEYSHR_LOCK != 0 {
+        *infomask |= HEAP_XMAX_KEYSHR_LOCK;
+    }
+    if infobits & pg_constants::XLHL_KEYS_UPDATED != 0 {
+        *infomask2 |= HEAP_KEYS_UPDATED;
+    }
+}
+
+/// Port of the `HEAP_XMAX_IS_LOCKED_ONLY` macro.
+fn heap_xmax_is_locked_only(infomask: u16) -> bool {
+    infomask & HEAP_XMAX_LOCK_ONLY != 0
+        || infomask & (HEAP_XMAX_IS_MULTI | HEAP_LOCK_MASK) == HEAP_XMAX_EXCL_LOCK
+}
+
+fn update_infomask(tuple: &mut [u8], f: impl FnOnce(&mut u16, &mut u16)) {
+    let mut infomask = get_u16(tuple, T_INFOMASK_OFFSET);
+    let mut infomask2 = get_u16(tuple, T_INFOMASK2_OFFSET);
+    f(&mut infomask, &mut infomask2);
+    set_u16(tuple, T_INFOMASK_OFFSET, infomask);
+    set_u16(tuple, T_INFOMASK2_OFFSET, infomask2);
+}
+
+fn set_ctid(tuple: &mut [u8], blkno: u32, offnum: u16) {
+    set_u16(tuple, T_CTID_OFFSET, (blkno >> 16) as u16);
+    set_u16(tuple, T_CTID_OFFSET + 2, blkno as u16);
+    set_u16(tuple, T_CTID_OFFSET + 4, offnum);
+}
+
+/// The heap tuple with line pointer `offnum`, which must be in use.
+fn heap_tuple_mut(page: &mut [u8], offnum: u16) -> anyhow::Result<&mut [u8]> {
+    ensure!(
+        offnum != 0 && offnum <= page_max_offset(page),
+        "invalid lp {offnum}"
+    );
+    let (lp_off, lp_flags, lp_len) = item_id(page, offnum);
+    ensure!(lp_flags == LP_NORMAL, "invalid lp {offnum}");
+    ensure!(
+        lp_len >= SIZEOF_HEAP_TUPLE_HEADER && lp_off + lp_len <= page.len(),
+        "invalid lp {offnum}"
+    );
+    Ok(&mut page[lp_off..lp_off + lp_len])
+}
+
+/// Port of `PageAddItemExtended` from bufpage.c, for callers which specify the offset number.
+fn page_add_item(
+    page: &mut [u8],
+    item: &[u8],
+    offnum: u16,
+    overwrite: bool,
+    is_heap: bool,
+) -> anyhow::Result<()> {
+    let lower = get_u16(page, PD_LOWER_OFFSET) as usize;
+    let upper = get_u16(page, PD_UPPER_OFFSET) as usize;
+    let special = get_u16(page, PD_SPECIAL_OFFSET) as usize;
+    ensure!(
+        lower >= SIZE_OF_PAGE_HEADER_DATA
+            && lower <= upper
+            && upper <= special
+            && special <= BLCKSZ as usize,
+        "corrupted page pointers: lower = {lower}, upper = {upper}, special = {special}"
+    );
+    ensure!(offnum != 0, "invalid offset number");
+
+    let limit = page_max_offset(page) + 1;
+    let mut needshuffle = false;
+    if overwrite {
+        if offnum < limit {
+            let (_, lp_flags, lp_len) = item_id(page, offnum);
+            ensure!(
+                lp_flags == LP_UNUSED && lp_len == 0,
+                "will not overwrite a used ItemId"
+            );
+        }
+    } else if offnum < limit {
+        needshuffle = true;
+    }
+    ensure!(offnum <= limit, "specified item offset is too large");
+    ensure!(
+        !is_heap || offnum <= MAX_HEAP_TUPLES_PER_PAGE,
+        "can't put more than MaxHeapTuplesPerPage items in a heap page"
+    );
+
+    let new_lower = if offnum == limit || needshuffle {
+        lower + SIZE_OF_ITEM_ID_DATA
+    } else {
+        lower
+    };
+    let new_upper = upper
+        .checked_sub(maxalign(item.len()))
+        .filter(|new_upper| *new_upper >= new_lower)
+        .context("not enough free space on page")?;
+
+    let lp = item_id_offset(offnum);
+    if needshuffle {
+        page.copy_within(lp..item_id_offset(limit), lp + SIZE_OF_ITEM_ID_DATA);
+    }
+    let item_id = new_upper as u32 | (LP_NORMAL << 15) | ((item.len() as u32) << 17);
+    set_u32(page, lp, item_id);
+    page[new_upper..new_upper + item.len()].copy_from_slice(item);
+    set_u16(page, PD_LOWER_OFFSET, new_lower as u16);
+    set_u16(page, PD_UPPER_OFFSET, new_upper as u16);
+    Ok(())
+}
+
+fn page_max_offset(page: &[u8]) -> u16 {
+    let lower = get_u16(page, PD_LOWER_OFFSET) as usize;
+    (lower.saturating_sub(SIZE_OF_PAGE_HEADER_DATA) / SIZE_OF_ITEM_ID_DATA) as u16
+}
+
+fn item_id_offset(offnum: u16) -> usize {
+    SIZE_OF_PAGE_HEADER_DATA + (offnum as usize - 1) * SIZE_OF_ITEM_ID_DATA
+}
+
+/// Returns `lp_off`, `lp_flags` and `lp_len` of a line pointer.
+fn item_id(page: &[u8], offnum: u16) -> (usize, u32, usize) {
+    let lp = get_u32(page, item_id_offset(offnum));
+    (
+        (lp & 0x7fff) as usize,
+        (lp >> 15) & 0x3,
+        (lp >> 17) as usize,
+    )
+}
+
+fn page_set_prunable(page: &mut [u8], xid: TransactionId) {
+    let prune_xid = get_u32(page, PD_PRUNE_XID_OFFSET);
+    if prune_xid == pg_constants::INVALID_TRANSACTION_ID || transaction_id_precedes(xid, prune_xid)
+    {
+        set_u32(page, PD_PRUNE_XID_OFFSET, xid);
+    }
+}
+
+fn page_clear_all_visible(page: &mut [u8]) {
+    let flags = get_u16(page, PD_FLAGS_OFFSET);
+    set_u16(page, PD_FLAGS_OFFSET, flags & !PD_ALL_VISIBLE);
+}
+
+fn page_set_all_visible(page: &mut [u8]) {
+    let flags = get_u16(page, PD_FLAGS_OFFSET);
+    set_u16(page, PD_FLAGS_OFFSET, flags | PD_ALL_VISIBLE);
+}
+
+const fn maxalign(len: usize) -> usize {
+    (len + 7) & !7
+}
+
+fn get_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(buf[offset..offset + 2].try_into().unwrap())
+}
+
+fn set_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn set_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bytes::{Bytes, BytesMut};
+    use pageserver_api::key::{Key, rel_block_to_key};
+    use pageserver_api::record::NeonWalRecord;
+    use pageserver_api::reltag::RelTag;
+    use pageserver_api::shard::TenantShardId;
+    use postgres_ffi::v14::xlog_utils::XLOG_RECORD_CRC_OFFS;
+    use postgres_ffi::{BLCKSZ, XLOG_SIZE_OF_XLOG_RECORD, XLogRecord, pg_constants};
+    use tracing::Instrument;
+    use utils::id::TenantId;
+    use utils::lsn::Lsn;
+
+    use super::*;
+    use crate::config::PageServerConf;
+    use crate::walredo::PostgresRedoManager;
+
+    const REL: RelTag = RelTag {
+        forknum: 0,
+        spcnode: 1663,
+        dbnode: 5,
+        relnode: 16384,
+    };
+
+    const XID: u32 = 1000;
+
+    fn key(blkno: u32) -> Key {
+        rel_block_to_key(REL, blkno)
+    }
+
+    /// An empty page, like `PageInit`.
+    fn empty_page(special_size: usize) -> BytesMut {
+        let mut page = BytesMut::from(&[0u8; BLCKSZ as usize][..]);
+        let special = (BLCKSZ as usize - special_size) as u16;
+        set_u16(&mut page, PD_LOWER_OFFSET, SIZE_OF_PAGE_HEADER_DATA as u16);
+        set_u16(&mut page, PD_UPPER_OFFSET, special);
+        set_u16(&mut page, PD_SPECIAL_OFFSET, special);
+        // pd_pagesize_version
+        set_u16(&mut page, 18, BLCKSZ | 4);
+        page
+    }
+
+    /// Encodes a WAL record of `REL`, with one block reference per entry of `blocks`.
+    fn encode_record(
+        rmid: u8,
+        info: u8,
+        blocks: &[(u32, &[u8])],
+        main_data: &[u8],
+    ) -> NeonWalRecord {
+        let mut body = Vec::new();
+        for (block_id, (blkno, data)) in blocks.iter().enumerate() {
+            let mut fork_flags = REL.forknum;
+            if !data.is_empty() {
+                fork_flags |= pg_constants::BKPBLOCK_HAS_DATA;
+            }
+            if block_id > 0 {
+                fork_flags |= pg_constants::BKPBLOCK_SAME_REL;
+            }
+            body.push(block_id as u8);
+            body.push(fork_flags);
+            body.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            if block_id == 0 {
+                for oid in [REL.spcnode, REL.dbnode, REL.relnode] {
+                    body.extend_from_slice(&oid.to_le_bytes());
+                }
+            }
+            body.extend_from_slice(&blkno.to_le_bytes());
+        }
+        if !main_data.is_empty() {
+            body.push(pg_constants::XLR_BLOCK_ID_DATA_SHORT);
+            body.push(main_data.len() as u8);
+        }
+        for (_, data) in blocks {
+            body.extend_from_slice(data);
+        }
+        body.extend_from_slice(main_data);
+
+        let mut header = XLogRecord {
+            xl_tot_len: (XLOG_SIZE_OF_XLOG_RECORD + body.len()) as u32,
+            xl_xid: XID,
+            xl_prev: 0,
+            xl_info: info,
+            xl_rmid: rmid,
+            __bindgen_padding_0: [0; 2],
+            xl_crc: 0,
+        };
+        let crc = crc32c::crc32c(&body);
+        header.xl_crc =
+            crc32c::crc32c_append(crc, &header.encode().unwrap()[..XLOG_RECORD_CRC_OFFS]);
+
+        NeonWalRecord::Postgres {
+            will_init: false,
+            rec: [header.encode().unwrap(), Bytes::from(body)]
+                .concat()
+                .into(),
+        }
+    }
+
+    /// Block data of a neon heap insert or update: `XlNeonHeapHeader` and the tuple data
+    /// following the tuple header.
+    fn heap_tuple_data(t_cid: u32, data: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        // t_infomask2: 1 attribute
+        buf.extend_from_slice(&1u16.to_le_bytes());
+        // t_infomask: HEAP_XMAX_INVALID
+        buf.extend_from_slice(&HEAP_XMAX_INVALID.to_le_bytes());
+        buf.extend_from_slice(&t_cid.to_le_bytes());
+        // t_hoff: no null bitmap
+        buf.push(maxalign(SIZEOF_HEAP_TUPLE_HEADER) as u8);
+        // padding up to t_hoff
+        buf.push(0);
+        buf.extend_from_slice(data);
+        buf
+    }
+
+    fn tuple(page: &[u8], offnum: u16) -> &[u8] {
+        let (lp_off, lp_flags, lp_len) = item_id(page, offnum);
+        assert_eq!(lp_flags, LP_NORMAL);
+        &page[lp_off..lp_off + lp_len]
+    }
+
+    fn can_apply_natively(key: Key, record: &NeonWalRecord, pg_version: u32) -> bool {
+        decode_natively(key, record, pg_version).is_some()
+    }
+
+    fn apply_record(
+        record: &NeonWalRecord,
+        lsn: Lsn,
+        key: Key,
+        page: &mut BytesMut,
+        pg_version: u32,
+    ) -> anyhow::Result<()> {
+        let native = decode_natively(key, record, pg_version).expect("supported record");
+        apply_natively(&native, lsn, page)
+    }
+
+    fn apply(page: &mut BytesMut, blkno: u32, lsn: u64, record: &NeonWalRecord, pg_version: u32) {
+        apply_record(record, Lsn(lsn), key(blkno), page, pg_version).unwrap();
+        assert_eq!(postgres_ffi::page_get_lsn(page), Lsn(lsn));
+    }
+
+    #[test]
+    fn heap_insert_update_delete() {
+        let mut page = empty_page(0);
+
+        // insert a tuple at offset 1
+        let mut main_data = 1u16.to_le_bytes().to_vec();
+        main_data.push(0);
+        let data = heap_tuple_data(3, b"hello world");
+        let insert = encode_record(
+            pg_constants::RM_NEON_ID,
+            pg_constants::XLOG_NEON_HEAP_INSERT,
+            &[(7, &data[..])],
+            &main_data,
+        );
+        apply(&mut page, 7, 0x100, &insert, 16);
+
+        assert_eq!(page_max_offset(&page), 1);
+        let inserted = tuple(&page, 1);
+        assert_eq!(inserted.len(), 24 + b"hello world".len());
+        assert_eq!(get_u32(inserted, T_XMIN_OFFSET), XID);
+        assert_eq!(get_u32(inserted, T_CID_OFFSET), 3);
+        assert_eq!(get_u16(inserted, T_CTID_OFFSET + 2), 7);
+        assert_eq!(get_u16(inserted, T_CTID_OFFSET + 4), 1);
+        assert_eq!(&inserted[24..], b"hello world");
+
+        // HOT update it to offset 2, keeping the prefix "hello " of the old tuple
+        let mut main_data = Vec::new();
+        main_data.extend_from_slice(&XID.to_le_bytes()); // old_xmax
+        main_data.extend_from_slice(&1u16.to_le_bytes()); // old_offnum
+        main_data.push(pg_constants::XLHL_KEYS_UPDATED); // old_infobits_set
+        main_data.push(pg_constants::XLH_UPDATE_PREFIX_FROM_OLD); // flags
+        main_data.extend_from_slice(&4u32.to_le_bytes()); // t_cid
+        main_data.extend_from_slice(&0u32.to_le_bytes()); // new_xmax
+        main_data.extend_from_slice(&2u16.to_le_bytes()); // new_offnum
+        let mut data = 6u16.to_le_bytes().to_vec();
+        data.extend_from_slice(&heap_tuple_data(4, b"there"));
+        let update = encode_record(
+            pg_constants::RM_NEON_ID,
+            pg_constants::XLOG_NEON_HEAP_HOT_UPDATE,
+            &[(7, &data[..])],
+            &main_data,
+        );
+        apply(&mut page, 7, 0x200, &update, 16);
+
+        assert_eq!(page_max_offset(&page), 2);
+        let old = tuple(&page, 1);
+        assert_eq!(get_u32(old, T_XMAX_OFFSET), XID);
+        assert_eq!(get_u32(old, T_CID_OFFSET), 4);
+        assert_eq!(get_u16(old, T_CTID_OFFSET + 4), 2);
+        let infomask2 = get_u16(old, T_INFOMASK2_OFFSET);
+        assert_ne!(infomask2 & HEAP_HOT_UPDATED, 0);
+        assert_ne!(infomask2 & HEAP_KEYS_UPDATED, 0);
+        assert_eq!(get_u16(old, T_INFOMASK_OFFSET) & HEAP_XMAX_INVALID, 0);
+        let new = tuple(&page, 2);
+        assert_eq!(&new[24..], b"hello there");
+        assert_eq!(get_u16(new, T_CTID_OFFSET + 4), 2);
+        assert_eq!(get_u32(&page, PD_PRUNE_XID_OFFSET), XID);
+
+        // delete the new tuple version
+        let mut main_data = Vec::new();
+        main_data.extend_from_slice(&(XID + 1).to_le_bytes()); // xmax
+        main_data.extend_from_slice(&2u16.to_le_bytes()); // offnum
+        main_data.push(0); // infobits_set
+        main_data.push(0); // flags
+        main_data.extend_from_slice(&5u32.to_le_bytes()); // t_cid
+        let delete = encode_record(
+            pg_constants::RM_NEON_ID,
+            pg_constants::XLOG_NEON_HEAP_DELETE,
+            &[(7, &[][..])],
+            &main_data,
+        );
+        apply(&mut page, 7, 0x300, &delete, 16);
+
+        let deleted = tuple(&page, 2);
+        assert_eq!(get_u32(deleted, T_XMAX_OFFSET), XID + 1);
+        assert_eq!(get_u32(deleted, T_CID_OFFSET), 5);
+        assert_eq!(&deleted[24..], b"hello there");
+        // the prune xid only moves backwards
+        assert_eq!(get_u32(&page, PD_PRUNE_XID_OFFSET), XID);
+    }
+
+    #[test]
+    fn cross_page_update_only_touches_the_page_of_the_key() {
+        let mut main_data = Vec::new();
+        main_data.extend_from_slice(&XID.to_le_bytes()); // old_xmax
+        main_data.extend_from_slice(&1u16.to_le_bytes()); // old_offnum
+        main_data.push(0); // old_infobits_set
+        main_data.push(0); // flags
+        main_data.extend_from_slice(&0u32.to_le_bytes()); // t_cid
+        main_data.extend_from_slice(&0u32.to_le_bytes()); // new_xmax
+        main_data.extend_from_slice(&1u16.to_le_bytes()); // new_offnum
+        let data = heap_tuple_data(0, b"moved");
+        let update = encode_record(
+            pg_constants::RM_NEON_ID,
+            pg_constants::XLOG_NEON_HEAP_UPDATE,
+            &[(9, &data[..]), (8, &[][..])],
+            &main_data,
+        );
+
+        // the new page receives the tuple
+        let mut new_page = empty_page(0);
+        apply(&mut new_page, 9, 0x100, &update, 16);
+        assert_eq!(&tuple(&new_page, 1)[24..], b"moved");
+
+        // the old page gets the old tuple version updated
+        let mut old_page = empty_page(0);
+        let mut main_data = 1u16.to_le_bytes().to_vec();
+        main_data.push(0);
+        let data = heap_tuple_data(0, b"old");
+        let insert = encode_record(
+            pg_constants::RM_NEON_ID,
+            pg_constants::XLOG_NEON_HEAP_INSERT,
+            &[(8, &data[..])],
+            &main_data,
+        );
+        apply(&mut old_page, 8, 0x80, &insert, 16);
+        apply(&mut old_page, 8, 0x100, &update, 16);
+        assert_eq!(page_max_offset(&old_page), 1);
+        let old = tuple(&old_page, 1);
+        assert_eq!(get_u32(old, T_XMAX_OFFSET), XID);
+        assert_eq!(get_u16(old, T_CTID_OFFSET + 2), 9);
+        assert_eq!(get_u16(old, T_CTID_OFFSET + 4), 1);
+    }
+
+    /// The records of a multi-insert of two tuples, an insert, a delete, a lock and a HOT update
+    /// on block 7, in the `RM_HEAP_ID` and `RM_HEAP2_ID` layouts of Postgres 14 and 15.
+    fn v14_heap_records() -> Vec<NeonWalRecord> {
+        let mut records = Vec::new();
+
+        // multi-insert at offsets 1 and 2
+        let mut main_data = vec![0, 0]; // flags, padding
+        main_data.extend_from_slice(&2u16.to_le_bytes()); // ntuples
+        main_data.extend_from_slice(&1u16.to_le_bytes());
+        main_data.extend_from_slice(&2u16.to_le_bytes());
+        let mut data = Vec::new();
+        for item in [&b"one"[..], &b"two!"[..]] {
+            // tuple headers are SHORTALIGNed
+            if data.len() % 2 != 0 {
+                data.push(0);
+            }
+            data.extend_from_slice(&(1 + item.len() as u16).to_le_bytes()); // datalen
+            data.extend_from_slice(&1u16.to_le_bytes()); // t_infomask2
+            data.extend_from_slice(&HEAP_XMAX_INVALID.to_le_bytes()); // t_infomask
+            data.push(maxalign(SIZEOF_HEAP_TUPLE_HEADER) as u8); // t_hoff
+            data.push(0); // padding up to t_hoff
+            data.extend_from_slice(item);
+        }
+        records.push(encode_record(
+            pg_constants::RM_HEAP2_ID,
+            pg_constants::XLOG_HEAP2_MULTI_INSERT,
+            &[(7, &data[..])],
+            &main_data,
+        ));
+
+        // insert at offset 3
+        let mut main_data = 3u16.to_le_bytes().to_vec();
+        main_data.push(0);
+        let data = heap_tuple_data(3, b"three");
+        records.push(encode_record(
+            pg_constants::RM_HEAP_ID,
+            pg_constants::XLOG_HEAP_INSERT,
+            &[(7, &data[..])],
+            &main_data,
+        ));
+
+        // delete offset 1
+        let mut main_data = Vec::new();
+        main_data.extend_from_slice(&(XID + 1).to_le_bytes()); // xmax
+        main_data.extend_from_slice(&1u16.to_le_bytes()); // offnum
+        main_data.extend_from_slice(&[0, 0]); // padding
+        main_data.extend_from_slice(&5u32.to_le_bytes()); // t_cid
+        main_data.push(0); // infobits_set
+        main_data.push(0); // flags
+        records.push(encode_record(
+            pg_constants::RM_HEAP_ID,
+            pg_constants::XLOG_HEAP_DELETE,
+            &[(7, &[][..])],
+            &main_data,
+        ));
+
+        // lock offset 2 FOR UPDATE
+        let mut main_data = Vec::new();
+        main_data.extend_from_slice(&(XID + 2).to_le_bytes()); // locking_xid
+        main_data.extend_from_slice(&2u16.to_le_bytes()); // offnum
+        main_data.extend_from_slice(&[0, 0]); // padding
+        main_data.extend_from_slice(&6u32.to_le_bytes()); // t_cid
+        main_data.push(pg_constants::XLHL_XMAX_LOCK_ONLY | pg_constants::XLHL_XMAX_EXCL_LOCK);
+        main_data.push(0); // flags
+        records.push(encode_record(
+            pg_constants::RM_HEAP_ID,
+            pg_constants::XLOG_HEAP_LOCK,
+            &[(7, &[][..])],
+            &main_data,
+        ));
+
+        // HOT update offset 3 to offset 4
+        let mut main_data = Vec::new();
+        main_data.extend_from_slice(&(XID + 3).to_le_bytes()); // old_xmax
+        main_data.extend_from_slice(&3u16.to_le_bytes()); // old_offnum
+        main_data.push(0); // old_infobits_set
+        main_data.push(0); // flags
+        main_data.extend_from_slice(&7u32.to_le_bytes()); // t_cid
+        main_data.extend_from_slice(&0u32.to_le_bytes()); // new_xmax
+        main_data.extend_from_slice(&4u16.to_le_bytes()); // new_offnum
+        let data = heap_tuple_data(7, b"four");
+        records.push(encode_record(
+            pg_constants::RM_HEAP_ID,
+            pg_constants::XLOG_HEAP_HOT_UPDATE,
+            &[(7, &data[..])],
+            &main_data,
+        ));
+
+        records
+    }
+
+    fn heap_records_v14_layout(pg_version: u32) {
+        let mut page = empty_page(0);
+        for (i, record) in v14_heap_records().iter().enumerate() {
+            assert!(can_apply_natively(key(7), record, pg_version));
+            apply(&mut page, 7, 0x100 * (i as u64 + 1), record, pg_version);
+        }
+
+        assert_eq!(page_max_offset(&page), 4);
+        let deleted = tuple(&page, 1);
+        assert_eq!(&deleted[24..], b"one");
+        assert_eq!(get_u32(deleted, T_XMIN_OFFSET), XID);
+        assert_eq!(get_u32(deleted, T_XMAX_OFFSET), XID + 1);
+        assert_eq!(get_u32(deleted, T_CID_OFFSET), 5);
+        let locked = tuple(&page, 2);
+        assert_eq!(&locked[24..], b"two!");
+        assert_eq!(get_u32(locked, T_XMAX_OFFSET), XID + 2);
+        assert_eq!(get_u32(locked, T_CID_OFFSET), 6);
+        assert_ne!(get_u16(locked, T_INFOMASK_OFFSET) & HEAP_XMAX_LOCK_ONLY, 0);
+        assert_eq!(get_u16(locked, T_CTID_OFFSET + 4), 2);
+        let updated = tuple(&page, 3);
+        assert_eq!(&updated[24..], b"three");
+        assert_eq!(get_u32(updated, T_XMAX_OFFSET), XID + 3);
+        assert_eq!(get_u32(updated, T_CID_OFFSET), 7);
+        assert_eq!(get_u16(updated, T_CTID_OFFSET + 4), 4);
+        assert_ne!(get_u16(updated, T_INFOMASK2_OFFSET) & HEAP_HOT_UPDATED, 0);
+        let new = tuple(&page, 4);
+        assert_eq!(&new[24..], b"four");
+        assert_eq!(get_u32(new, T_CID_OFFSET), 7);
+        assert_eq!(get_u32(&page, PD_PRUNE_XID_OFFSET), XID);
+    }
+
+    #[test]
+    fn heap_records_pg14() {
+        heap_records_v14_layout(14);
+    }
+
+    #[test]
+    fn heap_records_pg15() {
+        heap_records_v14_layout(15);
+    }
+
+    #[test]
+    fn multi_insert_gets_first_command_id_before_pg16() {
+        let mut page = empty_page(0);
+        let records = v14_heap_records();
+        apply(&mut page, 7, 0x100, &records[0], 14);
+        for offnum in [1, 2] {
+            assert_eq!(
+                get_u32(tuple(&page, offnum), T_CID_OFFSET),
+                FIRST_COMMAND_ID
+            );
+        }
+    }
+
+    #[test]
+    fn btree_insert_leaf_shifts_line_pointers() {
+        let mut page = empty_page(16);
+        for (lsn, (offnum, item)) in [
+            (1u16, &b"second!!"[..]),
+            (1, &b"first!!!"[..]),
+            (3, &b"third!!!"[..]),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let insert = encode_record(
+                pg_constants::RM_BTREE_ID,
+                pg_constants::XLOG_BTREE_INSERT_LEAF,
+                &[(3, item)],
+                &offnum.to_le_bytes(),
+            );
+            assert!(can_apply_natively(key(3), &insert, 14));
+            apply_record(&insert, Lsn(lsn as u64 + 1), key(3), &mut page, 14).unwrap();
+        }
+
+        assert_eq!(page_max_offset(&page), 3);
+        assert_eq!(tuple(&page, 1), b"first!!!");
+        assert_eq!(tuple(&page, 2), b"second!!");
+        assert_eq!(tuple(&page, 3), b"third!!!");
+        assert_eq!(
+            get_u16(&page, PD_UPPER_OFFSET) as usize,
+            BLCKSZ as usize - 16 - 3 * 8
+        );
+    }
+
+    #[test]
+    fn records_at_or_below_page_lsn_are_skipped() {
+        let mut page = empty_page(16);
+        postgres_ffi::page_set_lsn(&mut page, Lsn(0x200));
+        let before = page.clone();
+
+        let insert = encode_record(
+            pg_constants::RM_BTREE_ID,
+            pg_constants::XLOG_BTREE_INSERT_LEAF,
+            &[(3, &b"item"[..])],
+            &1u16.to_le_bytes(),
+        );
+        apply_record(&insert, Lsn(0x200), key(3), &mut page, 14).unwrap();
+        assert_eq!(page, before);
+    }
+
+    #[test]
+    fn unsupported_records() {
+        let main_data = [1, 0, 0];
+        let data = heap_tuple_data(0, b"x");
+        let insert = encode_record(
+            pg_constants::RM_NEON_ID,
+            pg_constants::XLOG_NEON_HEAP_INSERT,
+            &[(7, &data[..])],
+            &main_data,
+        );
+        assert!(can_apply_natively(key(7), &insert, 17));
+
+        // another page
+        assert!(!can_apply_natively(key(8), &insert, 17));
+        // the neon rmgr only exists since Postgres 16
+        assert!(!can_apply_natively(key(7), &insert, 15));
+        // records which initialize the page
+        let init = encode_record(
+            pg_constants::RM_NEON_ID,
+            pg_constants::XLOG_NEON_HEAP_INSERT | pg_constants::XLOG_NEON_HEAP_INIT_PAGE,
+            &[(7, &data[..])],
+            &main_data,
+        );
+        assert!(!can_apply_natively(key(7), &init, 17));
+        let NeonWalRecord::Postgres { rec, .. } = insert else {
+            unreachable!()
+        };
+        let will_init = NeonWalRecord::Postgres {
+            will_init: true,
+            rec,
+        };
+        assert!(!can_apply_natively(key(7), &will_init, 17));
+        // heap records of the postgres rmgr, which computes only write before Postgres 16
+        let heap_insert = encode_record(
+            pg_constants::RM_HEAP_ID,
+            pg_constants::XLOG_HEAP_INSERT,
+            &[(7, &data[..])],
+            &main_data,
+        );
+        assert!(can_apply_natively(key(7), &heap_insert, 14));
+        assert!(can_apply_natively(key(7), &heap_insert, 15));
+        assert!(!can_apply_natively(key(7), &heap_insert, 16));
+        // neon records
+        let clear_vm = NeonWalRecord::ClearVisibilityMapFlags {
+            new_heap_blkno: Some(7),
+            old_heap_blkno: None,
+            flags: pg_constants::VISIBILITYMAP_VALID_BITS,
+        };
+        assert!(!can_apply_natively(key(7), &clear_vm, 17));
+    }
+
+    /// Differential test against the walredo process.
+    #[tokio::test]
+    async fn btree_insert_leaf_matches_postgres() {
+        crate::tenant::harness::setup_logging();
+
+        let repo_dir = camino_tempfile::tempdir().unwrap();
+        let conf = PageServerConf::dummy_conf(repo_dir.path().to_path_buf());
+        let conf = Box::leak(Box::new(conf));
+        let tenant_shard_id = TenantShardId::unsharded(TenantId::generate());
+        let manager = PostgresRedoManager::new(conf, tenant_shard_id);
+        let span = tracing::info_span!("native", tenant_id=%tenant_shard_id.tenant_id);
+
+        let base_img = empty_page(16).freeze();
+        let records = [
+            (1u16, &b"bbbbbbbbbbbb"[..]),
+            (1, &b"aaaa"[..]),
+            (2, &b"abababab"[..]),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (offnum, item))| {
+            let lsn = Lsn::from_str("0/1000000").unwrap() + (i as u64 + 1) * 0x100;
+            let record = encode_record(
+                pg_constants::RM_BTREE_ID,
+                pg_constants::XLOG_BTREE_INSERT_LEAF,
+                &[(3, item)],
+                &offnum.to_le_bytes(),
+            );
+            (lsn, record)
+        })
+        .collect::<Vec<_>>();
+        let lsn = records.last().unwrap().0;
+
+        let expected = manager
+            .request_redo(
+                key(3),
+                lsn,
+                Some((Lsn(0), base_img.clone())),
+                records.clone(),
+                14,
+            )
+            .instrument(span)
+            .await
+            .unwrap();
+
+        let mut page = BytesMut::from(&base_img[..]);
+        for (lsn, record) in &records {
+            apply_record(record, *lsn, key(3), &mut page, 14).unwrap();
+        }
+        assert_eq!(&page[..], &expected[..]);
+
+        manager.shutdown().await;
+    }
+
+    /// Differential test of the Postgres 14 and 15 heap records against the walredo process.
+    async fn heap_records_match_postgres(pg_version: u32) {
+        crate::tenant::harness::setup_logging();
+
+        let repo_dir = camino_tempfile::tempdir().unwrap();
+        let conf = PageServerConf::dummy_conf(repo_dir.path().to_path_buf());
+        let conf = Box::leak(Box::new(conf));
+        let tenant_shard_id = TenantShardId::unsharded(TenantId::generate());
+        let manager = PostgresRedoManager::new(conf, tenant_shard_id);
+        let span = tracing::info_span!("native", tenant_id=%tenant_shard_id.tenant_id);
+
+        let base_img = empty_page(0).freeze();
+        let records = v14_heap_records()
+            .into_iter()
+            .enumerate()
+            .map(|(i, record)| {
+                let lsn = Lsn::from_str("0/1000000").unwrap() + (i as u64 + 1) * 0x100;
+                (lsn, record)
+            })
+            .collect::<Vec<_>>();
+        let lsn = records.last().unwrap().0;
+
+        let expected = manager
+            .request_redo(
+                key(7),
+                lsn,
+                Some((Lsn(0), base_img.clone())),
+                records.clone(),
+                pg_version,
+            )
+            .instrument(span)
+            .await
+            .unwrap();
+
+        let mut page = BytesMut::from(&base_img[..]);
+        for (lsn, record) in &records {
+            apply_record(record, *lsn, key(7), &mut page, pg_version).unwrap();
+        }
+        assert_eq!(&page[..], &expected[..]);
+
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn heap_records_match_postgres_pg14() {
+        heap_records_match_postgres(14).await;
+    }
+
+    #[tokio::test]
+    async fn heap_records_match_postgres_pg15() {
+        heap_records_match_postgres(15).await;
+    }
+}
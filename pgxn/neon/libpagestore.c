@@ -22,6 +22,7 @@
 #include "libpq/libpq.h"
 #include "libpq/pqformat.h"
 #include "miscadmin.h"
+#include "nodes/pg_list.h"
 #include "pgstat.h"
 #include "portability/instr_time.h"
 #include "postmaster/interrupt.h"
@@ -73,6 +74,7 @@ int			readahead_buffer_size = 128;
 int			flush_every_n_requests = 8;
 
 int         neon_protocol_version = 2;
+int			prefetch_hint_distance = 1024;
 
 static int	neon_compute_mode = 0;
 static int	max_reconnect_attempts = 60;
@@ -162,8 +164,39 @@ typedef struct
 	 *	- WL_EXIT_ON_PM_DEATH.
 	 */
 	WaitEventSet   *wes_read;
+
+	/*
+	 * Protocol version of the current connection. Lower than
+	 * neon_protocol_version if the pageserver refused version 4, which is
+	 * remembered in 'v4_rejected' until the shard map changes.
+	 */
+	int				protocol_version;
+	bool			v4_rejected;
+
+	/*---
+	 * With protocol version 4, GetPage requests are sent as GetPages requests:
+	 *	- getpages_pending: GetPage requests not sent yet, collected into one
+	 *	  GetPages request until the next flush or another kind of request
+	 *	- getpages_inflight: GetPagesBatches sent, in order, whose responses
+	 *	  haven't been received yet
+	 *	- getpages_split: the response to the oldest batch, handed out to
+	 *	  the callers of receive as one GetPage response per request
+	 */
+	struct GetPagesBatch *getpages_pending;
+	List		   *getpages_inflight;
+	struct GetPagesBatch *getpages_split_batch;
+	NeonResponse   *getpages_split;
+	int				getpages_split_next;
 } PageServer;
 
+/* GetPage requests sent together as one GetPages request */
+typedef struct GetPagesBatch
+{
+	NeonGetPagesRequest request;
+	NeonRequestId reqids[PAGESTREAM_MAX_GETPAGES_PAGES];	/* of the GetPage
+															 * requests */
+} GetPagesBatch;
+
 static PageServer page_servers[MAX_SHARDS];
 
 static bool pageserver_flush(shardno_t shard_no);
@@ -329,6 +362,8 @@ load_shard_map(shardno_t shard_no, char *connstr_p, shardno_t *num_shards_p)
 		{
 			if (page_servers[i].conn)
 				pageserver_disconnect(i);
+			/* the shard may have moved to a pageserver that supports it */
+			page_servers[i].v4_rejected = false;
 		}
 		pagestore_local_counter = end_update_counter;
 	}
@@ -358,9 +393,35 @@ get_shard_number(BufferTag *tag)
 	return hash % n_shards;
 }
 
+/*
+ * Forget about batched GetPage requests, and the responses to them. Their
+ * callers learn about it from the disconnect.
+ */
+static void
+reset_getpages_batches(PageServer *shard)
+{
+	if (shard->getpages_pending)
+	{
+		pfree(shard->getpages_pending);
+		shard->getpages_pending = NULL;
+	}
+	list_free_deep(shard->getpages_inflight);
+	shard->getpages_inflight = NIL;
+	if (shard->getpages_split)
+	{
+		pfree(shard->getpages_split);
+		pfree(shard->getpages_split_batch);
+		shard->getpages_split = NULL;
+		shard->getpages_split_batch = NULL;
+	}
+	shard->getpages_split_next = 0;
+}
+
 static inline void
 CLEANUP_AND_DISCONNECT(PageServer *shard) 
 {
+	reset_getpages_batches(shard);
+
 	if (shard->wes_read)
 	{
 		FreeWaitEventSet(shard->wes_read);
@@ -628,8 +689,15 @@ pageserver_connect(shardno_t shard_no, int elevel)
 		AddWaitEventToSet(shard->wes_read, WL_SOCKET_READABLE, PQsocket(shard->conn), NULL, NULL);
 
 
-		switch (neon_protocol_version)
+		shard->protocol_version = neon_protocol_version;
+		if (shard->protocol_version >= 4 && shard->v4_rejected)
+			shard->protocol_version = 3;
+
+		switch (shard->protocol_version)
 		{
+		case 4:
+			pagestream_query = psprintf("pagestream_v4 %s %s", neon_tenant, neon_timeline);
+			break;
 		case 3:
 			pagestream_query = psprintf("pagestream_v3 %s %s", neon_tenant, neon_timeline);
 			break;
@@ -710,6 +778,30 @@ pageserver_connect(shardno_t shard_no, int elevel)
 			}
 		}
 
+		/*
+		 * Pageservers that don't know protocol version 4 refuse the
+		 * pagestream_v4 command. Fall back to version 3 on the next attempt,
+		 * which only loses prefetch hints and GetPages batching.
+		 */
+		if (shard->protocol_version >= 4)
+		{
+			PGresult   *res = PQgetResult(shard->conn);
+
+			if (PQresultStatus(res) != PGRES_COPY_BOTH)
+			{
+				char	   *msg = pchomp(PQresultErrorMessage(res));
+
+				PQclear(res);
+				CLEANUP_AND_DISCONNECT(shard);
+				shard->v4_rejected = true;
+				neon_shard_log(shard_no, LOG, "pageserver does not support protocol version 4, falling back to version 3: %s",
+							   msg);
+				pfree(msg);
+				return false;
+			}
+			PQclear(res);
+		}
+
 		shard->state = PS_Connected;
 		shard->nrequests_sent = 0;
 		shard->nresponses_received = 0;
@@ -726,7 +818,7 @@ pageserver_connect(shardno_t shard_no, int elevel)
 		shard->delay_us = MIN_RECONNECT_INTERVAL_USEC;
 
 		neon_shard_log(shard_no, DEBUG5, "Connection state: Connected");
-		neon_shard_log(shard_no, LOG, "libpagestore: connected to '%s' with protocol version %d", connstr, neon_protocol_version);
+		neon_shard_log(shard_no, LOG, "libpagestore: connected to '%s' with protocol version %d", connstr, shard->protocol_version);
 		return true;
 	default:
 		neon_shard_log(shard_no, ERROR, "libpagestore: invalid connection state %d", shard->state);
@@ -974,12 +1066,144 @@ pageserver_disconnect_shard(shardno_t shard_no)
 	shard->state = PS_Disconnected;
 }
 
+/*
+ * Send one request on an established connection.
+ */
 static bool
-pageserver_send(shardno_t shard_no, NeonRequest *request)
+pageserver_put_request(shardno_t shard_no, NeonRequest *request)
 {
 	StringInfoData req_buff;
 	PageServer *shard = &page_servers[shard_no];
-	PGconn	   *pageserver_conn;
+	PGconn	   *pageserver_conn = shard->conn;
+
+	req_buff = nm_pack_request(request);
+
+	/*
+	 * Send request.
+	 *
+	 * In principle, this could block if the output buffer is full, and we
+	 * should use async mode and check for interrupts while waiting. In
+	 * practice, our requests are small enough to always fit in the output and
+	 * TCP buffer.
+	 *
+	 * Note that this also will fail when the connection is in the
+	 * PGRES_POLLING_WRITING state. It's kinda dirty to disconnect at this
+	 * point, but on the grand scheme of things it's only a small issue.
+	 */
+	shard->nrequests_sent++;
+	if (PQputCopyData(pageserver_conn, req_buff.data, req_buff.len) <= 0)
+	{
+		char	   *msg = pchomp(PQerrorMessage(pageserver_conn));
+
+		pageserver_disconnect(shard_no);
+		neon_shard_log(shard_no, LOG, "pageserver_send disconnected: failed to send page request (try to reconnect): %s", msg);
+		pfree(msg);
+		pfree(req_buff.data);
+		return false;
+	}
+
+	pfree(req_buff.data);
+
+	if (message_level_is_interesting(PageStoreTrace))
+	{
+		char	   *msg = nm_to_string((NeonMessage *) request);
+
+		neon_shard_log(shard_no, PageStoreTrace, "sent request: %s", msg);
+		pfree(msg);
+	}
+
+	return true;
+}
+
+/*
+ * Send the pending GetPage requests of the shard as one GetPages request.
+ */
+static bool
+pageserver_send_getpages(shardno_t shard_no)
+{
+	PageServer *shard = &page_servers[shard_no];
+	GetPagesBatch *batch = shard->getpages_pending;
+	MemoryContext oldcontext;
+
+	Assert(batch != NULL);
+	shard->getpages_pending = NULL;
+
+	/* A lone request is cheaper to send, and to answer, as a plain GetPage */
+	if (batch->request.npages == 1)
+	{
+		NeonGetPageRequest request = {
+			.hdr = batch->request.hdr,
+			.rinfo = batch->request.pages[0].rinfo,
+			.forknum = batch->request.pages[0].forknum,
+			.blkno = batch->request.pages[0].blkno,
+		};
+		bool		ok;
+
+		request.hdr.tag = T_NeonGetPageRequest;
+		request.hdr.reqid = batch->reqids[0];
+		pfree(batch);
+		ok = pageserver_put_request(shard_no, (NeonRequest *) &request);
+		return ok;
+	}
+
+	if (!pageserver_put_request(shard_no, (NeonRequest *) &batch->request))
+	{
+		pfree(batch);
+		return false;
+	}
+
+	oldcontext = MemoryContextSwitchTo(TopMemoryContext);
+	shard->getpages_inflight = lappend(shard->getpages_inflight, batch);
+	MemoryContextSwitchTo(oldcontext);
+
+	return true;
+}
+
+/*
+ * Add a GetPage request to the shard's pending GetPages request. Requests
+ * at different LSNs can't share a GetPages request, so a change of LSN sends
+ * the pending one first.
+ */
+static bool
+pageserver_batch_getpage(shardno_t shard_no, NeonGetPageRequest *request)
+{
+	PageServer *shard = &page_servers[shard_no];
+	GetPagesBatch *batch = shard->getpages_pending;
+	int			i;
+
+	if (batch != NULL &&
+		(batch->request.hdr.lsn != request->hdr.lsn ||
+		 batch->request.hdr.not_modified_since != request->hdr.not_modified_since))
+	{
+		if (!pageserver_send_getpages(shard_no))
+			return false;
+		batch = NULL;
+	}
+
+	if (batch == NULL)
+	{
+		batch = MemoryContextAllocZero(TopMemoryContext, sizeof(GetPagesBatch));
+		batch->request.hdr = request->hdr;
+		batch->request.hdr.tag = T_NeonGetPagesRequest;
+		shard->getpages_pending = batch;
+	}
+
+	i = batch->request.npages++;
+	batch->request.pages[i].rinfo = request->rinfo;
+	batch->request.pages[i].forknum = request->forknum;
+	batch->request.pages[i].blkno = request->blkno;
+	batch->reqids[i] = request->hdr.reqid;
+
+	if (batch->request.npages == PAGESTREAM_MAX_GETPAGES_PAGES)
+		return pageserver_send_getpages(shard_no);
+
+	return true;
+}
+
+static bool
+pageserver_send(shardno_t shard_no, NeonRequest *request)
+{
+	PageServer *shard = &page_servers[shard_no];
 
 	MyNeonCounters->pageserver_requests_sent_total++;
 
@@ -988,11 +1212,8 @@ pageserver_send(shardno_t shard_no, NeonRequest *request)
 	{
 		neon_shard_log(shard_no, LOG, "pageserver_send disconnect bad connection");
 		pageserver_disconnect(shard_no);
-		pageserver_conn = NULL;
 	}
 
-	req_buff = nm_pack_request(request);
-
 	/*
 	 * If pageserver is stopped, the connections from compute node are broken.
 	 * The compute node doesn't notice that immediately, but it will cause the
@@ -1013,47 +1234,115 @@ pageserver_send(shardno_t shard_no, NeonRequest *request)
 		Assert(shard->conn != NULL);
 	}
 
-	pageserver_conn = shard->conn;
+	if (shard->protocol_version >= 4 && messageTag(request) == T_NeonGetPageRequest)
+		return pageserver_batch_getpage(shard_no, (NeonGetPageRequest *) request);
 
-	/*
-	 * Send request.
-	 *
-	 * In principle, this could block if the output buffer is full, and we
-	 * should use async mode and check for interrupts while waiting. In
-	 * practice, our requests are small enough to always fit in the output and
-	 * TCP buffer.
-	 *
-	 * Note that this also will fail when the connection is in the
-	 * PGRES_POLLING_WRITING state. It's kinda dirty to disconnect at this
-	 * point, but on the grand scheme of things it's only a small issue.
-	 */
-	shard->nrequests_sent++;
-	if (PQputCopyData(pageserver_conn, req_buff.data, req_buff.len) <= 0)
+	/* The responses must come back in the order of the requests */
+	if (shard->getpages_pending != NULL && !pageserver_send_getpages(shard_no))
+		return false;
+
+	/* Prefetch hints are only an optimization, older pageservers don't get them */
+	if (messageTag(request) == T_NeonPrefetchRequest && shard->protocol_version < 4)
+		return true;
+
+	return pageserver_put_request(shard_no, request);
+}
+
+/*
+ * Hand out the next page of the GetPages response being split up, as the
+ * response to the GetPage request it belongs to.
+ */
+static NeonResponse *
+pageserver_next_split_response(shardno_t shard_no)
+{
+	PageServer *shard = &page_servers[shard_no];
+	GetPagesBatch *batch = shard->getpages_split_batch;
+	NeonResponse *split = shard->getpages_split;
+	int			i = shard->getpages_split_next++;
+	NeonResponse *resp;
+
+	if (messageTag(split) == T_NeonGetPagesResponse)
+		resp = nm_getpages_response_page((NeonGetPagesResponse *) split, i,
+										 batch->reqids[i]);
+	else
 	{
-		char	   *msg = pchomp(PQerrorMessage(pageserver_conn));
+		/* The whole GetPages request failed, so does each GetPage request */
+		NeonErrorResponse *err = (NeonErrorResponse *) split;
+		Size		size = offsetof(NeonErrorResponse, message) + strlen(err->message) + 1;
 
-		pageserver_disconnect(shard_no);
-		neon_shard_log(shard_no, LOG, "pageserver_send disconnected: failed to send page request (try to reconnect): %s", msg);
-		pfree(msg);
-		pfree(req_buff.data);
-		return false;
+		resp = palloc(size);
+		memcpy(resp, err, size);
+		resp->reqid = batch->reqids[i];
 	}
 
-	pfree(req_buff.data);
+	if (shard->getpages_split_next == batch->request.npages)
+	{
+		pfree(shard->getpages_split);
+		pfree(shard->getpages_split_batch);
+		shard->getpages_split = NULL;
+		shard->getpages_split_batch = NULL;
+		shard->getpages_split_next = 0;
+	}
 
-	if (message_level_is_interesting(PageStoreTrace))
+	return resp;
+}
+
+/*
+ * If 'resp' answers the oldest GetPages request in flight, start handing it
+ * out as GetPage responses, and return the first one.
+ */
+static NeonResponse *
+pageserver_unbatch_response(shardno_t shard_no, NeonResponse *resp)
+{
+	PageServer *shard = &page_servers[shard_no];
+	GetPagesBatch *batch;
+
+	if (resp == NULL)
+		return resp;
+
+	batch = shard->getpages_inflight != NIL ?
+		(GetPagesBatch *) linitial(shard->getpages_inflight) : NULL;
+	if (messageTag(resp) == T_NeonGetPagesResponse)
 	{
-		char	   *msg = nm_to_string((NeonMessage *) request);
+		NeonRequestId reqid = resp->reqid;
+		int			npages = ((NeonGetPagesResponse *) resp)->req.npages;
 
-		neon_shard_log(shard_no, PageStoreTrace, "sent request: %s", msg);
-		pfree(msg);
+		if (batch == NULL || reqid != batch->request.hdr.reqid ||
+			npages != batch->request.npages)
+		{
+			pfree(resp);
+			pageserver_disconnect(shard_no);
+			neon_shard_log(shard_no, ERROR, "unexpected getpages response with reqid %lx and %d pages",
+						   reqid, npages);
+		}
+		/* nm_unpack_response allocated it in TopMemoryContext already */
+		shard->getpages_split = resp;
 	}
+	else if (batch != NULL && messageTag(resp) == T_NeonErrorResponse &&
+			 resp->reqid == batch->request.hdr.reqid)
+	{
+		NeonErrorResponse *err = (NeonErrorResponse *) resp;
+		Size		size = offsetof(NeonErrorResponse, message) + strlen(err->message) + 1;
 
-	return true;
+		shard->getpages_split = MemoryContextAlloc(TopMemoryContext, size);
+		memcpy(shard->getpages_split, err, size);
+		pfree(resp);
+	}
+	else
+	{
+		/* a response to a request sent before the batch */
+		return resp;
+	}
+
+	shard->getpages_inflight = list_delete_first(shard->getpages_inflight);
+	shard->getpages_split_batch = batch;
+	shard->getpages_split_next = 0;
+
+	return pageserver_next_split_response(shard_no);
 }
 
 static NeonResponse *
-pageserver_receive(shardno_t shard_no)
+pageserver_receive_message(shardno_t shard_no)
 {
 	StringInfoData resp_buff;
 	NeonResponse *resp;
@@ -1133,7 +1422,7 @@ pageserver_receive(shardno_t shard_no)
 }
 
 static NeonResponse *
-pageserver_try_receive(shardno_t shard_no)
+pageserver_try_receive_message(shardno_t shard_no)
 {
 	StringInfoData resp_buff;
 	NeonResponse *resp;
@@ -1211,6 +1500,33 @@ pageserver_try_receive(shardno_t shard_no)
 	return (NeonResponse *) resp;
 }
 
+static bool pageserver_flush(shardno_t shard_no);
+
+static NeonResponse *
+pageserver_receive(shardno_t shard_no)
+{
+	PageServer *shard = &page_servers[shard_no];
+
+	if (shard->getpages_split != NULL)
+		return pageserver_next_split_response(shard_no);
+
+	/* Requests are flushed before waiting for them, but don't wait forever */
+	if (shard->getpages_pending != NULL && !pageserver_flush(shard_no))
+		return NULL;
+
+	return pageserver_unbatch_response(shard_no, pageserver_receive_message(shard_no));
+}
+
+static NeonResponse *
+pageserver_try_receive(shardno_t shard_no)
+{
+	PageServer *shard = &page_servers[shard_no];
+
+	if (shard->getpages_split != NULL)
+		return pageserver_next_split_response(shard_no);
+
+	return pageserver_unbatch_response(shard_no, pageserver_try_receive_message(shard_no));
+}
 
 static bool
 pageserver_flush(shardno_t shard_no)
@@ -1223,6 +1539,10 @@ pageserver_flush(shardno_t shard_no)
 	}
 	else
 	{
+		if (page_servers[shard_no].getpages_pending != NULL &&
+			!pageserver_send_getpages(shard_no))
+			return false;
+
 		MyNeonCounters->pageserver_send_flushes_total++;
 		if (PQflush(pageserver_conn))
 		{
@@ -1398,6 +1718,17 @@ pg_init_libpagestore(void)
 							PGC_USERSET,
 							0,	/* no flags required */
 							NULL, (GucIntAssignHook) &readahead_buffer_resize, NULL);
+	DefineCustomIntVariable("neon.prefetch_hint_distance",
+							"number of blocks ahead of a prefetch to hint to the pageserver",
+							"With protocol version 4, prefetches also tell the "
+							"pageserver about the blocks that are likely to be "
+							"read next, so that it can warm its caches for them. "
+							"0 disables the hints.",
+							&prefetch_hint_distance,
+							1024, 0, 8192,
+							PGC_USERSET,
+							GUC_UNIT_BLOCKS,
+							NULL, NULL, NULL);
 	DefineCustomIntVariable("neon.readahead_getpage_pull_timeout",
 							"readahead response pull timeout",
 							"Time between active tries to pull data from the "
@@ -1414,7 +1745,7 @@ pg_init_libpagestore(void)
 							&neon_protocol_version,
 							2,	/* use protocol version 2 */
 							2,	/* min */
-							3,	/* max */
+							4,	/* max */
 							PGC_SU_BACKEND,
 							0,	/* no flags required */
 							NULL, NULL, NULL);
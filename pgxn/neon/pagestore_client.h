@@ -31,6 +31,8 @@ typedef enum
 	T_NeonGetPageRequest,
 	T_NeonDbSizeRequest,
 	T_NeonGetSlruSegmentRequest,
+	T_NeonPrefetchRequest,		/* only in protocol version 4, no response */
+	T_NeonGetPagesRequest,		/* only in protocol version 4 */
 	/* future tags above this line */
 	T_NeonTestRequest = 99, /* only in cfg(feature = "testing") */
 
@@ -41,6 +43,7 @@ typedef enum
 	T_NeonErrorResponse,
 	T_NeonDbSizeResponse,
 	T_NeonGetSlruSegmentResponse,
+	T_NeonGetPagesResponse,
 	/* future tags above this line */
 	T_NeonTestResponse = 199, /* only in cfg(feature = "testing") */
 } NeonMessageTag;
@@ -99,6 +102,11 @@ typedef enum {
  * as well as other fields from requests, which allows to verify that we receive response for our request.
  * We copy fields from request to response to make checking more reliable: request ID is formed from process ID
  * and local counter, so in principle there can be duplicated requests IDs if process PID is reused.
+ *
+ * V4 version of protocol adds the Prefetch request, a hint to read a range of blocks into the pageserver's
+ * caches that is not responded to, and the GetPages request, which asks for several pages at one LSN and is
+ * answered with all of them in a single response. libpagestore sends GetPage requests to a shard as GetPages
+ * requests when the connection speaks V4, and hands out the pages as individual GetPage responses.
  */
 typedef NeonMessage NeonRequest;
 
@@ -137,6 +145,32 @@ typedef struct
 	int			segno;
 } NeonGetSlruSegmentRequest;
 
+typedef struct
+{
+	NeonRequest hdr;
+	NRelFileInfo rinfo;
+	ForkNumber	forknum;
+	BlockNumber blkno;
+	BlockNumber nblocks;
+} NeonPrefetchRequest;
+
+/* Maximum number of pages in a GetPages request, must match the pageserver */
+#define PAGESTREAM_MAX_GETPAGES_PAGES 32
+
+typedef struct
+{
+	NRelFileInfo rinfo;
+	ForkNumber	forknum;
+	BlockNumber blkno;
+} NeonGetPagesEntry;
+
+typedef struct
+{
+	NeonRequest hdr;
+	int			npages;
+	NeonGetPagesEntry pages[PAGESTREAM_MAX_GETPAGES_PAGES];
+} NeonGetPagesRequest;
+
 /* supertype of all the Neon*Response structs below */
 typedef NeonMessage NeonResponse;
 
@@ -180,9 +214,18 @@ typedef struct
 	char		data[BLCKSZ * SLRU_PAGES_PER_SEGMENT];
 } NeonGetSlruSegmentResponse;
 
+typedef struct
+{
+	NeonGetPagesRequest req;
+	char		pages[FLEXIBLE_ARRAY_MEMBER];	/* req.npages pages, in the
+												 * order of req.pages */
+} NeonGetPagesResponse;
+
 
 extern StringInfoData nm_pack_request(NeonRequest *msg);
 extern NeonResponse *nm_unpack_response(StringInfo s);
+extern NeonResponse *nm_getpages_response_page(NeonGetPagesResponse *resp, int i,
+											   NeonRequestId reqid);
 extern char *nm_to_string(NeonMessage *msg);
 
 /*
@@ -234,6 +277,7 @@ extern char *neon_timeline;
 extern char *neon_tenant;
 extern int32 max_cluster_size;
 extern int  neon_protocol_version;
+extern int	prefetch_hint_distance;
 extern bool lfc_store_prefetch_result;
 
 extern shardno_t get_shard_number(BufferTag* tag);
@@ -1490,6 +1490,39 @@ nm_pack_request(NeonRequest *msg)
 				break;
 			}
 
+		case T_NeonPrefetchRequest:
+			{
+				NeonPrefetchRequest *msg_req = (NeonPrefetchRequest *) msg;
+
+				pq_sendint32(&s, NInfoGetSpcOid(msg_req->rinfo));
+				pq_sendint32(&s, NInfoGetDbOid(msg_req->rinfo));
+				pq_sendint32(&s, NInfoGetRelNumber(msg_req->rinfo));
+				pq_sendbyte(&s, msg_req->forknum);
+				pq_sendint32(&s, msg_req->blkno);
+				pq_sendint32(&s, msg_req->nblocks);
+
+				break;
+			}
+
+		case T_NeonGetPagesRequest:
+			{
+				NeonGetPagesRequest *msg_req = (NeonGetPagesRequest *) msg;
+
+				pq_sendint32(&s, msg_req->npages);
+				for (int i = 0; i < msg_req->npages; i++)
+				{
+					NeonGetPagesEntry *page = &msg_req->pages[i];
+
+					pq_sendint32(&s, NInfoGetSpcOid(page->rinfo));
+					pq_sendint32(&s, NInfoGetDbOid(page->rinfo));
+					pq_sendint32(&s, NInfoGetRelNumber(page->rinfo));
+					pq_sendbyte(&s, page->forknum);
+					pq_sendint32(&s, page->blkno);
+				}
+
+				break;
+			}
+
 			/* pagestore -> pagestore_client. We never need to create these. */
 		case T_NeonExistsResponse:
 		case T_NeonNblocksResponse:
@@ -1497,6 +1530,7 @@ nm_pack_request(NeonRequest *msg)
 		case T_NeonErrorResponse:
 		case T_NeonDbSizeResponse:
 		case T_NeonGetSlruSegmentResponse:
+		case T_NeonGetPagesResponse:
 		default:
 			neon_log(ERROR, "unexpected neon message tag 0x%02x", msg->tag);
 			break;
@@ -1639,6 +1673,40 @@ nm_unpack_response(StringInfo s)
 				break;
 			}
 
+		case T_NeonGetPagesResponse:
+			{
+				NeonGetPagesResponse *msg_resp;
+				int			npages;
+
+				npages = pq_getmsgint(s, 4);
+				if (npages <= 0 || npages > PAGESTREAM_MAX_GETPAGES_PAGES)
+					neon_log(ERROR, "unexpected number of pages in getpages response: %d", npages);
+
+				/*
+				 * libpagestore hands out the pages one by one, over several
+				 * receive calls, so this must outlive the caller's context.
+				 */
+				msg_resp = MemoryContextAllocZero(TopMemoryContext,
+												  offsetof(NeonGetPagesResponse, pages) + npages * BLCKSZ);
+				msg_resp->req.hdr = resp_hdr;
+				msg_resp->req.npages = npages;
+				for (int i = 0; i < npages; i++)
+				{
+					NeonGetPagesEntry *page = &msg_resp->req.pages[i];
+
+					NInfoGetSpcOid(page->rinfo) = pq_getmsgint(s, 4);
+					NInfoGetDbOid(page->rinfo) = pq_getmsgint(s, 4);
+					NInfoGetRelNumber(page->rinfo) = pq_getmsgint(s, 4);
+					page->forknum = pq_getmsgbyte(s);
+					page->blkno = pq_getmsgint(s, 4);
+					memcpy(msg_resp->pages + i * BLCKSZ, pq_getmsgbytes(s, BLCKSZ), BLCKSZ);
+				}
+				pq_getmsgend(s);
+
+				resp = (NeonResponse *) msg_resp;
+				break;
+			}
+
 			/*
 			 * pagestore_client -> pagestore
 			 *
@@ -1649,6 +1717,8 @@ nm_unpack_response(StringInfo s)
 		case T_NeonGetPageRequest:
 		case T_NeonDbSizeRequest:
 		case T_NeonGetSlruSegmentRequest:
+		case T_NeonPrefetchRequest:
+		case T_NeonGetPagesRequest:
 		default:
 			neon_log(ERROR, "unexpected neon message tag 0x%02x", tag);
 			break;
@@ -1657,6 +1727,31 @@ nm_unpack_response(StringInfo s)
 	return resp;
 }
 
+/*
+ * Make a GetPage response out of the i'th page of a GetPages response, as if
+ * it was the response to the GetPage request with the given request ID.
+ */
+NeonResponse *
+nm_getpages_response_page(NeonGetPagesResponse *resp, int i, NeonRequestId reqid)
+{
+	NeonGetPageResponse *page_resp;
+	NeonGetPagesEntry *page = &resp->req.pages[i];
+
+	Assert(i < resp->req.npages);
+
+	page_resp = MemoryContextAllocZero(MyPState->bufctx, PS_GETPAGERESPONSE_SIZE);
+	page_resp->req.hdr.tag = T_NeonGetPageResponse;
+	page_resp->req.hdr.reqid = reqid;
+	page_resp->req.hdr.lsn = resp->req.hdr.lsn;
+	page_resp->req.hdr.not_modified_since = resp->req.hdr.not_modified_since;
+	page_resp->req.rinfo = page->rinfo;
+	page_resp->req.forknum = page->forknum;
+	page_resp->req.blkno = page->blkno;
+	memcpy(page_resp->page, resp->pages + i * BLCKSZ, BLCKSZ);
+
+	return (NeonResponse *) page_resp;
+}
+
 /* dump to json for debugging / error reporting purposes */
 char *
 nm_to_string(NeonMessage *msg)
@@ -1730,6 +1825,31 @@ nm_to_string(NeonMessage *msg)
 				appendStringInfoChar(&s, '}');
 				break;
 			}
+		case T_NeonPrefetchRequest:
+			{
+				NeonPrefetchRequest *msg_req = (NeonPrefetchRequest *) msg;
+
+				appendStringInfoString(&s, "{\"type\": \"NeonPrefetchRequest\"");
+				appendStringInfo(&s, ", \"rinfo\": \"%u/%u/%u\"", RelFileInfoFmt(msg_req->rinfo));
+				appendStringInfo(&s, ", \"forknum\": %d", msg_req->forknum);
+				appendStringInfo(&s, ", \"blkno\": %u", msg_req->blkno);
+				appendStringInfo(&s, ", \"nblocks\": %u", msg_req->nblocks);
+				appendStringInfo(&s, ", \"lsn\": \"%X/%X\"", LSN_FORMAT_ARGS(msg_req->hdr.lsn));
+				appendStringInfo(&s, ", \"not_modified_since\": \"%X/%X\"", LSN_FORMAT_ARGS(msg_req->hdr.not_modified_since));
+				appendStringInfoChar(&s, '}');
+				break;
+			}
+		case T_NeonGetPagesRequest:
+			{
+				NeonGetPagesRequest *msg_req = (NeonGetPagesRequest *) msg;
+
+				appendStringInfoString(&s, "{\"type\": \"NeonGetPagesRequest\"");
+				appendStringInfo(&s, ", \"npages\": %d", msg_req->npages);
+				appendStringInfo(&s, ", \"lsn\": \"%X/%X\"", LSN_FORMAT_ARGS(msg_req->hdr.lsn));
+				appendStringInfo(&s, ", \"not_modified_since\": \"%X/%X\"", LSN_FORMAT_ARGS(msg_req->hdr.not_modified_since));
+				appendStringInfoChar(&s, '}');
+				break;
+			}
 			/* pagestore -> pagestore_client */
 		case T_NeonExistsResponse:
 			{
@@ -1794,6 +1914,16 @@ nm_to_string(NeonMessage *msg)
 								 msg_resp->n_blocks);
 				appendStringInfoChar(&s, '}');
 
+				break;
+			}
+		case T_NeonGetPagesResponse:
+			{
+				NeonGetPagesResponse *msg_resp = (NeonGetPagesResponse *) msg;
+
+				appendStringInfoString(&s, "{\"type\": \"NeonGetPagesResponse\"");
+				appendStringInfo(&s, ", \"npages\": %d", msg_resp->req.npages);
+				appendStringInfoChar(&s, '}');
+
 				break;
 			}
 
@@ -2994,6 +3124,99 @@ neon_close(SMgrRelation reln, ForkNumber forknum)
 }
 
 
+/*
+ * Prefetch hints (protocol version 4)
+ *
+ * When the compute reads ahead in a relation, tell the pageserver about the
+ * next neon.prefetch_hint_distance blocks as well, so that it can download
+ * and cache the layers holding them before we ask for the pages. To not send
+ * a hint for every block, the hinted range is only extended once less than
+ * half of it is left ahead of the blocks being read.
+ */
+static NRelFileInfo hint_rinfo;
+static ForkNumber hint_forknum = InvalidForkNumber;
+static BlockNumber hint_end;	/* first block not hinted yet */
+
+static void
+prefetch_send_hint(shardno_t shard_no, NRelFileInfo rinfo, ForkNumber forknum,
+				   BlockNumber blkno, BlockNumber nblocks, neon_request_lsns *lsns)
+{
+	NeonPrefetchRequest request = {
+		.hdr.tag = T_NeonPrefetchRequest,
+		.hdr.reqid = GENERATE_REQUEST_ID(),
+		.hdr.lsn = lsns->request_lsn,
+		.hdr.not_modified_since = lsns->not_modified_since,
+		.rinfo = rinfo,
+		.forknum = forknum,
+		.blkno = blkno,
+		.nblocks = nblocks,
+	};
+
+	/*
+	 * The pageserver doesn't respond to hints, so there's nothing to wait for.
+	 * If sending fails, the hint is lost, which is fine.
+	 */
+	if (page_server->send(shard_no, (NeonRequest *) &request))
+	{
+		BITMAP_SET(MyPState->shard_bitmap, shard_no);
+		MyPState->max_shard_no = Max(shard_no + 1, MyPState->max_shard_no);
+	}
+}
+
+static void
+prefetch_hint(NRelFileInfo rinfo, ForkNumber forknum, BlockNumber blkno)
+{
+	BlockNumber start;
+	BlockNumber end;
+	BlockNumber relsize;
+	BlockNumber run_start;
+	shardno_t	run_shard;
+	BufferTag	tag;
+	neon_request_lsns lsns;
+	bool		same_rel;
+
+	if (neon_protocol_version < 4 || prefetch_hint_distance <= 0)
+		return;
+
+	same_rel = hint_forknum == forknum && RelFileInfoEquals(hint_rinfo, rinfo);
+	if (same_rel && hint_end > blkno && hint_end - blkno > prefetch_hint_distance / 2)
+		return;
+
+	start = (same_rel && hint_end > blkno) ? hint_end : blkno;
+	end = blkno + prefetch_hint_distance;
+	if (get_cached_relsize(rinfo, forknum, &relsize))
+		end = Min(end, relsize);
+	if (start >= end)
+		return;
+
+	neon_get_request_lsns(rinfo, forknum, start, &lsns, 1);
+
+	/* Send each shard the part of the range that it holds */
+	CopyNRelFileInfoToBufTag(tag, rinfo);
+	tag.forkNum = forknum;
+	tag.blockNum = start;
+	run_start = start;
+	run_shard = get_shard_number(&tag);
+	for (BlockNumber b = start + 1; b < end; b++)
+	{
+		shardno_t	shard_no;
+
+		tag.blockNum = b;
+		shard_no = get_shard_number(&tag);
+		if (shard_no != run_shard)
+		{
+			prefetch_send_hint(run_shard, rinfo, forknum, run_start, b - run_start, &lsns);
+			run_start = b;
+			run_shard = shard_no;
+		}
+	}
+	prefetch_send_hint(run_shard, rinfo, forknum, run_start, end - run_start, &lsns);
+
+	hint_rinfo = rinfo;
+	hint_forknum = forknum;
+	hint_end = end;
+}
+
 #if PG_MAJORVERSION_NUM >= 17
 /*
  *	neon_prefetch() -- Initiate asynchronous read of the specified block of a relation
@@ -3024,6 +3247,8 @@ neon_prefetch(SMgrRelation reln, ForkNumber forknum, BlockNumber blocknum,
 	tag.relNumber = reln->smgr_rlocator.locator.relNumber;
 	tag.forkNum = forknum;
 
+	prefetch_hint(InfoFromSMgrRel(reln), forknum, blocknum);
+
 	while (nblocks > 0)
 	{
 		int		iterblocks = Min(nblocks, PG_IOV_MAX);
@@ -3079,6 +3304,8 @@ neon_prefetch(SMgrRelation reln, ForkNumber forknum, BlockNumber blocknum)
 			neon_log(ERROR, "unknown relpersistence '%c'", reln->smgr_relpersistence);
 	}
 
+	prefetch_hint(InfoFromSMgrRel(reln), forknum, blocknum);
+
 	if (lfc_cache_contains(InfoFromSMgrRel(reln), forknum, blocknum))
 		return false;
 
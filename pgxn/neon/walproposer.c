@@ -1593,6 +1593,12 @@ ParsePageserverFeedbackMessage(WalProposer *wp, StringInfo reply_message, Pagese
 			ps_feedback->shard_number = pq_getmsgint(reply_message, sizeof(uint32));
 			psfeedback_log("%u", key, ps_feedback->shard_number);
 		}
+		else if (strcmp(key, "ps_ingest_throttle_wait_us") == 0)
+		{
+			Assert(value_len == sizeof(int64));
+			ps_feedback->ingest_throttle_wait_us = pq_getmsgint64(reply_message);
+			psfeedback_log(UINT64_FORMAT, key, ps_feedback->ingest_throttle_wait_us);
+		}
 		else
 		{
 			/*
@@ -373,6 +373,8 @@ typedef struct PageserverFeedback
 	XLogRecPtr	remote_consistent_lsn;
 	TimestampTz replytime;
 	uint32		shard_number;
+	/* time the pageserver's WAL ingest waited for the tenant's ingest throttle */
+	uint64		ingest_throttle_wait_us;
 } PageserverFeedback;
 
 typedef struct WalproposerShmemState
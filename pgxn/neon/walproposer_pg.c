@@ -64,6 +64,7 @@ char	   *wal_acceptors_list = "";
 int			wal_acceptor_reconnect_timeout = 1000;
 int			wal_acceptor_connection_timeout = 10000;
 int			safekeeper_proto_version = 2;
+int			ingest_throttle_write_lag = 1;
 
 /* Set to true in the walproposer bgw. */
 static bool am_walproposer;
@@ -84,6 +85,7 @@ static void assign_neon_safekeepers(const char *newval, void *extra);
 static void nwp_prepare_shmem(void);
 static uint64 backpressure_lag_impl(void);
 static uint64 startup_backpressure_wrap(void);
+static bool replication_feedback_ingest_throttled(void);
 static bool backpressure_throttling_impl(void);
 static void walprop_register_bgworker(void);
 
@@ -232,6 +234,16 @@ nwp_register_gucs(void)
 							PGC_POSTMASTER,
 							0,
 							NULL, NULL, NULL);
+
+	DefineCustomIntVariable(
+							"neon.ingest_throttle_write_lag",
+							"Maximal write lag while a pageserver throttles ingest of this timeline's WAL.",
+							"Takes effect below max_replication_write_lag only.",
+							&ingest_throttle_write_lag,
+							1, 0, INT_MAX,
+							PGC_SIGHUP,
+							GUC_UNIT_MB,
+							NULL, NULL, NULL);
 }
 
 
@@ -368,6 +380,16 @@ backpressure_lag_impl(void)
 		{
 			return (myFlushLsn - applyPtr - max_replication_apply_lag * MB);
 		}
+
+		/*
+		 * The pageserver throttles ingest of this tenant's WAL: don't wait
+		 * for the lag to reach max_replication_write_lag, slow down as soon
+		 * as it exceeds the lower ingest_throttle_write_lag.
+		 */
+		if (writePtr != InvalidXLogRecPtr && myFlushLsn > writePtr + ingest_throttle_write_lag * MB && replication_feedback_ingest_throttled())
+		{
+			return (myFlushLsn - writePtr - ingest_throttle_write_lag * MB);
+		}
 	}
 	return 0;
 }
@@ -607,6 +629,10 @@ record_pageserver_feedback(PageserverFeedback *ps_feedback)
 
 			if (min_feedback.remote_consistent_lsn == InvalidXLogRecPtr || feedback->remote_consistent_lsn < min_feedback.remote_consistent_lsn)
 				min_feedback.remote_consistent_lsn = feedback->remote_consistent_lsn;
+
+			/* the most throttled shard determines how much we need to slow down */
+			if (feedback->ingest_throttle_wait_us > min_feedback.ingest_throttle_wait_us)
+				min_feedback.ingest_throttle_wait_us = feedback->ingest_throttle_wait_us;
 		}
 	}
 	/* Copy min_feedback back to shmem */
@@ -627,6 +653,22 @@ replication_feedback_get_lsns(XLogRecPtr *writeLsn, XLogRecPtr *flushLsn, XLogRe
 	SpinLockRelease(&walprop_shared->mutex);
 }
 
+/*
+ * Returns true if a pageserver reported that its WAL ingest for this timeline
+ * is being throttled.
+ */
+static bool
+replication_feedback_ingest_throttled(void)
+{
+	bool		throttled;
+
+	SpinLockAcquire(&walprop_shared->mutex);
+	throttled = walprop_shared->min_ps_feedback.ingest_throttle_wait_us > 0;
+	SpinLockRelease(&walprop_shared->mutex);
+
+	return throttled;
+}
+
 /*
  * Start walproposer streaming replication
  */
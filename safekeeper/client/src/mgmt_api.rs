@@ -13,6 +13,7 @@
 };
 use utils::id::{NodeId, TenantId, TimelineId};
 use utils::logging::SecretString;
+use utils::lsn::Lsn;
 
 #[derive(Debug, Clone)]
 pub struct Client {
@@ -106,6 +107,20 @@ pub async fn exclude_timeline(
         resp.json().await.map_err(Error::ReceiveBody)
     }
 
+    pub async fn membership_switch(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        req: &models::TimelineMembershipSwitchRequest,
+    ) -> Result<models::TimelineMembershipSwitchResponse> {
+        let uri = format!(
+            "{}/v1/tenant/{}/timeline/{}/membership",
+            self.mgmt_api_endpoint, tenant_id, timeline_id
+        );
+        let resp = self.put(&uri, req).await?;
+        resp.json().await.map_err(Error::ReceiveBody)
+    }
+
     pub async fn delete_timeline(
         &self,
         tenant_id: TenantId,
@@ -156,6 +171,21 @@ pub async fn timeline_status(
         resp.json().await.map_err(Error::ReceiveBody)
     }
 
+    pub async fn timeline_digest(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        from_lsn: Lsn,
+        until_lsn: Lsn,
+    ) -> Result<models::TimelineDigest> {
+        let uri = format!(
+            "{}/v1/tenant/{}/timeline/{}/digest?from_lsn={}&until_lsn={}",
+            self.mgmt_api_endpoint, tenant_id, timeline_id, from_lsn, until_lsn
+        );
+        let resp = self.get(&uri).await?;
+        resp.json().await.map_err(Error::ReceiveBody)
+    }
+
     pub async fn snapshot(
         &self,
         tenant_id: TenantId,
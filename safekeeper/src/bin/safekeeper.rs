@@ -145,6 +145,10 @@ struct Args {
     /// WAL backup horizon.
     #[arg(long)]
     disable_wal_backup: bool,
+    /// Compress offloaded WAL segments, full and partial, with zstd. Segments
+    /// offloaded either way are readable regardless of this setting.
+    #[arg(long)]
+    wal_backup_compression: bool,
     /// If given, enables auth on incoming connections to WAL service endpoint
     /// (--listen-pg). Value specifies path to a .pem public key used for
     /// validations of JWT tokens. Empty string is allowed and means disabling
@@ -382,6 +386,7 @@ async fn main() -> anyhow::Result<()> {
         remote_storage: args.remote_storage,
         max_offloader_lag_bytes: args.max_offloader_lag,
         wal_backup_enabled: !args.disable_wal_backup,
+        wal_backup_compression: args.wal_backup_compression,
         backup_parallel_jobs: args.wal_backup_parallel_jobs,
         pg_auth,
         pg_tenant_only_auth,
@@ -15,12 +15,14 @@
 use utils::bin_ser::LeSer;
 use utils::crashsafe::durable_rename;
 
-use crate::control_file_upgrade::{downgrade_v10_to_v9, upgrade_control_file};
+use crate::control_file_upgrade::{
+    downgrade_v10_to_v9, downgrade_v11_to_v10, upgrade_control_file,
+};
 use crate::metrics::PERSIST_CONTROL_FILE_SECONDS;
 use crate::state::{EvictionState, TimelinePersistentState};
 
 pub const SK_MAGIC: u32 = 0xcafeceefu32;
-pub const SK_FORMAT_VERSION: u32 = 10;
+pub const SK_FORMAT_VERSION: u32 = 11;
 
 // contains persistent metadata for safekeeper
 pub const CONTROL_FILE_NAME: &str = "safekeeper.control";
@@ -169,13 +171,20 @@ pub(crate) fn write_to_buf(&self) -> Result<Vec<u8>> {
         let mut buf: Vec<u8> = Vec::new();
         WriteBytesExt::write_u32::<LittleEndian>(&mut buf, SK_MAGIC)?;
 
-        if self.mconf.generation == INVALID_GENERATION {
+        if self.mconf.generation == INVALID_GENERATION && self.replication_slots.is_empty() {
             // Temp hack for forward compatibility test: in case of none
             // configuration save cfile in previous v9 format.
             const PREV_FORMAT_VERSION: u32 = 9;
             let prev = downgrade_v10_to_v9(self);
             WriteBytesExt::write_u32::<LittleEndian>(&mut buf, PREV_FORMAT_VERSION)?;
             prev.ser_into(&mut buf)?;
+        } else if self.replication_slots.is_empty() {
+            // Likewise, v11 differs only by replication slots, so write v10
+            // unless there are some.
+            const PREV_FORMAT_VERSION: u32 = 10;
+            let prev = downgrade_v11_to_v10(self);
+            WriteBytesExt::write_u32::<LittleEndian>(&mut buf, PREV_FORMAT_VERSION)?;
+            prev.ser_into(&mut buf)?;
         } else {
             // otherwise, we write the current format version
             WriteBytesExt::write_u32::<LittleEndian>(&mut buf, SK_FORMAT_VERSION)?;
@@ -238,6 +247,7 @@ mod test {
     use utils::lsn::Lsn;
 
     use super::*;
+    use crate::state::ReplicationSlot;
 
     const NO_SYNC: bool = true;
 
@@ -262,6 +272,31 @@ async fn test_read_write_safekeeper_state() -> anyhow::Result<()> {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_read_write_replication_slots() -> anyhow::Result<()> {
+        let tempdir = camino_tempfile::tempdir()?;
+        let mut state = TimelinePersistentState::empty();
+        let mut storage = FileStorage::create_new(tempdir.path(), state.clone(), NO_SYNC).await?;
+
+        // Slots are kept even without membership configuration.
+        state.replication_slots = vec![ReplicationSlot {
+            name: "receivewal".to_owned(),
+            restart_lsn: Lsn(0x1000000),
+        }];
+        storage.persist(&state).await?;
+        let loaded_state = FileStorage::load_control_file_from_dir(tempdir.path())?;
+        assert_eq!(loaded_state, state);
+
+        // Dropping the last slot writes the previous format again.
+        state.replication_slots.clear();
+        storage.persist(&state).await?;
+        let data = fs::read(tempdir.path().join(CONTROL_FILE_NAME)).await?;
+        assert_eq!(u32::from_le_bytes(data[4..8].try_into()?), 9);
+        let loaded_state = FileStorage::load_control_file_from_dir(tempdir.path())?;
+        assert!(loaded_state.replication_slots.is_empty());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_safekeeper_state_checksum_mismatch() -> anyhow::Result<()> {
         let tempdir = camino_tempfile::tempdir()?;
@@ -11,7 +11,7 @@
 use chrono::{DateTime, Utc};
 use postgres_ffi::v14::xlog_utils::{IsPartialXLogFileName, IsXLogFileName};
 use postgres_ffi::{MAX_SEND_SIZE, XLogSegNo};
-use safekeeper_api::models::WalSenderState;
+use safekeeper_api::models::{TimelineDigest, WalSenderState};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use utils::id::{NodeId, TenantId, TenantTimelineId, TimelineId};
@@ -350,11 +350,6 @@ pub struct TimelineDigestRequest {
     pub until_lsn: Lsn,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct TimelineDigest {
-    pub sha256: String,
-}
-
 pub async fn calculate_digest(
     tli: &WalResidentTimeline,
     request: TimelineDigestRequest,
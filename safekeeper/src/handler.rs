@@ -10,7 +10,7 @@
 use pageserver_api::shard::{ShardIdentity, ShardStripeSize};
 use postgres_backend::{PostgresBackend, QueryError};
 use postgres_ffi::PG_TLI;
-use pq_proto::{BeMessage, FeStartupPacket, INT4_OID, RowDescriptor, TEXT_OID};
+use pq_proto::{BeMessage, FeStartupPacket, INT4_OID, INT8_OID, RowDescriptor, TEXT_OID};
 use regex::Regex;
 use safekeeper_api::Term;
 use safekeeper_api::models::ConnectionId;
@@ -58,9 +58,19 @@ enum SafekeeperPostgresCommand {
     StartReplication {
         start_lsn: Lsn,
         term: Option<Term>,
+        slot_name: Option<String>,
     },
     IdentifySystem,
     TimelineStatus,
+    CreateReplicationSlot {
+        slot_name: String,
+    },
+    DropReplicationSlot {
+        slot_name: String,
+    },
+    ReadReplicationSlot {
+        slot_name: String,
+    },
 }
 
 fn parse_cmd(cmd: &str) -> anyhow::Result<SafekeeperPostgresCommand> {
@@ -112,24 +122,63 @@ fn parse_cmd(cmd: &str) -> anyhow::Result<SafekeeperPostgresCommand> {
     } else if cmd.starts_with("START_REPLICATION") {
         let re = Regex::new(
             // We follow postgres START_REPLICATION LOGICAL options to pass term.
-            r"START_REPLICATION(?: SLOT [^ ]+)?(?: PHYSICAL)? ([[:xdigit:]]+/[[:xdigit:]]+)(?: \(term='(\d+)'\))?",
+            r#"START_REPLICATION(?: SLOT "?([^ "]+)"?)?(?: PHYSICAL)? ([[:xdigit:]]+/[[:xdigit:]]+)(?: \(term='(\d+)'\))?"#,
         )
         .unwrap();
         let caps = re
             .captures(cmd)
             .context(format!("failed to parse START_REPLICATION command {}", cmd))?;
+        let slot_name = caps.get(1).map(|m| m.as_str().to_owned());
         let start_lsn =
-            Lsn::from_str(&caps[1]).context("parse start LSN from START_REPLICATION command")?;
-        let term = if let Some(m) = caps.get(2) {
+            Lsn::from_str(&caps[2]).context("parse start LSN from START_REPLICATION command")?;
+        let term = if let Some(m) = caps.get(3) {
             Some(m.as_str().parse::<u64>().context("invalid term")?)
         } else {
             None
         };
-        Ok(SafekeeperPostgresCommand::StartReplication { start_lsn, term })
+        Ok(SafekeeperPostgresCommand::StartReplication {
+            start_lsn,
+            term,
+            slot_name,
+        })
     } else if cmd.starts_with("IDENTIFY_SYSTEM") {
         Ok(SafekeeperPostgresCommand::IdentifySystem)
     } else if cmd.starts_with("TIMELINE_STATUS") {
         Ok(SafekeeperPostgresCommand::TimelineStatus)
+    } else if cmd.starts_with("CREATE_REPLICATION_SLOT") {
+        // Only persistent physical slots are supported; they always reserve
+        // WAL, so RESERVE_WAL in either the old or the parenthesized options
+        // syntax is accepted and ignored.
+        let re = Regex::new(
+            r#"^CREATE_REPLICATION_SLOT\s+"?([^ "]+)"?\s+PHYSICAL(?:\s+RESERVE_WAL|\s+\(\s*RESERVE_WAL(?:\s+'?true'?)?\s*\))?\s*;?$"#,
+        )
+        .unwrap();
+        let caps = re.captures(cmd).context(format!(
+            "failed to parse CREATE_REPLICATION_SLOT command {}, only persistent physical slots are supported",
+            cmd
+        ))?;
+        Ok(SafekeeperPostgresCommand::CreateReplicationSlot {
+            slot_name: caps[1].to_owned(),
+        })
+    } else if cmd.starts_with("DROP_REPLICATION_SLOT") {
+        // WAIT is meaningless here as slots are never acquired exclusively.
+        let re = Regex::new(r#"^DROP_REPLICATION_SLOT\s+"?([^ "]+)"?(?:\s+WAIT)?\s*;?$"#).unwrap();
+        let caps = re.captures(cmd).context(format!(
+            "failed to parse DROP_REPLICATION_SLOT command {}",
+            cmd
+        ))?;
+        Ok(SafekeeperPostgresCommand::DropReplicationSlot {
+            slot_name: caps[1].to_owned(),
+        })
+    } else if cmd.starts_with("READ_REPLICATION_SLOT") {
+        let re = Regex::new(r#"^READ_REPLICATION_SLOT\s+"?([^ "]+)"?\s*;?$"#).unwrap();
+        let caps = re.captures(cmd).context(format!(
+            "failed to parse READ_REPLICATION_SLOT command {}",
+            cmd
+        ))?;
+        Ok(SafekeeperPostgresCommand::ReadReplicationSlot {
+            slot_name: caps[1].to_owned(),
+        })
     } else {
         anyhow::bail!("unsupported command {cmd}");
     }
@@ -141,6 +190,9 @@ fn cmd_to_string(cmd: &SafekeeperPostgresCommand) -> &str {
         SafekeeperPostgresCommand::StartReplication { .. } => "START_REPLICATION",
         SafekeeperPostgresCommand::TimelineStatus => "TIMELINE_STATUS",
         SafekeeperPostgresCommand::IdentifySystem => "IDENTIFY_SYSTEM",
+        SafekeeperPostgresCommand::CreateReplicationSlot { .. } => "CREATE_REPLICATION_SLOT",
+        SafekeeperPostgresCommand::DropReplicationSlot { .. } => "DROP_REPLICATION_SLOT",
+        SafekeeperPostgresCommand::ReadReplicationSlot { .. } => "READ_REPLICATION_SLOT",
     }
 }
 
@@ -342,13 +394,26 @@ fn process_query(
                         .instrument(info_span!("WAL receiver"))
                         .await
                 }
-                SafekeeperPostgresCommand::StartReplication { start_lsn, term } => {
-                    self.handle_start_replication(pgb, start_lsn, term)
+                SafekeeperPostgresCommand::StartReplication {
+                    start_lsn,
+                    term,
+                    slot_name,
+                } => {
+                    self.handle_start_replication(pgb, start_lsn, term, slot_name)
                         .instrument(info_span!("WAL sender"))
                         .await
                 }
                 SafekeeperPostgresCommand::IdentifySystem => self.handle_identify_system(pgb).await,
                 SafekeeperPostgresCommand::TimelineStatus => self.handle_timeline_status(pgb).await,
+                SafekeeperPostgresCommand::CreateReplicationSlot { slot_name } => {
+                    self.handle_create_replication_slot(pgb, &slot_name).await
+                }
+                SafekeeperPostgresCommand::DropReplicationSlot { slot_name } => {
+                    self.handle_drop_replication_slot(pgb, &slot_name).await
+                }
+                SafekeeperPostgresCommand::ReadReplicationSlot { slot_name } => {
+                    self.handle_read_replication_slot(pgb, &slot_name).await
+                }
             }
         })
     }
@@ -494,6 +559,104 @@ async fn handle_identify_system<IO: AsyncRead + AsyncWrite + Unpin>(
         Ok(())
     }
 
+    ///
+    /// Handle CREATE_REPLICATION_SLOT replication command
+    ///
+    async fn handle_create_replication_slot<IO: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        pgb: &mut PostgresBackend<IO>,
+        slot_name: &str,
+    ) -> Result<(), QueryError> {
+        let tli = self
+            .global_timelines
+            .get(self.ttid)
+            .map_err(|e| QueryError::Other(e.into()))?;
+        let slot = tli
+            .create_replication_slot(slot_name, None)
+            .await
+            .map_err(|e| QueryError::Other(e.into()))?;
+
+        let restart_lsn = slot.restart_lsn.to_string();
+        pgb.write_message_noflush(&BeMessage::RowDescription(&[
+            RowDescriptor::text_col(b"slot_name"),
+            RowDescriptor::text_col(b"consistent_point"),
+            RowDescriptor::text_col(b"snapshot_name"),
+            RowDescriptor::text_col(b"output_plugin"),
+        ]))?
+        .write_message_noflush(&BeMessage::DataRow(&[
+            Some(slot.name.as_bytes()),
+            Some(restart_lsn.as_bytes()),
+            None,
+            None,
+        ]))?
+        .write_message_noflush(&BeMessage::CommandComplete(b"CREATE_REPLICATION_SLOT"))?;
+        Ok(())
+    }
+
+    ///
+    /// Handle DROP_REPLICATION_SLOT replication command
+    ///
+    async fn handle_drop_replication_slot<IO: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        pgb: &mut PostgresBackend<IO>,
+        slot_name: &str,
+    ) -> Result<(), QueryError> {
+        let tli = self
+            .global_timelines
+            .get(self.ttid)
+            .map_err(|e| QueryError::Other(e.into()))?;
+        tli.drop_replication_slot(slot_name)
+            .await
+            .map_err(|e| QueryError::Other(e.into()))?;
+
+        pgb.write_message_noflush(&BeMessage::CommandComplete(b"DROP_REPLICATION_SLOT"))?;
+        Ok(())
+    }
+
+    ///
+    /// Handle READ_REPLICATION_SLOT replication command. Like in postgres, a
+    /// row of NULLs is returned if the slot doesn't exist.
+    ///
+    async fn handle_read_replication_slot<IO: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        pgb: &mut PostgresBackend<IO>,
+        slot_name: &str,
+    ) -> Result<(), QueryError> {
+        let tli = self
+            .global_timelines
+            .get(self.ttid)
+            .map_err(|e| QueryError::Other(e.into()))?;
+        let restart_lsn = tli
+            .replication_slots()
+            .await
+            .into_iter()
+            .find(|s| s.name == slot_name)
+            .map(|s| s.restart_lsn.to_string());
+        let tli = PG_TLI.to_string();
+
+        pgb.write_message_noflush(&BeMessage::RowDescription(&[
+            RowDescriptor::text_col(b"slot_type"),
+            RowDescriptor::text_col(b"restart_lsn"),
+            RowDescriptor {
+                name: b"restart_tli",
+                typoid: INT8_OID,
+                typlen: 8,
+                ..Default::default()
+            },
+        ]))?;
+        let row: [Option<&[u8]>; 3] = match &restart_lsn {
+            Some(restart_lsn) => [
+                Some(b"physical"),
+                Some(restart_lsn.as_bytes()),
+                Some(tli.as_bytes()),
+            ],
+            None => [None, None, None],
+        };
+        pgb.write_message_noflush(&BeMessage::DataRow(&row))?
+            .write_message_noflush(&BeMessage::CommandComplete(b"READ_REPLICATION_SLOT"))?;
+        Ok(())
+    }
+
     /// Returns true if current connection is a replication connection, originating
     /// from a walproposer recovery function. This connection gets a special handling:
     /// safekeeper must stream all local WAL till the flush_lsn, whether committed or not.
@@ -543,4 +706,54 @@ fn test_start_wal_push_parse() {
             _ => panic!("unexpected command"),
         }
     }
+
+    /// Test parsing of replication slot commands as sent by pg_receivewal
+    #[test]
+    fn test_replication_slot_commands_parse() {
+        for cmd in [
+            "CREATE_REPLICATION_SLOT \"receivewal\" PHYSICAL RESERVE_WAL",
+            "CREATE_REPLICATION_SLOT \"receivewal\" PHYSICAL (RESERVE_WAL)",
+            "CREATE_REPLICATION_SLOT receivewal PHYSICAL",
+        ] {
+            match super::parse_cmd(cmd).expect("failed to parse") {
+                SafekeeperPostgresCommand::CreateReplicationSlot { slot_name } => {
+                    assert_eq!(slot_name, "receivewal")
+                }
+                _ => panic!("unexpected command"),
+            }
+        }
+        super::parse_cmd("CREATE_REPLICATION_SLOT \"receivewal\" TEMPORARY PHYSICAL")
+            .expect_err("temporary slots are not supported");
+        super::parse_cmd("CREATE_REPLICATION_SLOT \"receivewal\" LOGICAL pgoutput")
+            .expect_err("logical slots are not supported");
+
+        match super::parse_cmd("DROP_REPLICATION_SLOT \"receivewal\" WAIT") {
+            Ok(SafekeeperPostgresCommand::DropReplicationSlot { slot_name }) => {
+                assert_eq!(slot_name, "receivewal")
+            }
+            _ => panic!("unexpected command"),
+        }
+
+        match super::parse_cmd("START_REPLICATION SLOT \"receivewal\" 0/1000000 TIMELINE 1") {
+            Ok(SafekeeperPostgresCommand::StartReplication {
+                start_lsn,
+                term,
+                slot_name,
+            }) => {
+                assert_eq!(start_lsn, utils::lsn::Lsn(0x1000000));
+                assert_eq!(term, None);
+                assert_eq!(slot_name.as_deref(), Some("receivewal"));
+            }
+            _ => panic!("unexpected command"),
+        }
+        match super::parse_cmd("START_REPLICATION PHYSICAL 0/1000000 (term='5')") {
+            Ok(SafekeeperPostgresCommand::StartReplication {
+                term, slot_name, ..
+            }) => {
+                assert_eq!(term, Some(5));
+                assert_eq!(slot_name, None);
+            }
+            _ => panic!("unexpected command"),
+        }
+    }
 }
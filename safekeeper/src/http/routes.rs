@@ -16,9 +16,9 @@
 use hyper::{Body, Request, Response, StatusCode};
 use postgres_ffi::WAL_SEGMENT_SIZE;
 use safekeeper_api::models::{
-    AcceptorStateStatus, PullTimelineRequest, SafekeeperStatus, SkTimelineInfo, TenantDeleteResult,
-    TermSwitchApiEntry, TimelineCopyRequest, TimelineCreateRequest, TimelineDeleteResult,
-    TimelineStatus, TimelineTermBumpRequest,
+    AcceptorStateStatus, PullTimelineRequest, ReplicationSlotCreateRequest, ReplicationSlotInfo,
+    SafekeeperStatus, SkTimelineInfo, TenantDeleteResult, TermSwitchApiEntry, TimelineCopyRequest,
+    TimelineCreateRequest, TimelineDeleteResult, TimelineStatus, TimelineTermBumpRequest,
 };
 use safekeeper_api::{ServerInfo, membership, models};
 use storage_broker::proto::{SafekeeperTimelineInfo, TenantTimelineId as ProtoTenantTimelineId};
@@ -33,6 +33,7 @@
 
 use crate::debug_dump::TimelineDigestRequest;
 use crate::safekeeper::TermLsn;
+use crate::state::ReplicationSlotError;
 use crate::timelines_global_map::DeleteOrExclude;
 use crate::{
     GlobalTimelines, SafeKeeperConf, copy_timeline, debug_dump, patch_control_file, pull_timeline,
@@ -112,6 +113,7 @@ async fn timeline_create_handler(mut request: Request<Body>) -> Result<Response<
             server_info,
             request_data.start_lsn,
             request_data.commit_lsn.unwrap_or(request_data.start_lsn),
+            request_data.replace_deleted,
         )
         .await
         .map_err(ApiError::InternalServerError)?;
@@ -286,6 +288,8 @@ pub enum DeleteOrExcludeError {
         requested: membership::Configuration,
         current: membership::Configuration,
     },
+    #[error("timeline has replication slots {0:?}, drop them first")]
+    ReplicationSlots(Vec<String>),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -298,6 +302,9 @@ fn from(de: DeleteOrExcludeError) -> ApiError {
                 requested: _,
                 current: _,
             } => ApiError::Conflict(de.to_string()),
+            DeleteOrExcludeError::ReplicationSlots(_) => {
+                ApiError::PreconditionFailed(de.to_string().into())
+            }
             DeleteOrExcludeError::Other(e) => ApiError::InternalServerError(e),
         }
     }
@@ -514,6 +521,76 @@ async fn timeline_term_bump_handler(
     json_response(StatusCode::OK, response)
 }
 
+/// Convert ReplicationSlotError to ApiError.
+impl From<ReplicationSlotError> for ApiError {
+    fn from(e: ReplicationSlotError) -> ApiError {
+        match e {
+            ReplicationSlotError::InvalidName(_)
+            | ReplicationSlotError::InvalidRestartLsn { .. } => ApiError::BadRequest(e.into()),
+            ReplicationSlotError::AlreadyExists(_) => ApiError::Conflict(e.to_string()),
+            ReplicationSlotError::NotFound(_) => ApiError::NotFound(e.into()),
+            ReplicationSlotError::Other(e) => ApiError::InternalServerError(e),
+        }
+    }
+}
+
+/// List replication slots of the timeline.
+async fn replication_slot_list_handler(request: Request<Body>) -> Result<Response<Body>, ApiError> {
+    let ttid = TenantTimelineId::new(
+        parse_request_param(&request, "tenant_id")?,
+        parse_request_param(&request, "timeline_id")?,
+    );
+    check_permission(&request, Some(ttid.tenant_id))?;
+
+    let global_timelines = get_global_timelines(&request);
+    let tli = global_timelines.get(ttid).map_err(ApiError::from)?;
+    let slots = tli
+        .replication_slots()
+        .await
+        .into_iter()
+        .map(ReplicationSlotInfo::from)
+        .collect::<Vec<_>>();
+    json_response(StatusCode::OK, slots)
+}
+
+/// Create replication slot retaining WAL for an external consumer.
+async fn replication_slot_create_handler(
+    mut request: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    let ttid = TenantTimelineId::new(
+        parse_request_param(&request, "tenant_id")?,
+        parse_request_param(&request, "timeline_id")?,
+    );
+    check_permission(&request, Some(ttid.tenant_id))?;
+
+    let request_data: ReplicationSlotCreateRequest = json_request(&mut request).await?;
+
+    let global_timelines = get_global_timelines(&request);
+    let tli = global_timelines.get(ttid).map_err(ApiError::from)?;
+    let slot = tli
+        .create_replication_slot(&request_data.name, request_data.restart_lsn)
+        .await?;
+    json_response(StatusCode::CREATED, ReplicationSlotInfo::from(slot))
+}
+
+/// Drop replication slot, releasing WAL it retains.
+async fn replication_slot_drop_handler(
+    mut request: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    let ttid = TenantTimelineId::new(
+        parse_request_param(&request, "tenant_id")?,
+        parse_request_param(&request, "timeline_id")?,
+    );
+    let slot_name: String = parse_request_param(&request, "slot_name")?;
+    check_permission(&request, Some(ttid.tenant_id))?;
+    ensure_no_body(&mut request).await?;
+
+    let global_timelines = get_global_timelines(&request);
+    let tli = global_timelines.get(ttid).map_err(ApiError::from)?;
+    tli.drop_replication_slot(&slot_name).await?;
+    json_response(StatusCode::OK, ())
+}
+
 /// Used only in tests to hand craft required data.
 async fn record_safekeeper_info(mut request: Request<Body>) -> Result<Response<Body>, ApiError> {
     let ttid = TenantTimelineId::new(
@@ -757,6 +834,18 @@ pub fn make_router(
             "/v1/tenant/:tenant_id/timeline/:timeline_id/term_bump",
             |r| request_span(r, timeline_term_bump_handler),
         )
+        .get(
+            "/v1/tenant/:tenant_id/timeline/:timeline_id/replication_slots",
+            |r| request_span(r, replication_slot_list_handler),
+        )
+        .post(
+            "/v1/tenant/:tenant_id/timeline/:timeline_id/replication_slots",
+            |r| request_span(r, replication_slot_create_handler),
+        )
+        .delete(
+            "/v1/tenant/:tenant_id/timeline/:timeline_id/replication_slots/:slot_name",
+            |r| request_span(r, replication_slot_drop_handler),
+        )
         .post("/v1/record_safekeeper_info/:tenant_id/:timeline_id", |r| {
             request_span(r, record_safekeeper_info)
         })
@@ -101,6 +101,7 @@ pub struct SafeKeeperConf {
     pub max_offloader_lag_bytes: u64,
     pub backup_parallel_jobs: usize,
     pub wal_backup_enabled: bool,
+    pub wal_backup_compression: bool,
     pub pg_auth: Option<Arc<JwtAuth>>,
     pub pg_tenant_only_auth: Option<Arc<JwtAuth>>,
     pub http_auth: Option<Arc<SwappableJwtAuth>>,
@@ -149,6 +150,7 @@ pub fn dummy() -> Self {
             broker_keepalive_interval: Duration::from_secs(5),
             peer_recovery_enabled: true,
             wal_backup_enabled: true,
+            wal_backup_compression: false,
             backup_parallel_jobs: 1,
             pg_auth: None,
             pg_tenant_only_auth: None,
@@ -353,6 +353,7 @@ async fn read_first_message(
                             server_info,
                             Lsn::INVALID,
                             Lsn::INVALID,
+                            false,
                         )
                         .await
                         .context("create timeline")?
@@ -7,7 +7,8 @@
 /// We hold WAL till it is consumed by
 /// 1) pageserver (remote_consistent_lsn)
 /// 2) s3 offloading.
-/// 3) Additionally we must store WAL since last local commit_lsn because
+/// 3) replication slots of external consumers (restart_lsn).
+/// 4) Additionally we must store WAL since last local commit_lsn because
 ///    that's where we start looking for last WAL record on start.
 ///
 /// If some peer safekeeper misses data it will fetch it from the remote
@@ -25,6 +26,9 @@ pub(crate) fn calc_horizon_lsn(state: &StateSnapshot, extra_horizon_lsn: Option<
     // flush_lsn, but let's be double safe by including it as well.
     horizon_lsn = min(horizon_lsn, state.cfile_commit_lsn);
     horizon_lsn = min(horizon_lsn, state.flush_lsn);
+    if let Some(slots_horizon_lsn) = state.cfile_replication_slots_horizon {
+        horizon_lsn = min(horizon_lsn, slots_horizon_lsn);
+    }
     if let Some(extra_horizon_lsn) = extra_horizon_lsn {
         horizon_lsn = min(horizon_lsn, extra_horizon_lsn);
     }
@@ -1664,6 +1664,64 @@ async fn test_non_consecutive_write() {
             .unwrap_err();
     }
 
+    #[tokio::test]
+    async fn test_replication_slots() {
+        let mut persisted_state = test_sk_state();
+        persisted_state.timeline_start_lsn = Lsn(0x1000000);
+        persisted_state.commit_lsn = Lsn(0x3000000);
+        persisted_state.backup_lsn = Lsn(0x1800000);
+        persisted_state.remote_consistent_lsn = Lsn(0x2000000);
+        let mut state = TimelineState::new(InMemoryState { persisted_state });
+
+        // Defaults to commit_lsn, must be within the WAL not yet removed.
+        let slot = state.create_replication_slot("b", None).await.unwrap();
+        assert_eq!(slot.restart_lsn, Lsn(0x3000000));
+        state
+            .create_replication_slot("a", Some(Lsn(0x100)))
+            .await
+            .unwrap_err();
+        state
+            .create_replication_slot("a", Some(Lsn(0x1400000)))
+            .await
+            .unwrap_err();
+        state
+            .create_replication_slot("a", Some(Lsn(0x3100000)))
+            .await
+            .unwrap_err();
+        state
+            .create_replication_slot("a", Some(Lsn(0x1800000)))
+            .await
+            .unwrap();
+        state.create_replication_slot("a", None).await.unwrap_err();
+        state
+            .create_replication_slot("Bad-Name", None)
+            .await
+            .unwrap_err();
+        let names = state
+            .replication_slots
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a", "b"]);
+        assert_eq!(state.replication_slots_horizon(), Some(Lsn(0x1800000)));
+
+        // Progress within the segment isn't persisted.
+        state
+            .advance_replication_slot("a", Lsn(0x1900000))
+            .await
+            .unwrap();
+        assert_eq!(state.replication_slots_horizon(), Some(Lsn(0x1800000)));
+        state
+            .advance_replication_slot("a", Lsn(0x2100000))
+            .await
+            .unwrap();
+        assert_eq!(state.replication_slots_horizon(), Some(Lsn(0x2100000)));
+
+        state.drop_replication_slot("a").await.unwrap();
+        state.drop_replication_slot("a").await.unwrap_err();
+        assert_eq!(state.replication_slots_horizon(), Some(Lsn(0x3000000)));
+    }
+
     #[test]
     fn test_find_highest_common_point_none() {
         let prop_th = TermHistory(vec![(0, Lsn(1)).into()]);
@@ -1768,6 +1826,10 @@ fn test_sk_state_bincode_serde_roundtrip() {
             partial_backup: crate::wal_backup_partial::State::default(),
             eviction_state: EvictionState::Present,
             creation_ts: UNIX_EPOCH,
+            replication_slots: vec![crate::state::ReplicationSlot {
+                name: "receivewal".to_owned(),
+                restart_lsn: Lsn(1234560000),
+            }],
         };
 
         let ser = state.ser().unwrap();
@@ -36,6 +36,7 @@
 use crate::send_interpreted_wal::{
     Batch, InterpretedWalReader, InterpretedWalReaderHandle, InterpretedWalSender,
 };
+use crate::state::ReplicationSlotError;
 use crate::timeline::WalResidentTimeline;
 use crate::wal_reader_stream::StreamingWalReader;
 use crate::wal_storage::WalReader;
@@ -444,15 +445,28 @@ pub async fn handle_start_replication<IO: AsyncRead + AsyncWrite + Unpin + Send>
         pgb: &mut PostgresBackend<IO>,
         start_pos: Lsn,
         term: Option<Term>,
+        slot_name: Option<String>,
     ) -> Result<(), QueryError> {
         let tli = self
             .global_timelines
             .get(self.ttid)
             .map_err(|e| QueryError::Other(e.into()))?;
+        if let Some(slot_name) = &slot_name {
+            if !tli
+                .replication_slots()
+                .await
+                .iter()
+                .any(|s| &s.name == slot_name)
+            {
+                return Err(QueryError::Other(
+                    ReplicationSlotError::NotFound(slot_name.clone()).into(),
+                ));
+            }
+        }
         let residence_guard = tli.wal_residence_guard().await?;
 
         if let Err(end) = self
-            .handle_start_replication_guts(pgb, start_pos, term, residence_guard)
+            .handle_start_replication_guts(pgb, start_pos, term, slot_name, residence_guard)
             .await
         {
             let info = tli.get_safekeeper_info(&self.conf).await;
@@ -469,6 +483,7 @@ pub async fn handle_start_replication_guts<IO: AsyncRead + AsyncWrite + Unpin +
         pgb: &mut PostgresBackend<IO>,
         start_pos: Lsn,
         term: Option<Term>,
+        slot_name: Option<String>,
         tli: WalResidentTimeline,
     ) -> Result<(), CopyStreamHandlerEnd> {
         let appname = self.appname.clone();
@@ -520,12 +535,13 @@ pub async fn handle_start_replication_guts<IO: AsyncRead + AsyncWrite + Unpin +
         }
 
         info!(
-            "starting streaming from {:?}, available WAL ends at {}, recovery={}, appname={:?}, protocol={:?}",
+            "starting streaming from {:?}, available WAL ends at {}, recovery={}, appname={:?}, protocol={:?}, slot={:?}",
             start_pos,
             end_pos,
             matches!(end_watch, EndWatch::Flush(_)),
             appname,
             self.protocol(),
+            slot_name,
         );
 
         // switch to copy
@@ -661,6 +677,7 @@ pub async fn handle_start_replication_guts<IO: AsyncRead + AsyncWrite + Unpin +
             reader,
             ws_guard: ws_guard.clone(),
             tli,
+            slot_name,
         };
 
         let res = tokio::select! {
@@ -977,6 +994,8 @@ struct ReplyReader<IO> {
     reader: PostgresBackendReader<IO>,
     ws_guard: Arc<WalSenderGuard>,
     tli: WalResidentTimeline,
+    /// Replication slot advanced by flush positions the receiver reports.
+    slot_name: Option<String>,
 }
 
 impl<IO: AsyncRead + AsyncWrite + Unpin> ReplyReader<IO> {
@@ -1009,6 +1028,14 @@ async fn handle_feedback(&mut self, msg: &Bytes) -> anyhow::Result<()> {
                 self.ws_guard
                     .walsenders
                     .record_standby_reply(self.ws_guard.id, &reply);
+                if let Some(slot_name) = &self.slot_name {
+                    // Receivers which don't flush WAL report invalid LSN.
+                    if reply.flush_lsn != Lsn::INVALID {
+                        self.tli
+                            .advance_replication_slot(slot_name, reply.flush_lsn)
+                            .await?;
+                    }
+                }
             }
             Some(NEON_STATUS_UPDATE_TAG_BYTE) => {
                 // pageserver sends this.
@@ -1,14 +1,16 @@
 //! Defines per timeline data stored persistently (SafeKeeperPersistentState)
 //! and its wrapper with in memory layer (SafekeeperState).
 
-use std::cmp::max;
+use std::cmp::{max, min};
 use std::ops::Deref;
 use std::time::SystemTime;
 
 use anyhow::{Result, bail};
 use postgres_ffi::WAL_SEGMENT_SIZE;
 use safekeeper_api::membership::Configuration;
-use safekeeper_api::models::{TimelineMembershipSwitchResponse, TimelineTermBumpResponse};
+use safekeeper_api::models::{
+    ReplicationSlotInfo, TimelineMembershipSwitchResponse, TimelineTermBumpResponse,
+};
 use safekeeper_api::{INITIAL_TERM, ServerInfo, Term};
 use serde::{Deserialize, Serialize};
 use tracing::info;
@@ -68,6 +70,11 @@ pub struct TimelinePersistentState {
     /// WAL files from remote storage to serve the timeline.
     pub eviction_state: EvictionState,
     pub creation_ts: SystemTime,
+    /// Replication slots of external WAL consumers, sorted by name. WAL since
+    /// the oldest restart_lsn is neither removed locally nor deleted from
+    /// remote storage. They are specific to this safekeeper, see
+    /// [`ReplicationSlotInfo`].
+    pub replication_slots: Vec<ReplicationSlot>,
 }
 
 /// State of the local WAL files. Used to track current timeline state,
@@ -82,6 +89,59 @@ pub enum EvictionState {
     Offloaded(Lsn),
 }
 
+/// Named replication slot of an external WAL consumer like pg_receivewal.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReplicationSlot {
+    pub name: String,
+    /// WAL since this LSN may still be needed by the consumer. Advanced by
+    /// flush positions the consumer reports while streaming.
+    pub restart_lsn: Lsn,
+}
+
+impl From<ReplicationSlot> for ReplicationSlotInfo {
+    fn from(slot: ReplicationSlot) -> Self {
+        ReplicationSlotInfo {
+            name: slot.name,
+            restart_lsn: slot.restart_lsn,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ReplicationSlotError {
+    #[error(
+        "invalid replication slot name \"{0}\": only lower case letters, numbers and underscores up to 63 characters are allowed"
+    )]
+    InvalidName(String),
+    #[error("replication slot \"{0}\" already exists")]
+    AlreadyExists(String),
+    #[error("replication slot \"{0}\" does not exist")]
+    NotFound(String),
+    #[error("restart_lsn {requested} is out of the retained WAL range {start}..{end}")]
+    InvalidRestartLsn {
+        requested: Lsn,
+        start: Lsn,
+        end: Lsn,
+    },
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Same as in postgres: NAMEDATALEN - 1.
+const MAX_SLOT_NAME_LEN: usize = 63;
+
+fn validate_slot_name(name: &str) -> Result<(), ReplicationSlotError> {
+    if name.is_empty()
+        || name.len() > MAX_SLOT_NAME_LEN
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(ReplicationSlotError::InvalidName(name.to_owned()));
+    }
+    Ok(())
+}
+
 impl TimelinePersistentState {
     /// commit_lsn is the same as start_lsn in the normal creaiton; see
     /// `TimelineCreateRequest` comments.`
@@ -141,6 +201,7 @@ pub fn new(
             partial_backup: wal_backup_partial::State::default(),
             eviction_state: EvictionState::Present,
             creation_ts: SystemTime::now(),
+            replication_slots: Vec::new(),
         })
     }
 
@@ -158,6 +219,11 @@ pub fn empty() -> Self {
         )
         .unwrap()
     }
+
+    /// Oldest restart_lsn of the replication slots, if there are any.
+    pub fn replication_slots_horizon(&self) -> Option<Lsn> {
+        self.replication_slots.iter().map(|s| s.restart_lsn).min()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -282,6 +348,84 @@ pub async fn membership_switch(
             current_conf: self.mconf.clone(),
         })
     }
+
+    /// Create replication slot `name` retaining WAL since `restart_lsn`, or
+    /// since commit_lsn if it is not given.
+    pub async fn create_replication_slot(
+        &mut self,
+        name: &str,
+        restart_lsn: Option<Lsn>,
+    ) -> Result<ReplicationSlot, ReplicationSlotError> {
+        validate_slot_name(name)?;
+        let mut state = self.start_change();
+        let pos = match state
+            .replication_slots
+            .binary_search_by(|slot| slot.name.as_str().cmp(name))
+        {
+            Ok(_) => return Err(ReplicationSlotError::AlreadyExists(name.to_owned())),
+            Err(pos) => pos,
+        };
+        let restart_lsn = restart_lsn.unwrap_or(state.commit_lsn);
+        // WAL below both remote_consistent_lsn and backup_lsn may already be
+        // removed (see calc_horizon_lsn), so the slot can't retain it.
+        let start = max(
+            state.timeline_start_lsn,
+            min(state.remote_consistent_lsn, state.backup_lsn),
+        );
+        if restart_lsn < start || restart_lsn > state.commit_lsn {
+            return Err(ReplicationSlotError::InvalidRestartLsn {
+                requested: restart_lsn,
+                start,
+                end: state.commit_lsn,
+            });
+        }
+        let slot = ReplicationSlot {
+            name: name.to_owned(),
+            restart_lsn,
+        };
+        state.replication_slots.insert(pos, slot.clone());
+        self.finish_change(&state).await?;
+        info!("created replication slot {} at {}", name, restart_lsn);
+        Ok(slot)
+    }
+
+    /// Drop replication slot `name`, releasing WAL it retains.
+    pub async fn drop_replication_slot(&mut self, name: &str) -> Result<(), ReplicationSlotError> {
+        let mut state = self.start_change();
+        let Some(pos) = state.replication_slots.iter().position(|s| s.name == name) else {
+            return Err(ReplicationSlotError::NotFound(name.to_owned()));
+        };
+        state.replication_slots.remove(pos);
+        self.finish_change(&state).await?;
+        info!("dropped replication slot {}", name);
+        Ok(())
+    }
+
+    /// Advance restart_lsn of replication slot `name` to `lsn` if it is higher.
+    /// WAL is removed segment by segment, so the control file is written only
+    /// once the slot moves into the next segment; until then restart_lsn may
+    /// lag behind the reported position.
+    pub async fn advance_replication_slot(
+        &mut self,
+        name: &str,
+        lsn: Lsn,
+    ) -> Result<(), ReplicationSlotError> {
+        let wal_seg_size = self.server.wal_seg_size as usize;
+        let Some(slot) = self.replication_slots.iter().find(|s| s.name == name) else {
+            return Err(ReplicationSlotError::NotFound(name.to_owned()));
+        };
+        if lsn.segment_number(wal_seg_size) <= slot.restart_lsn.segment_number(wal_seg_size) {
+            return Ok(());
+        }
+        let mut state = self.start_change();
+        for slot in state.replication_slots.iter_mut() {
+            if slot.name == name {
+                slot.restart_lsn = lsn;
+            }
+        }
+        self.finish_change(&state).await?;
+        Ok(())
+    }
 }
 
 impl<CTRL> Deref for TimelineState<CTRL>
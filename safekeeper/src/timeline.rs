@@ -31,7 +31,10 @@
 use crate::receive_wal::WalReceivers;
 use crate::safekeeper::{AcceptorProposerMessage, ProposerAcceptorMessage, SafeKeeper, TermLsn};
 use crate::send_wal::{WalSenders, WalSendersTimelineMetricValues};
-use crate::state::{EvictionState, TimelineMemState, TimelinePersistentState, TimelineState};
+use crate::state::{
+    EvictionState, ReplicationSlot, ReplicationSlotError, TimelineMemState,
+    TimelinePersistentState, TimelineState,
+};
 use crate::timeline_guard::ResidenceGuard;
 use crate::timeline_manager::{AtomicStatus, ManagerCtl};
 use crate::timelines_set::TimelinesSet;
@@ -881,6 +884,46 @@ pub async fn membership_switch(
         state.sk.membership_switch(to).await
     }
 
+    pub async fn replication_slots(self: &Arc<Self>) -> Vec<ReplicationSlot> {
+        let state = self.read_shared_state().await;
+        state.sk.state().replication_slots.clone()
+    }
+
+    pub async fn create_replication_slot(
+        self: &Arc<Self>,
+        name: &str,
+        restart_lsn: Option<Lsn>,
+    ) -> Result<ReplicationSlot, ReplicationSlotError> {
+        let mut state = self.write_shared_state().await;
+        state
+            .sk
+            .state_mut()
+            .create_replication_slot(name, restart_lsn)
+            .await
+    }
+
+    pub async fn drop_replication_slot(
+        self: &Arc<Self>,
+        name: &str,
+    ) -> Result<(), ReplicationSlotError> {
+        let mut state = self.write_shared_state().await;
+        state.sk.state_mut().drop_replication_slot(name).await
+    }
+
+    /// Advance replication slot by position the consumer has flushed.
+    pub async fn advance_replication_slot(
+        self: &Arc<Self>,
+        name: &str,
+        lsn: Lsn,
+    ) -> Result<(), ReplicationSlotError> {
+        let mut state = self.write_shared_state().await;
+        state
+            .sk
+            .state_mut()
+            .advance_replication_slot(name, lsn)
+            .await
+    }
+
     /// Guts of [`Self::wal_residence_guard`] and [`Self::try_wal_residence_guard`]
     async fn do_wal_residence_guard(
         self: &Arc<Self>,
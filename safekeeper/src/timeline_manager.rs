@@ -48,6 +48,7 @@ pub(crate) struct StateSnapshot {
     pub(crate) cfile_commit_lsn: Lsn,
     pub(crate) cfile_remote_consistent_lsn: Lsn,
     pub(crate) cfile_backup_lsn: Lsn,
+    pub(crate) cfile_replication_slots_horizon: Option<Lsn>,
 
     // latest state
     pub(crate) flush_lsn: Lsn,
@@ -71,6 +72,7 @@ fn new(read_guard: ReadGuardSharedState, heartbeat_timeout: Duration) -> Self {
             cfile_commit_lsn: state.commit_lsn,
             cfile_remote_consistent_lsn: state.remote_consistent_lsn,
             cfile_backup_lsn: state.backup_lsn,
+            cfile_replication_slots_horizon: state.replication_slots_horizon(),
             flush_lsn: read_guard.sk.flush_lsn(),
             last_log_term: read_guard.sk.last_log_term(),
             cfile_last_persist_at: state.pers.last_persist_at(),
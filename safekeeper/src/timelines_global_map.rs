@@ -214,6 +214,8 @@ pub fn get_global_broker_active_set(&self) -> Arc<TimelinesSet> {
 
     /// Create a new timeline with the given id. If the timeline already exists, returns
     /// an existing timeline.
+    ///
+    /// A deleted timeline is only recreated with `replace_deleted`.
     pub(crate) async fn create(
         &self,
         ttid: TenantTimelineId,
@@ -221,6 +223,7 @@ pub(crate) async fn create(
         server_info: ServerInfo,
         start_lsn: Lsn,
         commit_lsn: Lsn,
+        replace_deleted: bool,
     ) -> Result<Arc<Timeline>> {
         let (conf, _, _) = {
             let state = self.state.lock().unwrap();
@@ -229,7 +232,7 @@ pub(crate) async fn create(
                 return Ok(timeline);
             }
 
-            if state.tombstones.contains_key(&ttid) {
+            if state.tombstones.contains_key(&ttid) && !replace_deleted {
                 anyhow::bail!("Timeline {ttid} is deleted, refusing to recreate");
             }
 
@@ -245,7 +248,9 @@ pub(crate) async fn create(
         // immediately initialize first WAL segment as well.
         let state = TimelinePersistentState::new(&ttid, mconf, server_info, start_lsn, commit_lsn)?;
         control_file::FileStorage::create_new(&tmp_dir_path, state, conf.no_sync).await?;
-        let timeline = self.load_temp_timeline(ttid, &tmp_dir_path, true).await?;
+        let timeline = self
+            .load_temp_timeline(ttid, &tmp_dir_path, !replace_deleted)
+            .await?;
         Ok(timeline)
     }
 
@@ -470,6 +475,20 @@ pub(crate) async fn delete_or_exclude(
             Ok(timeline) => {
                 info!("deleting timeline {}, action={:?}", ttid, action);
 
+                // Replication slots hold back deletion of WAL from remote
+                // storage; they must be dropped first. They are per
+                // safekeeper, so excluding this one would lose them: new
+                // members get only the donor's slots. Explicit local deletion
+                // is fine as the WAL stays available remotely.
+                if !matches!(action, DeleteOrExclude::DeleteLocal) {
+                    let slots = timeline.replication_slots().await;
+                    if !slots.is_empty() {
+                        return Err(DeleteOrExcludeError::ReplicationSlots(
+                            slots.into_iter().map(|s| s.name).collect(),
+                        ));
+                    }
+                }
+
                 // If node is getting excluded, check the generation first.
                 // Then, while holding the lock cancel the timeline; it will be
                 // unusable after this point, and if node is added back first
@@ -5,16 +5,18 @@
 use std::time::Duration;
 
 use anyhow::{Context, Result};
+use bytes::Bytes;
 use camino::{Utf8Path, Utf8PathBuf};
 use futures::StreamExt;
 use futures::stream::FuturesOrdered;
 use postgres_ffi::v14::xlog_utils::XLogSegNoOffsetToRecPtr;
 use postgres_ffi::{PG_TLI, XLogFileName, XLogSegNo};
 use remote_storage::{
-    DownloadOpts, GenericRemoteStorage, ListingMode, RemotePath, StorageMetadata,
+    DownloadError, DownloadOpts, GenericRemoteStorage, ListingMode, RemotePath, StorageMetadata,
 };
 use safekeeper_api::models::PeerInfo;
 use tokio::fs::File;
+use tokio::io::AsyncReadExt;
 use tokio::select;
 use tokio::sync::mpsc::{self, Receiver, Sender};
 use tokio::sync::{OnceCell, watch};
@@ -36,6 +38,12 @@
 /// Default buffer size when interfacing with [`tokio::fs::File`].
 const BUFFER_SIZE: usize = 32 * 1024;
 
+/// Suffix of the names of zstd compressed objects, see `--wal-backup-compression`.
+///
+/// Full and partial segments may be stored either way, depending on the configuration of the
+/// safekeeper which uploaded them: readers decide by the object name whether to decompress.
+pub const COMPRESSED_OBJECT_SUFFIX: &str = ".zst";
+
 pub struct WalBackupTaskHandle {
     shutdown_tx: Sender<()>,
     handle: JoinHandle<()>,
@@ -82,7 +90,12 @@ pub(crate) async fn update_task(mgr: &mut Manager, need_backup: bool, state: &St
                 return;
             };
 
-            let async_task = backup_task_main(resident, mgr.conf.backup_parallel_jobs, shutdown_rx);
+            let async_task = backup_task_main(
+                resident,
+                mgr.conf.backup_parallel_jobs,
+                mgr.conf.wal_backup_compression,
+                shutdown_rx,
+            );
 
             let handle = if mgr.conf.current_thread_runtime {
                 tokio::spawn(async_task)
@@ -203,6 +216,7 @@ struct WalBackupTask {
     timeline_dir: Utf8PathBuf,
     wal_seg_size: usize,
     parallel_jobs: usize,
+    compression: bool,
     commit_lsn_watch_rx: watch::Receiver<Lsn>,
 }
 
@@ -211,6 +225,7 @@ struct WalBackupTask {
 async fn backup_task_main(
     tli: WalResidentTimeline,
     parallel_jobs: usize,
+    compression: bool,
     mut shutdown_rx: Receiver<()>,
 ) {
     let _guard = WAL_BACKUP_TASKS.guard();
@@ -223,6 +238,7 @@ async fn backup_task_main(
         timeline_dir: tli.get_timeline_dir(),
         timeline: tli,
         parallel_jobs,
+        compression,
     };
 
     // task is spinned up only when wal_seg_size already initialized
@@ -298,6 +314,7 @@ async fn run(&mut self) {
                 self.wal_seg_size,
                 &self.timeline_dir,
                 self.parallel_jobs,
+                self.compression,
             )
             .await
             {
@@ -327,6 +344,7 @@ async fn backup_lsn_range(
     wal_seg_size: usize,
     timeline_dir: &Utf8Path,
     parallel_jobs: usize,
+    compression: bool,
 ) -> Result<()> {
     if parallel_jobs < 1 {
         anyhow::bail!("parallel_jobs must be >= 1");
@@ -352,7 +370,12 @@ async fn backup_lsn_range(
     loop {
         let added_task = match iter.next() {
             Some(s) => {
-                uploads.push_back(backup_single_segment(s, timeline_dir, remote_timeline_path));
+                uploads.push_back(backup_single_segment(
+                    s,
+                    timeline_dir,
+                    remote_timeline_path,
+                    compression,
+                ));
                 true
             }
             None => false,
@@ -391,9 +414,14 @@ async fn backup_single_segment(
     seg: &Segment,
     timeline_dir: &Utf8Path,
     remote_timeline_path: &RemotePath,
+    compression: bool,
 ) -> Result<Segment> {
     let segment_file_path = seg.file_path(timeline_dir)?;
-    let remote_segment_path = seg.remote_path(remote_timeline_path);
+    let remote_segment_path = if compression {
+        seg.compressed_remote_path(remote_timeline_path)
+    } else {
+        seg.remote_path(remote_timeline_path)
+    };
 
     let res = backup_object(&segment_file_path, &remote_segment_path, seg.size()).await;
     if res.is_ok() {
@@ -435,6 +463,10 @@ pub fn remote_path(self, remote_timeline_path: &RemotePath) -> RemotePath {
         remote_timeline_path.join(self.object_name())
     }
 
+    pub fn compressed_remote_path(self, remote_timeline_path: &RemotePath) -> RemotePath {
+        remote_timeline_path.join(self.object_name() + COMPRESSED_OBJECT_SUFFIX)
+    }
+
     pub fn size(self) -> usize {
         (u64::from(self.end_lsn) - u64::from(self.start_lsn)) as usize
     }
@@ -461,14 +493,27 @@ async fn backup_object(
 ) -> Result<()> {
     let storage = get_configured_remote_storage();
 
+    let cancel = CancellationToken::new();
+
+    if is_compressed(target_file) {
+        let compressed = compress_file(source_file, size).await?;
+        let size = compressed.len();
+        return storage
+            .upload_storage_object(
+                futures::stream::once(futures::future::ready(Ok(compressed))),
+                size,
+                target_file,
+                &cancel,
+            )
+            .await;
+    }
+
     let file = File::open(&source_file)
         .await
         .with_context(|| format!("Failed to open file {source_file:?} for wal backup"))?;
 
     let file = tokio_util::io::ReaderStream::with_capacity(file, BUFFER_SIZE);
 
-    let cancel = CancellationToken::new();
-
     storage
         .upload_storage_object(file, size, target_file, &cancel)
         .await
@@ -481,26 +526,59 @@ pub(crate) async fn backup_partial_segment(
 ) -> Result<()> {
     let storage = get_configured_remote_storage();
 
+    let cancel = CancellationToken::new();
+    let metadata = Some(StorageMetadata::from([("sk_type", "partial_segment")]));
+
+    if is_compressed(target_file) {
+        let compressed = compress_file(source_file, size).await?;
+        let size = compressed.len();
+        return storage
+            .upload(
+                futures::stream::once(futures::future::ready(Ok(compressed))),
+                size,
+                target_file,
+                metadata,
+                &cancel,
+            )
+            .await;
+    }
+
     let file = File::open(&source_file)
         .await
         .with_context(|| format!("Failed to open file {source_file:?} for wal backup"))?;
 
     // limiting the file to read only the first `size` bytes
-    let limited_file = tokio::io::AsyncReadExt::take(file, size as u64);
+    let limited_file = file.take(size as u64);
 
     let file = tokio_util::io::ReaderStream::with_capacity(limited_file, BUFFER_SIZE);
 
-    let cancel = CancellationToken::new();
-
     storage
-        .upload(
-            file,
-            size,
-            target_file,
-            Some(StorageMetadata::from([("sk_type", "partial_segment")])),
-            &cancel,
-        )
+        .upload(file, size, target_file, metadata, &cancel)
+        .await
+}
+
+/// Whether the object at `path` is zstd compressed, see [`COMPRESSED_OBJECT_SUFFIX`].
+pub fn is_compressed(path: &RemotePath) -> bool {
+    path.object_name()
+        .is_some_and(|name| name.ends_with(COMPRESSED_OBJECT_SUFFIX))
+}
+
+/// Compresses the first `size` bytes of `source_file` in memory.
+///
+/// S3 needs the size of an object upfront, and a segment is at most 16MiB before compression.
+async fn compress_file(source_file: &Utf8Path, size: usize) -> Result<Bytes> {
+    let file = File::open(&source_file)
+        .await
+        .with_context(|| format!("Failed to open file {source_file:?} for wal backup"))?;
+
+    let reader = tokio::io::BufReader::with_capacity(BUFFER_SIZE, file.take(size as u64));
+    let mut encoder = async_compression::tokio::bufread::ZstdEncoder::new(reader);
+    let mut compressed = Vec::with_capacity(size / 2);
+    encoder
+        .read_to_end(&mut compressed)
         .await
+        .with_context(|| format!("Failed to compress file {source_file:?} for wal backup"))?;
+    Ok(Bytes::from(compressed))
 }
 
 pub(crate) async fn copy_partial_segment(
@@ -527,6 +605,35 @@ pub async fn read_object(
 
     let cancel = CancellationToken::new();
 
+    if is_compressed(file_path) {
+        // Compressed objects can't be read from an offset: decompress the whole object, which is
+        // at most a segment.
+        let download = storage
+            .download(file_path, &DownloadOpts::default(), &cancel)
+            .await
+            .with_context(|| {
+                format!("Failed to open WAL segment download stream for remote path {file_path:?}")
+            })?;
+        let reader = tokio_util::io::StreamReader::new(download.download_stream);
+        let reader = tokio::io::BufReader::with_capacity(BUFFER_SIZE, reader);
+        let mut decoder = async_compression::tokio::bufread::ZstdDecoder::new(reader);
+        let mut decompressed = Vec::new();
+        decoder
+            .read_to_end(&mut decompressed)
+            .await
+            .with_context(|| format!("Failed to decompress WAL segment {file_path:?}"))?;
+
+        let offset = offset as usize;
+        if offset > decompressed.len() {
+            anyhow::bail!(
+                "offset {offset} is beyond the end of WAL segment {file_path:?} of {} bytes",
+                decompressed.len()
+            );
+        }
+        let decompressed = Bytes::from(decompressed).slice(offset..);
+        return Ok(Box::pin(std::io::Cursor::new(decompressed)));
+    }
+
     let opts = DownloadOpts {
         byte_start: std::ops::Bound::Included(offset),
         ..Default::default()
@@ -545,6 +652,22 @@ pub async fn read_object(
     Ok(Box::pin(reader))
 }
 
+/// Like [`read_object`], for the full segment `segment_name` of the timeline at
+/// `remote_timeline_path`, whether or not it was compressed when offloaded.
+pub async fn read_segment(
+    remote_timeline_path: &RemotePath,
+    segment_name: &str,
+    offset: u64,
+) -> anyhow::Result<Pin<Box<dyn tokio::io::AsyncRead + Send + Sync>>> {
+    match read_object(&remote_timeline_path.join(segment_name), offset).await {
+        Err(e) if matches!(e.downcast_ref(), Some(DownloadError::NotFound)) => {
+            let compressed_name = format!("{segment_name}{COMPRESSED_OBJECT_SUFFIX}");
+            read_object(&remote_timeline_path.join(compressed_name), offset).await
+        }
+        res => res,
+    }
+}
+
 /// Delete WAL files for the given timeline. Remote storage must be configured
 /// when called.
 pub async fn delete_timeline(ttid: &TenantTimelineId) -> Result<()> {
@@ -640,24 +763,14 @@ pub async fn copy_s3_segments(
         .as_ref()
         .unwrap();
 
+    let remote_src_path = remote_timeline_path(src_ttid)?;
     let remote_dst_path = remote_timeline_path(dst_ttid)?;
 
     let cancel = CancellationToken::new();
 
-    let files = storage
-        .list(
-            Some(&remote_dst_path),
-            ListingMode::NoDelimiter,
-            None,
-            &cancel,
-        )
-        .await?
-        .keys;
-
-    let uploaded_segments = &files
-        .iter()
-        .filter_map(|o| o.key.object_name().map(ToOwned::to_owned))
-        .collect::<HashSet<_>>();
+    // Segments are copied as they are, compressed or not.
+    let src_segments = &list_object_names(storage, &remote_src_path, &cancel).await?;
+    let uploaded_segments = &list_object_names(storage, &remote_dst_path, &cancel).await?;
 
     debug!(
         "these segments have already been uploaded: {:?}",
@@ -670,13 +783,20 @@ pub async fn copy_s3_segments(
         }
 
         let segment_name = XLogFileName(PG_TLI, segno, wal_seg_size);
-        if uploaded_segments.contains(&segment_name) {
+        let compressed_name = format!("{segment_name}{COMPRESSED_OBJECT_SUFFIX}");
+        if uploaded_segments.contains(&segment_name) || uploaded_segments.contains(&compressed_name)
+        {
             continue;
         }
-        debug!("copying segment {}", segment_name);
+        let object_name = if src_segments.contains(&compressed_name) {
+            compressed_name
+        } else {
+            segment_name
+        };
+        debug!("copying segment {}", object_name);
 
-        let from = remote_timeline_path(src_ttid)?.join(&segment_name);
-        let to = remote_dst_path.join(&segment_name);
+        let from = remote_src_path.join(&object_name);
+        let to = remote_dst_path.join(&object_name);
 
         storage.copy_object(&from, &to, &cancel).await?;
     }
@@ -688,6 +808,22 @@ pub async fn copy_s3_segments(
     Ok(())
 }
 
+async fn list_object_names(
+    storage: &GenericRemoteStorage,
+    prefix: &RemotePath,
+    cancel: &CancellationToken,
+) -> Result<HashSet<String>> {
+    let files = storage
+        .list(Some(prefix), ListingMode::NoDelimiter, None, cancel)
+        .await?
+        .keys;
+
+    Ok(files
+        .iter()
+        .filter_map(|o| o.key.object_name().map(ToOwned::to_owned))
+        .collect())
+}
+
 /// Get S3 (remote_storage) prefix path used for timeline files.
 pub fn remote_timeline_path(ttid: &TenantTimelineId) -> Result<RemotePath> {
     RemotePath::new(&Utf8Path::new(&ttid.tenant_id.to_string()).join(ttid.timeline_id.to_string()))
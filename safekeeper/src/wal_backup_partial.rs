@@ -15,6 +15,9 @@
 //! The full object name example:
 //! `000000010000000000000002_2_0000000002534868_0000000002534410_sk1.partial`
 //!
+//! With `--wal-backup-compression`, segments are uploaded zstd compressed, and
+//! their names get the `.zst` suffix.
+//!
 //! Each safekeeper will keep info about remote partial segments in its control
 //! file. Code updates state in the control file before doing any S3 operations.
 //! This way control file stores information about all potentially existing
@@ -121,10 +124,11 @@ pub(crate) fn replace_uploaded_segment(
 
         // Sanity check that the partial segment we are replacing is belongs
         // to the `source` SK.
-        if !current
+        let uncompressed_name = current
             .name
-            .ends_with(format!("sk{}.partial", source.0).as_str())
-        {
+            .strip_suffix(wal_backup::COMPRESSED_OBJECT_SUFFIX)
+            .unwrap_or(&current.name);
+        if !uncompressed_name.ends_with(format!("sk{}.partial", source.0).as_str()) {
             anyhow::bail!(
                 "Partial segment name ({}) doesn't match self node id ({})",
                 current.name,
@@ -192,14 +196,19 @@ fn remote_segment_name(
         commit_lsn: Lsn,
         flush_lsn: Lsn,
     ) -> String {
-        format!(
+        let name = format!(
             "{}_{}_{:016X}_{:016X}_sk{}.partial",
             self.segment_name(segno),
             term,
             flush_lsn.0,
             commit_lsn.0,
             self.conf.my_id.0,
-        )
+        );
+        if self.conf.wal_backup_compression {
+            name + wal_backup::COMPRESSED_OBJECT_SUFFIX
+        } else {
+            name
+        }
     }
 
     fn local_segment_name(&self, segno: u64) -> String {
@@ -32,7 +32,7 @@
     REMOVED_WAL_SEGMENTS, WAL_STORAGE_OPERATION_SECONDS, WalStorageMetrics, time_io_closure,
 };
 use crate::state::TimelinePersistentState;
-use crate::wal_backup::{read_object, remote_timeline_path};
+use crate::wal_backup::{read_segment, remote_timeline_path};
 
 pub trait Storage {
     // Last written LSN.
@@ -813,8 +813,7 @@ async fn open_segment(&self) -> Result<Pin<Box<dyn AsyncRead + Send + Sync>>> {
 
         // Try to open remote file, if remote reads are enabled
         if self.enable_remote_read {
-            let remote_wal_file_path = self.remote_path.join(&wal_file_name);
-            return read_object(&remote_wal_file_path, xlogoff as u64).await;
+            return read_segment(&self.remote_path, &wal_file_name, xlogoff as u64).await;
         }
 
         bail!("WAL segment is not found")
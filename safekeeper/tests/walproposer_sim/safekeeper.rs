@@ -160,6 +160,7 @@ pub fn run_server(os: NodeOs, disk: Arc<SafekeeperDisk>) -> Result<()> {
         remote_storage: None,
         max_offloader_lag_bytes: 0,
         wal_backup_enabled: false,
+        wal_backup_compression: false,
         listen_pg_addr_tenant_only: None,
         advertise_pg_addr: None,
         availability_zone: None,
@@ -0,0 +1 @@
+DROP TABLE safekeeper_wal_divergences;
@@ -0,0 +1,8 @@
+CREATE TABLE safekeeper_wal_divergences (
+  tenant_id VARCHAR NOT NULL,
+  timeline_id VARCHAR NOT NULL,
+  verification VARCHAR NOT NULL,
+  PRIMARY KEY(tenant_id, timeline_id),
+  -- Rely on cascade behavior for delete
+  FOREIGN KEY(tenant_id, timeline_id) REFERENCES timelines ON DELETE CASCADE
+);
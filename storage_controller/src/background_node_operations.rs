@@ -1,9 +1,12 @@
 use std::borrow::Cow;
 use std::fmt::{Debug, Display};
 
+use pageserver_api::controller_api::SafekeeperOperationDescribe;
 use tokio_util::sync::CancellationToken;
 use utils::id::NodeId;
 
+use crate::persistence::DatabaseError;
+
 pub(crate) const MAX_RECONCILES_PER_OPERATION: usize = 64;
 
 #[derive(Copy, Clone)]
@@ -16,10 +19,22 @@ pub(crate) struct Fill {
     pub(crate) node_id: NodeId,
 }
 
+#[derive(Copy, Clone)]
+pub(crate) struct SafekeeperDrain {
+    pub(crate) node_id: NodeId,
+}
+
+#[derive(Copy, Clone)]
+pub(crate) struct SafekeeperFill {
+    pub(crate) node_id: NodeId,
+}
+
 #[derive(Copy, Clone)]
 pub(crate) enum Operation {
     Drain(Drain),
     Fill(Fill),
+    SafekeeperDrain(SafekeeperDrain),
+    SafekeeperFill(SafekeeperFill),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -30,12 +45,16 @@ pub(crate) enum OperationError {
     FinalizeError(Cow<'static, str>),
     #[error("Operation cancelled")]
     Cancelled,
+    #[error("Database error: {0}")]
+    Database(#[from] DatabaseError),
 }
 
 pub(crate) struct OperationHandler {
     pub(crate) operation: Operation,
     #[allow(unused)]
     pub(crate) cancel: CancellationToken,
+    /// Only tracked for safekeeper operations, which move timelines one by one.
+    pub(crate) progress: Option<SafekeeperOperationDescribe>,
 }
 
 impl Display for Drain {
@@ -50,11 +69,25 @@ fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     }
 }
 
+impl Display for SafekeeperDrain {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "safekeeper drain {}", self.node_id)
+    }
+}
+
+impl Display for SafekeeperFill {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "safekeeper fill {}", self.node_id)
+    }
+}
+
 impl Display for Operation {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Operation::Drain(op) => write!(f, "{op}"),
             Operation::Fill(op) => write!(f, "{op}"),
+            Operation::SafekeeperDrain(op) => write!(f, "{op}"),
+            Operation::SafekeeperFill(op) => write!(f, "{op}"),
         }
     }
 }
@@ -12,11 +12,12 @@
 use pageserver_api::controller_api::AvailabilityZone;
 use pageserver_api::shard::{ShardCount, ShardNumber, ShardStripeSize, TenantShardId};
 use postgres_connection::parse_host_port;
+use safekeeper_api::membership::{SafekeeperGeneration, SafekeeperId};
 use serde::{Deserialize, Serialize};
 use tokio_util::sync::CancellationToken;
 use tracing::{Instrument, info_span};
 use utils::backoff::{self};
-use utils::id::{NodeId, TenantId};
+use utils::id::{NodeId, TenantId, TimelineId};
 
 use crate::service::Config;
 
@@ -204,6 +205,16 @@ struct ComputeHookNotifyRequest {
     shards: Vec<ComputeHookNotifyRequestShard>,
 }
 
+/// Request body that we send to the control plane to notify it of the safekeepers of a timeline.
+/// Computes of the timeline must switch to them, in the given membership generation.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub(crate) struct SafekeepersUpdate {
+    pub(crate) tenant_id: TenantId,
+    pub(crate) timeline_id: TimelineId,
+    pub(crate) generation: SafekeeperGeneration,
+    pub(crate) safekeepers: Vec<SafekeeperId>,
+}
+
 /// Error type for attempts to call into the control plane compute notification hook
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum NotifyError {
@@ -231,6 +242,10 @@ pub(crate) enum NotifyError {
 
     #[error("neon_local error: {0}")]
     NeonLocal(anyhow::Error),
+
+    // There is no control plane URL to send the notification to
+    #[error("Control plane URL not configured")]
+    NotConfigured,
 }
 
 enum MaybeSendResult {
@@ -445,10 +460,55 @@ async fn do_notify_local(
         Ok(())
     }
 
+    /// For test environments: use neon_local's LocalEnv to switch computes to new safekeepers
+    async fn do_notify_safekeepers_local(
+        &self,
+        update: &SafekeepersUpdate,
+    ) -> Result<(), NotifyError> {
+        // neon_local updates are not safe to call concurrently, use a lock to serialize
+        // all calls to this function
+        let _locked = self.neon_local_lock.lock().await;
+
+        let Some(repo_dir) = self.config.neon_local_repo_dir.as_deref() else {
+            tracing::warn!(
+                "neon_local_repo_dir not set, likely a bug in neon_local; skipping compute update"
+            );
+            return Ok(());
+        };
+        let env = match LocalEnv::load_config(repo_dir) {
+            Ok(e) => e,
+            Err(e) => {
+                tracing::warn!("Couldn't load neon_local config, skipping compute update ({e})");
+                return Ok(());
+            }
+        };
+        let cplane = ComputeControlPlane::load(env).expect("Error loading compute control plane");
+
+        let safekeepers = update
+            .safekeepers
+            .iter()
+            .map(|sk| sk.id)
+            .collect::<Vec<_>>();
+        for (endpoint_name, endpoint) in &cplane.endpoints {
+            if endpoint.tenant_id == update.tenant_id
+                && endpoint.timeline_id == update.timeline_id
+                && endpoint.status() == EndpointStatus::Running
+            {
+                tracing::info!("Reconfiguring safekeepers of endpoint {}", endpoint_name);
+                endpoint
+                    .reconfigure_safekeepers(safekeepers.clone(), update.generation)
+                    .await
+                    .map_err(NotifyError::NeonLocal)?;
+            }
+        }
+
+        Ok(())
+    }
+
     async fn do_notify_iteration(
         &self,
         url: &String,
-        reconfigure_request: &ComputeHookNotifyRequest,
+        reconfigure_request: &(impl Serialize + std::fmt::Debug),
         cancel: &CancellationToken,
     ) -> Result<(), NotifyError> {
         let req = self.client.request(reqwest::Method::PUT, url);
@@ -463,7 +523,7 @@ async fn do_notify_iteration(
             url,
             reconfigure_request
         );
-        let send_result = req.json(&reconfigure_request).send().await;
+        let send_result = req.json(reconfigure_request).send().await;
         let response = match send_result {
             Ok(r) => r,
             Err(e) => return Err(e.into()),
@@ -526,7 +586,7 @@ async fn do_notify_iteration(
     async fn do_notify(
         &self,
         url: &String,
-        reconfigure_request: &ComputeHookNotifyRequest,
+        reconfigure_request: &(impl Serialize + std::fmt::Debug),
         cancel: &CancellationToken,
     ) -> Result<(), NotifyError> {
         // We hold these semaphore units across all retries, rather than only across each
@@ -764,6 +824,38 @@ pub(super) async fn notify<'a>(
             .await
     }
 
+    /// Call this to notify the compute (postgres) tier of new safekeepers to use for a
+    /// timeline. Unlike [`Self::notify`], nothing is coalesced or remembered here: the caller
+    /// persists which generation was notified, and calls again until it succeeds.
+    #[tracing::instrument(skip_all, fields(tenant_id=%update.tenant_id, timeline_id=%update.timeline_id, generation=%update.generation))]
+    pub(super) async fn notify_safekeepers(
+        &self,
+        update: SafekeepersUpdate,
+        cancel: &CancellationToken,
+    ) -> Result<(), NotifyError> {
+        if self.config.use_local_compute_notifications {
+            return self
+                .do_notify_safekeepers_local(&update)
+                .await
+                .map_err(|e| {
+                    // This path is for testing only, so munge the error into our prod-style error type.
+                    tracing::error!("neon_local notification hook failed: {e}");
+                    NotifyError::Fatal(StatusCode::INTERNAL_SERVER_ERROR)
+                });
+        }
+
+        // Unlike pageserver attachments, `compute_hook_url` can't be used for this.
+        let Some(control_plane_url) = &self.config.control_plane_url else {
+            return Err(NotifyError::NotConfigured);
+        };
+        let notify_url = if control_plane_url.ends_with('/') {
+            format!("{control_plane_url}notify-safekeepers")
+        } else {
+            format!("{control_plane_url}/notify-safekeepers")
+        };
+        self.do_notify(&notify_url, &update, cancel).await
+    }
+
     /// Reflect a detach for a particular shard in the compute hook state.
     ///
     /// The goal is to avoid sending compute notifications with stale information (i.e.
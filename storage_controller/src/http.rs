@@ -22,11 +22,13 @@
     MetadataHealthListUnhealthyResponse, MetadataHealthUpdateRequest, MetadataHealthUpdateResponse,
     NodeAvailability, NodeConfigureRequest, NodeRegisterRequest, SafekeeperSchedulingPolicyRequest,
     ShardsPreferredAzsRequest, TenantCreateRequest, TenantPolicyRequest, TenantShardMigrateRequest,
+    TimelineSafekeeperMigrateRequest,
 };
 use pageserver_api::models::{
     DetachBehavior, LsnLeaseRequest, TenantConfigPatchRequest, TenantConfigRequest,
-    TenantLocationConfigRequest, TenantShardSplitRequest, TenantTimeTravelRequest,
-    TimelineArchivalConfigRequest, TimelineCreateRequest,
+    TenantLocationConfigRequest, TenantShardMergeRequest, TenantShardSplitRequest,
+    TenantTimeTravelRequest, TimelineArchivalConfigRequest, TimelineCreateRequest,
+    TimelineExportPgdataRequest, TimelineResetToLsnRequest, TimelineRetentionPolicyRequest,
 };
 use pageserver_api::shard::TenantShardId;
 use pageserver_api::upcall_api::{ReAttachRequest, ValidateRequest};
@@ -519,6 +521,32 @@ async fn handle_tenant_timeline_archival_config(
     json_response(StatusCode::OK, ())
 }
 
+async fn handle_tenant_timeline_retention_policy(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let timeline_id: TimelineId = parse_request_param(&req, "timeline_id")?;
+
+    check_permissions(&req, Scope::PageServerApi)?;
+    maybe_rate_limit(&req, tenant_id).await;
+
+    let mut req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let retention_req = json_request::<TimelineRetentionPolicyRequest>(&mut req).await?;
+
+    service
+        .tenant_timeline_retention_policy(tenant_id, timeline_id, retention_req)
+        .await?;
+
+    json_response(StatusCode::OK, ())
+}
+
 async fn handle_tenant_timeline_detach_ancestor(
     service: Arc<Service>,
     req: Request<Body>,
@@ -544,6 +572,32 @@ async fn handle_tenant_timeline_detach_ancestor(
     json_response(StatusCode::OK, res)
 }
 
+async fn handle_tenant_timeline_reset_to_lsn(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let timeline_id: TimelineId = parse_request_param(&req, "timeline_id")?;
+
+    check_permissions(&req, Scope::PageServerApi)?;
+    maybe_rate_limit(&req, tenant_id).await;
+
+    let mut req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let reset_req = json_request::<TimelineResetToLsnRequest>(&mut req).await?;
+
+    service
+        .tenant_timeline_reset_to_lsn(tenant_id, timeline_id, reset_req)
+        .await?;
+
+    json_response(StatusCode::OK, ())
+}
+
 async fn handle_tenant_timeline_block_unblock_gc(
     service: Arc<Service>,
     req: Request<Body>,
@@ -582,6 +636,32 @@ async fn handle_tenant_timeline_download_heatmap_layers(
     json_response(StatusCode::OK, ())
 }
 
+async fn handle_tenant_timeline_export_pgdata(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let timeline_id: TimelineId = parse_request_param(&req, "timeline_id")?;
+
+    check_permissions(&req, Scope::PageServerApi)?;
+    maybe_rate_limit(&req, tenant_id).await;
+
+    let mut req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let export_req = json_request::<TimelineExportPgdataRequest>(&mut req).await?;
+
+    let info = service
+        .tenant_timeline_export_pgdata(tenant_id, timeline_id, export_req)
+        .await?;
+
+    json_response(StatusCode::ACCEPTED, info)
+}
+
 async fn handle_tenant_timeline_lsn_lease(
     service: Arc<Service>,
     req: Request<Body>,
@@ -1022,6 +1102,153 @@ async fn handle_safekeeper_list(req: Request<Body>) -> Result<Response<Body>, Ap
     json_response(StatusCode::OK, safekeepers)
 }
 
+async fn handle_safekeeper_drain(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Infra)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "id")?;
+
+    state.service.start_safekeeper_drain(node_id).await?;
+
+    json_response(StatusCode::ACCEPTED, ())
+}
+
+async fn handle_cancel_safekeeper_drain(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Infra)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "id")?;
+
+    state.service.cancel_safekeeper_drain(node_id)?;
+
+    json_response(StatusCode::ACCEPTED, ())
+}
+
+async fn handle_safekeeper_fill(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Infra)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "id")?;
+
+    state.service.start_safekeeper_fill(node_id).await?;
+
+    json_response(StatusCode::ACCEPTED, ())
+}
+
+async fn handle_cancel_safekeeper_fill(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Infra)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "id")?;
+
+    state.service.cancel_safekeeper_fill(node_id)?;
+
+    json_response(StatusCode::ACCEPTED, ())
+}
+
+async fn handle_safekeeper_operation(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Infra)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    let node_id: NodeId = parse_request_param(&req, "id")?;
+
+    json_response(
+        StatusCode::OK,
+        state.service.safekeeper_operation_describe(node_id)?,
+    )
+}
+
+async fn handle_safekeeper_migrations_list(req: Request<Body>) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    json_response(StatusCode::OK, state.service.safekeeper_migrations_list())
+}
+
+async fn handle_safekeeper_wal_divergences_list(
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let state = get_state(&req);
+    json_response(
+        StatusCode::OK,
+        state.service.safekeeper_wal_divergences_list(),
+    )
+}
+
+async fn handle_timeline_safekeeper_wal_verification(
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+
+    let req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let timeline_id: TimelineId = parse_request_param(&req, "timeline_id")?;
+    let state = get_state(&req);
+    json_response(
+        StatusCode::OK,
+        state
+            .service
+            .timeline_safekeeper_wal_verification(tenant_id, timeline_id)?,
+    )
+}
+
 async fn handle_metadata_health_update(req: Request<Body>) -> Result<Response<Body>, ApiError> {
     check_permissions(&req, Scope::Scrubber)?;
 
@@ -1111,6 +1338,29 @@ async fn handle_tenant_shard_split(
     )
 }
 
+async fn handle_tenant_shard_merge(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+    // NB: don't rate limit: admin operation.
+
+    let mut req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let merge_req = json_request::<TenantShardMergeRequest>(&mut req).await?;
+
+    json_response(
+        StatusCode::OK,
+        service.tenant_shard_merge(tenant_id, merge_req).await?,
+    )
+}
+
 async fn handle_tenant_shard_migrate(
     service: Arc<Service>,
     req: Request<Body>,
@@ -1135,6 +1385,29 @@ async fn handle_tenant_shard_migrate(
     )
 }
 
+async fn handle_timeline_safekeeper_migrate(
+    service: Arc<Service>,
+    req: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    check_permissions(&req, Scope::Admin)?;
+    // NB: don't rate limit: admin operation.
+
+    let mut req = match maybe_forward(req).await {
+        ForwardOutcome::Forwarded(res) => {
+            return res;
+        }
+        ForwardOutcome::NotForwarded(req) => req,
+    };
+
+    let tenant_id: TenantId = parse_request_param(&req, "tenant_id")?;
+    let timeline_id: TimelineId = parse_request_param(&req, "timeline_id")?;
+    let migrate_req = json_request::<TimelineSafekeeperMigrateRequest>(&mut req).await?;
+    service
+        .tenant_timeline_safekeeper_migrate(tenant_id, timeline_id, migrate_req.new_sk_set)
+        .await?;
+    json_response(StatusCode::OK, ())
+}
+
 async fn handle_tenant_shard_migrate_secondary(
     service: Arc<Service>,
     req: Request<Body>,
@@ -1459,7 +1732,7 @@ async fn handle_safekeeper_scheduling_policy(
 
     state
         .service
-        .set_safekeeper_scheduling_policy(id, body.scheduling_policy)
+        .external_set_safekeeper_scheduling_policy(id, body.scheduling_policy)
         .await?;
 
     json_response(StatusCode::OK, ())
@@ -2063,6 +2336,75 @@ pub fn make_router(
                 RequestName("v1_safekeeper_status"),
             )
         })
+        .put("/control/v1/safekeeper/:id/drain", |r| {
+            named_request_span(
+                r,
+                handle_safekeeper_drain,
+                RequestName("control_v1_safekeeper_drain"),
+            )
+        })
+        .delete("/control/v1/safekeeper/:id/drain", |r| {
+            named_request_span(
+                r,
+                handle_cancel_safekeeper_drain,
+                RequestName("control_v1_cancel_safekeeper_drain"),
+            )
+        })
+        .put("/control/v1/safekeeper/:id/fill", |r| {
+            named_request_span(
+                r,
+                handle_safekeeper_fill,
+                RequestName("control_v1_safekeeper_fill"),
+            )
+        })
+        .delete("/control/v1/safekeeper/:id/fill", |r| {
+            named_request_span(
+                r,
+                handle_cancel_safekeeper_fill,
+                RequestName("control_v1_cancel_safekeeper_fill"),
+            )
+        })
+        .get("/control/v1/safekeeper/:id/operation", |r| {
+            named_request_span(
+                r,
+                handle_safekeeper_operation,
+                RequestName("control_v1_safekeeper_operation"),
+            )
+        })
+        .get("/control/v1/safekeeper_migrations", |r| {
+            named_request_span(
+                r,
+                handle_safekeeper_migrations_list,
+                RequestName("control_v1_safekeeper_migrations"),
+            )
+        })
+        .get("/control/v1/safekeeper_wal_divergences", |r| {
+            named_request_span(
+                r,
+                handle_safekeeper_wal_divergences_list,
+                RequestName("control_v1_safekeeper_wal_divergences"),
+            )
+        })
+        .get(
+            "/control/v1/tenant/:tenant_id/timeline/:timeline_id/safekeeper_wal_verification",
+            |r| {
+                named_request_span(
+                    r,
+                    handle_timeline_safekeeper_wal_verification,
+                    RequestName("control_v1_timeline_safekeeper_wal_verification"),
+                )
+            },
+        )
+        .put(
+            "/control/v1/tenant/:tenant_id/timeline/:timeline_id/safekeeper_migrate",
+            |r| {
+                tenant_service_handler(
+                    r,
+                    handle_timeline_safekeeper_migrate,
+                    RequestName("control_v1_timeline_safekeeper_migrate"),
+                )
+            },
+        )
         // Tenant Shard operations
         .put("/control/v1/tenant/:tenant_shard_id/migrate", |r| {
             tenant_service_handler(
@@ -2098,6 +2440,13 @@ pub fn make_router(
                 RequestName("control_v1_tenant_shard_split"),
             )
         })
+        .put("/control/v1/tenant/:tenant_id/shard_merge", |r| {
+            tenant_service_handler(
+                r,
+                handle_tenant_shard_merge,
+                RequestName("control_v1_tenant_shard_merge"),
+            )
+        })
         .get("/control/v1/tenant/:tenant_id", |r| {
             tenant_service_handler(
                 r,
@@ -2193,6 +2542,16 @@ pub fn make_router(
                 )
             },
         )
+        .put(
+            "/v1/tenant/:tenant_id/timeline/:timeline_id/retention_policy",
+            |r| {
+                tenant_service_handler(
+                    r,
+                    handle_tenant_timeline_retention_policy,
+                    RequestName("v1_tenant_timeline_retention_policy"),
+                )
+            },
+        )
         .put(
             "/v1/tenant/:tenant_id/timeline/:timeline_id/detach_ancestor",
             |r| {
@@ -2203,6 +2562,16 @@ pub fn make_router(
                 )
             },
         )
+        .put(
+            "/v1/tenant/:tenant_id/timeline/:timeline_id/reset_to_lsn",
+            |r| {
+                tenant_service_handler(
+                    r,
+                    handle_tenant_timeline_reset_to_lsn,
+                    RequestName("v1_tenant_timeline_reset_to_lsn"),
+                )
+            },
+        )
         .post(
             "/v1/tenant/:tenant_id/timeline/:timeline_id/block_gc",
             |r| {
@@ -2233,6 +2602,16 @@ pub fn make_router(
                 )
             },
         )
+        .post(
+            "/v1/tenant/:tenant_id/timeline/:timeline_id/export_pgdata",
+            |r| {
+                tenant_service_handler(
+                    r,
+                    handle_tenant_timeline_export_pgdata,
+                    RequestName("v1_tenant_timeline_export_pgdata"),
+                )
+            },
+        )
         // LSN lease passthrough to all shards
         .post(
             "/v1/tenant/:tenant_id/timeline/:timeline_id/lsn_lease",
@@ -19,7 +19,8 @@
 use storage_controller::service::{
     Config, HEARTBEAT_INTERVAL_DEFAULT, LONG_RECONCILE_THRESHOLD_DEFAULT,
     MAX_OFFLINE_INTERVAL_DEFAULT, MAX_WARMING_UP_INTERVAL_DEFAULT,
-    PRIORITY_RECONCILER_CONCURRENCY_DEFAULT, RECONCILER_CONCURRENCY_DEFAULT, Service,
+    PRIORITY_RECONCILER_CONCURRENCY_DEFAULT, RECONCILER_CONCURRENCY_DEFAULT,
+    SAFEKEEPER_MIGRATION_CONCURRENCY_DEFAULT, Service,
 };
 use tokio::signal::unix::SignalKind;
 use tokio_util::sync::CancellationToken;
@@ -206,6 +207,20 @@ struct Cli {
     /// the compute notification directly (instead of via control plane).
     #[arg(long, default_value = "false")]
     use_local_compute_notifications: bool,
+
+    /// Period with which to move timelines off draining or overloaded safekeepers.
+    /// Safekeeper rebalancing is disabled if not set.
+    #[arg(long)]
+    safekeeper_rebalance_interval: Option<humantime::Duration>,
+
+    /// Maximum number of timeline migrations between safekeepers that may run in parallel
+    #[arg(long)]
+    safekeeper_migration_concurrency: Option<usize>,
+
+    /// Period with which to compare the newly committed WAL across the safekeepers of
+    /// each timeline. WAL verification is disabled if not set.
+    #[arg(long)]
+    safekeeper_wal_verification_interval: Option<humantime::Duration>,
 }
 
 enum StrictMode {
@@ -432,6 +447,15 @@ async fn async_main() -> anyhow::Result<()> {
         ssl_ca_certs,
         timelines_onto_safekeepers: args.timelines_onto_safekeepers,
         use_local_compute_notifications: args.use_local_compute_notifications,
+        safekeeper_rebalance_interval: args
+            .safekeeper_rebalance_interval
+            .map(humantime::Duration::into),
+        safekeeper_migration_concurrency: args
+            .safekeeper_migration_concurrency
+            .unwrap_or(SAFEKEEPER_MIGRATION_CONCURRENCY_DEFAULT),
+        safekeeper_wal_verification_interval: args
+            .safekeeper_wal_verification_interval
+            .map(humantime::Duration::into),
     };
 
     // Validate that we can connect to the database
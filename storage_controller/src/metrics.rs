@@ -130,6 +130,22 @@ pub(crate) struct StorageControllerMetricGroup {
     /// HTTP request status counters for handled requests
     pub(crate) storage_controller_reconcile_long_running:
         measured::CounterVec<ReconcileLongRunningLabelGroupSet>,
+
+    /// Timeline migrations between safekeepers completed, broken down by success/failure/cancelled
+    pub(crate) storage_controller_safekeeper_migration_complete:
+        measured::CounterVec<ReconcileCompleteLabelGroupSet>,
+
+    /// How many timeline migrations between safekeepers are currently in progress
+    pub(crate) storage_controller_safekeeper_migrations_ongoing: measured::Gauge,
+
+    /// Bytes of WAL found identical on all the safekeepers of a timeline
+    pub(crate) storage_controller_safekeeper_wal_verified_bytes: measured::Counter,
+
+    /// Times the safekeepers of a timeline were found to hold different committed WAL
+    pub(crate) storage_controller_safekeeper_wal_divergences: measured::Counter,
+
+    /// How many timelines currently have diverged WAL on their safekeepers
+    pub(crate) storage_controller_safekeeper_wal_divergent_timelines: measured::Gauge,
 }
 
 impl StorageControllerMetrics {
@@ -151,6 +167,9 @@ fn default() -> Self {
         metrics_group
             .storage_controller_reconcile_complete
             .init_all_dense();
+        metrics_group
+            .storage_controller_safekeeper_migration_complete
+            .init_all_dense();
 
         Self {
             metrics_group,
@@ -1,13 +1,15 @@
 use pageserver_api::models::detach_ancestor::AncestorDetached;
 use pageserver_api::models::{
     DetachBehavior, LocationConfig, LocationConfigListResponse, LsnLease, PageserverUtilization,
-    SecondaryProgress, TenantScanRemoteStorageResponse, TenantShardSplitRequest,
-    TenantShardSplitResponse, TenantWaitLsnRequest, TimelineArchivalConfigRequest,
-    TimelineCreateRequest, TimelineInfo, TopTenantShardsRequest, TopTenantShardsResponse,
+    SecondaryProgress, TenantScanRemoteStorageResponse, TenantShardMergeRequest,
+    TenantShardMergeResponse, TenantShardSplitRequest, TenantShardSplitResponse,
+    TenantWaitLsnRequest, TimelineArchivalConfigRequest, TimelineCreateRequest,
+    TimelineExportPgdataRequest, TimelineExportPgdataTaskInfo, TimelineInfo,
+    TimelineResetToLsnRequest, TopTenantShardsRequest, TopTenantShardsResponse,
 };
 use pageserver_api::shard::TenantShardId;
 use pageserver_client::BlockUnblock;
-use pageserver_client::mgmt_api::{Client, Result};
+use pageserver_client::mgmt_api::{Client, ForceAwaitLogicalSize, Result};
 use reqwest::StatusCode;
 use utils::id::{NodeId, TenantId, TimelineId};
 use utils::lsn::Lsn;
@@ -225,6 +227,19 @@ pub(crate) async fn tenant_shard_split(
         )
     }
 
+    pub(crate) async fn tenant_shard_merge(
+        &self,
+        tenant_shard_id: TenantShardId,
+        req: TenantShardMergeRequest,
+    ) -> Result<TenantShardMergeResponse> {
+        measured_request!(
+            "tenant_shard_merge",
+            crate::metrics::Method::Put,
+            &self.node_id_label,
+            self.inner.tenant_shard_merge(tenant_shard_id, req).await
+        )
+    }
+
     pub(crate) async fn timeline_list(
         &self,
         tenant_shard_id: &TenantShardId,
@@ -269,6 +284,53 @@ pub(crate) async fn timeline_detach_ancestor(
         )
     }
 
+    pub(crate) async fn timeline_info(
+        &self,
+        tenant_shard_id: TenantShardId,
+        timeline_id: TimelineId,
+    ) -> Result<TimelineInfo> {
+        measured_request!(
+            "timeline",
+            crate::metrics::Method::Get,
+            &self.node_id_label,
+            self.inner
+                .timeline_info(tenant_shard_id, timeline_id, ForceAwaitLogicalSize::No)
+                .await
+        )
+    }
+
+    pub(crate) async fn timeline_reset_to_lsn(
+        &self,
+        tenant_shard_id: TenantShardId,
+        timeline_id: TimelineId,
+        req: &TimelineResetToLsnRequest,
+    ) -> Result<()> {
+        measured_request!(
+            "timeline_reset_to_lsn",
+            crate::metrics::Method::Put,
+            &self.node_id_label,
+            self.inner
+                .timeline_reset_to_lsn(tenant_shard_id, timeline_id, req)
+                .await
+        )
+    }
+
+    pub(crate) async fn timeline_export_pgdata(
+        &self,
+        tenant_shard_id: TenantShardId,
+        timeline_id: TimelineId,
+        req: &TimelineExportPgdataRequest,
+    ) -> Result<TimelineExportPgdataTaskInfo> {
+        measured_request!(
+            "timeline_export_pgdata",
+            crate::metrics::Method::Post,
+            &self.node_id_label,
+            self.inner
+                .timeline_export_pgdata(tenant_shard_id, timeline_id, req)
+                .await
+        )
+    }
+
     pub(crate) async fn timeline_block_unblock_gc(
         &self,
         tenant_shard_id: TenantShardId,
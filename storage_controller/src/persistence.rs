@@ -20,7 +20,8 @@
 use itertools::Itertools;
 use pageserver_api::controller_api::{
     AvailabilityZone, MetadataHealthRecord, NodeSchedulingPolicy, PlacementPolicy,
-    SafekeeperDescribeResponse, ShardSchedulingPolicy, SkSchedulingPolicy,
+    SafekeeperDescribeResponse, SafekeeperWalVerificationDescribe, ShardSchedulingPolicy,
+    SkSchedulingPolicy,
 };
 use pageserver_api::models::TenantConfig;
 use pageserver_api::shard::{
@@ -32,7 +33,7 @@
 use scoped_futures::ScopedBoxFuture;
 use serde::{Deserialize, Serialize};
 use utils::generation::Generation;
-use utils::id::{NodeId, TenantId, TimelineId};
+use utils::id::{NodeId, TenantId, TenantTimelineId, TimelineId};
 use utils::lsn::Lsn;
 
 use self::split_state::SplitState;
@@ -102,6 +103,8 @@ pub(crate) enum DatabaseOperation {
     BeginShardSplit,
     CompleteShardSplit,
     AbortShardSplit,
+    BeginShardMerge,
+    AbortShardMerge,
     Detach,
     ReAttach,
     IncrementGeneration,
@@ -123,9 +126,16 @@ pub(crate) enum DatabaseOperation {
     SetPreferredAzs,
     InsertTimeline,
     GetTimeline,
+    UpdateTimelineMembership,
+    UpdateTimelineStartLsn,
+    UpdateTimelineCplaneNotifiedGeneration,
+    ListTimelines,
     InsertTimelineReconcile,
     RemoveTimelineReconcile,
     ListTimelineReconcile,
+    UpsertSafekeeperWalDivergence,
+    RemoveSafekeeperWalDivergence,
+    ListSafekeeperWalDivergences,
 }
 
 #[must_use]
@@ -962,7 +972,8 @@ pub(crate) async fn begin_shard_split(
     }
 
     // When we finish shard splitting, we must atomically clean up the old shards
-    // and insert the new shards, and clear the splitting marker.
+    // and insert the new shards, and clear the splitting marker.  This is also how
+    // we finish shard merging: the merged shards are the new shards.
     pub(crate) async fn complete_shard_split(
         &self,
         split_tenant_id: TenantId,
@@ -1054,6 +1065,115 @@ pub(crate) async fn abort_shard_split(
         .await
     }
 
+    // When we start shard merging, we must durably mark the tenant so that on restart, we know
+    // that we must go through recovery, as in [`Self::begin_shard_split`].
+    //
+    // Unlike the children of a split, merged shards do not inherit their parents' generation: the caller
+    // picks a generation for them that is newer than both of their parents'.
+    pub(crate) async fn begin_shard_merge(
+        &self,
+        old_shard_count: ShardCount,
+        merge_tenant_id: TenantId,
+        merged_shards: Vec<TenantShardPersistence>,
+    ) -> DatabaseResult<()> {
+        use crate::schema::tenant_shards::dsl::*;
+        let merged_shards = merged_shards.as_slice();
+        self.with_measured_conn(DatabaseOperation::BeginShardMerge, move |conn| {
+            Box::pin(async move {
+                // Mark parent shards as merging
+                let updated = diesel::update(tenant_shards)
+                    .filter(tenant_id.eq(merge_tenant_id.to_string()))
+                    .filter(shard_count.eq(old_shard_count.literal() as i32))
+                    .set((splitting.eq(2),))
+                    .execute(conn)
+                    .await?;
+                if updated != old_shard_count.count() as usize {
+                    // Perhaps a deletion, split or another merge raced with this attempt to merge.
+                    return Err(DatabaseError::Logical(format!(
+                        "Unexpected existing shard count {updated} when preparing tenant for merge (expected {})",
+                        old_shard_count.count()
+                    )));
+                }
+
+                let parent_generation = tenant_shards
+                    .filter(tenant_id.eq(merge_tenant_id.to_string()))
+                    .filter(shard_count.eq(old_shard_count.literal() as i32))
+                    .load::<TenantShardPersistence>(conn)
+                    .await?
+                    .into_iter()
+                    .filter_map(|parent| parent.generation)
+                    .max();
+
+                // FIXME: spurious clone to sidestep closure move rules
+                let merged_shards = merged_shards.to_vec();
+
+                // Insert merged shards
+                for shard in merged_shards {
+                    if shard.generation <= parent_generation {
+                        return Err(DatabaseError::Logical(format!(
+                            "Merged shard generation {:?} is not newer than parent generation {parent_generation:?}",
+                            shard.generation
+                        )));
+                    }
+
+                    debug_assert!(shard.splitting == SplitState::Merging);
+                    diesel::insert_into(tenant_shards)
+                        .values(shard)
+                        .execute(conn)
+                        .await?;
+                }
+
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// Used when the remote part of a shard merge failed: we will revert the database state to have only
+    /// the parent shards, with SplitState::Idle.
+    pub(crate) async fn abort_shard_merge(
+        &self,
+        merge_tenant_id: TenantId,
+        new_shard_count: ShardCount,
+    ) -> DatabaseResult<AbortShardSplitStatus> {
+        use crate::schema::tenant_shards::dsl::*;
+        self.with_measured_conn(DatabaseOperation::AbortShardMerge, move |conn| {
+            Box::pin(async move {
+                // Clear the merging state on parent shards
+                let updated = diesel::update(tenant_shards)
+                    .filter(tenant_id.eq(merge_tenant_id.to_string()))
+                    .filter(shard_count.ne(new_shard_count.literal() as i32))
+                    .set((splitting.eq(0),))
+                    .execute(conn)
+                    .await?;
+
+                // Parent shards are already gone: we cannot abort.
+                if updated == 0 {
+                    return Ok(AbortShardSplitStatus::Complete);
+                }
+
+                // Sanity check: if parent shards were present, there should be two of them
+                // for each merged shard.
+                if updated != new_shard_count.count() as usize * 2 {
+                    return Err(DatabaseError::Logical(format!(
+                        "Unexpected parent shard count {updated} while aborting merge to \
+                            count {new_shard_count:?} on tenant {merge_tenant_id}"
+                    )));
+                }
+
+                // Erase merged shards
+                diesel::delete(tenant_shards)
+                    .filter(tenant_id.eq(merge_tenant_id.to_string()))
+                    .filter(shard_count.eq(new_shard_count.literal() as i32))
+                    .execute(conn)
+                    .await?;
+
+                Ok(AbortShardSplitStatus::Aborted)
+            })
+        })
+        .await
+    }
+
     /// Stores all the latest metadata health updates durably. Updates existing entry on conflict.
     ///
     /// **Correctness:** `metadata_health_updates` should all belong the tenant shards managed by the storage controller.
@@ -1455,6 +1575,245 @@ pub(crate) async fn list_timelines_for_tenant(
         Ok(timelines)
     }
 
+    /// Update the membership configuration of a timeline: generation and safekeeper sets.
+    ///
+    /// The update is only applied if the currently persisted generation equals
+    /// `expected_generation` and the timeline is not being deleted, so that
+    /// concurrent configuration changes can't step on each other. Returns a
+    /// [`DatabaseError::Logical`] error otherwise.
+    pub(crate) async fn update_timeline_membership(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        expected_generation: i32,
+        new_generation: i32,
+        sk_set: &[i64],
+        new_sk_set: Option<&[i64]>,
+    ) -> DatabaseResult<()> {
+        use crate::schema::timelines::dsl;
+
+        let sk_set = sk_set.iter().copied().map(Some).collect::<Vec<_>>();
+        let new_sk_set = new_sk_set.map(|s| s.iter().copied().map(Some).collect::<Vec<_>>());
+        let sk_set = &sk_set;
+        let new_sk_set = &new_sk_set;
+        self.with_measured_conn(DatabaseOperation::UpdateTimelineMembership, move |conn| {
+            Box::pin(async move {
+                let updated = diesel::update(dsl::timelines)
+                    .filter(dsl::tenant_id.eq(tenant_id.to_string()))
+                    .filter(dsl::timeline_id.eq(timeline_id.to_string()))
+                    .filter(dsl::generation.eq(expected_generation))
+                    .filter(dsl::deleted_at.is_null())
+                    .set((
+                        dsl::generation.eq(new_generation),
+                        dsl::sk_set.eq(sk_set.clone()),
+                        dsl::new_sk_set.eq(new_sk_set.clone()),
+                    ))
+                    .execute(conn)
+                    .await?;
+
+                match updated {
+                    1 => Ok(()),
+                    0 => Err(DatabaseError::Logical(format!(
+                        "timeline {tenant_id}/{timeline_id} is deleted or its generation is not {expected_generation}"
+                    ))),
+                    _ => Err(DatabaseError::Logical(format!(
+                        "unexpected number of rows ({})",
+                        updated
+                    ))),
+                }
+            })
+        })
+        .await
+    }
+
+    /// Moves the start of a timeline on the safekeepers to `start_lsn`, after it got reset to it.
+    pub(crate) async fn update_timeline_start_lsn(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        expected_generation: i32,
+        start_lsn: Lsn,
+    ) -> DatabaseResult<()> {
+        use crate::schema::timelines::dsl;
+
+        self.with_measured_conn(DatabaseOperation::UpdateTimelineStartLsn, move |conn| {
+            Box::pin(async move {
+                let updated = diesel::update(dsl::timelines)
+                    .filter(dsl::tenant_id.eq(tenant_id.to_string()))
+                    .filter(dsl::timeline_id.eq(timeline_id.to_string()))
+                    .filter(dsl::generation.eq(expected_generation))
+                    .filter(dsl::deleted_at.is_null())
+                    .set(dsl::start_lsn.eq(LsnWrapper(start_lsn)))
+                    .execute(conn)
+                    .await?;
+
+                match updated {
+                    1 => Ok(()),
+                    0 => Err(DatabaseError::Logical(format!(
+                        "timeline {tenant_id}/{timeline_id} is deleted or its generation is not {expected_generation}"
+                    ))),
+                    _ => Err(DatabaseError::Logical(format!(
+                        "unexpected number of rows ({})",
+                        updated
+                    ))),
+                }
+            })
+        })
+        .await
+    }
+
+    /// Records that the control plane was notified of the safekeepers of a timeline in
+    /// `generation`, if that is still its current generation.
+    pub(crate) async fn update_cplane_notified_generation(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        generation: i32,
+    ) -> DatabaseResult<()> {
+        use crate::schema::timelines::dsl;
+
+        self.with_measured_conn(
+            DatabaseOperation::UpdateTimelineCplaneNotifiedGeneration,
+            move |conn| {
+                Box::pin(async move {
+                    let updated = diesel::update(dsl::timelines)
+                        .filter(dsl::tenant_id.eq(tenant_id.to_string()))
+                        .filter(dsl::timeline_id.eq(timeline_id.to_string()))
+                        .filter(dsl::generation.eq(generation))
+                        .filter(dsl::deleted_at.is_null())
+                        .set(dsl::cplane_notified_generation.eq(generation))
+                        .execute(conn)
+                        .await?;
+
+                    match updated {
+                        1 => Ok(()),
+                        0 => Err(DatabaseError::Logical(format!(
+                            "timeline {tenant_id}/{timeline_id} is deleted or its generation is not {generation}"
+                        ))),
+                        _ => Err(DatabaseError::Logical(format!(
+                            "unexpected number of rows ({})",
+                            updated
+                        ))),
+                    }
+                })
+            },
+        )
+        .await
+    }
+
+    /// Load up to `limit` timelines which are hosted on the given safekeeper and
+    /// are neither being deleted nor in the middle of a membership change, ordered
+    /// by tenant and timeline id and starting right after `after`.
+    pub(crate) async fn list_timelines_on_safekeeper(
+        &self,
+        sk_id: NodeId,
+        after: Option<TenantTimelineId>,
+        limit: i64,
+    ) -> DatabaseResult<Vec<TimelinePersistence>> {
+        use crate::schema::timelines::dsl;
+
+        let (after_tenant_id, after_timeline_id) = after
+            .map(|ttid| (ttid.tenant_id.to_string(), ttid.timeline_id.to_string()))
+            .unwrap_or_default();
+        let after_tenant_id = &after_tenant_id;
+        let after_timeline_id = &after_timeline_id;
+        let timelines = self
+            .with_measured_conn(DatabaseOperation::ListTimelines, move |conn| {
+                Box::pin(async move {
+                    let timelines: Vec<TimelineFromDb> = dsl::timelines
+                        .filter(dsl::sk_set.contains(vec![Some(sk_id.0 as i64)]))
+                        .filter(
+                            dsl::tenant_id.gt(after_tenant_id).or(dsl::tenant_id
+                                .eq(after_tenant_id)
+                                .and(dsl::timeline_id.gt(after_timeline_id))),
+                        )
+                        .filter(dsl::new_sk_set.is_null())
+                        .filter(dsl::deleted_at.is_null())
+                        .order((dsl::tenant_id, dsl::timeline_id))
+                        .limit(limit)
+                        .load(conn)
+                        .await?;
+                    Ok(timelines)
+                })
+            })
+            .await?;
+
+        let timelines = timelines
+            .into_iter()
+            .map(TimelineFromDb::into_persistence)
+            .collect();
+        Ok(timelines)
+    }
+
+    /// Load up to `limit` timelines ordered by tenant and timeline id, starting
+    /// right after `after`. Timelines being deleted or in the middle of a membership
+    /// change are skipped.
+    pub(crate) async fn list_timelines_after(
+        &self,
+        after: Option<TenantTimelineId>,
+        limit: i64,
+    ) -> DatabaseResult<Vec<TimelinePersistence>> {
+        use crate::schema::timelines::dsl;
+
+        let (after_tenant_id, after_timeline_id) = after
+            .map(|ttid| (ttid.tenant_id.to_string(), ttid.timeline_id.to_string()))
+            .unwrap_or_default();
+        let after_tenant_id = &after_tenant_id;
+        let after_timeline_id = &after_timeline_id;
+        let timelines = self
+            .with_measured_conn(DatabaseOperation::ListTimelines, move |conn| {
+                Box::pin(async move {
+                    let timelines: Vec<TimelineFromDb> = dsl::timelines
+                        .filter(
+                            dsl::tenant_id.gt(after_tenant_id).or(dsl::tenant_id
+                                .eq(after_tenant_id)
+                                .and(dsl::timeline_id.gt(after_timeline_id))),
+                        )
+                        .filter(dsl::new_sk_set.is_null())
+                        .filter(dsl::deleted_at.is_null())
+                        .order((dsl::tenant_id, dsl::timeline_id))
+                        .limit(limit)
+                        .load(conn)
+                        .await?;
+                    Ok(timelines)
+                })
+            })
+            .await?;
+
+        let timelines = timelines
+            .into_iter()
+            .map(TimelineFromDb::into_persistence)
+            .collect();
+        Ok(timelines)
+    }
+
+    /// Load all timelines which are in the middle of a membership change, i.e.
+    /// have `new_sk_set` set.
+    pub(crate) async fn list_timelines_in_migration(
+        &self,
+    ) -> DatabaseResult<Vec<TimelinePersistence>> {
+        use crate::schema::timelines::dsl;
+
+        let timelines = self
+            .with_measured_conn(DatabaseOperation::ListTimelines, move |conn| {
+                Box::pin(async move {
+                    let timelines: Vec<TimelineFromDb> = dsl::timelines
+                        .filter(dsl::new_sk_set.is_not_null())
+                        .filter(dsl::deleted_at.is_null())
+                        .load(conn)
+                        .await?;
+                    Ok(timelines)
+                })
+            })
+            .await?;
+
+        let timelines = timelines
+            .into_iter()
+            .map(TimelineFromDb::into_persistence)
+            .collect();
+        Ok(timelines)
+    }
+
     /// Persist pending op. Returns if it was newly inserted. If it wasn't, we haven't done any writes.
     pub(crate) async fn insert_pending_op(
         &self,
@@ -1595,6 +1954,77 @@ pub(crate) async fn remove_pending_ops_for_timeline(
 
         Ok(())
     }
+
+    /// Store the divergence found in the WAL of a timeline, replacing the one
+    /// stored for it before if any.
+    pub(crate) async fn upsert_safekeeper_wal_divergence(
+        &self,
+        entry: SafekeeperWalDivergencePersistence,
+    ) -> DatabaseResult<()> {
+        use crate::schema::safekeeper_wal_divergences::dsl;
+
+        let entry = &entry;
+        self.with_measured_conn(
+            DatabaseOperation::UpsertSafekeeperWalDivergence,
+            move |conn| {
+                Box::pin(async move {
+                    diesel::insert_into(dsl::safekeeper_wal_divergences)
+                        .values(entry)
+                        .on_conflict((dsl::tenant_id, dsl::timeline_id))
+                        .do_update()
+                        .set(entry)
+                        .execute(conn)
+                        .await?;
+                    Ok(())
+                })
+            },
+        )
+        .await
+    }
+
+    /// Remove the divergence stored for the timeline, if any.
+    pub(crate) async fn remove_safekeeper_wal_divergence(
+        &self,
+        ttid: TenantTimelineId,
+    ) -> DatabaseResult<()> {
+        use crate::schema::safekeeper_wal_divergences::dsl;
+
+        let tenant_id = &ttid.tenant_id.to_string();
+        let timeline_id = &ttid.timeline_id.to_string();
+        self.with_measured_conn(
+            DatabaseOperation::RemoveSafekeeperWalDivergence,
+            move |conn| {
+                Box::pin(async move {
+                    diesel::delete(dsl::safekeeper_wal_divergences)
+                        .filter(dsl::tenant_id.eq(tenant_id))
+                        .filter(dsl::timeline_id.eq(timeline_id))
+                        .execute(conn)
+                        .await?;
+                    Ok(())
+                })
+            },
+        )
+        .await
+    }
+
+    /// Load all the stored divergences.
+    pub(crate) async fn list_safekeeper_wal_divergences(
+        &self,
+    ) -> DatabaseResult<Vec<SafekeeperWalDivergencePersistence>> {
+        use crate::schema::safekeeper_wal_divergences::dsl;
+
+        self.with_measured_conn(
+            DatabaseOperation::ListSafekeeperWalDivergences,
+            move |conn| {
+                Box::pin(async move {
+                    let from_db: Vec<SafekeeperWalDivergencePersistence> =
+                        dsl::safekeeper_wal_divergences.load(conn).await?;
+                    Ok(from_db)
+                })
+            },
+        )
+        .await
+    }
 }
 
 pub(crate) fn load_certs() -> anyhow::Result<Arc<rustls::RootCertStore>> {
@@ -2152,3 +2582,28 @@ fn to_sql<'b>(
             .map_err(Into::into)
     }
 }
+
+/// A divergence found in the WAL of a timeline across its safekeepers. Stored so
+/// that it is reported after a restart of the storage controller as well.
+#[derive(Insertable, AsChangeset, Queryable, Selectable, Clone)]
+#[diesel(table_name = crate::schema::safekeeper_wal_divergences)]
+pub(crate) struct SafekeeperWalDivergencePersistence {
+    pub(crate) tenant_id: String,
+    pub(crate) timeline_id: String,
+    /// Serialized [`SafekeeperWalVerificationDescribe`]
+    pub(crate) verification: String,
+}
+
+impl SafekeeperWalDivergencePersistence {
+    pub(crate) fn new(verification: &SafekeeperWalVerificationDescribe) -> Self {
+        SafekeeperWalDivergencePersistence {
+            tenant_id: verification.tenant_id.to_string(),
+            timeline_id: verification.timeline_id.to_string(),
+            verification: serde_json::to_string(verification).unwrap(),
+        }
+    }
+
+    pub(crate) fn into_describe(self) -> serde_json::Result<SafekeeperWalVerificationDescribe> {
+        serde_json::from_str(&self.verification)
+    }
+}
@@ -11,6 +11,7 @@
 pub enum SplitState {
     Idle = 0,
     Splitting = 1,
+    Merging = 2,
 }
 
 impl Default for SplitState {
@@ -37,6 +38,7 @@ fn from_sql(pg_value: PgValue) -> diesel::deserialize::Result<Self> {
         match FromSql::<SplitStateSQLRepr, Pg>::from_sql(pg_value).map(|v| match v {
             0 => Some(Self::Idle),
             1 => Some(Self::Splitting),
+            2 => Some(Self::Merging),
             _ => None,
         })? {
             Some(v) => Ok(v),
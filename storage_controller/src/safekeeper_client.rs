@@ -1,9 +1,11 @@
 use safekeeper_api::models::{
     self, PullTimelineRequest, PullTimelineResponse, SafekeeperUtilization, TimelineCreateRequest,
+    TimelineStatus,
 };
 use safekeeper_client::mgmt_api::{Client, Result};
 use utils::id::{NodeId, TenantId, TimelineId};
 use utils::logging::SecretString;
+use utils::lsn::Lsn;
 
 use crate::metrics::PageserverRequestLabelGroup;
 
@@ -85,6 +87,52 @@ pub(crate) async fn exclude_timeline(
         )
     }
 
+    pub(crate) async fn membership_switch(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        req: &models::TimelineMembershipSwitchRequest,
+    ) -> Result<models::TimelineMembershipSwitchResponse> {
+        measured_request!(
+            "membership_switch",
+            crate::metrics::Method::Put,
+            &self.node_id_label,
+            self.inner
+                .membership_switch(tenant_id, timeline_id, req)
+                .await
+        )
+    }
+
+    pub(crate) async fn timeline_status(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+    ) -> Result<TimelineStatus> {
+        measured_request!(
+            "timeline_status",
+            crate::metrics::Method::Get,
+            &self.node_id_label,
+            self.inner.timeline_status(tenant_id, timeline_id).await
+        )
+    }
+
+    pub(crate) async fn timeline_digest(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        from_lsn: Lsn,
+        until_lsn: Lsn,
+    ) -> Result<models::TimelineDigest> {
+        measured_request!(
+            "timeline_digest",
+            crate::metrics::Method::Get,
+            &self.node_id_label,
+            self.inner
+                .timeline_digest(tenant_id, timeline_id, from_lsn, until_lsn)
+                .await
+        )
+    }
+
     pub(crate) async fn delete_timeline(
         &self,
         tenant_id: TenantId,
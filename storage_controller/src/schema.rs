@@ -46,6 +46,14 @@ pub mod sql_types {
     }
 }
 
+diesel::table! {
+    safekeeper_wal_divergences (tenant_id, timeline_id) {
+        tenant_id -> Varchar,
+        timeline_id -> Varchar,
+        verification -> Varchar,
+    }
+}
+
 diesel::table! {
     safekeepers (id) {
         id -> Int8,
@@ -97,6 +105,7 @@ pub mod sql_types {
     metadata_health,
     nodes,
     safekeeper_timeline_pending_ops,
+    safekeeper_wal_divergences,
     safekeepers,
     tenant_shards,
     timelines,
@@ -1,7 +1,10 @@
 pub mod chaos_injector;
 mod context_iterator;
+mod safekeeper_operations;
+mod safekeeper_rebalancer;
 pub(crate) mod safekeeper_reconciler;
 mod safekeeper_service;
+mod safekeeper_verifier;
 
 use std::borrow::Cow;
 use std::cmp::Ordering;
@@ -28,8 +31,9 @@
 use pageserver_api::controller_api::{
     AvailabilityZone, MetadataHealthRecord, MetadataHealthUpdateRequest, NodeAvailability,
     NodeRegisterRequest, NodeSchedulingPolicy, NodeShard, NodeShardResponse, PlacementPolicy,
-    ShardSchedulingPolicy, ShardsPreferredAzsRequest, ShardsPreferredAzsResponse,
-    TenantCreateRequest, TenantCreateResponse, TenantCreateResponseShard, TenantDescribeResponse,
+    SafekeeperMigrationDescribe, SafekeeperWalVerificationDescribe, ShardSchedulingPolicy,
+    ShardsPreferredAzsRequest, ShardsPreferredAzsResponse, TenantCreateRequest,
+    TenantCreateResponse, TenantCreateResponseShard, TenantDescribeResponse,
     TenantDescribeResponseShard, TenantLocateResponse, TenantPolicyRequest,
     TenantShardMigrateRequest, TenantShardMigrateResponse,
 };
@@ -37,10 +41,12 @@
     self, DetachBehavior, LocationConfig, LocationConfigListResponse, LocationConfigMode, LsnLease,
     PageserverUtilization, SecondaryProgress, ShardParameters, TenantConfig,
     TenantConfigPatchRequest, TenantConfigRequest, TenantLocationConfigRequest,
-    TenantLocationConfigResponse, TenantShardLocation, TenantShardSplitRequest,
-    TenantShardSplitResponse, TenantSorting, TenantTimeTravelRequest,
-    TimelineArchivalConfigRequest, TimelineCreateRequest, TimelineCreateResponseStorcon,
-    TimelineInfo, TopTenantShardItem, TopTenantShardsRequest,
+    TenantLocationConfigResponse, TenantShardLocation, TenantShardMergeRequest,
+    TenantShardMergeResponse, TenantShardSplitRequest, TenantShardSplitResponse, TenantSorting,
+    TenantTimeTravelRequest, TimelineArchivalConfigRequest, TimelineCreateRequest,
+    TimelineCreateResponseStorcon, TimelineExportPgdataRequest, TimelineExportPgdataShard,
+    TimelineExportPgdataTaskInfo, TimelineInfo, TimelineResetToLsnRequest,
+    TimelineRetentionPolicyRequest, TopTenantShardItem, TopTenantShardsRequest,
 };
 use pageserver_api::shard::{
     ShardCount, ShardIdentity, ShardNumber, ShardStripeSize, TenantShardId,
@@ -59,7 +65,7 @@
 use tracing::{Instrument, debug, error, info, info_span, instrument, warn};
 use utils::completion::Barrier;
 use utils::generation::Generation;
-use utils::id::{NodeId, TenantId, TimelineId};
+use utils::id::{NodeId, TenantId, TenantTimelineId, TimelineId};
 use utils::lsn::Lsn;
 use utils::sync::gate::Gate;
 use utils::{failpoint_support, pausable_failpoint};
@@ -144,6 +150,7 @@ enum TenantOperations {
     Delete,
     UpdatePolicy,
     ShardSplit,
+    ShardMerge,
     SecondaryDownload,
     TimelineCreate,
     TimelineDelete,
@@ -151,9 +158,13 @@ enum TenantOperations {
     TimelineArchivalConfig,
     TimelineDetachAncestor,
     TimelineGcBlockUnblock,
+    TimelineRetentionPolicy,
     DropDetached,
     DownloadHeatmapLayers,
     TimelineLsnLease,
+    TimelineExportPgdata,
+    TimelineResetToLsn,
+    TimelineSafekeeperMigrate,
 }
 
 #[derive(Clone, strum_macros::Display)]
@@ -191,6 +202,7 @@ pub(crate) enum LeadershipStatus {
 
 pub const RECONCILER_CONCURRENCY_DEFAULT: usize = 128;
 pub const PRIORITY_RECONCILER_CONCURRENCY_DEFAULT: usize = 256;
+pub const SAFEKEEPER_MIGRATION_CONCURRENCY_DEFAULT: usize = 4;
 
 // Depth of the channel used to enqueue shards for reconciliation when they can't do it immediately.
 // This channel is finite-size to avoid using excessive memory if we get into a state where reconciles are finishing more slowly
@@ -209,6 +221,12 @@ struct ServiceState {
 
     safekeeper_reconcilers: SafekeeperReconcilers,
 
+    /// Ongoing migrations of timelines between safekeepers
+    safekeeper_migrations: HashMap<TenantTimelineId, SafekeeperMigrationDescribe>,
+
+    /// Results of comparing the WAL of timelines across their safekeepers
+    safekeeper_wal_verification: HashMap<TenantTimelineId, SafekeeperWalVerificationDescribe>,
+
     scheduler: Scheduler,
 
     /// Ongoing background operation on the cluster if any is running.
@@ -274,9 +292,11 @@ fn passthrough_api_error(node: &Node, e: mgmt_api::Error) -> ApiError {
 }
 
 impl ServiceState {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         nodes: HashMap<NodeId, Node>,
         safekeepers: HashMap<NodeId, Safekeeper>,
+        safekeeper_wal_verification: HashMap<TenantTimelineId, SafekeeperWalVerificationDescribe>,
         tenants: BTreeMap<TenantShardId, TenantShard>,
         scheduler: Scheduler,
         delayed_reconcile_rx: tokio::sync::mpsc::Receiver<TenantShardId>,
@@ -291,6 +311,8 @@ fn new(
             nodes: Arc::new(nodes),
             safekeepers: Arc::new(safekeepers),
             safekeeper_reconcilers: SafekeeperReconcilers::new(reconcilers_cancel),
+            safekeeper_migrations: HashMap::new(),
+            safekeeper_wal_verification,
             scheduler,
             ongoing_operation: None,
             delayed_reconcile_rx,
@@ -452,6 +474,17 @@ pub struct Config {
     pub timelines_onto_safekeepers: bool,
 
     pub use_local_compute_notifications: bool,
+
+    /// How often to look for timelines to move off draining or overloaded
+    /// safekeepers. None disables safekeeper rebalancing.
+    pub safekeeper_rebalance_interval: Option<Duration>,
+
+    /// How many timeline migrations between safekeepers may run concurrently
+    pub safekeeper_migration_concurrency: usize,
+
+    /// How often to compare the newly committed WAL across the safekeepers of
+    /// timelines. None disables the verification.
+    pub safekeeper_wal_verification_interval: Option<Duration>,
 }
 
 impl From<DatabaseError> for ApiError {
@@ -585,13 +618,42 @@ struct ShardSplitTarget {
     child_ids: Vec<TenantShardId>,
 }
 
+struct ShardMergeParams {
+    old_shard_count: ShardCount,
+    new_shard_count: ShardCount,
+    targets: Vec<ShardMergeTarget>,
+    policy: PlacementPolicy,
+    config: TenantConfig,
+    shard_ident: ShardIdentity,
+    preferred_az_id: Option<AvailabilityZone>,
+}
+
+// When preparing for a shard merge, we may either choose to proceed with the merge,
+// or find that the work is already done and return NoOp.
+enum ShardMergeAction {
+    Merge(Box<ShardMergeParams>),
+    NoOp(TenantShardMergeResponse),
+}
+
+// A pair of parent shards which will be merged.  The merge is done on the node where
+// the lower-numbered parent is attached.
+struct ShardMergeTarget {
+    parent_ids: Vec<TenantShardId>,
+    node: Node,
+    child_id: TenantShardId,
+}
+
 /// When we tenant shard split operation fails, we may not be able to clean up immediately, because nodes
 /// might not be available.  We therefore use a queue of abort operations processed in the background.
+///
+/// Failed shard merges are cleaned up the same way.
 struct TenantShardSplitAbort {
     tenant_id: TenantId,
     /// The target values from the request that failed
     new_shard_count: ShardCount,
     new_stripe_size: Option<ShardStripeSize>,
+    /// Whether the request that failed was a merge rather than a split
+    merge: bool,
     /// Until this abort op is complete, no other operations may be done on the tenant
     _tenant_lock: TracingExclusiveGuard<TenantOperations>,
 }
@@ -1461,11 +1523,17 @@ async fn process_aborts(
             // processed, as it holds a lock guard that prevents other operations trying to do things
             // to the tenant while it is in a weird part-split state.
             while !self.cancel.is_cancelled() {
-                match self.abort_tenant_shard_split(&op).await {
+                let result = if op.merge {
+                    self.abort_tenant_shard_merge(&op).await
+                } else {
+                    self.abort_tenant_shard_split(&op).await
+                };
+                match result {
                     Ok(_) => break,
                     Err(e) => {
                         tracing::warn!(
-                            "Failed to abort shard split on {}, will retry: {e}",
+                            "Failed to abort shard {} on {}, will retry: {e}",
+                            if op.merge { "merge" } else { "split" },
                             op.tenant_id
                         );
 
@@ -1527,6 +1595,25 @@ pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Res
             safekeepers.into_iter().map(|n| (n.get_id(), n)).collect();
         tracing::info!("Loaded {} safekeepers from database.", safekeepers.len());
 
+        tracing::info!("Loading safekeeper WAL divergences from database...");
+        let safekeeper_wal_verification = persistence
+            .list_safekeeper_wal_divergences()
+            .await?
+            .into_iter()
+            .map(|d| {
+                let verification = d
+                    .into_describe()
+                    .context("invalid stored safekeeper WAL divergence")?;
+                let ttid = TenantTimelineId::new(verification.tenant_id, verification.timeline_id);
+                Ok((ttid, verification))
+            })
+            .collect::<anyhow::Result<HashMap<_, _>>>()?;
+        tracing::info!(
+            "Loaded {} safekeeper WAL divergences from database.",
+            safekeeper_wal_verification.len()
+        );
+        safekeeper_verifier::update_divergence_gauge(&safekeeper_wal_verification);
+
         tracing::info!("Loading shards from database...");
         let mut tenant_shard_persistence = persistence.load_active_tenant_shards().await?;
         tracing::info!(
@@ -1548,7 +1635,32 @@ pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Res
         }
 
         for (tenant_id, (count_min, count_max)) in tenant_shard_count_min_max {
-            if count_min != count_max {
+            let merging = tenant_shard_persistence.iter().any(|tsp| {
+                tsp.splitting == SplitState::Merging
+                    && TenantId::from_str(tsp.tenant_id.as_str()).unwrap() == tenant_id
+            });
+            if count_min != count_max && merging {
+                // A merge was in progress: the merged shards are the ones with the smaller count.  As for splits,
+                // dropping them from the database is sufficient, and reconciliation will clean up the pageservers.
+                tracing::info!("Aborting shard merge {tenant_id} {count_max:?} -> {count_min:?}");
+                let abort_status = persistence.abort_shard_merge(tenant_id, count_min).await?;
+                assert!(matches!(abort_status, AbortShardSplitStatus::Aborted));
+
+                tenant_shard_persistence.retain_mut(|tsp| {
+                    let tsp_tenant_id = TenantId::from_str(tsp.tenant_id.as_str()).unwrap();
+                    if tsp_tenant_id != tenant_id {
+                        true
+                    } else if tsp.get_shard_identity().unwrap().count == count_max {
+                        tsp.splitting = SplitState::Idle;
+                        true
+                    } else {
+                        tracing::info!(
+                            "Shard {tsp_tenant_id} will be dropped after shard merge abort",
+                        );
+                        false
+                    }
+                });
+            } else if count_min != count_max {
                 // Aborting the split in the database and dropping the child shards is sufficient: the reconciliation in
                 // [`Self::startup_reconcile`] will implicitly drop the child shards on remote pageservers, or they'll
                 // be dropped later in [`Self::node_activate_reconcile`] if it isn't available right now.
@@ -1703,6 +1815,7 @@ pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Res
             inner: Arc::new(std::sync::RwLock::new(ServiceState::new(
                 nodes,
                 safekeepers,
+                safekeeper_wal_verification,
                 tenants,
                 scheduler,
                 delayed_reconcile_rx,
@@ -1806,6 +1919,37 @@ pub async fn spawn(config: Config, persistence: Arc<Persistence>) -> anyhow::Res
             }
         });
 
+        if let Some(interval) = this.config.safekeeper_rebalance_interval {
+            tokio::task::spawn({
+                let this = this.clone();
+                let startup_complete = startup_complete.clone();
+                async move {
+                    startup_complete.wait().await;
+                    this.safekeeper_rebalance_loop(interval).await;
+                }
+            });
+        }
+
+        if let Some(interval) = this.config.safekeeper_wal_verification_interval {
+            tokio::task::spawn({
+                let this = this.clone();
+                let startup_complete = startup_complete.clone();
+                async move {
+                    startup_complete.wait().await;
+                    this.safekeeper_wal_verification_loop(interval).await;
+                }
+            });
+        }
+
+        tokio::task::spawn({
+            let this = this.clone();
+            let startup_complete = startup_complete.clone();
+            async move {
+                startup_complete.wait().await;
+                this.resume_safekeeper_operations();
+            }
+        });
+
         Ok(this)
     }
 
@@ -3678,6 +3822,10 @@ async fn create_one(
                 models::TimelineCreateRequestMode::Branch { ancestor_start_lsn, .. } if ancestor_start_lsn.is_none() => {
                     *ancestor_start_lsn = timeline_info.ancestor_lsn;
                 },
+                // Cross-tenant branches are root timelines: the branch point is where they start.
+                models::TimelineCreateRequestMode::CrossTenantBranch { ancestor_start_lsn, .. } if ancestor_start_lsn.is_none() => {
+                    *ancestor_start_lsn = Some(timeline_info.last_record_lsn);
+                },
                 _ => {}
             }
 
@@ -3823,6 +3971,81 @@ async fn config_one(
         }).await?
     }
 
+    pub(crate) async fn tenant_timeline_retention_policy(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        req: TimelineRetentionPolicyRequest,
+    ) -> Result<(), ApiError> {
+        tracing::info!(
+            "Setting retention policy of timeline {tenant_id}/{timeline_id} to {:?}",
+            req.retention_policy
+        );
+
+        // Validate up front rather than failing on some of the shards.
+        if let Some(retention_policy) = &req.retention_policy {
+            retention_policy.validate().map_err(|e| {
+                ApiError::BadRequest(anyhow::anyhow!("invalid retention policy: {e}"))
+            })?;
+        }
+
+        let _tenant_lock = trace_shared_lock(
+            &self.tenant_op_locks,
+            tenant_id,
+            TenantOperations::TimelineRetentionPolicy,
+        )
+        .await;
+
+        self.tenant_remote_mutation(tenant_id, move |targets| async move {
+            if targets.0.is_empty() {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant not found").into(),
+                ));
+            }
+
+            async fn do_one(
+                tenant_shard_id: TenantShardId,
+                timeline_id: TimelineId,
+                node: Node,
+                http_client: reqwest::Client,
+                jwt: Option<String>,
+                req: TimelineRetentionPolicyRequest,
+            ) -> Result<(), ApiError> {
+                let client = PageserverClient::new(
+                    node.get_id(),
+                    http_client,
+                    node.base_url(),
+                    jwt.as_deref(),
+                );
+
+                client
+                    .timeline_retention_policy(tenant_shard_id, timeline_id, &req)
+                    .await
+                    .map_err(|e| passthrough_api_error(&node, e))
+            }
+
+            // no shard needs to go first/last; the operation should be idempotent
+            let locations = targets
+                .0
+                .iter()
+                .map(|t| (*t.0, t.1.latest.node.clone()))
+                .collect();
+            self.tenant_for_shards(locations, |tenant_shard_id, node| {
+                futures::FutureExt::boxed(do_one(
+                    tenant_shard_id,
+                    timeline_id,
+                    node,
+                    self.http_client.clone(),
+                    self.config.pageserver_jwt_token.clone(),
+                    req.clone(),
+                ))
+            })
+            .await
+        })
+        .await??;
+        Ok(())
+    }
+
     pub(crate) async fn tenant_timeline_detach_ancestor(
         &self,
         tenant_id: TenantId,
@@ -3926,6 +4149,177 @@ async fn detach_one(
         }).await?
     }
 
+    /// Reset a timeline of all the shards of a tenant in place to an earlier LSN, discarding
+    /// everything it ingested after it.
+    ///
+    /// The reset is first validated on every shard. The timeline is then reset on its
+    /// safekeepers, so that they don't stream the discarded WAL again, and finally on every
+    /// shard, each in a new generation. Computes must be stopped. A failed reset can be
+    /// retried: the shards which were already reset are reset again to the same LSN.
+    pub(crate) async fn tenant_timeline_reset_to_lsn(
+        self: &Arc<Self>,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        req: TimelineResetToLsnRequest,
+    ) -> Result<(), ApiError> {
+        tracing::info!(
+            "Resetting timeline {tenant_id}/{timeline_id} to {}",
+            req.lsn
+        );
+
+        if req.generation.is_some() {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "generations are allocated by the storage controller"
+            )));
+        }
+        let lsn = req.lsn;
+
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            tenant_id,
+            TenantOperations::TimelineResetToLsn,
+        )
+        .await;
+
+        // Settle any ongoing reconciliation before the generations change under it.
+        let waiters = {
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, _scheduler) = locked.parts_mut();
+            let mut waiters = Vec::new();
+            for (_, shard) in tenants.range_mut(TenantShardId::tenant_range(tenant_id)) {
+                if let Some(waiter) =
+                    self.maybe_reconcile_shard(shard, nodes, ReconcilerPriority::High)
+                {
+                    waiters.push(waiter);
+                }
+            }
+            waiters
+        };
+        self.await_waiters(waiters, RECONCILE_TIMEOUT).await?;
+
+        let (locations, pg_version) = self
+            .tenant_remote_mutation(tenant_id, move |targets| async move {
+                if targets.0.is_empty() {
+                    return Err(ApiError::NotFound(
+                        anyhow::anyhow!("Tenant not found").into(),
+                    ));
+                }
+
+                let locations = targets
+                    .0
+                    .iter()
+                    .map(|(tenant_shard_id, t)| (*tenant_shard_id, t.latest.node.clone()))
+                    .collect::<Vec<_>>();
+                let dry_run = TimelineResetToLsnRequest {
+                    lsn,
+                    generation: None,
+                    dry_run: true,
+                };
+                let results = self
+                    .tenant_for_shards_api(
+                        locations.clone(),
+                        |tenant_shard_id, client| {
+                            let dry_run = &dry_run;
+                            async move {
+                                client
+                                    .timeline_reset_to_lsn(tenant_shard_id, timeline_id, dry_run)
+                                    .await
+                            }
+                        },
+                        1,
+                        1,
+                        SHORT_RECONCILE_TIMEOUT,
+                        &self.cancel,
+                    )
+                    .await;
+                for ((_, node), res) in locations.iter().zip(results) {
+                    res.map_err(|e| match e {
+                        mgmt_api::Error::ApiError(StatusCode::BAD_REQUEST, msg) => {
+                            ApiError::BadRequest(anyhow::anyhow!("{node}: {msg}"))
+                        }
+                        mgmt_api::Error::ApiError(StatusCode::CONFLICT, msg) => ApiError::Conflict(
+                            format!("{node}: {}", msg.strip_prefix("Conflict: ").unwrap_or(&msg)),
+                        ),
+                        other => passthrough_api_error(node, other),
+                    })?;
+                }
+
+                // Shard zero comes first.
+                let (shard_zero, node) = &locations[0];
+                let client = PageserverClient::new(
+                    node.get_id(),
+                    self.http_client.clone(),
+                    node.base_url(),
+                    self.config.pageserver_jwt_token.as_deref(),
+                );
+                let info = client
+                    .timeline_info(*shard_zero, timeline_id)
+                    .await
+                    .map_err(|e| passthrough_api_error(node, e))?;
+
+                Ok((locations, info.pg_version))
+            })
+            .await??;
+        if req.dry_run {
+            return Ok(());
+        }
+
+        // The safekeepers go first: a shard which is reset while they still have the discarded
+        // WAL would ingest it again.
+        self.tenant_timeline_reset_safekeepers(tenant_id, timeline_id, pg_version * 10000, lsn)
+            .await?;
+
+        for (tenant_shard_id, node) in locations {
+            let generation = self
+                .persistence
+                .increment_generation(tenant_shard_id, node.get_id())
+                .await?;
+            {
+                let mut locked = self.inner.write().unwrap();
+                if let Some(shard) = locked.tenants.get_mut(&tenant_shard_id) {
+                    shard.generation = Some(generation);
+                }
+            }
+
+            let client = PageserverClient::new(
+                node.get_id(),
+                self.http_client.clone(),
+                node.base_url(),
+                self.config.pageserver_jwt_token.as_deref(),
+            );
+            let req = TimelineResetToLsnRequest {
+                lsn,
+                generation: generation.into(),
+                dry_run: false,
+            };
+            let res = client
+                .timeline_reset_to_lsn(tenant_shard_id, timeline_id, &req)
+                .await;
+
+            // The pageserver comes back attached in the new generation. If it failed, we don't
+            // know in which generation it is: let the reconciler find out.
+            {
+                let mut locked = self.inner.write().unwrap();
+                if let Some(shard) = locked.tenants.get_mut(&tenant_shard_id) {
+                    match shard.observed.locations.get_mut(&node.get_id()) {
+                        Some(ObservedStateLocation { conf: Some(conf) }) if res.is_ok() => {
+                            conf.generation = generation.into();
+                        }
+                        Some(observed) => observed.conf = None,
+                        None => {}
+                    }
+                }
+            }
+
+            res.map_err(|e| passthrough_api_error(&node, e))?;
+            tracing::info!(
+                "Reset timeline {tenant_shard_id}/{timeline_id} to {lsn} on {node}, generation {generation:?}"
+            );
+        }
+
+        Ok(())
+    }
+
     pub(crate) async fn tenant_timeline_block_unblock_gc(
         &self,
         tenant_id: TenantId,
@@ -4058,6 +4452,56 @@ pub(crate) async fn tenant_timeline_lsn_lease(
         })
     }
 
+    /// Start exporting a timeline on shard zero, telling it where the other shards are attached,
+    /// so that it can read their relation blocks.
+    pub(crate) async fn tenant_timeline_export_pgdata(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        mut req: TimelineExportPgdataRequest,
+    ) -> Result<TimelineExportPgdataTaskInfo, ApiError> {
+        let _tenant_lock = trace_shared_lock(
+            &self.tenant_op_locks,
+            tenant_id,
+            TenantOperations::TimelineExportPgdata,
+        )
+        .await;
+
+        self.tenant_remote_mutation(tenant_id, move |targets| async move {
+            let mut locations = targets.0.into_iter();
+            // Shard zero comes first.
+            let Some((shard_zero, location)) = locations.next() else {
+                return Err(ApiError::NotFound(
+                    anyhow::anyhow!("Tenant not found").into(),
+                ));
+            };
+            req.shards = locations
+                .map(|(tenant_shard_id, location)| {
+                    let shard_location = location.latest.node.shard_location(tenant_shard_id);
+                    TimelineExportPgdataShard {
+                        shard_number: tenant_shard_id.shard_number,
+                        listen_pg_addr: shard_location.listen_pg_addr,
+                        listen_pg_port: shard_location.listen_pg_port,
+                    }
+                })
+                .collect();
+            req.auth_token = self.config.pageserver_jwt_token.clone();
+
+            let node = location.latest.node;
+            let client = PageserverClient::new(
+                node.get_id(),
+                self.http_client.clone(),
+                node.base_url(),
+                self.config.pageserver_jwt_token.as_deref(),
+            );
+            client
+                .timeline_export_pgdata(shard_zero, timeline_id, &req)
+                .await
+                .map_err(|e| passthrough_api_error(&node, e))
+        })
+        .await?
+    }
+
     pub(crate) async fn tenant_timeline_download_heatmap_layers(
         &self,
         tenant_shard_id: TenantShardId,
@@ -4663,7 +5107,7 @@ fn tenant_describe_impl<'a>(
                     .clone(),
                 is_reconciling: shard.reconciler.is_some(),
                 is_pending_compute_notification: shard.pending_compute_notification,
-                is_splitting: matches!(shard.splitting, SplitState::Splitting),
+                is_splitting: !matches!(shard.splitting, SplitState::Idle),
                 scheduling_policy: shard.get_scheduling_policy(),
                 preferred_az_id: shard.preferred_az().map(ToString::to_string),
             })
@@ -4824,37 +5268,8 @@ async fn abort_tenant_shard_split(
                     );
                 }
 
-                tracing::info!("Restoring parent shard {tenant_shard_id}");
-
-                // Drop any intents that refer to unavailable nodes, to enable this abort to proceed even
-                // if the original attachment location is offline.
-                if let Some(node_id) = shard.intent.get_attached() {
-                    if !nodes.get(node_id).unwrap().is_available() {
-                        tracing::info!(
-                            "Demoting attached intent for {tenant_shard_id} on unavailable node {node_id}"
-                        );
-                        shard.intent.demote_attached(scheduler, *node_id);
-                    }
-                }
-                for node_id in shard.intent.get_secondary().clone() {
-                    if !nodes.get(&node_id).unwrap().is_available() {
-                        tracing::info!(
-                            "Dropping secondary intent for {tenant_shard_id} on unavailable node {node_id}"
-                        );
-                        shard.intent.remove_secondary(scheduler, node_id);
-                    }
-                }
-
-                shard.splitting = SplitState::Idle;
-                if let Err(e) = shard.schedule(scheduler, &mut ScheduleContext::default()) {
-                    // If this shard can't be scheduled now (perhaps due to offline nodes or
-                    // capacity issues), that must not prevent us rolling back a split.  In this
-                    // case it should be eventually scheduled in the background.
-                    tracing::warn!("Failed to schedule {tenant_shard_id} during shard abort: {e}")
-                }
-
-                self.maybe_reconcile_shard(shard, nodes, ReconcilerPriority::High);
-            }
+                self.abort_restore_parent(*tenant_shard_id, shard, nodes, scheduler);
+            }
 
             // We don't expect any new_shard_count shards to exist here, but drop them just in case
             tenants.retain(|_id, s| s.shard.count != *new_shard_count);
@@ -4862,6 +5277,59 @@ async fn abort_tenant_shard_split(
             detach_locations
         };
 
+        self.abort_detach_children(detach_locations).await?;
+
+        tracing::info!("Successfully aborted split");
+        Ok(())
+    }
+
+    /// Part of aborting a shard split or merge: restore a parent shard to its normal state, and reconcile
+    /// it to make sure it is attached again.
+    fn abort_restore_parent(
+        &self,
+        tenant_shard_id: TenantShardId,
+        shard: &mut TenantShard,
+        nodes: &Arc<HashMap<NodeId, Node>>,
+        scheduler: &mut Scheduler,
+    ) {
+        tracing::info!("Restoring parent shard {tenant_shard_id}");
+
+        // Drop any intents that refer to unavailable nodes, to enable this abort to proceed even
+        // if the original attachment location is offline.
+        if let Some(node_id) = shard.intent.get_attached() {
+            if !nodes.get(node_id).unwrap().is_available() {
+                tracing::info!(
+                    "Demoting attached intent for {tenant_shard_id} on unavailable node {node_id}"
+                );
+                shard.intent.demote_attached(scheduler, *node_id);
+            }
+        }
+        for node_id in shard.intent.get_secondary().clone() {
+            if !nodes.get(&node_id).unwrap().is_available() {
+                tracing::info!(
+                    "Dropping secondary intent for {tenant_shard_id} on unavailable node {node_id}"
+                );
+                shard.intent.remove_secondary(scheduler, node_id);
+            }
+        }
+
+        shard.splitting = SplitState::Idle;
+        if let Err(e) = shard.schedule(scheduler, &mut ScheduleContext::default()) {
+            // If this shard can't be scheduled now (perhaps due to offline nodes or
+            // capacity issues), that must not prevent us rolling back a split.  In this
+            // case it should be eventually scheduled in the background.
+            tracing::warn!("Failed to schedule {tenant_shard_id} during shard abort: {e}")
+        }
+
+        self.maybe_reconcile_shard(shard, nodes, ReconcilerPriority::High);
+    }
+
+    /// Part of aborting a shard split or merge: detach the shards that the aborted operation may
+    /// have created on pageservers.
+    async fn abort_detach_children(
+        &self,
+        detach_locations: Vec<(Node, TenantShardId)>,
+    ) -> Result<(), TenantShardSplitAbortError> {
         for (node, child_id) in detach_locations {
             if !node.is_available() {
                 // An unavailable node cannot be cleaned up now: to avoid blocking forever, we will permit this, and
@@ -4869,12 +5337,12 @@ async fn abort_tenant_shard_split(
                 // removed child shards from our in-memory state and database, the reconciliation will implicitly remove
                 // them from the node.
                 tracing::warn!(
-                    "Node {node} unavailable, can't clean up during split abort. It will be cleaned up when it is reactivated."
+                    "Node {node} unavailable, can't clean up during abort. It will be cleaned up when it is reactivated."
                 );
                 continue;
             }
 
-            // Detach the remote child.  If the pageserver split API call is still in progress, this call will get
+            // Detach the remote child.  If the pageserver split or merge API call is still in progress, this call will get
             // a 503 and retry, up to our limit.
             tracing::info!("Detaching {child_id} on {node}...");
             match node
@@ -4919,7 +5387,6 @@ async fn abort_tenant_shard_split(
             };
         }
 
-        tracing::info!("Successfully aborted split");
         Ok(())
     }
 
@@ -5175,6 +5642,7 @@ pub(crate) async fn tenant_shard_split(
                         tenant_id,
                         new_shard_count,
                         new_stripe_size,
+                        merge: false,
                         _tenant_lock,
                     })
                     // Ignore error sending: that just means we're shutting down: aborts are ephemeral so it's fine to drop it.
@@ -5599,6 +6067,640 @@ async fn do_tenant_shard_split(
         Ok((response, waiters))
     }
 
+    pub(crate) async fn tenant_shard_merge(
+        &self,
+        tenant_id: TenantId,
+        merge_req: TenantShardMergeRequest,
+    ) -> Result<TenantShardMergeResponse, ApiError> {
+        let _tenant_lock = trace_exclusive_lock(
+            &self.tenant_op_locks,
+            tenant_id,
+            TenantOperations::ShardMerge,
+        )
+        .await;
+
+        let new_shard_count = ShardCount::new(merge_req.new_shard_count);
+
+        // Validate the request and construct parameters.  This phase is fallible, but does not require
+        // rollback on errors, as it does no I/O and mutates no state.
+        let shard_merge_params = match self.prepare_tenant_shard_merge(tenant_id, &merge_req)? {
+            ShardMergeAction::NoOp(resp) => return Ok(resp),
+            ShardMergeAction::Merge(params) => params,
+        };
+
+        // Execute this merge: this phase mutates state and does remote I/O on pageservers.  If it fails,
+        // we must roll back.
+        let r = self
+            .do_tenant_shard_merge(tenant_id, shard_merge_params)
+            .await;
+
+        let (response, waiters) = match r {
+            Ok(r) => r,
+            Err(e) => {
+                // Merge might be part-done, we must do work to abort it.
+                tracing::warn!("Enqueuing background abort of merge on {tenant_id}");
+                self.abort_tx
+                    .send(TenantShardSplitAbort {
+                        tenant_id,
+                        new_shard_count,
+                        new_stripe_size: None,
+                        merge: true,
+                        _tenant_lock,
+                    })
+                    // Ignore error sending: that just means we're shutting down: aborts are ephemeral so it's fine to drop it.
+                    .ok();
+                return Err(e);
+            }
+        };
+
+        // As after a split, warm up the secondary locations of the merged shards promptly.
+        self.tenant_shard_split_start_secondaries(tenant_id, waiters)
+            .await;
+        Ok(response)
+    }
+
+    fn prepare_tenant_shard_merge(
+        &self,
+        tenant_id: TenantId,
+        merge_req: &TenantShardMergeRequest,
+    ) -> Result<ShardMergeAction, ApiError> {
+        let new_shard_count = ShardCount::new(merge_req.new_shard_count);
+        if new_shard_count.literal() == 0 {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "Cannot merge to shard count 0"
+            )));
+        }
+
+        let locked = self.inner.read().unwrap();
+
+        let mut old_shard_count = None;
+        let mut shards_found = Vec::new();
+        for (tenant_shard_id, shard) in locked.tenants.range(TenantShardId::tenant_range(tenant_id))
+        {
+            match old_shard_count {
+                None => old_shard_count = Some(shard.shard.count),
+                Some(old_shard_count) if old_shard_count != shard.shard.count => {
+                    return Err(ApiError::Conflict(
+                        "Cannot merge, currently mid-split".to_string(),
+                    ));
+                }
+                Some(_) => {}
+            }
+            if !matches!(shard.splitting, SplitState::Idle) {
+                return Err(ApiError::Conflict(
+                    "Cannot merge, currently mid-split".to_string(),
+                ));
+            }
+            shards_found.push(*tenant_shard_id);
+        }
+
+        let Some(old_shard_count) = old_shard_count else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("Tenant {} not found", tenant_id).into(),
+            ));
+        };
+
+        if old_shard_count.count() == new_shard_count.count() {
+            // Already merged (perhaps this is a retry)
+            return Ok(ShardMergeAction::NoOp(TenantShardMergeResponse {
+                new_shards: shards_found,
+            }));
+        }
+
+        if u16::from(old_shard_count.count()) != u16::from(new_shard_count.count()) * 2 {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "Requested count {} but shards can only be merged in pairs, to count {}",
+                new_shard_count.count(),
+                old_shard_count.count() / 2
+            )));
+        }
+
+        let mut targets = Vec::new();
+        for shard_number in 0..new_shard_count.count() {
+            let child_id = TenantShardId {
+                tenant_id,
+                shard_number: ShardNumber(shard_number),
+                shard_count: new_shard_count,
+            };
+            let parent_ids = child_id.split(old_shard_count);
+
+            for parent_id in &parent_ids {
+                let Some(parent) = locked.tenants.get(parent_id) else {
+                    return Err(ApiError::Conflict(format!("Shard {parent_id} not found")));
+                };
+                if !matches!(parent.policy, PlacementPolicy::Attached(_))
+                    || parent.intent.get_attached().is_none()
+                {
+                    return Err(ApiError::BadRequest(anyhow::anyhow!(
+                        "Cannot merge a tenant that is not attached"
+                    )));
+                }
+            }
+
+            // unwrap safety: we checked above that both parents exist and are attached
+            let node_id = locked
+                .tenants
+                .get(&parent_ids[0])
+                .unwrap()
+                .intent
+                .get_attached()
+                .unwrap();
+            let node = locked
+                .nodes
+                .get(node_id)
+                .expect("Pageservers may not be deleted while referenced");
+
+            targets.push(ShardMergeTarget {
+                parent_ids,
+                node: node.clone(),
+                child_id,
+            });
+        }
+
+        // All shards share the policy, config and preferred AZ of shard zero, which also serves as the
+        // template for the merged shards' identity.
+        let shard_zero = locked
+            .tenants
+            .get(&targets[0].parent_ids[0])
+            .expect("Checked above");
+
+        Ok(ShardMergeAction::Merge(Box::new(ShardMergeParams {
+            old_shard_count,
+            new_shard_count,
+            targets,
+            policy: shard_zero.policy.clone(),
+            config: shard_zero.config.clone(),
+            shard_ident: shard_zero.shard,
+            preferred_az_id: shard_zero.preferred_az().cloned(),
+        })))
+    }
+
+    async fn do_tenant_shard_merge(
+        &self,
+        tenant_id: TenantId,
+        params: Box<ShardMergeParams>,
+    ) -> Result<(TenantShardMergeResponse, Vec<ReconcilerWaiter>), ApiError> {
+        let ShardMergeParams {
+            old_shard_count,
+            new_shard_count,
+            targets,
+            policy,
+            config,
+            shard_ident,
+            preferred_az_id,
+        } = *params;
+
+        // The pageserver merges shards that it has attached: move each upper parent onto the node where its
+        // lower parent is attached.  Secondary locations are dropped, as for splits.  The reconciliation
+        // calls in this block also implicitly cancel+barrier wrt any ongoing reconciliation.
+        let waiters = {
+            let mut locked = self.inner.write().unwrap();
+            let mut waiters = Vec::new();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+            for target in &targets {
+                for parent_id in &target.parent_ids {
+                    let Some(shard) = tenants.get_mut(parent_id) else {
+                        // Paranoia check: this shouldn't happen: we have the oplock for this tenant ID.
+                        return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                            "Shard {} not found",
+                            parent_id
+                        )));
+                    };
+
+                    shard.intent.clear_secondary(scheduler);
+                    if shard.intent.get_attached() != &Some(target.node.get_id()) {
+                        tracing::info!(
+                            "Migrating {parent_id} to {} before merging it",
+                            target.node
+                        );
+                        shard
+                            .intent
+                            .set_attached(scheduler, Some(target.node.get_id()));
+                        shard.sequence = shard.sequence.next();
+                        shard.set_preferred_node(None);
+                    }
+
+                    if let Some(waiter) =
+                        self.maybe_reconcile_shard(shard, nodes, ReconcilerPriority::High)
+                    {
+                        waiters.push(waiter);
+                    }
+                }
+            }
+            waiters
+        };
+        self.await_waiters(waiters, RECONCILE_TIMEOUT).await?;
+
+        // A merged shard is attached in the generation after the newest of its parents' generations.  Read
+        // the parents' generations now that they are attached where we want them.
+        let generations = {
+            let locked = self.inner.read().unwrap();
+            let mut generations = Vec::new();
+            for target in &targets {
+                let mut parent_generation = None;
+                for parent_id in &target.parent_ids {
+                    let Some(shard) = locked.tenants.get(parent_id) else {
+                        return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                            "Shard {} not found",
+                            parent_id
+                        )));
+                    };
+                    if shard.intent.get_attached() != &Some(target.node.get_id()) {
+                        // Paranoia check: this shouldn't happen: we have the oplock for this tenant ID.
+                        return Err(ApiError::Conflict(format!(
+                            "Shard {} unexpectedly rescheduled during merge",
+                            parent_id
+                        )));
+                    }
+                    parent_generation = std::cmp::max(parent_generation, shard.generation);
+                }
+                let Some(parent_generation) = parent_generation else {
+                    return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                        "Shards {:?} have no generation",
+                        target.parent_ids
+                    )));
+                };
+                generations.push(parent_generation.next());
+            }
+            generations
+        };
+
+        // Before creating any merged shards on the pageservers, persist them: this enables us to ensure
+        // that we will always be able to clean up if something goes wrong.
+        let mut merged_tsps = Vec::new();
+        for (target, generation) in targets.iter().zip(generations.iter().copied()) {
+            merged_tsps.push(TenantShardPersistence {
+                tenant_id: target.child_id.tenant_id.to_string(),
+                shard_number: target.child_id.shard_number.0 as i32,
+                shard_count: target.child_id.shard_count.literal() as i32,
+                shard_stripe_size: shard_ident.stripe_size.0 as i32,
+                generation: generation.into().map(|g| g as i32),
+                generation_pageserver: Some(target.node.get_id().0 as i64),
+                placement_policy: serde_json::to_string(&policy).unwrap(),
+                config: serde_json::to_string(&config).unwrap(),
+                splitting: SplitState::Merging,
+                scheduling_policy: serde_json::to_string(&ShardSchedulingPolicy::default())
+                    .unwrap(),
+                preferred_az_id: preferred_az_id.as_ref().map(|az| az.0.clone()),
+            });
+        }
+
+        if let Err(e) = self
+            .persistence
+            .begin_shard_merge(old_shard_count, tenant_id, merged_tsps)
+            .await
+        {
+            match e {
+                DatabaseError::Query(diesel::result::Error::DatabaseError(
+                    DatabaseErrorKind::UniqueViolation,
+                    _,
+                )) => {
+                    tracing::warn!("Conflicting attempt to merge {tenant_id}: {e}");
+                    return Err(ApiError::Conflict("Tenant is already merging".into()));
+                }
+                _ => return Err(ApiError::InternalServerError(e.into())),
+            }
+        }
+        fail::fail_point!("shard-merge-post-begin", |_| Err(
+            ApiError::InternalServerError(anyhow::anyhow!("failpoint"))
+        ));
+
+        // Now that we have persisted the merging state, apply it in-memory.  As for splits, this is
+        // infallible, so the in-memory state always reflects whether merging was persisted.
+        {
+            let mut locked = self.inner.write().unwrap();
+            for target in &targets {
+                for parent_id in &target.parent_ids {
+                    if let Some(parent_shard) = locked.tenants.get_mut(parent_id) {
+                        parent_shard.splitting = SplitState::Merging;
+                        // Put the observed state to None, to reflect that it is indeterminate once we
+                        // start the merge operation.
+                        parent_shard
+                            .observed
+                            .locations
+                            .insert(target.node.get_id(), ObservedStateLocation { conf: None });
+                    }
+                }
+            }
+        }
+
+        for (target, generation) in targets.iter().zip(generations.iter().copied()) {
+            let ShardMergeTarget {
+                parent_ids,
+                node,
+                child_id,
+            } = target;
+            let client = PageserverClient::new(
+                node.get_id(),
+                self.http_client.clone(),
+                node.base_url(),
+                self.config.pageserver_jwt_token.as_deref(),
+            );
+            let response = client
+                .tenant_shard_merge(
+                    parent_ids[0],
+                    TenantShardMergeRequest {
+                        new_shard_count: new_shard_count.literal(),
+                        generation: generation.into(),
+                    },
+                )
+                .await
+                .map_err(|e| {
+                    ApiError::Conflict(format!("Failed to merge into {}: {}", child_id, e))
+                })?;
+
+            fail::fail_point!("shard-merge-post-remote", |_| Err(ApiError::Conflict(
+                "failpoint".to_string()
+            )));
+
+            tracing::info!("Merged {:?} into {}", parent_ids, child_id);
+
+            if response.new_shards != [*child_id] {
+                // This should never happen: the pageserver should agree with us on how shard merges work.
+                return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                    "Merging shards {:?} resulted in unexpected IDs: {:?} (expected {})",
+                    parent_ids,
+                    response.new_shards,
+                    child_id
+                )));
+            }
+        }
+
+        pausable_failpoint!("shard-merge-pre-complete");
+
+        // Completing a merge in the database is the same as completing a split: drop the old shards and
+        // clear the marker on the new ones.
+        self.persistence
+            .complete_shard_split(tenant_id, old_shard_count, new_shard_count)
+            .await?;
+
+        // Replace all the shards we just merged with the merged shards: this phase is infallible.
+        let (response, child_locations, waiters) =
+            self.tenant_shard_merge_commit_inmem(tenant_id, new_shard_count);
+
+        // Send compute notifications for all the new shards
+        let mut failed_notifications = Vec::new();
+        for (child_id, child_ps, stripe_size) in child_locations {
+            if let Err(e) = self
+                .compute_hook
+                .notify(
+                    compute_hook::ShardUpdate {
+                        tenant_shard_id: child_id,
+                        node_id: child_ps,
+                        stripe_size,
+                        preferred_az: preferred_az_id.as_ref().map(Cow::Borrowed),
+                    },
+                    &self.cancel,
+                )
+                .await
+            {
+                tracing::warn!(
+                    "Failed to update compute of {}->{} during merge, proceeding anyway to complete merge ({e})",
+                    child_id,
+                    child_ps
+                );
+                failed_notifications.push(child_id);
+            }
+        }
+
+        // If we failed any compute notifications, make a note to retry later.
+        if !failed_notifications.is_empty() {
+            let mut locked = self.inner.write().unwrap();
+            for failed in failed_notifications {
+                if let Some(shard) = locked.tenants.get_mut(&failed) {
+                    shard.pending_compute_notification = true;
+                }
+            }
+        }
+
+        // Unlike split children, merged shards do not reference any of their parents' layers, so
+        // the parents' remote data can go now that the merge can no longer be aborted.  The
+        // pageserver already dropped the parents locally, so deleting them only deletes their
+        // remote prefixes.
+        for target in &targets {
+            for parent_id in &target.parent_ids {
+                let parent_id = *parent_id;
+                let result = target
+                    .node
+                    .with_client_retries(
+                        |client| async move { client.tenant_delete(parent_id).await },
+                        &self.http_client,
+                        &self.config.pageserver_jwt_token,
+                        1,
+                        3,
+                        RECONCILE_TIMEOUT,
+                        &self.cancel,
+                    )
+                    .await
+                    .unwrap_or(Err(mgmt_api::Error::Cancelled));
+                if let Err(e) = result {
+                    // Not fatal: the merge is complete, the parents' objects are just garbage
+                    // left for the scrubber.
+                    tracing::warn!(
+                        "Failed to delete remote data of merged shard {parent_id} via node {}: {e}",
+                        target.node
+                    );
+                }
+            }
+        }
+
+        Ok((response, waiters))
+    }
+
+    /// Infallible final stage of [`Self::tenant_shard_merge`]: update the contents
+    /// of the tenant map to replace each pair of parent shards with their merged shard.
+    fn tenant_shard_merge_commit_inmem(
+        &self,
+        tenant_id: TenantId,
+        new_shard_count: ShardCount,
+    ) -> (
+        TenantShardMergeResponse,
+        Vec<(TenantShardId, NodeId, ShardStripeSize)>,
+        Vec<ReconcilerWaiter>,
+    ) {
+        let mut response = TenantShardMergeResponse {
+            new_shards: Vec::new(),
+        };
+        let mut child_locations = Vec::new();
+        let mut waiters = Vec::new();
+
+        let mut locked = self.inner.write().unwrap();
+
+        // The lower-numbered parent of each pair: the merged shard was created where it is attached.
+        let lower_parent_ids = locked
+            .tenants
+            .range(TenantShardId::tenant_range(tenant_id))
+            .map(|(shard_id, _)| *shard_id)
+            .filter(|shard_id| {
+                shard_id.shard_count != new_shard_count
+                    && shard_id.shard_number.0 < new_shard_count.count()
+            })
+            .collect::<Vec<_>>();
+
+        let (nodes, tenants, scheduler) = locked.parts_mut();
+        let mut schedule_context = ScheduleContext::default();
+        for lower_parent_id in lower_parent_ids {
+            let child = lower_parent_id.merge(new_shard_count);
+
+            let (pageserver, policy, parent_ident, config, preferred_az) = {
+                let lower_parent = tenants
+                    .get(&lower_parent_id)
+                    .expect("It was present, we just merged it");
+                (
+                    lower_parent
+                        .intent
+                        .get_attached()
+                        .expect("Shard must have been attached"),
+                    lower_parent.policy.clone(),
+                    lower_parent.shard,
+                    lower_parent.config.clone(),
+                    lower_parent.preferred_az().cloned(),
+                )
+            };
+
+            // This is the generation that [`Self::do_tenant_shard_merge`] persisted for the merged shard.
+            let mut parent_generation = None;
+            for parent_id in child.split(lower_parent_id.shard_count) {
+                let mut old_state = tenants
+                    .remove(&parent_id)
+                    .expect("It was present, we just merged it");
+
+                // A non-merging state is impossible, because [`Self::tenant_shard_merge`] holds
+                // a TenantId lock and passes it through to [`TenantShardSplitAbort`] in case of cleanup:
+                // nothing else can clear this.
+                assert!(matches!(old_state.splitting, SplitState::Merging));
+
+                old_state.intent.clear(scheduler);
+                parent_generation = std::cmp::max(parent_generation, old_state.generation);
+            }
+            let generation = parent_generation
+                .expect("Shard must have been attached")
+                .next();
+
+            let mut child_shard = parent_ident;
+            child_shard.number = child.shard_number;
+            child_shard.count = child.shard_count;
+
+            let mut child_observed: HashMap<NodeId, ObservedStateLocation> = HashMap::new();
+            child_observed.insert(
+                pageserver,
+                ObservedStateLocation {
+                    conf: Some(attached_location_conf(
+                        generation,
+                        &child_shard,
+                        &config,
+                        &policy,
+                    )),
+                },
+            );
+
+            let mut child_state =
+                TenantShard::new(child, child_shard, policy.clone(), preferred_az.clone());
+            child_state.intent = IntentState::single(scheduler, Some(pageserver), preferred_az);
+            child_state.observed = ObservedState {
+                locations: child_observed,
+            };
+            child_state.generation = Some(generation);
+            child_state.config = config;
+
+            child_locations.push((child, pageserver, child_shard.stripe_size));
+
+            if let Err(e) = child_state.schedule(scheduler, &mut schedule_context) {
+                // Not fatal: the merged shard is already attached, we just couldn't find a secondary.
+                tracing::warn!("Failed to schedule merged shard {child}: {e}");
+            }
+            // In the background, attach secondary locations for the new shards
+            if let Some(waiter) =
+                self.maybe_reconcile_shard(&mut child_state, nodes, ReconcilerPriority::High)
+            {
+                waiters.push(waiter);
+            }
+
+            tenants.insert(child, child_state);
+            response.new_shards.push(child);
+        }
+
+        (response, child_locations, waiters)
+    }
+
+    #[instrument(skip_all, fields(tenant_id=%op.tenant_id))]
+    async fn abort_tenant_shard_merge(
+        &self,
+        op: &TenantShardSplitAbort,
+    ) -> Result<(), TenantShardSplitAbortError> {
+        // Cleaning up a merge is like cleaning up a split (see [`Self::abort_tenant_shard_split`]): the
+        // pageserver keeps the parent shards attached until the merged shard has caught up, so our work
+        // is to drop the merged shards and make sure the parents are attached.
+        let TenantShardSplitAbort {
+            tenant_id,
+            new_shard_count,
+            ..
+        } = op;
+
+        // First abort persistent state, if any exists.
+        match self
+            .persistence
+            .abort_shard_merge(*tenant_id, *new_shard_count)
+            .await?
+        {
+            AbortShardSplitStatus::Aborted => {
+                // Proceed to roll back any merged shards created on pageservers
+            }
+            AbortShardSplitStatus::Complete => {
+                // The merge completed: we must update in-memory state to reflect it.
+                self.tenant_shard_merge_commit_inmem(*tenant_id, *new_shard_count);
+                return Ok(());
+            }
+        }
+
+        // Clean up in-memory state, and accumulate the list of merged shard locations that need detaching
+        let detach_locations: Vec<(Node, TenantShardId)> = {
+            let mut detach_locations = Vec::new();
+            let mut locked = self.inner.write().unwrap();
+            let (nodes, tenants, scheduler) = locked.parts_mut();
+
+            for (tenant_shard_id, shard) in
+                tenants.range_mut(TenantShardId::tenant_range(op.tenant_id))
+            {
+                if shard.shard.count == op.new_shard_count {
+                    tracing::warn!(
+                        "During merge abort, merged shard {tenant_shard_id} found in-memory"
+                    );
+                    continue;
+                }
+
+                // The merged shard was created where the lower-numbered parent is attached
+                if tenant_shard_id.shard_number.0 < new_shard_count.count() {
+                    if let Some(node_id) = shard.intent.get_attached() {
+                        detach_locations.push((
+                            nodes
+                                .get(node_id)
+                                .expect("Intent references nonexistent node")
+                                .clone(),
+                            tenant_shard_id.merge(*new_shard_count),
+                        ));
+                    } else {
+                        tracing::warn!(
+                            "During merge abort, shard {tenant_shard_id} has no attached location"
+                        );
+                    }
+                }
+
+                self.abort_restore_parent(*tenant_shard_id, shard, nodes, scheduler);
+            }
+
+            // We don't expect any new_shard_count shards to exist here, but drop them just in case
+            tenants.retain(|_id, s| s.shard.count != *new_shard_count);
+
+            detach_locations
+        };
+
+        self.abort_detach_children(detach_locations).await?;
+
+        tracing::info!("Successfully aborted merge");
+        Ok(())
+    }
+
     /// A graceful migration: update the preferred node and let optimisation handle the migration
     /// in the background (may take a long time as it will fully warm up a location before cutting over)
     ///
@@ -6905,6 +8007,7 @@ pub(crate) async fn start_node_drain(
                 self.inner.write().unwrap().ongoing_operation = Some(OperationHandler {
                     operation: Operation::Drain(Drain { node_id }),
                     cancel: cancel.clone(),
+                    progress: None,
                 });
 
                 let span = tracing::info_span!(parent: None, "drain_node", %node_id);
@@ -7036,6 +8139,7 @@ pub(crate) async fn start_node_fill(self: &Arc<Self>, node_id: NodeId) -> Result
                 self.inner.write().unwrap().ongoing_operation = Some(OperationHandler {
                     operation: Operation::Fill(Fill { node_id }),
                     cancel: cancel.clone(),
+                    progress: None,
                 });
 
                 let span = tracing::info_span!(parent: None, "fill_node", %node_id);
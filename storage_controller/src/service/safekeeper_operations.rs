@@ -0,0 +1,637 @@
+//! Drain and fill of safekeepers, the counterpart of pageserver node drains and fills.
+//!
+//! Timelines are moved one by one with [`Service::tenant_timeline_safekeeper_migrate`],
+//! which switches their running computes to the new safekeepers through the control
+//! plane before the drained safekeeper stops serving them.
+//! The operation itself is persisted as the scheduling policy of the safekeeper
+//! (`Draining` or `Filling`), so that one interrupted by a storage controller
+//! restart is resumed on startup.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use http_utils::error::ApiError;
+use pageserver_api::controller_api::{
+    SafekeeperOperationDescribe, SafekeeperOperationKind, SkSchedulingPolicy,
+};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use utils::id::{NodeId, TenantTimelineId};
+
+use super::Service;
+use super::safekeeper_rebalancer::{
+    Candidate, pick_target, safekeeper_azs, safekeeper_candidates, timeline_ttid,
+};
+use super::safekeeper_service::to_node_ids;
+use crate::background_node_operations::{
+    Operation, OperationError, OperationHandler, SafekeeperDrain, SafekeeperFill,
+};
+use crate::heartbeater::SafekeeperState;
+use crate::persistence::DatabaseError;
+
+/// A timeline to move along with the safekeeper set to move it to.
+type TimelineMove = (TenantTimelineId, Vec<NodeId>);
+
+/// How many timelines of a safekeeper to load at once when planning a drain or fill.
+const PLAN_BATCH_SIZE: i64 = 128;
+
+fn replace_member(sk_set: &[NodeId], from: NodeId, to: NodeId) -> Vec<NodeId> {
+    sk_set
+        .iter()
+        .map(|id| if *id == from { to } else { *id })
+        .collect()
+}
+
+impl Service {
+    pub(crate) async fn start_safekeeper_drain(
+        self: &Arc<Self>,
+        node_id: NodeId,
+    ) -> Result<(), ApiError> {
+        self.start_safekeeper_operation(node_id, SafekeeperOperationKind::Drain)
+            .await
+    }
+
+    pub(crate) fn cancel_safekeeper_drain(&self, node_id: NodeId) -> Result<(), ApiError> {
+        self.cancel_safekeeper_operation(node_id, SafekeeperOperationKind::Drain)
+    }
+
+    pub(crate) async fn start_safekeeper_fill(
+        self: &Arc<Self>,
+        node_id: NodeId,
+    ) -> Result<(), ApiError> {
+        self.start_safekeeper_operation(node_id, SafekeeperOperationKind::Fill)
+            .await
+    }
+
+    pub(crate) fn cancel_safekeeper_fill(&self, node_id: NodeId) -> Result<(), ApiError> {
+        self.cancel_safekeeper_operation(node_id, SafekeeperOperationKind::Fill)
+    }
+
+    /// Progress of the drain or fill ongoing for the given safekeeper.
+    pub(crate) fn safekeeper_operation_describe(
+        &self,
+        node_id: NodeId,
+    ) -> Result<SafekeeperOperationDescribe, ApiError> {
+        let locked = self.inner.read().unwrap();
+        locked
+            .ongoing_operation
+            .as_ref()
+            .and_then(|op| op.progress.as_ref())
+            .filter(|progress| progress.node_id == node_id)
+            .cloned()
+            .ok_or_else(|| {
+                ApiError::NotFound(
+                    anyhow::anyhow!("Safekeeper {node_id} has no drain or fill in progress").into(),
+                )
+            })
+    }
+
+    /// Resume the drains and fills which were interrupted by a restart, as recorded
+    /// in the safekeepers' scheduling policies.
+    pub(super) fn resume_safekeeper_operations(self: &Arc<Self>) {
+        let interrupted = {
+            let locked = self.inner.read().unwrap();
+            locked
+                .safekeepers
+                .values()
+                .filter_map(|sk| match sk.scheduling_policy() {
+                    SkSchedulingPolicy::Draining => {
+                        Some((sk.get_id(), SafekeeperOperationKind::Drain))
+                    }
+                    SkSchedulingPolicy::Filling => {
+                        Some((sk.get_id(), SafekeeperOperationKind::Fill))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        for (node_id, kind) in interrupted {
+            tracing::info!("Resuming {kind:?} of safekeeper {node_id}");
+            if let Err(err) = self.spawn_safekeeper_operation(node_id, kind) {
+                tracing::warn!("Couldn't resume {kind:?} of safekeeper {node_id}: {err}");
+            }
+        }
+    }
+
+    async fn start_safekeeper_operation(
+        self: &Arc<Self>,
+        node_id: NodeId,
+        kind: SafekeeperOperationKind,
+    ) -> Result<(), ApiError> {
+        let (ongoing_op, sk_available, sk_policy, active_sks_count) = {
+            let locked = self.inner.read().unwrap();
+            let sk = locked.safekeepers.get(&node_id).ok_or(ApiError::NotFound(
+                anyhow::anyhow!("Safekeeper {} not registered", node_id).into(),
+            ))?;
+            let active_sks_count = locked
+                .safekeepers
+                .values()
+                .filter(|sk| sk.get_id() != node_id)
+                .filter(|sk| sk.scheduling_policy() == SkSchedulingPolicy::Active)
+                .count();
+
+            (
+                locked
+                    .ongoing_operation
+                    .as_ref()
+                    .map(|ongoing| ongoing.operation),
+                matches!(sk.availability(), SafekeeperState::Available { .. }),
+                sk.scheduling_policy(),
+                active_sks_count,
+            )
+        };
+
+        if let Some(ongoing) = ongoing_op {
+            return Err(ApiError::PreconditionFailed(
+                format!("Background operation already ongoing for node: {}", ongoing).into(),
+            ));
+        }
+
+        if !sk_available {
+            return Err(ApiError::ResourceUnavailable(
+                format!("Safekeeper {node_id} is currently unavailable").into(),
+            ));
+        }
+
+        let policy = match (kind, sk_policy) {
+            (SafekeeperOperationKind::Drain, SkSchedulingPolicy::Active) => {
+                if active_sks_count == 0 {
+                    return Err(ApiError::PreconditionFailed(
+                        "No other active safekeepers to drain to".into(),
+                    ));
+                }
+                SkSchedulingPolicy::Draining
+            }
+            // A drained safekeeper is left paused, so it is the usual starting point of a fill.
+            (
+                SafekeeperOperationKind::Fill,
+                SkSchedulingPolicy::Active | SkSchedulingPolicy::Pause,
+            ) => SkSchedulingPolicy::Filling,
+            (SafekeeperOperationKind::Drain, SkSchedulingPolicy::Draining) => {
+                return Err(ApiError::Conflict(format!(
+                    "Safekeeper {node_id} has drain in progress"
+                )));
+            }
+            (SafekeeperOperationKind::Fill, SkSchedulingPolicy::Filling) => {
+                return Err(ApiError::Conflict(format!(
+                    "Safekeeper {node_id} has fill in progress"
+                )));
+            }
+            (kind, policy) => {
+                return Err(ApiError::PreconditionFailed(
+                    format!("Safekeeper {node_id} cannot {kind:?} due to {policy:?} policy").into(),
+                ));
+            }
+        };
+
+        self.set_safekeeper_scheduling_policy(node_id.0 as i64, policy)
+            .await?;
+        self.spawn_safekeeper_operation(node_id, kind)
+    }
+
+    fn spawn_safekeeper_operation(
+        self: &Arc<Self>,
+        node_id: NodeId,
+        kind: SafekeeperOperationKind,
+    ) -> Result<(), ApiError> {
+        let cancel = self.cancel.child_token();
+        let gate_guard = self.gate.enter().map_err(|_| ApiError::ShuttingDown)?;
+
+        {
+            let mut locked = self.inner.write().unwrap();
+            if let Some(ongoing) = locked.ongoing_operation.as_ref() {
+                return Err(ApiError::PreconditionFailed(
+                    format!(
+                        "Background operation already ongoing for node: {}",
+                        ongoing.operation
+                    )
+                    .into(),
+                ));
+            }
+            locked.ongoing_operation = Some(OperationHandler {
+                operation: match kind {
+                    SafekeeperOperationKind::Drain => {
+                        Operation::SafekeeperDrain(SafekeeperDrain { node_id })
+                    }
+                    SafekeeperOperationKind::Fill => {
+                        Operation::SafekeeperFill(SafekeeperFill { node_id })
+                    }
+                },
+                cancel: cancel.clone(),
+                progress: Some(SafekeeperOperationDescribe {
+                    node_id,
+                    kind,
+                    started_at: chrono::Utc::now(),
+                    planned: 0,
+                    migrated: 0,
+                    failed: 0,
+                }),
+            });
+        }
+
+        let span = tracing::info_span!(parent: None, "safekeeper_operation", %node_id, ?kind);
+
+        tokio::task::spawn(
+            {
+                let service = self.clone();
+                async move {
+                    let _gate_guard = gate_guard;
+
+                    scopeguard::defer! {
+                        let prev = service.inner.write().unwrap().ongoing_operation.take();
+
+                        match prev.map(|h| h.operation) {
+                            Some(Operation::SafekeeperDrain(SafekeeperDrain { node_id: removed }))
+                            | Some(Operation::SafekeeperFill(SafekeeperFill { node_id: removed })) => {
+                                assert_eq!(removed, node_id, "We always take the same operation");
+                            }
+                            _ => panic!("We always remove the same operation"),
+                        }
+                    }
+
+                    tracing::info!("{kind:?} background operation starting");
+                    let res = match kind {
+                        SafekeeperOperationKind::Drain => {
+                            service.drain_safekeeper(node_id, cancel).await
+                        }
+                        SafekeeperOperationKind::Fill => {
+                            service.fill_safekeeper(node_id, cancel).await
+                        }
+                    };
+                    match res {
+                        Ok(()) => {
+                            tracing::info!("{kind:?} background operation completed successfully");
+                        }
+                        Err(OperationError::Cancelled) => {
+                            tracing::info!("{kind:?} background operation was cancelled");
+                        }
+                        Err(err) => {
+                            tracing::error!("{kind:?} background operation encountered: {err}")
+                        }
+                    }
+                }
+            }
+            .instrument(span),
+        );
+
+        Ok(())
+    }
+
+    fn cancel_safekeeper_operation(
+        &self,
+        node_id: NodeId,
+        kind: SafekeeperOperationKind,
+    ) -> Result<(), ApiError> {
+        if let Some(op_handler) = self.inner.read().unwrap().ongoing_operation.as_ref() {
+            let ongoing = match op_handler.operation {
+                Operation::SafekeeperDrain(drain) => {
+                    Some((drain.node_id, SafekeeperOperationKind::Drain))
+                }
+                Operation::SafekeeperFill(fill) => {
+                    Some((fill.node_id, SafekeeperOperationKind::Fill))
+                }
+                Operation::Drain(_) | Operation::Fill(_) => None,
+            };
+            if ongoing == Some((node_id, kind)) {
+                op_handler.cancel.cancel();
+                return Ok(());
+            }
+        }
+
+        Err(ApiError::PreconditionFailed(
+            format!("Safekeeper {node_id} has no {kind:?} in progress").into(),
+        ))
+    }
+
+    /// Move all the timelines off the safekeeper, then pause it so that it doesn't
+    /// get new ones until it is filled again.
+    async fn drain_safekeeper(
+        self: &Arc<Self>,
+        node_id: NodeId,
+        cancel: CancellationToken,
+    ) -> Result<(), OperationError> {
+        // Timelines created or moved here concurrently with the drain are picked up
+        // by the next round. Ones we already tried to move aren't retried.
+        let mut attempted = HashSet::new();
+        loop {
+            let plan = self.safekeeper_drain_plan(node_id, &mut attempted).await?;
+            if plan.is_empty() {
+                break;
+            }
+            self.safekeeper_operation_migrate(node_id, SkSchedulingPolicy::Draining, plan, &cancel)
+                .await?;
+        }
+
+        // Like for pageservers, failing to move some timelines doesn't fail the drain:
+        // they are accounted for in the progress of the operation.
+        if let Err(err) = self
+            .set_safekeeper_scheduling_policy(node_id.0 as i64, SkSchedulingPolicy::Pause)
+            .await
+        {
+            return Err(OperationError::FinalizeError(
+                format!(
+                    "Failed to finalise drain of safekeeper {node_id} by setting scheduling policy to Pause: {err}"
+                )
+                .into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn safekeeper_drain_plan(
+        &self,
+        node_id: NodeId,
+        attempted: &mut HashSet<TenantTimelineId>,
+    ) -> Result<Vec<TimelineMove>, OperationError> {
+        let (mut candidates, azs) = {
+            let locked = self.inner.read().unwrap();
+            (
+                safekeeper_candidates(&locked.safekeepers, &locked.safekeeper_migrations),
+                safekeeper_azs(&locked.safekeepers),
+            )
+        };
+
+        let mut plan = Vec::new();
+        let mut unplaced = 0;
+        let mut cursor = None;
+        loop {
+            let timelines = self
+                .persistence
+                .list_timelines_on_safekeeper(node_id, cursor, PLAN_BATCH_SIZE)
+                .await?;
+            let Some(last) = timelines.last() else {
+                break;
+            };
+            cursor =
+                Some(timeline_ttid(last).map_err(|e| DatabaseError::Logical(format!("{e:#}")))?);
+            for timeline in timelines {
+                let Ok(ttid) = timeline_ttid(&timeline) else {
+                    continue;
+                };
+                if !attempted.insert(ttid) {
+                    continue;
+                }
+                let sk_set = to_node_ids(&timeline.sk_set);
+                let Some(target) = pick_target(&candidates, &azs, &sk_set, node_id, None) else {
+                    tracing::warn!(%ttid, "No safekeeper to move timeline off {node_id} to");
+                    unplaced += 1;
+                    continue;
+                };
+                if let Some(c) = candidates.iter_mut().find(|c| c.id == target) {
+                    c.load += 1;
+                }
+                plan.push((ttid, replace_member(&sk_set, node_id, target)));
+            }
+        }
+
+        self.update_safekeeper_operation_progress(|progress| {
+            progress.planned += plan.len() + unplaced;
+            progress.failed += unplaced;
+        });
+        Ok(plan)
+    }
+
+    /// Move timelines from the most loaded safekeepers to this one until it hosts
+    /// the average number of timelines, then activate it.
+    async fn fill_safekeeper(
+        self: &Arc<Self>,
+        node_id: NodeId,
+        cancel: CancellationToken,
+    ) -> Result<(), OperationError> {
+        let plan = self.safekeeper_fill_plan(node_id).await?;
+        self.safekeeper_operation_migrate(node_id, SkSchedulingPolicy::Filling, plan, &cancel)
+            .await?;
+
+        if let Err(err) = self
+            .set_safekeeper_scheduling_policy(node_id.0 as i64, SkSchedulingPolicy::Active)
+            .await
+        {
+            return Err(OperationError::FinalizeError(
+                format!(
+                    "Failed to finalise fill of safekeeper {node_id} by setting scheduling policy to Active: {err}"
+                )
+                .into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn safekeeper_fill_plan(
+        &self,
+        node_id: NodeId,
+    ) -> Result<Vec<TimelineMove>, OperationError> {
+        let (candidates, azs) = {
+            let locked = self.inner.read().unwrap();
+            (
+                safekeeper_candidates(&locked.safekeepers, &locked.safekeeper_migrations),
+                safekeeper_azs(&locked.safekeepers),
+            )
+        };
+        let Some(node) = candidates.iter().find(|c| c.id == node_id) else {
+            tracing::info!("Safekeeper {node_id} is unavailable, nothing to fill");
+            return Ok(Vec::new());
+        };
+        let mean = candidates.iter().map(|c| c.load).sum::<u64>() / candidates.len() as u64;
+        let mut wanted = mean.saturating_sub(node.load);
+        // The load of the filled safekeeper doesn't matter for picking it, only its AZ does.
+        let target = [Candidate {
+            id: node_id,
+            az: node.az.clone(),
+            load: 0,
+        }];
+
+        let mut donors = candidates
+            .iter()
+            .filter(|c| c.id != node_id && c.load > mean)
+            .map(|c| (c.load, c.id))
+            .collect::<Vec<_>>();
+        donors.sort_by(|a, b| b.cmp(a));
+
+        let mut plan = Vec::new();
+        for (load, donor) in donors {
+            if wanted == 0 {
+                break;
+            }
+            let mut excess = load - mean;
+            // Only as many timelines of the donor are loaded as it takes to find enough
+            // which can move.
+            let mut cursor = None;
+            while wanted > 0 && excess > 0 {
+                let timelines = self
+                    .persistence
+                    .list_timelines_on_safekeeper(donor, cursor, PLAN_BATCH_SIZE)
+                    .await?;
+                let Some(last) = timelines.last() else {
+                    break;
+                };
+                cursor = Some(
+                    timeline_ttid(last).map_err(|e| DatabaseError::Logical(format!("{e:#}")))?,
+                );
+                for timeline in timelines {
+                    if wanted == 0 || excess == 0 {
+                        break;
+                    }
+                    let Ok(ttid) = timeline_ttid(&timeline) else {
+                        continue;
+                    };
+                    let sk_set = to_node_ids(&timeline.sk_set);
+                    if pick_target(&target, &azs, &sk_set, donor, None).is_none() {
+                        continue;
+                    }
+                    plan.push((ttid, replace_member(&sk_set, donor, node_id)));
+                    wanted -= 1;
+                    excess -= 1;
+                }
+            }
+        }
+
+        self.update_safekeeper_operation_progress(|progress| {
+            progress.planned += plan.len();
+        });
+        Ok(plan)
+    }
+
+    /// Carry out the moves of a drain or fill of `node_id`, keeping up to
+    /// `safekeeper_migration_concurrency` migrations in flight.
+    async fn safekeeper_operation_migrate(
+        self: &Arc<Self>,
+        node_id: NodeId,
+        policy: SkSchedulingPolicy,
+        plan: Vec<TimelineMove>,
+        cancel: &CancellationToken,
+    ) -> Result<(), OperationError> {
+        let concurrency = std::cmp::max(self.config.safekeeper_migration_concurrency, 1);
+        let mut plan = plan.into_iter();
+        let mut migrations = JoinSet::new();
+
+        loop {
+            if cancel.is_cancelled() {
+                // Migrations can't be interrupted without leaving their timelines
+                // in a joint configuration, so let the ongoing ones finish.
+                while let Some(res) = migrations.join_next().await {
+                    self.record_safekeeper_operation_migration(res);
+                }
+                return self.cancel_safekeeper_operation_finalize(node_id).await;
+            }
+
+            self.validate_safekeeper_state(node_id, policy)?;
+
+            while migrations.len() < concurrency {
+                let Some((ttid, new_sk_set)) = plan.next() else {
+                    break;
+                };
+                let service = self.clone();
+                let TenantTimelineId {
+                    tenant_id,
+                    timeline_id,
+                } = ttid;
+                migrations.spawn(
+                    async move {
+                        let res = service
+                            .tenant_timeline_safekeeper_migrate(tenant_id, timeline_id, new_sk_set)
+                            .await;
+                        (ttid, res)
+                    }
+                    .instrument(tracing::info_span!(
+                        "safekeeper_migration",
+                        %tenant_id,
+                        %timeline_id
+                    )),
+                );
+            }
+
+            if migrations.is_empty() {
+                return Ok(());
+            }
+
+            tokio::select! {
+                Some(res) = migrations.join_next() => self.record_safekeeper_operation_migration(res),
+                _ = cancel.cancelled() => {}
+            }
+        }
+    }
+
+    fn record_safekeeper_operation_migration(
+        &self,
+        res: Result<(TenantTimelineId, Result<(), ApiError>), tokio::task::JoinError>,
+    ) {
+        let migrated = match res {
+            Ok((_, Ok(()))) => true,
+            Ok((ttid, Err(err))) => {
+                tracing::warn!(%ttid, "Failed to move timeline: {err}");
+                false
+            }
+            Err(err) => {
+                tracing::warn!("Timeline move task failed: {err}");
+                false
+            }
+        };
+        self.update_safekeeper_operation_progress(|progress| {
+            if migrated {
+                progress.migrated += 1;
+            } else {
+                progress.failed += 1;
+            }
+        });
+    }
+
+    /// On shutdown the policy is kept, for the operation to be resumed on startup.
+    async fn cancel_safekeeper_operation_finalize(
+        &self,
+        node_id: NodeId,
+    ) -> Result<(), OperationError> {
+        if self.cancel.is_cancelled() {
+            return Err(OperationError::Cancelled);
+        }
+        match self
+            .set_safekeeper_scheduling_policy(node_id.0 as i64, SkSchedulingPolicy::Active)
+            .await
+        {
+            Ok(()) => Err(OperationError::Cancelled),
+            Err(err) => Err(OperationError::FinalizeError(
+                format!(
+                    "Failed to finalise cancel of safekeeper {node_id} operation by setting scheduling policy to Active: {err}"
+                )
+                .into(),
+            )),
+        }
+    }
+
+    fn validate_safekeeper_state(
+        &self,
+        node_id: NodeId,
+        policy: SkSchedulingPolicy,
+    ) -> Result<(), OperationError> {
+        let locked = self.inner.read().unwrap();
+        let sk = locked.safekeepers.get(&node_id).ok_or_else(|| {
+            OperationError::NodeStateChanged(format!("safekeeper {node_id} was removed").into())
+        })?;
+        if sk.scheduling_policy() != policy {
+            return Err(OperationError::NodeStateChanged(
+                format!(
+                    "safekeeper {node_id} scheduling policy changed to {:?}",
+                    sk.scheduling_policy()
+                )
+                .into(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn update_safekeeper_operation_progress(
+        &self,
+        update: impl FnOnce(&mut SafekeeperOperationDescribe),
+    ) {
+        let mut locked = self.inner.write().unwrap();
+        if let Some(progress) = locked
+            .ongoing_operation
+            .as_mut()
+            .and_then(|op| op.progress.as_mut())
+        {
+            update(progress);
+        }
+    }
+}
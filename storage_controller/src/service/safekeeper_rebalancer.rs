@@ -0,0 +1,389 @@
+//! Background rebalancing of timelines between safekeepers.
+//!
+//! Periodically moves timelines off safekeepers which are decomissioned or host
+//! notably more timelines than the average, using
+//! [`Service::tenant_timeline_safekeeper_migrate`]. Timelines whose migration got
+//! interrupted (e.g. by a storage controller restart) are resumed first.
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pageserver_api::controller_api::{SafekeeperMigrationDescribe, SkSchedulingPolicy};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::Instrument;
+use utils::id::{NodeId, TenantId, TenantTimelineId, TimelineId};
+
+use super::Service;
+use crate::heartbeater::SafekeeperState;
+use crate::persistence::TimelinePersistence;
+use crate::safekeeper::Safekeeper;
+
+/// A safekeeper is overloaded if it hosts more than this ratio above the
+/// average number of timelines per active safekeeper...
+const OVERLOAD_RATIO: f64 = 0.2;
+
+/// ...and at least this many timelines above the average. This keeps small
+/// deployments from shuffling timelines around for no real gain.
+const OVERLOAD_MIN_EXCESS: u64 = 10;
+
+/// How many timelines of a source safekeeper to consider at once.
+const REBALANCE_BATCH_SIZE: i64 = 128;
+
+/// A safekeeper which timelines can be moved to.
+#[derive(Debug)]
+pub(super) struct Candidate {
+    pub(super) id: NodeId,
+    pub(super) az: String,
+    pub(super) load: u64,
+}
+
+/// Availability zones of all the safekeepers, members of a timeline's set
+/// included, which aren't necessarily candidates.
+pub(super) fn safekeeper_azs(safekeepers: &HashMap<NodeId, Safekeeper>) -> HashMap<NodeId, String> {
+    safekeepers
+        .iter()
+        .map(|(id, sk)| (*id, sk.skp.availability_zone_id.clone()))
+        .collect()
+}
+
+/// Only active (or filling) and available safekeepers take timelines. Account
+/// for the ongoing migrations, which the reported utilization doesn't reflect yet.
+pub(super) fn safekeeper_candidates(
+    safekeepers: &HashMap<NodeId, Safekeeper>,
+    ongoing: &HashMap<TenantTimelineId, SafekeeperMigrationDescribe>,
+) -> Vec<Candidate> {
+    let mut candidates = safekeepers
+        .values()
+        .filter(|sk| {
+            matches!(
+                sk.scheduling_policy(),
+                SkSchedulingPolicy::Active | SkSchedulingPolicy::Filling
+            )
+        })
+        .filter_map(|sk| match sk.availability() {
+            SafekeeperState::Available { utilization, .. } => Some(Candidate {
+                id: sk.get_id(),
+                az: sk.skp.availability_zone_id.clone(),
+                load: utilization.timeline_count,
+            }),
+            SafekeeperState::Offline => None,
+        })
+        .collect::<Vec<_>>();
+    for migration in ongoing.values() {
+        for c in candidates.iter_mut() {
+            if migration.to.contains(&c.id) && !migration.from.contains(&c.id) {
+                c.load += 1;
+            } else if migration.from.contains(&c.id) && !migration.to.contains(&c.id) {
+                c.load = c.load.saturating_sub(1);
+            }
+        }
+    }
+    candidates
+}
+
+/// Pick the least loaded candidate to take over a timeline hosted on `sk_set` from
+/// `source`: it must not host the timeline already and must be in an AZ different
+/// from the members which stay. With `max_load`, the target must remain below it
+/// after taking the timeline, so that moving it actually improves the balance.
+pub(super) fn pick_target(
+    candidates: &[Candidate],
+    azs: &HashMap<NodeId, String>,
+    sk_set: &[NodeId],
+    source: NodeId,
+    max_load: Option<u64>,
+) -> Option<NodeId> {
+    let member_azs = sk_set
+        .iter()
+        .filter(|sk_id| **sk_id != source)
+        .filter_map(|sk_id| azs.get(sk_id))
+        .collect::<HashSet<_>>();
+    candidates
+        .iter()
+        .filter(|c| !sk_set.contains(&c.id))
+        .filter(|c| !member_azs.contains(&c.az))
+        .filter(|c| max_load.is_none_or(|max_load| c.load + 1 < max_load))
+        .min_by_key(|c| (c.load, c.id))
+        .map(|c| c.id)
+}
+
+/// The description of a migration which has been scheduled, until it shows up in the
+/// service's state.
+fn scheduled_migration(
+    ttid: TenantTimelineId,
+    from: Vec<NodeId>,
+    to: Vec<NodeId>,
+) -> SafekeeperMigrationDescribe {
+    SafekeeperMigrationDescribe {
+        tenant_id: ttid.tenant_id,
+        timeline_id: ttid.timeline_id,
+        from,
+        to,
+        started_at: chrono::Utc::now(),
+    }
+}
+
+pub(super) fn timeline_ttid(timeline: &TimelinePersistence) -> anyhow::Result<TenantTimelineId> {
+    Ok(TenantTimelineId::new(
+        TenantId::from_str(&timeline.tenant_id)?,
+        TimelineId::from_str(&timeline.timeline_id)?,
+    ))
+}
+
+impl Service {
+    /// Rebalance timelines between safekeepers every `interval`.
+    pub(super) async fn safekeeper_rebalance_loop(self: &Arc<Self>, interval: Duration) {
+        let concurrency = Arc::new(Semaphore::new(self.config.safekeeper_migration_concurrency));
+        let mut interval = tokio::time::interval(interval);
+        while !self.reconcilers_cancel.is_cancelled() {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = self.reconcilers_cancel.cancelled() => return,
+            }
+            if let Err(e) = self.safekeeper_rebalance_iteration(&concurrency).await {
+                tracing::warn!("safekeeper rebalancing failed: {e}");
+            }
+        }
+    }
+
+    async fn safekeeper_rebalance_iteration(
+        self: &Arc<Self>,
+        concurrency: &Arc<Semaphore>,
+    ) -> anyhow::Result<()> {
+        if concurrency.available_permits() == 0 {
+            return Ok(());
+        }
+
+        // Migrations scheduled below are added to `ongoing`, so that a timeline listed twice,
+        // e.g. on two sources, is only moved once, and its target counts as loaded by it.
+        let (safekeepers, mut ongoing) = {
+            let locked = self.inner.read().unwrap();
+            (
+                locked.safekeepers.clone(),
+                locked.safekeeper_migrations.clone(),
+            )
+        };
+
+        // Resume migrations which have been interrupted first.
+        for timeline in self.persistence.list_timelines_in_migration().await? {
+            let Some(new_sk_set) = timeline.new_sk_set.as_ref() else {
+                continue;
+            };
+            let ttid = timeline_ttid(&timeline)?;
+            if ongoing.contains_key(&ttid) {
+                continue;
+            }
+            let new_sk_set = new_sk_set
+                .iter()
+                .map(|id| NodeId(*id as u64))
+                .collect::<Vec<_>>();
+            let Ok(permit) = concurrency.clone().try_acquire_owned() else {
+                return Ok(());
+            };
+            let sk_set = timeline
+                .sk_set
+                .iter()
+                .map(|id| NodeId(*id as u64))
+                .collect();
+            ongoing.insert(ttid, scheduled_migration(ttid, sk_set, new_sk_set.clone()));
+            self.spawn_safekeeper_migration(permit, ttid, new_sk_set);
+        }
+
+        let azs = safekeeper_azs(&safekeepers);
+        let mut candidates = safekeeper_candidates(&safekeepers, &ongoing);
+
+        // Drain decomissioned safekeepers first, then the overloaded ones.
+        let mut sources = safekeepers
+            .values()
+            .filter(|sk| sk.scheduling_policy() == SkSchedulingPolicy::Decomissioned)
+            .map(|sk| (sk.get_id(), None))
+            .collect::<Vec<_>>();
+        if !candidates.is_empty() {
+            let mean = candidates.iter().map(|c| c.load).sum::<u64>() / candidates.len() as u64;
+            let threshold = std::cmp::max(
+                (mean as f64 * (1.0 + OVERLOAD_RATIO)) as u64,
+                mean + OVERLOAD_MIN_EXCESS,
+            );
+            let mut overloaded = candidates
+                .iter()
+                .filter(|c| c.load > threshold)
+                .map(|c| (c.load, c.id))
+                .collect::<Vec<_>>();
+            overloaded.sort_by(|a, b| b.cmp(a));
+            sources.extend(
+                overloaded
+                    .into_iter()
+                    .map(|(load, id)| (id, Some(load - mean))),
+            );
+        }
+
+        for (source, excess) in sources {
+            let mut limit = std::cmp::min(
+                excess.unwrap_or(u64::MAX),
+                concurrency.available_permits() as u64,
+            );
+            // Go through the timelines of the source until enough of them are moved: some
+            // of them may not fit anywhere, e.g. because of their AZs.
+            let mut cursor = None;
+            while limit > 0 {
+                let timelines = self
+                    .persistence
+                    .list_timelines_on_safekeeper(source, cursor, REBALANCE_BATCH_SIZE)
+                    .await?;
+                let Some(last) = timelines.last() else {
+                    break;
+                };
+                cursor = Some(timeline_ttid(last)?);
+                for timeline in timelines {
+                    if limit == 0 {
+                        break;
+                    }
+                    let ttid = timeline_ttid(&timeline)?;
+                    if ongoing.contains_key(&ttid) {
+                        continue;
+                    }
+                    let sk_set = timeline
+                        .sk_set
+                        .iter()
+                        .map(|id| NodeId(*id as u64))
+                        .collect::<Vec<_>>();
+                    let max_load = excess
+                        .and_then(|_| candidates.iter().find(|c| c.id == source).map(|c| c.load));
+                    let Some(target) = pick_target(&candidates, &azs, &sk_set, source, max_load)
+                    else {
+                        tracing::info!(
+                            tenant_id = timeline.tenant_id,
+                            timeline_id = timeline.timeline_id,
+                            "no safekeeper to move timeline off {source} to"
+                        );
+                        continue;
+                    };
+                    let Ok(permit) = concurrency.clone().try_acquire_owned() else {
+                        return Ok(());
+                    };
+                    let new_sk_set = sk_set
+                        .iter()
+                        .map(|id| if *id == source { target } else { *id })
+                        .collect::<Vec<_>>();
+                    tracing::info!(
+                        tenant_id = timeline.tenant_id,
+                        timeline_id = timeline.timeline_id,
+                        "moving timeline from safekeeper {source} to {target}"
+                    );
+                    ongoing.insert(ttid, scheduled_migration(ttid, sk_set, new_sk_set.clone()));
+                    self.spawn_safekeeper_migration(permit, ttid, new_sk_set);
+                    limit -= 1;
+                    for c in candidates.iter_mut() {
+                        if c.id == target {
+                            c.load += 1;
+                        } else if c.id == source {
+                            c.load = c.load.saturating_sub(1);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn spawn_safekeeper_migration(
+        self: &Arc<Self>,
+        permit: OwnedSemaphorePermit,
+        ttid: TenantTimelineId,
+        new_sk_set: Vec<NodeId>,
+    ) {
+        let TenantTimelineId {
+            tenant_id,
+            timeline_id,
+        } = ttid;
+        let this = self.clone();
+        tokio::task::spawn(
+            async move {
+                let _permit = permit;
+                let Ok(_gate) = this.gate.enter() else {
+                    return;
+                };
+                if let Err(e) = this
+                    .tenant_timeline_safekeeper_migrate(tenant_id, timeline_id, new_sk_set)
+                    .await
+                {
+                    tracing::warn!("safekeeper migration failed: {e}");
+                }
+            }
+            .instrument(tracing::info_span!(
+                "safekeeper_migration",
+                %tenant_id,
+                %timeline_id
+            )),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(loads: &[(u64, &str, u64)]) -> (Vec<Candidate>, HashMap<NodeId, String>) {
+        let candidates = loads
+            .iter()
+            .map(|(id, az, load)| Candidate {
+                id: NodeId(*id),
+                az: az.to_string(),
+                load: *load,
+            })
+            .collect::<Vec<_>>();
+        let azs = candidates.iter().map(|c| (c.id, c.az.clone())).collect();
+        (candidates, azs)
+    }
+
+    #[test]
+    fn pick_target_respects_azs() {
+        let (candidates, azs) = candidates(&[
+            (1, "az-a", 10),
+            (2, "az-b", 10),
+            (3, "az-c", 10),
+            (4, "az-a", 1),
+            (5, "az-d", 5),
+        ]);
+        let sk_set = [NodeId(1), NodeId(2), NodeId(3)];
+
+        // Moving off 3, 4 is the least loaded but shares the AZ with 1.
+        assert_eq!(
+            pick_target(&candidates, &azs, &sk_set, NodeId(3), None),
+            Some(NodeId(5))
+        );
+        // Moving off 1, its own AZ is fine.
+        assert_eq!(
+            pick_target(&candidates, &azs, &sk_set, NodeId(1), None),
+            Some(NodeId(4))
+        );
+    }
+
+    #[test]
+    fn pick_target_improves_balance() {
+        let (candidates, azs) = candidates(&[
+            (1, "az-a", 10),
+            (2, "az-b", 10),
+            (3, "az-c", 10),
+            (4, "az-d", 9),
+        ]);
+        let sk_set = [NodeId(1), NodeId(2), NodeId(3)];
+
+        // Moving wouldn't help: 4 would end up as loaded as 3 is now.
+        assert_eq!(
+            pick_target(&candidates, &azs, &sk_set, NodeId(3), Some(10)),
+            None
+        );
+        assert_eq!(
+            pick_target(&candidates, &azs, &sk_set, NodeId(3), Some(11)),
+            Some(NodeId(4))
+        );
+        // Draining doesn't care about the balance.
+        assert_eq!(
+            pick_target(&candidates, &azs, &sk_set, NodeId(3), None),
+            Some(NodeId(4))
+        );
+    }
+}
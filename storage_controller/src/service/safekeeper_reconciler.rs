@@ -1,6 +1,7 @@
 use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
 
 use clashmap::{ClashMap, Entry};
+use http_utils::error::ApiError;
 use safekeeper_api::models::PullTimelineRequest;
 use safekeeper_client::mgmt_api;
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
@@ -292,6 +293,13 @@ async fn reconcile_one(&self, req: ScheduleRequest, req_cancel: CancellationToke
                     );
                     return;
                 };
+                // Computes must have moved to the new members before we stop serving them.
+                if !self
+                    .wait_cplane_notified(tenant_id, timeline_id, req.generation, &req_cancel)
+                    .await
+                {
+                    return;
+                }
                 self.reconcile_inner(
                     req,
                     async |client| client.delete_timeline(tenant_id, timeline_id).await,
@@ -386,6 +394,41 @@ async fn delete_tenant_timelines_from_db(&self, tenant_id: TenantId) {
             self.delete_timeline_from_db(tenant_id, timeline_id).await;
         }
     }
+    /// Waits until the control plane has been notified of the safekeepers of a timeline in
+    /// `generation` or a later one, notifying it again if needed. Returns false if cancelled.
+    async fn wait_cplane_notified(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        generation: u32,
+        req_cancel: &CancellationToken,
+    ) -> bool {
+        loop {
+            match self
+                .service
+                .cplane_notify_safekeepers_if_needed(tenant_id, timeline_id, req_cancel)
+                .await
+            {
+                Ok(Some(notified)) if notified.into_inner() >= generation => return true,
+                // Nothing to keep serving: the timeline is being deleted anyway
+                Ok(None) => return true,
+                Ok(Some(notified)) => {
+                    tracing::info!(
+                        "waiting for the control plane to be notified of generation {generation}, last notified of {notified}"
+                    );
+                }
+                Err(ApiError::ShuttingDown) => return false,
+                Err(e) => {
+                    tracing::info!("couldn't notify control plane, retrying after sleep: {e}");
+                }
+            }
+            const SLEEP_TIME: Duration = Duration::from_secs(5);
+            tokio::select! {
+                _ = tokio::time::sleep(SLEEP_TIME) => {}
+                _ = req_cancel.cancelled() => return false,
+            }
+        }
+    }
     /// Returns whether the reconciliation happened successfully
     async fn reconcile_inner<T, F, U>(
         &self,
@@ -1,25 +1,93 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
 use super::safekeeper_reconciler::ScheduleRequest;
+use crate::compute_hook::{NotifyError, SafekeepersUpdate};
 use crate::heartbeater::SafekeeperState;
+use crate::id_lock_map::trace_shared_lock;
+use crate::metrics::{self, ReconcileCompleteLabelGroup, ReconcileOutcome};
 use crate::persistence::{
     DatabaseError, SafekeeperTimelineOpKind, TimelinePendingOpPersistence, TimelinePersistence,
 };
 use crate::safekeeper::Safekeeper;
+use crate::safekeeper_client::SafekeeperClient;
 use anyhow::Context;
 use http_utils::error::ApiError;
-use pageserver_api::controller_api::{SafekeeperDescribeResponse, SkSchedulingPolicy};
+use pageserver_api::controller_api::{
+    SafekeeperDescribeResponse, SafekeeperMigrationDescribe, SkSchedulingPolicy,
+};
 use pageserver_api::models::{self, SafekeeperInfo, SafekeepersInfo, TimelineInfo};
-use safekeeper_api::membership::{MemberSet, SafekeeperId};
+use reqwest::StatusCode;
+use safekeeper_api::membership::{
+    Configuration, INITIAL_GENERATION, MemberSet, SafekeeperGeneration, SafekeeperId,
+};
+use safekeeper_api::models::{PullTimelineRequest, TimelineMembershipSwitchRequest};
+use safekeeper_client::mgmt_api;
 use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
-use utils::id::{NodeId, TenantId, TimelineId};
+use utils::id::{NodeId, TenantId, TenantTimelineId, TimelineId};
 use utils::logging::SecretString;
+use utils::lsn::Lsn;
+
+use super::{Service, TenantOperations};
+
+/// Timeout of a single request to a safekeeper during a timeline migration.
+const SK_MIGRATION_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Timeout of a `pull_timeline` request during a timeline migration: it copies the
+/// whole timeline, so give it more time than to the other requests.
+const SK_MIGRATION_PULL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How long to wait for the new members of a timeline to catch up with the
+/// current ones before giving up on the migration attempt.
+const SK_MIGRATION_CATCHUP_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Unregisters an ongoing timeline migration once it is done, whatever the outcome.
+struct SafekeeperMigrationGuard {
+    service: Arc<Service>,
+    ttid: TenantTimelineId,
+}
+
+impl Drop for SafekeeperMigrationGuard {
+    fn drop(&mut self) {
+        let mut locked = self.service.inner.write().unwrap();
+        locked.safekeeper_migrations.remove(&self.ttid);
+        metrics::METRICS_REGISTRY
+            .metrics_group
+            .storage_controller_safekeeper_migrations_ongoing
+            .set(locked.safekeeper_migrations.len() as i64);
+    }
+}
+
+pub(super) fn to_node_ids(sk_set: &[i64]) -> Vec<NodeId> {
+    sk_set.iter().map(|id| NodeId(*id as u64)).collect()
+}
 
-use super::Service;
+pub(super) fn same_members(a: &[NodeId], b: &[NodeId]) -> bool {
+    a.iter().collect::<HashSet<_>>() == b.iter().collect::<HashSet<_>>()
+}
+
+fn member_set(
+    safekeepers: &HashMap<NodeId, Safekeeper>,
+    sk_set: &[NodeId],
+) -> Result<MemberSet, ApiError> {
+    let mut members = Vec::with_capacity(sk_set.len());
+    for sk_id in sk_set {
+        let Some(safekeeper) = safekeepers.get(sk_id) else {
+            return Err(ApiError::InternalServerError(anyhow::anyhow!(
+                "couldn't find entry for safekeeper with id {sk_id}"
+            )));
+        };
+        members.push(SafekeeperId {
+            id: *sk_id,
+            host: safekeeper.skp.host.clone(),
+            pg_port: safekeeper.skp.port as u16,
+        });
+    }
+    MemberSet::new(members).map_err(ApiError::InternalServerError)
+}
 
 impl Service {
     /// Timeline creation on safekeepers
@@ -27,12 +95,15 @@ impl Service {
     /// Returns `Ok(left)` if the timeline has been created on a quorum of safekeepers,
     /// where `left` contains the list of safekeepers that didn't have a successful response.
     /// Assumes tenant lock is held while calling this function.
+    ///
+    /// With `replace_deleted`, safekeepers recreate the timeline even if they deleted it.
     pub(super) async fn tenant_timeline_create_safekeepers_quorum(
         &self,
         tenant_id: TenantId,
         timeline_id: TimelineId,
         pg_version: u32,
         timeline_persistence: &TimelinePersistence,
+        replace_deleted: bool,
     ) -> Result<Vec<NodeId>, ApiError> {
         // If quorum is reached, return if we are outside of a specified timeout
         let jwt = self
@@ -62,7 +133,11 @@ pub(super) async fn tenant_timeline_create_safekeepers_quorum(
             });
         }
         let mset = MemberSet::new(members).map_err(ApiError::InternalServerError)?;
-        let mconf = safekeeper_api::membership::Configuration::new(mset);
+        let mut mconf = safekeeper_api::membership::Configuration::new(mset);
+        // Timelines are created with generation 0 in the database, which corresponds to
+        // INITIAL_GENERATION on safekeepers. A reset timeline keeps its generation.
+        mconf.generation = SafekeeperGeneration::new(timeline_persistence.generation as u32)
+            .max(INITIAL_GENERATION);
 
         let req = safekeeper_api::models::TimelineCreateRequest {
             commit_lsn: None,
@@ -73,6 +148,7 @@ pub(super) async fn tenant_timeline_create_safekeepers_quorum(
             tenant_id,
             timeline_id,
             wal_seg_size: None,
+            replace_deleted,
         };
         const SK_CREATE_TIMELINE_RECONCILE_TIMEOUT: Duration = Duration::from_secs(30);
         for sk in timeline_persistence.sk_set.iter() {
@@ -187,6 +263,9 @@ pub(super) async fn tenant_timeline_create_safekeepers(
         let start_lsn = match create_mode {
             models::TimelineCreateRequestMode::Bootstrap { .. } => timeline_info.last_record_lsn,
             models::TimelineCreateRequestMode::Branch { .. } => timeline_info.last_record_lsn,
+            models::TimelineCreateRequestMode::CrossTenantBranch { .. } => {
+                timeline_info.last_record_lsn
+            }
             models::TimelineCreateRequestMode::ImportPgdata { .. } => {
                 return Err(ApiError::InternalServerError(anyhow::anyhow!(
                     "import pgdata doesn't specify the start lsn, aborting creation on safekeepers"
@@ -234,9 +313,38 @@ pub(super) async fn tenant_timeline_create_safekeepers(
                 timeline_id,
                 pg_version,
                 &timeline_persist,
+                false,
             )
             .await?;
 
+        let sk_set = sks.iter().map(|sk| sk.id).collect::<Vec<_>>();
+        self.schedule_missed_creations(
+            tenant_id,
+            timeline_id,
+            &timeline_persist,
+            &sk_set,
+            remaining,
+        )
+        .await?;
+
+        Ok(SafekeepersInfo {
+            generation: timeline_persist.generation as u32,
+            safekeepers: sks,
+            tenant_id,
+            timeline_id,
+        })
+    }
+
+    /// Makes the safekeepers among `sk_set` which missed the creation of a timeline pull it
+    /// from the others, asynchronously and with infinite retries.
+    async fn schedule_missed_creations(
+        self: &Arc<Self>,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        timeline_persist: &TimelinePersistence,
+        sk_set: &[NodeId],
+        remaining: Vec<NodeId>,
+    ) -> Result<(), ApiError> {
         // For the remaining safekeepers, take care of their reconciliation asynchronously
         for &remaining_id in remaining.iter() {
             let pending_op = TimelinePendingOpPersistence {
@@ -257,18 +365,18 @@ pub(super) async fn tenant_timeline_create_safekeepers(
                         "Couldn't find safekeeper with id {remaining_id}"
                     )));
                 };
-                let Ok(host_list) = sks
+                let Ok(host_list) = sk_set
                     .iter()
-                    .map(|sk| {
+                    .map(|sk_id| {
                         Ok((
-                            sk.id,
+                            *sk_id,
                             locked
                                 .safekeepers
-                                .get(&sk.id)
+                                .get(sk_id)
                                 .ok_or_else(|| {
                                     ApiError::InternalServerError(anyhow::anyhow!(
                                         "Couldn't find safekeeper with id {} to pull from",
-                                        sk.id
+                                        sk_id
                                     ))
                                 })?
                                 .base_url(),
@@ -290,12 +398,7 @@ pub(super) async fn tenant_timeline_create_safekeepers(
             }
         }
 
-        Ok(SafekeepersInfo {
-            generation: timeline_persist.generation as u32,
-            safekeepers: sks,
-            tenant_id,
-            timeline_id,
-        })
+        Ok(())
     }
     /// Perform timeline deletion on safekeepers. Will return success: we persist the deletion into the reconciler.
     pub(super) async fn tenant_timeline_delete_safekeepers(
@@ -360,6 +463,131 @@ pub(super) async fn tenant_timeline_delete_safekeepers(
         Ok(())
     }
 
+    /// Reset a timeline on its safekeepers to start at `lsn`, when it is reset to an earlier
+    /// LSN on the pageservers: the timeline is deleted on all of its safekeepers, together with
+    /// its WAL, and created again at `lsn` in the same membership configuration. Otherwise
+    /// safekeepers would stream the discarded WAL to the pageservers again.
+    ///
+    /// All members must be available, as one which kept the WAL after `lsn` could win an
+    /// election. The call can be retried until success. Assumes the tenant lock is held
+    /// exclusively, and `pg_version` is in the PG_VERSION_NUM format.
+    pub(super) async fn tenant_timeline_reset_safekeepers(
+        self: &Arc<Self>,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        pg_version: u32,
+        lsn: Lsn,
+    ) -> Result<(), ApiError> {
+        let timeline = self
+            .persistence
+            .get_timeline(tenant_id, timeline_id)
+            .await?;
+        let Some(mut timeline) = timeline.filter(|tl| tl.deleted_at.is_none()) else {
+            return Err(ApiError::PreconditionFailed(
+                format!(
+                    "timeline {tenant_id}/{timeline_id} is not managed on safekeepers by the storage controller"
+                )
+                .into(),
+            ));
+        };
+        if let Some(new_sk_set) = &timeline.new_sk_set {
+            return Err(ApiError::Conflict(format!(
+                "timeline {tenant_id}/{timeline_id} is being migrated to {:?}",
+                to_node_ids(new_sk_set)
+            )));
+        }
+
+        let sk_set = to_node_ids(&timeline.sk_set);
+        let sks = {
+            let locked = self.inner.read().unwrap();
+            sk_set
+                .iter()
+                .map(|sk_id| {
+                    locked.safekeepers.get(sk_id).cloned().ok_or_else(|| {
+                        ApiError::InternalServerError(anyhow::anyhow!(
+                            "couldn't find entry for safekeeper with id {sk_id}"
+                        ))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let cancel = self.reconcilers_cancel.child_token();
+        let jwt = self
+            .config
+            .safekeeper_jwt_token
+            .clone()
+            .map(SecretString::from);
+
+        // A background pull of the timeline onto a member which missed its creation must not
+        // seed it again with the old WAL.
+        let pending_ops = self
+            .persistence
+            .list_pending_ops_for_timeline(tenant_id, timeline_id)
+            .await?
+            .into_iter()
+            .filter(|op| op.timeline_id == timeline_id.to_string())
+            .filter(|op| sk_set.contains(&NodeId(op.sk_id as u64)))
+            .collect::<Vec<_>>();
+        for op in pending_ops {
+            let sk_id = NodeId(op.sk_id as u64);
+            self.inner
+                .write()
+                .unwrap()
+                .safekeeper_reconcilers
+                .cancel_reconciles_for_timeline(sk_id, tenant_id, Some(timeline_id));
+            self.persistence
+                .remove_pending_op(tenant_id, Some(timeline_id), sk_id, op.generation as u32)
+                .await?;
+        }
+
+        // Deleting a timeline which is already deleted succeeds, so this is fine to retry.
+        let deletions = futures::future::join_all(sks.iter().map(|sk| {
+            let jwt = &jwt;
+            let cancel = &cancel;
+            async move {
+                sk.with_client_retries(
+                    |client| async move { client.delete_timeline(tenant_id, timeline_id).await },
+                    &self.http_client,
+                    jwt,
+                    3,
+                    3,
+                    SK_MIGRATION_REQUEST_TIMEOUT,
+                    cancel,
+                )
+                .await
+                .map_err(|e| safekeeper_api_error(sk, e))
+            }
+        }))
+        .await;
+        for res in deletions {
+            res?;
+        }
+
+        if timeline.start_lsn.0 != lsn {
+            self.persistence
+                .update_timeline_start_lsn(tenant_id, timeline_id, timeline.generation, lsn)
+                .await?;
+            timeline.start_lsn = lsn.into();
+        }
+
+        let remaining = self
+            .tenant_timeline_create_safekeepers_quorum(
+                tenant_id,
+                timeline_id,
+                pg_version,
+                &timeline,
+                true,
+            )
+            .await?;
+        self.schedule_missed_creations(tenant_id, timeline_id, &timeline, &sk_set, remaining)
+            .await?;
+
+        tracing::info!(
+            "reset timeline {tenant_id}/{timeline_id} on safekeepers {sk_set:?} to {lsn}"
+        );
+        Ok(())
+    }
+
     /// Perform tenant deletion on safekeepers.
     pub(super) async fn tenant_delete_safekeepers(
         self: &Arc<Self>,
@@ -458,6 +686,756 @@ pub(super) async fn tenant_delete_safekeepers(
         Ok(())
     }
 
+    /// Migrate the timeline to the given set of safekeepers.
+    ///
+    /// This follows the joint consensus procedure of
+    /// rfcs/035-safekeeper-dynamic-membership-change.md: the joint configuration
+    /// is persisted and switched to first, then the new members get the timeline
+    /// via `pull_timeline` and catch up with the current ones, and only then the
+    /// final configuration is switched to and persisted. The safekeepers which
+    /// are not part of the new set are cleaned up asynchronously by the
+    /// safekeeper reconcilers.
+    ///
+    /// The pending set is persisted in `new_sk_set`, so a migration which got
+    /// interrupted midway is resumed by calling this again with the same set.
+    pub(crate) async fn tenant_timeline_safekeeper_migrate(
+        self: &Arc<Self>,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        new_sk_set: Vec<NodeId>,
+    ) -> Result<(), ApiError> {
+        let _tenant_lock = trace_shared_lock(
+            &self.tenant_op_locks,
+            tenant_id,
+            TenantOperations::TimelineSafekeeperMigrate,
+        )
+        .await;
+
+        let timeline = self
+            .persistence
+            .get_timeline(tenant_id, timeline_id)
+            .await?;
+        let Some(timeline) = timeline.filter(|tl| tl.deleted_at.is_none()) else {
+            return Err(ApiError::NotFound(
+                anyhow::anyhow!("timeline {tenant_id}/{timeline_id} not found on safekeepers")
+                    .into(),
+            ));
+        };
+
+        if new_sk_set.is_empty() {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "new safekeeper set is empty"
+            )));
+        }
+        if new_sk_set.iter().collect::<HashSet<_>>().len() != new_sk_set.len() {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "duplicate safekeeper id in the new set {new_sk_set:?}"
+            )));
+        }
+        let cur_sk_set = to_node_ids(&timeline.sk_set);
+        {
+            let locked = self.inner.read().unwrap();
+            for sk_id in new_sk_set.iter() {
+                let Some(sk) = locked.safekeepers.get(sk_id) else {
+                    return Err(ApiError::NotFound(
+                        anyhow::anyhow!("safekeeper {sk_id} not found").into(),
+                    ));
+                };
+                if sk.scheduling_policy() == SkSchedulingPolicy::Decomissioned {
+                    return Err(ApiError::PreconditionFailed(
+                        format!("safekeeper {sk_id} is decomissioned").into(),
+                    ));
+                }
+                if sk.scheduling_policy() == SkSchedulingPolicy::Draining
+                    && !cur_sk_set.contains(sk_id)
+                {
+                    return Err(ApiError::PreconditionFailed(
+                        format!("safekeeper {sk_id} is being drained").into(),
+                    ));
+                }
+            }
+        }
+        match timeline.new_sk_set.as_deref().map(to_node_ids) {
+            Some(pending_sk_set) if !same_members(&pending_sk_set, &new_sk_set) => {
+                return Err(ApiError::Conflict(format!(
+                    "timeline {tenant_id}/{timeline_id} is being migrated to {pending_sk_set:?}"
+                )));
+            }
+            Some(_) => {}
+            None if same_members(&cur_sk_set, &new_sk_set) => {
+                tracing::info!(
+                    "timeline {tenant_id}/{timeline_id} is already on safekeepers {new_sk_set:?}"
+                );
+                // A previous migration may have failed to notify the control plane.
+                let cancel = self.reconcilers_cancel.child_token();
+                self.cplane_notify_safekeepers_if_needed(tenant_id, timeline_id, &cancel)
+                    .await?;
+                return Ok(());
+            }
+            None => {}
+        }
+
+        let _migration_guard = {
+            let ttid = TenantTimelineId::new(tenant_id, timeline_id);
+            let mut locked = self.inner.write().unwrap();
+            if locked.safekeeper_migrations.contains_key(&ttid) {
+                return Err(ApiError::Conflict(format!(
+                    "timeline {ttid} is already being migrated"
+                )));
+            }
+            locked.safekeeper_migrations.insert(
+                ttid,
+                SafekeeperMigrationDescribe {
+                    tenant_id,
+                    timeline_id,
+                    from: cur_sk_set.clone(),
+                    to: new_sk_set.clone(),
+                    started_at: chrono::Utc::now(),
+                },
+            );
+            metrics::METRICS_REGISTRY
+                .metrics_group
+                .storage_controller_safekeeper_migrations_ongoing
+                .set(locked.safekeeper_migrations.len() as i64);
+            SafekeeperMigrationGuard {
+                service: self.clone(),
+                ttid,
+            }
+        };
+
+        let res = self
+            .do_tenant_timeline_safekeeper_migrate(&timeline, cur_sk_set, new_sk_set)
+            .await;
+        let outcome = match &res {
+            Ok(()) => ReconcileOutcome::Success,
+            Err(ApiError::ShuttingDown) => ReconcileOutcome::Cancel,
+            Err(_) => ReconcileOutcome::Error,
+        };
+        metrics::METRICS_REGISTRY
+            .metrics_group
+            .storage_controller_safekeeper_migration_complete
+            .inc(ReconcileCompleteLabelGroup { status: outcome });
+        res
+    }
+
+    /// Guts of [`Self::tenant_timeline_safekeeper_migrate`]. Assumes the tenant lock is
+    /// held and the migration is registered.
+    async fn do_tenant_timeline_safekeeper_migrate(
+        self: &Arc<Self>,
+        timeline: &TimelinePersistence,
+        cur_sk_set: Vec<NodeId>,
+        new_sk_set: Vec<NodeId>,
+    ) -> Result<(), ApiError> {
+        let tenant_id = TenantId::from_str(&timeline.tenant_id)
+            .context("tenant id loaded from db")
+            .map_err(ApiError::InternalServerError)?;
+        let timeline_id = TimelineId::from_str(&timeline.timeline_id)
+            .context("timeline id loaded from db")
+            .map_err(ApiError::InternalServerError)?;
+        let cancel = self.reconcilers_cancel.child_token();
+
+        let safekeepers = {
+            let locked = self.inner.read().unwrap();
+            locked.safekeepers.clone()
+        };
+        let get_safekeepers = |sk_set: &[NodeId]| {
+            sk_set
+                .iter()
+                .map(|sk_id| {
+                    safekeepers.get(sk_id).cloned().ok_or_else(|| {
+                        ApiError::InternalServerError(anyhow::anyhow!(
+                            "couldn't find entry for safekeeper with id {sk_id}"
+                        ))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let cur_sks = get_safekeepers(&cur_sk_set)?;
+        let new_only_sk_set = new_sk_set
+            .iter()
+            .filter(|sk_id| !cur_sk_set.contains(sk_id))
+            .copied()
+            .collect::<Vec<_>>();
+        let new_only_sks = get_safekeepers(&new_only_sk_set)?;
+        let new_sks = get_safekeepers(&new_sk_set)?;
+
+        // Persist the joint configuration first, unless we are resuming a migration.
+        // Timelines are created with generation 0 in the database, which
+        // corresponds to INITIAL_GENERATION on safekeepers.
+        let joint_generation = if timeline.new_sk_set.is_some() {
+            tracing::info!(
+                "resuming migration of timeline {tenant_id}/{timeline_id} from {cur_sk_set:?} to {new_sk_set:?}"
+            );
+            SafekeeperGeneration::new(timeline.generation as u32)
+        } else {
+            let joint_generation = SafekeeperGeneration::new(timeline.generation as u32)
+                .max(INITIAL_GENERATION)
+                .next();
+            let new_sk_set_persistence =
+                new_sk_set.iter().map(|id| id.0 as i64).collect::<Vec<_>>();
+            self.persistence
+                .update_timeline_membership(
+                    tenant_id,
+                    timeline_id,
+                    timeline.generation,
+                    joint_generation.into_inner() as i32,
+                    &timeline.sk_set,
+                    Some(&new_sk_set_persistence),
+                )
+                .await?;
+            tracing::info!(
+                "starting migration of timeline {tenant_id}/{timeline_id} from {cur_sk_set:?} to {new_sk_set:?} at generation {joint_generation}"
+            );
+            joint_generation
+        };
+        let final_generation = joint_generation.next();
+
+        let joint_mconf = Configuration {
+            generation: joint_generation,
+            members: member_set(&safekeepers, &cur_sk_set)?,
+            new_members: Some(member_set(&safekeepers, &new_sk_set)?),
+        };
+        let final_mconf = Configuration {
+            generation: final_generation,
+            members: member_set(&safekeepers, &new_sk_set)?,
+            new_members: None,
+        };
+
+        // Switch the current members into the joint configuration. Once a majority
+        // of them did it, they won't accept WAL without a majority of the new set,
+        // so the position we learn from them next is the one to sync the new set to.
+        self.safekeepers_membership_switch(tenant_id, timeline_id, &cur_sks, &joint_mconf, &cancel)
+            .await?;
+
+        let statuses = self
+            .safekeepers_majority_call(
+                &cur_sks,
+                |client| async move { client.timeline_status(tenant_id, timeline_id).await },
+                SK_MIGRATION_REQUEST_TIMEOUT,
+                &cancel,
+            )
+            .await?;
+        // Unwrap is fine: a majority of a non-empty set responded.
+        let sync_position = statuses
+            .iter()
+            .map(|(_, status)| (status.acceptor_state.epoch, status.flush_lsn))
+            .max()
+            .unwrap();
+        let donors = statuses
+            .iter()
+            .map(|(sk_id, _)| safekeepers[sk_id].base_url())
+            .collect::<Vec<_>>();
+
+        // A new member may have hosted the timeline before, e.g. if it got drained
+        // and is being filled again. Drop the pending ops left from then, so that
+        // an old exclusion doesn't delete the timeline under the new configuration.
+        let stale_ops = self
+            .persistence
+            .list_pending_ops_for_timeline(tenant_id, timeline_id)
+            .await?
+            .into_iter()
+            .filter(|op| op.timeline_id == timeline_id.to_string())
+            .filter(|op| new_only_sk_set.contains(&NodeId(op.sk_id as u64)))
+            .collect::<Vec<_>>();
+        for op in stale_ops {
+            let sk_id = NodeId(op.sk_id as u64);
+            self.inner
+                .write()
+                .unwrap()
+                .safekeeper_reconcilers
+                .cancel_reconciles_for_timeline(sk_id, tenant_id, Some(timeline_id));
+            self.persistence
+                .remove_pending_op(tenant_id, Some(timeline_id), sk_id, op.generation as u32)
+                .await?;
+        }
+
+        // Seed the new members with the timeline. The pulled timeline may carry an
+        // older configuration, so switch it to the joint one afterwards.
+        let mut failed_pulls = Vec::new();
+        for sk in new_only_sks.iter() {
+            let res = self
+                .safekeeper_pull_timeline(
+                    sk,
+                    tenant_id,
+                    timeline_id,
+                    joint_generation,
+                    &donors,
+                    &cancel,
+                )
+                .await;
+            if let Err(e) = res {
+                if matches!(e, ApiError::ShuttingDown) {
+                    return Err(e);
+                }
+                tracing::warn!(
+                    "couldn't pull timeline {tenant_id}/{timeline_id} onto safekeeper {}: {e}",
+                    sk.get_id()
+                );
+                failed_pulls.push(sk.get_id());
+            }
+        }
+        let pulled_sks = new_only_sks
+            .iter()
+            .filter(|sk| !failed_pulls.contains(&sk.get_id()))
+            .cloned()
+            .collect::<Vec<_>>();
+        if new_sks.len() - failed_pulls.len() <= new_sks.len() / 2 {
+            return Err(ApiError::ResourceUnavailable(
+                format!(
+                    "couldn't pull timeline {tenant_id}/{timeline_id} onto a majority of {new_sk_set:?}, failed on {failed_pulls:?}"
+                )
+                .into(),
+            ));
+        }
+        for sk in pulled_sks.iter() {
+            self.safekeeper_membership_switch(sk, tenant_id, timeline_id, &joint_mconf, &cancel)
+                .await?;
+        }
+
+        self.wait_safekeepers_catch_up(tenant_id, timeline_id, &new_sks, sync_position, &cancel)
+            .await?;
+
+        // The new set is in sync: switch to the final configuration and persist it.
+        self.safekeepers_membership_switch(tenant_id, timeline_id, &new_sks, &final_mconf, &cancel)
+            .await?;
+        let new_sk_set_persistence = new_sk_set.iter().map(|id| id.0 as i64).collect::<Vec<_>>();
+        self.persistence
+            .update_timeline_membership(
+                tenant_id,
+                timeline_id,
+                joint_generation.into_inner() as i32,
+                final_generation.into_inner() as i32,
+                &new_sk_set_persistence,
+                None,
+            )
+            .await?;
+        tracing::info!(
+            "migrated timeline {tenant_id}/{timeline_id} from {cur_sk_set:?} to {new_sk_set:?}, generation {final_generation}"
+        );
+
+        // Finally, exclude the removed members and retry pulls which failed above
+        // in the background, like timeline creation does.
+        let mut pending_ops = Vec::new();
+        for sk in cur_sks.iter() {
+            if new_sk_set.contains(&sk.get_id()) {
+                continue;
+            }
+            if sk.scheduling_policy() == SkSchedulingPolicy::Decomissioned {
+                // Decomissioned safekeepers are going away together with their data
+                continue;
+            }
+            pending_ops.push((sk, SafekeeperTimelineOpKind::Exclude));
+        }
+        for sk in new_only_sks.iter() {
+            if failed_pulls.contains(&sk.get_id()) {
+                pending_ops.push((sk, SafekeeperTimelineOpKind::Pull));
+            }
+        }
+        for (sk, op_kind) in pending_ops.iter() {
+            let pending_op = TimelinePendingOpPersistence {
+                tenant_id: tenant_id.to_string(),
+                timeline_id: timeline_id.to_string(),
+                generation: final_generation.into_inner() as i32,
+                op_kind: *op_kind,
+                sk_id: sk.get_id().0 as i64,
+            };
+            tracing::info!("writing pending op for sk id {}", sk.get_id());
+            self.persistence.insert_pending_op(pending_op).await?;
+        }
+        if !pending_ops.is_empty() {
+            let host_list = new_sks
+                .iter()
+                .filter(|sk| !failed_pulls.contains(&sk.get_id()))
+                .map(|sk| (sk.get_id(), sk.base_url()))
+                .collect::<Vec<_>>();
+            let mut locked = self.inner.write().unwrap();
+            for (sk, op_kind) in pending_ops {
+                let req = ScheduleRequest {
+                    safekeeper: Box::new(sk.clone()),
+                    host_list: match op_kind {
+                        SafekeeperTimelineOpKind::Pull => host_list.clone(),
+                        _ => Vec::new(),
+                    },
+                    tenant_id,
+                    timeline_id: Some(timeline_id),
+                    generation: final_generation.into_inner(),
+                    kind: op_kind,
+                };
+                locked.safekeeper_reconcilers.schedule_request(self, req);
+            }
+        }
+
+        // Computes keep streaming to the safekeepers they were configured with: tell them
+        // about the new set. The exclusions scheduled above wait for it, see
+        // `Self::cplane_notify_safekeepers_if_needed`.
+        self.cplane_notify_safekeepers(
+            tenant_id,
+            timeline_id,
+            final_generation,
+            &new_sk_set,
+            &cancel,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Notifies the control plane of the safekeepers of a timeline in `generation`, and
+    /// records it in `cplane_notified_generation`.
+    ///
+    /// Until then, computes keep streaming WAL to the safekeepers they were started with:
+    /// walproposer adopts the membership generation of the safekeepers, but not their
+    /// members. The control plane must ignore notifications of older generations than the
+    /// one it last applied, as they may be retried concurrently.
+    async fn cplane_notify_safekeepers(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        generation: SafekeeperGeneration,
+        sk_set: &[NodeId],
+        cancel: &CancellationToken,
+    ) -> Result<(), ApiError> {
+        let safekeepers = {
+            let locked = self.inner.read().unwrap();
+            member_set(&locked.safekeepers, sk_set)?.m
+        };
+        let update = SafekeepersUpdate {
+            tenant_id,
+            timeline_id,
+            generation,
+            safekeepers,
+        };
+        self.compute_hook
+            .notify_safekeepers(update, cancel)
+            .await
+            .map_err(|e| match e {
+                NotifyError::ShuttingDown => ApiError::ShuttingDown,
+                e => ApiError::ResourceUnavailable(
+                    format!(
+                        "couldn't notify control plane of the safekeepers of timeline {tenant_id}/{timeline_id}: {e}"
+                    )
+                    .into(),
+                ),
+            })?;
+
+        self.persistence
+            .update_cplane_notified_generation(
+                tenant_id,
+                timeline_id,
+                generation.into_inner() as i32,
+            )
+            .await?;
+        tracing::info!(
+            "notified control plane of the safekeepers {sk_set:?} of timeline {tenant_id}/{timeline_id} at generation {generation}"
+        );
+        Ok(())
+    }
+
+    /// Makes sure the control plane knows the current safekeepers of a timeline, unless it is
+    /// being migrated, in which case the migration notifies it once done.
+    ///
+    /// Returns the generation the control plane was last notified of, or `None` if the
+    /// timeline is gone.
+    pub(crate) async fn cplane_notify_safekeepers_if_needed(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        cancel: &CancellationToken,
+    ) -> Result<Option<SafekeeperGeneration>, ApiError> {
+        let timeline = self
+            .persistence
+            .get_timeline(tenant_id, timeline_id)
+            .await?;
+        let Some(timeline) = timeline.filter(|tl| tl.deleted_at.is_none()) else {
+            return Ok(None);
+        };
+        let notified = SafekeeperGeneration::new(timeline.cplane_notified_generation as u32);
+        if timeline.new_sk_set.is_some()
+            || timeline.cplane_notified_generation >= timeline.generation
+        {
+            return Ok(Some(notified));
+        }
+
+        let generation = SafekeeperGeneration::new(timeline.generation as u32);
+        self.cplane_notify_safekeepers(
+            tenant_id,
+            timeline_id,
+            generation,
+            &to_node_ids(&timeline.sk_set),
+            cancel,
+        )
+        .await?;
+        Ok(Some(generation))
+    }
+
+    /// Call `op` on all the given safekeepers concurrently, succeeding if a
+    /// majority of them succeeded. Returns the successful responses.
+    async fn safekeepers_majority_call<T, O, F>(
+        &self,
+        safekeepers: &[Safekeeper],
+        op: O,
+        timeout: Duration,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<(NodeId, T)>, ApiError>
+    where
+        O: FnMut(SafekeeperClient) -> F + Clone,
+        F: std::future::Future<Output = mgmt_api::Result<T>>,
+    {
+        let jwt = self
+            .config
+            .safekeeper_jwt_token
+            .clone()
+            .map(SecretString::from);
+        let results = futures::future::join_all(safekeepers.iter().map(|sk| {
+            let op = op.clone();
+            let jwt = &jwt;
+            async move {
+                let res = sk
+                    .with_client_retries(op, &self.http_client, jwt, 3, 3, timeout, cancel)
+                    .await;
+                (sk.get_id(), res)
+            }
+        }))
+        .await;
+        if cancel.is_cancelled() {
+            return Err(ApiError::ShuttingDown);
+        }
+
+        let mut successes = Vec::new();
+        let mut errors = Vec::new();
+        for (sk_id, res) in results {
+            match res {
+                Ok(resp) => successes.push((sk_id, resp)),
+                Err(e) => {
+                    tracing::info!("request to safekeeper {sk_id} failed: {e}");
+                    errors.push(sk_id);
+                }
+            }
+        }
+        if successes.len() <= safekeepers.len() / 2 {
+            return Err(ApiError::ResourceUnavailable(
+                format!("no majority of safekeepers responded, failed on {errors:?}").into(),
+            ));
+        }
+        Ok(successes)
+    }
+
+    /// Switch a majority of the given safekeepers to the membership configuration `mconf`.
+    async fn safekeepers_membership_switch(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        safekeepers: &[Safekeeper],
+        mconf: &Configuration,
+        cancel: &CancellationToken,
+    ) -> Result<(), ApiError> {
+        let req = TimelineMembershipSwitchRequest {
+            mconf: mconf.clone(),
+        };
+        let req = &req;
+        self.safekeepers_majority_call(
+            safekeepers,
+            |client| async move {
+                tolerate_newer_generation(
+                    client.membership_switch(tenant_id, timeline_id, req).await,
+                )
+            },
+            SK_MIGRATION_REQUEST_TIMEOUT,
+            cancel,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Switch a single safekeeper to the membership configuration `mconf`.
+    async fn safekeeper_membership_switch(
+        &self,
+        safekeeper: &Safekeeper,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        mconf: &Configuration,
+        cancel: &CancellationToken,
+    ) -> Result<(), ApiError> {
+        let jwt = self
+            .config
+            .safekeeper_jwt_token
+            .clone()
+            .map(SecretString::from);
+        let req = TimelineMembershipSwitchRequest {
+            mconf: mconf.clone(),
+        };
+        let req = &req;
+        safekeeper
+            .with_client_retries(
+                |client| async move {
+                    tolerate_newer_generation(
+                        client.membership_switch(tenant_id, timeline_id, req).await,
+                    )
+                },
+                &self.http_client,
+                &jwt,
+                3,
+                3,
+                SK_MIGRATION_REQUEST_TIMEOUT,
+                cancel,
+            )
+            .await
+            .map_err(|e| safekeeper_api_error(safekeeper, e))
+    }
+
+    /// Make sure the safekeeper has the timeline, pulling it from `donors` if it doesn't.
+    ///
+    /// A copy with a configuration older than `generation` is left from the time the
+    /// safekeeper was a member before, and is replaced with a fresh one.
+    async fn safekeeper_pull_timeline(
+        &self,
+        safekeeper: &Safekeeper,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        generation: SafekeeperGeneration,
+        donors: &[String],
+        cancel: &CancellationToken,
+    ) -> Result<(), ApiError> {
+        let jwt = self
+            .config
+            .safekeeper_jwt_token
+            .clone()
+            .map(SecretString::from);
+        // Pulling a timeline which already exists fails, which happens if we
+        // are resuming a migration.
+        let status = safekeeper
+            .with_client_retries(
+                |client| async move { client.timeline_status(tenant_id, timeline_id).await },
+                &self.http_client,
+                &jwt,
+                3,
+                3,
+                SK_MIGRATION_REQUEST_TIMEOUT,
+                cancel,
+            )
+            .await;
+        match status {
+            Ok(status) if status.mconf.generation >= generation => {
+                tracing::info!(
+                    "timeline {tenant_id}/{timeline_id} already exists on safekeeper {}",
+                    safekeeper.get_id()
+                );
+                return Ok(());
+            }
+            Ok(status) => {
+                tracing::info!(
+                    "deleting stale timeline {tenant_id}/{timeline_id} at generation {} from safekeeper {}",
+                    status.mconf.generation,
+                    safekeeper.get_id()
+                );
+                safekeeper
+                    .with_client_retries(
+                        |client| async move { client.delete_timeline(tenant_id, timeline_id).await },
+                        &self.http_client,
+                        &jwt,
+                        3,
+                        3,
+                        SK_MIGRATION_REQUEST_TIMEOUT,
+                        cancel,
+                    )
+                    .await
+                    .map_err(|e| safekeeper_api_error(safekeeper, e))?;
+            }
+            Err(mgmt_api::Error::ApiError(StatusCode::NOT_FOUND, _)) => {}
+            Err(e) => return Err(safekeeper_api_error(safekeeper, e)),
+        }
+
+        let req = PullTimelineRequest {
+            tenant_id,
+            timeline_id,
+            http_hosts: donors.to_vec(),
+        };
+        let req = &req;
+        let resp = safekeeper
+            .with_client_retries(
+                |client| async move { client.pull_timeline(req).await },
+                &self.http_client,
+                &jwt,
+                3,
+                3,
+                SK_MIGRATION_PULL_TIMEOUT,
+                cancel,
+            )
+            .await
+            .map_err(|e| safekeeper_api_error(safekeeper, e))?;
+        tracing::info!(
+            "pulled timeline {tenant_id}/{timeline_id} from {} onto safekeeper {}",
+            resp.safekeeper_host,
+            safekeeper.get_id()
+        );
+        Ok(())
+    }
+
+    /// Wait until a majority of the given safekeepers reach `sync_position`, i.e.
+    /// have at least its last log term and flush position.
+    async fn wait_safekeepers_catch_up(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        safekeepers: &[Safekeeper],
+        sync_position: (u64, Lsn),
+        cancel: &CancellationToken,
+    ) -> Result<(), ApiError> {
+        const POLL_INTERVAL: Duration = Duration::from_secs(1);
+        let deadline = tokio::time::Instant::now() + SK_MIGRATION_CATCHUP_TIMEOUT;
+        loop {
+            let statuses = self
+                .safekeepers_majority_call(
+                    safekeepers,
+                    |client| async move { client.timeline_status(tenant_id, timeline_id).await },
+                    SK_MIGRATION_REQUEST_TIMEOUT,
+                    cancel,
+                )
+                .await;
+            let caught_up = match statuses {
+                Ok(statuses) => statuses
+                    .iter()
+                    .filter(|(_, status)| {
+                        (status.acceptor_state.epoch, status.flush_lsn) >= sync_position
+                    })
+                    .count(),
+                Err(ApiError::ShuttingDown) => return Err(ApiError::ShuttingDown),
+                Err(e) => {
+                    tracing::info!("couldn't get timeline status from new safekeepers: {e}");
+                    0
+                }
+            };
+            if caught_up > safekeepers.len() / 2 {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() + POLL_INTERVAL > deadline {
+                return Err(ApiError::Timeout(
+                    format!(
+                        "new safekeepers of timeline {tenant_id}/{timeline_id} didn't catch up with {}/{} in {SK_MIGRATION_CATCHUP_TIMEOUT:?}",
+                        sync_position.0, sync_position.1
+                    )
+                    .into(),
+                ));
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = cancel.cancelled() => return Err(ApiError::ShuttingDown),
+            }
+        }
+    }
+
+    /// Ongoing migrations of timelines between safekeepers.
+    pub(crate) fn safekeeper_migrations_list(&self) -> Vec<SafekeeperMigrationDescribe> {
+        let locked = self.inner.read().unwrap();
+        let mut list = locked
+            .safekeeper_migrations
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        list.sort_by_key(|m| m.started_at);
+        list
+    }
+
     /// Choose safekeepers for the new timeline: 3 in different azs.
     pub(crate) async fn safekeepers_for_new_timeline(
         &self,
@@ -470,7 +1448,10 @@ pub(crate) async fn safekeepers_for_new_timeline(
                 .safekeepers
                 .iter()
                 .filter_map(|sk| {
-                    if sk.1.scheduling_policy() != SkSchedulingPolicy::Active {
+                    if !matches!(
+                        sk.1.scheduling_policy(),
+                        SkSchedulingPolicy::Active | SkSchedulingPolicy::Filling
+                    ) {
                         // If we don't want to schedule stuff onto the safekeeper, respect that.
                         return None;
                     }
@@ -594,6 +1575,35 @@ pub(crate) async fn upsert_safekeeper(
         Ok(())
     }
 
+    /// Wrapper around [`Self::set_safekeeper_scheduling_policy`] for the HTTP api: draining and
+    /// filling are only entered through their operations, which forbid other changes meanwhile.
+    pub(crate) async fn external_set_safekeeper_scheduling_policy(
+        &self,
+        id: i64,
+        scheduling_policy: SkSchedulingPolicy,
+    ) -> Result<(), ApiError> {
+        if matches!(
+            scheduling_policy,
+            SkSchedulingPolicy::Draining | SkSchedulingPolicy::Filling
+        ) {
+            return Err(ApiError::BadRequest(anyhow::anyhow!(
+                "scheduling policy {scheduling_policy:?} can only be set by starting a drain or fill"
+            )));
+        }
+        {
+            let locked = self.inner.read().unwrap();
+            if let Some(op) = locked.ongoing_operation.as_ref().map(|op| op.operation) {
+                return Err(ApiError::PreconditionFailed(
+                    format!("Ongoing background operation forbids configuring: {op}").into(),
+                ));
+            }
+        }
+
+        self.set_safekeeper_scheduling_policy(id, scheduling_policy)
+            .await?;
+        Ok(())
+    }
+
     pub(crate) async fn set_safekeeper_scheduling_policy(
         &self,
         id: i64,
@@ -613,7 +1623,9 @@ pub(crate) async fn set_safekeeper_scheduling_policy(
             sk.set_scheduling_policy(scheduling_policy);
 
             match scheduling_policy {
-                SkSchedulingPolicy::Active => (),
+                SkSchedulingPolicy::Active
+                | SkSchedulingPolicy::Draining
+                | SkSchedulingPolicy::Filling => (),
                 SkSchedulingPolicy::Decomissioned | SkSchedulingPolicy::Pause => {
                     locked.safekeeper_reconcilers.cancel_safekeeper(node_id);
                 }
@@ -624,3 +1636,22 @@ pub(crate) async fn set_safekeeper_scheduling_policy(
         Ok(())
     }
 }
+
+/// Safekeepers ignore switches to a generation older than the one they have,
+/// responding with 409. Only the storage controller bumps generations, so a
+/// newer one means an interrupted attempt of the same migration got further.
+fn tolerate_newer_generation(res: mgmt_api::Result<impl Sized>) -> mgmt_api::Result<()> {
+    match res {
+        Ok(_) | Err(mgmt_api::Error::ApiError(StatusCode::CONFLICT, _)) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+fn safekeeper_api_error(safekeeper: &Safekeeper, e: mgmt_api::Error) -> ApiError {
+    match e {
+        mgmt_api::Error::Cancelled => ApiError::ShuttingDown,
+        e => {
+            ApiError::ResourceUnavailable(format!("safekeeper {}: {e}", safekeeper.get_id()).into())
+        }
+    }
+}
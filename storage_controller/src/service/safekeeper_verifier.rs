@@ -0,0 +1,471 @@
+//! Background verification that the safekeepers of a timeline hold identical WAL.
+//!
+//! Periodically requests digests of the WAL committed since the previous check from
+//! all the members of each timeline's safekeeper set and compares them, going over
+//! the timelines in batches. Divergence is reported via metrics and the
+//! `/control/v1/safekeeper_wal_divergences` API. A diverged timeline isn't verified
+//! any further until its safekeeper set changes, e.g. by migrating it off the
+//! broken safekeeper.
+//!
+//! Divergences are stored in the database and loaded on startup, so they keep being
+//! reported across restarts of the storage controller: the diverged range may be
+//! gone from the safekeepers by then, and verification after a restart starts from
+//! the recent WAL only. The progress of the verification isn't stored.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use futures::StreamExt;
+use http_utils::error::ApiError;
+use itertools::Itertools;
+use pageserver_api::controller_api::{
+    SafekeeperWalDigest, SafekeeperWalDivergence, SafekeeperWalVerificationDescribe,
+};
+use safekeeper_api::models::TimelineStatus;
+use safekeeper_client::mgmt_api;
+use tracing::Instrument;
+use utils::id::{NodeId, TenantId, TenantTimelineId, TimelineId};
+use utils::logging::SecretString;
+use utils::lsn::Lsn;
+
+use super::Service;
+use super::safekeeper_rebalancer::timeline_ttid;
+use super::safekeeper_service::{same_members, to_node_ids};
+use crate::heartbeater::SafekeeperState;
+use crate::metrics;
+use crate::persistence::SafekeeperWalDivergencePersistence;
+use crate::safekeeper::Safekeeper;
+use crate::safekeeper_client::SafekeeperClient;
+
+/// How many timelines to verify per iteration.
+const VERIFY_BATCH_SIZE: i64 = 64;
+
+/// How many timelines of a batch to verify concurrently.
+const VERIFY_CONCURRENCY: usize = 8;
+
+/// At most this much WAL is digested per timeline and iteration, so that a
+/// timeline with a lot of new WAL doesn't hold up the others.
+const MAX_VERIFY_RANGE: u64 = 64 * 1024 * 1024;
+
+/// If the verification falls this far behind the committed WAL, skip ahead
+/// instead of catching up: the older WAL may be gone from the safekeepers' disks
+/// already, and digesting its copy in remote storage proves nothing.
+const MAX_VERIFY_LAG: u64 = 1024 * 1024 * 1024;
+
+/// Timeout of a single request to a safekeeper during the verification.
+const SK_VERIFY_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// WAL a member of the safekeeper set holds locally and knows to be committed.
+#[derive(Debug, Clone, Copy)]
+struct MemberWal {
+    local_start_lsn: Lsn,
+    committed_lsn: Lsn,
+}
+
+impl From<&TimelineStatus> for MemberWal {
+    fn from(status: &TimelineStatus) -> Self {
+        MemberWal {
+            local_start_lsn: status.local_start_lsn,
+            committed_lsn: std::cmp::min(status.commit_lsn, status.flush_lsn),
+        }
+    }
+}
+
+/// Pick the range of WAL to compare across the members: committed WAL all of them
+/// hold locally, starting where the previous check ended. Returns None if there is
+/// nothing new to compare.
+fn verification_range(verified_lsn: Option<Lsn>, members: &[MemberWal]) -> Option<(Lsn, Lsn)> {
+    let start = members.iter().map(|m| m.local_start_lsn).max()?;
+    let end = members.iter().map(|m| m.committed_lsn).min()?;
+    let from = match verified_lsn {
+        Some(lsn) if end.0.saturating_sub(lsn.0) <= MAX_VERIFY_LAG => lsn,
+        _ => Lsn(end.0.saturating_sub(MAX_VERIFY_RANGE)),
+    };
+    let from = std::cmp::max(from, start);
+    let until = std::cmp::min(end, from + MAX_VERIFY_RANGE);
+    (from < until).then_some((from, until))
+}
+
+impl Service {
+    /// Verify the newly committed WAL of a batch of timelines every `interval`,
+    /// cycling through all of them.
+    pub(super) async fn safekeeper_wal_verification_loop(self: &Arc<Self>, interval: Duration) {
+        let mut cursor = None;
+        let mut visited = HashSet::new();
+        let mut interval = tokio::time::interval(interval);
+        while !self.reconcilers_cancel.is_cancelled() {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = self.reconcilers_cancel.cancelled() => return,
+            }
+            if let Err(e) = self
+                .safekeeper_wal_verification_iteration(&mut cursor, &mut visited)
+                .await
+            {
+                tracing::warn!("safekeeper WAL verification failed: {e}");
+            }
+        }
+    }
+
+    async fn safekeeper_wal_verification_iteration(
+        self: &Arc<Self>,
+        cursor: &mut Option<TenantTimelineId>,
+        visited: &mut HashSet<TenantTimelineId>,
+    ) -> anyhow::Result<()> {
+        let timelines = self
+            .persistence
+            .list_timelines_after(*cursor, VERIFY_BATCH_SIZE)
+            .await?;
+        let pass_complete = timelines.len() < VERIFY_BATCH_SIZE as usize;
+
+        let (safekeepers, ongoing) = {
+            let locked = self.inner.read().unwrap();
+            (
+                locked.safekeepers.clone(),
+                locked
+                    .safekeeper_migrations
+                    .keys()
+                    .copied()
+                    .collect::<HashSet<_>>(),
+            )
+        };
+
+        let mut batch = Vec::with_capacity(timelines.len());
+        for timeline in timelines {
+            let ttid = timeline_ttid(&timeline)?;
+            *cursor = Some(ttid);
+            visited.insert(ttid);
+            // The membership is in flux, check the timeline once it settles.
+            if ongoing.contains(&ttid) {
+                continue;
+            }
+            batch.push((ttid, to_node_ids(&timeline.sk_set)));
+        }
+
+        futures::stream::iter(batch)
+            .for_each_concurrent(VERIFY_CONCURRENCY, |(ttid, sk_set)| {
+                let safekeepers = &safekeepers;
+                async move {
+                    if let Err(e) = self.verify_timeline_wal(safekeepers, ttid, sk_set).await {
+                        tracing::info!("skipping WAL verification: {e:#}");
+                    }
+                }
+                .instrument(tracing::info_span!(
+                    "safekeeper_wal_verification",
+                    tenant_id = %ttid.tenant_id,
+                    timeline_id = %ttid.timeline_id
+                ))
+            })
+            .await;
+
+        if pass_complete {
+            // Forget about the timelines which are gone. The ones being migrated
+            // aren't listed, but keep their divergence in case the migration is
+            // aborted.
+            *cursor = None;
+            let mut gone_divergences = Vec::new();
+            {
+                let mut locked = self.inner.write().unwrap();
+                let state = &mut *locked;
+                let ongoing = &state.safekeeper_migrations;
+                state.safekeeper_wal_verification.retain(|ttid, v| {
+                    let keep = visited.contains(ttid)
+                        || (v.divergence.is_some() && ongoing.contains_key(ttid));
+                    if !keep && v.divergence.is_some() {
+                        gone_divergences.push(*ttid);
+                    }
+                    keep
+                });
+                update_divergence_gauge(&state.safekeeper_wal_verification);
+            }
+            visited.clear();
+            for ttid in gone_divergences {
+                self.persistence
+                    .remove_safekeeper_wal_divergence(ttid)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compare the WAL committed since the previous check across all the members
+    /// of the timeline's safekeeper set.
+    async fn verify_timeline_wal(
+        &self,
+        safekeepers: &HashMap<NodeId, Safekeeper>,
+        ttid: TenantTimelineId,
+        sk_set: Vec<NodeId>,
+    ) -> anyhow::Result<()> {
+        let TenantTimelineId {
+            tenant_id,
+            timeline_id,
+        } = ttid;
+        let members = sk_set
+            .iter()
+            .map(|sk_id| {
+                safekeepers
+                    .get(sk_id)
+                    .with_context(|| format!("safekeeper {sk_id} not found"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        // Comparing with a part of the set only would leave the rest of it unverified
+        // while advancing the verified LSN, wait for all the members instead.
+        if let Some(sk) = members
+            .iter()
+            .find(|sk| matches!(sk.availability(), SafekeeperState::Offline))
+        {
+            tracing::debug!("safekeeper {} is offline", sk.get_id());
+            return Ok(());
+        }
+
+        let (previous, stored_divergence) = {
+            let locked = self.inner.read().unwrap();
+            let stored = locked.safekeeper_wal_verification.get(&ttid);
+            (
+                stored.filter(|v| same_members(&v.sk_set, &sk_set)).cloned(),
+                stored.is_some_and(|v| v.divergence.is_some()),
+            )
+        };
+        if previous.as_ref().is_some_and(|v| v.divergence.is_some()) {
+            return Ok(());
+        }
+
+        let statuses = self
+            .safekeepers_all_call(&members, |client| async move {
+                client.timeline_status(tenant_id, timeline_id).await
+            })
+            .await?;
+        let member_wal = statuses
+            .iter()
+            .map(|(_, status)| MemberWal::from(status))
+            .collect::<Vec<_>>();
+        let verified_lsn = previous.as_ref().map(|v| v.verified_lsn);
+        let Some((from_lsn, until_lsn)) = verification_range(verified_lsn, &member_wal) else {
+            return Ok(());
+        };
+
+        let digests = self
+            .safekeepers_all_call(&members, |client| async move {
+                client
+                    .timeline_digest(tenant_id, timeline_id, from_lsn, until_lsn)
+                    .await
+            })
+            .await?;
+
+        let now = chrono::Utc::now();
+        let verification = if digests.iter().map(|(_, d)| &d.sha256).all_equal() {
+            metrics::METRICS_REGISTRY
+                .metrics_group
+                .storage_controller_safekeeper_wal_verified_bytes
+                .inc_by(until_lsn.0 - from_lsn.0);
+            SafekeeperWalVerificationDescribe {
+                tenant_id,
+                timeline_id,
+                sk_set,
+                verified_lsn: until_lsn,
+                verified_at: now,
+                divergence: None,
+            }
+        } else {
+            tracing::error!(
+                "safekeepers hold different WAL in {from_lsn}..{until_lsn}: {}",
+                digests
+                    .iter()
+                    .map(|(sk_id, d)| format!("{sk_id}: {}", d.sha256))
+                    .join(", ")
+            );
+            SafekeeperWalVerificationDescribe {
+                tenant_id,
+                timeline_id,
+                sk_set,
+                verified_lsn: verified_lsn.unwrap_or(from_lsn),
+                verified_at: previous.as_ref().map(|v| v.verified_at).unwrap_or(now),
+                divergence: Some(SafekeeperWalDivergence {
+                    from_lsn,
+                    until_lsn,
+                    digests: digests
+                        .into_iter()
+                        .map(|(node_id, d)| SafekeeperWalDigest {
+                            node_id,
+                            sha256: d.sha256,
+                        })
+                        .collect(),
+                    detected_at: now,
+                }),
+            }
+        };
+
+        // Store the divergence before recording it: if that fails, it is found and
+        // stored on the next check rather than lost on restart.
+        if verification.divergence.is_some() {
+            self.persistence
+                .upsert_safekeeper_wal_divergence(SafekeeperWalDivergencePersistence::new(
+                    &verification,
+                ))
+                .await?;
+            metrics::METRICS_REGISTRY
+                .metrics_group
+                .storage_controller_safekeeper_wal_divergences
+                .inc();
+        } else if stored_divergence {
+            // The timeline has moved off the diverged safekeeper set.
+            self.persistence
+                .remove_safekeeper_wal_divergence(ttid)
+                .await?;
+        }
+
+        let mut locked = self.inner.write().unwrap();
+        locked
+            .safekeeper_wal_verification
+            .insert(ttid, verification);
+        update_divergence_gauge(&locked.safekeeper_wal_verification);
+        Ok(())
+    }
+
+    /// Call `op` on all the given safekeepers concurrently, failing if any of them
+    /// failed. Returns the responses in the order of `safekeepers`.
+    async fn safekeepers_all_call<T, O, F>(
+        &self,
+        safekeepers: &[&Safekeeper],
+        op: O,
+    ) -> anyhow::Result<Vec<(NodeId, T)>>
+    where
+        O: FnMut(SafekeeperClient) -> F + Clone,
+        F: std::future::Future<Output = mgmt_api::Result<T>>,
+    {
+        let jwt = self
+            .config
+            .safekeeper_jwt_token
+            .clone()
+            .map(SecretString::from);
+        let results = futures::future::join_all(safekeepers.iter().map(|sk| {
+            let op = op.clone();
+            let jwt = &jwt;
+            async move {
+                let res = sk
+                    .with_client_retries(
+                        op,
+                        &self.http_client,
+                        jwt,
+                        3,
+                        3,
+                        SK_VERIFY_REQUEST_TIMEOUT,
+                        &self.reconcilers_cancel,
+                    )
+                    .await;
+                (sk.get_id(), res)
+            }
+        }))
+        .await;
+        results
+            .into_iter()
+            .map(|(sk_id, res)| {
+                res.map(|resp| (sk_id, resp))
+                    .with_context(|| format!("request to safekeeper {sk_id} failed"))
+            })
+            .collect()
+    }
+
+    pub(crate) fn safekeeper_wal_divergences_list(&self) -> Vec<SafekeeperWalVerificationDescribe> {
+        let locked = self.inner.read().unwrap();
+        let mut list = locked
+            .safekeeper_wal_verification
+            .values()
+            .filter(|v| v.divergence.is_some())
+            .cloned()
+            .collect::<Vec<_>>();
+        list.sort_by_key(|v| v.divergence.as_ref().map(|d| d.detected_at));
+        list
+    }
+
+    pub(crate) fn timeline_safekeeper_wal_verification(
+        &self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+    ) -> Result<SafekeeperWalVerificationDescribe, ApiError> {
+        let locked = self.inner.read().unwrap();
+        locked
+            .safekeeper_wal_verification
+            .get(&TenantTimelineId::new(tenant_id, timeline_id))
+            .cloned()
+            .ok_or_else(|| {
+                ApiError::NotFound(
+                    anyhow::anyhow!(
+                        "WAL of timeline {tenant_id}/{timeline_id} hasn't been verified yet"
+                    )
+                    .into(),
+                )
+            })
+    }
+}
+
+pub(super) fn update_divergence_gauge(
+    verification: &HashMap<TenantTimelineId, SafekeeperWalVerificationDescribe>,
+) {
+    metrics::METRICS_REGISTRY
+        .metrics_group
+        .storage_controller_safekeeper_wal_divergent_timelines
+        .set(
+            verification
+                .values()
+                .filter(|v| v.divergence.is_some())
+                .count() as i64,
+        );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(wal: &[(u64, u64)]) -> Vec<MemberWal> {
+        wal.iter()
+            .map(|(local_start_lsn, committed_lsn)| MemberWal {
+                local_start_lsn: Lsn(*local_start_lsn),
+                committed_lsn: Lsn(*committed_lsn),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn verification_range_continues() {
+        let wal = members(&[(0x100, 0x5000), (0x100, 0x4000), (0x200, 0x6000)]);
+
+        // The first check starts where all the members have local WAL.
+        assert_eq!(
+            verification_range(None, &wal),
+            Some((Lsn(0x200), Lsn(0x4000)))
+        );
+        // Later ones continue from the previous check up to the lowest commit LSN.
+        assert_eq!(
+            verification_range(Some(Lsn(0x3000)), &wal),
+            Some((Lsn(0x3000), Lsn(0x4000)))
+        );
+        assert_eq!(verification_range(Some(Lsn(0x4000)), &wal), None);
+    }
+
+    #[test]
+    fn verification_range_bounded() {
+        let start = 0x1000;
+        let wal = members(&[(start, start + 3 * MAX_VERIFY_RANGE)]);
+
+        // A backlog is worked through in steps...
+        assert_eq!(
+            verification_range(Some(Lsn(start)), &wal),
+            Some((Lsn(start), Lsn(start + MAX_VERIFY_RANGE)))
+        );
+        // ...unless the check is so far behind that it skips to the recent WAL.
+        let end = start + 2 * MAX_VERIFY_LAG;
+        let wal = members(&[(start, end)]);
+        assert_eq!(
+            verification_range(Some(Lsn(start)), &wal),
+            Some((Lsn(end - MAX_VERIFY_RANGE), Lsn(end)))
+        );
+        assert_eq!(
+            verification_range(None, &wal),
+            Some((Lsn(end - MAX_VERIFY_RANGE), Lsn(end)))
+        );
+    }
+}
@@ -9,7 +9,8 @@
 use pageserver::tenant::remote_timeline_client::index::LayerFileMetadata;
 use pageserver::tenant::remote_timeline_client::manifest::TenantManifest;
 use pageserver::tenant::remote_timeline_client::{
-    parse_remote_index_path, parse_remote_tenant_manifest_path, remote_layer_path,
+    BASEBACKUPS_SEGMENT_NAME, parse_remote_index_path, parse_remote_tenant_manifest_path,
+    remote_layer_path_in_index,
 };
 use pageserver::tenant::storage_layer::LayerName;
 use pageserver_api::shard::ShardIndex;
@@ -150,13 +151,15 @@ pub(crate) async fn branch_cleanup_and_check_errors(
                             ))
                         }
 
-                        if !tenant_objects.check_ref(id.timeline_id, &layer, &metadata) {
-                            let path = remote_layer_path(
+                        // Layers referenced from another tenant's prefix are not in our listing.
+                        if metadata.cross_tenant_owner.is_some()
+                            || !tenant_objects.check_ref(id.timeline_id, &layer, &metadata)
+                        {
+                            let path = remote_layer_path_in_index(
                                 &id.tenant_shard_id.tenant_id,
                                 &id.timeline_id,
-                                metadata.shard,
                                 &layer,
-                                metadata.generation,
+                                &metadata,
                             );
 
                             // HEAD request used here to address a race condition  when an index was uploaded concurrently
@@ -185,6 +188,38 @@ pub(crate) async fn branch_cleanup_and_check_errors(
                             }
                         }
                     }
+
+                    // Timelines of other tenants may reference layers which we no longer do.
+                    for child in index_part.cross_tenant_children {
+                        for (layer, metadata) in child.layers {
+                            if tenant_objects.check_ref(id.timeline_id, &layer, &metadata) {
+                                continue;
+                            }
+                            let path = remote_layer_path_in_index(
+                                &id.tenant_shard_id.tenant_id,
+                                &id.timeline_id,
+                                &layer,
+                                &metadata,
+                            );
+                            let response = remote_client
+                                .head_object(&path, &CancellationToken::new())
+                                .await;
+                            if response.is_err() {
+                                let msg = format!(
+                                    "index_part.json records a layer {}{} (shard {}) referenced by cross-tenant child {} that is not present in remote storage",
+                                    layer,
+                                    metadata.generation.get_suffix(),
+                                    metadata.shard,
+                                    child.id(),
+                                );
+                                if ignore_error {
+                                    result.warnings.push(msg);
+                                } else {
+                                    result.errors.push(msg);
+                                }
+                            }
+                        }
+                    }
                 }
                 BlobDataParseResult::Relic => {}
                 BlobDataParseResult::Incorrect {
@@ -305,6 +340,9 @@ pub(crate) struct RemoteTimelineBlobData {
     /// Index objects that were not used when loading `blob_data`, e.g. those from old generations
     pub(crate) unused_index_keys: Vec<ListingObject>,
 
+    /// Basebackups prepared by the pageserver's basebackup cache
+    pub(crate) basebackup_keys: Vec<ListingObject>,
+
     /// Objects whose keys were not recognized at all, i.e. not layer files, not indices
     pub(crate) unknown_keys: Vec<ListingObject>,
 }
@@ -396,7 +434,9 @@ async fn list_timeline_blobs_impl(
     timeline_dir_target.delimiter = String::new();
 
     let mut index_part_keys: Vec<ListingObject> = Vec::new();
+    let mut basebackup_keys: Vec<ListingObject> = Vec::new();
     let mut initdb_archive: bool = false;
+    let basebackups_prefix = format!("{BASEBACKUPS_SEGMENT_NAME}/");
 
     let prefix_str = &timeline_dir_target
         .prefix_in_bucket
@@ -422,6 +462,10 @@ async fn list_timeline_blobs_impl(
             Some("initdb-preserved.tar.zst") => {
                 tracing::info!("initdb archive preserved {key}");
             }
+            Some(name) if name.starts_with(&basebackups_prefix) => {
+                tracing::debug!("Basebackup key {key}");
+                basebackup_keys.push(obj);
+            }
             Some(maybe_layer_name) => match parse_layer_object_name(maybe_layer_name) {
                 Ok((new_layer, gen_)) => {
                     tracing::debug!("Parsed layer key: {new_layer} {gen_:?}");
@@ -448,6 +492,7 @@ async fn list_timeline_blobs_impl(
         return Ok(ListTimelineBlobsResult::Ready(RemoteTimelineBlobData {
             blob_data: BlobDataParseResult::Relic,
             unused_index_keys: index_part_keys,
+            basebackup_keys,
             unknown_keys,
         }));
     }
@@ -486,6 +531,7 @@ async fn list_timeline_blobs_impl(
             return Ok(ListTimelineBlobsResult::Ready(RemoteTimelineBlobData {
                 blob_data: BlobDataParseResult::Relic,
                 unused_index_keys: index_part_keys,
+                basebackup_keys,
                 unknown_keys,
             }));
         }
@@ -503,6 +549,7 @@ async fn list_timeline_blobs_impl(
                         RemoteTimelineBlobData {
                             blob_data: BlobDataParseResult::Incorrect { errors, s3_layers },
                             unused_index_keys: index_part_keys,
+                            basebackup_keys,
                             unknown_keys,
                         },
                     ));
@@ -520,6 +567,7 @@ async fn list_timeline_blobs_impl(
                         index_part_snapshot_time,
                     },
                     unused_index_keys: index_part_keys,
+                    basebackup_keys,
                     unknown_keys,
                 }));
             }
@@ -538,6 +586,7 @@ async fn list_timeline_blobs_impl(
     Ok(ListTimelineBlobsResult::Ready(RemoteTimelineBlobData {
         blob_data: BlobDataParseResult::Incorrect { errors, s3_layers },
         unused_index_keys: index_part_keys,
+        basebackup_keys,
         unknown_keys,
     }))
 }
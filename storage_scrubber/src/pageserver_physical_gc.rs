@@ -5,6 +5,7 @@
 use async_stream::try_stream;
 use futures::future::Either;
 use futures_util::{StreamExt, TryStreamExt};
+use pageserver::basebackup_cache::parse_remote_object_name;
 use pageserver::tenant::IndexPart;
 use pageserver::tenant::remote_timeline_client::index::LayerFileMetadata;
 use pageserver::tenant::remote_timeline_client::manifest::OffloadedTimelineManifest;
@@ -38,6 +39,7 @@ pub struct GcSummary {
     remote_storage_errors: usize,
     controller_api_errors: usize,
     ancestor_layers_deleted: usize,
+    basebackups_deleted: usize,
 }
 
 impl GcSummary {
@@ -48,6 +50,7 @@ fn merge(&mut self, other: Self) {
             remote_storage_errors,
             ancestor_layers_deleted,
             controller_api_errors,
+            basebackups_deleted,
         } = other;
 
         self.indices_deleted += indices_deleted;
@@ -55,6 +58,7 @@ fn merge(&mut self, other: Self) {
         self.remote_storage_errors += remote_storage_errors;
         self.ancestor_layers_deleted += ancestor_layers_deleted;
         self.controller_api_errors += controller_api_errors;
+        self.basebackups_deleted += basebackups_deleted;
     }
 }
 
@@ -152,6 +156,15 @@ fn update(&mut self, ttid: TenantShardTimelineId, index_part: &IndexPart) {
             }
         }
 
+        // Timelines of other tenants may reference layers of ancestor shards which we no longer do.
+        for child in &index_part.cross_tenant_children {
+            for (layer_name, layer_metadata) in &child.layers {
+                if layer_metadata.shard != this_shard_idx {
+                    ancestor_refs.push((layer_name.clone(), layer_metadata.clone()));
+                }
+            }
+        }
+
         tracing::info!(%ttid, "Found {} ancestor refs", ancestor_refs.len());
         self.ancestor_ref_shards.update(ttid, ancestor_refs);
     }
@@ -362,6 +375,60 @@ async fn maybe_delete_index(
     }
 }
 
+/// Delete a basebackup prepared by the pageserver's basebackup cache, if nothing can be using it
+/// any more: it was uploaded by a generation before the latest one's predecessor, or
+/// `latest_gen` is None because it belongs to an ancestor shard.
+async fn maybe_delete_basebackup(
+    remote_client: &GenericRemoteStorage,
+    min_age: &Duration,
+    latest_gen: Option<Generation>,
+    obj: &ListingObject,
+    mode: GcMode,
+    summary: &mut GcSummary,
+) {
+    // Validation: we will only delete things that parse cleanly
+    let Some((_lsn, _compression, candidate_generation)) = obj
+        .key
+        .get_path()
+        .file_name()
+        .and_then(parse_remote_object_name)
+    else {
+        tracing::warn!("Bad basebackup key");
+        return;
+    };
+
+    // Like for indices, leave the latest-1th generation's objects to the pageserver, which
+    // deletes superseded basebackups itself.
+    if let Some(latest_gen) = latest_gen {
+        if candidate_generation >= latest_gen || candidate_generation.next() == latest_gen {
+            return;
+        }
+    }
+
+    if !is_old_enough(min_age, obj, summary) {
+        return;
+    }
+
+    if !matches!(mode, GcMode::Full) {
+        tracing::info!("Dry run: would delete this key");
+        return;
+    }
+
+    match remote_client
+        .delete(&obj.key, &CancellationToken::new())
+        .await
+    {
+        Ok(_) => {
+            tracing::info!("Successfully deleted basebackup");
+            summary.basebackups_deleted += 1;
+        }
+        Err(e) => {
+            tracing::warn!("Failed to delete basebackup: {e}");
+            summary.remote_storage_errors += 1;
+        }
+    }
+}
+
 async fn maybe_delete_tenant_manifest(
     remote_client: &GenericRemoteStorage,
     min_age: &Duration,
@@ -446,6 +513,13 @@ async fn gc_ancestor(
 
         let data = list_timeline_blobs(remote_client, ttid, root_target).await?;
 
+        // Children of a split never use their ancestor's basebackups
+        for key in &data.basebackup_keys {
+            maybe_delete_basebackup(remote_client, min_age, None, key, mode, summary)
+                .instrument(info_span!("maybe_delete_basebackup", %ttid, %key.key))
+                .await;
+        }
+
         let s3_layers = match data.blob_data {
             BlobDataParseResult::Parsed {
                 index_part: _,
@@ -585,14 +659,19 @@ async fn gc_timeline(
     let mut summary = GcSummary::default();
     let data = list_timeline_blobs(remote_client, ttid, target).await?;
 
-    let (index_part, latest_gen, candidates) = match &data.blob_data {
+    let (index_part, latest_gen, candidates, basebackups) = match &data.blob_data {
         BlobDataParseResult::Parsed {
             index_part,
             index_part_generation,
             s3_layers: _,
             index_part_last_modified_time: _,
             index_part_snapshot_time: _,
-        } => (index_part, *index_part_generation, data.unused_index_keys),
+        } => (
+            index_part,
+            *index_part_generation,
+            data.unused_index_keys,
+            data.basebackup_keys,
+        ),
         BlobDataParseResult::Relic => {
             // Post-deletion tenant location: don't try and GC it.
             return Ok(summary);
@@ -669,6 +748,19 @@ async fn gc_timeline(
             .await;
     }
 
+    for key in basebackups {
+        maybe_delete_basebackup(
+            remote_client,
+            min_age,
+            Some(latest_gen),
+            &key,
+            mode,
+            &mut summary,
+        )
+        .instrument(info_span!("maybe_delete_basebackup", %ttid, ?latest_gen, %key.key))
+        .await;
+    }
+
     Ok(summary)
 }
 
@@ -708,6 +800,8 @@ fn validate_index_part_with_offloaded(
 /// - Objects that were uploaded but never referenced in the remote index (e.g. because of a shutdown between
 ///   uploading a layer and uploading an index)
 /// - Index objects and tenant manifests from historic generations
+/// - Basebackups prepared by the pageserver's basebackup cache in historic generations, or in
+///   ancestor shards
 ///
 /// This type of GC is not necessary for correctness: rather it serves to reduce wasted storage capacity, and
 /// make sure that object listings don't get slowed down by large numbers of garbage objects.
@@ -24,6 +24,9 @@
 /// Generally we should ask safekeepers, but so far we use everywhere default 16MB.
 const WAL_SEGSIZE: usize = 16 * 1024 * 1024;
 
+/// Suffix of zstd compressed segments, see `--wal-backup-compression` of safekeepers.
+const COMPRESSED_SEGMENT_SUFFIX: &str = ".zst";
+
 #[derive(Serialize)]
 pub struct MetadataSummary {
     timeline_count: usize,
@@ -187,6 +190,10 @@ async fn check_timeline(
             .as_str()
             .strip_prefix(prefix_str)
             .expect("failed to extract segment name");
+        // A segment counts whether it was offloaded compressed or not.
+        let seg_name = seg_name
+            .strip_suffix(COMPRESSED_SEGMENT_SUFFIX)
+            .unwrap_or(seg_name);
         expected_segfiles.remove(seg_name);
     }
     if !expected_segfiles.is_empty() {
@@ -6,7 +6,7 @@ from typing import TYPE_CHECKING
 import pytest
 from werkzeug.wrappers.response import Response
 
-from fixtures.common_types import TenantId
+from fixtures.common_types import TenantId, TimelineId
 from fixtures.log_helper import log
 
 if TYPE_CHECKING:
@@ -77,7 +77,26 @@ def compute_reconfigure_listener(make_httpserver: HTTPServer):
 
         return Response(status=200)
 
+    def safekeepers_handler(request: Request) -> Response:
+        assert request.json is not None
+        body: dict[str, Any] = request.json
+        log.info(f"notify-safekeepers request: {body}")
+
+        if self.on_notify is not None:
+            self.on_notify(body)
+
+        workload = self.workloads.get(TenantId(body["tenant_id"]))
+        if workload is not None and workload.timeline_id == TimelineId(body["timeline_id"]):
+            safekeepers = [sk["id"] for sk in body["safekeepers"]]
+            fut = reconfigure_threads.submit(workload.reconfigure, safekeepers)
+            fut.result()
+
+        return Response(status=200)
+
     self.server.expect_request("/notify-attach", method="PUT").respond_with_handler(handler)
+    self.server.expect_request("/notify-safekeepers", method="PUT").respond_with_handler(
+        safekeepers_handler
+    )
 
     yield self
     reconfigure_threads.shutdown()
@@ -2241,6 +2241,18 @@ class NeonStorageController(MetricsGetter, LogUtils):
         shards: list[TenantShardId] = body["new_shards"]
         return shards
 
+    def tenant_shard_merge(self, tenant_id: TenantId, shard_count: int) -> list[TenantShardId]:
+        response = self.request(
+            "PUT",
+            f"{self.api}/control/v1/tenant/{tenant_id}/shard_merge",
+            json={"new_shard_count": shard_count},
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        body = response.json()
+        log.info(f"tenant_shard_merge success: {body}")
+        shards: list[TenantShardId] = body["new_shards"]
+        return shards
+
     def tenant_shard_migrate(
         self,
         tenant_shard_id: TenantShardId,
@@ -2595,6 +2607,95 @@ class NeonStorageController(MetricsGetter, LogUtils):
         assert isinstance(json, list)
         return json
 
+    def timeline_safekeeper_migrate(
+        self, tenant_id: TenantId, timeline_id: TimelineId, new_sk_set: list[int]
+    ):
+        self.request(
+            "PUT",
+            f"{self.api}/control/v1/tenant/{tenant_id}/timeline/{timeline_id}/safekeeper_migrate",
+            headers=self.headers(TokenScope.ADMIN),
+            json={"new_sk_set": new_sk_set},
+        )
+
+    def safekeeper_migrations(self) -> list[dict[str, Any]]:
+        response = self.request(
+            "GET",
+            f"{self.api}/control/v1/safekeeper_migrations",
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        json = response.json()
+        assert isinstance(json, list)
+        return json
+
+    def safekeeper_wal_divergences(self) -> list[dict[str, Any]]:
+        response = self.request(
+            "GET",
+            f"{self.api}/control/v1/safekeeper_wal_divergences",
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        json = response.json()
+        assert isinstance(json, list)
+        return json
+
+    def timeline_safekeeper_wal_verification(
+        self, tenant_id: TenantId, timeline_id: TimelineId
+    ) -> dict[str, Any]:
+        response = self.request(
+            "GET",
+            f"{self.api}/control/v1/tenant/{tenant_id}/timeline/{timeline_id}/safekeeper_wal_verification",
+            headers=self.headers(TokenScope.ADMIN),
+        )
+        json = response.json()
+        assert isinstance(json, dict)
+        return json
+
+    def safekeeper_drain(self, id: int):
+        log.info(f"safekeeper_drain({id})")
+        self.request(
+            "PUT",
+            f"{self.api}/control/v1/safekeeper/{id}/drain",
+            headers=self.headers(TokenScope.INFRA),
+        )
+
+    def cancel_safekeeper_drain(self, id: int):
+        log.info(f"cancel_safekeeper_drain({id})")
+        self.request(
+            "DELETE",
+            f"{self.api}/control/v1/safekeeper/{id}/drain",
+            headers=self.headers(TokenScope.INFRA),
+        )
+
+    def safekeeper_fill(self, id: int):
+        log.info(f"safekeeper_fill({id})")
+        self.request(
+            "PUT",
+            f"{self.api}/control/v1/safekeeper/{id}/fill",
+            headers=self.headers(TokenScope.INFRA),
+        )
+
+    def cancel_safekeeper_fill(self, id: int):
+        log.info(f"cancel_safekeeper_fill({id})")
+        self.request(
+            "DELETE",
+            f"{self.api}/control/v1/safekeeper/{id}/fill",
+            headers=self.headers(TokenScope.INFRA),
+        )
+
+    def safekeeper_operation(self, id: int) -> dict[str, Any] | None:
+        try:
+            response = self.request(
+                "GET",
+                f"{self.api}/control/v1/safekeeper/{id}/operation",
+                headers=self.headers(TokenScope.INFRA),
+            )
+            json = response.json()
+            assert isinstance(json, dict)
+            return json
+        except StorageControllerApiException as e:
+            if e.status_code == 404:
+                return None
+            raise e
+
     def set_preferred_azs(self, preferred_azs: dict[TenantShardId, str]) -> list[TenantShardId]:
         response = self.request(
             "PUT",
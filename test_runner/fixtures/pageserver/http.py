@@ -460,6 +460,20 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         self.verbose_error(res)
         return TenantConfig.from_json(res.json())
 
+    def tenant_disk_quota(self, tenant_id: TenantId | TenantShardId) -> dict[str, Any]:
+        res = self.get(f"http://localhost:{self.port}/v1/tenant/{tenant_id}/disk_quota")
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
+    def tenant_scrub_status(self, tenant_id: TenantId | TenantShardId) -> dict[str, Any]:
+        res = self.get(f"http://localhost:{self.port}/v1/tenant/{tenant_id}/scrub")
+        self.verbose_error(res)
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
     def tenant_heatmap_upload(self, tenant_id: TenantId | TenantShardId):
         res = self.post(f"http://localhost:{self.port}/v1/tenant/{tenant_id}/heatmap_upload")
         self.verbose_error(res)
@@ -618,11 +632,14 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         ancestor_timeline_id: TimelineId | None = None,
         ancestor_start_lsn: Lsn | None = None,
         existing_initdb_timeline_id: TimelineId | None = None,
+        ancestor_tenant_id: TenantId | None = None,
         **kwargs,
     ) -> dict[Any, Any]:
         body: dict[str, Any] = {
             "new_timeline_id": str(new_timeline_id),
         }
+        if ancestor_tenant_id:
+            body["ancestor_tenant_id"] = str(ancestor_tenant_id)
         if ancestor_timeline_id:
             body["ancestor_timeline_id"] = str(ancestor_timeline_id)
         if ancestor_start_lsn:
@@ -714,6 +731,18 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         assert isinstance(res_json, dict)
         return res_json
 
+    def timeline_retention_policy(
+        self,
+        tenant_id: TenantId | TenantShardId,
+        timeline_id: TimelineId,
+        retention_policy: dict[str, Any] | None,
+    ):
+        res = self.put(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/retention_policy",
+            json={"retention_policy": retention_policy},
+        )
+        self.verbose_error(res)
+
     def timeline_block_gc(self, tenant_id: TenantId | TenantShardId, timeline_id: TimelineId):
         res = self.post(
             f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/block_gc",
@@ -998,6 +1027,60 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
                 assert completed["successful_download_count"] > 0
             return completed
 
+    def timeline_spawn_export_pgdata(
+        self,
+        tenant_id: TenantId | TenantShardId,
+        timeline_id: TimelineId,
+        location: dict[str, Any],
+        lsn: Lsn | None = None,
+    ) -> dict[str, Any]:
+        body: dict[str, Any] = {"location": location}
+        if lsn is not None:
+            body["lsn"] = str(lsn)
+        res = self.post(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/export_pgdata",
+            json=body,
+        )
+        self.verbose_error(res)
+        res_json = res.json()
+        assert res_json is not None
+        assert isinstance(res_json, dict)
+        return res_json
+
+    def timeline_export_pgdata_status(
+        self,
+        tenant_id: TenantId | TenantShardId,
+        timeline_id: TimelineId,
+    ) -> dict[str, Any]:
+        res = self.get(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/export_pgdata",
+        )
+        self.verbose_error(res)
+        res_json = res.json()
+        assert res_json is not None
+        assert isinstance(res_json, dict)
+        return res_json
+
+    def timeline_export_pgdata(
+        self,
+        tenant_id: TenantId | TenantShardId,
+        timeline_id: TimelineId,
+        location: dict[str, Any],
+        lsn: Lsn | None = None,
+    ) -> dict[str, Any]:
+        """
+        Export the timeline and wait for the export to finish.
+        """
+        spawned = self.timeline_spawn_export_pgdata(tenant_id, timeline_id, location, lsn)
+        while True:
+            status = self.timeline_export_pgdata_status(tenant_id, timeline_id)
+            assert status["task_id"] == spawned["task_id"]
+            if status["state"] == "Running":
+                time.sleep(0.1)
+                continue
+            assert status["state"] == "Completed", f"export failed: {status}"
+            return status
+
     def get_metrics_str(self) -> str:
         """You probably want to use get_metrics() instead."""
         res = self.get(f"http://localhost:{self.port}/metrics")
@@ -1108,6 +1191,20 @@ class PageserverHttpClient(requests.Session, MetricsGetter):
         json = res.json()
         return set(map(TimelineId, json["reparented_timelines"]))
 
+    def timeline_reset_to_lsn(
+        self,
+        tenant_id: TenantId | TenantShardId,
+        timeline_id: TimelineId,
+        lsn: Lsn,
+        **kwargs,
+    ):
+        res = self.put(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/reset_to_lsn",
+            json={"lsn": str(lsn)},
+            **kwargs,
+        )
+        self.verbose_error(res)
+
     def evict_layer(
         self, tenant_id: TenantId | TenantShardId, timeline_id: TimelineId, layer_name: str
     ):
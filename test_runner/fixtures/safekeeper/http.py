@@ -367,6 +367,42 @@ class SafekeeperHttpClient(requests.Session, MetricsGetter):
         res.raise_for_status()
         return TermBumpResponse.from_json(res.json())
 
+    def replication_slot_create(
+        self,
+        tenant_id: TenantId,
+        timeline_id: TimelineId,
+        name: str,
+        restart_lsn: Lsn | None = None,
+    ) -> dict[str, Any]:
+        body: dict[str, Any] = {"name": name}
+        if restart_lsn is not None:
+            body["restart_lsn"] = str(restart_lsn)
+        res = self.post(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/replication_slots",
+            json=body,
+        )
+        res.raise_for_status()
+        res_json = res.json()
+        assert isinstance(res_json, dict)
+        return res_json
+
+    def replication_slot_drop(self, tenant_id: TenantId, timeline_id: TimelineId, name: str):
+        res = self.delete(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/replication_slots/{name}",
+        )
+        res.raise_for_status()
+
+    def replication_slots(
+        self, tenant_id: TenantId, timeline_id: TimelineId
+    ) -> list[dict[str, Any]]:
+        res = self.get(
+            f"http://localhost:{self.port}/v1/tenant/{tenant_id}/timeline/{timeline_id}/replication_slots",
+        )
+        res.raise_for_status()
+        res_json = res.json()
+        assert isinstance(res_json, list)
+        return res_json
+
     def record_safekeeper_info(self, tenant_id: TenantId, timeline_id: TimelineId, body):
         res = self.post(
             f"http://localhost:{self.port}/v1/record_safekeeper_info/{tenant_id}/{timeline_id}",
@@ -72,13 +72,14 @@ class Workload:
         branch_workload.churn_cursor = self.churn_cursor
         return branch_workload
 
-    def reconfigure(self) -> None:
+    def reconfigure(self, safekeepers: list[int] | None = None) -> None:
         """
-        Request the endpoint to reconfigure based on location reported by storage controller
+        Request the endpoint to reconfigure based on location reported by storage controller,
+        and optionally switch it to the given safekeepers.
         """
         if self._endpoint is not None:
             with ENDPOINT_LOCK:
-                self._endpoint.reconfigure()
+                self._endpoint.reconfigure(safekeepers=safekeepers)
 
     def endpoint(self, pageserver_id: int | None = None) -> Endpoint:
         # We may be running alongside other Workloads for different tenants.  Full TTID is
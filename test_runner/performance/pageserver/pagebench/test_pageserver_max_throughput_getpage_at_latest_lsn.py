@@ -48,7 +48,7 @@ def test_pageserver_characterize_throughput_with_n_tenants(
     duration: int,
 ):
     setup_and_run_pagebench_benchmark(
-        neon_env_builder, zenbenchmark, pg_bin, n_tenants, pgbench_scale, duration, 1
+        neon_env_builder, zenbenchmark, pg_bin, n_tenants, pgbench_scale, duration, 1, None
     )
 
 
@@ -62,6 +62,8 @@ def test_pageserver_characterize_throughput_with_n_tenants(
 # which by default uses 64 connections
 @pytest.mark.parametrize("n_clients", [1, 64])
 @pytest.mark.parametrize("n_tenants", [1])
+# lz4 trades compression ratio for cheaper decompression on the getpage path
+@pytest.mark.parametrize("image_compression", ["zstd(1)", "lz4"])
 @pytest.mark.timeout(2400)
 @skip_on_ci(
     "This test needs lot of resources and should run on dedicated HW, not in github action runners as part of CI"
@@ -74,9 +76,17 @@ def test_pageserver_characterize_latencies_with_1_client_and_throughput_with_man
     pgbench_scale: int,
     duration: int,
     n_clients: int,
+    image_compression: str,
 ):
     setup_and_run_pagebench_benchmark(
-        neon_env_builder, zenbenchmark, pg_bin, n_tenants, pgbench_scale, duration, n_clients
+        neon_env_builder,
+        zenbenchmark,
+        pg_bin,
+        n_tenants,
+        pgbench_scale,
+        duration,
+        n_clients,
+        image_compression,
     )
 
 
@@ -88,6 +98,7 @@ def setup_and_run_pagebench_benchmark(
     pgbench_scale: int,
     duration: int,
     n_clients: int,
+    image_compression: str | None,
 ):
     def record(metric, **kwargs):
         zenbenchmark.record(
@@ -109,9 +120,15 @@ def setup_and_run_pagebench_benchmark(
     # configure cache sizes like in prod
     page_cache_size = 16384
     max_file_descriptors = 500000
-    neon_env_builder.pageserver_config_override = (
+    config_override = (
         f"page_cache_size={page_cache_size}; max_file_descriptors={max_file_descriptors}"
     )
+    snapshot_name = f"max_throughput_latest_lsn-{n_tenants}-{pgbench_scale}"
+    if image_compression is not None:
+        # image layers are written while creating the snapshot, so each algorithm needs its own
+        config_override += f"; image_compression='{image_compression}'"
+        snapshot_name += f"-{image_compression}"
+    neon_env_builder.pageserver_config_override = config_override
 
     tracing_config = PageserverTracingConfig(
         sampling_ratio=(0, 1000),
@@ -137,7 +154,7 @@ def setup_and_run_pagebench_benchmark(
 
     env = setup_pageserver_with_tenants(
         neon_env_builder,
-        f"max_throughput_latest_lsn-{n_tenants}-{pgbench_scale}",
+        snapshot_name,
         n_tenants,
         lambda env: setup_tenant_template(env, pg_bin, pgbench_scale),
         # https://github.com/neondatabase/neon/issues/8070
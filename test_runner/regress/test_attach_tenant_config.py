@@ -169,6 +169,7 @@ def test_fully_custom_config(positive_env: NeonEnv):
         "lazy_slru_download": True,
         "max_lsn_wal_lag": 230000,
         "min_resident_size_override": 23,
+        "max_resident_size": 1024 * 1024 * 1024 * 1024,
         "timeline_get_throttle": {
             "task_kinds": ["PageRequestHandler"],
             "initial": 0,
@@ -176,6 +177,13 @@ def test_fully_custom_config(positive_env: NeonEnv):
             "refill_amount": 1000,
             "max": 1000,
         },
+        "wal_ingest_throttle": {
+            "enabled": True,
+            "initial": 0,
+            "refill_interval": "1s",
+            "refill_amount": 16777216,
+            "max": 16777216,
+        },
         "walreceiver_connect_timeout": "13m",
         "image_layer_creation_check_threshold": 1,
         "lsn_lease_length": "1m",
@@ -194,6 +202,10 @@ def test_fully_custom_config(positive_env: NeonEnv):
             "numerator": 0,
             "denominator": 10,
         },
+        "delta_compression": "zstd(1)",
+        "eviction_priority": 3,
+        "scrub_period": "1h",
+        "scrub_bytes_per_second": 1024 * 1024,
     }
 
     vps_http = env.storage_controller.pageserver_api()
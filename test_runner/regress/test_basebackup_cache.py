@@ -0,0 +1,139 @@
+from __future__ import annotations
+
+from typing import Any
+
+from fixtures.log_helper import log
+from fixtures.neon_fixtures import NeonEnvBuilder
+from fixtures.pageserver.utils import timeline_delete_wait_completed
+from fixtures.remote_storage import LocalFsStorage, RemoteStorageKind
+from fixtures.utils import wait_until
+
+
+def enable_basebackup_cache(neon_env_builder: NeonEnvBuilder):
+    neon_env_builder.enable_pageserver_remote_storage(RemoteStorageKind.LOCAL_FS)
+
+    def patch_pageserver_toml(ps_cfg: dict[str, Any]):
+        ps_cfg["basebackup_cache"] = {
+            "max_total_size_bytes": 64 * 1024 * 1024,
+            "compressions": ["gzip", "zstd"],
+            "remote_storage": True,
+        }
+
+    neon_env_builder.pageserver_config_override = patch_pageserver_toml
+
+
+def test_basebackup_cache(neon_env_builder: NeonEnvBuilder):
+    """
+    A compute shutdown prepares basebackups at the shutdown checkpoint, the next compute
+    start is served from the local cache, and after a pageserver restart from remote storage.
+    """
+    enable_basebackup_cache(neon_env_builder)
+    env = neon_env_builder.init_start()
+    client = env.pageserver.http_client()
+
+    def metric(name: str, filter: dict[str, str] | None = None) -> float:
+        return client.get_metric_value(f"pageserver_basebackup_cache_{name}", filter) or 0
+
+    def lookups(outcome: str) -> float:
+        return metric("lookups_total", {"outcome": outcome})
+
+    endpoint = env.endpoints.create_start("main")
+    with endpoint.cursor() as cur:
+        cur.execute("CREATE TABLE foo (id int PRIMARY KEY, n int)")
+        cur.execute("INSERT INTO foo SELECT g, 0 FROM generate_series(1, 10000) g")
+
+    def stop_and_wait_prepared():
+        prepared = metric("prepared_total")
+        endpoint.stop()
+
+        def prepared_both():
+            # One basebackup per configured compression
+            assert metric("prepared_total") >= prepared + 2
+
+        wait_until(prepared_both)
+
+    def start_and_check():
+        endpoint.start()
+        with endpoint.cursor() as cur:
+            cur.execute("SELECT count(*) FROM foo")
+            assert cur.fetchone() == (10000,)
+
+    stop_and_wait_prepared()
+    start_and_check()
+    log.info(f"local hits: {lookups('hit_local')}, misses: {lookups('miss')}")
+    assert lookups("hit_local") == 1
+
+    # The restarted pageserver starts with an empty local cache, and downloads the basebackup
+    stop_and_wait_prepared()
+    env.pageserver.restart()
+    start_and_check()
+    assert lookups("hit_remote") == 1
+
+    # Writes after the cached LSN are not lost: the compute starts at the new shutdown checkpoint
+    with endpoint.cursor() as cur:
+        cur.execute("UPDATE foo SET n = 1")
+    endpoint.stop()
+    endpoint.start()
+    with endpoint.cursor() as cur:
+        cur.execute("SELECT sum(n) FROM foo")
+        assert cur.fetchone() == (10000,)
+
+    assert metric("prepare_errors_total") == 0
+
+
+def test_basebackup_cache_remote_objects(neon_env_builder: NeonEnvBuilder):
+    """
+    Remote basebackups live within the timeline's prefix, carry the uploading generation in
+    their names, and are deleted along with the timeline.
+    """
+    enable_basebackup_cache(neon_env_builder)
+    env = neon_env_builder.init_start()
+    client = env.pageserver.http_client()
+    remote_storage = env.pageserver_remote_storage
+    assert isinstance(remote_storage, LocalFsStorage)
+
+    tenant_id = env.initial_tenant
+    timeline_id = env.create_branch("child")
+    basebackups_path = remote_storage.timeline_path(tenant_id, timeline_id) / "basebackups"
+
+    def remote_basebackups() -> list[str]:
+        if not basebackups_path.exists():
+            return []
+        return sorted(p.name for p in basebackups_path.iterdir())
+
+    endpoint = env.endpoints.create_start("child")
+    with endpoint.cursor() as cur:
+        cur.execute("CREATE TABLE foo AS SELECT g FROM generate_series(1, 1000) g")
+    endpoint.stop()
+
+    def uploaded_both():
+        names = remote_basebackups()
+        log.info(f"remote basebackups: {names}")
+        assert len(names) == 2
+
+    wait_until(uploaded_both)
+
+    # The pageserver restart attaches a new generation: it uploads objects of its own, and
+    # deletes the superseded ones of the previous generation.
+    generation = remote_storage.timeline_latest_generation(tenant_id, timeline_id)
+    assert generation is not None
+    for name in remote_basebackups():
+        assert name.endswith(f"-{generation:08x}")
+
+    env.pageserver.restart()
+    endpoint.start()
+    with endpoint.cursor() as cur:
+        cur.execute("INSERT INTO foo SELECT g FROM generate_series(1, 1000) g")
+    endpoint.stop()
+
+    def uploaded_in_new_generation():
+        names = remote_basebackups()
+        log.info(f"remote basebackups: {names}")
+        assert len(names) == 2
+        assert all(not name.endswith(f"-{generation:08x}") for name in names)
+
+    wait_until(uploaded_in_new_generation)
+
+    endpoint.stop_and_destroy()
+    timeline_delete_wait_completed(client, tenant_id, timeline_id)
+    assert not basebackups_path.exists() or remote_basebackups() == []
@@ -0,0 +1,133 @@
+from __future__ import annotations
+
+from typing import Any
+
+import pytest
+from fixtures.common_types import Lsn, TenantId, TimelineId
+from fixtures.neon_fixtures import NeonEnvBuilder, wait_for_last_flush_lsn
+from fixtures.pageserver.http import PageserverApiException
+from fixtures.pageserver.utils import wait_timeline_detail_404
+from fixtures.remote_storage import LocalFsStorage, RemoteStorageKind
+
+
+def test_cross_tenant_branch(neon_env_builder: NeonEnvBuilder):
+    """
+    Branch a timeline off a timeline of another tenant, without copying the layers of the ancestor.
+    """
+    neon_env_builder.enable_pageserver_remote_storage(RemoteStorageKind.LOCAL_FS)
+    env = neon_env_builder.init_start()
+    src_tenant_id = env.initial_tenant
+    src_timeline_id = env.initial_timeline
+    ps_http = env.pageserver.http_client()
+    remote_storage = env.pageserver_remote_storage
+    assert isinstance(remote_storage, LocalFsStorage)
+
+    def layer_exists(tenant_id: TenantId, timeline_id: TimelineId, name: str, meta: Any) -> bool:
+        path = remote_storage.timeline_path(tenant_id, timeline_id) / name
+        if meta.get("generation") is not None:
+            path = path.with_name(f"{name}-{meta['generation']:08x}")
+        return path.exists()
+
+    endpoint = env.endpoints.create_start("main")
+    endpoint.safe_psql("CREATE TABLE t (id int PRIMARY KEY, data text)")
+    endpoint.safe_psql("INSERT INTO t SELECT g, 'before' FROM generate_series(1, 10000) g")
+    wait_for_last_flush_lsn(env, endpoint, src_tenant_id, src_timeline_id)
+    ps_http.timeline_checkpoint(src_tenant_id, src_timeline_id)
+    endpoint.safe_psql("INSERT INTO t SELECT g, 'before' FROM generate_series(10001, 20000) g")
+    branch_lsn = wait_for_last_flush_lsn(env, endpoint, src_tenant_id, src_timeline_id)
+
+    dst_tenant_id = TenantId.generate()
+    dst_timeline_id = TimelineId.generate()
+    env.storage_controller.tenant_create(dst_tenant_id)
+    ps_http.timeline_create(
+        env.pg_version,
+        dst_tenant_id,
+        dst_timeline_id,
+        ancestor_tenant_id=src_tenant_id,
+        ancestor_timeline_id=src_timeline_id,
+        ancestor_start_lsn=branch_lsn,
+    )
+    detail = ps_http.timeline_detail(dst_tenant_id, dst_timeline_id)
+    assert detail["ancestor_timeline_id"] is None
+    assert Lsn(detail["last_record_lsn"]) == branch_lsn
+
+    # The source records exactly the layers the branch references.
+    [child] = remote_storage.index_content(src_tenant_id, src_timeline_id)[
+        "cross_tenant_children"
+    ]
+    assert child["tenant_id"] == str(dst_tenant_id)
+    assert child["timeline_id"] == str(dst_timeline_id)
+    assert Lsn(child["lsn"]) == branch_lsn
+    dst_index = remote_storage.index_content(dst_tenant_id, dst_timeline_id)
+    referenced = {
+        name for name, meta in dst_index["layer_metadata"].items() if "cross_tenant_owner" in meta
+    }
+    assert referenced
+    assert set(child["layers"]) == referenced
+
+    # Writes to the source after the branch point are not visible on the branch.
+    endpoint.safe_psql("UPDATE t SET data = 'after' WHERE id <= 100")
+    wait_for_last_flush_lsn(env, endpoint, src_tenant_id, src_timeline_id)
+    endpoint.stop()
+
+    # Compacting the source unlinks its L0 layers: only the ones the branch references are kept in
+    # remote storage.
+    ps_http.timeline_checkpoint(
+        src_tenant_id, src_timeline_id, compact=False, wait_until_uploaded=True
+    )
+    layers_before = remote_storage.index_content(src_tenant_id, src_timeline_id)["layer_metadata"]
+    ps_http.timeline_compact(
+        src_tenant_id, src_timeline_id, force_l0_compaction=True, wait_until_uploaded=True
+    )
+    ps_http.deletion_queue_flush(execute=True)
+    layers_after = remote_storage.index_content(src_tenant_id, src_timeline_id)["layer_metadata"]
+    unlinked = set(layers_before) - set(layers_after)
+    assert unlinked - referenced, "expected compaction to unlink layers after the branch point"
+    for name in unlinked:
+        exists = layer_exists(src_tenant_id, src_timeline_id, name, layers_before[name])
+        assert exists == (name in referenced), name
+
+    env.neon_cli.mappings_map_branch("cross", dst_tenant_id, dst_timeline_id)
+    branch = env.endpoints.create_start("cross", tenant_id=dst_tenant_id)
+    assert branch.safe_psql("SELECT count(*) FROM t WHERE data = 'before'") == [(20000,)]
+    branch.safe_psql("INSERT INTO t VALUES (0, 'branch')")
+    wait_for_last_flush_lsn(env, branch, dst_tenant_id, dst_timeline_id)
+    ps_http.timeline_checkpoint(dst_tenant_id, dst_timeline_id)
+    branch.stop()
+
+    # The branch reads the layers of the source from remote storage.
+    env.pageserver.restart()
+    for layer in ps_http.layer_map_info(dst_tenant_id, dst_timeline_id).historic_layers:
+        ps_http.evict_layer(dst_tenant_id, dst_timeline_id, layer.layer_file_name)
+    branch.start()
+    assert branch.safe_psql("SELECT count(*) FROM t") == [(20001,)]
+    branch.stop()
+
+    # The source must outlive the branch.
+    with pytest.raises(PageserverApiException, match="child timelines in other tenants") as e:
+        ps_http.timeline_delete(src_tenant_id, src_timeline_id)
+    assert e.value.status_code == 412
+
+    # Delete the branch while the source is not attached: the source unregisters it once it finds
+    # out, and deletes the layers it kept for it.
+    env.storage_controller.tenant_policy_update(src_tenant_id, {"placement": "Detached"})
+    env.storage_controller.reconcile_until_idle()
+    ps_http.timeline_delete(dst_tenant_id, dst_timeline_id)
+    wait_timeline_detail_404(ps_http, dst_tenant_id, dst_timeline_id)
+    env.storage_controller.tenant_policy_update(src_tenant_id, {"placement": {"Attached": 0}})
+    env.storage_controller.reconcile_until_idle()
+    assert remote_storage.index_content(src_tenant_id, src_timeline_id)["cross_tenant_children"]
+
+    ps_http.timeline_gc(src_tenant_id, src_timeline_id, 0)
+    ps_http.timeline_checkpoint(
+        src_tenant_id, src_timeline_id, compact=False, wait_until_uploaded=True
+    )
+    ps_http.deletion_queue_flush(execute=True)
+    assert "cross_tenant_children" not in remote_storage.index_content(
+        src_tenant_id, src_timeline_id
+    )
+    for name in unlinked:
+        assert not layer_exists(src_tenant_id, src_timeline_id, name, layers_before[name]), name
+
+    ps_http.timeline_delete(src_tenant_id, src_timeline_id)
+    wait_timeline_detail_404(ps_http, src_tenant_id, src_timeline_id)
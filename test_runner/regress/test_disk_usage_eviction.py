@@ -84,9 +84,15 @@ def test_min_resident_size_override_handling(
 class EvictionOrder(StrEnum):
     RELATIVE_ORDER_EQUAL = "relative_equal"
     RELATIVE_ORDER_SPARE = "relative_spare"
+    ABSOLUTE_ORDER = "absolute"
+    TENANT_PRIORITY = "tenant_priority"
 
     def config(self) -> dict[str, Any]:
-        if self == EvictionOrder.RELATIVE_ORDER_EQUAL:
+        if self == EvictionOrder.ABSOLUTE_ORDER:
+            return {"type": "AbsoluteAccessed"}
+        elif self == EvictionOrder.TENANT_PRIORITY:
+            return {"type": "TenantPriority"}
+        elif self == EvictionOrder.RELATIVE_ORDER_EQUAL:
             return {
                 "type": "RelativeAccessed",
                 "args": {"highest_layer_count_loses_first": False},
@@ -613,18 +619,55 @@ def test_partial_evict_tenant(eviction_env: EvictionEnv, order: EvictionOrder):
         assert abs_diff < expectation
 
 
+def test_tenant_priority_order(eviction_env: EvictionEnv):
+    """
+    Raise the eviction_priority of one tenant, then evict less than the other tenant's layers
+    above its min resident size. Only the low priority tenant may lose layers, regardless of
+    which tenant was accessed more recently.
+    """
+    env = eviction_env
+    ps_http = env.pageserver_http
+    vps_http = env.neon_env.storage_controller.pageserver_api()
+
+    du_by_timeline = env.du_by_timeline(env.pageserver)
+    tenant_layers = env.count_layers_per_tenant(env.pageserver)
+
+    [high, low] = list(du_by_timeline.keys())
+    vps_http.set_tenant_config(high[0], {"eviction_priority": 10})
+
+    # make the low priority tenant the most recently used one, which the priority must override
+    time.sleep(ATIME_RESOLUTION)
+    env.warm_up_tenant(low[0])
+
+    # stay well within the low priority tenant's layers above its min resident size
+    target = du_by_timeline[low] // 2
+    response = ps_http.disk_usage_eviction_run(
+        {"evict_bytes": target, "eviction_order": EvictionOrder.TENANT_PRIORITY.config()}
+    )
+    log.info(f"{response}")
+    assert response["Finished"]["assumed"]["failed"]["count"] == 0, "zero failures expected"
+
+    layers_now = env.count_layers_per_tenant(env.pageserver)
+    assert layers_now[low[0]] < tenant_layers[low[0]], "low priority tenant must lose layers"
+    assert layers_now[high[0]] == tenant_layers[high[0]], (
+        "high priority tenant must keep its layers"
+    )
+
+
 @pytest.mark.parametrize(
     "order",
     [
         EvictionOrder.RELATIVE_ORDER_EQUAL,
         EvictionOrder.RELATIVE_ORDER_SPARE,
+        EvictionOrder.ABSOLUTE_ORDER,
     ],
 )
 def test_fast_growing_tenant(neon_env_builder: NeonEnvBuilder, pg_bin: PgBin, order: EvictionOrder):
     """
     Create in order first smaller tenants and finally a single larger tenant.
     Assert that with relative order modes, the disk usage based eviction is
-    more fair towards the smaller tenants.
+    more fair towards the smaller tenants, and that with the absolute order
+    the least recently created tenant loses layers first.
     """
     env = neon_env_builder.init_configs()
     env.start()
@@ -677,6 +720,9 @@ def test_fast_growing_tenant(neon_env_builder: NeonEnvBuilder, pg_bin: PgBin, or
         # with different layer sizes and pg versions, there are different combinations
         assert len([x for x in ratios if x < 1.0]) >= 2, "require 2..4 tenants to lose layers"
         assert ratios[3] < 1.0, "largest tenant always loses layers"
+    elif order == EvictionOrder.ABSOLUTE_ORDER:
+        assert ratios[0] < 1.0, "least recently used tenant always loses layers"
+        assert ratios[0] <= ratios[3], "least recently used tenant loses most"
     else:
         raise RuntimeError(f"unimplemented {order}")
 
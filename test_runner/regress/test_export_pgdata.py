@@ -0,0 +1,167 @@
+import json
+import re
+import shutil
+import time
+
+import pytest
+from fixtures.common_types import Lsn, TenantId, TimelineId
+from fixtures.log_helper import log
+from fixtures.neon_fixtures import NeonEnvBuilder, PgBin, VanillaPostgres, wait_for_last_flush_lsn
+from fixtures.pageserver.http import ImportPgdataIdemptencyKey, PageserverApiException
+from fixtures.port_distributor import PortDistributor
+from fixtures.remote_storage import RemoteStorageKind
+from pytest_httpserver import HTTPServer
+from werkzeug.wrappers.request import Request
+from werkzeug.wrappers.response import Response
+
+# Directories of a data directory that may be empty, and thus have no object in the export
+PGDATA_SUBDIRS = [
+    "pg_wal/archive_status",
+    "pg_commit_ts",
+    "pg_dynshmem",
+    "pg_notify",
+    "pg_serial",
+    "pg_snapshots",
+    "pg_subtrans",
+    "pg_twophase",
+    "pg_multixact/members",
+    "pg_multixact/offsets",
+    "pg_replslot",
+    "pg_tblspc",
+    "pg_stat",
+    "pg_stat_tmp",
+    "pg_xact",
+    "pg_logical/snapshots",
+    "pg_logical/mappings",
+]
+
+
+@pytest.mark.parametrize("shard_count", [None, 4])
+def test_export_pgdata(
+    neon_env_builder: NeonEnvBuilder,
+    pg_bin: PgBin,
+    port_distributor: PortDistributor,
+    make_httpserver: HTTPServer,
+    shard_count: int | None,
+):
+    """
+    Export a timeline as a PGDATA directory, then import it back as a new timeline, and
+    start vanilla PostgreSQL on it.
+
+    A sharded tenant's shards are spread over pageservers, so that the export reads relation
+    blocks from the page service of other pageservers.
+    """
+
+    def handler(request: Request) -> Response:
+        log.info(f"control plane request: {request.json}")
+        return Response(json.dumps({}), status=200)
+
+    cplane_mgmt_api_server = make_httpserver
+    cplane_mgmt_api_server.expect_request(re.compile(".*")).respond_with_handler(handler)
+
+    neon_env_builder.enable_pageserver_remote_storage(RemoteStorageKind.LOCAL_FS)
+    if shard_count is not None:
+        neon_env_builder.num_pageservers = 2
+    env = neon_env_builder.init_start(
+        initial_tenant_shard_count=shard_count,
+        # Small stripes, so that the table's blocks are spread over all shards
+        initial_tenant_shard_stripe_size=16 if shard_count is not None else None,
+    )
+
+    # The test needs LocalFs support, which is only built in testing mode.
+    env.pageserver.is_testing_enabled_or_skip()
+
+    for pageserver in env.pageservers:
+        pageserver.patch_config_toml_nonrecursive(
+            {
+                "import_pgdata_upcall_api": f"http://{cplane_mgmt_api_server.host}:{cplane_mgmt_api_server.port}/path/to/mgmt/api"
+            }
+        )
+        pageserver.restart()
+
+    endpoint = env.endpoints.create_start("main")
+    endpoint.safe_psql_many(
+        [
+            "CREATE TABLE t (id int PRIMARY KEY, data text)",
+            "INSERT INTO t SELECT g, repeat('x', 100) FROM generate_series(1, 200000) g",
+        ]
+    )
+    expected = endpoint.safe_psql("SELECT count(*), sum(id) FROM t")
+    export_lsn = wait_for_last_flush_lsn(env, endpoint, env.initial_tenant, env.initial_timeline)
+
+    # Writes after the export LSN must not show up in the export
+    endpoint.safe_psql("INSERT INTO t VALUES (0, 'after export')")
+
+    exportbucket = neon_env_builder.repo_dir / "exportbucket"
+    exportbucket.mkdir()
+    # The storage controller sends it to shard zero, with the locations of the other shards
+    client = env.storage_controller.pageserver_api()
+    status = client.timeline_export_pgdata(
+        env.initial_tenant,
+        env.initial_timeline,
+        {"LocalFs": {"path": str(exportbucket.absolute())}},
+        lsn=export_lsn,
+    )
+    log.info(f"export status: {status}")
+    assert Lsn(status["lsn"]) == export_lsn
+    assert status["exported_file_count"] > 0
+    assert status["exported_bytes"] > 16 * 1024 * 1024
+    assert json.loads((exportbucket / "status" / "pgdata").read_text()) == {"done": True}
+    assert (exportbucket / "pgdata" / "global" / "pg_control").exists()
+
+    #
+    # Import the export as a new timeline
+    #
+    (exportbucket / "spec.json").write_text(
+        json.dumps({"branch_id": "somebranch", "project_id": "someproject"})
+    )
+    tenant_id = TenantId.generate()
+    timeline_id = TimelineId.generate()
+    env.storage_controller.tenant_create(tenant_id)
+    env.storage_controller.timeline_create(
+        tenant_id,
+        {
+            "new_timeline_id": str(timeline_id),
+            "import_pgdata": {
+                "idempotency_key": str(ImportPgdataIdemptencyKey.random()),
+                "location": {"LocalFs": {"path": str(exportbucket.absolute())}},
+            },
+        },
+    )
+    env.neon_cli.mappings_map_branch("imported", tenant_id, timeline_id)
+
+    while True:
+        try:
+            detail = client.timeline_detail(tenant_id, timeline_id)
+            if detail["state"] == "Active":
+                break
+        except PageserverApiException as e:
+            if e.status_code not in (404, 429):
+                raise
+        time.sleep(1)
+
+    ro_endpoint = env.endpoints.create_start(
+        branch_name="imported",
+        endpoint_id="ro",
+        tenant_id=tenant_id,
+        lsn=Lsn(detail["last_record_lsn"]),
+    )
+    assert ro_endpoint.safe_psql("SELECT count(*), sum(id) FROM t") == expected
+    ro_endpoint.stop()
+
+    #
+    # Start vanilla PostgreSQL on the export, as a customer would after downloading it
+    #
+    pgdatadir = neon_env_builder.repo_dir / "exported-pgdata"
+    shutil.copytree(exportbucket / "pgdata", pgdatadir)
+    for subdir in PGDATA_SUBDIRS:
+        (pgdatadir / subdir).mkdir(parents=True, exist_ok=True)
+    # Without it, Postgres can't take the Neon startup path
+    (pgdatadir / "zenith.signal").unlink()
+    pgdatadir.chmod(0o700)
+
+    with VanillaPostgres(pgdatadir, pg_bin, port_distributor.get_port(), init=False) as vanilla_pg:
+        vanilla_pg.start()
+        assert vanilla_pg.safe_psql("SELECT count(*), sum(id) FROM t") == expected
+        # It is writable
+        vanilla_pg.safe_psql("INSERT INTO t VALUES (0, 'vanilla')")
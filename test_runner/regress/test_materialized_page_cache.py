@@ -0,0 +1,63 @@
+from __future__ import annotations
+
+from fixtures.log_helper import log
+from fixtures.neon_fixtures import NeonEnvBuilder, flush_ep_to_pageserver
+
+
+def test_materialized_page_cache(neon_env_builder: NeonEnvBuilder):
+    """
+    Pages reconstructed through walredo are cached, reused by later reads, and
+    new WAL on top of a cached page is still visible.
+    """
+    neon_env_builder.pageserver_config_override = "materialized_page_cache_max_bytes=67108864"
+    env = neon_env_builder.init_start(
+        initial_tenant_conf={
+            # keep the WAL records around, so that reads need redo
+            "gc_period": "0s",
+            "compaction_period": "0s",
+        }
+    )
+    client = env.pageserver.http_client()
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    def lookups(outcome: str) -> float:
+        return (
+            client.get_metric_value(
+                "pageserver_materialized_page_cache_lookups_total", {"outcome": outcome}
+            )
+            or 0
+        )
+
+    endpoint = env.endpoints.create_start("main")
+    with endpoint.cursor() as cur:
+        cur.execute("CREATE TABLE foo (id int PRIMARY KEY, n int)")
+        cur.execute("INSERT INTO foo SELECT g, 0 FROM generate_series(1, 10000) g")
+        cur.execute("UPDATE foo SET n = n + 1")
+
+    def read_back(expected_sum: int):
+        flush_ep_to_pageserver(env, endpoint, tenant_id, timeline_id)
+        endpoint.stop()
+        # a fresh compute reads all pages back from the pageserver
+        endpoint.start()
+        with endpoint.cursor() as cur:
+            cur.execute("SELECT sum(n) FROM foo")
+            assert cur.fetchone() == (expected_sum,)
+
+    read_back(10000)
+    inserts = client.get_metric_value("pageserver_materialized_page_cache_inserts_total") or 0
+    assert inserts > 0
+
+    read_back(10000)
+    hits = lookups("hit_exact") + lookups("hit_base")
+    log.info(f"{inserts} pages cached, {hits} hits")
+    assert hits > 0
+
+    # WAL on top of the cached pages must show up in later reads
+    with endpoint.cursor() as cur:
+        cur.execute("UPDATE foo SET n = n + 1 WHERE id % 2 = 0")
+    read_back(15000)
+
+    assert (
+        client.get_metric_value("pageserver_materialized_page_cache_current_bytes") or 0
+    ) <= 67108864
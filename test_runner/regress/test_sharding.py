@@ -3,6 +3,7 @@ from __future__ import annotations
 import os
 import time
 from collections import defaultdict
+from concurrent.futures import ThreadPoolExecutor
 from typing import TYPE_CHECKING, Any
 
 import pytest
@@ -18,7 +19,11 @@ from fixtures.neon_fixtures import (
     tenant_get_shards,
     wait_for_last_flush_lsn,
 )
-from fixtures.pageserver.utils import assert_prefix_empty, assert_prefix_not_empty
+from fixtures.pageserver.utils import (
+    assert_prefix_empty,
+    assert_prefix_not_empty,
+    timeline_delete_wait_completed,
+)
 from fixtures.remote_storage import LocalFsStorage, RemoteStorageKind, s3_storage
 from fixtures.utils import skip_in_debug_build, wait_until
 from fixtures.workload import Workload
@@ -180,6 +185,108 @@ def test_sharding_split_unsharded(
     env.storage_controller.consistency_check()
 
 
+def test_sharding_merge(
+    neon_env_builder: NeonEnvBuilder,
+):
+    """
+    Test merging shards back to a lower shard count: parents spread across pageservers
+    are co-located, the merged shards hold all the data, and continue ingesting.
+    """
+    neon_env_builder.num_pageservers = 2
+    # The merged shards keep no history below the merge LSN
+    env = neon_env_builder.init_start(
+        initial_tenant_shard_count=4, initial_tenant_conf={"pitr_interval": "0s"}
+    )
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    workload = Workload(env, tenant_id, timeline_id, branch_name="main")
+    workload.init()
+    workload.write_rows(256)
+    workload.validate()
+
+    shards = env.storage_controller.tenant_shard_merge(tenant_id, shard_count=2)
+    assert sorted(TenantShardId.parse(s).shard_number for s in shards) == [0, 1]
+
+    for shard in tenant_get_shards(env, tenant_id):
+        assert shard[0].shard_count == 2
+
+    # Nothing references the parents' remote objects any more
+    for shard_number in range(4):
+        assert_prefix_empty(
+            neon_env_builder.pageserver_remote_storage,
+            prefix=f"tenants/{TenantShardId(tenant_id, shard_number, 4)}/",
+        )
+
+    # Merged shards serve the data written before the merge, and ingest new writes
+    workload.validate()
+    workload.churn_rows(256)
+    workload.validate()
+
+    # Merging all the way back to a single shard
+    env.storage_controller.tenant_shard_merge(tenant_id, shard_count=1)
+    assert env.storage_controller.inspect(TenantShardId(tenant_id, 0, 1)) is not None
+    workload.validate()
+
+    # Merging is idempotent, and shards cannot be merged other than in pairs
+    env.storage_controller.tenant_shard_merge(tenant_id, shard_count=1)
+    with pytest.raises(StorageControllerApiException, match="can only be merged in pairs"):
+        env.storage_controller.tenant_shard_merge(tenant_id, shard_count=4)
+
+    env.storage_controller.consistency_check()
+
+
+def test_sharding_merge_refused_with_history(neon_env_builder: NeonEnvBuilder):
+    """
+    Merged shards keep no history below the merge LSN: merges are refused while branches or
+    the PITR interval still need it, and the shards are left as they were.
+    """
+    neon_env_builder.num_pageservers = 2
+    env = neon_env_builder.init_start(
+        initial_tenant_shard_count=4, initial_tenant_conf={"pitr_interval": "0s"}
+    )
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    env.storage_controller.allowed_errors.extend(
+        [".*Enqueuing background abort.*", ".*Failed to merge into.*"]
+    )
+    for ps in env.pageservers:
+        ps.allowed_errors.extend([".*Cannot merge shards.*", ".*after shard merge failure.*"])
+
+    workload = Workload(env, tenant_id, timeline_id, branch_name="main")
+    workload.init()
+    workload.write_rows(256)
+
+    def assert_shard_count(count: int):
+        for shard in tenant_get_shards(env, tenant_id):
+            assert shard[0].shard_count == count
+
+    branch_id = env.create_branch("branch", tenant_id=tenant_id, ancestor_branch_name="main")
+    with pytest.raises(StorageControllerApiException, match="Cannot merge shards with branches"):
+        env.storage_controller.tenant_shard_merge(tenant_id, shard_count=2)
+    env.storage_controller.reconcile_until_idle()
+    assert_shard_count(4)
+    workload.churn_rows(64)
+    workload.validate()
+
+    # Once the branch is gone, nothing needs the history any more
+    timeline_delete_wait_completed(env.storage_controller.pageserver_api(), tenant_id, branch_id)
+    env.storage_controller.tenant_shard_merge(tenant_id, shard_count=2)
+    assert_shard_count(2)
+    workload.validate()
+
+    env.storage_controller.pageserver_api().set_tenant_config(tenant_id, {"pitr_interval": "1h"})
+    with pytest.raises(StorageControllerApiException, match="non-zero pitr_interval"):
+        env.storage_controller.tenant_shard_merge(tenant_id, shard_count=1)
+    env.storage_controller.reconcile_until_idle()
+    assert_shard_count(2)
+    workload.churn_rows(64)
+    workload.validate()
+
+    env.storage_controller.consistency_check()
+
+
 @pytest.mark.parametrize(
     "failpoint",
     [
@@ -1278,6 +1385,153 @@ class CompositeFailure(Failure):
         return list(expect)[0]
 
 
+def test_sharding_merge_while_ingesting(neon_env_builder: NeonEnvBuilder):
+    """
+    The compute keeps writing while its shards are merged: the parents stop ingesting and
+    uploading before the merge LSN is picked, so that the safekeepers retain the WAL after it,
+    and the merged shard ingests everything written during the merge.
+    """
+    env = neon_env_builder.init_start(
+        initial_tenant_shard_count=2,
+        initial_tenant_conf={
+            "pitr_interval": "0s",
+            # Small layers, so that the parents would keep uploading during the merge
+            "checkpoint_distance": f"{128 * 1024}",
+            "compaction_period": "0s",
+        },
+    )
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+    pageserver = env.pageserver
+    client = pageserver.http_client()
+
+    workload = Workload(env, tenant_id, timeline_id, branch_name="main")
+    workload.init()
+    workload.write_rows(256)
+
+    endpoint = workload.endpoint()
+    with endpoint.cursor() as cur:
+        cur.execute("CREATE TABLE during_merge (id int, t text)")
+
+    def write_during_merge(start: int, count: int):
+        with endpoint.cursor() as cur:
+            for i in range(start, start + count, 100):
+                cur.execute(
+                    f"INSERT INTO during_merge SELECT g, repeat('x', 500) FROM generate_series({i}, {i + 99}) g"
+                )
+
+    parents = [TenantShardId(tenant_id, shard_number, 2) for shard_number in range(2)]
+
+    def remote_consistent_lsns() -> list[Lsn]:
+        return [
+            Lsn(client.timeline_detail(parent, timeline_id)["remote_consistent_lsn"])
+            for parent in parents
+        ]
+
+    # Hold the merge after the merged shard's layers are written, before it is attached
+    client.configure_failpoints(("shard-merge-post-prepare", "pause"))
+    with ThreadPoolExecutor(max_workers=1) as executor:
+        write_during_merge(0, 1000)
+        merge = executor.submit(
+            env.storage_controller.tenant_shard_merge, tenant_id, shard_count=1
+        )
+        wait_until(lambda: pageserver.assert_log_contains("Uploading tenant manifest for merged"))
+
+        # The parents no longer advance their remote_consistent_lsn, however much is written
+        stopped_at = remote_consistent_lsns()
+        write_during_merge(1000, 5000)
+        time.sleep(2)
+        assert remote_consistent_lsns() == stopped_at
+
+        client.configure_failpoints(("shard-merge-post-prepare", "off"))
+        merge.result()
+
+    for shard in tenant_get_shards(env, tenant_id):
+        assert shard[0].shard_count == 1
+
+    # The merged shard has everything, including what was written while the merge ran
+    workload.validate()
+    with endpoint.cursor() as cur:
+        cur.execute("SELECT count(*) FROM during_merge")
+        assert cur.fetchone() == (6000,)
+    workload.churn_rows(64)
+    workload.validate()
+
+    env.storage_controller.consistency_check()
+
+
+@pytest.mark.parametrize(
+    "failure",
+    [
+        PageserverFailpoint("shard-merge-pre-prepare", 1, False),
+        PageserverFailpoint("shard-merge-post-prepare", 1, False),
+        PageserverFailpoint("shard-merge-post-child-conf", 1, False),
+        StorageControllerFailpoint("shard-merge-post-begin", "return(1)"),
+        StorageControllerFailpoint("shard-merge-post-remote", "return(1)"),
+        StorageControllerFailpoint("shard-merge-post-begin", "panic(failpoint)"),
+        StorageControllerFailpoint("shard-merge-post-remote", "panic(failpoint)"),
+    ],
+)
+def test_sharding_merge_failures(neon_env_builder: NeonEnvBuilder, failure: Failure):
+    """
+    A merge that fails part way is rolled back to the parent shards, which keep serving
+    their data, and can be retried.
+    """
+    env = neon_env_builder.init_start(
+        initial_tenant_shard_count=4, initial_tenant_conf={"pitr_interval": "0s"}
+    )
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    env.storage_controller.allowed_errors.extend(
+        [
+            # All merge failures log a warning when they enqueue the abort operation
+            ".*Enqueuing background abort.*",
+            ".*Failed to abort.*",
+            # Tolerate any error logs that mention a failpoint
+            ".*failpoint.*",
+        ]
+    )
+    for ps in env.pageservers:
+        ps.allowed_errors.extend(
+            [
+                ".*failpoint.*",
+                ".*Resetting.*after shard merge failure.*",
+                # If the storage controller panics, background upcalls from the pageserver can fail
+                ".*calling control plane generation validation API failed.*",
+            ]
+        )
+
+    workload = Workload(env, tenant_id, timeline_id, branch_name="main")
+    workload.init()
+    workload.write_rows(256)
+
+    def assert_rolled_back():
+        env.storage_controller.reconcile_until_idle()
+        shards = tenant_get_shards(env, tenant_id)
+        assert len(shards) == 4
+        for shard in shards:
+            assert shard[0].shard_count == 4
+
+    failure.apply(env)
+    with pytest.raises(failure.expect_exception()):
+        env.storage_controller.tenant_shard_merge(tenant_id, shard_count=2)
+    failure.clear(env)
+
+    wait_until(assert_rolled_back)
+    workload.churn_rows(64)
+    workload.validate()
+
+    # With the failure cleared, the merge can be retried
+    env.storage_controller.tenant_shard_merge(tenant_id, shard_count=2)
+    for shard in tenant_get_shards(env, tenant_id):
+        assert shard[0].shard_count == 2
+    workload.churn_rows(64)
+    workload.validate()
+
+    env.storage_controller.consistency_check()
+
+
 @pytest.mark.parametrize(
     "failure",
     [
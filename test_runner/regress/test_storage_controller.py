@@ -12,7 +12,13 @@ from typing import TYPE_CHECKING
 import fixtures.utils
 import pytest
 from fixtures.auth_tokens import TokenScope
-from fixtures.common_types import TenantId, TenantShardId, TimelineId
+from fixtures.common_types import (
+    DEFAULT_WAL_SEG_SIZE,
+    Lsn,
+    TenantId,
+    TenantShardId,
+    TimelineId,
+)
 from fixtures.log_helper import log
 from fixtures.neon_fixtures import (
     DEFAULT_AZ_ID,
@@ -4168,6 +4174,356 @@ def test_storcon_create_delete_sk_down(neon_env_builder: NeonEnvBuilder, restart
     wait_until(timeline_deleted_on_sk)
 
 
+@run_only_on_default_postgres("PG version is not interesting here")
+def test_storcon_timeline_safekeeper_migrate(neon_env_builder: NeonEnvBuilder):
+    """
+    Test that the storcon migrates a timeline to a new set of safekeepers: the
+    new member gets the timeline with all its data, the removed one drops it.
+    """
+
+    neon_env_builder.num_safekeepers = 4
+    neon_env_builder.storage_controller_config = {
+        "timelines_onto_safekeepers": True,
+    }
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    timeline_id = TimelineId.generate()
+    env.create_tenant(tenant_id, timeline_id)
+
+    config_lines = [
+        "neon.safekeeper_proto_version = 3",
+    ]
+    with env.endpoints.create("main", tenant_id=tenant_id, config_lines=config_lines) as ep:
+        ep.start(safekeeper_generation=1, safekeepers=[1, 2, 3])
+        ep.safe_psql("CREATE TABLE t(key int, value text)")
+        ep.safe_psql("INSERT INTO t SELECT generate_series(1, 1000), 'payload'")
+
+    env.storage_controller.timeline_safekeeper_migrate(tenant_id, timeline_id, [2, 3, 4])
+    assert env.storage_controller.safekeeper_migrations() == []
+
+    # Generation 2 is the joint configuration, 3 the final one.
+    for sk in env.safekeepers[1:]:
+        mconf = sk.http_client().get_membership(tenant_id, timeline_id)
+        assert mconf.generation == 3
+        assert sorted(m.id for m in mconf.members) == [2, 3, 4]
+        assert mconf.new_members is None
+
+    def timeline_deleted_on_removed_sk():
+        env.safekeepers[0].assert_log_contains(
+            f"deleting timeline {tenant_id}/{timeline_id} from disk"
+        )
+
+    wait_until(timeline_deleted_on_removed_sk)
+
+    # Migrating to the current set is a no-op.
+    env.storage_controller.timeline_safekeeper_migrate(tenant_id, timeline_id, [4, 3, 2])
+    assert (
+        env.storage_controller.get_metric_value(
+            "storage_controller_safekeeper_migration_complete_total", filter={"status": "ok"}
+        )
+        == 1
+    )
+
+    with env.endpoints.create("main", tenant_id=tenant_id, config_lines=config_lines) as ep:
+        ep.start(safekeeper_generation=3, safekeepers=[2, 3, 4])
+        assert ep.safe_psql("SELECT count(*) FROM t")[0][0] == 1000
+
+
+@run_only_on_default_postgres("PG version is not interesting here")
+def test_storcon_timeline_safekeeper_migrate_live_compute(neon_env_builder: NeonEnvBuilder):
+    """
+    Test that a running compute is switched to the new safekeepers of its timeline, so that
+    it keeps writing once none of the safekeepers it was started with are left.
+    """
+
+    neon_env_builder.num_safekeepers = 6
+    neon_env_builder.storage_controller_config = {
+        "timelines_onto_safekeepers": True,
+    }
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    timeline_id = TimelineId.generate()
+    env.create_tenant(tenant_id, timeline_id)
+
+    config_lines = [
+        "neon.safekeeper_proto_version = 3",
+    ]
+    ep = env.endpoints.create("main", tenant_id=tenant_id, config_lines=config_lines)
+    ep.start(safekeeper_generation=1, safekeepers=[1, 2, 3])
+    ep.safe_psql("CREATE TABLE t(key int, value text)")
+    ep.safe_psql("INSERT INTO t SELECT generate_series(1, 1000), 'payload'")
+
+    # Two moves leave none of the original safekeepers.
+    for new_sk_set, removed in [([3, 4, 5], [1, 2]), ([4, 5, 6], [3])]:
+        env.storage_controller.timeline_safekeeper_migrate(tenant_id, timeline_id, new_sk_set)
+
+        def timeline_deleted_on_removed_sks(removed: list[int] = removed):
+            for sk_id in removed:
+                env.safekeepers[sk_id - 1].assert_log_contains(
+                    f"deleting timeline {tenant_id}/{timeline_id} from disk"
+                )
+
+        wait_until(timeline_deleted_on_removed_sks)
+        ep.safe_psql("INSERT INTO t SELECT generate_series(1, 1000), 'payload'")
+
+    for sk in env.safekeepers[:3]:
+        sk.stop()
+    ep.safe_psql("INSERT INTO t SELECT generate_series(1, 1000), 'payload'")
+    assert ep.safe_psql("SELECT count(*) FROM t")[0][0] == 4000
+    assert ep.log_contains("restarting walproposer to change safekeeper list")
+    ep.stop()
+
+
+@run_only_on_default_postgres("PG version is not interesting here")
+def test_storcon_safekeeper_decommission_rebalance(neon_env_builder: NeonEnvBuilder):
+    """
+    Test that the background rebalancer moves timelines off a decomissioned safekeeper.
+    """
+
+    neon_env_builder.num_safekeepers = 4
+    neon_env_builder.storage_controller_config = {
+        "timelines_onto_safekeepers": True,
+        "safekeeper_rebalance_interval": "1s",
+    }
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    timeline_id = TimelineId.generate()
+    env.create_tenant(tenant_id, timeline_id)
+
+    config_lines = [
+        "neon.safekeeper_proto_version = 3",
+    ]
+    with env.endpoints.create("main", tenant_id=tenant_id, config_lines=config_lines) as ep:
+        ep.start(safekeeper_generation=1, safekeepers=[1, 2, 3])
+        ep.safe_psql("CREATE TABLE t(key int, value text)")
+        ep.safe_psql("INSERT INTO t SELECT generate_series(1, 1000), 'payload'")
+
+    env.storage_controller.safekeeper_scheduling_policy(1, "Decomissioned")
+
+    def migrated_to_sk4():
+        mconf = env.safekeepers[3].http_client().get_membership(tenant_id, timeline_id)
+        assert mconf.generation == 3
+        assert sorted(m.id for m in mconf.members) == [2, 3, 4]
+        assert mconf.new_members is None
+
+    wait_until(migrated_to_sk4)
+
+    with env.endpoints.create("main", tenant_id=tenant_id, config_lines=config_lines) as ep:
+        ep.start(safekeeper_generation=3, safekeepers=[2, 3, 4])
+        assert ep.safe_psql("SELECT count(*) FROM t")[0][0] == 1000
+
+
+@run_only_on_default_postgres("PG version is not interesting here")
+def test_storcon_safekeeper_drain_fill(neon_env_builder: NeonEnvBuilder):
+    """
+    Test that draining a safekeeper moves its timelines to other safekeepers and
+    pauses it, and that filling it makes it active again.
+    """
+
+    neon_env_builder.num_safekeepers = 4
+    neon_env_builder.storage_controller_config = {
+        "timelines_onto_safekeepers": True,
+    }
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    timeline_id = TimelineId.generate()
+    env.create_tenant(tenant_id, timeline_id)
+
+    config_lines = [
+        "neon.safekeeper_proto_version = 3",
+    ]
+    with env.endpoints.create("main", tenant_id=tenant_id, config_lines=config_lines) as ep:
+        ep.start(safekeeper_generation=1, safekeepers=[1, 2, 3])
+        ep.safe_psql("CREATE TABLE t(key int, value text)")
+        ep.safe_psql("INSERT INTO t SELECT generate_series(1, 1000), 'payload'")
+
+    # Draining and filling are only entered through their operations.
+    with pytest.raises(StorageControllerApiException, match="can only be set"):
+        env.storage_controller.safekeeper_scheduling_policy(1, "Draining")
+    with pytest.raises(StorageControllerApiException, match="no Drain in progress"):
+        env.storage_controller.cancel_safekeeper_drain(1)
+
+    # The drain needs the safekeeper to have been heartbeated.
+    wait_until(lambda: env.storage_controller.safekeeper_drain(1))
+
+    def drained():
+        sk = env.storage_controller.get_safekeeper(1)
+        assert sk is not None
+        assert sk["scheduling_policy"] == "Pause"
+
+    wait_until(drained)
+    assert env.storage_controller.safekeeper_operation(1) is None
+
+    mconf = env.safekeepers[3].http_client().get_membership(tenant_id, timeline_id)
+    assert mconf.generation == 3
+    assert sorted(m.id for m in mconf.members) == [2, 3, 4]
+
+    with env.endpoints.create("main", tenant_id=tenant_id, config_lines=config_lines) as ep:
+        ep.start(safekeeper_generation=3, safekeepers=[2, 3, 4])
+        assert ep.safe_psql("SELECT count(*) FROM t")[0][0] == 1000
+
+    # A single timeline is not worth moving back: the fill just activates the safekeeper.
+    env.storage_controller.safekeeper_fill(1)
+
+    def filled():
+        sk = env.storage_controller.get_safekeeper(1)
+        assert sk is not None
+        assert sk["scheduling_policy"] == "Active"
+
+    wait_until(filled)
+
+
+@run_only_on_default_postgres("PG version is not interesting here")
+def test_storcon_safekeeper_wal_verification(neon_env_builder: NeonEnvBuilder):
+    """
+    Test that the storcon compares the committed WAL across the safekeepers of a
+    timeline and reports the divergence once one of them gets a corrupted copy.
+    """
+
+    neon_env_builder.num_safekeepers = 3
+    neon_env_builder.storage_controller_config = {
+        "timelines_onto_safekeepers": True,
+        "safekeeper_wal_verification_interval": "1s",
+    }
+    env = neon_env_builder.init_start()
+    env.storage_controller.allowed_errors.append(".*safekeepers hold different WAL.*")
+
+    tenant_id = TenantId.generate()
+    timeline_id = TimelineId.generate()
+    env.create_tenant(tenant_id, timeline_id)
+
+    config_lines = [
+        "neon.safekeeper_proto_version = 3",
+    ]
+    with env.endpoints.create("main", tenant_id=tenant_id, config_lines=config_lines) as ep:
+        ep.start(safekeeper_generation=1, safekeepers=[1, 2, 3])
+        ep.safe_psql("CREATE TABLE t(key int, value text)")
+        start_lsn = Lsn(ep.safe_psql("SELECT pg_current_wal_flush_lsn()")[0][0])
+        ep.safe_psql("INSERT INTO t SELECT generate_series(1, 10000), 'payload'")
+        end_lsn = Lsn(ep.safe_psql("SELECT pg_current_wal_flush_lsn()")[0][0])
+
+    def verified():
+        verification = env.storage_controller.timeline_safekeeper_wal_verification(
+            tenant_id, timeline_id
+        )
+        assert Lsn(verification["verified_lsn"]) >= end_lsn
+        assert verification["divergence"] is None
+
+    wait_until(verified)
+    assert env.storage_controller.safekeeper_wal_divergences() == []
+    assert (
+        env.storage_controller.get_metric_value(
+            "storage_controller_safekeeper_wal_verified_bytes_total"
+        )
+        or 0
+    ) > 0
+
+    # Flip a byte of the committed WAL on safekeeper 1.
+    corrupt_lsn = Lsn(start_lsn.lsn_int + (end_lsn.lsn_int - start_lsn.lsn_int) // 2)
+    sk = env.safekeepers[0]
+    sk.stop()
+    segment = sk.timeline_dir(tenant_id, timeline_id) / corrupt_lsn.segment_name()
+    if not segment.exists():
+        segment = segment.with_name(f"{segment.name}.partial")
+    with open(segment, "r+b") as f:
+        f.seek(corrupt_lsn.lsn_int % DEFAULT_WAL_SEG_SIZE)
+        byte = f.read(1)[0]
+        f.seek(-1, 1)
+        f.write(bytes([byte ^ 0xFF]))
+    sk.start()
+
+    # The corrupted range has been verified already, start over.
+    env.storage_controller.stop()
+    env.storage_controller.start()
+
+    def diverged():
+        divergences = env.storage_controller.safekeeper_wal_divergences()
+        assert len(divergences) == 1
+        return divergences[0]
+
+    verification = wait_until(diverged)
+    assert verification["timeline_id"] == str(timeline_id)
+    divergence = verification["divergence"]
+    assert Lsn(divergence["from_lsn"]) <= corrupt_lsn < Lsn(divergence["until_lsn"])
+    digests = {d["node_id"]: d["sha256"] for d in divergence["digests"]}
+    assert digests[2] == digests[3]
+    assert digests[1] != digests[2]
+    assert (
+        env.storage_controller.get_metric_value(
+            "storage_controller_safekeeper_wal_divergent_timelines"
+        )
+        == 1
+    )
+
+    # The divergence keeps being reported after a restart of the storage controller.
+    env.storage_controller.stop()
+    env.storage_controller.start()
+    assert env.storage_controller.safekeeper_wal_divergences() == [verification]
+    assert (
+        env.storage_controller.get_metric_value(
+            "storage_controller_safekeeper_wal_divergent_timelines"
+        )
+        == 1
+    )
+
+
+@run_only_on_default_postgres("PG version is not interesting here")
+def test_storcon_safekeeper_drain_live_compute(neon_env_builder: NeonEnvBuilder):
+    """
+    Test that a compute running during a safekeeper drain is switched to the new
+    safekeepers, and keeps writing once the drained safekeeper is gone.
+    """
+
+    neon_env_builder.num_safekeepers = 4
+    neon_env_builder.storage_controller_config = {
+        "timelines_onto_safekeepers": True,
+    }
+    env = neon_env_builder.init_start()
+
+    tenant_id = TenantId.generate()
+    timeline_id = TimelineId.generate()
+    env.create_tenant(tenant_id, timeline_id)
+
+    config_lines = [
+        "neon.safekeeper_proto_version = 3",
+    ]
+    ep = env.endpoints.create("main", tenant_id=tenant_id, config_lines=config_lines)
+    ep.start(safekeeper_generation=1, safekeepers=[1, 2, 3])
+    ep.safe_psql("CREATE TABLE t(key int, value text)")
+    ep.safe_psql("INSERT INTO t SELECT generate_series(1, 1000), 'payload'")
+
+    # The drain needs the safekeeper to have been heartbeated.
+    wait_until(lambda: env.storage_controller.safekeeper_drain(1))
+
+    def drained():
+        sk = env.storage_controller.get_safekeeper(1)
+        assert sk is not None
+        assert sk["scheduling_policy"] == "Pause"
+
+    wait_until(drained)
+    ep.safe_psql("INSERT INTO t SELECT generate_series(1, 1000), 'payload'")
+
+    def timeline_deleted_on_drained_sk():
+        env.safekeepers[0].assert_log_contains(
+            f"deleting timeline {tenant_id}/{timeline_id} from disk"
+        )
+
+    wait_until(timeline_deleted_on_drained_sk)
+
+    # Only safekeeper 3 is left of the ones the compute was started with: it needs
+    # safekeeper 4 for a quorum.
+    env.safekeepers[0].stop()
+    env.safekeepers[1].stop()
+    ep.safe_psql("INSERT INTO t SELECT generate_series(1, 1000), 'payload'")
+    assert ep.safe_psql("SELECT count(*) FROM t")[0][0] == 3000
+    assert ep.log_contains("restarting walproposer to change safekeeper list")
+    ep.stop()
+
+
 @pytest.mark.parametrize("wrong_az", [True, False])
 def test_storage_controller_graceful_migration(neon_env_builder: NeonEnvBuilder, wrong_az: bool):
     """
@@ -0,0 +1,89 @@
+from __future__ import annotations
+
+import pytest
+from fixtures.log_helper import log
+from fixtures.neon_fixtures import (
+    NeonEnvBuilder,
+    flush_ep_to_pageserver,
+)
+from fixtures.pageserver.http import PageserverApiException
+from fixtures.pageserver.utils import wait_for_upload
+from fixtures.remote_storage import RemoteStorageKind
+from fixtures.utils import wait_until
+
+
+def test_tenant_disk_quota(neon_env_builder: NeonEnvBuilder):
+    """
+    Once a tenant's resident size exceeds its max_resident_size, the tenant's own layers are
+    evicted until it fits again, without any disk pressure on the pageserver. Downloads that can't
+    fit below max_resident_size are refused.
+    """
+    neon_env_builder.enable_pageserver_remote_storage(RemoteStorageKind.LOCAL_FS)
+
+    env = neon_env_builder.init_start(
+        initial_tenant_conf={
+            # disable gc and compaction background loops because they perform on-demand downloads
+            "gc_period": "0s",
+            "compaction_period": "0s",
+            # create many small layers
+            "checkpoint_distance": f"{1024 * 1024}",
+        }
+    )
+    client = env.pageserver.http_client()
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    endpoint = env.endpoints.create_start("main")
+    with endpoint.cursor() as cur:
+        cur.execute("CREATE TABLE foo (t text)")
+        cur.execute(
+            """
+            INSERT INTO foo
+            SELECT 'long string to consume some space' || g
+            FROM generate_series(1, 200000) g
+            """
+        )
+
+    current_lsn = flush_ep_to_pageserver(env, endpoint, tenant_id, timeline_id)
+    client.timeline_checkpoint(tenant_id, timeline_id)
+    wait_for_upload(client, tenant_id, timeline_id, current_lsn)
+
+    status = client.tenant_disk_quota(tenant_id)
+    log.info(f"before setting a quota: {status}")
+    assert status["max_resident_size"] is None
+    resident_size = status["resident_size"]
+    assert resident_size > 0
+
+    max_resident_size = resident_size // 2
+    env.storage_controller.pageserver_api().update_tenant_config(
+        tenant_id, {"max_resident_size": max_resident_size}
+    )
+
+    def quota_enforced():
+        status = client.tenant_disk_quota(tenant_id)
+        assert status["max_resident_size"] == max_resident_size
+        assert status["resident_size"] <= max_resident_size
+
+    wait_until(quota_enforced)
+
+    assert (
+        client.get_metric_value("pageserver_tenant_disk_quota_evicted_layers_total") or 0
+    ) > 0
+
+    # no amount of eviction makes room for a layer larger than max_resident_size
+    evicted = next(
+        layer
+        for layer in client.layer_map_info(tenant_id, timeline_id).historic_layers
+        if layer.remote
+    )
+    env.storage_controller.pageserver_api().update_tenant_config(
+        tenant_id, {"max_resident_size": evicted.layer_file_size - 1}
+    )
+    with pytest.raises(PageserverApiException) as exc:
+        client.download_layer(tenant_id, timeline_id, evicted.layer_file_name)
+    assert exc.value.status_code == 503
+    assert evicted.layer_file_name not in {
+        layer.layer_file_name
+        for layer in client.layer_map_info(tenant_id, timeline_id).historic_layers
+        if not layer.remote
+    }
@@ -0,0 +1,82 @@
+from __future__ import annotations
+
+from fixtures.log_helper import log
+from fixtures.neon_fixtures import (
+    NeonEnvBuilder,
+    flush_ep_to_pageserver,
+)
+from fixtures.pageserver.utils import wait_for_upload
+from fixtures.remote_storage import RemoteStorageKind
+from fixtures.utils import wait_until
+
+
+def test_tenant_scrub(neon_env_builder: NeonEnvBuilder):
+    """
+    The scrub loop re-reads the resident layer files of a tenant and reports the ones which
+    no longer match their index_part.json metadata or fail to read.
+    """
+    neon_env_builder.enable_pageserver_remote_storage(RemoteStorageKind.LOCAL_FS)
+
+    env = neon_env_builder.init_start(
+        initial_tenant_conf={
+            # keep the set of layers stable
+            "gc_period": "0s",
+            "compaction_period": "0s",
+            "checkpoint_distance": f"{1024 * 1024}",
+        }
+    )
+    env.pageserver.allowed_errors.append(".*layer file is corrupt.*")
+
+    client = env.pageserver.http_client()
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    endpoint = env.endpoints.create_start("main")
+    with endpoint.cursor() as cur:
+        cur.execute("CREATE TABLE foo (t text)")
+        cur.execute(
+            """
+            INSERT INTO foo
+            SELECT 'long string to consume some space' || g
+            FROM generate_series(1, 50000) g
+            """
+        )
+
+    current_lsn = flush_ep_to_pageserver(env, endpoint, tenant_id, timeline_id)
+    client.timeline_checkpoint(tenant_id, timeline_id)
+    wait_for_upload(client, tenant_id, timeline_id, current_lsn)
+    endpoint.stop()
+
+    status = client.tenant_scrub_status(tenant_id)
+    assert status["last_scrub_finished_at"] is None
+
+    env.storage_controller.pageserver_api().update_tenant_config(
+        tenant_id, {"scrub_period": "1s"}
+    )
+
+    def scrubbed_without_errors():
+        status = client.tenant_scrub_status(tenant_id)
+        assert status["last_scrub_finished_at"] is not None
+        assert status["layers_checked"] > 0
+        assert status["corrupt_layers"] == []
+
+    wait_until(scrubbed_without_errors)
+
+    layers = env.pageserver.list_layers(tenant_id, timeline_id)
+    victim = layers[-1]
+    log.info(f"corrupting layer {victim}")
+    with open(env.pageserver.timeline_dir(tenant_id, timeline_id) / victim, "ab") as f:
+        f.write(b"garbage")
+
+    def corruption_reported():
+        status = client.tenant_scrub_status(tenant_id)
+        corrupt = status["corrupt_layers"]
+        assert len(corrupt) == 1
+        assert corrupt[0]["timeline_id"] == str(timeline_id)
+        # the local file name carries the generation suffix
+        assert victim.name.startswith(corrupt[0]["layer_file_name"])
+        assert "index_part.json" in corrupt[0]["error"]
+
+    wait_until(corruption_reported)
+
+    assert (client.get_metric_value("pageserver_layer_scrub_corrupt_layers_total") or 0) > 0
@@ -0,0 +1,130 @@
+from __future__ import annotations
+
+import json
+
+import pytest
+from fixtures.common_types import Lsn, TenantShardId, TimelineId
+from fixtures.neon_fixtures import NeonEnvBuilder, wait_for_last_flush_lsn
+from fixtures.pageserver.http import PageserverApiException
+from fixtures.remote_storage import LocalFsStorage, RemoteStorageKind
+from fixtures.utils import run_only_on_default_postgres
+
+
+@run_only_on_default_postgres("PG version is not interesting here")
+@pytest.mark.parametrize("shard_count", [None, 2])
+def test_timeline_reset_to_lsn(neon_env_builder: NeonEnvBuilder, shard_count: int | None):
+    """
+    Reset a timeline to an earlier LSN through the storage controller, and continue writing to
+    it with the same timeline ID.
+    """
+    neon_env_builder.num_safekeepers = 3
+    neon_env_builder.storage_controller_config = {
+        "timelines_onto_safekeepers": True,
+    }
+    neon_env_builder.enable_pageserver_remote_storage(RemoteStorageKind.LOCAL_FS)
+    env = neon_env_builder.init_start()
+    tenant_id, timeline_id = env.create_tenant(shard_count=shard_count)
+    ps_http = env.pageserver.http_client()
+    storcon_api = env.storage_controller.pageserver_api()
+    remote_storage = env.pageserver_remote_storage
+    assert isinstance(remote_storage, LocalFsStorage)
+
+    # These are expected after timeline deletion on safekeepers.
+    env.pageserver.allowed_errors.extend(
+        [
+            ".*Timeline .* was not found in global map.*",
+            ".*Timeline .* was cancelled and cannot be used anymore.*",
+        ]
+    )
+
+    shards = [TenantShardId.parse(s["shard_id"]) for s in env.storage_controller.locate(tenant_id)]
+
+    def last_record_lsn() -> Lsn:
+        lsns = {
+            Lsn(ps_http.timeline_detail(shard, timeline_id)["last_record_lsn"]) for shard in shards
+        }
+        assert len(lsns) == 1
+        return lsns.pop()
+
+    def generations() -> dict[TenantShardId, int]:
+        return {shard: ps_http.tenant_get_location(shard)["generation"] for shard in shards}
+
+    config_lines = [
+        "neon.safekeeper_proto_version = 3",
+    ]
+    endpoint = env.endpoints.create("main", tenant_id=tenant_id, config_lines=config_lines)
+    endpoint.start(safekeeper_generation=1, safekeepers=[1, 2, 3])
+    endpoint.safe_psql("CREATE TABLE t (id int PRIMARY KEY, data text)")
+    wait_for_last_flush_lsn(env, endpoint, tenant_id, timeline_id)
+    for shard in shards:
+        ps_http.timeline_checkpoint(shard, timeline_id)
+    before_rows_lsn = last_record_lsn()
+
+    endpoint.safe_psql("INSERT INTO t SELECT g, 'kept' FROM generate_series(1, 10000) g")
+    wait_for_last_flush_lsn(env, endpoint, tenant_id, timeline_id)
+    reset_lsn = last_record_lsn()
+
+    # Lands in the same in-memory layer as the rows we keep, which must then be rewritten.
+    endpoint.safe_psql("INSERT INTO t SELECT g, 'discarded' FROM generate_series(10001, 20000) g")
+    endpoint.safe_psql("UPDATE t SET data = 'discarded' WHERE id <= 100")
+    wait_for_last_flush_lsn(env, endpoint, tenant_id, timeline_id)
+    endpoint.stop()
+
+    with pytest.raises(PageserverApiException, match="ahead of the last record lsn") as e:
+        storcon_api.timeline_reset_to_lsn(tenant_id, timeline_id, last_record_lsn() + 0x1000)
+    assert e.value.status_code == 400
+
+    generations_before = generations()
+    storcon_api.timeline_reset_to_lsn(tenant_id, timeline_id, reset_lsn)
+    assert last_record_lsn() == reset_lsn
+
+    # Every shard is reset in a new generation, and the index of the previous one keeps the
+    # discarded layers.
+    generations_after = generations()
+    for shard in shards:
+        assert generations_after[shard] > generations_before[shard]
+        old_index = json.loads(
+            (
+                remote_storage.timeline_path(shard, timeline_id)
+                / f"index_part.json-{generations_before[shard]:08x}"
+            ).read_text()
+        )
+        new_index = remote_storage.index_content(shard, timeline_id)
+        assert Lsn(new_index["disk_consistent_lsn"]) == reset_lsn
+        assert set(old_index["layer_metadata"]) - set(new_index["layer_metadata"])
+
+    # The safekeepers don't have the discarded WAL anymore.
+    for sk in env.safekeepers:
+        status = sk.http_client().timeline_status(tenant_id, timeline_id)
+        assert status.timeline_start_lsn == reset_lsn
+        assert status.commit_lsn == reset_lsn
+
+    endpoint.start(safekeeper_generation=1, safekeepers=[1, 2, 3])
+    assert endpoint.safe_psql("SELECT count(*), count(*) FILTER (WHERE data = 'kept') FROM t") == [
+        (10000, 10000)
+    ]
+    endpoint.safe_psql("INSERT INTO t VALUES (0, 'after reset')")
+    wait_for_last_flush_lsn(env, endpoint, tenant_id, timeline_id)
+    endpoint.stop()
+
+    # The reset is persisted in remote storage.
+    env.pageserver.restart()
+    endpoint.start(safekeeper_generation=1, safekeepers=[1, 2, 3])
+    assert endpoint.safe_psql("SELECT count(*) FROM t") == [(10001,)]
+    assert endpoint.safe_psql("SELECT data FROM t WHERE id = 0") == [("after reset",)]
+
+    # A branch after the LSN would lose its history.
+    child_id = TimelineId.generate()
+    storcon_api.timeline_create(
+        env.pg_version, tenant_id, child_id, ancestor_timeline_id=timeline_id
+    )
+    endpoint.stop()
+    with pytest.raises(PageserverApiException, match="is branched off after the lsn") as e:
+        storcon_api.timeline_reset_to_lsn(tenant_id, timeline_id, before_rows_lsn)
+    assert e.value.status_code == 409
+
+    # So would a lease after the LSN.
+    storcon_api.timeline_lsn_lease(tenant_id, timeline_id, last_record_lsn())
+    with pytest.raises(PageserverApiException, match="after the lsn is leased") as e:
+        storcon_api.timeline_reset_to_lsn(tenant_id, timeline_id, before_rows_lsn)
+    assert e.value.status_code == 409
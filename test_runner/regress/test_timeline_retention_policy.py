@@ -0,0 +1,82 @@
+from __future__ import annotations
+
+import time
+from datetime import datetime
+
+import pytest
+from fixtures.common_types import Lsn
+from fixtures.neon_fixtures import NeonEnvBuilder, wait_for_last_flush_lsn
+from fixtures.pageserver.http import PageserverApiException
+from fixtures.remote_storage import RemoteStorageKind
+
+
+def test_timeline_retention_policy(neon_env_builder: NeonEnvBuilder):
+    """
+    Keep restore points beyond the PITR window of a timeline, and branch off one of them after
+    GC has moved past it.
+    """
+    neon_env_builder.enable_pageserver_remote_storage(RemoteStorageKind.LOCAL_FS)
+    env = neon_env_builder.init_start(
+        initial_tenant_conf={
+            # Leave GC to the explicit calls below.
+            "gc_period": "0s",
+            "lsn_lease_length": "0s",
+        }
+    )
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+    ps_http = env.pageserver.http_client()
+
+    with pytest.raises(PageserverApiException, match="invalid retention policy") as e:
+        ps_http.timeline_retention_policy(
+            tenant_id,
+            timeline_id,
+            {"tiers": [{"interval": "1day", "keep_for": "1h"}]},
+        )
+    assert e.value.status_code == 400
+
+    retention_policy = {
+        "pitr_interval": "1s",
+        "tiers": [{"interval": "1s", "keep_for": "10m"}],
+    }
+    ps_http.timeline_retention_policy(tenant_id, timeline_id, retention_policy)
+    assert ps_http.timeline_detail(tenant_id, timeline_id)["retention_policy"] == retention_policy
+
+    endpoint = env.endpoints.create_start("main")
+    endpoint.safe_psql("CREATE TABLE t (id int PRIMARY KEY, data text)")
+    endpoint.safe_psql("INSERT INTO t SELECT g, 'old' FROM generate_series(1, 10000) g")
+    wait_for_last_flush_lsn(env, endpoint, tenant_id, timeline_id)
+    time.sleep(2.5)
+
+    updated_at = time.time()
+    endpoint.safe_psql("UPDATE t SET data = 'new'")
+    wait_for_last_flush_lsn(env, endpoint, tenant_id, timeline_id)
+    ps_http.timeline_checkpoint(tenant_id, timeline_id)
+    time.sleep(2)
+
+    ps_http.timeline_gc(tenant_id, timeline_id, 0)
+    detail = ps_http.timeline_detail(tenant_id, timeline_id)
+    before_update = [
+        Lsn(p["lsn"])
+        for p in detail["restore_points"]
+        if datetime.fromisoformat(p["timestamp"].replace("Z", "+00:00")).timestamp()
+        < updated_at - 1
+    ]
+    assert len(before_update) > 0, detail["restore_points"]
+    restore_point = before_update[-1]
+    assert restore_point < Lsn(detail["applied_gc_cutoff_lsn"])
+
+    # The restore points are persisted, and still allow branching below the GC cutoff.
+    env.pageserver.restart()
+    endpoint.stop()
+    env.create_branch("restored", ancestor_branch_name="main", ancestor_start_lsn=restore_point)
+    restored = env.endpoints.create_start("restored")
+    assert restored.safe_psql("SELECT count(*) FROM t WHERE data = 'old'") == [(10000,)]
+    restored.stop()
+
+    # Without a policy, the restore points are dropped at the next GC iteration.
+    ps_http.timeline_retention_policy(tenant_id, timeline_id, None)
+    ps_http.timeline_gc(tenant_id, timeline_id, 0)
+    detail = ps_http.timeline_detail(tenant_id, timeline_id)
+    assert detail["retention_policy"] is None
+    assert "restore_points" not in detail
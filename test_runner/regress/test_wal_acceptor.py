@@ -40,6 +40,7 @@ from fixtures.pageserver.utils import (
 )
 from fixtures.pg_version import PgVersion
 from fixtures.remote_storage import (
+    LocalFsStorage,
     RemoteStorageKind,
     default_remote_storage,
     s3_storage,
@@ -422,6 +423,99 @@ def wait(f, desc, timeout=30, wait_f=None):
             wait_f()
 
 
+# Test that replication slots retain WAL on safekeepers until they are dropped,
+# survive restarts and are advanced by the consumer's feedback.
+def test_replication_slots(neon_env_builder: NeonEnvBuilder):
+    neon_env_builder.num_safekeepers = 1
+    # to advance remote_consistent_lsn
+    neon_env_builder.enable_pageserver_remote_storage(RemoteStorageKind.LOCAL_FS)
+    env = neon_env_builder.init_start()
+
+    tenant_id = env.initial_tenant
+    timeline_id = env.create_branch("test_replication_slots")
+    endpoint = env.endpoints.create_start("test_replication_slots")
+    endpoint.safe_psql("CREATE TABLE t(key int primary key, value text)")
+
+    sk = env.safekeepers[0]
+    http_cli = sk.http_client()
+    timeline_start_lsn = http_cli.timeline_status(tenant_id, timeline_id).timeline_start_lsn
+
+    def slots() -> dict[str, Lsn]:
+        return {
+            s["name"]: Lsn(s["restart_lsn"])
+            for s in http_cli.replication_slots(tenant_id, timeline_id)
+        }
+
+    def replication_conn():
+        return psycopg2.connect(
+            host="127.0.0.1",
+            options=f"-c timeline_id={timeline_id} tenant_id={tenant_id}",
+            port=sk.port.pg,
+            connection_factory=psycopg2.extras.PhysicalReplicationConnection,
+        )
+
+    # One slot from the timeline start over http, another one over the
+    # replication protocol, like pg_receivewal --create-slot does.
+    http_cli.replication_slot_create(tenant_id, timeline_id, "from_start", timeline_start_lsn)
+    with closing(replication_conn()) as conn, conn.cursor() as cur:
+        cur.create_replication_slot("receivewal")
+    assert slots().keys() == {"from_start", "receivewal"}
+    assert slots()["from_start"] == timeline_start_lsn
+    with pytest.raises(http_cli.HTTPError, match="409"):
+        http_cli.replication_slot_create(tenant_id, timeline_id, "receivewal")
+    with pytest.raises(http_cli.HTTPError, match="400"):
+        http_cli.replication_slot_create(tenant_id, timeline_id, "Not-A-Slot")
+
+    sk.stop().start()
+    assert slots()["from_start"] == timeline_start_lsn
+
+    endpoint.safe_psql("INSERT INTO t SELECT generate_series(1,200000), 'payload'")
+    wait_lsn_force_checkpoint(tenant_id, timeline_id, endpoint, env.pageserver)
+    # Pretend WAL is offloaded to s3.
+    http_cli.record_safekeeper_info(tenant_id, timeline_id, {"backup_lsn": "FFFFFFFF/FEFFFFFF"})
+
+    # All horizons but the slots allow removing the first segment.
+    first_segment = sk.timeline_dir(tenant_id, timeline_id) / timeline_start_lsn.segment_name()
+    time.sleep(3)
+    assert os.path.exists(first_segment)
+
+    # Stream from the slot, confirming everything received as flushed.
+    until = http_cli.get_commit_lsn(tenant_id, timeline_id)
+
+    def consume(msg):
+        end = msg.data_start + len(msg.payload)
+        msg.cursor.send_feedback(flush_lsn=end, force=True)
+        if end >= until.as_int():
+            raise psycopg2.extras.StopReplication
+
+    with closing(replication_conn()) as conn, conn.cursor() as cur:
+        cur.start_replication(slot_name="from_start", start_lsn=str(timeline_start_lsn))
+        cur.consume_stream(consume)
+    def slot_advanced():
+        assert slots()["from_start"].segno() >= until.segno()
+
+    wait_until(slot_advanced)
+
+    # Slots hold back deletion of WAL from remote storage.
+    with pytest.raises(http_cli.HTTPError, match="412"):
+        http_cli.timeline_delete(tenant_id, timeline_id)
+    # Slots are per safekeeper, so it can't be excluded while holding them.
+    mconf = http_cli.get_membership(tenant_id, timeline_id)
+    other_sk = SafekeeperId(99, "localhost", 5434)  # just a mock
+    excluded = Configuration(generation=mconf.generation + 1, members=[other_sk], new_members=None)
+    with pytest.raises(http_cli.HTTPError, match="412"):
+        http_cli.timeline_exclude(tenant_id, timeline_id, excluded)
+
+    http_cli.replication_slot_drop(tenant_id, timeline_id, "from_start")
+    with closing(replication_conn()) as conn, conn.cursor() as cur:
+        cur.drop_replication_slot("receivewal")
+    assert slots() == {}
+    with pytest.raises(http_cli.HTTPError, match="404"):
+        http_cli.replication_slot_drop(tenant_id, timeline_id, "from_start")
+
+    wait(lambda: not os.path.exists(first_segment), "first segment get removed")
+
+
 def test_wal_backup(neon_env_builder: NeonEnvBuilder):
     neon_env_builder.num_safekeepers = 3
     remote_storage_kind = s3_storage()
@@ -488,6 +582,82 @@ def test_wal_backup(neon_env_builder: NeonEnvBuilder):
     assert_prefix_empty(neon_env_builder.safekeepers_remote_storage, prefix)
 
 
+def test_wal_backup_compression(neon_env_builder: NeonEnvBuilder):
+    """
+    Offload WAL compressed, and read it back: full segments when computing digests of a
+    timeline and of its copy, and the partial segment when the timeline is un-evicted.
+    """
+    neon_env_builder.num_safekeepers = 1
+    neon_env_builder.enable_safekeeper_remote_storage(RemoteStorageKind.LOCAL_FS)
+    neon_env_builder.safekeeper_extra_opts = [
+        "--wal-backup-compression",
+        "--enable-offload",
+        "--delete-offloaded-wal",
+        "--partial-backup-timeout",
+        "50ms",
+        "--control-file-save-interval",
+        "1s",
+        "--eviction-min-resident=100ms",
+    ]
+    initial_tenant_conf = {"lagging_wal_timeout": "1s", "checkpoint_timeout": "100ms"}
+    env = neon_env_builder.init_start(initial_tenant_conf=initial_tenant_conf)
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+    sk = env.safekeepers[0]
+    sk_http = sk.http_client()
+
+    endpoint = env.endpoints.create_start("main")
+    endpoint.safe_psql("create table t(key int, value text)")
+    timeline_start_lsn = sk_http.get_non_zero_timeline_start_lsn(tenant_id, timeline_id)
+    # roughly fills two segments
+    endpoint.safe_psql("insert into t select generate_series(1,500000), 'payload'")
+    lsn = Lsn(endpoint.safe_psql("SELECT pg_current_wal_flush_lsn()")[0][0])
+    endpoint.stop()
+
+    offloaded_seg_end = Lsn("0/3000000")
+    wait(
+        partial(is_segment_offloaded, sk, tenant_id, timeline_id, offloaded_seg_end),
+        f"segment ending at {offloaded_seg_end} get offloaded",
+    )
+    wait_lsn_force_checkpoint_at_sk(sk, tenant_id, timeline_id, env.pageserver)
+
+    remote_storage = neon_env_builder.safekeepers_remote_storage
+    assert isinstance(remote_storage, LocalFsStorage)
+    remote_timeline_dir = remote_storage.root / str(tenant_id) / str(timeline_id)
+
+    def partial_segment_offloaded():
+        objects = os.listdir(remote_timeline_dir)
+        log.info(f"offloaded objects: {objects}")
+        assert "000000010000000000000001.zst" in objects
+        assert "000000010000000000000002.zst" in objects
+        assert any(o.endswith(".partial.zst") for o in objects)
+        assert all(o.endswith(".zst") for o in objects)
+
+    wait_until(partial_segment_offloaded)
+
+    # The WAL offloaded before the copy is read from remote storage.
+    orig_digest = sk_http.timeline_digest(tenant_id, timeline_id, timeline_start_lsn, lsn)
+    new_timeline_id = TimelineId.generate()
+    sk_http.copy_timeline(
+        tenant_id,
+        timeline_id,
+        {"target_timeline_id": str(new_timeline_id), "until_lsn": str(lsn)},
+    )
+    new_digest = sk_http.timeline_digest(tenant_id, new_timeline_id, timeline_start_lsn, lsn)
+    assert orig_digest == new_digest
+
+    def evicted():
+        assert sk_http.get_eviction_state(timeline_id) != "Present"
+
+    wait_until(evicted, timeout=60)
+
+    # Un-eviction downloads the compressed partial segment.
+    endpoint.start()
+    endpoint.safe_psql("insert into t values (0, 'payload')")
+    assert endpoint.safe_psql("select count(*) from t") == [(500001,)]
+    endpoint.stop()
+
+
 # This test is flaky, probably because PUTs of local fs storage are not atomic.
 # Let's keep both remote storage kinds for a while to see if this is the case.
 # https://github.com/neondatabase/neon/issues/10761
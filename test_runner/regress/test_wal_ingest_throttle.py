@@ -0,0 +1,54 @@
+from __future__ import annotations
+
+import time
+
+from fixtures.log_helper import log
+from fixtures.neon_fixtures import NeonEnvBuilder, wait_for_last_flush_lsn
+
+
+def test_wal_ingest_throttle(neon_env_builder: NeonEnvBuilder):
+    """
+    A tenant with a WAL ingest throttle configured ingests no faster than the
+    configured rate, and the throttling shows up in the metrics.
+    """
+    rate_limit_bytes_per_second = 1024 * 1024
+    env = neon_env_builder.init_start(
+        initial_tenant_conf={
+            "wal_ingest_throttle": {
+                "enabled": True,
+                "initial": 0,
+                "refill_interval": "100ms",
+                "refill_amount": rate_limit_bytes_per_second // 10,
+                "max": rate_limit_bytes_per_second // 10,
+            },
+        }
+    )
+    client = env.pageserver.http_client()
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    endpoint = env.endpoints.create_start("main")
+
+    start = time.time()
+    with endpoint.cursor() as cur:
+        cur.execute("CREATE TABLE foo (id int, t text)")
+        # roughly 8MiB of WAL
+        cur.execute("INSERT INTO foo SELECT g, repeat('x', 1000) FROM generate_series(1, 8000) g")
+    wait_for_last_flush_lsn(env, endpoint, tenant_id, timeline_id)
+    elapsed = time.time() - start
+    log.info(f"ingest took {elapsed:.1f}s")
+
+    throttled = (
+        client.get_metric_value(
+            "pageserver_tenant_throttling_count_global", {"kind": "wal_ingest"}
+        )
+        or 0
+    )
+    assert throttled > 0
+    # allow for some slack in the burst allowance and the rate measurement
+    assert elapsed >= 4
+
+    # the data made it through intact
+    with endpoint.cursor() as cur:
+        cur.execute("SELECT count(*) FROM foo")
+        assert cur.fetchone() == (8000,)
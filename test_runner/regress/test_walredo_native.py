@@ -0,0 +1,50 @@
+from __future__ import annotations
+
+from fixtures.log_helper import log
+from fixtures.neon_fixtures import NeonEnvBuilder, flush_ep_to_pageserver
+
+
+def test_walredo_native_verify(neon_env_builder: NeonEnvBuilder):
+    """
+    With wal_redo_native in verify mode, the records supported by the native redo are applied
+    both natively and by the walredo process, and the results compared.
+    """
+    neon_env_builder.pageserver_config_override = "wal_redo_native='verify'"
+    env = neon_env_builder.init_start(
+        initial_tenant_conf={
+            # keep the WAL records around, so that reads need redo
+            "gc_period": "0s",
+            "compaction_period": "0s",
+        }
+    )
+    client = env.pageserver.http_client()
+    tenant_id = env.initial_tenant
+    timeline_id = env.initial_timeline
+
+    endpoint = env.endpoints.create_start("main")
+    with endpoint.cursor() as cur:
+        cur.execute("CREATE TABLE foo (id int PRIMARY KEY, t text)")
+        cur.execute("INSERT INTO foo SELECT g, 'row ' || g FROM generate_series(1, 10000) g")
+        cur.execute("UPDATE foo SET t = t || ' updated' WHERE id % 3 = 0")
+        cur.execute("DELETE FROM foo WHERE id % 7 = 0")
+        cur.execute("SELECT id FROM foo WHERE id % 11 = 0 FOR UPDATE")
+        cur.execute("SELECT sum(length(t)) FROM foo")
+        expected = cur.fetchone()
+
+    flush_ep_to_pageserver(env, endpoint, tenant_id, timeline_id)
+    endpoint.stop()
+
+    # a fresh compute reads all pages back from the pageserver
+    endpoint.start()
+    with endpoint.cursor() as cur:
+        cur.execute("SET enable_seqscan = off")
+        cur.execute("SELECT count(*) FROM foo WHERE id > 0")
+        cur.execute("RESET enable_seqscan")
+        cur.execute("SELECT sum(length(t)) FROM foo")
+        assert cur.fetchone() == expected
+
+    native_records = client.get_metric_value("pageserver_wal_redo_native_records_total") or 0
+    mismatches = client.get_metric_value("pageserver_wal_redo_native_mismatches_total") or 0
+    log.info(f"{native_records} records applied natively, {mismatches} mismatches")
+    assert native_records > 0
+    assert mismatches == 0